@@ -1,15 +1,44 @@
 //! WebSocket event types
+//!
+//! Events travel in a versioned [`EventEnvelope`]:
+//!
+//! ```json
+//! {"v": 1, "seq": 12345, "ts": "2026-10-14T03:00:00Z",
+//!  "event": {"type": "host_state_changed", "host": "web", "from": "idle", "to": "querying"}}
+//! ```
+//!
+//! New event types and fields are added without bumping `v`; clients
+//! deserialize types they don't know as [`WsEvent::Unknown`] and should
+//! ignore fields they don't know. `v` changes only when existing fields
+//! change meaning.
+//!
+//! A stream opened with `?batch_ms=` sends events collected over that
+//! interval as one JSON array of envelopes per frame.
 
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+/// Version of the [`EventEnvelope`] wire format
+pub const ENVELOPE_VERSION: u32 = 1;
+
+/// Longest interval the event stream batches events over, in milliseconds
+pub const MAX_BATCH_MS: u64 = 5000;
+
+/// Something that happened in the daemon, tagged by `type`
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-#[serde(tag = "type")]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[non_exhaustive]
 pub enum WsEvent {
     HostStateChanged {
         host: String,
         from: String,
         to: String,
+        /// Class of the failure when `to` is "failed", e.g. "connection"
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        failure_kind: Option<String>,
     },
     UpdateProgress {
         host: String,
@@ -19,6 +48,9 @@ pub enum WsEvent {
     UpdateCompleted {
         host: String,
         result: String,
+        /// The update was only simulated; nothing was installed
+        #[serde(default)]
+        dry_run: bool,
     },
     HostConnected {
         host: String,
@@ -27,4 +59,424 @@ pub enum WsEvent {
         host: String,
         reason: String,
     },
+    /// A pre- or post-update hook finished
+    HookExecuted {
+        host: String,
+        hook: String,
+        success: bool,
+    },
+    /// A failed host will retry its last operation after `delay_secs`
+    RetryScheduled {
+        host: String,
+        attempt: u32,
+        max_retries: u32,
+        delay_secs: u64,
+    },
+    /// Another process holds the package manager lock; the update tries
+    /// again until the host's lock wait is spent
+    WaitingForLock {
+        host: String,
+        /// The holder as named by the package manager, e.g.
+        /// "process 4211 (unattended-upgr)"
+        holder: Option<String>,
+        /// Seconds waited so far
+        waited_secs: u64,
+    },
+    /// An automatic retry started
+    RetryStarted {
+        host: String,
+        attempt: u32,
+    },
+    /// Automatic retries ran out; the host stays failed until an operator retries it
+    RetriesExhausted {
+        host: String,
+        attempts: u32,
+    },
+    /// An operator acknowledged a host's failure
+    HostAcknowledged {
+        host: String,
+    },
+    /// An operator paused or resumed automation on a host
+    HostPauseChanged {
+        host: String,
+        paused: bool,
+    },
+    /// Repeated connection failures opened the host's circuit breaker;
+    /// operations fail fast until `retry_at`
+    CircuitOpened {
+        host: String,
+        failures: u32,
+        retry_at: DateTime<Utc>,
+    },
+    /// The host answered again or was retried, closing its circuit breaker
+    CircuitClosed {
+        host: String,
+    },
+    /// A newly collected inventory differs from the previous one
+    InventoryChanged {
+        host: String,
+        summary: String,
+    },
+    /// Upgradable packages were queried from the host's package sources
+    InventoryQueried {
+        host: String,
+        /// Outcome per source, e.g. "inventory: apt ok (4 updates),
+        /// docker-compose error: daemon unreachable"
+        summary: String,
+        /// Some sources failed; the query used the others
+        partial: bool,
+    },
+    /// A host in a fleet update finished updating
+    FleetHostFinished {
+        host: String,
+        phase: FleetPhase,
+        success: bool,
+        /// Not contacted because its circuit breaker was open or it is paused
+        #[serde(default)]
+        skipped: bool,
+        error: Option<String>,
+    },
+    /// A canary host failed, so the rest of the fleet update was skipped
+    FleetUpdateHalted {
+        failed_canaries: Vec<String>,
+        skipped_hosts: usize,
+    },
+    /// A fleet update ran its last batch or was halted
+    FleetUpdateFinished {
+        total_hosts: usize,
+        completed: usize,
+        failed: usize,
+        skipped: usize,
+        /// Nothing was installed; the hosts only simulated their updates
+        dry_run: bool,
+    },
+    /// Synthetic event: this subscriber fell behind and `count` events were discarded
+    EventsDropped {
+        count: u64,
+    },
+    /// An event type this version does not know, sent by a newer daemon
+    #[serde(other)]
+    Unknown,
+}
+
+impl WsEvent {
+    /// Every event type the daemon sends, as returned by [`kind`](Self::kind)
+    pub const KINDS: [&'static str; 20] = [
+        "host_state_changed",
+        "update_progress",
+        "update_completed",
+        "host_connected",
+        "host_disconnected",
+        "hook_executed",
+        "retry_scheduled",
+        "waiting_for_lock",
+        "retry_started",
+        "retries_exhausted",
+        "host_acknowledged",
+        "host_pause_changed",
+        "circuit_opened",
+        "circuit_closed",
+        "inventory_changed",
+        "inventory_queried",
+        "fleet_host_finished",
+        "fleet_update_halted",
+        "fleet_update_finished",
+        "events_dropped",
+    ];
+
+    /// The event's `type` tag
+    #[must_use]
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::HostStateChanged { .. } => "host_state_changed",
+            Self::UpdateProgress { .. } => "update_progress",
+            Self::UpdateCompleted { .. } => "update_completed",
+            Self::HostConnected { .. } => "host_connected",
+            Self::HostDisconnected { .. } => "host_disconnected",
+            Self::HookExecuted { .. } => "hook_executed",
+            Self::RetryScheduled { .. } => "retry_scheduled",
+            Self::WaitingForLock { .. } => "waiting_for_lock",
+            Self::RetryStarted { .. } => "retry_started",
+            Self::RetriesExhausted { .. } => "retries_exhausted",
+            Self::HostAcknowledged { .. } => "host_acknowledged",
+            Self::HostPauseChanged { .. } => "host_pause_changed",
+            Self::CircuitOpened { .. } => "circuit_opened",
+            Self::CircuitClosed { .. } => "circuit_closed",
+            Self::InventoryChanged { .. } => "inventory_changed",
+            Self::InventoryQueried { .. } => "inventory_queried",
+            Self::FleetHostFinished { .. } => "fleet_host_finished",
+            Self::FleetUpdateHalted { .. } => "fleet_update_halted",
+            Self::FleetUpdateFinished { .. } => "fleet_update_finished",
+            Self::EventsDropped { .. } => "events_dropped",
+            Self::Unknown => "unknown",
+        }
+    }
+
+    /// Host the event is about, if it concerns a single host
+    #[must_use]
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            Self::HostStateChanged { host, .. }
+            | Self::UpdateProgress { host, .. }
+            | Self::UpdateCompleted { host, .. }
+            | Self::HostConnected { host }
+            | Self::HostDisconnected { host, .. }
+            | Self::HookExecuted { host, .. }
+            | Self::RetryScheduled { host, .. }
+            | Self::WaitingForLock { host, .. }
+            | Self::RetryStarted { host, .. }
+            | Self::RetriesExhausted { host, .. }
+            | Self::HostAcknowledged { host }
+            | Self::HostPauseChanged { host, .. }
+            | Self::CircuitOpened { host, .. }
+            | Self::CircuitClosed { host }
+            | Self::InventoryChanged { host, .. }
+            | Self::InventoryQueried { host, .. }
+            | Self::FleetHostFinished { host, .. } => Some(host),
+            Self::FleetUpdateHalted { .. }
+            | Self::FleetUpdateFinished { .. }
+            | Self::EventsDropped { .. }
+            | Self::Unknown => None,
+        }
+    }
+}
+
+/// Part of a fleet update a host was updated in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FleetPhase {
+    /// Canary hosts, updated first in a single batch
+    Canary,
+    /// Every other host, dependents before their dependencies, in batches
+    /// of at most `batch_size`
+    Main,
+}
+
+impl std::fmt::Display for FleetPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Canary => "canary",
+            Self::Main => "main",
+        })
+    }
+}
+
+/// Event as delivered to subscribers, with its sequence number and time
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct EventEnvelope {
+    /// Wire format version, [`ENVELOPE_VERSION`]
+    pub v: u32,
+    /// Monotonically increasing sequence number (gaps mean coalesced or dropped events)
+    pub seq: u64,
+    /// When the daemon published the event
+    pub ts: DateTime<Utc>,
+    pub event: WsEvent,
+}
+
+impl EventEnvelope {
+    /// Wrap `event`, published now as number `seq`
+    #[must_use]
+    pub fn new(seq: u64, event: WsEvent) -> Self {
+        Self {
+            v: ENVELOPE_VERSION,
+            seq,
+            ts: Utc::now(),
+            event,
+        }
+    }
+}
+
+/// The last `per_host` events of each host in `events`, in sequence order
+///
+/// Events that are not about a single host, such as fleet results, are
+/// left out.
+#[must_use]
+pub fn recent_per_host(events: &[EventEnvelope], per_host: usize) -> Vec<EventEnvelope> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    let mut recent: Vec<EventEnvelope> = events
+        .iter()
+        .rev()
+        .filter(|envelope| {
+            let Some(host) = envelope.event.host() else {
+                return false;
+            };
+            let count = counts.entry(host).or_default();
+            *count += 1;
+            *count <= per_host
+        })
+        .cloned()
+        .collect();
+    recent.sort_by_key(|envelope| envelope.seq);
+    recent
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// One of each event the daemon sends
+    fn every_event() -> Vec<WsEvent> {
+        let host = || "web".to_string();
+        vec![
+            WsEvent::HostStateChanged {
+                host: host(),
+                from: "idle".to_string(),
+                to: "querying".to_string(),
+                failure_kind: None,
+            },
+            WsEvent::UpdateProgress {
+                host: host(),
+                package: "openssl".to_string(),
+                progress: 40,
+            },
+            WsEvent::UpdateCompleted {
+                host: host(),
+                result: "upgraded 3 packages".to_string(),
+                dry_run: false,
+            },
+            WsEvent::HostConnected { host: host() },
+            WsEvent::HostDisconnected {
+                host: host(),
+                reason: "connection reset".to_string(),
+            },
+            WsEvent::HookExecuted {
+                host: host(),
+                hook: "pre_update".to_string(),
+                success: true,
+            },
+            WsEvent::RetryScheduled {
+                host: host(),
+                attempt: 1,
+                max_retries: 3,
+                delay_secs: 30,
+            },
+            WsEvent::WaitingForLock {
+                host: host(),
+                holder: Some("process 4211 (unattended-upgr)".to_string()),
+                waited_secs: 15,
+            },
+            WsEvent::RetryStarted {
+                host: host(),
+                attempt: 1,
+            },
+            WsEvent::RetriesExhausted {
+                host: host(),
+                attempts: 3,
+            },
+            WsEvent::HostAcknowledged { host: host() },
+            WsEvent::HostPauseChanged {
+                host: host(),
+                paused: true,
+            },
+            WsEvent::CircuitOpened {
+                host: host(),
+                failures: 5,
+                retry_at: DateTime::UNIX_EPOCH,
+            },
+            WsEvent::CircuitClosed { host: host() },
+            WsEvent::InventoryChanged {
+                host: host(),
+                summary: "kernel 6.1 -> 6.2".to_string(),
+            },
+            WsEvent::InventoryQueried {
+                host: host(),
+                summary: "inventory: apt ok (4 updates)".to_string(),
+                partial: false,
+            },
+            WsEvent::FleetHostFinished {
+                host: host(),
+                phase: FleetPhase::Canary,
+                success: false,
+                skipped: false,
+                error: Some("dpkg lock".to_string()),
+            },
+            WsEvent::FleetUpdateHalted {
+                failed_canaries: vec![host()],
+                skipped_hosts: 4,
+            },
+            WsEvent::FleetUpdateFinished {
+                total_hosts: 3,
+                completed: 2,
+                failed: 1,
+                skipped: 0,
+                dry_run: false,
+            },
+            WsEvent::EventsDropped { count: 4 },
+        ]
+    }
+
+    #[test]
+    fn test_every_event_round_trips_in_an_envelope() {
+        let events = every_event();
+        assert_eq!(events.len(), WsEvent::KINDS.len());
+
+        for (seq, event) in (1..).zip(events) {
+            let envelope = EventEnvelope::new(seq, event);
+            let value = serde_json::to_value(&envelope).unwrap();
+            assert_eq!(value["v"], ENVELOPE_VERSION);
+            assert_eq!(value["seq"], seq);
+            assert_eq!(value["event"]["type"], envelope.event.kind());
+            assert!(WsEvent::KINDS.contains(&envelope.event.kind()));
+
+            let parsed: EventEnvelope = serde_json::from_value(value.clone()).unwrap();
+            assert_eq!(parsed.ts, envelope.ts);
+            assert_eq!(serde_json::to_value(&parsed).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_unknown_event_type_is_not_an_error() {
+        // From a newer daemon: an unknown event type and an extra field
+        let value = json!({
+            "v": 1,
+            "seq": 12345,
+            "ts": "2026-10-14T03:00:00Z",
+            "region": "eu",
+            "event": {"type": "fleet_update_progress", "completed": 3, "total": 9},
+        });
+        let envelope: EventEnvelope = serde_json::from_value(value).unwrap();
+        assert_eq!(envelope.seq, 12345);
+        assert!(matches!(envelope.event, WsEvent::Unknown));
+        assert_eq!(envelope.event.host(), None);
+
+        // Unknown fields of a known type are ignored as well
+        let event: WsEvent =
+            serde_json::from_value(json!({"type": "host_connected", "host": "web", "via": "ssh"}))
+                .unwrap();
+        assert!(matches!(event, WsEvent::HostConnected { host } if host == "web"));
+        assert_eq!(
+            serde_json::to_value(WsEvent::Unknown).unwrap(),
+            json!({"type": "unknown"})
+        );
+    }
+
+    #[test]
+    fn test_recent_per_host_keeps_last_events_of_each_host() {
+        let connected = |host: &str| WsEvent::HostConnected {
+            host: host.to_string(),
+        };
+        let events: Vec<EventEnvelope> = [
+            connected("web"),
+            connected("db"),
+            connected("web"),
+            WsEvent::EventsDropped { count: 1 },
+            connected("web"),
+        ]
+        .into_iter()
+        .zip(1..)
+        .map(|(event, seq)| EventEnvelope::new(seq, event))
+        .collect();
+
+        let seqs = |per_host| -> Vec<u64> {
+            recent_per_host(&events, per_host)
+                .iter()
+                .map(|e| e.seq)
+                .collect()
+        };
+        assert_eq!(seqs(2), [2, 3, 5]);
+        assert_eq!(seqs(1), [2, 5]);
+        assert!(seqs(0).is_empty());
+    }
 }
@@ -4,5 +4,7 @@
 //! used across the daemon, CLI, and TUI.
 
 pub mod events;
+pub mod pagination;
 pub mod requests;
 pub mod responses;
+pub mod version;
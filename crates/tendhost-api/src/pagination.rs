@@ -0,0 +1,286 @@
+//! Pagination shared by list endpoints and their clients
+//!
+//! Endpoints backed by a list they hold in full (hosts) are paged by
+//! number. Append-only logs (events) are paged by sequence number instead:
+//! a client passes back the `next_cursor` of the previous page and gets the
+//! entries after it, so entries appended between requests are neither
+//! skipped nor repeated.
+
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Page size used when the request doesn't set one
+pub const DEFAULT_PER_PAGE: u64 = 50;
+
+/// Largest accepted page size
+pub const MAX_PER_PAGE: u64 = 200;
+
+/// Query parameters selecting one page of a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageParams {
+    /// Page number (1-indexed); ignored by cursor-paged endpoints
+    #[serde(default = "default_page")]
+    #[param(minimum = 1)]
+    #[schema(minimum = 1)]
+    pub page: u64,
+    /// Items per page (at most 200)
+    #[serde(default = "default_per_page")]
+    #[param(minimum = 1, maximum = 200)]
+    #[schema(minimum = 1, maximum = 200)]
+    pub per_page: u64,
+    /// Return items after this sequence number (cursor-paged endpoints only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<u64>,
+}
+
+fn default_page() -> u64 {
+    1
+}
+
+fn default_per_page() -> u64 {
+    DEFAULT_PER_PAGE
+}
+
+impl Default for PageParams {
+    fn default() -> Self {
+        Self {
+            page: default_page(),
+            per_page: default_per_page(),
+            cursor: None,
+        }
+    }
+}
+
+impl PageParams {
+    /// Page `page` of `per_page` items
+    #[must_use]
+    pub fn new(page: u64, per_page: u64) -> Self {
+        Self {
+            page,
+            per_page,
+            cursor: None,
+        }
+    }
+
+    /// The page following `cursor` in an append-only log
+    #[must_use]
+    pub fn after(cursor: u64) -> Self {
+        Self {
+            cursor: Some(cursor),
+            ..Self::default()
+        }
+    }
+
+    /// Set the page size
+    #[must_use]
+    pub fn with_per_page(mut self, per_page: u64) -> Self {
+        self.per_page = per_page;
+        self
+    }
+
+    /// Check the page number and size are in range
+    ///
+    /// # Errors
+    /// Returns one error per out-of-range parameter.
+    pub fn validate(&self) -> Result<(), Vec<PageParamError>> {
+        let mut errors = Vec::new();
+        if self.page == 0 {
+            errors.push(PageParamError {
+                field: "page",
+                message: "must be at least 1".to_string(),
+            });
+        }
+        if self.per_page == 0 || self.per_page > MAX_PER_PAGE {
+            errors.push(PageParamError {
+                field: "per_page",
+                message: format!("must be between 1 and {MAX_PER_PAGE}"),
+            });
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn per_page_len(&self) -> usize {
+        usize::try_from(self.per_page).unwrap_or(usize::MAX)
+    }
+}
+
+/// A page parameter that is out of range
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{field}: {message}")]
+pub struct PageParamError {
+    /// Query parameter name
+    pub field: &'static str,
+    /// What is wrong with it
+    pub message: String,
+}
+
+/// One page of a list, with where it sits in the whole
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub pagination: Pagination,
+}
+
+/// Position of a page in a list
+///
+/// For cursor-paged lists `page` is always 1 and the totals count the
+/// items after the request's cursor.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct Pagination {
+    pub page: u64,
+    pub per_page: u64,
+    pub total_items: u64,
+    pub total_pages: u64,
+    /// Cursor for the next request; repeats the request's cursor when
+    /// nothing newer exists yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<u64>,
+}
+
+impl<T> Paginated<T> {
+    /// Convert the items of the page, keeping its position
+    #[must_use]
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Paginated<U> {
+        Paginated {
+            data: self.data.into_iter().map(f).collect(),
+            pagination: self.pagination,
+        }
+    }
+}
+
+/// Take page `params.page` out of `items`
+///
+/// `items` must already be filtered and sorted so pages are stable between
+/// requests. Pages past the end are empty.
+#[must_use]
+pub fn paginate_vec<T>(items: Vec<T>, params: &PageParams) -> Paginated<T> {
+    let total_items = items.len() as u64;
+    let per_page = params.per_page_len();
+    let start = usize::try_from(params.page.saturating_sub(1))
+        .unwrap_or(usize::MAX)
+        .saturating_mul(per_page);
+
+    Paginated {
+        data: items.into_iter().skip(start).take(per_page).collect(),
+        pagination: Pagination {
+            page: params.page,
+            per_page: params.per_page,
+            total_items,
+            total_pages: total_items.div_ceil(params.per_page.max(1)),
+            next_cursor: None,
+        },
+    }
+}
+
+/// Take the items after `params.cursor` out of a log ordered by `seq`
+///
+/// `items` must be sorted by ascending sequence number, as append-only logs
+/// are. Without a cursor the page starts at the oldest item.
+#[must_use]
+pub fn paginate_by_cursor<T>(
+    items: impl IntoIterator<Item = T>,
+    params: &PageParams,
+    seq: impl Fn(&T) -> u64,
+) -> Paginated<T> {
+    let mut after: Vec<T> = items
+        .into_iter()
+        .filter(|item| params.cursor.is_none_or(|cursor| seq(item) > cursor))
+        .collect();
+    let total_items = after.len() as u64;
+    after.truncate(params.per_page_len());
+
+    Paginated {
+        pagination: Pagination {
+            page: 1,
+            per_page: params.per_page,
+            total_items,
+            total_pages: total_items.div_ceil(params.per_page.max(1)),
+            next_cursor: after.last().map(&seq).or(params.cursor),
+        },
+        data: after,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_page_params() {
+        assert!(PageParams::default().validate().is_ok());
+
+        let errors = PageParams::new(0, MAX_PER_PAGE + 1).validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field).collect();
+        assert_eq!(fields, ["page", "per_page"]);
+    }
+
+    #[test]
+    fn test_page_params_defaults_from_query() {
+        let params: PageParams = serde_json::from_str("{}").unwrap();
+        assert_eq!(params, PageParams::default());
+        assert_eq!(params.per_page, DEFAULT_PER_PAGE);
+    }
+
+    #[test]
+    fn test_paginate_vec() {
+        let items: Vec<u32> = (1..=5).collect();
+
+        let page = paginate_vec(items.clone(), &PageParams::new(2, 2));
+        assert_eq!(page.data, [3, 4]);
+        assert_eq!(page.pagination.total_items, 5);
+        assert_eq!(page.pagination.total_pages, 3);
+        assert_eq!(page.pagination.next_cursor, None);
+
+        let past_end = paginate_vec(items, &PageParams::new(9, 2));
+        assert!(past_end.data.is_empty());
+        assert_eq!(past_end.pagination.total_pages, 3);
+    }
+
+    #[test]
+    fn test_cursor_continues_across_inserts() {
+        let mut log: Vec<u64> = (1..=5).collect();
+        let params = PageParams::default().with_per_page(3);
+
+        let first = paginate_by_cursor(log.clone(), &params, |seq| *seq);
+        assert_eq!(first.data, [1, 2, 3]);
+        assert_eq!(first.pagination.total_items, 5);
+        assert_eq!(first.pagination.next_cursor, Some(3));
+
+        // Entries appended between requests show up after the old ones
+        log.extend(6..=7);
+        let cursor = first.pagination.next_cursor.unwrap();
+        let second = paginate_by_cursor(
+            log.clone(),
+            &PageParams::after(cursor).with_per_page(3),
+            |seq| *seq,
+        );
+        assert_eq!(second.data, [4, 5, 6]);
+        assert_eq!(second.pagination.total_items, 4);
+        assert_eq!(second.pagination.next_cursor, Some(6));
+
+        let third =
+            paginate_by_cursor(log.clone(), &PageParams::after(6).with_per_page(3), |seq| {
+                *seq
+            });
+        assert_eq!(third.data, [7]);
+
+        // Caught up: the cursor stays put until something new arrives
+        let caught_up = paginate_by_cursor(log, &PageParams::after(7), |seq| *seq);
+        assert!(caught_up.data.is_empty());
+        assert_eq!(caught_up.pagination.next_cursor, Some(7));
+    }
+
+    #[test]
+    fn test_cursor_skips_gaps_left_by_trimmed_history() {
+        // Oldest entries fell out of a bounded log; sequence numbers still order it
+        let log = [10_u64, 11, 14, 15];
+        let page = paginate_by_cursor(log, &PageParams::after(3), |seq| *seq);
+        assert_eq!(page.data, [10, 11, 14, 15]);
+        assert_eq!(page.pagination.next_cursor, Some(15));
+    }
+}
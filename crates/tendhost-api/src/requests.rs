@@ -1,19 +1,96 @@
 //! Request types for the API
 
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
+
+/// Which upgradable packages an update applies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateScope {
+    /// Every available upgrade
+    #[default]
+    All,
+    /// Only upgrades from security repositories
+    SecurityOnly,
+}
+
+impl std::fmt::Display for UpdateScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpdateScope::All => write!(f, "all"),
+            UpdateScope::SecurityOnly => write!(f, "security_only"),
+        }
+    }
+}
+
+/// Field the host list is sorted by; ties are broken by name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HostSort {
+    /// Host name
+    #[default]
+    Name,
+    /// Lifecycle state, in state machine order
+    State,
+    /// Number of pending updates, unknown counts first
+    PendingUpdates,
+    /// Last successful update, never-updated hosts first
+    LastUpdated,
+}
+
+/// Sort direction
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    /// Ascending
+    #[default]
+    Asc,
+    /// Descending
+    Desc,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct UpdateRequest {
     pub dry_run: bool,
+    /// Overrides the host's default scope when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<UpdateScope>,
+    /// Update only this docker compose stack instead of system packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+    /// Upgrade only these upgradable packages; `scope` is ignored when set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<String>,
+    /// Update the host even though it is paused
+    #[serde(default)]
+    pub force: bool,
+    /// Skip the host policy's `autoremove` and `clean_cache` this time
+    #[serde(default)]
+    pub skip_cleanup: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct FleetUpdateRequest {
     pub batch_size: usize,
     pub delay_ms: u64,
+    /// Overrides each host's default scope when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<UpdateScope>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub filter: Option<FleetUpdateFilter>,
+    /// Only report what would change instead of updating
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Hosts updated first, together in one batch regardless of `batch_size`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub canary_hosts: Vec<String>,
+    /// Skip the remaining hosts if any canary fails (default true)
+    #[serde(default = "default_true")]
+    pub halt_on_canary_failure: bool,
+}
+
+fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
@@ -24,4 +101,89 @@ pub struct FleetUpdateFilter {
     pub groups: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub exclude_hosts: Option<Vec<String>>,
+    /// Facts hosts must all have, as `key:value`, e.g. `os_id:debian`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub facts: Option<Vec<String>>,
+}
+
+/// Host registration request
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RegisterHostRequest {
+    /// Host name
+    pub name: String,
+    /// Host address, optionally as `host:port`
+    pub addr: String,
+    /// SSH port; overrides a port in `addr` (default 22)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// SSH user
+    #[serde(default = "default_user")]
+    pub user: String,
+    /// SSH key path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key: Option<String>,
+    /// Seconds establishing the SSH connection may take
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout_secs: Option<u64>,
+    /// Configured host name or `[user@]host[:port]` to connect through
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump_host: Option<String>,
+    /// Tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// SSH user for hosts registered without one
+#[must_use]
+pub fn default_user() -> String {
+    "root".to_string()
+}
+
+/// What `POST /hosts/import` does with hosts already registered
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    /// Register new hosts and leave registered ones unchanged
+    #[default]
+    Merge,
+    /// Make the registered hosts match the document: new hosts are
+    /// registered, changed ones reconfigured and missing ones unregistered
+    Replace,
+}
+
+impl std::fmt::Display for ImportMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportMode::Merge => write!(f, "merge"),
+            ImportMode::Replace => write!(f, "replace"),
+        }
+    }
+}
+
+/// Query parameters of `POST /hosts/{hostname}/update`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UpdateParams {
+    /// Answer once the update has finished, with its result, instead of
+    /// as soon as it has started
+    #[serde(default)]
+    pub wait: bool,
+}
+
+/// Query parameters of `GET /hosts/{hostname}/update-preview`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PreviewParams {
+    /// Which packages to simulate; the host policy's scope if unset
+    #[serde(default)]
+    pub scope: Option<UpdateScope>,
+}
+
+/// Query parameters of `POST /hosts/import`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportParams {
+    /// How registered hosts are treated (default `merge`)
+    #[serde(default)]
+    pub mode: ImportMode,
 }
@@ -1,23 +1,905 @@
 //! Response types for the API
 
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+pub use crate::pagination::Pagination;
+use crate::requests::{HostSort, SortOrder, UpdateScope};
+
+/// A page of list results, see [`crate::pagination`]
+pub type PaginatedResponse<T> = crate::pagination::Paginated<T>;
+
+/// Health check response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HealthResponse {
+    /// Service status
+    pub status: String,
+    /// Service version
+    pub version: String,
+    /// Protocol version, [`crate::version::API_VERSION`]; 0 from daemons
+    /// that predate it
+    #[serde(default)]
+    pub api_version: u32,
+    /// Next run of each enabled schedule
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedules: Vec<ScheduleNextRun>,
+    /// Delivery counters of each configured webhook
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<NotifierStats>,
+    /// Event counters of each host that emitted events
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<HostEventStats>,
+}
+
+/// Events emitted by one host since the daemon started
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct HostEventStats {
+    /// Host name
+    pub host: String,
+    /// Events published for the host
+    pub emitted: u64,
+    /// Events no subscriber received, because none was connected or every
+    /// subscriber's queue was full
+    pub undelivered: u64,
+}
+
+/// Delivery counters of one `[[notify]]` webhook since the daemon started
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct NotifierStats {
+    /// Webhook name from the config
+    pub name: String,
+    /// Notifications the webhook accepted
+    pub sent: u64,
+    /// Notifications given up on after every retry failed
+    pub failed: u64,
+    /// Notifications dropped by the rate limit or a full queue
+    pub dropped: u64,
+}
+
+/// When an enabled schedule runs next
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleNextRun {
+    /// Schedule id from the config
+    pub id: String,
+    /// Next run time, absent if none can be computed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run: Option<DateTime<Utc>>,
+}
+
+/// A recurring fleet update configured as `[[schedule]]`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleInfo {
+    /// Schedule id from the config
+    pub id: String,
+    /// Disabled schedules only run through `run-now`
+    pub enabled: bool,
+    /// Days the schedule runs on (empty means every day)
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// Time of day in `HH:MM` format
+    pub at: String,
+    /// Time zone `at` is interpreted in (`local`, `UTC`, or an offset like `+02:00`)
+    pub timezone: String,
+    /// Only update hosts with one of these tags (all hosts if empty)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Number of hosts updated in parallel
+    pub batch_size: usize,
+    /// Simulate the update without installing anything
+    pub dry_run: bool,
+    /// Next scheduled run, absent for disabled schedules
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_run: Option<DateTime<Utc>>,
+    /// A run started by this schedule is still in progress
+    pub running: bool,
+    /// Most recent run, absent if the schedule has not run yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<ScheduleRunInfo>,
+}
+
+/// One run of a schedule
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ScheduleRunInfo {
+    /// What started the run (`schedule` or `manual`)
+    pub trigger: String,
+    /// When the run started
+    pub started_at: DateTime<Utc>,
+    /// When the run finished, absent while running
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Hosts selected for the fleet update
+    #[serde(default)]
+    pub total_hosts: usize,
+    /// Hosts updated successfully
+    #[serde(default)]
+    pub completed: usize,
+    /// Hosts whose update failed
+    #[serde(default)]
+    pub failed: usize,
+    /// Why the fleet update could not be run at all
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A command recently run on a host, as recorded by its executor
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CommandHistoryEntry {
+    /// Command string with secrets redacted
+    pub command: String,
+    /// When the command started
+    pub started_at: DateTime<Utc>,
+    /// Time until the command finished or failed
+    pub duration_ms: u64,
+    /// Exit status, absent if the command could not be run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<i32>,
+    /// Executor error if the command could not be run or timed out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// End of the command's stdout
+    #[serde(default)]
+    pub stdout_tail: String,
+    /// End of the command's stderr
+    #[serde(default)]
+    pub stderr_tail: String,
+}
+
+/// Outcome of a finished or failed package update on a host
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpdateHistoryEntry {
+    /// When the update finished
+    pub timestamp: DateTime<Utc>,
+    /// Whether the update was only simulated
+    pub dry_run: bool,
+    /// Which upgradable packages the update applied
+    pub scope: UpdateScope,
+    /// Compose stack the update was limited to, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack: Option<String>,
+    /// Number of packages upgraded
+    pub upgraded_count: u32,
+    /// Names of the upgraded packages
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// Packages the autoremove after the update removed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub autoremoved_packages: Vec<String>,
+    /// Whether the host needed a reboot afterwards
+    pub reboot_required: bool,
+    /// Whether the update succeeded
+    pub success: bool,
+    /// Why the update failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// What a fleet update would change, from a dry run on every matching host
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FleetDryRunReport {
+    /// Hosts the update would touch
+    pub total_hosts: usize,
+    /// Hosts with at least one pending update
+    pub hosts_with_updates: usize,
+    /// Hosts whose dry run failed
+    pub failed_hosts: usize,
+    /// Pending updates summed over all hosts
+    pub total_updates: u32,
+    /// Per-host results, by host name
+    pub hosts: Vec<HostDryRun>,
+    /// Pending packages across the fleet, most widespread first
+    pub packages: Vec<FleetPackage>,
+    /// Host names in the order a real run would update them, one list
+    /// per batch; canaries first, then dependents before dependencies
+    #[serde(default)]
+    pub batches: Vec<Vec<String>>,
+}
+
+impl FleetDryRunReport {
+    /// Aggregate per-host dry runs into fleet totals
+    #[must_use]
+    pub fn new(mut hosts: Vec<HostDryRun>) -> Self {
+        hosts.sort_by(|a, b| a.host.cmp(&b.host));
+
+        let mut by_package: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for host in &hosts {
+            for package in &host.packages {
+                by_package
+                    .entry(package)
+                    .or_default()
+                    .push(host.host.clone());
+            }
+        }
+        let mut packages: Vec<FleetPackage> = by_package
+            .into_iter()
+            .map(|(name, hosts)| FleetPackage {
+                name: name.to_string(),
+                host_count: hosts.len(),
+                hosts,
+            })
+            .collect();
+        packages.sort_by(|a, b| b.host_count.cmp(&a.host_count).then(a.name.cmp(&b.name)));
+
+        Self {
+            total_hosts: hosts.len(),
+            hosts_with_updates: hosts.iter().filter(|h| h.pending_updates > 0).count(),
+            failed_hosts: hosts.iter().filter(|h| h.error.is_some()).count(),
+            total_updates: hosts.iter().map(|h| h.pending_updates).sum(),
+            hosts,
+            packages,
+            batches: Vec::new(),
+        }
+    }
+}
+
+/// Reply to a host update request
+///
+/// The update runs in the background, so this only confirms it started
+/// and whether it is a simulation.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpdateAccepted {
+    /// Host name
+    pub host: String,
+    /// Whether the update is simulated; a dry run installs nothing
+    pub dry_run: bool,
+    /// Human-readable summary
+    pub message: String,
+}
+
+/// Result of a host update, for requests that wait for it to finish
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpdateResultInfo {
+    /// Whether the update succeeded
+    pub success: bool,
+    /// Number of packages upgraded
+    pub upgraded_count: u32,
+    /// Whether a reboot is required
+    pub reboot_required: bool,
+    /// Reboot and service restarts the update left pending
+    pub restart: RestartInfo,
+    /// Services restarted automatically after the update
+    #[serde(default)]
+    pub restarted_services: Vec<String>,
+    /// Problems found that didn't stop the update
+    #[serde(default)]
+    pub warnings: Vec<String>,
+}
+
+/// Dry-run result for one host of a fleet update
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HostDryRun {
+    /// Host name
+    pub host: String,
+    /// Number of packages the update would upgrade
+    pub pending_updates: u32,
+    /// Number of those from security repositories
+    pub security_updates: u32,
+    /// Names of the pending packages
+    #[serde(default)]
+    pub packages: Vec<String>,
+    /// Why the host could not be checked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Problems the real update would run into, e.g. too little disk space
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+/// What an update of one host would do, simulated without changing it
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UpdatePreview {
+    /// Host name
+    pub host: String,
+    /// Scope the update was simulated with
+    pub scope: UpdateScope,
+    /// Names of the packages the update would upgrade
+    pub packages: Vec<String>,
+    /// Number of packages the update would upgrade
+    pub upgraded_count: u32,
+    /// Number of packages it would newly install
+    pub new_count: u32,
+    /// Number of packages it would remove
+    pub removed_count: u32,
+    /// Size of the archives to download, if the package manager said
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_bytes: Option<u64>,
+    /// Whether the host is likely to need a reboot afterwards: it already
+    /// does, or the update touches the kernel or core system packages
+    pub reboot_likely: bool,
+    /// Why a real update would be refused right now, e.g. the host is
+    /// paused or short of disk space
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blockers: Vec<String>,
+    /// Things worth knowing that wouldn't stop the update
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+    /// When the simulation ran
+    pub previewed_at: DateTime<Utc>,
+}
+
+impl UpdatePreview {
+    /// Whether a real update would be refused
+    #[must_use]
+    pub fn is_blocked(&self) -> bool {
+        !self.blockers.is_empty()
+    }
+}
+
+/// A package pending on one or more hosts
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FleetPackage {
+    /// Package name
+    pub name: String,
+    /// Number of hosts it is pending on
+    pub host_count: usize,
+    /// Hosts it is pending on
+    pub hosts: Vec<String>,
+}
+
+/// A recorded mutating operation from the audit log
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct PaginatedResponse<T> {
-    pub data: Vec<T>,
+pub struct AuditEntry {
+    /// When the operation was requested
+    pub timestamp: DateTime<Utc>,
+    /// Operation name (e.g. `update`, `reboot`, `register_host`)
+    pub operation: String,
+    /// Hosts the operation applies to (empty for fleet-wide operations)
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Operation parameters such as `dry_run`
+    #[serde(default)]
+    #[schema(value_type = Object)]
+    pub params: serde_json::Value,
+    /// What triggered the operation (`api`, `fleet`, `scheduler`)
+    pub source: String,
+    /// Client address for API-triggered operations
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_addr: Option<String>,
+    /// Authenticated identity, once authentication exists
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+    /// HTTP status returned to the client
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+}
+
+/// What happened to one host of a bulk registration
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationStatus {
+    /// The host was registered by this request
+    Created,
+    /// A host with this name was already registered and was left unchanged
+    AlreadyExists,
+    /// The host was not registered
+    Error,
+}
+
+/// Result of registering one host of a bulk registration
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HostRegistration {
+    /// Host name
+    pub name: String,
+    /// Whether the host was registered
+    pub status: RegistrationStatus,
+    /// Why the host was not registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl HostRegistration {
+    /// Whether a host with this name is registered after the request
+    #[must_use]
+    pub fn exists(&self) -> bool {
+        self.status != RegistrationStatus::Error
+    }
+}
+
+/// Outcome of a bulk registration
+///
+/// Hosts are registered independently: one failing leaves the others
+/// registered. Every host with `created` or `already_exists` status is
+/// registered afterwards, and none with `error` status are.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BulkRegisterReport {
+    /// Hosts registered by this request
+    pub created: usize,
+    /// Hosts that were already registered
+    pub already_exists: usize,
+    /// Hosts that could not be registered
+    pub failed: usize,
+    /// Per-host results, in request order
+    pub results: Vec<HostRegistration>,
+}
+
+impl BulkRegisterReport {
+    /// Count the per-host results
+    #[must_use]
+    pub fn new(results: Vec<HostRegistration>) -> Self {
+        let count = |status| results.iter().filter(|r| r.status == status).count();
+        Self {
+            created: count(RegistrationStatus::Created),
+            already_exists: count(RegistrationStatus::AlreadyExists),
+            failed: count(RegistrationStatus::Error),
+            results,
+        }
+    }
+}
+
+/// Host list response
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HostListResponse {
+    /// List of hosts
+    pub hosts: Vec<HostSummary>,
+    /// Pagination info
     pub pagination: Pagination,
+    /// Filters and sorting the server applied
+    pub filters: AppliedFilters,
 }
 
+/// Filters and sorting applied to a host list
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct Pagination {
-    pub page: u64,
-    pub per_page: u64,
-    pub total_items: u64,
-    pub total_pages: u64,
+pub struct AppliedFilters {
+    /// Required tags
+    pub tags: Vec<String>,
+    /// Required facts, as `key:value`
+    #[serde(default)]
+    pub facts: Vec<String>,
+    /// Required state
+    pub state: Option<String>,
+    /// Required group
+    pub group: Option<String>,
+    /// Required name prefix
+    pub search: Option<String>,
+    /// Required acknowledgement of the failure
+    pub acknowledged: Option<bool>,
+    /// Sort field
+    pub sort: HostSort,
+    /// Sort direction
+    pub order: SortOrder,
 }
 
+/// One host in the `GET /hosts` list
+///
+/// Carries everything a list view shows, so clients don't need a detail
+/// request per host.
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct HealthResponse {
-    pub status: String,
+pub struct HostSummary {
+    /// Host name
+    pub name: String,
+    /// Current state, e.g. `Idle` or `PendingUpdates`
+    pub state: String,
+    /// Operating system, e.g. "Debian GNU/Linux 12"
+    pub os: Option<String>,
+    /// Upgradable packages found by the last query; `null` if no query
+    /// has run since the last update
+    pub pending_updates: Option<u32>,
+    /// Number of pending security updates
+    pub security_updates: Option<u32>,
+    /// When upgradable packages were last queried
+    pub last_checked: Option<DateTime<Utc>>,
+    /// Tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Last successful update
+    pub last_updated: Option<DateTime<Utc>>,
+    /// Error message if failed
+    pub error: Option<String>,
+    /// Whether the host answers reachability probes
+    pub reachable: bool,
+    /// Last time the host answered a probe
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Whether passwordless sudo, or the host's configured escalation
+    /// command, works for the SSH user, once checked
+    pub sudo_available: Option<bool>,
+    /// Whether an operator has acknowledged the failure; `null` unless failed
+    pub acknowledged: Option<bool>,
+    /// When the failure occurred
+    pub failed_at: Option<DateTime<Utc>>,
+    /// Number of retries since the failure
+    pub retry_count: Option<u32>,
+    /// Failure class, as in [`HostDetail::failure_kind`]; `null` unless failed
+    #[serde(default)]
+    pub failure_kind: Option<String>,
+    /// Whether automation leaves the host alone
+    #[serde(default)]
+    pub paused: bool,
+}
+
+/// Everything known about one host, from `GET /hosts/{hostname}`
+///
+/// The status fields come from the host actor and are always present. The
+/// `config`, `inventory` and `upgradable_packages` sections are `null`
+/// until there is something to show, e.g. before the first inventory
+/// collection or query.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HostDetail {
+    /// Host name
+    pub name: String,
+    /// Current state, e.g. `Idle` or `PendingUpdates`
+    pub state: String,
+    /// Operating system, e.g. "Debian GNU/Linux 12"
+    pub os: Option<String>,
+    /// Facts detected on the host so far, e.g. `os_id` = `debian`
+    #[serde(default)]
+    pub facts: BTreeMap<String, String>,
+    /// Number of pending updates
+    pub pending_updates: Option<u32>,
+    /// Number of pending security updates
+    pub security_updates: Option<u32>,
+    /// When upgradable packages were last queried
+    pub last_checked: Option<DateTime<Utc>>,
+    /// Tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Last successful update
+    pub last_updated: Option<DateTime<Utc>>,
+    /// Error message if failed
+    pub error: Option<String>,
+    /// Whether the host answers reachability probes
+    pub reachable: bool,
+    /// Last time the host answered a probe
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Until when operations fail fast because the host kept refusing
+    /// connections
+    pub circuit_open_until: Option<DateTime<Utc>>,
+    /// Who the host is busy with or reserved for, e.g. "fleet update 42";
+    /// operations by anyone else are refused meanwhile
+    #[serde(default)]
+    pub initiator: Option<String>,
+    /// When that operation started or the host was reserved
+    #[serde(default)]
+    pub initiated_at: Option<DateTime<Utc>>,
+    /// Whether automation leaves the host alone: scheduled and fleet
+    /// updates skip it, and it isn't probed or retried automatically
+    #[serde(default)]
+    pub paused: bool,
+    /// State the host was in when it failed
+    pub previous_state: Option<String>,
+    /// When the failure occurred
+    pub failed_at: Option<DateTime<Utc>>,
+    /// Number of retries since the failure
+    pub retry_count: Option<u32>,
+    /// Whether an operator has acknowledged the failure
+    pub acknowledged: Option<bool>,
+    /// Failure class: `connection`, `authentication`, `package_lock`,
+    /// `package_operation`, `timeout` or `internal`; daemons speaking API
+    /// version 1 send `lock_conflict`, `command_failed` or `permanent`
+    pub failure_kind: Option<String>,
+    /// Failed automatic retries, oldest first
+    #[serde(default)]
+    pub retry_attempts: Vec<RetryAttemptInfo>,
+    /// Package sources that failed in the last query while others
+    /// answered, e.g. "docker-compose: daemon unreachable"
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Latest state transitions, oldest first; see
+    /// `/hosts/{hostname}/transitions` for the full history
+    #[serde(default)]
+    pub recent_transitions: Vec<StateTransitionInfo>,
+    /// Last lines of the failed command's stdout and stderr
+    pub failure_output: Option<String>,
+    /// When the next automatic retry runs
+    pub next_retry_at: Option<DateTime<Utc>>,
+    /// Reboot or service restarts still needed after the last update
+    pub needs_restart: Option<RestartInfo>,
+    /// Whether passwordless sudo, or the host's configured escalation
+    /// command, works for the SSH user; `null` until checked or when
+    /// updates don't need escalation
+    pub sudo_available: Option<bool>,
+    /// Result of the last health check; `null` until one has run
+    pub last_health_check: Option<HealthCheckInfo>,
+    /// How the connection to the host has been doing; `null` until a
+    /// command has run since the daemon started
+    #[serde(default)]
+    pub connection: Option<ConnectionStatsInfo>,
+    /// Configured connection settings and policy
+    pub config: Option<HostConfigInfo>,
+    /// Summary of the last collected inventory
+    pub inventory: Option<InventorySummary>,
+    /// Packages found by the last query; `null` like `pending_updates`
+    pub upgradable_packages: Option<Vec<UpgradablePackageInfo>>,
+}
+
+/// Statistics of the commands run on a host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConnectionStatsInfo {
+    /// Commands run since the daemon started
+    pub commands: u64,
+    /// Commands that could not be run, e.g. because the connection
+    /// failed, or that timed out
+    pub failures: u64,
+    /// Median duration of the recent commands, in milliseconds
+    pub p50_ms: Option<u64>,
+    /// 95th percentile duration of the recent commands, in milliseconds
+    pub p95_ms: Option<u64>,
+    /// Number of recent commands the percentiles cover
+    pub window: usize,
+    /// How long the last connection took to establish, in milliseconds;
+    /// `null` for local hosts
+    pub last_connect_ms: Option<u64>,
+    /// Commands in a row that couldn't reach the host
+    pub consecutive_connection_failures: u32,
+}
+
+/// One failed automatic retry
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RetryAttemptInfo {
+    /// When the attempt started
+    pub attempted_at: DateTime<Utc>,
+    /// Error the attempt failed with
+    pub error: String,
+}
+
+/// One change of a host's state
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StateTransitionInfo {
+    /// State before the change, e.g. "querying"
+    pub from: String,
+    /// State after the change
+    pub to: String,
+    /// When the change happened
+    pub at: DateTime<Utc>,
+    /// Message that caused it, e.g. `QueryInventory` or `AutoRetry`
+    pub trigger: String,
+    /// Who the operation belonged to, e.g. "fleet update 3"
+    #[serde(default)]
+    pub initiator: Option<String>,
+    /// Why the host failed, for transitions into "failed"
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+/// What has to be restarted for installed updates to take effect
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct RestartInfo {
+    /// Kernel-level changes that only a reboot applies
+    pub reboot_needed: bool,
+    /// Services still running outdated binaries or libraries
+    pub services_needing_restart: Vec<String>,
+    /// Packages that caused the requirement, if known
+    pub triggered_by: Vec<String>,
+}
+
+/// Outcome of a host's health checks
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HealthCheckInfo {
+    /// Whether every check passed
+    pub healthy: bool,
+    /// When the checks finished
+    pub checked_at: DateTime<Utc>,
+    /// Each configured check, in order
+    pub checks: Vec<CheckOutcomeInfo>,
+}
+
+/// Outcome of one health check command
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CheckOutcomeInfo {
+    /// Command that was run
+    pub command: String,
+    /// Whether the exit code and output matched the expectations
+    pub passed: bool,
+    /// Exit code, if the command ran to completion
+    pub exit_code: Option<i32>,
+    /// Why the check failed
+    pub message: Option<String>,
+    /// How long the command took, in milliseconds
+    pub duration_ms: u64,
+    /// When the command finished
+    pub checked_at: DateTime<Utc>,
+}
+
+/// A host's configuration as the API shows it
+///
+/// Key paths, and environment values that look like secrets, are replaced
+/// by [`REDACTED`] so they don't leak to every API client; `null` still
+/// means not configured.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HostConfigInfo {
+    /// Host address, optionally as `host:port`
+    pub addr: String,
+    /// SSH port, if set apart from `addr`
+    pub port: Option<u16>,
+    /// SSH user
+    pub user: String,
+    /// SSH private key, redacted
+    pub ssh_key: Option<String>,
+    /// Environment variable holding the key passphrase, redacted
+    pub ssh_key_passphrase_env: Option<String>,
+    /// Seconds to wait for the SSH connection
+    pub connect_timeout_secs: Option<u64>,
+    /// Commands run over SSH at once
+    pub max_ssh_channels: Option<usize>,
+    /// Jump host the SSH connection goes through
+    #[serde(default)]
+    pub jump_host: Option<String>,
+    /// Directories with docker-compose files
+    #[serde(default)]
+    pub compose_paths: Vec<String>,
+    /// Env files used instead of `.env`, by compose directory
+    #[serde(default)]
+    pub compose_env_files: BTreeMap<String, String>,
+    /// Tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Hosts this one depends on, updated after it in fleet updates
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    /// Variables set for every command, with secrets redacted
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Update policy, as in the config file's `[host.policy]`
+    #[schema(value_type = Object)]
+    pub policy: serde_json::Value,
+}
+
+/// Replacement for configured secrets in API responses
+pub const REDACTED: &str = "***";
+
+/// The parts of a collected inventory a detail view shows
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InventorySummary {
+    /// Operating system name and version
+    pub os: String,
+    /// Kernel version
+    pub kernel: String,
+    /// Seconds since boot, at collection time
+    pub uptime_seconds: u64,
+    /// Used space of each mounted filesystem
+    #[serde(default)]
+    pub disks: Vec<DiskUsage>,
+    /// When the inventory was collected
+    pub collected_at: DateTime<Utc>,
+}
+
+/// Pending updates and collected inventory of a host
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct HostInventoryResponse {
+    /// Host name
+    pub name: String,
+    /// Number of pending updates
+    pub pending_updates: u32,
+    /// Number of pending security updates
+    pub security_updates: u32,
+    /// Package names with updates available
+    pub upgradable_packages: Vec<String>,
+    /// osquery inventory, with only the requested sections
+    #[schema(value_type = Object)]
+    pub inventory: serde_json::Value,
+}
+
+/// Outcome of `POST /hosts/{hostname}/install-osquery`
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct OsqueryInstallResponse {
+    /// Host name
+    pub name: String,
+    /// osquery version on the host afterwards, if it could be read
+    pub version: Option<String>,
+    /// `false` if a supported version was already installed
+    pub installed: bool,
+}
+
+/// Used space of one filesystem
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DiskUsage {
+    /// Mount point, e.g. `/`
+    pub mount_point: String,
+    /// Used share of the filesystem, 0 to 100
+    pub used_percent: f64,
+}
+
+/// A package with an update available
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct UpgradablePackageInfo {
+    /// Package name
+    pub name: String,
+    /// Installed version
+    pub current_version: String,
+    /// Version the update installs
+    pub new_version: String,
+    /// Whether the update fixes a security issue
+    #[serde(default)]
+    pub security: bool,
+}
+
+/// How the fleet is doing, from every registered host's status
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct FleetSummary {
+    /// Registered hosts
+    pub total_hosts: usize,
+    /// Number of hosts in each state, keyed by state name (e.g. `idle`,
+    /// `waiting_reboot`); states no host is in are left out
+    pub states: BTreeMap<String, usize>,
+    /// Pending updates summed over all hosts
+    pub pending_updates: u32,
+    /// Hosts waiting for, or needing, a reboot
+    pub reboot_pending: usize,
+    /// Names of the hosts in the failed state, sorted
+    pub failed_hosts: Vec<String>,
+    /// Oldest last successful update among hosts that have been updated
+    pub oldest_last_updated: Option<DateTime<Utc>>,
+}
+
+/// What re-reading the config file changed
+///
+/// Host-level changes are applied; daemon-level settings listed in
+/// `requires_restart` keep their old values until the daemon restarts.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ReloadReport {
+    /// Hosts registered from the file
+    pub added: Vec<String>,
+    /// Hosts no longer in the file that were unregistered
+    pub removed: Vec<String>,
+    /// Hosts whose configuration changed
+    pub updated: Vec<String>,
+    /// Hosts left as they were, with the reason in `warnings`
+    pub skipped: Vec<String>,
+    /// Changed settings that only take effect after a restart, e.g. `daemon.bind`
+    pub requires_restart: Vec<String>,
+    /// Human-readable problems found while reloading
+    pub warnings: Vec<String>,
+}
+
+/// A problem found in a config file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConfigIssue {
+    /// Key the problem is at, e.g. `host[1].policy.maintenance_window.start`;
+    /// empty for problems with the file as a whole
+    pub path: String,
+    /// Line of the key in the file, counting from 1, when it is known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Column of the key on its line, counting from 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// What is wrong
+    pub message: String,
+}
+
+/// Written as `line 12, column 1: host[1].addr: must not be empty`
+impl std::fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "line {line}, column {column}: ")?;
+        }
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+/// Outcome of `POST /system/config/validate`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ConfigValidationReport {
+    /// Whether the daemon would load the file without complaints
+    pub valid: bool,
+    /// Every problem found, in file order where known
+    pub issues: Vec<ConfigIssue>,
+}
+
+/// Outcome of `POST /hosts/import`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ImportReport {
+    /// Hosts registered from the document
+    pub added: Vec<String>,
+    /// Registered hosts given the document's configuration (replace mode)
+    pub updated: Vec<String>,
+    /// Registered hosts missing from the document that were unregistered
+    /// (replace mode)
+    pub removed: Vec<String>,
+    /// Hosts left as they were, with the reason in `warnings`
+    pub skipped: Vec<String>,
+    /// Human-readable reasons hosts were skipped
+    pub warnings: Vec<String>,
+}
+
+impl FleetSummary {
+    /// Number of hosts in `state`
+    #[must_use]
+    pub fn count(&self, state: &str) -> usize {
+        self.states.get(state).copied().unwrap_or(0)
+    }
 }
@@ -0,0 +1,23 @@
+//! API protocol version
+//!
+//! Every daemon reply carries [`API_VERSION_HEADER`] with the protocol
+//! version it speaks and [`DAEMON_VERSION_HEADER`] with its release, and
+//! `/health` reports both. Clients refuse daemons outside the range they
+//! were built for rather than misreading renamed or retyped fields.
+//!
+//! Adding endpoints, fields, or event types keeps the version; it goes up
+//! only when an existing field is renamed, removed, or changes meaning.
+//!
+//! - 2: `failure_kind` values `lock_conflict`, `command_failed` and
+//!   `permanent` became `package_lock`, `package_operation` and `internal`,
+//!   alongside the new `authentication` and `timeout`
+//! - 1: the first versioned protocol
+
+/// Version of the REST and WebSocket protocol this crate describes
+pub const API_VERSION: u32 = 2;
+
+/// Response header with the daemon's [`API_VERSION`]
+pub const API_VERSION_HEADER: &str = "x-tendhost-api-version";
+
+/// Response header with the daemon's release, e.g. `0.3.1`
+pub const DAEMON_VERSION_HEADER: &str = "x-tendhost-version";
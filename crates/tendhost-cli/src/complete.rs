@@ -0,0 +1,107 @@
+//! Shell completions
+//!
+//! Completion scripts call back into the binary with `COMPLETE=<shell>`
+//! set, so candidates always match the installed version and host names
+//! can be fetched from the daemon as the user types.
+
+use std::io::Write;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use clap_complete::engine::CompletionCandidate;
+use clap_complete::env::Shells;
+use color_eyre::Result;
+use color_eyre::eyre::eyre;
+use tendhost_client::HttpClient;
+
+/// Environment variable the completion scripts set when calling back
+pub const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Daemon URL used when neither `--url` nor `TENDHOST_URL` is given
+pub const DEFAULT_URL: &str = "http://localhost:8080";
+
+/// Environment variable holding the daemon URL
+pub const URL_VAR: &str = "TENDHOST_URL";
+
+/// How long host name completion waits for the daemon
+const HOSTS_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Shells completion scripts can be generated for
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Bash => "bash",
+            Self::Zsh => "zsh",
+            Self::Fish => "fish",
+        }
+    }
+}
+
+/// Write the completion script for `shell` to `out`
+///
+/// `name` identifies the command inside the script. The script completes
+/// the binary as it was invoked and calls back into the running
+/// executable.
+pub fn write_script(shell: Shell, name: &str, out: &mut dyn Write) -> Result<()> {
+    let shells = Shells::builtins();
+    let completer = shells
+        .completer(shell.as_str())
+        .ok_or_else(|| eyre!("no completion support for {}", shell.as_str()))?;
+    let exe = std::env::current_exe()?;
+    let bin = std::env::args_os()
+        .next()
+        .and_then(|arg0| {
+            std::path::Path::new(&arg0)
+                .file_name()
+                .map(|f| f.to_string_lossy().into_owned())
+        })
+        .unwrap_or_else(|| "tendhost-cli".to_string());
+    completer.write_registration(COMPLETE_VAR, name, &bin, &exe.to_string_lossy(), out)?;
+    Ok(())
+}
+
+/// Registered host names, or nothing if the daemon cannot be reached
+///
+/// Completion runs before arguments are parsed, so the daemon is found
+/// through `TENDHOST_URL` rather than `--url`.
+pub fn host_names() -> Vec<CompletionCandidate> {
+    let url = std::env::var(URL_VAR).unwrap_or_else(|_| DEFAULT_URL.to_string());
+    let Ok(runtime) = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    else {
+        return Vec::new();
+    };
+    runtime
+        .block_on(fetch_host_names(&url))
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+async fn fetch_host_names(url: &str) -> Result<Vec<String>> {
+    let client = HttpClient::builder(url)
+        .timeout(HOSTS_TIMEOUT)
+        .connect_timeout(HOSTS_TIMEOUT)
+        .retries(0)
+        .build()?;
+    let mut names = Vec::new();
+    let mut page = 1;
+    loop {
+        let response = client.list_hosts().page(page).per_page(200).send().await?;
+        names.extend(response.hosts.into_iter().map(|host| host.name));
+        if page >= response.pagination.total_pages {
+            break;
+        }
+        page += 1;
+    }
+    Ok(names)
+}
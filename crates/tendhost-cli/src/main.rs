@@ -2,13 +2,45 @@
 //!
 //! Command-line interface for interacting with tendhost daemon
 
-use clap::{Parser, Subcommand};
+mod complete;
+mod status;
+
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::ArgValueCandidates;
+use clap_complete::env::CompleteEnv;
 use color_eyre::Result;
+use color_eyre::eyre::{bail, eyre};
+use serde::Deserialize;
+use tendhost_api::requests::{
+    FleetUpdateFilter, FleetUpdateRequest, ImportMode, RegisterHostRequest, UpdateRequest,
+    UpdateScope,
+};
+use tendhost_api::responses::{
+    FleetDryRunReport, HostDetail, ImportReport, RegistrationStatus, UpdatePreview,
+};
+use tendhost_client::HttpClient;
+use tendhost_client::ssh_config;
+use tendhost_client::wait::is_failed_state;
 
 #[derive(Parser)]
 #[command(name = "tendhost")]
 #[command(about = "Actor-based homelab orchestration CLI", long_about = None)]
 struct Cli {
+    /// Daemon base URL
+    #[arg(long, global = true, env = complete::URL_VAR, default_value = complete::DEFAULT_URL)]
+    url: String,
+
+    /// Talk to a daemon whose API version this CLI doesn't support
+    ///
+    /// Its replies may be misread; only for when the differences are known
+    /// not to matter.
+    #[arg(long, global = true)]
+    ignore_api_version: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -17,18 +49,895 @@ struct Cli {
 enum Commands {
     /// List all hosts
     #[command(name = "hosts")]
-    Hosts,
+    Hosts {
+        #[command(subcommand)]
+        command: Option<HostCommands>,
+    },
+
+    /// Show a compact overview of the fleet
+    #[command(name = "status")]
+    Status {
+        /// Keep refreshing the overview
+        #[arg(long)]
+        watch: bool,
+
+        /// Seconds between refreshes
+        #[arg(long, default_value = "5", requires = "watch")]
+        interval: u64,
+    },
+
+    /// Print a shell completion script
+    ///
+    /// Host names are completed from the daemon at `TENDHOST_URL` when it
+    /// is reachable. For bash, add `source <(tendhost-cli completions bash)`
+    /// to ~/.bashrc.
+    #[command(name = "completions")]
+    Completions {
+        /// Shell to generate the script for
+        shell: complete::Shell,
+    },
+
+    /// Update packages on a host
+    #[command(name = "update")]
+    Update {
+        /// Host name
+        #[arg(add = ArgValueCandidates::new(complete::host_names))]
+        host: String,
+
+        /// Show what would be updated without changing anything; the same
+        /// as `preview`
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Update the host even though it is paused
+        #[arg(long)]
+        force: bool,
+
+        /// Skip the autoremove and cache cleaning the host's policy asks for
+        #[arg(long)]
+        skip_cleanup: bool,
+
+        #[command(flatten)]
+        wait: WaitArgs,
+    },
+
+    /// Show what an update of a host would do, without changing it
+    ///
+    /// Simulates the upgrade and lists what would stop a real update, such
+    /// as a pause or too little disk space; exits non-zero if anything
+    /// would. Nothing is recorded on the host.
+    #[command(name = "preview")]
+    Preview {
+        /// Host name
+        #[arg(add = ArgValueCandidates::new(complete::host_names))]
+        host: String,
+
+        /// Only simulate security updates
+        #[arg(long)]
+        security_only: bool,
+    },
+
+    /// Reboot a host
+    #[command(name = "reboot")]
+    Reboot {
+        /// Host name
+        #[arg(add = ArgValueCandidates::new(complete::host_names))]
+        host: String,
+
+        #[command(flatten)]
+        wait: WaitArgs,
+    },
+
+    /// Cancel a running update on a host
+    #[command(name = "cancel")]
+    Cancel {
+        /// Host name
+        #[arg(add = ArgValueCandidates::new(complete::host_names))]
+        host: String,
+    },
+
+    /// Pause a host so scheduled and fleet updates leave it alone
+    ///
+    /// Manual updates of a paused host need `update --force`.
+    #[command(name = "pause")]
+    Pause {
+        /// Host name
+        #[arg(add = ArgValueCandidates::new(complete::host_names))]
+        host: String,
+    },
+
+    /// Resume a paused host
+    #[command(name = "resume")]
+    Resume {
+        /// Host name
+        #[arg(add = ArgValueCandidates::new(complete::host_names))]
+        host: String,
+    },
+
+    /// Show commands recently run on a host
+    #[command(name = "logs")]
+    Logs {
+        /// Host name
+        #[arg(add = ArgValueCandidates::new(complete::host_names))]
+        host: String,
+    },
+
+    /// Show recent audit log entries
+    #[command(name = "audit")]
+    Audit {
+        /// Only show entries for this host
+        #[arg(long, add = ArgValueCandidates::new(complete::host_names))]
+        host: Option<String>,
+
+        /// Only show entries at or after this RFC 3339 timestamp
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Maximum number of entries to show
+        #[arg(long, default_value = "50")]
+        limit: usize,
+    },
+
+    /// Fleet-wide operations
+    #[command(name = "fleet", subcommand)]
+    Fleet(FleetCommands),
+
+    /// Export fleet reports
+    #[command(name = "report", subcommand)]
+    Report(ReportCommands),
+
+    /// Daemon configuration
+    #[command(name = "config", subcommand)]
+    Config(ConfigCommands),
+}
+
+/// Flags for following an operation until it finishes
+#[derive(clap::Args)]
+struct WaitArgs {
+    /// Wait for the operation to finish, failing if a host ends up failed
+    #[arg(long)]
+    wait: bool,
+
+    /// Seconds to wait before giving up
+    #[arg(long, default_value = "1800", requires = "wait")]
+    timeout: u64,
+}
+
+#[derive(Subcommand)]
+enum HostCommands {
+    /// Register the hosts listed in a TOML file in one request
+    ///
+    /// The file holds `[[host]]` tables with `name`, `addr` and optionally
+    /// `port`, `user`, `ssh_key`, `connect_timeout_secs` and `tags`, as in
+    /// the daemon config. Hosts that are already registered are left
+    /// unchanged.
+    #[command(name = "add")]
+    Add {
+        /// TOML file listing the hosts
+        #[arg(long, value_name = "PATH")]
+        from_file: PathBuf,
+
+        /// Register without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+
+    /// Write every registered host to stdout as `[[host]]` TOML tables
+    ///
+    /// The output can be read back with `hosts import FILE`, on this daemon
+    /// or another.
+    #[command(name = "export")]
+    Export,
+
+    /// Register hosts from an exported TOML file or an OpenSSH client config
+    ///
+    /// With FILE, the hosts of a `hosts export` document are imported.
+    /// Without it, the concrete hosts of the SSH config are registered:
+    /// wildcard `Host` patterns and `Match` blocks are not imported, and
+    /// hosts without a `User` are registered as root.
+    #[command(name = "import")]
+    Import {
+        /// TOML file written by `hosts export`
+        #[arg(value_name = "FILE", conflicts_with_all = ["ssh_config", "tags"])]
+        file: Option<PathBuf>,
+
+        /// How to treat hosts that are already registered
+        #[arg(long, value_enum, requires = "file")]
+        mode: Option<ImportModeArg>,
+
+        /// SSH config to read (defaults to ~/.ssh/config)
+        #[arg(long, value_name = "PATH")]
+        ssh_config: Option<PathBuf>,
+
+        /// Tag every imported host (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Register without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
+/// How `hosts import FILE` treats registered hosts
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportModeArg {
+    /// Keep registered hosts; skip names that already exist
+    Merge,
+    /// Make the registered hosts match the file, unregistering the others
+    Replace,
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
+impl From<ImportModeArg> for ImportMode {
+    fn from(mode: ImportModeArg) -> Self {
+        match mode {
+            ImportModeArg::Merge => Self::Merge,
+            ImportModeArg::Replace => Self::Replace,
+        }
+    }
+}
+
+/// Output format for reports
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ReportCommands {
+    /// Installed packages on every host with collected inventory
+    #[command(name = "packages")]
+    Packages {
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: ReportFormat,
+    },
+
+    /// Pending updates per host
+    #[command(name = "updates")]
+    Updates {
+        /// Output format
+        #[arg(long, value_enum, default_value = "json")]
+        format: ReportFormat,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Check a config file on the daemon without applying it
+    ///
+    /// Every problem is listed with its key path and position; the command
+    /// fails if there is any. Without FILE the daemon checks its own
+    /// config file.
+    #[command(name = "validate")]
+    Validate {
+        /// Config file to check
+        #[arg(value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum FleetCommands {
+    /// Update all matching hosts in batches
+    #[command(name = "update")]
+    Update {
+        /// Only apply security updates
+        #[arg(long)]
+        security_only: bool,
+
+        /// Only update hosts with this tag (repeatable)
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Exclude this host (repeatable)
+        #[arg(long = "exclude", add = ArgValueCandidates::new(complete::host_names))]
+        exclude_hosts: Vec<String>,
+
+        /// Number of hosts to update in parallel
+        #[arg(long, default_value = "2")]
+        batch_size: usize,
+
+        /// Delay between batches in milliseconds
+        #[arg(long, default_value = "30000")]
+        delay_ms: u64,
+
+        /// Update this host first, before the rest (repeatable)
+        #[arg(long = "canary", add = ArgValueCandidates::new(complete::host_names))]
+        canary_hosts: Vec<String>,
+
+        /// Update the remaining hosts even if a canary fails
+        #[arg(long, requires = "canary_hosts")]
+        continue_on_canary_failure: bool,
+
+        /// Show what would be updated on each host without changing anything
+        #[arg(long, conflicts_with = "wait")]
+        dry_run: bool,
+
+        #[command(flatten)]
+        wait: WaitArgs,
+    },
+}
+
+fn main() -> Result<()> {
+    // Completion requests exit here, before anything writes to stdout; the
+    // host name completer runs its own runtime, so none may exist yet
+    CompleteEnv::with_factory(Cli::command)
+        .var(complete::COMPLETE_VAR)
+        .complete();
+
+    color_eyre::install()?;
     let cli = Cli::parse();
+    tokio::runtime::Runtime::new()?.block_on(run(cli))
+}
 
+async fn run(cli: Cli) -> Result<()> {
+    let connect = || http_client(&cli.url, cli.ignore_api_version);
     match cli.command {
-        Commands::Hosts => {
+        Commands::Hosts { command: None } => {
             println!("Listing hosts...");
         }
+        Commands::Hosts {
+            command:
+                Some(HostCommands::Import {
+                    file: Some(file),
+                    mode,
+                    ..
+                }),
+        } => {
+            let text = std::fs::read_to_string(&file)
+                .map_err(|e| eyre!("failed to read {}: {e}", file.display()))?;
+            let client = connect()?;
+            let mode = mode.map_or_else(ImportMode::default, ImportMode::from);
+            let report = client.import_hosts(&text, mode).await?;
+            print_import_report(&report);
+        }
+        Commands::Hosts {
+            command: Some(HostCommands::Export),
+        } => {
+            let client = connect()?;
+            print!("{}", client.export_hosts().await?);
+        }
+        Commands::Hosts {
+            command:
+                Some(HostCommands::Import {
+                    ssh_config,
+                    tags,
+                    yes,
+                    ..
+                }),
+        } => {
+            let path = match ssh_config {
+                Some(path) => path,
+                None => default_ssh_config()?,
+            };
+            import_hosts(&connect()?, &path, &tags, yes).await?;
+        }
+        Commands::Hosts {
+            command: Some(HostCommands::Add { from_file, yes }),
+        } => {
+            let text = std::fs::read_to_string(&from_file)
+                .map_err(|e| eyre!("failed to read {}: {e}", from_file.display()))?;
+            let file: HostsFile = toml::from_str(&text)
+                .map_err(|e| eyre!("invalid hosts file {}: {e}", from_file.display()))?;
+            if file.host.is_empty() {
+                println!("No hosts found in {}", from_file.display());
+            } else {
+                register_hosts(&connect()?, &file.host, yes).await?;
+            }
+        }
+        Commands::Config(ConfigCommands::Validate { file }) => {
+            let text = file
+                .as_ref()
+                .map(|file| {
+                    std::fs::read_to_string(file)
+                        .map_err(|e| eyre!("failed to read {}: {e}", file.display()))
+                })
+                .transpose()?;
+            let report = connect()?.validate_config(text.as_deref()).await?;
+            let name =
+                file.map_or_else(|| "daemon config".to_string(), |f| f.display().to_string());
+            for issue in &report.issues {
+                println!("{name}: {issue}");
+            }
+            if !report.valid {
+                bail!("{name} is invalid: {} problem(s)", report.issues.len());
+            }
+            println!("{name}: OK");
+        }
+        Commands::Status { watch, interval } => {
+            let client = connect()?;
+            let watch = watch.then(|| Duration::from_secs(interval.max(1)));
+            status::run(&client, watch).await?;
+        }
+        Commands::Completions { shell } => {
+            let cmd = Cli::command();
+            complete::write_script(shell, cmd.get_name(), &mut std::io::stdout())?;
+        }
+        Commands::Update {
+            host,
+            dry_run,
+            force,
+            skip_cleanup,
+            wait,
+        } => {
+            let client = connect()?;
+            if dry_run {
+                return preview(&client, &host, None).await;
+            }
+            let request = UpdateRequest {
+                dry_run,
+                scope: None,
+                stack: None,
+                packages: Vec::new(),
+                force,
+                skip_cleanup,
+            };
+            let accepted = client.start_update(&host, &request).await?;
+            println!("{}", accepted.message);
+            if wait.wait {
+                wait_for_host(&client, &host, wait.timeout).await?;
+            }
+        }
+        Commands::Preview {
+            host,
+            security_only,
+        } => {
+            let client = connect()?;
+            let scope = security_only.then_some(UpdateScope::SecurityOnly);
+            preview(&client, &host, scope).await?;
+        }
+        Commands::Reboot { host, wait } => {
+            let client = connect()?;
+            client.reboot_host(&host).await?;
+            println!("Reboot started on {host}");
+            if wait.wait {
+                wait_for_host(&client, &host, wait.timeout).await?;
+            }
+        }
+        Commands::Cancel { host } => {
+            let client = connect()?;
+            client.cancel_host_update(&host).await?;
+            println!("Cancelled update on {host}; run a retry once it has been inspected");
+        }
+        Commands::Pause { host } => {
+            let client = connect()?;
+            client.pause_host(&host).await?;
+            println!("Paused {host}; automation will leave it alone until resumed");
+        }
+        Commands::Resume { host } => {
+            let client = connect()?;
+            client.resume_host(&host).await?;
+            println!("Resumed {host}");
+        }
+        Commands::Logs { host } => {
+            let client = connect()?;
+            for entry in client.command_history(&host).await? {
+                let outcome = match (entry.status, &entry.error) {
+                    (_, Some(error)) => format!("error: {error}"),
+                    (Some(status), None) => format!("exit {status}"),
+                    (None, None) => "-".to_string(),
+                };
+                println!(
+                    "{}  {:<24} {:>7}ms  {}",
+                    entry.started_at.to_rfc3339(),
+                    outcome,
+                    entry.duration_ms,
+                    entry.command,
+                );
+                if entry.status.is_some_and(|s| s != 0) && !entry.stderr_tail.is_empty() {
+                    for line in entry.stderr_tail.lines() {
+                        println!("    {line}");
+                    }
+                }
+            }
+        }
+        Commands::Audit { host, since, limit } => {
+            let client = connect()?;
+            let entries = client
+                .audit_log(host.as_deref(), since.as_deref(), Some(limit))
+                .await?;
+            for entry in entries {
+                let status = entry.status.map(|s| s.to_string()).unwrap_or_default();
+                println!(
+                    "{}  {:<18} {:<24} {:<6} {:<8} {}",
+                    entry.timestamp.to_rfc3339(),
+                    entry.operation,
+                    entry.hosts.join(","),
+                    status,
+                    entry.source,
+                    entry.remote_addr.as_deref().unwrap_or("-"),
+                );
+            }
+        }
+        Commands::Fleet(FleetCommands::Update {
+            security_only,
+            tags,
+            exclude_hosts,
+            batch_size,
+            delay_ms,
+            canary_hosts,
+            continue_on_canary_failure,
+            dry_run,
+            wait,
+        }) => {
+            let client = connect()?;
+            // Resolve targets before starting so later batches are waited for too
+            let targets = if wait.wait {
+                fleet_targets(&client, &tags, &exclude_hosts).await?
+            } else {
+                Vec::new()
+            };
+
+            let filter =
+                (!tags.is_empty() || !exclude_hosts.is_empty()).then(|| FleetUpdateFilter {
+                    tags: (!tags.is_empty()).then_some(tags),
+                    groups: None,
+                    exclude_hosts: (!exclude_hosts.is_empty()).then_some(exclude_hosts),
+                    facts: None,
+                });
+            let request = FleetUpdateRequest {
+                batch_size,
+                delay_ms,
+                scope: security_only.then_some(UpdateScope::SecurityOnly),
+                filter,
+                dry_run,
+                canary_hosts: canary_hosts.clone(),
+                halt_on_canary_failure: !continue_on_canary_failure,
+            };
+
+            if dry_run {
+                print_dry_run(&client.fleet_dry_run(request).await?);
+                return Ok(());
+            }
+
+            client.update_fleet(request).await?;
+            println!("Fleet update started");
+            if wait.wait {
+                let timeout = Duration::from_secs(wait.timeout);
+                let (canaries, rest): (Vec<String>, Vec<String>) = targets
+                    .into_iter()
+                    .partition(|name| canary_hosts.iter().any(|c| c.eq_ignore_ascii_case(name)));
+                let mut hosts = Vec::new();
+                if !canaries.is_empty() {
+                    hosts = client
+                        .wait_for_hosts(&canaries, timeout, print_state)
+                        .await?;
+                    let failed = failed_hosts(&hosts);
+                    if !failed.is_empty() && !continue_on_canary_failure {
+                        bail!(
+                            "canary update failed on {}; remaining hosts were skipped",
+                            failed.join(", ")
+                        );
+                    }
+                }
+                hosts.extend(client.wait_for_hosts(&rest, timeout, print_state).await?);
+                let failed = failed_hosts(&hosts);
+                if !failed.is_empty() {
+                    bail!("fleet update failed on {}", failed.join(", "));
+                }
+                println!("Fleet update finished on {} hosts", hosts.len());
+            }
+        }
+        Commands::Report(report) => {
+            let client = connect()?;
+            let body = match report {
+                ReportCommands::Packages { format } => {
+                    client.packages_report(format.as_str()).await?
+                }
+                ReportCommands::Updates { format } => {
+                    client.updates_report(format.as_str()).await?
+                }
+            };
+            print!("{body}");
+        }
     }
 
     Ok(())
 }
+
+/// Print an update preview of `host`, failing if the update would be refused
+async fn preview(client: &HttpClient, host: &str, scope: Option<UpdateScope>) -> Result<()> {
+    let preview = client.preview_update(host, scope).await?;
+    print_preview(&preview);
+    if preview.is_blocked() {
+        bail!(
+            "{host} would not be updated: {} blocker(s)",
+            preview.blockers.len()
+        );
+    }
+    Ok(())
+}
+
+/// Render an update preview
+fn print_preview(preview: &UpdatePreview) {
+    println!(
+        "{}: {} to upgrade, {} new, {} to remove ({} packages)",
+        preview.host,
+        preview.upgraded_count,
+        preview.new_count,
+        preview.removed_count,
+        preview.scope,
+    );
+    for package in &preview.packages {
+        println!("  {package}");
+    }
+    if let Some(bytes) = preview.download_bytes {
+        println!("download: {}", format_size(bytes));
+    }
+    if preview.reboot_likely {
+        println!("reboot: likely");
+    }
+    for warning in &preview.warnings {
+        println!("warning: {warning}");
+    }
+    for blocker in &preview.blockers {
+        println!("blocked: {blocker}");
+    }
+}
+
+/// Format a byte count in the decimal units package managers print
+fn format_size(bytes: u64) -> String {
+    #[allow(clippy::cast_precision_loss)]
+    let size = bytes as f64;
+    match bytes {
+        1_000_000_000.. => format!("{:.1} GB", size / 1e9),
+        1_000_000.. => format!("{:.1} MB", size / 1e6),
+        1_000.. => format!("{} kB", bytes / 1_000),
+        _ => format!("{bytes} B"),
+    }
+}
+
+/// Render a fleet dry-run report as tables
+fn print_dry_run(report: &FleetDryRunReport) {
+    println!("{:<24} {:>7} {:>8}  STATUS", "HOST", "UPDATES", "SECURITY");
+    for host in &report.hosts {
+        let status = match (&host.error, host.warnings.len()) {
+            (Some(error), _) => error.clone(),
+            (None, 0) => "ok".to_string(),
+            (None, n) => format!("{n} warnings"),
+        };
+        println!(
+            "{:<24} {:>7} {:>8}  {status}",
+            host.host, host.pending_updates, host.security_updates,
+        );
+    }
+
+    let warnings: Vec<_> = report
+        .hosts
+        .iter()
+        .flat_map(|host| host.warnings.iter().map(move |w| (&host.host, w)))
+        .collect();
+    if !warnings.is_empty() {
+        println!();
+        for (host, warning) in warnings {
+            println!("warning: {host}: {warning}");
+        }
+    }
+
+    if !report.packages.is_empty() {
+        println!();
+        println!("{:<32} {:>5}  ON", "PACKAGE", "HOSTS");
+        for package in &report.packages {
+            println!(
+                "{:<32} {:>5}  {}",
+                package.name,
+                package.host_count,
+                package.hosts.join(","),
+            );
+        }
+    }
+
+    if !report.batches.is_empty() {
+        println!();
+        for (i, batch) in report.batches.iter().enumerate() {
+            println!("batch {}: {}", i + 1, batch.join(", "));
+        }
+    }
+
+    println!();
+    println!(
+        "{} updates on {} of {} hosts, {} hosts failed",
+        report.total_updates, report.hosts_with_updates, report.total_hosts, report.failed_hosts,
+    );
+}
+
+fn print_state(host: &str, state: &str) {
+    println!("{host}: {state}");
+}
+
+/// Follow a single host until its operation finishes
+async fn wait_for_host(client: &HttpClient, host: &str, timeout: u64) -> Result<()> {
+    let details = client
+        .wait_for_state(host, Duration::from_secs(timeout), print_state)
+        .await?;
+    if is_failed_state(&details.state) {
+        match details.error.as_deref() {
+            Some(error) => bail!("{host} failed: {error}"),
+            None => bail!("{host} failed"),
+        }
+    }
+    Ok(())
+}
+
+/// Names of the hosts in `hosts` that ended up failed
+fn failed_hosts(hosts: &[HostDetail]) -> Vec<&str> {
+    hosts
+        .iter()
+        .filter(|h| is_failed_state(&h.state))
+        .map(|h| h.name.as_str())
+        .collect()
+}
+
+/// Hosts a fleet update with these filters will touch
+///
+/// Mirrors the daemon's fleet filter: a host matches if it has any of the
+/// tags and is not excluded.
+async fn fleet_targets(
+    client: &HttpClient,
+    tags: &[String],
+    exclude_hosts: &[String],
+) -> Result<Vec<String>> {
+    let mut targets = Vec::new();
+    let mut page = 1;
+    loop {
+        let response = client.list_hosts().page(page).per_page(200).send().await?;
+        for host in response.hosts {
+            let tagged = tags.is_empty() || host.tags.iter().any(|t| tags.contains(t));
+            // The daemon reports names in lowercase; users may not type them so
+            let excluded = exclude_hosts
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(&host.name));
+            if tagged && !excluded {
+                targets.push(host.name);
+            }
+        }
+        if page >= response.pagination.total_pages {
+            break;
+        }
+        page += 1;
+    }
+    Ok(targets)
+}
+
+fn default_ssh_config() -> Result<PathBuf> {
+    let home =
+        std::env::var_os("HOME").ok_or_else(|| eyre!("HOME is not set; pass --ssh-config"))?;
+    Ok(PathBuf::from(home).join(".ssh").join("config"))
+}
+
+/// Hosts file read by `hosts add --from-file`
+#[derive(Deserialize)]
+struct HostsFile {
+    #[serde(default)]
+    host: Vec<RegisterHostRequest>,
+}
+
+/// Client for the daemon at `url`
+fn http_client(url: &str, ignore_api_version: bool) -> Result<HttpClient> {
+    let mut builder = HttpClient::builder(url);
+    if ignore_api_version {
+        builder = builder.ignore_api_version();
+    }
+    Ok(builder.build()?)
+}
+
+/// Register the concrete hosts found in the SSH config at `path`
+async fn import_hosts(
+    client: &HttpClient,
+    path: &std::path::Path,
+    tags: &[String],
+    yes: bool,
+) -> Result<()> {
+    let hosts = ssh_config::parse_file(path)?;
+    if hosts.is_empty() {
+        println!("No concrete hosts found in {}", path.display());
+        return Ok(());
+    }
+
+    let requests: Vec<_> = hosts
+        .iter()
+        .map(|host| host.to_register_request(tags))
+        .collect();
+    register_hosts(client, &requests, yes).await
+}
+
+/// Print what a TOML import changed
+fn print_import_report(report: &ImportReport) {
+    for name in &report.added {
+        println!("registered {name}");
+    }
+    for name in &report.updated {
+        println!("updated {name}");
+    }
+    for name in &report.removed {
+        println!("unregistered {name}");
+    }
+    for warning in &report.warnings {
+        eprintln!("skipped {warning}");
+    }
+    println!(
+        "{} added, {} updated, {} removed, {} skipped",
+        report.added.len(),
+        report.updated.len(),
+        report.removed.len(),
+        report.skipped.len()
+    );
+}
+
+/// Preview `requests`, confirm, then register them through the bulk endpoint
+async fn register_hosts(
+    client: &HttpClient,
+    requests: &[RegisterHostRequest],
+    yes: bool,
+) -> Result<()> {
+    println!(
+        "{:<24} {:<28} {:<6} {:<12} KEY",
+        "NAME", "ADDRESS", "PORT", "USER"
+    );
+    for request in requests {
+        println!(
+            "{:<24} {:<28} {:<6} {:<12} {}",
+            request.name,
+            request.addr,
+            request.port.unwrap_or(22),
+            request.user,
+            request.ssh_key.as_deref().unwrap_or("-"),
+        );
+    }
+
+    if !yes && !confirm(&format!("Register {} hosts?", requests.len()))? {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let report = client.create_hosts(requests).await?;
+    for result in &report.results {
+        match result.status {
+            RegistrationStatus::Created => println!("registered {}", result.name),
+            RegistrationStatus::AlreadyExists => {
+                println!("already registered {}", result.name);
+            }
+            RegistrationStatus::Error => eprintln!(
+                "failed {}: {}",
+                result.name,
+                result.error.as_deref().unwrap_or("unknown error")
+            ),
+        }
+    }
+
+    if report.failed > 0 {
+        bail!(
+            "{} of {} hosts could not be registered",
+            report.failed,
+            requests.len()
+        );
+    }
+    println!(
+        "Registered {} hosts ({} already registered)",
+        report.created, report.already_exists
+    );
+    Ok(())
+}
+
+/// Ask a yes/no question on stdin; anything but `y`/`yes` is no
+fn confirm(prompt: &str) -> Result<bool> {
+    print!("{prompt} [y/N] ");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().lock().read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
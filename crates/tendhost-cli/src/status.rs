@@ -0,0 +1,212 @@
+//! `status`: compact fleet overview
+
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::time::Duration;
+
+use color_eyre::Result;
+use owo_colors::{OwoColorize, Style};
+use tendhost_api::responses::{FleetSummary, HostSummary};
+use tendhost_client::HttpClient;
+
+/// Everything the overview shows, fetched in one go
+struct Snapshot {
+    summary: FleetSummary,
+    failed: Vec<HostSummary>,
+    waiting_reboot: Vec<HostSummary>,
+}
+
+/// Print the overview once, or every `interval` until interrupted
+///
+/// Colors and screen clearing are only used when stdout is a terminal.
+/// While watching, a failed refresh is reported and retried instead of
+/// ending the command.
+pub async fn run(client: &HttpClient, watch: Option<Duration>) -> Result<()> {
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let Some(interval) = watch else {
+        print!("{}", render(&fetch(client).await?, color));
+        return Ok(());
+    };
+
+    loop {
+        let body = match fetch(client).await {
+            Ok(snapshot) => render(&snapshot, color),
+            Err(e) => format!("error: {e}\n"),
+        };
+        if color {
+            // Clear the screen and move the cursor home
+            print!("\x1b[2J\x1b[H");
+        } else {
+            println!();
+        }
+        print!("{body}");
+        println!("\nRefreshing every {}s, Ctrl-C to stop", interval.as_secs());
+        tokio::time::sleep(interval).await;
+    }
+}
+
+async fn fetch(client: &HttpClient) -> Result<Snapshot> {
+    Ok(Snapshot {
+        summary: client.fleet_status().await?,
+        failed: hosts_in_state(client, "failed").await?,
+        waiting_reboot: hosts_in_state(client, "waiting_reboot").await?,
+    })
+}
+
+async fn hosts_in_state(client: &HttpClient, state: &str) -> Result<Vec<HostSummary>> {
+    let mut hosts = Vec::new();
+    let mut page = 1;
+    loop {
+        let response = client
+            .list_hosts()
+            .state(state)
+            .page(page)
+            .per_page(200)
+            .send()
+            .await?;
+        hosts.extend(response.hosts);
+        if page >= response.pagination.total_pages {
+            break;
+        }
+        page += 1;
+    }
+    Ok(hosts)
+}
+
+/// Style for a host state name
+fn state_style(state: &str) -> Style {
+    match state {
+        "idle" => Style::new().green(),
+        "failed" => Style::new().red().bold(),
+        "pending_updates" | "waiting_reboot" => Style::new().yellow(),
+        _ => Style::new().cyan(),
+    }
+}
+
+fn paint(text: &str, style: Style, color: bool) -> String {
+    if color {
+        text.style(style).to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+fn render(snapshot: &Snapshot, color: bool) -> String {
+    let summary = &snapshot.summary;
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "{} hosts, {} pending updates",
+        paint(&summary.total_hosts.to_string(), Style::new().bold(), color),
+        paint(
+            &summary.pending_updates.to_string(),
+            Style::new().bold(),
+            color
+        ),
+    );
+    for (state, count) in &summary.states {
+        // Pad before painting so escape codes don't break alignment
+        let name = format!("{state:<16}");
+        let _ = writeln!(
+            out,
+            "  {} {count:>4}",
+            paint(&name, state_style(state), color)
+        );
+    }
+    if let Some(oldest) = summary.oldest_last_updated {
+        let _ = writeln!(
+            out,
+            "Oldest update: {}",
+            oldest.format("%Y-%m-%d %H:%M UTC")
+        );
+    }
+
+    if !snapshot.failed.is_empty() {
+        let header = format!("Failed ({})", snapshot.failed.len());
+        let _ = writeln!(out, "\n{}", paint(&header, state_style("failed"), color));
+        for host in &snapshot.failed {
+            let acknowledged = if host.acknowledged == Some(true) {
+                " (acknowledged)"
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                out,
+                "  {:<24} {}{acknowledged}",
+                host.name,
+                host.error.as_deref().unwrap_or("unknown error"),
+            );
+        }
+    }
+
+    if summary.reboot_pending > 0 {
+        let header = format!("Awaiting reboot ({})", summary.reboot_pending);
+        let _ = writeln!(
+            out,
+            "\n{}",
+            paint(&header, state_style("waiting_reboot"), color)
+        );
+        for host in &snapshot.waiting_reboot {
+            let _ = writeln!(out, "  {}", host.name);
+        }
+        // Hosts that need a reboot but are not waiting for one carry no state
+        let others = summary
+            .reboot_pending
+            .saturating_sub(snapshot.waiting_reboot.len());
+        if others > 0 {
+            let _ = writeln!(out, "  ... {others} more need a reboot after updating");
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    fn host(name: &str, state: &str, error: Option<&str>) -> HostSummary {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "state": state,
+            "error": error,
+            "reachable": true,
+            "acknowledged": error.map(|_| false),
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_plain_overview() {
+        let snapshot = Snapshot {
+            summary: FleetSummary {
+                total_hosts: 4,
+                states: BTreeMap::from([
+                    ("failed".to_string(), 1),
+                    ("idle".to_string(), 2),
+                    ("waiting_reboot".to_string(), 1),
+                ]),
+                pending_updates: 7,
+                reboot_pending: 2,
+                failed_hosts: vec!["db-1".to_string()],
+                oldest_last_updated: None,
+            },
+            failed: vec![host("db-1", "failed", Some("apt lock held"))],
+            waiting_reboot: vec![host("web-1", "waiting_reboot", None)],
+        };
+
+        let text = render(&snapshot, false);
+        assert!(!text.contains('\x1b'));
+        assert!(text.starts_with("4 hosts, 7 pending updates\n"));
+        assert!(text.contains("  idle                2\n"));
+        assert!(text.contains("Failed (1)\n  db-1"));
+        assert!(text.contains("apt lock held\n"));
+        assert!(text.contains("Awaiting reboot (2)\n  web-1\n"));
+        assert!(text.contains("1 more need a reboot"));
+
+        assert!(render(&snapshot, true).contains('\x1b'));
+    }
+}
@@ -1,5 +1,7 @@
 //! Error types for the tendhost client
 
+use std::ops::RangeInclusive;
+
 use thiserror::Error;
 
 /// Errors that can occur when using the tendhost client
@@ -41,6 +43,55 @@ pub enum ClientError {
     /// Invalid response format
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
+
+    /// An idempotent request kept failing after retries
+    #[error("Request failed after {attempts} attempts: {source}")]
+    RetriesExhausted {
+        /// Attempts made, including the first
+        attempts: u32,
+        /// Error of the last attempt
+        source: Box<ClientError>,
+    },
+
+    /// The daemon speaks a protocol version this client doesn't support
+    #[error(
+        "daemon speaks API version {server}, but this client supports {}; \
+         upgrade the older of the two",
+        version_range(supported)
+    )]
+    IncompatibleServer {
+        /// Protocol version the daemon reported
+        server: u32,
+        /// Versions this client supports
+        supported: RangeInclusive<u32>,
+    },
+
+    /// A list kept changing while it was fetched page by page
+    #[error("List changed from {before} to {after} items while fetching its pages")]
+    ListChanged {
+        /// Items the first page reported
+        before: u64,
+        /// Items a later page reported
+        after: u64,
+    },
+
+    /// Hosts had not finished their operation when waiting gave up
+    #[error("Timed out after {waited:?} waiting for {}", hosts.join(", "))]
+    WaitTimeout {
+        /// Hosts that were still busy
+        hosts: Vec<String>,
+        /// How long was waited
+        waited: std::time::Duration,
+    },
+}
+
+/// `version 1` or `versions 1 to 3`
+fn version_range(range: &RangeInclusive<u32>) -> String {
+    if range.start() == range.end() {
+        format!("version {}", range.start())
+    } else {
+        format!("versions {} to {}", range.start(), range.end())
+    }
 }
 
 /// Result type for client operations
@@ -1,26 +1,69 @@
 //! HTTP client for tendhost daemon
 
-use reqwest::Client;
+use std::time::Duration;
+
+use futures::{Stream, StreamExt, TryStreamExt, stream};
+use reqwest::{Client, RequestBuilder, Response};
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use tracing::debug;
 use url::Url;
 
 use tendhost_api::{
-    requests::{FleetUpdateRequest, UpdateRequest},
-    responses::{HealthResponse, PaginatedResponse},
+    events::EventEnvelope,
+    pagination::{MAX_PER_PAGE, PageParams, Paginated},
+    requests::{FleetUpdateRequest, ImportMode, RegisterHostRequest, UpdateRequest, UpdateScope},
+    responses::{
+        AuditEntry, BulkRegisterReport, CommandHistoryEntry, ConfigValidationReport,
+        FleetDryRunReport, FleetSummary, HealthResponse, HostDetail, HostInventoryResponse,
+        HostListResponse, HostSummary, ImportReport, OsqueryInstallResponse, StateTransitionInfo,
+        UpdateAccepted, UpdateHistoryEntry, UpdatePreview, UpdateResultInfo,
+    },
+    version::API_VERSION_HEADER,
 };
 
 use crate::error::{ClientError, Result};
+use crate::retry::{RetryPolicy, is_retryable, is_retryable_page, retry_after};
+use crate::version::VersionCheck;
+
+/// Default overall timeout for a single request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Default timeout for establishing a connection
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Pages [`ListHostsBuilder::all`] fetches at the same time
+pub const PAGES_IN_FLIGHT: usize = 4;
 
 /// HTTP client for communicating with tendhost daemon
 #[derive(Debug, Clone)]
 pub struct HttpClient {
     client: Client,
     base_url: Url,
+    retry: RetryPolicy,
+    version_check: VersionCheck,
+}
+
+/// A failed attempt, with the delay the server asked for before the next
+struct Failure {
+    error: ClientError,
+    retry_after: Option<Duration>,
+}
+
+impl From<ClientError> for Failure {
+    fn from(error: ClientError) -> Self {
+        Self {
+            error,
+            retry_after: None,
+        }
+    }
 }
 
 impl HttpClient {
-    /// Create a new HTTP client
+    /// Create a new HTTP client with default timeouts and retries
+    ///
+    /// Requests time out after 30 seconds; idempotent requests are retried
+    /// twice. Use [`HttpClient::builder`] to change either.
     ///
     /// # Errors
     /// Returns an error if the base URL is invalid.
@@ -33,20 +76,41 @@ impl HttpClient {
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
     pub fn new(base_url: impl AsRef<str>) -> Result<Self> {
-        let base_url = Url::parse(base_url.as_ref())?;
-        Ok(Self {
-            client: Client::new(),
-            base_url,
-        })
+        Self::builder(base_url).build()
+    }
+
+    /// Start configuring a client
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use tendhost_client::HttpClient;
+    ///
+    /// let client = HttpClient::builder("http://localhost:8080")
+    ///     .timeout(Duration::from_secs(10))
+    ///     .retries(3)
+    ///     .build()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn builder(base_url: impl AsRef<str>) -> HttpClientBuilder {
+        HttpClientBuilder::new(base_url.as_ref())
     }
 
     /// Create a new HTTP client with custom `reqwest::Client`
     ///
+    /// Timeouts are whatever `client` was built with; the default retry
+    /// policy and version check apply.
+    ///
     /// # Errors
     /// Returns an error if the base URL is invalid.
     pub fn with_client(base_url: impl AsRef<str>, client: Client) -> Result<Self> {
         let base_url = Url::parse(base_url.as_ref())?;
-        Ok(Self { client, base_url })
+        Ok(Self {
+            client,
+            base_url,
+            retry: RetryPolicy::default(),
+            version_check: VersionCheck::default(),
+        })
     }
 
     /// Build a full URL from a path
@@ -54,36 +118,157 @@ impl HttpClient {
         self.base_url.join(path).map_err(ClientError::Url)
     }
 
-    /// Perform a GET request and deserialize the response
-    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
-        let url = self.url(path)?;
-        let response = self.client.get(url).send().await?;
+    /// Whether this client checks the daemon's protocol version
+    pub(crate) fn version_check(&self) -> VersionCheck {
+        self.version_check
+    }
+
+    /// URL of the daemon's event stream, on the same host as the API
+    pub(crate) fn ws_url(&self) -> Result<Url> {
+        let mut url = self.url("/ws/events")?;
+        let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+        url.set_scheme(scheme).map_err(|()| {
+            ClientError::InvalidResponse(format!("cannot derive event stream URL from {url}"))
+        })?;
+        Ok(url)
+    }
+
+    /// Send a request, retrying transient failures if it is idempotent
+    ///
+    /// Non-success statuses become [`ClientError::Api`], and replies from a
+    /// daemon with an unsupported protocol version
+    /// [`ClientError::IncompatibleServer`]. When retries were made, the
+    /// final error is wrapped in [`ClientError::RetriesExhausted`].
+    async fn execute(&self, request: RequestBuilder, idempotent: bool) -> Result<Response> {
+        self.execute_retrying(request, idempotent.then_some(is_retryable))
+            .await
+    }
+
+    /// Send a request, retrying the failures `retryable` accepts
+    ///
+    /// Without `retryable` the request goes out exactly once.
+    async fn execute_retrying(
+        &self,
+        mut request: RequestBuilder,
+        retryable: Option<fn(&ClientError) -> bool>,
+    ) -> Result<Response> {
+        let mut attempt = 1;
+
+        loop {
+            // Streaming bodies cannot be cloned, so those go out only once
+            let next = (retryable.is_some() && attempt <= self.retry.retries)
+                .then(|| request.try_clone())
+                .flatten();
+
+            let Failure { error, retry_after } = match self.send_once(request).await {
+                Ok(response) => return Ok(response),
+                Err(failure) => failure,
+            };
+
+            match (next, retryable) {
+                (Some(next), Some(retryable)) if retryable(&error) => {
+                    let delay = self.retry.delay(attempt, retry_after);
+                    debug!(attempt, error = %error, delay_ms = delay.as_millis(), "retrying request");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    request = next;
+                }
+                _ if attempt > 1 => {
+                    return Err(ClientError::RetriesExhausted {
+                        attempts: attempt,
+                        source: Box::new(error),
+                    });
+                }
+                _ => return Err(error),
+            }
+        }
+    }
+
+    async fn send_once(&self, request: RequestBuilder) -> std::result::Result<Response, Failure> {
+        let response = request.send().await.map_err(|e| {
+            if e.is_timeout() {
+                ClientError::Timeout
+            } else {
+                ClientError::Http(e)
+            }
+        })?;
+
+        // Every reply is checked, so a daemon upgraded while a long-running
+        // client such as the TUI is open is caught as well
+        let version = response.headers().get(API_VERSION_HEADER);
+        self.version_check
+            .check(version.map(reqwest::header::HeaderValue::as_bytes))?;
 
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
+        // Only conditional requests get `304`, and they read it themselves
+        let status = response.status();
+        if !status.is_success() && status != reqwest::StatusCode::NOT_MODIFIED {
+            let retry_after = retry_after(
+                response
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .map(reqwest::header::HeaderValue::as_bytes),
+            );
+            let status = status.as_u16();
             let message = response.text().await.unwrap_or_default();
-            return Err(ClientError::Api { status, message });
+            return Err(Failure {
+                error: ClientError::Api { status, message },
+                retry_after,
+            });
         }
 
+        Ok(response)
+    }
+
+    /// Perform a GET request and deserialize the response
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let url = self.url(path)?;
+        let response = self.execute(self.client.get(url), true).await?;
         Ok(response.json().await?)
     }
 
-    /// Perform a POST request with JSON body
+    /// Perform a GET request and return the raw response body
+    async fn get_text(&self, path: &str) -> Result<String> {
+        let url = self.url(path)?;
+        let response = self.execute(self.client.get(url), true).await?;
+        Ok(response.text().await?)
+    }
+
+    /// Perform a POST request with JSON body, sent exactly once
     async fn post<T: DeserializeOwned>(
         &self,
         path: &str,
         body: impl serde::Serialize,
+    ) -> Result<T> {
+        self.post_with(path, body, false).await
+    }
+
+    /// Perform a POST request the daemon treats as idempotent
+    async fn post_idempotent<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: impl serde::Serialize,
+    ) -> Result<T> {
+        self.post_with(path, body, true).await
+    }
+
+    async fn post_with<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: impl serde::Serialize,
+        idempotent: bool,
     ) -> Result<T> {
         let url = self.url(path)?;
-        let response = self.client.post(url).json(&body).send().await?;
+        let response = self
+            .execute(self.client.post(url).json(&body), idempotent)
+            .await?;
 
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let message = response.text().await.unwrap_or_default();
-            return Err(ClientError::Api { status, message });
+        // Action endpoints answer `202 Accepted` without a body
+        let bytes = response.bytes().await?;
+        if bytes.is_empty() {
+            return Ok(serde_json::from_str("null")?);
         }
 
-        Ok(response.json().await?)
+        Ok(serde_json::from_slice(&bytes)?)
     }
 
     /// Perform a PATCH request with JSON body
@@ -93,28 +278,16 @@ impl HttpClient {
         body: impl serde::Serialize,
     ) -> Result<T> {
         let url = self.url(path)?;
-        let response = self.client.patch(url).json(&body).send().await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let message = response.text().await.unwrap_or_default();
-            return Err(ClientError::Api { status, message });
-        }
-
+        let response = self
+            .execute(self.client.patch(url).json(&body), false)
+            .await?;
         Ok(response.json().await?)
     }
 
     /// Perform a DELETE request
     async fn delete(&self, path: &str) -> Result<()> {
         let url = self.url(path)?;
-        let response = self.client.delete(url).send().await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let message = response.text().await.unwrap_or_default();
-            return Err(ClientError::Api { status, message });
-        }
-
+        self.execute(self.client.delete(url), false).await?;
         Ok(())
     }
 
@@ -167,6 +340,9 @@ impl HttpClient {
 
     /// Get a single host by name
     ///
+    /// Combines the host's live status with its configuration, the summary
+    /// of its last inventory and its pending packages.
+    ///
     /// # Errors
     /// Returns an error if the request fails or the daemon returns an error.
     ///
@@ -176,10 +352,11 @@ impl HttpClient {
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let client = HttpClient::new("http://localhost:8080")?;
     /// let host = client.get_host("debian-vm").await?;
+    /// println!("{} is {}", host.name, host.state);
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn get_host(&self, name: &str) -> Result<Value> {
+    pub async fn get_host(&self, name: &str) -> Result<HostDetail> {
         self.get(&format!("/hosts/{name}")).await
     }
 
@@ -207,6 +384,121 @@ impl HttpClient {
         self.post("/hosts", config).await
     }
 
+    /// Register a host from a typed request
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error,
+    /// e.g. `409` when the host already exists.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # use tendhost_api::requests::RegisterHostRequest;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let request = RegisterHostRequest {
+    ///     name: "nas".to_string(),
+    ///     addr: "192.168.1.10".to_string(),
+    ///     port: Some(2222),
+    ///     user: "admin".to_string(),
+    ///     ssh_key: None,
+    ///     connect_timeout_secs: None,
+    ///     jump_host: None,
+    ///     tags: vec!["storage".to_string()],
+    /// };
+    /// client.register_host(&request).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn register_host(&self, request: &RegisterHostRequest) -> Result<()> {
+        let _: Value = self.post("/hosts", request).await?;
+        Ok(())
+    }
+
+    /// Register several hosts in one request
+    ///
+    /// Hosts are registered independently, so a successful call can still
+    /// contain failures: check [`BulkRegisterReport::failed`] or each
+    /// result's status. Hosts that already exist are left unchanged.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error,
+    /// e.g. `400` when `hosts` names a host twice; nothing is registered then.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # use tendhost_api::requests::RegisterHostRequest;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let hosts: Vec<RegisterHostRequest> = (1..=3)
+    ///     .map(|i| RegisterHostRequest {
+    ///         name: format!("web-{i}"),
+    ///         addr: format!("192.168.1.{}", 20 + i),
+    ///         port: None,
+    ///         user: "root".to_string(),
+    ///         ssh_key: None,
+    ///         connect_timeout_secs: None,
+    ///         jump_host: None,
+    ///         tags: vec!["web".to_string()],
+    ///     })
+    ///     .collect();
+    /// let report = client.create_hosts(&hosts).await?;
+    /// for result in report.results.iter().filter(|r| !r.exists()) {
+    ///     eprintln!("{}: {}", result.name, result.error.as_deref().unwrap_or("failed"));
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn create_hosts(&self, hosts: &[RegisterHostRequest]) -> Result<BulkRegisterReport> {
+        self.post("/hosts/bulk", hosts).await
+    }
+
+    /// Export every registered host as `[[host]]` TOML tables
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    pub async fn export_hosts(&self) -> Result<String> {
+        self.get_text("/hosts/export").await
+    }
+
+    /// Import hosts from a TOML document as written by [`export_hosts`]
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon rejects the
+    /// document, e.g. `409` when a replace would unregister a busy host.
+    ///
+    /// [`export_hosts`]: HttpClient::export_hosts
+    pub async fn import_hosts(&self, toml: &str, mode: ImportMode) -> Result<ImportReport> {
+        let url = self.url(&format!("/hosts/import?mode={mode}"))?;
+        let request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/toml")
+            .body(toml.to_string());
+        let response = self.execute(request, false).await?;
+        Ok(response.json().await?)
+    }
+
+    /// Check a config file on the daemon without applying it
+    ///
+    /// Without `toml` the daemon checks its own config file. An invalid
+    /// file is not an error; its problems are listed in the report.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails, or `400` when there is no
+    /// `toml` and the daemon has no config file.
+    pub async fn validate_config(&self, toml: Option<&str>) -> Result<ConfigValidationReport> {
+        let url = self.url("/system/config/validate")?;
+        let request = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/toml")
+            .body(toml.unwrap_or_default().to_string());
+        let response = self.execute(request, false).await?;
+        Ok(response.json().await?)
+    }
+
     /// Update host configuration
     ///
     /// # Errors
@@ -223,7 +515,7 @@ impl HttpClient {
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn update_host(&self, name: &str, config: Value) -> Result<Value> {
+    pub async fn update_host(&self, name: &str, config: Value) -> Result<HostDetail> {
         self.patch(&format!("/hosts/{name}"), config).await
     }
 
@@ -245,8 +537,100 @@ impl HttpClient {
         self.delete(&format!("/hosts/{name}")).await
     }
 
+    /// Start an update on a host as described by `request`
+    ///
+    /// The other update methods are shorthands for this one; use it to set
+    /// several options at once, or `force` to update a paused host.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error,
+    /// e.g. a 409 for a paused host without `force`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # use tendhost_api::requests::UpdateRequest;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let request = UpdateRequest {
+    ///     dry_run: false,
+    ///     scope: None,
+    ///     stack: None,
+    ///     packages: Vec::new(),
+    ///     force: true,
+    ///     skip_cleanup: false,
+    /// };
+    /// client.start_update("debian-vm", &request).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn start_update(
+        &self,
+        name: &str,
+        request: &UpdateRequest,
+    ) -> Result<UpdateAccepted> {
+        self.post(&format!("/hosts/{name}/update"), request).await
+    }
+
+    /// Update a host and wait for the result
+    ///
+    /// Unlike [`start_update`](Self::start_update), the daemon answers only
+    /// once the update is done, however long that takes.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails, the daemon returns an error,
+    /// or the update itself fails.
+    pub async fn run_update(
+        &self,
+        name: &str,
+        request: &UpdateRequest,
+    ) -> Result<UpdateResultInfo> {
+        self.post(&format!("/hosts/{name}/update?wait=true"), request)
+            .await
+    }
+
+    /// Preview what an update of a host would do, without changing it
+    ///
+    /// The host simulates the upgrade and reports what would stop a real
+    /// update right now. Unlike a dry run through
+    /// [`update_host_packages`](Self::update_host_packages), nothing is
+    /// recorded and no events are sent. `scope` defaults to the host
+    /// policy's.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails, the host is updating or
+    /// rebooting, or the simulation fails.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let preview = client.preview_update("debian-vm", None).await?;
+    /// for blocker in &preview.blockers {
+    ///     println!("would be refused: {blocker}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn preview_update(
+        &self,
+        name: &str,
+        scope: Option<UpdateScope>,
+    ) -> Result<UpdatePreview> {
+        let path = match scope {
+            Some(scope) => format!("/hosts/{name}/update-preview?scope={scope}"),
+            None => format!("/hosts/{name}/update-preview"),
+        };
+        self.get(&path).await
+    }
+
     /// Trigger package update on a host
     ///
+    /// A dry run goes through the update path and shows up in the host's
+    /// update history; [`preview_update`](Self::preview_update) leaves the
+    /// host untouched.
+    ///
     /// # Errors
     /// Returns an error if the request fails or the daemon returns an error.
     ///
@@ -255,16 +639,102 @@ impl HttpClient {
     /// # use tendhost_client::HttpClient;
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let client = HttpClient::new("http://localhost:8080")?;
-    /// // Dry run
-    /// let result = client.update_host_packages("debian-vm", true).await?;
-    /// // Actual update
     /// let result = client.update_host_packages("debian-vm", false).await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn update_host_packages(&self, name: &str, dry_run: bool) -> Result<Value> {
-        let request = UpdateRequest { dry_run };
-        self.post(&format!("/hosts/{name}/update"), request).await
+    pub async fn update_host_packages(&self, name: &str, dry_run: bool) -> Result<UpdateAccepted> {
+        let request = UpdateRequest {
+            dry_run,
+            scope: None,
+            stack: None,
+            packages: Vec::new(),
+            force: false,
+            skip_cleanup: false,
+        };
+        self.start_update(name, &request).await
+    }
+
+    /// Upgrade only the named packages on a host
+    ///
+    /// Each name must be among the host's upgradable packages.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or a package is not upgradable.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let packages = vec!["openssl".to_string(), "curl".to_string()];
+    /// client.update_selected_packages("debian-vm", &packages).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn update_selected_packages(
+        &self,
+        name: &str,
+        packages: &[String],
+    ) -> Result<UpdateAccepted> {
+        let request = UpdateRequest {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            packages: packages.to_vec(),
+            force: false,
+            skip_cleanup: false,
+        };
+        self.start_update(name, &request).await
+    }
+
+    /// Update a single docker compose stack on a host
+    ///
+    /// Only that stack's images are pulled and its containers recreated.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the stack is unknown.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// client.update_host_stack("debian-vm", "monitoring").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn update_host_stack(&self, name: &str, stack: &str) -> Result<UpdateAccepted> {
+        let request = UpdateRequest {
+            dry_run: false,
+            scope: None,
+            stack: Some(stack.to_string()),
+            packages: Vec::new(),
+            force: false,
+            skip_cleanup: false,
+        };
+        self.start_update(name, &request).await
+    }
+
+    /// Cancel a running package update on a host
+    ///
+    /// The host moves to the failed state and can be retried afterwards.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the host is not updating.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// client.cancel_host_update("debian-vm").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn cancel_host_update(&self, name: &str) -> Result<Value> {
+        self.post(&format!("/hosts/{name}/cancel"), serde_json::json!({}))
+            .await
     }
 
     /// Trigger host reboot
@@ -288,6 +758,8 @@ impl HttpClient {
 
     /// Retry a failed host
     ///
+    /// Retried on transient failures like other idempotent requests.
+    ///
     /// # Errors
     /// Returns an error if the request fails or the daemon returns an error.
     ///
@@ -301,12 +773,14 @@ impl HttpClient {
     /// # }
     /// ```
     pub async fn retry_host(&self, name: &str) -> Result<Value> {
-        self.post(&format!("/hosts/{name}/retry"), serde_json::json!({}))
+        self.post_idempotent(&format!("/hosts/{name}/retry"), serde_json::json!({}))
             .await
     }
 
     /// Acknowledge a host failure
     ///
+    /// Retried on transient failures like other idempotent requests.
+    ///
     /// # Errors
     /// Returns an error if the request fails or the daemon returns an error.
     ///
@@ -320,163 +794,947 @@ impl HttpClient {
     /// # }
     /// ```
     pub async fn acknowledge_host(&self, name: &str) -> Result<Value> {
-        self.post(&format!("/hosts/{name}/acknowledge"), serde_json::json!({}))
+        self.post_idempotent(&format!("/hosts/{name}/acknowledge"), serde_json::json!({}))
             .await
     }
 
-    /// Get full osquery inventory for a host
+    /// Pause a host so automation leaves it alone
+    ///
+    /// Scheduled and fleet updates skip a paused host, and manual updates
+    /// need `force` (see [`start_update`](Self::start_update)). Retried on
+    /// transient failures like other idempotent requests.
     ///
     /// # Errors
-    /// Returns an error if the request fails or the daemon returns an error.
+    /// Returns an error if the request fails or the host is not found.
     ///
     /// # Example
     /// ```no_run
     /// # use tendhost_client::HttpClient;
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let client = HttpClient::new("http://localhost:8080")?;
-    /// let inventory = client.get_host_inventory("debian-vm").await?;
+    /// client.pause_host("debian-vm").await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn get_host_inventory(&self, name: &str) -> Result<Value> {
-        self.get(&format!("/hosts/{name}/inventory")).await
+    pub async fn pause_host(&self, name: &str) -> Result<Value> {
+        self.post_idempotent(&format!("/hosts/{name}/pause"), serde_json::json!({}))
+            .await
     }
 
-    // Fleet endpoints
+    /// Install osquery on a host
+    ///
+    /// The daemon only does this with `allow_remote_install` set. Not
+    /// retried, since the install may still be running.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails, remote installs are disabled
+    /// or the install fails.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let install = client.install_osquery("debian-vm").await?;
+    /// println!("osquery {:?}", install.version);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn install_osquery(&self, name: &str) -> Result<OsqueryInstallResponse> {
+        self.post(
+            &format!("/hosts/{name}/install-osquery"),
+            serde_json::json!({}),
+        )
+        .await
+    }
 
-    /// Trigger fleet-wide update
+    /// Resume a paused host
+    ///
+    /// Retried on transient failures like other idempotent requests.
     ///
     /// # Errors
-    /// Returns an error if the request fails or the daemon returns an error.
+    /// Returns an error if the request fails or the host is not found.
     ///
     /// # Example
     /// ```no_run
     /// # use tendhost_client::HttpClient;
-    /// # use tendhost_api::requests::{FleetUpdateRequest, FleetUpdateFilter};
     /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
     /// let client = HttpClient::new("http://localhost:8080")?;
-    /// let request = FleetUpdateRequest {
-    ///     batch_size: 5,
-    ///     delay_ms: 5000,
-    ///     filter: Some(FleetUpdateFilter {
-    ///         tags: Some(vec!["production".into()]),
-    ///         groups: None,
-    ///         exclude_hosts: None,
-    ///     }),
-    /// };
-    /// let result = client.update_fleet(request).await?;
+    /// client.resume_host("debian-vm").await?;
     /// # Ok(())
     /// # }
     /// ```
-    pub async fn update_fleet(&self, request: FleetUpdateRequest) -> Result<Value> {
-        self.post("/fleet/update", request).await
+    pub async fn resume_host(&self, name: &str) -> Result<Value> {
+        self.post_idempotent(&format!("/hosts/{name}/resume"), serde_json::json!({}))
+            .await
     }
-}
 
-/// Builder for listing hosts with filters
-#[derive(Debug, Clone)]
-pub struct ListHostsBuilder {
-    client: HttpClient,
-    page: Option<u64>,
-    per_page: Option<u64>,
-    tags: Vec<String>,
-    state: Option<String>,
-    group: Option<String>,
-    search: Option<String>,
-}
+    /// Get the osquery inventory of a host
+    ///
+    /// The whole inventory is returned unless the builder selects sections
+    /// or asks for a package summary.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let inventory = client.get_host_inventory("debian-vm")
+    ///     .include("system")
+    ///     .include("packages")
+    ///     .packages_summary()
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn get_host_inventory(&self, name: &str) -> InventoryBuilder {
+        InventoryBuilder::new(self.clone(), name)
+    }
+
+    /// Get a host's inventory after refreshing its package lists
+    ///
+    /// Unlike [`get_host_inventory`](Self::get_host_inventory), recently
+    /// refreshed lists are not reused.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    pub async fn refresh_host_inventory(&self, name: &str) -> Result<HostInventoryResponse> {
+        self.get_host_inventory(name).refresh().send().await
+    }
+
+    /// Get what changed between a host's last two inventory collections
+    ///
+    /// `diff` is `null` until the inventory has been collected twice.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let diff = client.get_inventory_diff("debian-vm").await?;
+    /// println!("{}", diff["summary"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_inventory_diff(&self, name: &str) -> Result<Value> {
+        self.get(&format!("/hosts/{name}/inventory/diff")).await
+    }
+
+    /// Get the commands recently run on a host, oldest first
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// for entry in client.command_history("debian-vm").await? {
+    ///     println!("{} {}", entry.started_at, entry.command);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn command_history(&self, name: &str) -> Result<Vec<CommandHistoryEntry>> {
+        self.get(&format!("/hosts/{name}/commands")).await
+    }
+
+    /// Get a host's finished and failed updates, newest first
+    ///
+    /// The daemon returns 20 records unless `limit` says otherwise.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// for run in client.get_update_history("debian-vm", Some(5)).await? {
+    ///     println!("{} upgraded {}", run.timestamp, run.upgraded_count);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_update_history(
+        &self,
+        name: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<UpdateHistoryEntry>> {
+        let path = match limit {
+            Some(limit) => format!("/hosts/{name}/updates?limit={limit}"),
+            None => format!("/hosts/{name}/updates"),
+        };
+        self.get(&path).await
+    }
+
+    /// Get a host's recorded state transitions, oldest first
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// for t in client.get_transitions("debian-vm").await? {
+    ///     println!("{} {} -> {} ({})", t.at, t.from, t.to, t.trigger);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_transitions(&self, name: &str) -> Result<Vec<StateTransitionInfo>> {
+        self.get(&format!("/hosts/{name}/transitions")).await
+    }
+
+    // Fleet endpoints
+
+    /// Trigger fleet-wide update
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # use tendhost_api::requests::{FleetUpdateRequest, FleetUpdateFilter};
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let request = FleetUpdateRequest {
+    ///     batch_size: 5,
+    ///     delay_ms: 5000,
+    ///     scope: None,
+    ///     filter: Some(FleetUpdateFilter {
+    ///         tags: Some(vec!["production".into()]),
+    ///         groups: None,
+    ///         exclude_hosts: None,
+    ///         facts: None,
+    ///     }),
+    ///     dry_run: false,
+    ///     canary_hosts: vec!["staging-web".into()],
+    ///     halt_on_canary_failure: true,
+    /// };
+    /// let result = client.update_fleet(request).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn update_fleet(&self, request: FleetUpdateRequest) -> Result<Value> {
+        self.post("/fleet/update", request).await
+    }
+
+    /// Report what a fleet update would change without updating anything
+    ///
+    /// Every matching host is queried and its update simulated; the call
+    /// returns once all hosts have been checked.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # use tendhost_api::requests::FleetUpdateRequest;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let request = FleetUpdateRequest {
+    ///     batch_size: 5,
+    ///     delay_ms: 0,
+    ///     scope: None,
+    ///     filter: None,
+    ///     dry_run: true,
+    ///     canary_hosts: Vec::new(),
+    ///     halt_on_canary_failure: true,
+    /// };
+    /// let report = client.fleet_dry_run(request).await?;
+    /// println!("{} updates on {} hosts", report.total_updates, report.hosts_with_updates);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fleet_dry_run(&self, request: FleetUpdateRequest) -> Result<FleetDryRunReport> {
+        let request = FleetUpdateRequest {
+            dry_run: true,
+            ..request
+        };
+        self.post("/fleet/update", request).await
+    }
+
+    /// Get hosts per state, pending updates and failures across the fleet
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let summary = client.fleet_status().await?;
+    /// println!(
+    ///     "{} of {} hosts idle, {} updates pending",
+    ///     summary.count("idle"),
+    ///     summary.total_hosts,
+    ///     summary.pending_updates
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn fleet_status(&self) -> Result<FleetSummary> {
+        self.get("/fleet/status").await
+    }
+
+    /// Fetch the installed packages report as `csv` or `json` text
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let csv = client.packages_report("csv").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn packages_report(&self, format: &str) -> Result<String> {
+        self.get_text(&format!("/reports/packages?format={format}"))
+            .await
+    }
+
+    /// Fetch the pending updates report as `csv` or `json` text
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    pub async fn updates_report(&self, format: &str) -> Result<String> {
+        self.get_text(&format!("/reports/updates?format={format}"))
+            .await
+    }
+
+    /// Read recent audit log entries, newest first
+    ///
+    /// `since` is an RFC 3339 timestamp.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let entries = client.audit_log(Some("debian-vm"), None, Some(20)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn audit_log(
+        &self,
+        host: Option<&str>,
+        since: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<Vec<AuditEntry>> {
+        let mut query = url::form_urlencoded::Serializer::new(String::new());
+        if let Some(host) = host {
+            query.append_pair("host", host);
+        }
+        if let Some(since) = since {
+            query.append_pair("since", since);
+        }
+        if let Some(limit) = limit {
+            query.append_pair("limit", &limit.to_string());
+        }
+        let query = query.finish();
+
+        if query.is_empty() {
+            self.get("/audit").await
+        } else {
+            self.get(&format!("/audit?{query}")).await
+        }
+    }
+
+    /// Read a page of recent events, oldest first
+    ///
+    /// Pass the returned `next_cursor` back with [`PageParams::after`] to
+    /// read the events delivered since.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_api::pagination::PageParams;
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let page = client.list_events(&PageParams::default()).await?;
+    /// for envelope in &page.data {
+    ///     println!("{}: {:?}", envelope.seq, envelope.event);
+    /// }
+    /// if let Some(cursor) = page.pagination.next_cursor {
+    ///     let newer = client.list_events(&PageParams::after(cursor)).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn list_events(&self, params: &PageParams) -> Result<Paginated<EventEnvelope>> {
+        // Build the query in its own scope: the serializer isn't `Send`
+        let path = {
+            let mut query = url::form_urlencoded::Serializer::new(String::new());
+            query.append_pair("per_page", &params.per_page.to_string());
+            if let Some(cursor) = params.cursor {
+                query.append_pair("cursor", &cursor.to_string());
+            }
+            format!("/events?{}", query.finish())
+        };
+        self.get(&path).await
+    }
+
+    /// Get the last `per_host` events of each host, oldest first
+    ///
+    /// Call it right after connecting a [`WsClient`](crate::WsClient) to
+    /// catch up on events missed while not subscribed; stream events with a
+    /// `seq` already replayed are duplicates.
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or `per_host` is out of range
+    pub async fn recent_events(&self, per_host: usize) -> Result<Vec<EventEnvelope>> {
+        self.get(&format!("/events/recent?per_host={per_host}"))
+            .await
+    }
+}
+
+/// Builder for [`HttpClient`] timeouts and retries
+#[derive(Debug, Clone)]
+pub struct HttpClientBuilder {
+    base_url: String,
+    timeout: Duration,
+    connect_timeout: Duration,
+    retry: RetryPolicy,
+    version_check: VersionCheck,
+}
+
+impl HttpClientBuilder {
+    fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            timeout: DEFAULT_TIMEOUT,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            retry: RetryPolicy::default(),
+            version_check: VersionCheck::default(),
+        }
+    }
+
+    /// Overall timeout for each attempt, including reading the body (default: 30s)
+    #[must_use]
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Timeout for establishing a connection (default: 5s)
+    #[must_use]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Retries for idempotent requests after the first attempt (default: 2)
+    #[must_use]
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retry.retries = retries;
+        self
+    }
+
+    /// Delay before the first retry and the cap for later ones
+    ///
+    /// The cap also bounds how long a `Retry-After` reply is waited for.
+    #[must_use]
+    pub fn retry_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.retry.initial_backoff = initial;
+        self.retry.max_backoff = max;
+        self
+    }
+
+    /// Replace the whole retry policy
+    #[must_use]
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Accept replies from daemons of any protocol version
+    ///
+    /// Replies of an unsupported version may be misread; only use this
+    /// when the differences are known not to matter.
+    #[must_use]
+    pub fn ignore_api_version(mut self) -> Self {
+        self.version_check = VersionCheck::Ignore;
+        self
+    }
+
+    /// Build the client
+    ///
+    /// # Errors
+    /// Returns an error if the base URL is invalid or the HTTP client cannot
+    /// be initialized.
+    pub fn build(self) -> Result<HttpClient> {
+        let base_url = Url::parse(&self.base_url)?;
+        let client = Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout)
+            .build()?;
+        Ok(HttpClient {
+            client,
+            base_url,
+            retry: self.retry,
+            version_check: self.version_check,
+        })
+    }
+}
+
+/// Filters and paging for `GET /hosts`; unset fields use the daemon's defaults
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HostListQuery {
+    /// Page number, from 1
+    pub page: Option<u64>,
+    /// Items per page
+    pub per_page: Option<u64>,
+    /// Tags every host must have
+    pub tags: Vec<String>,
+    /// Facts every host must have, as `key:value`
+    pub facts: Vec<String>,
+    /// State, e.g. `idle`
+    pub state: Option<String>,
+    /// Group name
+    pub group: Option<String>,
+    /// Hostname prefix
+    pub search: Option<String>,
+    /// Only failed hosts whose failure has or hasn't been acknowledged
+    pub acknowledged: Option<bool>,
+    /// Sort field
+    pub sort: Option<String>,
+    /// `asc` or `desc`
+    pub order: Option<String>,
+}
+
+impl HostListQuery {
+    /// Add the filters as query parameters to `url`
+    fn append_to(&self, url: &mut Url) {
+        let mut query = url.query_pairs_mut();
+        if let Some(page) = self.page {
+            query.append_pair("page", &page.to_string());
+        }
+        if let Some(per_page) = self.per_page {
+            query.append_pair("per_page", &per_page.to_string());
+        }
+        if !self.tags.is_empty() {
+            query.append_pair("tags", &self.tags.join(","));
+        }
+        if !self.facts.is_empty() {
+            query.append_pair("fact", &self.facts.join(","));
+        }
+        if let Some(state) = &self.state {
+            query.append_pair("state", state);
+        }
+        if let Some(group) = &self.group {
+            query.append_pair("group", group);
+        }
+        if let Some(search) = &self.search {
+            query.append_pair("search", search);
+        }
+        if let Some(acknowledged) = self.acknowledged {
+            query.append_pair("acknowledged", &acknowledged.to_string());
+        }
+        if let Some(sort) = &self.sort {
+            query.append_pair("sort", sort);
+        }
+        if let Some(order) = &self.order {
+            query.append_pair("order", order);
+        }
+    }
+}
+
+impl HttpClient {
+    /// List hosts matching `query`, as [`ListHostsBuilder::send`] does
+    pub(crate) async fn fetch_hosts(&self, query: &HostListQuery) -> Result<HostListResponse> {
+        let mut url = self.url("/hosts")?;
+        query.append_to(&mut url);
+        let response = self.execute(self.client.get(url), true).await?;
+        Ok(response.json().await?)
+    }
+
+    /// One page of a walk over the host list, retrying server errors too
+    async fn fetch_host_page(&self, query: &HostListQuery) -> Result<HostListResponse> {
+        let mut url = self.url("/hosts")?;
+        query.append_to(&mut url);
+        let response = self
+            .execute_retrying(self.client.get(url), Some(is_retryable_page))
+            .await?;
+        Ok(response.json().await?)
+    }
+}
+
+/// Builder for listing hosts with filters
+///
+/// [`send`](Self::send) fetches the list once. To poll, keep the builder
+/// and call [`poll`](Self::poll), which only returns the list when it
+/// changed since the previous call. [`all`](Self::all) and
+/// [`stream`](Self::stream) walk every page instead of returning one.
+#[derive(Debug, Clone)]
+pub struct ListHostsBuilder {
+    client: HttpClient,
+    query: HostListQuery,
+    /// `ETag` of the last list [`poll`](Self::poll) returned
+    etag: Option<String>,
+}
 
 impl ListHostsBuilder {
     fn new(client: HttpClient) -> Self {
         Self {
             client,
-            page: None,
-            per_page: None,
-            tags: Vec::new(),
-            state: None,
-            group: None,
-            search: None,
+            query: HostListQuery::default(),
+            etag: None,
         }
     }
 
     /// Set page number (default: 1)
     #[must_use]
     pub fn page(mut self, page: u64) -> Self {
-        self.page = Some(page);
+        self.query.page = Some(page);
         self
     }
 
     /// Set items per page (default: 50, max: 200)
     #[must_use]
     pub fn per_page(mut self, per_page: u64) -> Self {
-        self.per_page = Some(per_page);
+        self.query.per_page = Some(per_page);
         self
     }
 
-    /// Add a tag filter (repeatable for AND logic)
+    /// Add a tag filter (repeatable; hosts must have every tag)
     #[must_use]
     pub fn tag(mut self, tag: impl Into<String>) -> Self {
-        self.tags.push(tag.into());
+        self.query.tags.push(tag.into());
         self
     }
 
-    /// Filter by state (idle, updating, etc.)
+    /// Add a fact filter such as `os_id:debian` (repeatable; hosts must
+    /// have every fact)
+    #[must_use]
+    pub fn fact(mut self, fact: impl Into<String>) -> Self {
+        self.query.facts.push(fact.into());
+        self
+    }
+
+    /// Filter by state (`idle`, `pending_updates`, etc.)
     #[must_use]
     pub fn state(mut self, state: impl Into<String>) -> Self {
-        self.state = Some(state.into());
+        self.query.state = Some(state.into());
         self
     }
 
     /// Filter by group name
     #[must_use]
     pub fn group(mut self, group: impl Into<String>) -> Self {
-        self.group = Some(group.into());
+        self.query.group = Some(group.into());
         self
     }
 
     /// Search by hostname (prefix match)
     #[must_use]
     pub fn search(mut self, search: impl Into<String>) -> Self {
-        self.search = Some(search.into());
+        self.query.search = Some(search.into());
         self
     }
 
+    /// Only failed hosts whose failure has (`true`) or hasn't (`false`)
+    /// been acknowledged
+    #[must_use]
+    pub fn acknowledged(mut self, acknowledged: bool) -> Self {
+        self.query.acknowledged = Some(acknowledged);
+        self
+    }
+
+    /// Sort by `name` (default), `state`, `pending_updates` or `last_updated`
+    #[must_use]
+    pub fn sort(mut self, field: impl Into<String>) -> Self {
+        self.query.sort = Some(field.into());
+        self
+    }
+
+    /// Sort descending instead of ascending
+    #[must_use]
+    pub fn descending(mut self) -> Self {
+        self.query.order = Some("desc".to_string());
+        self
+    }
+
+    /// Build the request URL with all filters as query parameters
+    #[cfg(test)]
+    fn build_url(&self) -> Result<Url> {
+        let mut url = self.client.url("/hosts")?;
+        self.query.append_to(&mut url);
+        Ok(url)
+    }
+
+    /// The filters set so far
+    #[must_use]
+    pub fn query(&self) -> &HostListQuery {
+        &self.query
+    }
+
     /// Execute the request
     ///
     /// # Errors
     /// Returns an error if the request fails or the daemon returns an error.
-    pub async fn send(self) -> Result<PaginatedResponse<Value>> {
+    pub async fn send(self) -> Result<HostListResponse> {
+        self.client.fetch_hosts(&self.query).await
+    }
+
+    /// Fetch the list if it changed since the last call
+    ///
+    /// The daemon's `ETag` is remembered and sent back, so an unchanged
+    /// page costs an empty `304` and returns `None`. The first call always
+    /// returns the list.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let mut hosts = client.list_hosts().tag("production");
+    /// loop {
+    ///     if let Some(page) = hosts.poll().await? {
+    ///         println!("{} hosts", page.pagination.total_items);
+    ///     }
+    ///     tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    pub async fn poll(&mut self) -> Result<Option<HostListResponse>> {
         let mut url = self.client.url("/hosts")?;
+        self.query.append_to(&mut url);
+        let mut request = self.client.client.get(url);
+        if let Some(etag) = &self.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
 
-        {
-            let mut query = url.query_pairs_mut();
-            if let Some(page) = self.page {
-                query.append_pair("page", &page.to_string());
-            }
-            if let Some(per_page) = self.per_page {
-                query.append_pair("per_page", &per_page.to_string());
+        let response = self.client.execute(request, true).await?;
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            return Ok(None);
+        }
+        self.etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(ToString::to_string);
+        Ok(Some(response.json().await?))
+    }
+
+    /// Fetch every page and return all matching hosts
+    ///
+    /// The walk starts at page 1 whatever [`page`](Self::page) says, with
+    /// the builder's page size capped at [`MAX_PER_PAGE`], or that cap if
+    /// none was set. Once the first page tells how many there are, the
+    /// others are fetched [`PAGES_IN_FLIGHT`] at a time. Besides the
+    /// retries of every idempotent request, pages failing with a server
+    /// error are retried too.
+    ///
+    /// Hosts added or removed during the walk shift the others between
+    /// pages, so when a page reports a different total the walk starts
+    /// over once.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let hosts = client.list_hosts().tag("production").all().await?;
+    /// println!("{} production hosts", hosts.len());
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns the first error of any page, or
+    /// [`ClientError::ListChanged`] if the total changed again after
+    /// starting over.
+    pub async fn all(self) -> Result<Vec<HostSummary>> {
+        match self.walk().await {
+            Err(ClientError::ListChanged { before, after }) => {
+                debug!(
+                    before,
+                    after, "host list changed while paging, starting over"
+                );
+                self.walk().await
             }
-            for tag in &self.tags {
-                query.append_pair("tag", tag);
+            result => result,
+        }
+    }
+
+    /// Fetch every page once, failing if the total changes on the way
+    async fn walk(&self) -> Result<Vec<HostSummary>> {
+        let first = self.client.fetch_host_page(&self.walk_query(1)).await?;
+        let total = first.pagination.total_items;
+        let mut hosts = first.hosts;
+
+        let rest = (2..=first.pagination.total_pages).map(|page| {
+            let query = self.walk_query(page);
+            async move { self.client.fetch_host_page(&query).await }
+        });
+        let mut pages = stream::iter(rest).buffered(PAGES_IN_FLIGHT);
+        while let Some(page) = pages.next().await {
+            let page = page?;
+            if page.pagination.total_items != total {
+                return Err(ClientError::ListChanged {
+                    before: total,
+                    after: page.pagination.total_items,
+                });
             }
-            if let Some(state) = &self.state {
-                query.append_pair("state", state);
+            hosts.extend(page.hosts);
+        }
+        Ok(hosts)
+    }
+
+    /// The query for page `page` of a walk
+    fn walk_query(&self, page: u64) -> HostListQuery {
+        let per_page = self
+            .query
+            .per_page
+            .map_or(MAX_PER_PAGE, |n| n.clamp(1, MAX_PER_PAGE));
+        HostListQuery {
+            page: Some(page),
+            per_page: Some(per_page),
+            ..self.query.clone()
+        }
+    }
+
+    /// Stream all matching hosts, fetching a page when the previous one
+    /// has been consumed
+    ///
+    /// Pages are the same as for [`all`](Self::all), but only one is held
+    /// at a time, which suits very large fleets. Hosts yielded already
+    /// can't be taken back, so a list changing meanwhile is not detected:
+    /// a host may be skipped or seen twice. The stream ends after the
+    /// first error.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use futures::TryStreamExt;
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// let mut hosts = std::pin::pin!(client.list_hosts().state("failed").stream());
+    /// while let Some(host) = hosts.try_next().await? {
+    ///     println!("{}", host.name);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn stream(self) -> impl Stream<Item = Result<HostSummary>> + Send {
+        stream::try_unfold((self, Some(1)), |(builder, page)| async move {
+            let Some(page) = page else {
+                return Ok::<_, ClientError>(None);
+            };
+            let response = builder
+                .client
+                .fetch_host_page(&builder.walk_query(page))
+                .await?;
+            let next = (page < response.pagination.total_pages).then_some(page + 1);
+            Ok(Some((response.hosts, (builder, next))))
+        })
+        .map_ok(|hosts| stream::iter(hosts.into_iter().map(Ok)))
+        .try_flatten()
+    }
+}
+
+/// Builder for fetching a host's inventory
+#[derive(Debug, Clone)]
+pub struct InventoryBuilder {
+    client: HttpClient,
+    name: String,
+    refresh: bool,
+    include: Vec<String>,
+    packages_summary: bool,
+}
+
+impl InventoryBuilder {
+    fn new(client: HttpClient, name: &str) -> Self {
+        Self {
+            client,
+            name: name.to_string(),
+            refresh: false,
+            include: Vec::new(),
+            packages_summary: false,
+        }
+    }
+
+    /// Refresh the package lists first, even if they are recent
+    #[must_use]
+    pub fn refresh(mut self) -> Self {
+        self.refresh = true;
+        self
+    }
+
+    /// Return this section (repeatable): `system`, `hardware`, `packages`,
+    /// `docker_containers`, `docker_images`, `listening_ports` or
+    /// `services`; without any, every section is returned
+    #[must_use]
+    pub fn include(mut self, section: impl Into<String>) -> Self {
+        self.include.push(section.into());
+        self
+    }
+
+    /// Return only the name and version of each installed package
+    #[must_use]
+    pub fn packages_summary(mut self) -> Self {
+        self.packages_summary = true;
+        self
+    }
+
+    /// Build the request URL with the options as query parameters
+    fn build_url(&self) -> Result<Url> {
+        let mut url = self
+            .client
+            .url(&format!("/hosts/{}/inventory", self.name))?;
+        {
+            let mut query = url.query_pairs_mut();
+            if self.refresh {
+                query.append_pair("refresh", "true");
             }
-            if let Some(group) = &self.group {
-                query.append_pair("group", group);
+            if !self.include.is_empty() {
+                query.append_pair("include", &self.include.join(","));
             }
-            if let Some(search) = &self.search {
-                query.append_pair("search", search);
+            if self.packages_summary {
+                query.append_pair("packages", "summary");
             }
         }
-
-        let response = self.client.client.get(url).send().await?;
-
-        if !response.status().is_success() {
-            let status = response.status().as_u16();
-            let message = response.text().await.unwrap_or_default();
-            return Err(ClientError::Api { status, message });
+        if url.query() == Some("") {
+            url.set_query(None);
         }
+        Ok(url)
+    }
 
+    /// Execute the request
+    ///
+    /// # Errors
+    /// Returns an error if the request fails or the daemon returns an error.
+    pub async fn send(self) -> Result<HostInventoryResponse> {
+        let url = self.build_url()?;
+        let response = self
+            .client
+            .execute(self.client.client.get(url), true)
+            .await?;
         Ok(response.json().await?)
     }
 }
@@ -520,36 +1778,87 @@ mod tests {
     #[test]
     fn test_list_hosts_url_building() {
         let client = HttpClient::new("http://localhost:8080").unwrap();
-        let _builder = client
+        let builder = client
             .list_hosts()
             .page(2)
             .per_page(50)
             .tag("critical")
             .tag("production")
+            .fact("os_id:debian")
+            .fact("arch:x86_64")
             .state("idle")
             .group("webservers")
-            .search("web");
-
-        // Manually build URL to test
-        let mut url = client.url("/hosts").unwrap();
-        {
-            let mut query = url.query_pairs_mut();
-            query.append_pair("page", "2");
-            query.append_pair("per_page", "50");
-            query.append_pair("tag", "critical");
-            query.append_pair("tag", "production");
-            query.append_pair("state", "idle");
-            query.append_pair("group", "webservers");
-            query.append_pair("search", "web");
-        }
+            .search("web")
+            .acknowledged(false)
+            .sort("pending_updates")
+            .descending();
 
+        let url = builder.build_url().unwrap();
         let expected = url.as_str();
         assert!(expected.contains("page=2"));
         assert!(expected.contains("per_page=50"));
-        assert!(expected.contains("tag=critical"));
-        assert!(expected.contains("tag=production"));
+        assert!(expected.contains("tags=critical%2Cproduction"));
+        assert!(expected.contains("fact=os_id%3Adebian%2Carch%3Ax86_64"));
         assert!(expected.contains("state=idle"));
         assert!(expected.contains("group=webservers"));
         assert!(expected.contains("search=web"));
+        assert!(expected.contains("acknowledged=false"));
+        assert!(expected.contains("sort=pending_updates"));
+        assert!(expected.contains("order=desc"));
+    }
+
+    #[test]
+    fn test_inventory_url_building() {
+        let client = HttpClient::new("http://localhost:8080").unwrap();
+        let url = client.get_host_inventory("web-1").build_url().unwrap();
+        assert_eq!(url.as_str(), "http://localhost:8080/hosts/web-1/inventory");
+
+        let url = client
+            .get_host_inventory("web-1")
+            .refresh()
+            .include("system")
+            .include("packages")
+            .packages_summary()
+            .build_url()
+            .unwrap();
+        assert_eq!(
+            url.query(),
+            Some("refresh=true&include=system%2Cpackages&packages=summary")
+        );
+    }
+
+    #[test]
+    fn test_host_list_response_decodes() {
+        let body = serde_json::json!({
+            "hosts": [{
+                "name": "web-1",
+                "state": "Idle",
+                "os": "Debian GNU/Linux 12",
+                "pending_updates": 0,
+                "security_updates": 0,
+                "last_checked": "2026-03-01T12:00:00+00:00",
+                "tags": ["prod"],
+                "last_updated": null,
+                "error": null,
+                "reachable": true,
+                "last_seen": "2026-03-01T12:00:05+00:00",
+                "sudo_available": true,
+                "acknowledged": null,
+                "failed_at": null,
+                "retry_count": null
+            }],
+            "pagination": {"page": 1, "per_page": 50, "total_items": 1, "total_pages": 1},
+            "filters": {"tags": [], "sort": "name", "order": "asc"}
+        });
+
+        let page: HostListResponse = serde_json::from_value(body).unwrap();
+        let host = &page.hosts[0];
+        assert_eq!(host.os.as_deref(), Some("Debian GNU/Linux 12"));
+        assert_eq!(host.pending_updates, Some(0));
+        assert!(host.reachable);
+        assert_eq!(
+            host.last_checked.map(|t| t.to_rfc3339()).as_deref(),
+            Some("2026-03-01T12:00:00+00:00")
+        );
     }
 }
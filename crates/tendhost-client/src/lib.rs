@@ -44,7 +44,7 @@
 //!
 //! while let Some(event) = client.recv().await {
 //!     match event {
-//!         WsEvent::HostStateChanged { host, from, to } => {
+//!         WsEvent::HostStateChanged { host, from, to, .. } => {
 //!             println!("{host}: {from} -> {to}");
 //!         }
 //!         WsEvent::UpdateProgress { host, package, progress } => {
@@ -59,8 +59,20 @@
 
 pub mod error;
 pub mod http;
+#[cfg(feature = "test-util")]
+pub mod mock;
+pub mod retry;
+pub mod ssh_config;
+pub mod traits;
+pub mod version;
+pub mod wait;
 pub mod ws;
 
 pub use error::{ClientError, Result};
-pub use http::{HttpClient, ListHostsBuilder};
-pub use ws::WsClient;
+pub use http::{HostListQuery, HttpClient, HttpClientBuilder, InventoryBuilder, ListHostsBuilder};
+#[cfg(feature = "test-util")]
+pub use mock::{ApiCall, MockTendhostApi};
+pub use retry::RetryPolicy;
+pub use traits::TendhostApi;
+pub use version::{SUPPORTED_API_VERSIONS, VersionCheck};
+pub use ws::{WsClient, WsClientBuilder};
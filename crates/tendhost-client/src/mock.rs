@@ -0,0 +1,557 @@
+//! In-memory [`TendhostApi`] for tests
+//!
+//! Enabled by the `test-util` feature. [`MockTendhostApi`] answers from
+//! canned responses set up with its builder methods and records every call,
+//! so code holding a `dyn TendhostApi` can be tested without a daemon.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use tendhost_api::{
+    events::{EventEnvelope, recent_per_host},
+    pagination::{PageParams, Paginated, paginate_by_cursor, paginate_vec},
+    requests::{FleetUpdateRequest, UpdateRequest, UpdateScope},
+    responses::{
+        AppliedFilters, FleetDryRunReport, FleetSummary, HealthResponse, HostDetail,
+        HostInventoryResponse, HostListResponse, HostSummary, StateTransitionInfo, UpdateAccepted,
+        UpdateHistoryEntry, UpdatePreview,
+    },
+};
+
+use crate::error::{ClientError, Result};
+use crate::http::HostListQuery;
+use crate::traits::TendhostApi;
+
+/// A call made to a [`MockTendhostApi`], with its arguments
+#[derive(Debug, Clone)]
+#[allow(missing_docs)]
+pub enum ApiCall {
+    Health,
+    ListHosts(HostListQuery),
+    GetHost(String),
+    UpdateHost {
+        name: String,
+        config: Value,
+    },
+    StartUpdate {
+        name: String,
+        request: UpdateRequest,
+    },
+    UpdateHostPackages {
+        name: String,
+        dry_run: bool,
+    },
+    PreviewUpdate {
+        name: String,
+        scope: Option<UpdateScope>,
+    },
+    UpdateSelectedPackages {
+        name: String,
+        packages: Vec<String>,
+    },
+    UpdateHostStack {
+        name: String,
+        stack: String,
+    },
+    CancelHostUpdate(String),
+    RebootHost(String),
+    RetryHost(String),
+    AcknowledgeHost(String),
+    PauseHost(String),
+    ResumeHost(String),
+    GetHostInventory(String),
+    GetUpdateHistory {
+        name: String,
+        limit: Option<usize>,
+    },
+    GetTransitions(String),
+    UpdateFleet(FleetUpdateRequest),
+    FleetDryRun(FleetUpdateRequest),
+    FleetStatus,
+    ListEvents(PageParams),
+    RecentEvents(usize),
+}
+
+impl ApiCall {
+    /// Name of the [`TendhostApi`] method called
+    #[must_use]
+    pub fn method(&self) -> &'static str {
+        match self {
+            Self::Health => "health",
+            Self::ListHosts(_) => "list_hosts",
+            Self::GetHost(_) => "get_host",
+            Self::UpdateHost { .. } => "update_host",
+            Self::StartUpdate { .. } => "start_update",
+            Self::UpdateHostPackages { .. } => "update_host_packages",
+            Self::PreviewUpdate { .. } => "preview_update",
+            Self::UpdateSelectedPackages { .. } => "update_selected_packages",
+            Self::UpdateHostStack { .. } => "update_host_stack",
+            Self::CancelHostUpdate(_) => "cancel_host_update",
+            Self::RebootHost(_) => "reboot_host",
+            Self::RetryHost(_) => "retry_host",
+            Self::AcknowledgeHost(_) => "acknowledge_host",
+            Self::PauseHost(_) => "pause_host",
+            Self::ResumeHost(_) => "resume_host",
+            Self::GetHostInventory(_) => "get_host_inventory",
+            Self::GetUpdateHistory { .. } => "get_update_history",
+            Self::GetTransitions(_) => "get_transitions",
+            Self::UpdateFleet(_) => "update_fleet",
+            Self::FleetDryRun(_) => "fleet_dry_run",
+            Self::FleetStatus => "fleet_status",
+            Self::ListEvents(_) => "list_events",
+            Self::RecentEvents(_) => "recent_events",
+        }
+    }
+
+    /// Host the call is about, if it is about one
+    #[must_use]
+    pub fn host(&self) -> Option<&str> {
+        match self {
+            Self::GetHost(name)
+            | Self::UpdateHost { name, .. }
+            | Self::StartUpdate { name, .. }
+            | Self::UpdateHostPackages { name, .. }
+            | Self::PreviewUpdate { name, .. }
+            | Self::UpdateSelectedPackages { name, .. }
+            | Self::UpdateHostStack { name, .. }
+            | Self::CancelHostUpdate(name)
+            | Self::RebootHost(name)
+            | Self::RetryHost(name)
+            | Self::AcknowledgeHost(name)
+            | Self::PauseHost(name)
+            | Self::ResumeHost(name)
+            | Self::GetHostInventory(name)
+            | Self::GetUpdateHistory { name, .. }
+            | Self::GetTransitions(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
+/// [`TendhostApi`] answering from canned responses and recording calls
+///
+/// Hosts are known once added with [`with_host`](Self::with_host) or
+/// [`with_host_detail`](Self::with_host_detail); requests for any other
+/// host fail with a 404 like the daemon's. Operations on known hosts
+/// succeed unless [`failing`](Self::failing) says otherwise.
+///
+/// # Example
+/// ```
+/// use tendhost_client::mock::{ApiCall, MockTendhostApi};
+/// use tendhost_client::TendhostApi;
+///
+/// # async fn example() {
+/// let api = MockTendhostApi::new()
+///     .with_host_named("web-1", "Idle")
+///     .failing("reboot_host", 409, "host is busy");
+///
+/// api.update_host_packages("web-1", false).await.unwrap();
+/// assert!(api.reboot_host("web-1").await.is_err());
+/// assert!(api.get_host("db-1").await.is_err());
+///
+/// assert!(matches!(
+///     &api.calls()[0],
+///     ApiCall::UpdateHostPackages { name, dry_run: false } if name == "web-1"
+/// ));
+/// # }
+/// ```
+#[derive(Debug, Default)]
+pub struct MockTendhostApi {
+    hosts: Vec<HostSummary>,
+    details: HashMap<String, HostDetail>,
+    inventories: HashMap<String, HostInventoryResponse>,
+    previews: HashMap<String, UpdatePreview>,
+    update_history: HashMap<String, Vec<UpdateHistoryEntry>>,
+    transitions: HashMap<String, Vec<StateTransitionInfo>>,
+    fleet_summary: FleetSummary,
+    events: Vec<EventEnvelope>,
+    failures: HashMap<&'static str, (u16, String)>,
+    host_failures: HashMap<(&'static str, String), (u16, String)>,
+    calls: Mutex<Vec<ApiCall>>,
+}
+
+impl MockTendhostApi {
+    /// A mock without hosts
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a host to the host list
+    #[must_use]
+    pub fn with_host(mut self, host: HostSummary) -> Self {
+        self.hosts.push(host);
+        self
+    }
+
+    /// Add a host with just a name and state, e.g. `Idle` or `Failed`
+    #[must_use]
+    pub fn with_host_named(self, name: &str, state: &str) -> Self {
+        self.with_host(HostSummary {
+            name: name.to_string(),
+            state: state.to_string(),
+            os: None,
+            pending_updates: None,
+            security_updates: None,
+            last_checked: None,
+            tags: Vec::new(),
+            last_updated: None,
+            error: None,
+            reachable: true,
+            last_seen: None,
+            sudo_available: None,
+            acknowledged: None,
+            failed_at: None,
+            retry_count: None,
+            failure_kind: None,
+            paused: false,
+        })
+    }
+
+    /// Answer `get_host` and `update_host` for the host with `detail`
+    #[must_use]
+    pub fn with_host_detail(mut self, detail: HostDetail) -> Self {
+        self.details.insert(detail.name.clone(), detail);
+        self
+    }
+
+    /// Answer `get_host_inventory` for `host`
+    #[must_use]
+    pub fn with_inventory(mut self, host: &str, inventory: HostInventoryResponse) -> Self {
+        self.inventories.insert(host.to_string(), inventory);
+        self
+    }
+
+    /// Answer `preview_update` for `preview.host`
+    ///
+    /// Known hosts without one preview an update with nothing to do.
+    #[must_use]
+    pub fn with_preview(mut self, preview: UpdatePreview) -> Self {
+        self.previews.insert(preview.host.clone(), preview);
+        self
+    }
+
+    /// Answer `get_update_history` for `host`, newest first
+    #[must_use]
+    pub fn with_update_history(mut self, host: &str, history: Vec<UpdateHistoryEntry>) -> Self {
+        self.update_history.insert(host.to_string(), history);
+        self
+    }
+
+    /// Answer `get_transitions` for `host`
+    #[must_use]
+    pub fn with_transitions(mut self, host: &str, transitions: Vec<StateTransitionInfo>) -> Self {
+        self.transitions.insert(host.to_string(), transitions);
+        self
+    }
+
+    /// Answer `fleet_status`
+    #[must_use]
+    pub fn with_fleet_summary(mut self, summary: FleetSummary) -> Self {
+        self.fleet_summary = summary;
+        self
+    }
+
+    /// Answer `list_events` and `recent_events` from `events`, ordered by
+    /// sequence number
+    #[must_use]
+    pub fn with_events(mut self, events: Vec<EventEnvelope>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Fail every call of `method` with an API error
+    #[must_use]
+    pub fn failing(mut self, method: &'static str, status: u16, message: &str) -> Self {
+        self.failures.insert(method, (status, message.to_string()));
+        self
+    }
+
+    /// Fail calls of `method` about `host` with an API error
+    ///
+    /// Takes precedence over [`failing`](Self::failing) for that host.
+    #[must_use]
+    pub fn failing_for(
+        mut self,
+        method: &'static str,
+        host: &str,
+        status: u16,
+        message: &str,
+    ) -> Self {
+        self.host_failures
+            .insert((method, host.to_string()), (status, message.to_string()));
+        self
+    }
+
+    /// Calls made so far, oldest first
+    ///
+    /// # Panics
+    /// Panics if a thread panicked while recording a call.
+    #[must_use]
+    pub fn calls(&self) -> Vec<ApiCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Record `call`, failing it if configured to
+    fn record(&self, call: ApiCall) -> Result<()> {
+        let method = call.method();
+        let failure = call
+            .host()
+            .and_then(|host| self.host_failures.get(&(method, host.to_string())))
+            .or_else(|| self.failures.get(method))
+            .cloned();
+        self.calls.lock().unwrap().push(call);
+        match failure {
+            Some((status, message)) => Err(ClientError::Api { status, message }),
+            None => Ok(()),
+        }
+    }
+
+    /// Fail with a 404 unless `name` is a known host
+    fn known(&self, name: &str) -> Result<()> {
+        if self.hosts.iter().any(|h| h.name == name) || self.details.contains_key(name) {
+            Ok(())
+        } else {
+            Err(ClientError::Api {
+                status: 404,
+                message: format!("host not found: {name}"),
+            })
+        }
+    }
+
+    /// Record a host operation answered with an empty JSON object
+    fn host_operation(&self, call: ApiCall, name: &str) -> Result<Value> {
+        self.record(call)?;
+        self.known(name)?;
+        Ok(serde_json::json!({}))
+    }
+
+    fn update_accepted(&self, call: ApiCall, name: &str, dry_run: bool) -> Result<UpdateAccepted> {
+        self.record(call)?;
+        self.known(name)?;
+        Ok(UpdateAccepted {
+            host: name.to_string(),
+            dry_run,
+            message: "update started".to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl TendhostApi for MockTendhostApi {
+    async fn health(&self) -> Result<HealthResponse> {
+        self.record(ApiCall::Health)?;
+        Ok(HealthResponse {
+            status: "ok".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            api_version: tendhost_api::version::API_VERSION,
+            schedules: Vec::new(),
+            notifications: Vec::new(),
+            events: Vec::new(),
+        })
+    }
+
+    async fn list_hosts(&self, query: &HostListQuery) -> Result<HostListResponse> {
+        self.record(ApiCall::ListHosts(query.clone()))?;
+        let defaults = PageParams::default();
+        let params = PageParams::new(
+            query.page.unwrap_or(defaults.page),
+            query.per_page.unwrap_or(defaults.per_page),
+        );
+        let page = paginate_vec(self.hosts.clone(), &params);
+        // The mock doesn't filter or sort, but reports the query like the daemon
+        fn parse<T: serde::de::DeserializeOwned + Default>(value: &Option<String>) -> T {
+            value
+                .as_ref()
+                .and_then(|v| serde_json::from_value(Value::String(v.clone())).ok())
+                .unwrap_or_default()
+        }
+        Ok(HostListResponse {
+            hosts: page.data,
+            pagination: page.pagination,
+            filters: AppliedFilters {
+                tags: query.tags.clone(),
+                facts: query.facts.clone(),
+                state: query.state.clone(),
+                group: query.group.clone(),
+                search: query.search.clone(),
+                acknowledged: query.acknowledged,
+                sort: parse(&query.sort),
+                order: parse(&query.order),
+            },
+        })
+    }
+
+    async fn get_host(&self, name: &str) -> Result<HostDetail> {
+        self.record(ApiCall::GetHost(name.to_string()))?;
+        self.details.get(name).cloned().ok_or(ClientError::Api {
+            status: 404,
+            message: format!("host not found: {name}"),
+        })
+    }
+
+    async fn update_host(&self, name: &str, config: Value) -> Result<HostDetail> {
+        self.record(ApiCall::UpdateHost {
+            name: name.to_string(),
+            config,
+        })?;
+        self.details.get(name).cloned().ok_or(ClientError::Api {
+            status: 404,
+            message: format!("host not found: {name}"),
+        })
+    }
+
+    async fn start_update(&self, name: &str, request: &UpdateRequest) -> Result<UpdateAccepted> {
+        let call = ApiCall::StartUpdate {
+            name: name.to_string(),
+            request: request.clone(),
+        };
+        self.update_accepted(call, name, request.dry_run)
+    }
+
+    async fn update_host_packages(&self, name: &str, dry_run: bool) -> Result<UpdateAccepted> {
+        let call = ApiCall::UpdateHostPackages {
+            name: name.to_string(),
+            dry_run,
+        };
+        self.update_accepted(call, name, dry_run)
+    }
+
+    async fn preview_update(
+        &self,
+        name: &str,
+        scope: Option<UpdateScope>,
+    ) -> Result<UpdatePreview> {
+        self.record(ApiCall::PreviewUpdate {
+            name: name.to_string(),
+            scope,
+        })?;
+        self.known(name)?;
+        Ok(self
+            .previews
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| UpdatePreview {
+                host: name.to_string(),
+                scope: scope.unwrap_or_default(),
+                packages: Vec::new(),
+                upgraded_count: 0,
+                new_count: 0,
+                removed_count: 0,
+                download_bytes: None,
+                reboot_likely: false,
+                blockers: Vec::new(),
+                warnings: Vec::new(),
+                previewed_at: chrono::Utc::now(),
+            }))
+    }
+
+    async fn update_selected_packages(
+        &self,
+        name: &str,
+        packages: &[String],
+    ) -> Result<UpdateAccepted> {
+        let call = ApiCall::UpdateSelectedPackages {
+            name: name.to_string(),
+            packages: packages.to_vec(),
+        };
+        self.update_accepted(call, name, false)
+    }
+
+    async fn update_host_stack(&self, name: &str, stack: &str) -> Result<UpdateAccepted> {
+        let call = ApiCall::UpdateHostStack {
+            name: name.to_string(),
+            stack: stack.to_string(),
+        };
+        self.update_accepted(call, name, false)
+    }
+
+    async fn cancel_host_update(&self, name: &str) -> Result<Value> {
+        self.host_operation(ApiCall::CancelHostUpdate(name.to_string()), name)
+    }
+
+    async fn reboot_host(&self, name: &str) -> Result<Value> {
+        self.host_operation(ApiCall::RebootHost(name.to_string()), name)
+    }
+
+    async fn retry_host(&self, name: &str) -> Result<Value> {
+        self.host_operation(ApiCall::RetryHost(name.to_string()), name)
+    }
+
+    async fn acknowledge_host(&self, name: &str) -> Result<Value> {
+        self.host_operation(ApiCall::AcknowledgeHost(name.to_string()), name)
+    }
+
+    async fn pause_host(&self, name: &str) -> Result<Value> {
+        self.host_operation(ApiCall::PauseHost(name.to_string()), name)
+    }
+
+    async fn resume_host(&self, name: &str) -> Result<Value> {
+        self.host_operation(ApiCall::ResumeHost(name.to_string()), name)
+    }
+
+    async fn get_host_inventory(&self, name: &str) -> Result<HostInventoryResponse> {
+        self.record(ApiCall::GetHostInventory(name.to_string()))?;
+        self.known(name)?;
+        Ok(self
+            .inventories
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| HostInventoryResponse {
+                name: name.to_string(),
+                pending_updates: 0,
+                security_updates: 0,
+                upgradable_packages: Vec::new(),
+                inventory: serde_json::json!({}),
+            }))
+    }
+
+    async fn get_update_history(
+        &self,
+        name: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<UpdateHistoryEntry>> {
+        self.record(ApiCall::GetUpdateHistory {
+            name: name.to_string(),
+            limit,
+        })?;
+        self.known(name)?;
+        let mut history = self.update_history.get(name).cloned().unwrap_or_default();
+        history.truncate(limit.unwrap_or(usize::MAX));
+        Ok(history)
+    }
+
+    async fn get_transitions(&self, name: &str) -> Result<Vec<StateTransitionInfo>> {
+        self.record(ApiCall::GetTransitions(name.to_string()))?;
+        self.known(name)?;
+        Ok(self.transitions.get(name).cloned().unwrap_or_default())
+    }
+
+    async fn update_fleet(&self, request: FleetUpdateRequest) -> Result<Value> {
+        self.record(ApiCall::UpdateFleet(request))?;
+        Ok(serde_json::json!({}))
+    }
+
+    async fn fleet_dry_run(&self, request: FleetUpdateRequest) -> Result<FleetDryRunReport> {
+        self.record(ApiCall::FleetDryRun(request))?;
+        Ok(FleetDryRunReport::new(Vec::new()))
+    }
+
+    async fn fleet_status(&self) -> Result<FleetSummary> {
+        self.record(ApiCall::FleetStatus)?;
+        Ok(self.fleet_summary.clone())
+    }
+
+    async fn list_events(&self, params: &PageParams) -> Result<Paginated<EventEnvelope>> {
+        self.record(ApiCall::ListEvents(*params))?;
+        Ok(paginate_by_cursor(self.events.clone(), params, |e| e.seq))
+    }
+
+    async fn recent_events(&self, per_host: usize) -> Result<Vec<EventEnvelope>> {
+        self.record(ApiCall::RecentEvents(per_host))?;
+        Ok(recent_per_host(&self.events, per_host))
+    }
+}
@@ -0,0 +1,201 @@
+//! Retry policy for idempotent requests
+//!
+//! Only requests that are safe to repeat (GETs and the few POSTs the daemon
+//! treats as idempotent) are retried. Updates, reboots and fleet updates are
+//! sent exactly once so a lost response never starts a second operation.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::error::ClientError;
+
+/// Default number of retries after the first attempt
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// Default delay before the first retry
+pub const DEFAULT_INITIAL_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Default upper bound for the delay between retries
+pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// How idempotent requests are retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt (0 disables retrying)
+    pub retries: u32,
+    /// Delay before the first retry, doubled for each further retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between retries
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            initial_backoff: DEFAULT_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries
+    #[must_use]
+    pub fn none() -> Self {
+        Self {
+            retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before retry number `retry` (1-based)
+    ///
+    /// Exponential and capped at `max_backoff`, with "equal jitter": the
+    /// delay is between half and all of the exponential value, so clients
+    /// that failed together do not retry in lockstep.
+    #[must_use]
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let exponent = retry.saturating_sub(1).min(16);
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff);
+        let half = delay / 2;
+        half + half.mul_f64(jitter())
+    }
+
+    /// Delay before retry number `retry`, honoring the server's `Retry-After`
+    ///
+    /// A `Retry-After` hint replaces the exponential delay but is still
+    /// capped at `max_backoff`, so a draining daemon asking for 30 seconds
+    /// cannot stall a client configured to give up sooner.
+    #[must_use]
+    pub fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Duration {
+        retry_after.map_or_else(|| self.backoff(retry), |after| after.min(self.max_backoff))
+    }
+}
+
+/// The delay a `Retry-After` header asks for
+///
+/// Only the delay-seconds form is understood; HTTP dates are ignored and
+/// fall back to the policy's own backoff.
+pub(crate) fn retry_after(value: Option<&[u8]>) -> Option<Duration> {
+    let seconds = std::str::from_utf8(value?).ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Whether a failed attempt may succeed when repeated
+pub(crate) fn is_retryable(error: &ClientError) -> bool {
+    match error {
+        ClientError::Timeout => true,
+        ClientError::Http(e) => e.is_connect(),
+        // Gateway errors and 503 while the daemon restarts or drains
+        ClientError::Api { status, .. } => matches!(status, 502..=504),
+        _ => false,
+    }
+}
+
+/// Whether a failed page of a list walk may succeed when repeated
+///
+/// Besides what [`is_retryable`] allows, any server error is retried: a
+/// walk reads many pages, and one failing page shouldn't waste the rest.
+pub(crate) fn is_retryable_page(error: &ClientError) -> bool {
+    is_retryable(error)
+        || matches!(
+            error,
+            ClientError::Api {
+                status: 500..=599,
+                ..
+            }
+        )
+}
+
+/// Pseudo-random value in `[0, 1)`; good enough for spreading retries
+fn jitter() -> f64 {
+    let bits = RandomState::new().build_hasher().finish() >> 11;
+    #[allow(clippy::cast_precision_loss)]
+    let value = bits as f64 / (1u64 << 53) as f64;
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_is_capped_and_jittered() {
+        let policy = RetryPolicy {
+            retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(350),
+        };
+
+        for _ in 0..20 {
+            let first = policy.backoff(1);
+            assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+            let second = policy.backoff(2);
+            assert!(second >= Duration::from_millis(100) && second <= Duration::from_millis(200));
+            let capped = policy.backoff(10);
+            assert!(capped >= Duration::from_millis(175) && capped <= Duration::from_millis(350));
+        }
+    }
+
+    #[test]
+    fn test_retry_after_replaces_backoff_up_to_the_cap() {
+        let policy = RetryPolicy {
+            retries: 2,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        };
+
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(2))),
+            Duration::from_secs(2)
+        );
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(30))),
+            Duration::from_secs(5)
+        );
+        assert!(policy.delay(1, None) <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_retry_after_parsing() {
+        assert_eq!(retry_after(Some(b"30")), Some(Duration::from_secs(30)));
+        assert_eq!(retry_after(Some(b" 1 ")), Some(Duration::from_secs(1)));
+        assert_eq!(retry_after(Some(b"Wed, 21 Oct 2026 07:28:00 GMT")), None);
+        assert_eq!(retry_after(Some(b"-1")), None);
+        assert_eq!(retry_after(None), None);
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        assert!(is_retryable(&ClientError::Timeout));
+        for status in [502, 503, 504] {
+            assert!(is_retryable(&ClientError::Api {
+                status,
+                message: String::new(),
+            }));
+        }
+        for status in [400, 404, 409, 500] {
+            assert!(!is_retryable(&ClientError::Api {
+                status,
+                message: String::new(),
+            }));
+        }
+    }
+
+    #[test]
+    fn test_retryable_page_errors() {
+        assert!(is_retryable_page(&ClientError::Timeout));
+        for (status, retryable) in [(404, false), (422, false), (500, true), (503, true)] {
+            let error = ClientError::Api {
+                status,
+                message: String::new(),
+            };
+            assert_eq!(is_retryable_page(&error), retryable, "{status}");
+        }
+    }
+}
@@ -0,0 +1,595 @@
+//! OpenSSH client config parsing for host import
+//!
+//! Understands the subset of `ssh_config(5)` needed to register hosts:
+//! `Host` blocks with `HostName`, `User`, `Port` and `IdentityFile`, plus
+//! `Include`. As in ssh, the first value obtained for a directive wins, so
+//! `Host *` defaults at the end of a file only fill in what a host leaves
+//! unset. `Match` blocks depend on the connection being made and cannot be
+//! evaluated up front; their directives are skipped.
+
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use tendhost_api::requests::{RegisterHostRequest, default_user};
+
+/// Maximum `Include` nesting, the same limit ssh uses
+pub const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Errors reading an SSH config
+#[derive(Debug, Error)]
+pub enum SshConfigError {
+    /// A config file could not be read
+    #[error("failed to read {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: std::io::Error,
+    },
+
+    /// A directive has an invalid value
+    #[error("{}:{line}: {message}", path.display())]
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+
+    /// `Include` directives nest too deeply, usually an include loop
+    #[error("includes nested more than {MAX_INCLUDE_DEPTH} levels deep at {}", .0.display())]
+    IncludeDepth(PathBuf),
+}
+
+/// A concrete host alias with its resolved connection settings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshHost {
+    /// Name after `Host`
+    pub alias: String,
+    /// `HostName`, or the alias when unset
+    pub hostname: String,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    /// First `IdentityFile`, as written (`~` is not expanded)
+    pub identity_file: Option<String>,
+}
+
+impl SshHost {
+    /// Registration request for this host with the given tags
+    #[must_use]
+    pub fn to_register_request(&self, tags: &[String]) -> RegisterHostRequest {
+        RegisterHostRequest {
+            name: self.alias.clone(),
+            addr: self.hostname.clone(),
+            port: self.port,
+            user: self.user.clone().unwrap_or_else(default_user),
+            ssh_key: self.identity_file.clone(),
+            connect_timeout_secs: None,
+            jump_host: None,
+            tags: tags.to_vec(),
+        }
+    }
+}
+
+/// Parse an SSH config file and resolve every concrete host in it
+///
+/// Relative `Include` paths are resolved against the directory of `path`,
+/// as ssh does for `~/.ssh/config`.
+///
+/// # Errors
+/// Returns an error if `path` or an included file cannot be read or a
+/// directive has an invalid value. Included files that do not exist are
+/// ignored, like ssh does.
+pub fn parse_file(path: &Path) -> Result<Vec<SshHost>, SshConfigError> {
+    let content = read(path)?;
+    let base_dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let mut parser = Parser::new(base_dir);
+    parser.parse(&content, path, 0)?;
+    Ok(parser.resolve())
+}
+
+/// Parse SSH config text, resolving relative includes against `base_dir`
+///
+/// # Errors
+/// See [`parse_file`].
+pub fn parse_str(content: &str, base_dir: &Path) -> Result<Vec<SshHost>, SshConfigError> {
+    let mut parser = Parser::new(base_dir.to_path_buf());
+    parser.parse(content, &base_dir.join("config"), 0)?;
+    Ok(parser.resolve())
+}
+
+fn read(path: &Path) -> Result<String, SshConfigError> {
+    std::fs::read_to_string(path).map_err(|source| SshConfigError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Directives that apply to hosts matching `patterns`
+///
+/// `patterns` is `None` for `Match` blocks, which never apply.
+struct Block {
+    patterns: Option<Vec<String>>,
+    directives: Vec<(Directive, String)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    HostName,
+    User,
+    Port,
+    IdentityFile,
+}
+
+impl Directive {
+    fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "hostname" => Some(Self::HostName),
+            "user" => Some(Self::User),
+            "port" => Some(Self::Port),
+            "identityfile" => Some(Self::IdentityFile),
+            _ => None,
+        }
+    }
+}
+
+struct Parser {
+    base_dir: PathBuf,
+    /// Starts with the implicit block for directives before the first `Host`
+    blocks: Vec<Block>,
+}
+
+impl Parser {
+    fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            blocks: vec![Block {
+                patterns: Some(vec!["*".to_string()]),
+                directives: Vec::new(),
+            }],
+        }
+    }
+
+    fn parse(&mut self, content: &str, path: &Path, depth: usize) -> Result<(), SshConfigError> {
+        for (index, raw) in content.lines().enumerate() {
+            let Some((keyword, args)) = split_line(raw) else {
+                continue;
+            };
+            let syntax = |message: String| SshConfigError::Syntax {
+                path: path.to_path_buf(),
+                line: index + 1,
+                message,
+            };
+
+            match keyword.as_str() {
+                "host" => {
+                    if args.is_empty() {
+                        return Err(syntax("Host requires at least one pattern".to_string()));
+                    }
+                    self.blocks.push(Block {
+                        patterns: Some(args),
+                        directives: Vec::new(),
+                    });
+                }
+                "match" => self.blocks.push(Block {
+                    patterns: None,
+                    directives: Vec::new(),
+                }),
+                "include" => {
+                    if depth >= MAX_INCLUDE_DEPTH {
+                        return Err(SshConfigError::IncludeDepth(path.to_path_buf()));
+                    }
+                    // Host and Match lines in an included file end at its
+                    // end; the including block continues afterwards
+                    let enclosing = self.blocks.len() - 1;
+                    for arg in &args {
+                        for file in self.include_paths(arg) {
+                            let Ok(content) = std::fs::read_to_string(&file) else {
+                                tracing::debug!("skipping unreadable include {}", file.display());
+                                continue;
+                            };
+                            self.parse(&content, &file, depth + 1)?;
+                        }
+                    }
+                    if self.blocks.len() - 1 != enclosing {
+                        let patterns = self.blocks[enclosing].patterns.clone();
+                        self.blocks.push(Block {
+                            patterns,
+                            directives: Vec::new(),
+                        });
+                    }
+                }
+                other => {
+                    let Some(directive) = Directive::from_keyword(other) else {
+                        continue;
+                    };
+                    let Some(value) = args.into_iter().next() else {
+                        return Err(syntax(format!("{other} requires a value")));
+                    };
+                    if directive == Directive::Port && value.parse::<u16>().is_err() {
+                        return Err(syntax(format!("invalid port '{value}'")));
+                    }
+                    let block = self
+                        .blocks
+                        .last_mut()
+                        .expect("implicit block always exists");
+                    block.directives.push((directive, value));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Files named by an `Include` argument, glob matches sorted by name
+    fn include_paths(&self, arg: &str) -> Vec<PathBuf> {
+        let path = match arg.strip_prefix("~/") {
+            Some(rest) => match std::env::var_os("HOME") {
+                Some(home) => PathBuf::from(home).join(rest),
+                None => return Vec::new(),
+            },
+            None => self.base_dir.join(arg),
+        };
+
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            return vec![path];
+        };
+        if !name.contains(['*', '?']) {
+            return vec![path];
+        }
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut matches: Vec<PathBuf> = entries
+            .filter_map(Result::ok)
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|entry_name| wildcard_match(name, entry_name))
+            })
+            .map(|entry| entry.path())
+            .collect();
+        matches.sort();
+        matches
+    }
+
+    /// Resolve every concrete alias in the order it first appears
+    fn resolve(&self) -> Vec<SshHost> {
+        let mut aliases: Vec<&str> = Vec::new();
+        for patterns in self.blocks.iter().filter_map(|b| b.patterns.as_ref()) {
+            for pattern in patterns {
+                let concrete = !pattern.contains(['*', '?']) && !pattern.starts_with('!');
+                if concrete && !aliases.contains(&pattern.as_str()) {
+                    aliases.push(pattern);
+                }
+            }
+        }
+
+        aliases
+            .into_iter()
+            .map(|alias| self.resolve_alias(alias))
+            .collect()
+    }
+
+    fn resolve_alias(&self, alias: &str) -> SshHost {
+        let mut hostname = None;
+        let mut user = None;
+        let mut port = None;
+        let mut identity_file = None;
+
+        for block in &self.blocks {
+            let Some(patterns) = &block.patterns else {
+                continue;
+            };
+            if !host_matches(patterns, alias) {
+                continue;
+            }
+            for (directive, value) in &block.directives {
+                let slot = match directive {
+                    Directive::HostName => &mut hostname,
+                    Directive::User => &mut user,
+                    Directive::Port => &mut port,
+                    Directive::IdentityFile => &mut identity_file,
+                };
+                if slot.is_none() {
+                    *slot = Some(value.clone());
+                }
+            }
+        }
+
+        SshHost {
+            alias: alias.to_string(),
+            hostname: hostname.map_or_else(|| alias.to_string(), |h| expand_host_tokens(&h, alias)),
+            user,
+            // Validated while parsing
+            port: port.and_then(|p| p.parse().ok()),
+            identity_file,
+        }
+    }
+}
+
+/// Split a config line into a lowercased keyword and its arguments
+///
+/// Returns `None` for blank lines and comments.
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let end = line
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(line.len());
+    let keyword = line[..end].to_ascii_lowercase();
+    let rest = line[end..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+    Some((keyword, split_args(rest)))
+}
+
+/// Split arguments on whitespace, keeping double-quoted runs together
+fn split_args(rest: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_arg = false;
+
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_arg = true;
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if has_arg {
+                    args.push(std::mem::take(&mut current));
+                    has_arg = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_arg = true;
+            }
+        }
+    }
+    if has_arg {
+        args.push(current);
+    }
+    args
+}
+
+/// Whether a `Host` pattern list applies to `alias`
+///
+/// At least one positive pattern must match and no negated one may.
+fn host_matches(patterns: &[String], alias: &str) -> bool {
+    let alias = alias.to_ascii_lowercase();
+    let mut matched = false;
+    for pattern in patterns {
+        let pattern = pattern.to_ascii_lowercase();
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, &alias) {
+                return false;
+            }
+        } else if wildcard_match(&pattern, &alias) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+/// Match `text` against a pattern where `*` is any run and `?` one character
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Expand `%h` (the alias) and `%%` in a `HostName`
+fn expand_host_tokens(value: &str, alias: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('h') => out.push_str(alias),
+            Some('%') => out.push('%'),
+            Some(other) => {
+                out.push('%');
+                out.push(other);
+            }
+            None => out.push('%'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"
+# Defaults for the lab
+User admin
+
+Host nas
+    HostName 192.168.1.10
+    Port 2222
+    IdentityFile ~/.ssh/nas_ed25519
+    IdentityFile ~/.ssh/id_ed25519
+
+Host pi-01 pi-02
+    HostName %h.lan
+
+Match host pi-01 exec "test -f /tmp/vpn"
+    HostName 10.8.0.5
+    User vpn
+
+Host router
+    HostName=10.0.0.1
+    user root
+
+host "media box"
+    hostname 192.168.1.30
+
+Host * !router
+    IdentityFile ~/.ssh/id_ed25519
+    Port 22
+
+Host *.internal ??-test
+    User ignored
+"#;
+
+    fn host<'a>(hosts: &'a [SshHost], alias: &str) -> &'a SshHost {
+        hosts
+            .iter()
+            .find(|h| h.alias == alias)
+            .unwrap_or_else(|| panic!("missing host {alias}"))
+    }
+
+    #[test]
+    fn test_parses_concrete_hosts_in_order() {
+        let hosts = parse_str(FIXTURE, Path::new("/nonexistent")).unwrap();
+        let aliases: Vec<&str> = hosts.iter().map(|h| h.alias.as_str()).collect();
+        assert_eq!(aliases, ["nas", "pi-01", "pi-02", "router", "media box"]);
+    }
+
+    #[test]
+    fn test_first_value_wins() {
+        let hosts = parse_str(FIXTURE, Path::new("/nonexistent")).unwrap();
+
+        let nas = host(&hosts, "nas");
+        assert_eq!(nas.hostname, "192.168.1.10");
+        assert_eq!(nas.user.as_deref(), Some("admin"));
+        assert_eq!(nas.port, Some(2222));
+        assert_eq!(nas.identity_file.as_deref(), Some("~/.ssh/nas_ed25519"));
+
+        let router = host(&hosts, "router");
+        assert_eq!(router.hostname, "10.0.0.1");
+        // The top-level User precedes the block and wins, as in ssh
+        assert_eq!(router.user.as_deref(), Some("admin"));
+        // Excluded from `Host * !router`
+        assert_eq!(router.port, None);
+        assert_eq!(router.identity_file, None);
+    }
+
+    #[test]
+    fn test_match_blocks_are_ignored() {
+        let hosts = parse_str(FIXTURE, Path::new("/nonexistent")).unwrap();
+        let pi = host(&hosts, "pi-01");
+        assert_eq!(pi.hostname, "pi-01.lan");
+        assert_eq!(pi.user.as_deref(), Some("admin"));
+        assert_eq!(pi.port, Some(22));
+    }
+
+    #[test]
+    fn test_hostname_defaults_to_alias() {
+        let hosts = parse_str("Host backup\n  User borg\n", Path::new("/")).unwrap();
+        assert_eq!(hosts[0].hostname, "backup");
+        assert_eq!(hosts[0].user.as_deref(), Some("borg"));
+    }
+
+    #[test]
+    fn test_invalid_port_reports_line() {
+        let err = parse_str("Host a\n  Port ssh\n", Path::new("/cfg")).unwrap_err();
+        match err {
+            SshConfigError::Syntax { line, message, .. } => {
+                assert_eq!(line, 2);
+                assert!(message.contains("ssh"));
+            }
+            other => panic!("unexpected error: {other}"),
+        }
+    }
+
+    #[test]
+    fn test_includes_are_inlined() {
+        let dir = std::env::temp_dir().join(format!("tendhost-ssh-config-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("conf.d")).unwrap();
+        std::fs::write(
+            dir.join("conf.d/10-web.conf"),
+            "Host web\n  HostName 10.0.0.20\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("conf.d/20-db.conf"), "Host db\n  User postgres\n").unwrap();
+        std::fs::write(
+            dir.join("config"),
+            "Host jump\n  Include conf.d/*.conf missing.conf\n  HostName 10.0.0.1\n\nHost *\n  User ops\n",
+        )
+        .unwrap();
+
+        let hosts = parse_file(&dir.join("config")).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let aliases: Vec<&str> = hosts.iter().map(|h| h.alias.as_str()).collect();
+        assert_eq!(aliases, ["jump", "web", "db"]);
+        // The Host jump block continues after the include
+        assert_eq!(host(&hosts, "jump").hostname, "10.0.0.1");
+        assert_eq!(host(&hosts, "web").hostname, "10.0.0.20");
+        assert_eq!(host(&hosts, "db").user.as_deref(), Some("postgres"));
+        assert_eq!(host(&hosts, "web").user.as_deref(), Some("ops"));
+    }
+
+    #[test]
+    fn test_include_loop_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("tendhost-ssh-loop-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config"), "Include config\n").unwrap();
+
+        let result = parse_file(&dir.join("config"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(SshConfigError::IncludeDepth(_))));
+    }
+
+    #[test]
+    fn test_wildcard_match() {
+        assert!(wildcard_match("*", "anything"));
+        assert!(wildcard_match("pi-*", "pi-01"));
+        assert!(wildcard_match("??-test", "ab-test"));
+        assert!(wildcard_match("*.lan", "nas.lan"));
+        assert!(!wildcard_match("*.lan", "nas.local"));
+        assert!(!wildcard_match("??-test", "abc-test"));
+    }
+
+    #[test]
+    fn test_register_request_uses_defaults() {
+        let host = SshHost {
+            alias: "nas".to_string(),
+            hostname: "192.168.1.10".to_string(),
+            user: None,
+            port: None,
+            identity_file: Some("~/.ssh/nas".to_string()),
+        };
+        let request = host.to_register_request(&["imported".to_string()]);
+        assert_eq!(request.name, "nas");
+        assert_eq!(request.addr, "192.168.1.10");
+        assert_eq!(request.port, None);
+        assert_eq!(request.user, "root");
+        assert_eq!(request.ssh_key.as_deref(), Some("~/.ssh/nas"));
+        assert_eq!(request.tags, ["imported"]);
+    }
+}
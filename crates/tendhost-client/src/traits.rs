@@ -0,0 +1,233 @@
+//! The daemon API as a trait
+//!
+//! Code written against [`TendhostApi`] instead of [`HttpClient`] can be
+//! tested without a daemon, using `MockTendhostApi` from the `test-util`
+//! feature.
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use tendhost_api::{
+    events::EventEnvelope,
+    pagination::{PageParams, Paginated},
+    requests::{FleetUpdateRequest, UpdateRequest, UpdateScope},
+    responses::{
+        FleetDryRunReport, FleetSummary, HealthResponse, HostDetail, HostInventoryResponse,
+        HostListResponse, StateTransitionInfo, UpdateAccepted, UpdateHistoryEntry, UpdatePreview,
+    },
+};
+
+use crate::error::Result;
+use crate::http::{HostListQuery, HttpClient};
+
+/// Requests to a tendhost daemon
+///
+/// Each method does what the [`HttpClient`] method of the same name does;
+/// see there for details.
+///
+/// # Example
+/// ```no_run
+/// use std::sync::Arc;
+/// use tendhost_client::{HttpClient, TendhostApi};
+///
+/// async fn update_all_idle(api: &dyn TendhostApi) -> tendhost_client::Result<()> {
+///     let hosts = api.list_hosts(&Default::default()).await?;
+///     for host in hosts.hosts.iter().filter(|h| h.state == "Idle") {
+///         api.update_host_packages(&host.name, false).await?;
+///     }
+///     Ok(())
+/// }
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let api: Arc<dyn TendhostApi> = Arc::new(HttpClient::new("http://localhost:8080")?);
+/// update_all_idle(api.as_ref()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[async_trait]
+pub trait TendhostApi: Send + Sync {
+    /// Daemon health
+    async fn health(&self) -> Result<HealthResponse>;
+
+    /// One page of hosts matching `query`
+    async fn list_hosts(&self, query: &HostListQuery) -> Result<HostListResponse>;
+
+    /// A single host by name
+    async fn get_host(&self, name: &str) -> Result<HostDetail>;
+
+    /// Patch a host's configuration
+    async fn update_host(&self, name: &str, config: Value) -> Result<HostDetail>;
+
+    /// Start an update on a host as described by `request`
+    async fn start_update(&self, name: &str, request: &UpdateRequest) -> Result<UpdateAccepted>;
+
+    /// Update (or with `dry_run`, simulate updating) all packages on a host
+    async fn update_host_packages(&self, name: &str, dry_run: bool) -> Result<UpdateAccepted>;
+
+    /// Simulate an update of a host without changing it
+    async fn preview_update(&self, name: &str, scope: Option<UpdateScope>)
+    -> Result<UpdatePreview>;
+
+    /// Upgrade only the named packages on a host
+    async fn update_selected_packages(
+        &self,
+        name: &str,
+        packages: &[String],
+    ) -> Result<UpdateAccepted>;
+
+    /// Update a single docker compose stack on a host
+    async fn update_host_stack(&self, name: &str, stack: &str) -> Result<UpdateAccepted>;
+
+    /// Cancel a running package update on a host
+    async fn cancel_host_update(&self, name: &str) -> Result<Value>;
+
+    /// Reboot a host
+    async fn reboot_host(&self, name: &str) -> Result<Value>;
+
+    /// Retry a failed host
+    async fn retry_host(&self, name: &str) -> Result<Value>;
+
+    /// Acknowledge a failed host
+    async fn acknowledge_host(&self, name: &str) -> Result<Value>;
+
+    /// Pause a host so automation leaves it alone
+    async fn pause_host(&self, name: &str) -> Result<Value>;
+
+    /// Resume a paused host
+    async fn resume_host(&self, name: &str) -> Result<Value>;
+
+    /// A host's full inventory
+    async fn get_host_inventory(&self, name: &str) -> Result<HostInventoryResponse>;
+
+    /// A host's recent updates, newest first
+    async fn get_update_history(
+        &self,
+        name: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<UpdateHistoryEntry>>;
+
+    /// A host's state transitions, oldest first
+    async fn get_transitions(&self, name: &str) -> Result<Vec<StateTransitionInfo>>;
+
+    /// Start a fleet update
+    async fn update_fleet(&self, request: FleetUpdateRequest) -> Result<Value>;
+
+    /// Plan a fleet update without changing anything
+    async fn fleet_dry_run(&self, request: FleetUpdateRequest) -> Result<FleetDryRunReport>;
+
+    /// Fleet-wide counts
+    async fn fleet_status(&self) -> Result<FleetSummary>;
+
+    /// Recorded events after `params.cursor`
+    async fn list_events(&self, params: &PageParams) -> Result<Paginated<EventEnvelope>>;
+
+    /// The last `per_host` events of each host, oldest first
+    async fn recent_events(&self, per_host: usize) -> Result<Vec<EventEnvelope>>;
+}
+
+#[async_trait]
+impl TendhostApi for HttpClient {
+    async fn health(&self) -> Result<HealthResponse> {
+        HttpClient::health(self).await
+    }
+
+    async fn list_hosts(&self, query: &HostListQuery) -> Result<HostListResponse> {
+        self.fetch_hosts(query).await
+    }
+
+    async fn get_host(&self, name: &str) -> Result<HostDetail> {
+        HttpClient::get_host(self, name).await
+    }
+
+    async fn update_host(&self, name: &str, config: Value) -> Result<HostDetail> {
+        HttpClient::update_host(self, name, config).await
+    }
+
+    async fn start_update(&self, name: &str, request: &UpdateRequest) -> Result<UpdateAccepted> {
+        HttpClient::start_update(self, name, request).await
+    }
+
+    async fn update_host_packages(&self, name: &str, dry_run: bool) -> Result<UpdateAccepted> {
+        HttpClient::update_host_packages(self, name, dry_run).await
+    }
+
+    async fn preview_update(
+        &self,
+        name: &str,
+        scope: Option<UpdateScope>,
+    ) -> Result<UpdatePreview> {
+        HttpClient::preview_update(self, name, scope).await
+    }
+
+    async fn update_selected_packages(
+        &self,
+        name: &str,
+        packages: &[String],
+    ) -> Result<UpdateAccepted> {
+        HttpClient::update_selected_packages(self, name, packages).await
+    }
+
+    async fn update_host_stack(&self, name: &str, stack: &str) -> Result<UpdateAccepted> {
+        HttpClient::update_host_stack(self, name, stack).await
+    }
+
+    async fn cancel_host_update(&self, name: &str) -> Result<Value> {
+        HttpClient::cancel_host_update(self, name).await
+    }
+
+    async fn reboot_host(&self, name: &str) -> Result<Value> {
+        HttpClient::reboot_host(self, name).await
+    }
+
+    async fn retry_host(&self, name: &str) -> Result<Value> {
+        HttpClient::retry_host(self, name).await
+    }
+
+    async fn acknowledge_host(&self, name: &str) -> Result<Value> {
+        HttpClient::acknowledge_host(self, name).await
+    }
+
+    async fn pause_host(&self, name: &str) -> Result<Value> {
+        HttpClient::pause_host(self, name).await
+    }
+
+    async fn resume_host(&self, name: &str) -> Result<Value> {
+        HttpClient::resume_host(self, name).await
+    }
+
+    async fn get_host_inventory(&self, name: &str) -> Result<HostInventoryResponse> {
+        HttpClient::get_host_inventory(self, name).send().await
+    }
+
+    async fn get_update_history(
+        &self,
+        name: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<UpdateHistoryEntry>> {
+        HttpClient::get_update_history(self, name, limit).await
+    }
+
+    async fn get_transitions(&self, name: &str) -> Result<Vec<StateTransitionInfo>> {
+        HttpClient::get_transitions(self, name).await
+    }
+
+    async fn update_fleet(&self, request: FleetUpdateRequest) -> Result<Value> {
+        HttpClient::update_fleet(self, request).await
+    }
+
+    async fn fleet_dry_run(&self, request: FleetUpdateRequest) -> Result<FleetDryRunReport> {
+        HttpClient::fleet_dry_run(self, request).await
+    }
+
+    async fn fleet_status(&self) -> Result<FleetSummary> {
+        HttpClient::fleet_status(self).await
+    }
+
+    async fn list_events(&self, params: &PageParams) -> Result<Paginated<EventEnvelope>> {
+        HttpClient::list_events(self, params).await
+    }
+
+    async fn recent_events(&self, per_host: usize) -> Result<Vec<EventEnvelope>> {
+        HttpClient::recent_events(self, per_host).await
+    }
+}
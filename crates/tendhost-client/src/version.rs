@@ -0,0 +1,90 @@
+//! Protocol version check against the daemon
+//!
+//! The daemon names its protocol version in a header on every reply (see
+//! [`tendhost_api::version`]). A version outside [`SUPPORTED_API_VERSIONS`]
+//! may have renamed or retyped fields this client would misread, so such
+//! replies become [`ClientError::IncompatibleServer`] instead of data.
+//! Replies without the header come from daemons that predate it, or from
+//! a proxy in front of the daemon, and pass unchecked.
+
+use std::ops::RangeInclusive;
+
+use tendhost_api::version::API_VERSION;
+
+use crate::error::{ClientError, Result};
+
+/// Daemon protocol versions this client understands
+pub const SUPPORTED_API_VERSIONS: RangeInclusive<u32> = 1..=API_VERSION;
+
+/// Whether replies are checked for a supported protocol version
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum VersionCheck {
+    /// Refuse daemons outside [`SUPPORTED_API_VERSIONS`]
+    #[default]
+    Enforce,
+    /// Accept any daemon, for those who know the differences don't matter
+    /// to them
+    Ignore,
+}
+
+impl VersionCheck {
+    /// Check the value of a reply's version header
+    ///
+    /// # Errors
+    /// Returns [`ClientError::IncompatibleServer`] if the header names an
+    /// unsupported version, and [`ClientError::InvalidResponse`] if it
+    /// isn't a number.
+    pub(crate) fn check(self, header: Option<&[u8]>) -> Result<()> {
+        let Some(header) = header.filter(|_| self == Self::Enforce) else {
+            return Ok(());
+        };
+        let server = std::str::from_utf8(header)
+            .ok()
+            .and_then(|value| value.trim().parse::<u32>().ok())
+            .ok_or_else(|| {
+                ClientError::InvalidResponse(format!(
+                    "unreadable API version header {:?}",
+                    String::from_utf8_lossy(header)
+                ))
+            })?;
+        if SUPPORTED_API_VERSIONS.contains(&server) {
+            Ok(())
+        } else {
+            Err(ClientError::IncompatibleServer {
+                server,
+                supported: SUPPORTED_API_VERSIONS,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_supported_versions_pass() {
+        let check = VersionCheck::Enforce;
+        assert!(check.check(None).is_ok());
+        assert!(
+            check
+                .check(Some(API_VERSION.to_string().as_bytes()))
+                .is_ok()
+        );
+        assert!(matches!(
+            check.check(Some(b"99")),
+            Err(ClientError::IncompatibleServer { server: 99, .. })
+        ));
+        assert!(matches!(
+            check.check(Some(b"0")),
+            Err(ClientError::IncompatibleServer { server: 0, .. })
+        ));
+        assert!(matches!(
+            check.check(Some(b"one")),
+            Err(ClientError::InvalidResponse(_))
+        ));
+
+        assert!(VersionCheck::Ignore.check(Some(b"99")).is_ok());
+        assert!(VersionCheck::Ignore.check(Some(b"one")).is_ok());
+    }
+}
@@ -0,0 +1,278 @@
+//! Waiting for hosts to finish an operation
+//!
+//! Update and reboot endpoints answer `202 Accepted` right away. These
+//! helpers follow the host's state until it settles in `Idle`,
+//! `WaitingReboot` or `Failed`, using the event stream when it can be
+//! opened and polling `GET /hosts/{name}` otherwise.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use tokio::time::{Instant, sleep};
+use tracing::debug;
+
+use tendhost_api::events::WsEvent;
+use tendhost_api::responses::HostDetail;
+
+use crate::error::{ClientError, Result};
+use crate::http::HttpClient;
+use crate::ws::WsClient;
+
+/// Delay between polls when the event stream is unavailable
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Delay between status refreshes while following the event stream, in
+/// case events were missed during a reconnect
+const RESYNC_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long a host that is already settled must stay so before waiting
+/// ends without having seen it busy
+///
+/// Covers the gap between a `202` and the host actually starting.
+pub const SETTLE_GRACE: Duration = Duration::from_secs(3);
+
+/// States an operation ends in
+const TERMINAL_STATES: [&str; 3] = ["idle", "waitingreboot", "failed"];
+
+/// States of a running operation
+const BUSY_STATES: [&str; 4] = ["querying", "updating", "rebooting", "verifying"];
+
+/// When a host counts as done
+#[derive(Clone, Copy)]
+enum Settle {
+    /// Terminal after having been busy, or terminal for [`SETTLE_GRACE`]
+    AfterGrace,
+    /// Terminal after having been busy
+    AfterActivity,
+}
+
+/// Latest known state of a host being waited for
+struct Tracked {
+    state: String,
+    seen_busy: bool,
+}
+
+/// State names differ in case and separators between the REST API
+/// (`WaitingReboot`) and events (`waiting_reboot`)
+fn normalize(state: &str) -> String {
+    state
+        .chars()
+        .filter(|c| *c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Whether a host in `state` has finished its operation
+#[must_use]
+pub fn is_terminal_state(state: &str) -> bool {
+    TERMINAL_STATES.contains(&normalize(state).as_str())
+}
+
+/// Whether `state` is the failed state
+#[must_use]
+pub fn is_failed_state(state: &str) -> bool {
+    normalize(state) == "failed"
+}
+
+impl HttpClient {
+    /// Wait until a host's operation has finished, returning its final details
+    ///
+    /// Returns once the host is `Idle`, `WaitingReboot` or `Failed` after
+    /// having been busy, or has stayed in one of those states for
+    /// [`SETTLE_GRACE`]. `on_change` is called with each state the host
+    /// passes through, starting with the current one.
+    ///
+    /// # Errors
+    /// Returns [`ClientError::WaitTimeout`] if the host hasn't settled within
+    /// `timeout`, or an error if its status cannot be fetched.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::time::Duration;
+    /// # use tendhost_client::HttpClient;
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = HttpClient::new("http://localhost:8080")?;
+    /// client.update_host_packages("debian-vm", false).await?;
+    /// let host = client
+    ///     .wait_for_state("debian-vm", Duration::from_secs(1800), |host, state| {
+    ///         println!("{host}: {state}");
+    ///     })
+    ///     .await?;
+    /// println!("final state: {}", host.state);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn wait_for_state(
+        &self,
+        name: &str,
+        timeout: Duration,
+        mut on_change: impl FnMut(&str, &str),
+    ) -> Result<HostDetail> {
+        let mut hosts = self
+            .wait_for_hosts_with(
+                &[name.to_string()],
+                timeout,
+                Settle::AfterGrace,
+                &mut on_change,
+            )
+            .await?;
+        hosts
+            .pop()
+            .ok_or_else(|| ClientError::InvalidResponse(format!("no status for {name}")))
+    }
+
+    /// Wait until every host in `names` has run an operation and settled
+    ///
+    /// Unlike [`wait_for_state`](Self::wait_for_state), an idle host only
+    /// counts as done once it has been seen busy, so hosts in later batches
+    /// of a fleet update are waited for. Final details are returned in the
+    /// order of `names`.
+    ///
+    /// # Errors
+    /// Returns [`ClientError::WaitTimeout`] naming the hosts that hadn't
+    /// settled within `timeout`, or an error if a status cannot be fetched.
+    pub async fn wait_for_hosts(
+        &self,
+        names: &[String],
+        timeout: Duration,
+        mut on_change: impl FnMut(&str, &str),
+    ) -> Result<Vec<HostDetail>> {
+        self.wait_for_hosts_with(names, timeout, Settle::AfterActivity, &mut on_change)
+            .await
+    }
+
+    async fn wait_for_hosts_with(
+        &self,
+        names: &[String],
+        timeout: Duration,
+        settle: Settle,
+        on_change: &mut dyn FnMut(&str, &str),
+    ) -> Result<Vec<HostDetail>> {
+        let started = Instant::now();
+        let deadline = started + timeout;
+
+        // Subscribe before reading the current states so no change is missed
+        let mut events =
+            match WsClient::try_connect_with(self.ws_url()?.as_str(), self.version_check()).await {
+                Ok(ws) => Some(ws),
+                Err(e) => {
+                    debug!(error = %e, "event stream unavailable, polling host status");
+                    None
+                }
+            };
+
+        let mut tracked: HashMap<String, Tracked> = HashMap::new();
+        for name in names {
+            let state = self.get_host(name).await?.state;
+            on_change(name, &state);
+            tracked.insert(
+                name.clone(),
+                Tracked {
+                    seen_busy: BUSY_STATES.contains(&normalize(&state).as_str()),
+                    state,
+                },
+            );
+        }
+        let mut last_sync = Instant::now();
+
+        loop {
+            let grace_over = started.elapsed() >= SETTLE_GRACE;
+            let pending: Vec<String> = names
+                .iter()
+                .filter(|name| {
+                    let host = &tracked[*name];
+                    let allow_idle = match settle {
+                        Settle::AfterGrace => host.seen_busy || grace_over,
+                        Settle::AfterActivity => host.seen_busy,
+                    };
+                    !(is_terminal_state(&host.state) && allow_idle)
+                })
+                .cloned()
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ClientError::WaitTimeout {
+                    hosts: pending,
+                    waited: timeout,
+                });
+            }
+            let remaining = deadline - now;
+
+            let mut changes = Vec::new();
+            let resync = match events.as_mut() {
+                Some(ws) => {
+                    let tick = RESYNC_INTERVAL
+                        .saturating_sub(last_sync.elapsed())
+                        .min(remaining);
+                    tokio::select! {
+                        event = ws.recv() => match event {
+                            Some(WsEvent::HostStateChanged { host, to, .. }) => {
+                                changes.push((host, to));
+                                false
+                            }
+                            // Missed events may have included state changes
+                            Some(WsEvent::EventsDropped { .. }) => true,
+                            Some(_) => false,
+                            None => {
+                                debug!("event stream closed, polling host status");
+                                events = None;
+                                true
+                            }
+                        },
+                        () = sleep(tick) => true,
+                    }
+                }
+                None => {
+                    sleep(POLL_INTERVAL.min(remaining)).await;
+                    true
+                }
+            };
+
+            if resync {
+                for name in &pending {
+                    changes.push((name.clone(), self.get_host(name).await?.state));
+                }
+                last_sync = Instant::now();
+            }
+
+            for (host, state) in changes {
+                let Some(entry) = tracked.get_mut(&host) else {
+                    continue;
+                };
+                if BUSY_STATES.contains(&normalize(&state).as_str()) {
+                    entry.seen_busy = true;
+                }
+                if normalize(&state) != normalize(&entry.state) {
+                    on_change(&host, &state);
+                    entry.state = state;
+                }
+            }
+        }
+
+        let mut results = Vec::with_capacity(names.len());
+        for name in names {
+            results.push(self.get_host(name).await?);
+        }
+        Ok(results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_names_from_api_and_events_match() {
+        assert!(is_terminal_state("WaitingReboot"));
+        assert!(is_terminal_state("waiting_reboot"));
+        assert!(is_terminal_state("Idle"));
+        assert!(!is_terminal_state("PendingUpdates"));
+        assert!(!is_terminal_state("updating"));
+        assert!(is_failed_state("Failed"));
+        assert!(!is_failed_state("idle"));
+    }
+}
@@ -1,30 +1,39 @@
 //! WebSocket client for tendhost daemon
+//!
+//! Frames hold one [`EventEnvelope`] or, on a batched stream, an array of
+//! them; either way events are handed out one at a time.
 
 use std::time::Duration;
 
 use futures::StreamExt;
+use tokio::net::TcpStream;
 use tokio::sync::mpsc;
 use tokio::time::sleep;
-use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::{Message, handshake::client::Response};
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
 use url::Url;
 
-use tendhost_api::events::WsEvent;
+use tendhost_api::events::{EventEnvelope, MAX_BATCH_MS, WsEvent};
+use tendhost_api::version::API_VERSION_HEADER;
 
 use crate::error::{ClientError, Result};
+use crate::version::VersionCheck;
 
 /// WebSocket client for receiving live events from tendhost daemon
 #[derive(Debug)]
 pub struct WsClient {
     #[allow(dead_code)]
     url: Url,
-    receiver: mpsc::Receiver<WsEvent>,
+    receiver: mpsc::Receiver<EventEnvelope>,
     _task_handle: tokio::task::JoinHandle<()>,
 }
 
 impl WsClient {
     /// Connect to the WebSocket endpoint
     ///
-    /// Automatically reconnects on connection loss with exponential backoff.
+    /// Automatically reconnects on connection loss with exponential backoff,
+    /// but stops for good if the daemon speaks an unsupported protocol
+    /// version.
     ///
     /// # Errors
     /// Returns an error if the URL is invalid.
@@ -39,7 +48,7 @@ impl WsClient {
     ///
     /// while let Some(event) = client.recv().await {
     ///     match event {
-    ///         WsEvent::HostStateChanged { host, from, to } => {
+    ///         WsEvent::HostStateChanged { host, from, to, .. } => {
     ///             println!("{host}: {from} -> {to}");
     ///         }
     ///         _ => {}
@@ -48,14 +57,90 @@ impl WsClient {
     /// # Ok(())
     /// # }
     /// ```
-    #[allow(clippy::unused_async)]
     pub async fn connect(url: impl AsRef<str>) -> Result<Self> {
-        let url = Url::parse(url.as_ref())?;
+        Self::connect_with(url, VersionCheck::Enforce).await
+    }
+
+    /// Connect like [`connect`](Self::connect), checking the daemon's
+    /// protocol version as `check` says
+    ///
+    /// # Errors
+    /// Returns an error if the URL is invalid.
+    pub async fn connect_with(url: impl AsRef<str>, check: VersionCheck) -> Result<Self> {
+        Self::builder(url).version_check(check).connect().await
+    }
+
+    /// Start building a connection, e.g. one with batched events
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use tendhost_client::WsClient;
+    ///
+    /// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut client = WsClient::builder("ws://localhost:8080/ws/events")
+    ///     .batch(Duration::from_millis(250))
+    ///     .connect()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder(url: impl AsRef<str>) -> WsClientBuilder {
+        WsClientBuilder::new(url.as_ref())
+    }
+
+    /// Spawn the connection loop for `url`
+    fn spawn(url: Url, check: VersionCheck) -> Self {
         let (tx, rx) = mpsc::channel(100);
 
         let task_url = url.clone();
         let task_handle = tokio::spawn(async move {
-            Self::connection_loop(task_url, tx).await;
+            Self::connection_loop(task_url, check, tx).await;
+        });
+
+        Self {
+            url,
+            receiver: rx,
+            _task_handle: task_handle,
+        }
+    }
+
+    /// Connect to the WebSocket endpoint, failing if the first connection fails
+    ///
+    /// Unlike [`connect`](Self::connect), the initial handshake happens
+    /// before returning, so callers can fall back to polling when the
+    /// stream is unavailable. Later connection losses are retried as usual.
+    ///
+    /// # Errors
+    /// Returns an error if the URL is invalid, the connection cannot be
+    /// established, or the daemon speaks an unsupported protocol version.
+    pub async fn try_connect(url: impl AsRef<str>) -> Result<Self> {
+        Self::try_connect_with(url, VersionCheck::Enforce).await
+    }
+
+    /// Connect like [`try_connect`](Self::try_connect), checking the
+    /// daemon's protocol version as `check` says
+    ///
+    /// # Errors
+    /// Returns an error if the URL is invalid, the connection cannot be
+    /// established, or `check` refuses the daemon's protocol version.
+    pub async fn try_connect_with(url: impl AsRef<str>, check: VersionCheck) -> Result<Self> {
+        Self::builder(url).version_check(check).try_connect().await
+    }
+
+    /// Handshake with `url`, then keep the connection up in the background
+    async fn spawn_connected(url: Url, check: VersionCheck) -> Result<Self> {
+        let ws_stream = Self::handshake(&url, check).await?;
+        tracing::debug!("WebSocket connected to {}", url);
+
+        let (tx, rx) = mpsc::channel(100);
+        let task_url = url.clone();
+        let task_handle = tokio::spawn(async move {
+            match Self::receive(ws_stream, &tx).await {
+                Ok(()) => return,
+                Err(e) => tracing::warn!("WebSocket error: {}, reconnecting", e),
+            }
+            Self::connection_loop(task_url, check, tx).await;
         });
 
         Ok(Self {
@@ -68,22 +153,36 @@ impl WsClient {
     /// Receive the next event from the stream
     ///
     /// Returns `None` when the connection is closed and cannot be reconnected.
+    /// Use [`recv_envelope`](Self::recv_envelope) for the event's sequence
+    /// number and timestamp.
     pub async fn recv(&mut self) -> Option<WsEvent> {
+        self.recv_envelope().await.map(|envelope| envelope.event)
+    }
+
+    /// Receive the next event with its envelope
+    ///
+    /// Event types this client doesn't know arrive as [`WsEvent::Unknown`].
+    pub async fn recv_envelope(&mut self) -> Option<EventEnvelope> {
         self.receiver.recv().await
     }
 
     /// Connection loop with auto-reconnection
-    async fn connection_loop(url: Url, tx: mpsc::Sender<WsEvent>) {
+    async fn connection_loop(url: Url, check: VersionCheck, tx: mpsc::Sender<EventEnvelope>) {
         let mut backoff = Duration::from_secs(1);
         let max_backoff = Duration::from_secs(60);
 
         loop {
-            match Self::connect_and_receive(&url, &tx).await {
+            match Self::connect_and_receive(&url, check, &tx).await {
                 Ok(()) => {
                     // Connection closed gracefully
                     tracing::info!("WebSocket connection closed");
                     break;
                 }
+                Err(e @ ClientError::IncompatibleServer { .. }) => {
+                    // Reconnecting reaches the same daemon
+                    tracing::error!("WebSocket refused: {}", e);
+                    break;
+                }
                 Err(e) => {
                     tracing::warn!("WebSocket error: {}, reconnecting in {:?}", e, backoff);
                     sleep(backoff).await;
@@ -96,13 +195,36 @@ impl WsClient {
     }
 
     /// Connect and receive messages
-    async fn connect_and_receive(url: &Url, tx: &mpsc::Sender<WsEvent>) -> Result<()> {
-        let (ws_stream, _) = connect_async(url.as_str())
-            .await
-            .map_err(|e| ClientError::WebSocket(e.to_string()))?;
+    async fn connect_and_receive(
+        url: &Url,
+        check: VersionCheck,
+        tx: &mpsc::Sender<EventEnvelope>,
+    ) -> Result<()> {
+        let ws_stream = Self::handshake(url, check).await?;
 
         tracing::info!("WebSocket connected to {}", url);
 
+        Self::receive(ws_stream, tx).await
+    }
+
+    /// Open a connection and check the protocol version of the upgrade reply
+    async fn handshake(
+        url: &Url,
+        check: VersionCheck,
+    ) -> Result<WebSocketStream<MaybeTlsStream<TcpStream>>> {
+        let (ws_stream, response): (_, Response) = connect_async(url.as_str())
+            .await
+            .map_err(|e| ClientError::WebSocket(e.to_string()))?;
+        let version = response.headers().get(API_VERSION_HEADER);
+        check.check(version.map(|value| value.as_bytes()))?;
+        Ok(ws_stream)
+    }
+
+    /// Forward events from an established connection
+    async fn receive(
+        ws_stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+        tx: &mpsc::Sender<EventEnvelope>,
+    ) -> Result<()> {
         let (_write, mut read) = ws_stream.split();
 
         while let Some(msg) = read.next().await {
@@ -110,15 +232,10 @@ impl WsClient {
 
             match msg {
                 Message::Text(text) => {
-                    match serde_json::from_str::<WsEvent>(&text) {
-                        Ok(event) => {
-                            if tx.send(event).await.is_err() {
-                                // Receiver dropped, exit
-                                return Ok(());
-                            }
-                        }
-                        Err(e) => {
-                            tracing::warn!("Failed to parse event: {}", e);
+                    for envelope in parse_frame(&text) {
+                        if tx.send(envelope).await.is_err() {
+                            // Receiver dropped, exit
+                            return Ok(());
                         }
                     }
                 }
@@ -138,6 +255,90 @@ impl WsClient {
     }
 }
 
+/// The envelopes in a text frame, skipping those that can't be parsed
+fn parse_frame(text: &str) -> Vec<EventEnvelope> {
+    let parse = |value: serde_json::Value| {
+        serde_json::from_value::<EventEnvelope>(value)
+            .map_err(|e| tracing::warn!("Failed to parse event: {}", e))
+            .ok()
+    };
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(serde_json::Value::Array(values)) => values.into_iter().filter_map(parse).collect(),
+        Ok(value) => parse(value).into_iter().collect(),
+        Err(e) => {
+            tracing::warn!("Failed to parse event: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Builder for a [`WsClient`] connection
+#[derive(Debug, Clone)]
+pub struct WsClientBuilder {
+    url: String,
+    version_check: VersionCheck,
+    batch: Option<Duration>,
+}
+
+impl WsClientBuilder {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            version_check: VersionCheck::default(),
+            batch: None,
+        }
+    }
+
+    /// How the daemon's protocol version is checked (default: enforced)
+    #[must_use]
+    pub fn version_check(mut self, check: VersionCheck) -> Self {
+        self.version_check = check;
+        self
+    }
+
+    /// Have the daemon send the events of each `interval` together
+    ///
+    /// Fewer frames for slow links and clients, at the cost of up to
+    /// `interval` of delay; host failures still arrive right away. Capped
+    /// at five seconds; zero sends every event on its own, the default.
+    #[must_use]
+    pub fn batch(mut self, interval: Duration) -> Self {
+        self.batch = Some(interval);
+        self
+    }
+
+    /// Connect like [`WsClient::connect`]
+    ///
+    /// # Errors
+    /// Returns an error if the URL is invalid.
+    #[allow(clippy::unused_async)]
+    pub async fn connect(self) -> Result<WsClient> {
+        let check = self.version_check;
+        Ok(WsClient::spawn(self.url()?, check))
+    }
+
+    /// Connect like [`WsClient::try_connect`]
+    ///
+    /// # Errors
+    /// Returns an error if the URL is invalid, the connection cannot be
+    /// established, or the daemon's protocol version is refused.
+    pub async fn try_connect(self) -> Result<WsClient> {
+        let check = self.version_check;
+        WsClient::spawn_connected(self.url()?, check).await
+    }
+
+    /// The stream's URL, asking for batches if set
+    fn url(&self) -> Result<Url> {
+        let mut url = Url::parse(&self.url)?;
+        if let Some(interval) = self.batch {
+            let millis = u64::try_from(interval.as_millis()).unwrap_or(u64::MAX);
+            url.query_pairs_mut()
+                .append_pair("batch_ms", &millis.min(MAX_BATCH_MS).to_string());
+        }
+        Ok(url)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,6 +355,28 @@ mod tests {
         assert!(url.is_ok());
     }
 
+    #[test]
+    fn test_batch_interval_goes_into_the_url() {
+        let url = WsClient::builder("ws://localhost:8080/ws/events")
+            .batch(Duration::from_secs(60))
+            .url()
+            .unwrap();
+        assert_eq!(url.as_str(), "ws://localhost:8080/ws/events?batch_ms=5000");
+        let url = WsClient::builder("ws://localhost:8080/ws/events").url();
+        assert_eq!(url.unwrap().query(), None);
+    }
+
+    #[test]
+    fn test_frames_hold_one_envelope_or_an_array() {
+        let envelope = r#"{"v":1,"seq":7,"ts":"2026-10-14T03:00:00Z","event":{"type":"host_connected","host":"web"}}"#;
+        assert_eq!(parse_frame(envelope).len(), 1);
+
+        let batch = format!(r#"[{envelope},{{"seq":8}},{}]"#, envelope.replace("7", "9"));
+        let seqs: Vec<_> = parse_frame(&batch).iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, [7, 9]);
+        assert!(parse_frame("not json").is_empty());
+    }
+
     #[test]
     fn test_invalid_url() {
         let url = Url::parse("not a url");
@@ -0,0 +1,73 @@
+use std::sync::{Arc, Mutex};
+
+use axum::extract::{Query, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use tendhost_api::events::{EventEnvelope, WsEvent};
+use tendhost_api::pagination::{PageParams, Paginated, paginate_by_cursor};
+use tendhost_client::HttpClient;
+
+type Log = Arc<Mutex<Vec<EventEnvelope>>>;
+
+fn connected(seq: u64) -> EventEnvelope {
+    EventEnvelope::new(
+        seq,
+        WsEvent::HostConnected {
+            host: format!("host-{seq}"),
+        },
+    )
+}
+
+/// Serve `log` the way the daemon serves its event history
+async fn spawn_server(log: Log) -> String {
+    let app = Router::new()
+        .route(
+            "/events",
+            get(
+                |State(log): State<Log>, Query(params): Query<PageParams>| async move {
+                    let events = log.lock().unwrap().clone();
+                    Json::<Paginated<EventEnvelope>>(paginate_by_cursor(events, &params, |e| e.seq))
+                },
+            ),
+        )
+        .with_state(log);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+async fn test_event_cursor_continues_across_inserts() {
+    let log: Log = Arc::new(Mutex::new((1..=3).map(connected).collect()));
+    let url = spawn_server(log.clone()).await;
+    let client = HttpClient::new(&url).unwrap();
+
+    let first = client
+        .list_events(&PageParams::default().with_per_page(2))
+        .await
+        .unwrap();
+    let seqs: Vec<u64> = first.data.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, [1, 2]);
+    assert_eq!(first.pagination.total_items, 3);
+
+    log.lock().unwrap().extend((4..=5).map(connected));
+
+    let cursor = first.pagination.next_cursor.unwrap();
+    let second = client
+        .list_events(&PageParams::after(cursor).with_per_page(10))
+        .await
+        .unwrap();
+    let seqs: Vec<u64> = second.data.iter().map(|e| e.seq).collect();
+    assert_eq!(seqs, [3, 4, 5]);
+    assert!(matches!(&second.data[2].event, WsEvent::HostConnected { host } if host == "host-5"));
+
+    let caught_up = client
+        .list_events(&PageParams::after(second.pagination.next_cursor.unwrap()))
+        .await
+        .unwrap();
+    assert!(caught_up.data.is_empty());
+    assert_eq!(caught_up.pagination.next_cursor, Some(5));
+}
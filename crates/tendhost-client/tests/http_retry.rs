@@ -0,0 +1,190 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use tendhost_client::{ClientError, HttpClient};
+
+/// Number of requests each route has seen
+#[derive(Default)]
+struct Hits {
+    health: AtomicUsize,
+    update: AtomicUsize,
+    retry: AtomicUsize,
+    export: AtomicUsize,
+}
+
+/// Fail the first `failures` requests on a route with 502
+fn flaky(counter: &AtomicUsize, failures: usize, ok: impl IntoResponse) -> Response {
+    if counter.fetch_add(1, Ordering::SeqCst) < failures {
+        StatusCode::BAD_GATEWAY.into_response()
+    } else {
+        ok.into_response()
+    }
+}
+
+/// Start a server whose routes fail `failures` times before succeeding
+async fn spawn_server(failures: usize) -> (String, Arc<Hits>) {
+    let hits = Arc::new(Hits::default());
+    let app = Router::new()
+        .route(
+            "/health",
+            get(move |State(hits): State<Arc<Hits>>| async move {
+                flaky(
+                    &hits.health,
+                    failures,
+                    Json(serde_json::json!({ "status": "healthy", "version": "test" })),
+                )
+            }),
+        )
+        .route(
+            "/hosts/export",
+            get(|State(hits): State<Arc<Hits>>| async move {
+                // Drains once, asking for a second before the next attempt
+                if hits.export.fetch_add(1, Ordering::SeqCst) == 0 {
+                    (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        [(header::RETRY_AFTER, "1")],
+                    )
+                        .into_response()
+                } else {
+                    "name = \"web-1\"".into_response()
+                }
+            }),
+        )
+        .route(
+            "/hosts/{name}/update",
+            post(|State(hits): State<Arc<Hits>>| async move {
+                flaky(&hits.update, usize::MAX, StatusCode::ACCEPTED)
+            }),
+        )
+        .route(
+            "/hosts/{name}/retry",
+            post(move |State(hits): State<Arc<Hits>>| async move {
+                flaky(&hits.retry, failures, StatusCode::ACCEPTED)
+            }),
+        )
+        .route(
+            "/hosts/{name}",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                Json(serde_json::json!({}))
+            }),
+        )
+        .with_state(hits.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{addr}"), hits)
+}
+
+fn client(url: &str, retries: u32) -> HttpClient {
+    HttpClient::builder(url)
+        .timeout(Duration::from_millis(500))
+        .retries(retries)
+        .retry_backoff(Duration::from_millis(1), Duration::from_millis(5))
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_get_recovers_from_bad_gateway() {
+    let (url, hits) = spawn_server(2).await;
+
+    let health = client(&url, 2).health().await.unwrap();
+
+    assert_eq!(health.status, "healthy");
+    assert_eq!(hits.health.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_get_reports_attempts_when_exhausted() {
+    let (url, hits) = spawn_server(usize::MAX).await;
+
+    let err = client(&url, 2).health().await.unwrap_err();
+
+    let ClientError::RetriesExhausted { attempts, source } = err else {
+        panic!("expected exhausted retries, got {err:?}");
+    };
+    assert_eq!(attempts, 3);
+    assert!(matches!(*source, ClientError::Api { status: 502, .. }));
+    assert_eq!(hits.health.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_update_is_never_retried() {
+    let (url, hits) = spawn_server(0).await;
+
+    let err = client(&url, 5)
+        .update_host_packages("web-1", false)
+        .await
+        .unwrap_err();
+
+    assert!(matches!(err, ClientError::Api { status: 502, .. }));
+    assert_eq!(hits.update.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_idempotent_post_is_retried() {
+    let (url, hits) = spawn_server(1).await;
+
+    client(&url, 2).retry_host("web-1").await.unwrap();
+
+    assert_eq!(hits.retry.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_retry_after_is_honored() {
+    let (url, hits) = spawn_server(0).await;
+    let patient = HttpClient::builder(&url)
+        .retries(1)
+        .retry_backoff(Duration::from_millis(1), Duration::from_secs(5))
+        .build()
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    patient.export_hosts().await.unwrap();
+
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(hits.export.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_retry_after_is_capped_by_max_backoff() {
+    let (url, hits) = spawn_server(0).await;
+
+    let started = std::time::Instant::now();
+    client(&url, 1).export_hosts().await.unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert_eq!(hits.export.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_slow_response_times_out() {
+    let (url, _hits) = spawn_server(0).await;
+    let no_retry = HttpClient::builder(&url)
+        .timeout(Duration::from_millis(100))
+        .retries(0)
+        .build()
+        .unwrap();
+
+    let err = no_retry.get_host("web-1").await.unwrap_err();
+    assert!(matches!(err, ClientError::Timeout), "got {err:?}");
+
+    // Timeouts count as transient for idempotent requests
+    let err = client(&url, 1).get_host("web-1").await.unwrap_err();
+    assert!(
+        matches!(
+            err,
+            ClientError::RetriesExhausted { attempts: 2, ref source } if matches!(**source, ClientError::Timeout)
+        ),
+        "got {err:?}"
+    );
+}
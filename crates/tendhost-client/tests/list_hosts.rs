@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use futures::{StreamExt, TryStreamExt};
+use tendhost_client::{ClientError, HttpClient};
+
+/// Hosts the server lists, and how it misbehaves
+#[derive(Default)]
+struct Fleet {
+    hosts: Mutex<Vec<String>>,
+    /// `(page, per_page)` of every request
+    requests: Mutex<Vec<(u64, u64)>>,
+    /// Page answered with a 500 the first time it is asked for
+    failing_page: Mutex<Option<u64>>,
+    /// Hosts added one at a time, each right after page 1 was served
+    added_after_first_page: Mutex<Vec<String>>,
+}
+
+fn list(fleet: &Fleet, params: &HashMap<String, String>) -> Response {
+    let page: u64 = params.get("page").map_or(1, |p| p.parse().unwrap());
+    let per_page: u64 = params.get("per_page").map_or(50, |p| p.parse().unwrap());
+    fleet.requests.lock().unwrap().push((page, per_page));
+
+    let mut failing = fleet.failing_page.lock().unwrap();
+    if *failing == Some(page) {
+        *failing = None;
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    let mut hosts = fleet.hosts.lock().unwrap();
+    let total = hosts.len() as u64;
+    let data: Vec<_> = hosts
+        .iter()
+        .skip(((page - 1) * per_page) as usize)
+        .take(per_page as usize)
+        .map(|name| serde_json::json!({ "name": name, "state": "Idle", "reachable": true }))
+        .collect();
+    if page == 1 {
+        hosts.extend(fleet.added_after_first_page.lock().unwrap().pop());
+    }
+
+    Json(serde_json::json!({
+        "hosts": data,
+        "pagination": {
+            "page": page,
+            "per_page": per_page,
+            "total_items": total,
+            "total_pages": total.div_ceil(per_page),
+        },
+        "filters": { "tags": [], "sort": "name", "order": "asc" },
+    }))
+    .into_response()
+}
+
+/// Start a server listing `count` hosts named `host-01` and so on
+async fn spawn_server(count: usize) -> (String, Arc<Fleet>) {
+    let fleet = Arc::new(Fleet::default());
+    *fleet.hosts.lock().unwrap() = (1..=count).map(|i| format!("host-{i:02}")).collect();
+    let app =
+        Router::new()
+            .route(
+                "/hosts",
+                get(
+                    |State(fleet): State<Arc<Fleet>>,
+                     Query(params): Query<HashMap<String, String>>| async move {
+                        list(&fleet, &params)
+                    },
+                ),
+            )
+            .with_state(fleet.clone());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    (format!("http://{addr}"), fleet)
+}
+
+fn client(url: &str, retries: u32) -> HttpClient {
+    HttpClient::builder(url)
+        .retries(retries)
+        .retry_backoff(Duration::from_millis(1), Duration::from_millis(5))
+        .build()
+        .unwrap()
+}
+
+fn names(count: usize) -> Vec<String> {
+    (1..=count).map(|i| format!("host-{i:02}")).collect()
+}
+
+#[tokio::test]
+async fn test_all_walks_every_page_retrying_server_errors() {
+    let (url, fleet) = spawn_server(7).await;
+    *fleet.failing_page.lock().unwrap() = Some(2);
+
+    let hosts = client(&url, 1)
+        .list_hosts()
+        .page(3)
+        .per_page(3)
+        .all()
+        .await
+        .unwrap();
+
+    let got: Vec<_> = hosts.into_iter().map(|host| host.name).collect();
+    assert_eq!(got, names(7));
+    let mut requests = fleet.requests.lock().unwrap().clone();
+    requests.sort_unstable();
+    assert_eq!(requests, [(1, 3), (2, 3), (2, 3), (3, 3)]);
+}
+
+#[tokio::test]
+async fn test_all_fails_with_the_first_page_error() {
+    let (url, fleet) = spawn_server(7).await;
+    *fleet.failing_page.lock().unwrap() = Some(3);
+
+    let err = client(&url, 0)
+        .list_hosts()
+        .per_page(3)
+        .all()
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(err, ClientError::Api { status: 500, .. }),
+        "{err:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_all_starts_over_when_the_list_changes() {
+    let (url, fleet) = spawn_server(5).await;
+    *fleet.added_after_first_page.lock().unwrap() = vec!["host-06".to_string()];
+
+    let hosts = client(&url, 0)
+        .list_hosts()
+        .per_page(2)
+        .all()
+        .await
+        .unwrap();
+
+    let got: Vec<_> = hosts.into_iter().map(|host| host.name).collect();
+    assert_eq!(got, names(6));
+    // The first walk saw 5 hosts on page 1 and 6 on the next ones
+    let requests = fleet.requests.lock().unwrap();
+    assert_eq!(requests.iter().filter(|(page, _)| *page == 1).count(), 2);
+}
+
+#[tokio::test]
+async fn test_all_gives_up_when_the_list_keeps_changing() {
+    let (url, fleet) = spawn_server(5).await;
+    *fleet.added_after_first_page.lock().unwrap() =
+        vec!["host-07".to_string(), "host-06".to_string()];
+
+    let err = client(&url, 0)
+        .list_hosts()
+        .per_page(2)
+        .all()
+        .await
+        .unwrap_err();
+
+    assert!(
+        matches!(
+            err,
+            ClientError::ListChanged {
+                before: 6,
+                after: 7
+            }
+        ),
+        "{err:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_all_caps_the_page_size() {
+    let (url, fleet) = spawn_server(3).await;
+
+    let hosts = client(&url, 0).list_hosts().all().await.unwrap();
+    client(&url, 0)
+        .list_hosts()
+        .per_page(1000)
+        .all()
+        .await
+        .unwrap();
+
+    assert_eq!(hosts.len(), 3);
+    assert_eq!(*fleet.requests.lock().unwrap(), [(1, 200), (1, 200)]);
+}
+
+#[tokio::test]
+async fn test_stream_fetches_pages_as_they_are_consumed() {
+    let (url, fleet) = spawn_server(7).await;
+
+    let mut hosts = std::pin::pin!(client(&url, 0).list_hosts().per_page(3).stream());
+    let first = hosts.try_next().await.unwrap().unwrap();
+    assert_eq!(first.name, "host-01");
+    assert_eq!(fleet.requests.lock().unwrap().len(), 1);
+
+    let mut got = vec![first.name];
+    while let Some(host) = hosts.try_next().await.unwrap() {
+        got.push(host.name);
+    }
+    assert_eq!(got, names(7));
+    assert_eq!(*fleet.requests.lock().unwrap(), [(1, 3), (2, 3), (3, 3)]);
+}
+
+#[tokio::test]
+async fn test_stream_ends_after_an_error() {
+    let (url, fleet) = spawn_server(7).await;
+    *fleet.failing_page.lock().unwrap() = Some(2);
+
+    let results: Vec<_> = client(&url, 0)
+        .list_hosts()
+        .per_page(3)
+        .stream()
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(results.len(), 4);
+    assert!(results[..3].iter().all(Result::is_ok));
+    assert!(matches!(
+        results[3],
+        Err(ClientError::Api { status: 500, .. })
+    ));
+}
@@ -0,0 +1,130 @@
+use axum::Router;
+use axum::extract::ws::WebSocketUpgrade;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::{Json, middleware};
+use tendhost_api::version::{API_VERSION, API_VERSION_HEADER};
+use tendhost_client::{ClientError, HttpClient, SUPPORTED_API_VERSIONS, VersionCheck, WsClient};
+
+/// Start a daemon stand-in that tags every reply with API version `version`
+///
+/// Returns its HTTP base URL.
+async fn spawn_server(version: u32) -> String {
+    let app = Router::new()
+        .route(
+            "/health",
+            get(move || async move {
+                Json(serde_json::json!({
+                    "status": "healthy",
+                    "version": "9.9.9",
+                    "api_version": version,
+                }))
+            }),
+        )
+        .route(
+            "/hosts/{name}",
+            get(|| async { (StatusCode::NOT_FOUND, "{}").into_response() }),
+        )
+        .route(
+            "/ws/events",
+            get(|ws: WebSocketUpgrade| async move {
+                ws.on_upgrade(|mut socket| async move { while socket.recv().await.is_some() {} })
+            }),
+        )
+        .layer(middleware::map_response(
+            move |mut response: axum::response::Response| async move {
+                response
+                    .headers_mut()
+                    .insert(API_VERSION_HEADER, version.into());
+                response
+            },
+        ));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{addr}")
+}
+
+fn client(url: &str) -> HttpClient {
+    HttpClient::builder(url).retries(0).build().unwrap()
+}
+
+#[tokio::test]
+async fn test_supported_version_is_accepted() {
+    let url = spawn_server(API_VERSION).await;
+    let health = client(&url).health().await.unwrap();
+    assert_eq!(health.api_version, API_VERSION);
+
+    let ws = WsClient::try_connect(url.replace("http", "ws") + "/ws/events").await;
+    assert!(ws.is_ok(), "{:?}", ws.err());
+}
+
+#[tokio::test]
+async fn test_version_1_daemons_are_still_accepted() {
+    let url = spawn_server(1).await;
+    let health = client(&url).health().await.unwrap();
+    assert_eq!(health.api_version, 1);
+}
+
+#[tokio::test]
+async fn test_older_and_newer_daemons_are_refused() {
+    let older = *SUPPORTED_API_VERSIONS.start() - 1;
+    let newer = *SUPPORTED_API_VERSIONS.end() + 1;
+    for version in [older, newer] {
+        let url = spawn_server(version).await;
+        let err = client(&url).health().await.unwrap_err();
+        let ClientError::IncompatibleServer { server, supported } = &err else {
+            panic!("expected an incompatible server, got {err:?}");
+        };
+        assert_eq!(*server, version);
+        assert_eq!(*supported, SUPPORTED_API_VERSIONS);
+        let message = err.to_string();
+        assert!(
+            message.contains(&format!("API version {version}")),
+            "{message}"
+        );
+        assert!(
+            message.contains(&format!(
+                "supports versions {} to {API_VERSION}",
+                SUPPORTED_API_VERSIONS.start()
+            )),
+            "{message}"
+        );
+
+        // Error replies are checked too, before their status
+        let err = client(&url).get_host("web").await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::IncompatibleServer { .. }),
+            "{err:?}"
+        );
+
+        let ws_url = url.replace("http", "ws") + "/ws/events";
+        let err = WsClient::try_connect(&ws_url).await.unwrap_err();
+        assert!(
+            matches!(err, ClientError::IncompatibleServer { .. }),
+            "{err:?}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_version_check_can_be_ignored() {
+    let url = spawn_server(API_VERSION + 1).await;
+    let client = HttpClient::builder(&url)
+        .retries(0)
+        .ignore_api_version()
+        .build()
+        .unwrap();
+    let health = client.health().await.unwrap();
+    assert_eq!(health.api_version, API_VERSION + 1);
+    assert!(matches!(
+        client.get_host("web").await,
+        Err(ClientError::Api { status: 404, .. })
+    ));
+
+    let ws_url = url.replace("http", "ws") + "/ws/events";
+    let ws = WsClient::try_connect_with(&ws_url, VersionCheck::Ignore).await;
+    assert!(ws.is_ok(), "{:?}", ws.err());
+}
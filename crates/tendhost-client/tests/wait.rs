@@ -0,0 +1,64 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use axum::extract::{Path, State};
+use axum::routing::get;
+use axum::{Json, Router};
+use tendhost_client::{ClientError, HttpClient};
+
+/// Start a server without an event stream whose hosts report `states` on
+/// successive status requests, repeating the last one
+async fn spawn_server(states: &'static [&'static str]) -> String {
+    let polls = Arc::new(AtomicUsize::new(0));
+    let app = Router::new()
+        .route(
+            "/hosts/{name}",
+            get(
+                move |State(polls): State<Arc<AtomicUsize>>, Path(name): Path<String>| async move {
+                    let n = polls.fetch_add(1, Ordering::SeqCst).min(states.len() - 1);
+                    Json(serde_json::json!({ "name": name, "state": states[n], "reachable": true }))
+                },
+            ),
+        )
+        .with_state(polls);
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+async fn test_wait_falls_back_to_polling_until_settled() {
+    let url = spawn_server(&["Updating", "Updating", "Failed"]).await;
+    let client = HttpClient::new(&url).unwrap();
+
+    let mut seen = Vec::new();
+    let host = client
+        .wait_for_state("debian-vm", Duration::from_secs(10), |host, state| {
+            seen.push(format!("{host}: {state}"));
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(host.state, "Failed");
+    assert_eq!(seen, ["debian-vm: Updating", "debian-vm: Failed"]);
+}
+
+#[tokio::test]
+async fn test_wait_times_out_on_busy_host() {
+    let url = spawn_server(&["Rebooting"]).await;
+    let client = HttpClient::new(&url).unwrap();
+
+    let err = client
+        .wait_for_state("debian-vm", Duration::from_millis(1500), |_, _| {})
+        .await
+        .unwrap_err();
+
+    match err {
+        ClientError::WaitTimeout { hosts, .. } => assert_eq!(hosts, ["debian-vm"]),
+        other => panic!("expected wait timeout, got {other:?}"),
+    }
+}
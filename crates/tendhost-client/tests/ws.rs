@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use axum::Router;
+use axum::extract::RawQuery;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::routing::get;
+use tendhost_api::events::{ENVELOPE_VERSION, WsEvent};
+use tendhost_client::WsClient;
+
+/// Frames a newer daemon might send: a known event, an event type this
+/// client has never heard of, and a frame that isn't an envelope at all
+const FRAMES: [&str; 3] = [
+    r#"{"v":1,"seq":7,"ts":"2026-10-14T03:00:00Z","event":{"type":"host_connected","host":"web"}}"#,
+    r#"{"v":1,"seq":8,"ts":"2026-10-14T03:00:01Z","event":{"type":"fleet_update_progress","completed":1}}"#,
+    r#"{"seq":9,"type":"HostConnected","host":"db"}"#,
+];
+
+async fn send_frames(mut socket: WebSocket) {
+    for frame in FRAMES {
+        if socket.send(Message::Text(frame.into())).await.is_err() {
+            return;
+        }
+    }
+    // Keep the connection open so the client doesn't reconnect
+    while socket.recv().await.is_some() {}
+}
+
+/// Two envelopes as one frame, sent only to clients asking for batches
+async fn send_batch(mut socket: WebSocket, query: Option<String>) {
+    if query.as_deref() == Some("batch_ms=250") {
+        let batch = format!(
+            "[{},{}]",
+            FRAMES[0],
+            FRAMES[0].replace("\"seq\":7", "\"seq\":10")
+        );
+        if socket.send(Message::Text(batch.into())).await.is_err() {
+            return;
+        }
+    }
+    while socket.recv().await.is_some() {}
+}
+
+async fn spawn_server() -> String {
+    let app = Router::new()
+        .route(
+            "/ws/events",
+            get(|ws: WebSocketUpgrade| async move { ws.on_upgrade(send_frames) }),
+        )
+        .route(
+            "/ws/batched",
+            get(
+                |ws: WebSocketUpgrade, RawQuery(query): RawQuery| async move {
+                    ws.on_upgrade(move |socket| send_batch(socket, query))
+                },
+            ),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("ws://{addr}/ws/events")
+}
+
+#[tokio::test]
+async fn test_envelopes_and_unknown_events_are_delivered() {
+    let url = spawn_server().await;
+    let mut client = WsClient::try_connect(&url).await.unwrap();
+
+    let first = client.recv_envelope().await.unwrap();
+    assert_eq!(first.v, ENVELOPE_VERSION);
+    assert_eq!(first.seq, 7);
+    assert_eq!(first.ts.to_rfc3339(), "2026-10-14T03:00:00+00:00");
+    assert!(matches!(first.event, WsEvent::HostConnected { ref host } if host == "web"));
+
+    // An unknown type doesn't end the stream; the bare frame is skipped
+    let second = client.recv_envelope().await.unwrap();
+    assert_eq!(second.seq, 8);
+    assert!(matches!(second.event, WsEvent::Unknown));
+    let next = tokio::time::timeout(Duration::from_millis(200), client.recv()).await;
+    assert!(next.is_err(), "unexpected event: {next:?}");
+}
+
+#[tokio::test]
+async fn test_batched_frames_are_split_into_events() {
+    let url = spawn_server().await.replace("/ws/events", "/ws/batched");
+    let mut client = WsClient::builder(&url)
+        .batch(Duration::from_millis(250))
+        .try_connect()
+        .await
+        .unwrap();
+
+    let first = client.recv_envelope().await.unwrap();
+    let second = client.recv_envelope().await.unwrap();
+    assert_eq!((first.seq, second.seq), (7, 10));
+    assert!(matches!(second.event, WsEvent::HostConnected { ref host } if host == "web"));
+}
@@ -2,27 +2,74 @@
 //!
 //! Manages state machine for a single host and handles updates.
 
+use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::Duration;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 use kameo::actor::{ActorRef, WeakActorRef};
 use kameo::error::ActorStopReason;
 use kameo::message::{Context, Message};
 use kameo::prelude::*;
 use tokio::sync::broadcast;
-use tracing::{error, info, warn};
+use tokio::task::AbortHandle;
+use tracing::{Instrument, Span, debug, error, info, info_span, warn};
 
 use tendhost_api::events::WsEvent;
-use tendhost_exec::traits::RemoteExecutor;
+use tendhost_api::requests::UpdateScope;
+use tendhost_api::responses::{UpdateHistoryEntry, UpdatePreview};
+use tendhost_exec::ShellCommand;
+use tendhost_exec::recording::{CommandHistory, CommandRecord};
+use tendhost_exec::stats::{ExecutorStats, ExecutorStatsSnapshot};
+use tendhost_exec::traits::{RemoteExecutor, RemoteExecutorExt};
+use tendhost_inventory::diff::DEFAULT_DISK_DELTA_THRESHOLD;
+use tendhost_inventory::osquery::{parse_version, version_supported};
+use tendhost_inventory::{HostInventory, InventoryCollector, InventoryDiff};
+use tendhost_pkg::check_disk_space;
+use tendhost_pkg::error::PackageError;
+use tendhost_pkg::escalation::PrivilegeEscalation;
+use tendhost_pkg::kernel::is_reboot_package;
+use tendhost_pkg::lock::{LockHolder, retry_while_locked};
 use tendhost_pkg::traits::PackageManager;
+use tendhost_pkg::types::{RestartRequirement, UpdateResult as PkgUpdateResult, UpgradablePackage};
 
-use crate::config::HostConfig;
+use crate::config::{HealthCheckSpec, HostConfig};
 use crate::error::CoreError;
+use crate::facts::{FACTS_PROBE, FactKey, Facts, parse_probe};
 use crate::message::{
-    Acknowledge, GetState, GetStatus, HealthCheck, HealthCheckResult, HostStatus, InventoryResult,
-    QueryInventory, RebootIfRequired, Retry, StartUpdate, UpdateResult,
+    Acknowledge, CancelUpdate, CheckOutcome, CollectInventory, GetCommandHistory, GetExecutorStats,
+    GetInventoryDiff, GetState, GetStatus, GetTransitionHistory, GetUpdateHistory, HealthCheck,
+    HealthCheckResult, HostStatus, InstallOsquery, InventoryResult, OsqueryInstall, PreviewUpdate,
+    QueryInventory, RebootIfRequired, ReleaseReservation, ReserveForUpdate, Restore, Retry,
+    SetPaused, Snapshot, StartUpdate, UpdateConfig, UpdateResult,
 };
-use crate::state::{FailedStateContext, HostState, PendingUpdatesContext};
+use crate::snapshot::{CheckSnapshot, HostSnapshot};
+use crate::state::{
+    BreakerState, CircuitBreaker, FailedStateContext, FailureKind, HostState, Initiator,
+    MultiSourceInventory, OperationOwner, PendingUpdatesContext, RetryAttempt, StateTransition,
+};
+
+/// How long osquery results are cached between inventory collections,
+/// unless the query sets its own TTL
+const INVENTORY_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// Inventory tables a package update changes
+const PACKAGE_TABLES: &[&str] = &["deb_packages", "rpm_packages", "kernel_info"];
+
+/// Inventory tables a compose stack update changes
+const DOCKER_TABLES: &[&str] = &["docker_*"];
+
+/// Timeout for a single reachability probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long probed facts are kept before a package query probes again
+const FACTS_MAX_AGE: Duration = Duration::from_secs(3600);
+
+/// State transitions remembered per host
+const TRANSITION_HISTORY_LEN: usize = 100;
+
+/// State transitions included in `HostStatus`
+const RECENT_TRANSITIONS: usize = 5;
 
 /// Arguments for spawning a `HostActor`
 pub struct HostActorArgs {
@@ -32,8 +79,108 @@ pub struct HostActorArgs {
     pub executor: Arc<dyn RemoteExecutor>,
     /// Package manager implementation
     pub package_manager: Arc<dyn PackageManager>,
+    /// Docker compose manager, if the host has compose stacks
+    pub compose_manager: Option<Arc<dyn PackageManager>>,
     /// Event broadcast sender for WebSocket
     pub event_tx: broadcast::Sender<WsEvent>,
+    /// Recent commands run through the executor
+    pub command_history: Arc<CommandHistory>,
+    /// Statistics of the commands run through the executor
+    pub executor_stats: Arc<ExecutorStats>,
+}
+
+/// Sent by the background update task when the package manager returns
+struct UpdateFinished {
+    /// Identifies the update this result belongs to
+    id: u64,
+    /// Outcome of the hooks and the package manager
+    result: Result<PkgUpdateResult, CoreError>,
+    /// Class of a package manager failure, classified before it was flattened
+    failure_kind: Option<FailureKind>,
+    /// Output of the failed package manager command
+    failure_output: Option<String>,
+    /// Reboot and service restarts needed afterwards
+    restart: RestartRequirement,
+    /// Services the task restarted because of `auto_restart_services`
+    restarted_services: Vec<String>,
+    /// Problems that didn't stop the update, e.g. low disk space in a dry run
+    warnings: Vec<String>,
+}
+
+/// Sent by the retry timer once an automatic retry's backoff elapsed
+struct AutoRetry {
+    /// Attempt number (1-based), to ignore timers of abandoned retries
+    attempt: u32,
+}
+
+/// Sent by the probe timer to run a reachability check
+struct Probe;
+
+/// Sent by the background escalation check with its outcome
+struct SudoChecked {
+    /// `None` if the host could not be asked
+    available: Option<bool>,
+}
+
+/// Sent by the background osquery install with its outcome
+struct OsqueryInstallFinished {
+    /// State the host returns to once installed
+    previous: HostState,
+    /// osquery version read after installing, or why installing failed
+    result: Result<Option<String>, PackageError>,
+    /// Caller waiting for the outcome
+    reply: Option<ReplySender<Result<OsqueryInstall, CoreError>>>,
+}
+
+/// Sent by the background probe task with its outcome
+struct ProbeFinished {
+    /// `Err` carries the reason the host could not be reached
+    result: Result<(), String>,
+}
+
+/// Update task currently running in the background
+struct RunningUpdate {
+    /// Identifies this update among earlier, cancelled ones
+    id: u64,
+    /// Request that started the update, re-run by automatic retries
+    request: StartUpdate,
+    /// Manager performing the update, used for cancellation
+    manager: Arc<dyn PackageManager>,
+    /// Handle for aborting the task
+    abort: AbortHandle,
+    /// Caller waiting for the update result
+    reply: Option<ReplySender<Result<UpdateResult, CoreError>>>,
+}
+
+/// Operation that automatic retries re-run
+#[derive(Debug, Clone)]
+enum RetryOperation {
+    /// Query upgradable packages
+    Query,
+    /// Query, then update with the original request if updates are pending
+    Update(StartUpdate),
+}
+
+/// Packages and counts found by an upgradable-packages query
+#[derive(Debug, Clone)]
+struct UpdateCheck {
+    pending: u32,
+    security: u32,
+    packages: Vec<UpgradablePackage>,
+    checked_at: DateTime<Utc>,
+}
+
+/// Automatic retries of a failed operation, kept until it succeeds or the
+/// retries are abandoned
+struct RetrySequence {
+    /// What each attempt re-runs
+    operation: RetryOperation,
+    /// Attempts that failed, oldest first
+    attempts: Vec<RetryAttempt>,
+    /// Start of the attempt in progress
+    current: Option<DateTime<Utc>>,
+    /// Timer for the next attempt
+    timer: Option<AbortHandle>,
 }
 
 /// Per-host actor managing state machine and operations
@@ -50,10 +197,68 @@ pub struct HostActor {
     executor: Arc<dyn RemoteExecutor>,
     /// Package manager implementation
     package_manager: Arc<dyn PackageManager>,
+    /// Docker compose manager, if the host has compose stacks
+    compose_manager: Option<Arc<dyn PackageManager>>,
+    /// osquery inventory collector
+    inventory: InventoryCollector,
+    /// Inventory tables changed by updates, invalidated before the next
+    /// collection
+    stale_inventory: Vec<&'static str>,
+    /// Recent commands run through the executor
+    command_history: Arc<CommandHistory>,
+    /// Statistics of the commands run through the executor
+    executor_stats: Arc<ExecutorStats>,
     /// Event broadcast sender
     event_tx: broadcast::Sender<WsEvent>,
     /// Last successful update timestamp
     last_updated: Option<DateTime<Utc>>,
+    /// Update task in progress, if any
+    running_update: Option<RunningUpdate>,
+    /// Counter for identifying update tasks
+    next_update_id: u64,
+    /// Whether the host answered recent reachability probes
+    reachable: bool,
+    /// Last time the host answered a probe
+    last_seen: Option<DateTime<Utc>>,
+    /// Consecutive failed probes
+    probe_failures: u32,
+    /// Fails operations fast after repeated connection failures
+    breaker: CircuitBreaker,
+    /// Whether a probe is currently running
+    probe_in_flight: bool,
+    /// Periodic probe timer
+    probe_timer: Option<AbortHandle>,
+    /// Automatic retries in progress, if any
+    retry: Option<RetrySequence>,
+    /// Reboot or service restarts still needed after the last update
+    needs_restart: Option<RestartRequirement>,
+    /// Whether the escalation command (e.g. `sudo -n`) works for the SSH
+    /// user, once checked
+    sudo_available: Option<bool>,
+    /// Finished updates, oldest first, bounded by the policy
+    update_history: VecDeque<UpdateHistoryEntry>,
+    /// Result of the last health check
+    last_health_check: Option<HealthCheckResult>,
+    /// Result of the last successful upgradable-packages query
+    last_check: Option<UpdateCheck>,
+    /// Latest full inventory, compared against the next collection
+    last_inventory: Option<HostInventory>,
+    /// Changes between the last two inventory collections
+    inventory_diff: Option<InventoryDiff>,
+    /// Who the current operation or reservation belongs to
+    owner: Option<OperationOwner>,
+    /// Whether `owner` holds a reservation, kept between operations
+    reserved: bool,
+    /// Whether automation leaves the host alone
+    paused: bool,
+    /// Latest state transitions, oldest first; lost when the actor restarts
+    transitions: VecDeque<StateTransition>,
+    /// Package sources that failed in the last query while others answered
+    warnings: Vec<String>,
+    /// Facts found out so far, apart from those of the package manager
+    facts: Facts,
+    /// When the facts probe last answered; `None` once the facts are stale
+    facts_probed_at: Option<std::time::Instant>,
 }
 
 impl HostActor {
@@ -69,8 +274,83 @@ impl HostActor {
         self.state
     }
 
+    /// Facts found out so far, with the distribution the package manager
+    /// detected
+    fn facts(&self) -> Facts {
+        let mut facts = self.facts.clone();
+        if let Some(distro) = self.package_manager.distro() {
+            for (key, value) in [
+                (FactKey::OsId, &distro.id),
+                (FactKey::OsVersion, &distro.version_id),
+            ] {
+                if !value.is_empty() {
+                    facts.insert(key, value.clone());
+                }
+            }
+        }
+        facts
+    }
+
+    /// Run the facts probe unless its last answer is still fresh
+    ///
+    /// Facts are a side matter, so a failing probe is only logged and
+    /// tried again next time.
+    async fn refresh_facts(&mut self) {
+        if self
+            .facts_probed_at
+            .is_some_and(|at| at.elapsed() < FACTS_MAX_AGE)
+        {
+            return;
+        }
+        match self
+            .executor
+            .run_with_timeout(FACTS_PROBE, PROBE_TIMEOUT)
+            .await
+        {
+            Ok(result) if result.success() => {
+                self.facts.extend(parse_probe(&result.stdout));
+                self.facts_probed_at = Some(std::time::Instant::now());
+            }
+            Ok(result) => {
+                debug!(host = %self.config.name, status = result.status, "facts probe failed");
+            }
+            Err(e) => debug!(host = %self.config.name, error = %e, "facts probe failed"),
+        }
+    }
+
+    /// Installed osquery version, if the facts probe found one
+    fn osquery_version(&self) -> Option<&str> {
+        self.facts
+            .get(&FactKey::Osquery)
+            .map(String::as_str)
+            .filter(|version| *version != "none")
+    }
+
+    /// Operating system name, from the detected distribution or else the
+    /// last collected inventory
+    fn os(&self) -> Option<String> {
+        if let Some(distro) = self.package_manager.distro() {
+            return Some(distro.to_string());
+        }
+        let system = &self.last_inventory.as_ref()?.system;
+        let os = format!("{} {}", system.os_name, system.os_version);
+        let os = os.trim();
+        (!os.is_empty()).then(|| os.to_string())
+    }
+
+    /// Span that tags everything done for this host with its name
+    fn span(&self) -> Span {
+        info_span!("host", host = %self.config.name)
+    }
+
     /// Transition to a new state with validation and event emission
-    fn transition_to(&mut self, new_state: HostState) -> Result<(), CoreError> {
+    ///
+    /// `trigger` names the message whose handler made the change.
+    fn transition_to(
+        &mut self,
+        new_state: HostState,
+        trigger: &'static str,
+    ) -> Result<(), CoreError> {
         if !self.state.can_transition_to(new_state) {
             return Err(CoreError::InvalidTransition {
                 from: self.state,
@@ -80,6 +360,8 @@ impl HostActor {
 
         let old_state = self.state;
         self.state = new_state;
+        self.record_transition(old_state, trigger, None);
+        self.release_finished_operation();
 
         info!(
             host = %self.config.name,
@@ -90,9 +372,10 @@ impl HostActor {
 
         // Emit WebSocket event
         let event = WsEvent::HostStateChanged {
-            host: self.config.name.clone(),
+            host: self.config.name.to_string(),
             from: old_state.to_string(),
             to: new_state.to_string(),
+            failure_kind: None,
         };
         // Ignore send errors (no subscribers is fine)
         let _ = self.event_tx.send(event);
@@ -101,27 +384,1185 @@ impl HostActor {
     }
 
     /// Transition to `Failed` state, preserving error context
-    fn fail_with_error(&mut self, error: impl Into<String>) {
+    ///
+    /// Abandons any automatic retries; use [`HostActor::fail_operation`]
+    /// for failures that may be retried.
+    /// `output` is the failed command's output; its last
+    /// `failure_output_lines` lines are kept in the failure context.
+    fn fail_with_error(
+        &mut self,
+        error: impl Into<String>,
+        output: Option<&str>,
+        kind: FailureKind,
+        trigger: &'static str,
+    ) {
+        self.cancel_retry();
         let previous = self.state;
         let error_msg = error.into();
-        let context = FailedStateContext::new(previous, error_msg.clone());
+        let mut context = FailedStateContext::new(previous, error_msg.clone()).with_kind(kind);
+        if let Some(output) = output {
+            context = context.with_output(output, self.config.policy.failure_output_len());
+        }
         self.failed_context = Some(context);
         self.state = HostState::Failed;
+        self.record_transition(previous, trigger, Some(error_msg.clone()));
+        self.release_finished_operation();
 
         error!(
             host = %self.config.name,
             previous_state = %previous,
             error = %error_msg,
+            %kind,
             "host entered failed state"
         );
 
         let event = WsEvent::HostStateChanged {
-            host: self.config.name.clone(),
+            host: self.config.name.to_string(),
             from: previous.to_string(),
             to: "failed".to_string(),
+            failure_kind: Some(kind.to_string()),
         };
         let _ = self.event_tx.send(event);
     }
+
+    /// Remember the change from `from` to the current state, caused by `trigger`
+    ///
+    /// Runs before a finished operation releases the host, so the
+    /// transition out of a busy state still names its owner.
+    fn record_transition(&mut self, from: HostState, trigger: &'static str, error: Option<String>) {
+        if self.transitions.len() == TRANSITION_HISTORY_LEN {
+            self.transitions.pop_front();
+        }
+        self.transitions.push_back(StateTransition {
+            from,
+            to: self.state,
+            at: Utc::now(),
+            trigger: trigger.to_string(),
+            initiator: self.owner.as_ref().map(|o| o.initiator.to_string()),
+            error,
+        });
+    }
+
+    /// Transition to `Failed` and schedule an automatic retry if the policy allows
+    ///
+    /// A failure during an automatic retry continues that retry sequence, so
+    /// its original operation and attempt history are kept.
+    fn fail_operation(
+        &mut self,
+        error: impl Into<String>,
+        output: Option<&str>,
+        kind: FailureKind,
+        operation: RetryOperation,
+        trigger: &'static str,
+        actor_ref: WeakActorRef<Self>,
+    ) {
+        let error = error.into();
+        if kind == FailureKind::Connection {
+            self.record_probe(Err(error.clone()));
+        }
+        let sequence = self.retry.take();
+        self.fail_with_error(&error, output, kind, trigger);
+
+        let mut sequence = sequence.unwrap_or(RetrySequence {
+            operation,
+            attempts: Vec::new(),
+            current: None,
+            timer: None,
+        });
+        if let Some(attempted_at) = sequence.current.take() {
+            sequence.attempts.push(RetryAttempt {
+                attempted_at,
+                error: error.clone(),
+            });
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let attempts = sequence.attempts.len() as u32;
+
+        let retry = self.config.policy.auto_retry;
+        let next = (!self.paused && retry.is_enabled() && retry.retries(kind))
+            .then_some(attempts + 1)
+            .filter(|attempt| *attempt <= retry.max_attempts());
+
+        let Some(context) = self.failed_context.as_mut() else {
+            return;
+        };
+        context.retry_count = attempts;
+        context.attempts.clone_from(&sequence.attempts);
+
+        let Some(attempt) = next else {
+            if attempts > 0 && !self.paused {
+                warn!(host = %self.config.name, attempts, "automatic retries exhausted");
+                let _ = self.event_tx.send(WsEvent::RetriesExhausted {
+                    host: self.config.name.to_string(),
+                    attempts,
+                });
+            }
+            return;
+        };
+
+        let delay = retry.backoff(attempt, kind);
+        context.next_retry_at = chrono::TimeDelta::from_std(delay)
+            .ok()
+            .map(|delay| Utc::now() + delay);
+
+        sequence.timer = Some(schedule_auto_retry(attempt, delay, actor_ref));
+        self.retry = Some(sequence);
+
+        info!(
+            host = %self.config.name,
+            attempt,
+            delay_secs = delay.as_secs(),
+            "automatic retry scheduled"
+        );
+        let _ = self.event_tx.send(WsEvent::RetryScheduled {
+            host: self.config.name.to_string(),
+            attempt,
+            max_retries: retry.max_attempts(),
+            delay_secs: delay.as_secs(),
+        });
+    }
+
+    /// Refuse an operation by `initiator` if someone else owns the host
+    fn check_owner(&self, initiator: Initiator) -> Result<(), CoreError> {
+        match &self.owner {
+            Some(owner) if owner.initiator != initiator => Err(CoreError::HostOwned(owner.clone())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Refuse an operation on a paused host unless a person forced it
+    fn check_paused(&self, initiator: Initiator, force: bool) -> Result<(), CoreError> {
+        if !self.paused || (force && initiator == Initiator::ManualApi) {
+            return Ok(());
+        }
+        Err(CoreError::HostPaused(match initiator {
+            Initiator::ManualApi => format!("{}; force the update to run it anyway", self.name()),
+            other => format!("{}; not touched by {other}", self.name()),
+        }))
+    }
+
+    /// Transition into a busy state on behalf of `initiator`
+    ///
+    /// A reservation keeps its owner, and with it the time it was made.
+    fn begin(
+        &mut self,
+        new_state: HostState,
+        initiator: Initiator,
+        trigger: &'static str,
+    ) -> Result<(), CoreError> {
+        // Claimed first so the recorded transition names the owner
+        let claimed = self.owner.is_none();
+        if claimed {
+            self.owner = Some(OperationOwner::new(initiator));
+        }
+        let result = self.transition_to(new_state, trigger);
+        if result.is_err() && claimed {
+            self.owner = None;
+        }
+        result
+    }
+
+    /// Drop the owner once the host is no longer busy, unless reserved
+    fn release_finished_operation(&mut self) {
+        if !self.state.is_busy() && !self.reserved {
+            self.owner = None;
+        }
+    }
+
+    /// Abandon automatic retries, stopping a scheduled attempt
+    fn cancel_retry(&mut self) {
+        if let Some(timer) = self.retry.take().and_then(|sequence| sequence.timer) {
+            timer.abort();
+        }
+    }
+}
+
+impl HostActor {
+    /// Validate a single-stack update request and return the compose manager
+    async fn stack_manager(
+        &self,
+        stack: &str,
+        dry_run: bool,
+    ) -> Result<Arc<dyn PackageManager>, CoreError> {
+        let Some(ref compose) = self.compose_manager else {
+            return Err(CoreError::ConfigError(format!(
+                "host '{}' has no compose stacks configured",
+                self.config.name
+            )));
+        };
+        if dry_run {
+            return Err(CoreError::ConfigError(
+                "dry runs are not supported for single stack updates".to_string(),
+            ));
+        }
+
+        let stacks = compose
+            .list_stacks()
+            .await
+            .map_err(|e| CoreError::PackageError(e.to_string()))?;
+        if !stacks.iter().any(|s| s == stack) {
+            return Err(CoreError::ConfigError(
+                PackageError::UnknownStack {
+                    stack: stack.to_string(),
+                    valid: stacks,
+                }
+                .to_string(),
+            ));
+        }
+
+        Ok(compose.clone())
+    }
+
+    /// Validate the packages a selective update names
+    ///
+    /// They have to be among the upgradable packages of the last query, so
+    /// nothing gets newly installed by a stale or mistyped selection.
+    fn check_selected_packages(&self, request: &StartUpdate) -> Result<(), CoreError> {
+        if request.packages.is_empty() {
+            return Ok(());
+        }
+        if request.stack.is_some() {
+            return Err(CoreError::ConfigError(
+                "an update cannot select both packages and a stack".to_string(),
+            ));
+        }
+
+        let upgradable = self.last_check.as_ref().map_or(&[][..], |c| &c.packages);
+        let unknown: Vec<&str> = request
+            .packages
+            .iter()
+            .filter(|name| !upgradable.iter().any(|p| &p.name == *name))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(CoreError::ConfigError(format!(
+                "not upgradable on host '{}': {}",
+                self.config.name,
+                unknown.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// (Re)start the periodic reachability probe according to the policy
+    fn start_probe_timer(&mut self, actor_ref: WeakActorRef<Self>) {
+        if let Some(timer) = self.probe_timer.take() {
+            timer.abort();
+        }
+
+        let Some(period) = self.config.policy.health_check_interval() else {
+            return;
+        };
+
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            loop {
+                interval.tick().await;
+                let Some(actor_ref) = actor_ref.upgrade() else {
+                    break;
+                };
+                if actor_ref.tell(Probe).await.is_err() {
+                    break;
+                }
+            }
+        });
+        self.probe_timer = Some(task.abort_handle());
+    }
+
+    /// Check passwordless escalation in the background if the package
+    /// manager needs it
+    fn spawn_sudo_check(&self, actor_ref: WeakActorRef<Self>) {
+        let escalation = self.package_manager.escalation();
+        if escalation.is_none() {
+            return;
+        }
+
+        let executor = self.executor.clone();
+        let host = self.config.name.clone();
+        tokio::spawn(
+            async move {
+                let available = check_escalation(executor.as_ref(), &escalation, &host).await;
+                if let Some(actor_ref) = actor_ref.upgrade() {
+                    let _ = actor_ref.tell(SudoChecked { available }).await;
+                }
+            }
+            .instrument(self.span()),
+        );
+    }
+
+    /// Refuse to update through `manager` when its escalation would prompt
+    ///
+    /// Checks now if the background check has not answered yet. Without
+    /// this, `sudo` waits for a password until the command times out.
+    async fn ensure_sudo(&mut self, manager: &dyn PackageManager) -> Result<(), PackageError> {
+        // A deferred manager only knows its escalation once detected
+        manager.prepare().await?;
+        let escalation = manager.escalation();
+        if escalation.is_none() {
+            return Ok(());
+        }
+        if self.sudo_available.is_none() {
+            self.sudo_available =
+                check_escalation(self.executor.as_ref(), &escalation, &self.config.name).await;
+        }
+        if self.sudo_available == Some(false) {
+            return Err(PackageError::PermissionDenied(format!(
+                "passwordless {} not available for user {}",
+                escalation.tool(),
+                self.config.user
+            )));
+        }
+        Ok(())
+    }
+
+    /// Refuse to contact the host while its circuit breaker is open
+    fn check_breaker(&self) -> Result<(), CoreError> {
+        if self.config.policy.circuit_breaker_threshold().is_none() {
+            return Ok(());
+        }
+        match self.breaker.state(Utc::now()) {
+            BreakerState::Open(retry_at) => Err(CoreError::HostUnreachable {
+                host: self.config.name.to_string(),
+                retry_at,
+            }),
+            BreakerState::Closed | BreakerState::HalfOpen => Ok(()),
+        }
+    }
+
+    /// Why a manual update would be refused right now, leaving out busy
+    /// states and an open circuit breaker
+    ///
+    /// Checks only what the actor knows; the disk space is up to the caller.
+    fn update_blockers(&self) -> Vec<String> {
+        let mut blockers: Vec<String> = [
+            self.check_owner(Initiator::ManualApi),
+            self.check_paused(Initiator::ManualApi, false),
+        ]
+        .into_iter()
+        .filter_map(|check| check.err().map(|e| e.to_string()))
+        .collect();
+        if !self.state.can_start_operation() {
+            blockers.push(format!("host is {}", self.state));
+        }
+        if self.sudo_available == Some(false) {
+            blockers.push(format!(
+                "passwordless {} not available for user {}",
+                self.package_manager.escalation().tool(),
+                self.config.user
+            ));
+        }
+        blockers
+    }
+
+    /// Update reachability metadata from a probe or connection outcome
+    ///
+    /// Operations report here too, so their connection failures count
+    /// towards the circuit breaker. Never touches the state machine; only
+    /// emits events when reachability or the breaker flips.
+    fn record_probe(&mut self, result: Result<(), String>) {
+        match result {
+            Ok(()) => {
+                self.last_seen = Some(Utc::now());
+                self.probe_failures = 0;
+                if self.breaker.record_success() {
+                    info!(host = %self.config.name, "circuit breaker closed");
+                    let _ = self.event_tx.send(WsEvent::CircuitClosed {
+                        host: self.config.name.to_string(),
+                    });
+                }
+                if !self.reachable {
+                    self.reachable = true;
+                    info!(host = %self.config.name, "host reachable again");
+                    let _ = self.event_tx.send(WsEvent::HostConnected {
+                        host: self.config.name.to_string(),
+                    });
+                }
+            }
+            Err(reason) => {
+                self.probe_failures = self.probe_failures.saturating_add(1);
+                let policy = &self.config.policy;
+                let opened = self.breaker.record_failure(
+                    Utc::now(),
+                    policy.circuit_breaker_threshold(),
+                    policy.circuit_breaker_cooldown(),
+                );
+                if let Some(retry_at) = opened {
+                    warn!(
+                        host = %self.config.name,
+                        failures = self.breaker.failures(),
+                        %retry_at,
+                        "circuit breaker open, failing operations fast"
+                    );
+                    let _ = self.event_tx.send(WsEvent::CircuitOpened {
+                        host: self.config.name.to_string(),
+                        failures: self.breaker.failures(),
+                        retry_at,
+                    });
+                }
+                if self.reachable
+                    && (opened.is_some()
+                        || self.probe_failures >= self.config.policy.unreachable_threshold())
+                {
+                    self.reachable = false;
+                    warn!(
+                        host = %self.config.name,
+                        failures = self.probe_failures,
+                        reason = %reason,
+                        "host unreachable"
+                    );
+                    let _ = self.event_tx.send(WsEvent::HostDisconnected {
+                        host: self.config.name.to_string(),
+                        reason,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Append a finished update to the history, evicting the oldest once full
+    ///
+    /// `result` is the package manager's result, or the error that ended
+    /// the update.
+    fn record_update(
+        &mut self,
+        request: &StartUpdate,
+        result: Result<&PkgUpdateResult, &str>,
+        reboot_required: bool,
+    ) {
+        let (upgraded_count, packages, autoremoved_packages, success, error) = match result {
+            Ok(r) => (
+                r.upgraded_count,
+                r.upgraded_packages.clone(),
+                r.autoremoved_packages.clone(),
+                r.success,
+                r.error.clone(),
+            ),
+            Err(e) => (0, Vec::new(), Vec::new(), false, Some(e.to_string())),
+        };
+        self.update_history.push_back(UpdateHistoryEntry {
+            timestamp: Utc::now(),
+            dry_run: request.dry_run,
+            scope: request.scope.unwrap_or(self.config.policy.default_scope),
+            stack: request.stack.clone(),
+            upgraded_count,
+            packages,
+            autoremoved_packages,
+            reboot_required,
+            success,
+            error,
+        });
+        self.trim_update_history();
+    }
+
+    /// Drop the oldest updates beyond the policy's history size
+    fn trim_update_history(&mut self) {
+        let capacity = self.config.policy.update_history_len();
+        while self.update_history.len() > capacity {
+            self.update_history.pop_front();
+        }
+    }
+
+    /// Apply the outcome of a finished update task to the state machine
+    fn finish_update(
+        &mut self,
+        finished: UpdateFinished,
+        request: StartUpdate,
+        actor_ref: WeakActorRef<Self>,
+    ) -> Result<UpdateResult, CoreError> {
+        let restart = finished.restart;
+        let reboot_required = restart.reboot_needed;
+        match finished.result {
+            Ok(pkg_result) => {
+                self.retry = None;
+                self.record_probe(Ok(()));
+                self.record_update(&request, Ok(&pkg_result), reboot_required);
+
+                let mut remaining = restart.clone();
+                remaining
+                    .services_needing_restart
+                    .retain(|s| !finished.restarted_services.contains(s));
+                self.needs_restart = (!remaining.is_empty()).then_some(remaining);
+
+                if !request.dry_run {
+                    // Packages may have brought docker or a new kernel along
+                    self.facts_probed_at = None;
+                    if request.stack.is_none() {
+                        self.facts
+                            .insert(FactKey::RebootRequired, reboot_required.to_string());
+                    }
+                    let tables = if request.stack.is_some() {
+                        DOCKER_TABLES
+                    } else {
+                        PACKAGE_TABLES
+                    };
+                    for table in tables {
+                        if !self.stale_inventory.contains(table) {
+                            self.stale_inventory.push(table);
+                        }
+                    }
+                }
+
+                if request.dry_run {
+                    // A simulation leaves the pending updates in place
+                    if self.pending_context.is_some() {
+                        self.transition_to(HostState::PendingUpdates, "UpdateFinished")?;
+                    } else {
+                        self.transition_to(HostState::Idle, "UpdateFinished")?;
+                    }
+                } else if reboot_required {
+                    // Services alone never need the whole host rebooted
+                    self.transition_to(HostState::WaitingReboot, "UpdateFinished")?;
+                } else {
+                    self.last_updated = Some(Utc::now());
+                    self.pending_context = None;
+                    self.last_check = None;
+                    self.transition_to(HostState::Idle, "UpdateFinished")?;
+                }
+
+                // Emit completion event
+                let verb = if request.dry_run {
+                    "would upgrade"
+                } else {
+                    "upgraded"
+                };
+                let mut result = format!(
+                    "{verb} {} packages, reboot_required={}, services_needing_restart={}, services_restarted={}",
+                    pkg_result.upgraded_count,
+                    reboot_required,
+                    restart.services_needing_restart.len(),
+                    finished.restarted_services.len()
+                );
+                if !pkg_result.autoremoved_packages.is_empty() {
+                    result.push_str(&format!(
+                        ", autoremoved={}",
+                        pkg_result.autoremoved_packages.len()
+                    ));
+                }
+                for warning in &finished.warnings {
+                    result.push_str("; ");
+                    result.push_str(warning);
+                }
+                let event = WsEvent::UpdateCompleted {
+                    host: self.config.name.to_string(),
+                    dry_run: request.dry_run,
+                    result,
+                };
+                let _ = self.event_tx.send(event);
+
+                Ok(UpdateResult {
+                    success: pkg_result.success,
+                    upgraded_count: pkg_result.upgraded_count,
+                    reboot_required,
+                    restart,
+                    restarted_services: finished.restarted_services,
+                    warnings: finished.warnings,
+                })
+            }
+            Err(e) => {
+                let error_msg = match &e {
+                    CoreError::PackageError(msg) => msg.clone(),
+                    other => other.to_string(),
+                };
+                let kind = finished
+                    .failure_kind
+                    .unwrap_or_else(|| FailureKind::from(&e));
+                self.record_update(&request, Err(&error_msg), reboot_required);
+                self.fail_operation(
+                    error_msg,
+                    finished.failure_output.as_deref(),
+                    kind,
+                    RetryOperation::Update(request),
+                    "UpdateFinished",
+                    actor_ref,
+                );
+                Err(e)
+            }
+        }
+    }
+
+    /// Query every package source for upgradable packages
+    ///
+    /// The system package manager and the compose stacks, if any, are
+    /// queried concurrently; with `refresh` their package lists are updated
+    /// first, otherwise they reuse lists younger than the metadata max age.
+    async fn list_sources(&self, refresh: bool) -> MultiSourceInventory {
+        let list = |manager: Arc<dyn PackageManager>| async move {
+            if refresh {
+                manager.update_package_lists().await?;
+            }
+            manager.list_upgradable().await
+        };
+        let compose = self.compose_manager.clone();
+        let (system, stacks) = tokio::join!(list(self.package_manager.clone()), async {
+            match compose {
+                Some(manager) => Some(list(manager).await),
+                None => None,
+            }
+        });
+
+        let mut inventory = MultiSourceInventory::default();
+        inventory.push(self.package_manager.manager_type().to_string(), system);
+        if let (Some(manager), Some(stacks)) = (&self.compose_manager, stacks) {
+            inventory.push(manager.manager_type().to_string(), stacks);
+        }
+        inventory
+    }
+
+    /// Query upgradable packages, moving to `PendingUpdates` or `Idle`
+    ///
+    /// The host only fails when no package source answered; failures of
+    /// some sources are kept as warnings in the status.
+    async fn query_upgradable(
+        &mut self,
+        refresh: bool,
+        initiator: Initiator,
+        trigger: &'static str,
+        actor_ref: WeakActorRef<Self>,
+    ) -> Result<InventoryResult, CoreError> {
+        self.check_breaker()?;
+        self.begin(HostState::Querying, initiator, trigger)?;
+
+        let inventory = self.list_sources(refresh).await;
+        let listed = match inventory.first_error() {
+            Some(e) if inventory.all_failed() => Err(e),
+            _ => Ok(inventory.packages()),
+        };
+        match listed {
+            Ok(packages) => {
+                self.record_probe(Ok(()));
+                self.refresh_facts().await;
+                self.warnings = inventory.warnings();
+                if !self.warnings.is_empty() {
+                    warn!(
+                        host = %self.config.name,
+                        summary = %inventory.summary(),
+                        "some package sources failed"
+                    );
+                }
+                let _ = self.event_tx.send(WsEvent::InventoryQueried {
+                    host: self.config.name.to_string(),
+                    summary: format!("inventory: {}", inventory.summary()),
+                    partial: !self.warnings.is_empty(),
+                });
+
+                #[allow(clippy::cast_possible_truncation)]
+                let count = packages.len() as u32;
+                #[allow(clippy::cast_possible_truncation)]
+                let security_count = packages.iter().filter(|p| p.security).count() as u32;
+                let names: Vec<String> = packages.iter().map(|p| p.name.clone()).collect();
+
+                self.last_check = Some(UpdateCheck {
+                    pending: count,
+                    security: security_count,
+                    packages,
+                    checked_at: Utc::now(),
+                });
+                if count > 0 {
+                    self.pending_context = Some(PendingUpdatesContext {
+                        package_count: count,
+                        packages: names.clone(),
+                        security_count,
+                        queried_at: Utc::now(),
+                    });
+                    self.transition_to(HostState::PendingUpdates, trigger)?;
+                } else {
+                    self.transition_to(HostState::Idle, trigger)?;
+                }
+
+                Ok(InventoryResult {
+                    pending_updates: count,
+                    security_updates: security_count,
+                    packages: names,
+                })
+            }
+            Err(e) => {
+                // A lone source's error reads as before; several are listed
+                let error_msg = if inventory.sources.len() == 1 {
+                    e.to_string()
+                } else {
+                    inventory.warnings().join("; ")
+                };
+                self.warnings.clear();
+                self.fail_operation(
+                    &error_msg,
+                    e.output(),
+                    FailureKind::from(e),
+                    RetryOperation::Query,
+                    trigger,
+                    actor_ref,
+                );
+                Err(CoreError::InventoryError(error_msg))
+            }
+        }
+    }
+
+    /// Run an update in a background task; the host must already be `Updating`
+    ///
+    /// The task reports back with `UpdateFinished`, and `reply` receives the
+    /// result if a caller is waiting for it.
+    fn spawn_update(
+        &mut self,
+        request: StartUpdate,
+        manager: Arc<dyn PackageManager>,
+        reply: Option<ReplySender<Result<UpdateResult, CoreError>>>,
+        actor_ref: WeakActorRef<Self>,
+    ) {
+        self.next_update_id += 1;
+        let id = self.next_update_id;
+        let dry_run = request.dry_run;
+        let scope = request.scope.unwrap_or(self.config.policy.default_scope);
+        let stack = request.stack.clone();
+        let packages = request.packages.clone();
+        let package_manager = manager.clone();
+
+        // Dry runs change nothing, so there is nothing to prepare or undo
+        let policy = &self.config.policy;
+        let (pre_hooks, post_hooks) = if dry_run {
+            (Vec::new(), Vec::new())
+        } else {
+            (
+                policy.pre_update_hooks.clone(),
+                policy.post_update_hooks.clone(),
+            )
+        };
+        let run_post_on_failure = policy.runs_post_hooks_on_failure();
+        let auto_restart_services = policy.auto_restart_services && !dry_run;
+        // Cleanup only follows real system updates, unless the request opts out
+        let cleans_up = !dry_run && stack.is_none() && !request.skip_cleanup;
+        let autoremove = cleans_up && policy.autoremove;
+        let clean_cache = cleans_up && policy.clean_cache;
+        let hook_timeout = policy.hook_timeout();
+        // Compose stacks keep their images outside the package manager's reach
+        let min_free_space = if stack.is_none() {
+            policy.min_free_space()
+        } else {
+            Vec::new()
+        };
+        let check_timeout = policy.timeouts.operation_timeouts().query;
+        let lock_wait = policy.lock_wait();
+        let executor = self.executor.clone();
+        let event_tx = self.event_tx.clone();
+        let host = self.config.name.clone();
+
+        // Cancelling aborts this task, so post-update hooks don't run for
+        // cancelled updates.
+        let task = tokio::spawn(
+            async move {
+                let mut failure_kind = None;
+                let mut failure_output = None;
+                let mut warnings = Vec::new();
+                let result = async {
+                    warnings = check_free_space(
+                        &min_free_space,
+                        dry_run,
+                        executor.as_ref(),
+                        check_timeout,
+                        &host,
+                    )
+                    .await
+                    .map_err(|(e, output)| {
+                        failure_kind = Some(FailureKind::from(&e));
+                        failure_output = Some(output);
+                        CoreError::PackageError(e.to_string())
+                    })?;
+
+                    run_hooks(
+                        "pre-update",
+                        &pre_hooks,
+                        executor.as_ref(),
+                        hook_timeout,
+                        &host,
+                        &event_tx,
+                    )
+                    .await?;
+
+                    let on_wait = |holder: Option<&LockHolder>, waited: Duration| {
+                        let holder = holder.map(ToString::to_string);
+                        warn!(?holder, "package manager lock held, waiting");
+                        let _ = event_tx.send(WsEvent::WaitingForLock {
+                            host: host.to_string(),
+                            holder,
+                            waited_secs: waited.as_secs(),
+                        });
+                    };
+                    let upgrade = retry_while_locked(&lock_wait, &on_wait, || {
+                        run_upgrade(
+                            package_manager.as_ref(),
+                            stack.as_deref(),
+                            &packages,
+                            scope,
+                            dry_run,
+                        )
+                    })
+                    .await;
+                    let mut upgraded = upgrade.map_err(|e| {
+                        failure_kind = Some(FailureKind::from(&e));
+                        failure_output = e.output().map(str::to_string);
+                        CoreError::PackageError(e.to_string())
+                    })?;
+                    // Compose reports stacks that failed to come up in the result
+                    if !upgraded.success {
+                        failure_kind = Some(FailureKind::PackageOperation);
+                        let error = upgraded
+                            .error
+                            .take()
+                            .unwrap_or_else(|| "update reported failure".to_string());
+                        return Err(CoreError::PackageError(error));
+                    }
+
+                    // A failed cleanup leaves the update itself done
+                    if autoremove {
+                        let removed = retry_while_locked(&lock_wait, &on_wait, || {
+                            package_manager.autoremove()
+                        })
+                        .await;
+                        match removed {
+                            Ok(removed) => {
+                                let count = u32::try_from(removed.len()).unwrap_or(u32::MAX);
+                                upgraded.removed_count =
+                                    upgraded.removed_count.saturating_add(count);
+                                upgraded.autoremoved_packages = removed;
+                            }
+                            Err(e) => warnings.push(format!("autoremove failed: {e}")),
+                        }
+                    }
+                    if clean_cache
+                        && let Err(e) = retry_while_locked(&lock_wait, &on_wait, || {
+                            package_manager.clean_cache()
+                        })
+                        .await
+                    {
+                        warnings.push(format!("cleaning the package cache failed: {e}"));
+                    }
+                    Ok(upgraded)
+                }
+                .await;
+
+                let restart = if result.is_ok() {
+                    package_manager
+                        .restart_requirement()
+                        .await
+                        .unwrap_or_default()
+                } else {
+                    RestartRequirement::default()
+                };
+                // A reboot restarts everything anyway
+                let restarted_services = if auto_restart_services && !restart.reboot_needed {
+                    restart_services(
+                        &restart.services_needing_restart,
+                        executor.as_ref(),
+                        &package_manager.escalation(),
+                        hook_timeout,
+                        &host,
+                    )
+                    .await
+                } else {
+                    Vec::new()
+                };
+
+                if result.is_ok() || run_post_on_failure {
+                    let post = run_hooks(
+                        "post-update",
+                        &post_hooks,
+                        executor.as_ref(),
+                        hook_timeout,
+                        &host,
+                        &event_tx,
+                    )
+                    .await;
+                    // The packages are in place either way: a failed hook is a
+                    // warning on a successful update, and an earlier failure
+                    // is the more useful error to report
+                    if let Err(e) = post
+                        && result.is_ok()
+                    {
+                        warnings.push(e.to_string());
+                    }
+                }
+
+                if let Some(actor_ref) = actor_ref.upgrade() {
+                    let _ = actor_ref
+                        .tell(UpdateFinished {
+                            id,
+                            result,
+                            failure_kind,
+                            failure_output,
+                            restart,
+                            restarted_services,
+                            warnings,
+                        })
+                        .await;
+                }
+            }
+            .instrument(self.span()),
+        );
+
+        self.running_update = Some(RunningUpdate {
+            id,
+            request,
+            manager,
+            abort: task.abort_handle(),
+            reply,
+        });
+    }
+}
+
+/// Run the upgrade an update asked for
+async fn run_upgrade(
+    manager: &dyn PackageManager,
+    stack: Option<&str>,
+    packages: &[String],
+    scope: UpdateScope,
+    dry_run: bool,
+) -> Result<PkgUpdateResult, PackageError> {
+    match (stack, scope, dry_run) {
+        (Some(stack), _, _) => manager.upgrade_stack(stack).await,
+        (None, _, true) if !packages.is_empty() => manager.upgrade_packages_dry_run(packages).await,
+        (None, _, false) if !packages.is_empty() => manager.upgrade_packages(packages).await,
+        (None, UpdateScope::All, true) => manager.upgrade_dry_run().await,
+        (None, UpdateScope::All, false) => manager.upgrade_all().await,
+        (None, UpdateScope::SecurityOnly, true) => manager.upgrade_security_dry_run().await,
+        (None, UpdateScope::SecurityOnly, false) => manager.upgrade_security().await,
+    }
+}
+
+/// Send the actor automatic retry `attempt` after `delay`
+fn schedule_auto_retry(
+    attempt: u32,
+    delay: Duration,
+    actor_ref: WeakActorRef<HostActor>,
+) -> AbortHandle {
+    let timer = tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        if let Some(actor_ref) = actor_ref.upgrade() {
+            let _ = actor_ref.tell(AutoRetry { attempt }).await;
+        }
+    });
+    timer.abort_handle()
+}
+
+/// Whether `true` run through `escalation` (e.g. `sudo -n true`) succeeds;
+/// `None` if the command could not be run
+async fn check_escalation(
+    executor: &dyn RemoteExecutor,
+    escalation: &PrivilegeEscalation,
+    host: &str,
+) -> Option<bool> {
+    let probe = executor
+        .run_privileged_with_timeout(&ShellCommand::new("true"), escalation, PROBE_TIMEOUT)
+        .await;
+    match probe {
+        Ok(result) => {
+            if !result.success() {
+                warn!(
+                    host,
+                    tool = escalation.tool(),
+                    stderr = %result.stderr.trim(),
+                    "passwordless escalation not available"
+                );
+            }
+            Some(result.success())
+        }
+        Err(e) => {
+            debug!(host, error = %e, "could not check escalation");
+            None
+        }
+    }
+}
+
+/// osquery version reported by `osqueryi --version`; `None` if it is
+/// missing or could not be asked
+async fn read_osquery_version(executor: &dyn RemoteExecutor) -> Option<String> {
+    let result = executor
+        .run_with_timeout("osqueryi --version", PROBE_TIMEOUT)
+        .await
+        .ok()?;
+    result
+        .success()
+        .then(|| parse_version(&result.stdout))
+        .flatten()
+}
+
+/// Restart systemd services through `executor`, one at a time
+///
+/// Returns the services that restarted successfully. Failures are logged
+/// and leave the service listed as needing a restart; names that are not
+/// plain unit names are skipped rather than passed to the shell.
+async fn restart_services(
+    services: &[String],
+    executor: &dyn RemoteExecutor,
+    escalation: &PrivilegeEscalation,
+    timeout: Duration,
+    host: &str,
+) -> Vec<String> {
+    let mut restarted = Vec::new();
+    for service in services {
+        if service.is_empty()
+            || !service
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '@' | '.' | '_' | '-' | ':'))
+        {
+            warn!(host, service = %service, "skipping restart of oddly named service");
+            continue;
+        }
+
+        let cmd = ShellCommand::new("systemctl").arg("restart").arg(service);
+        match executor
+            .run_privileged_with_timeout(&cmd, escalation, timeout)
+            .await
+        {
+            Ok(result) if result.success() => {
+                info!(host, service = %service, "restarted service");
+                restarted.push(service.clone());
+            }
+            Ok(result) => {
+                warn!(host, service = %service, error = %result.stderr.trim(), "service restart failed");
+            }
+            Err(e) => warn!(host, service = %service, error = %e, "service restart failed"),
+        }
+    }
+    restarted
+}
+
+/// Run every health check in order through `executor`
+///
+/// All checks run even after one fails, so the result lists each problem.
+/// The second value is set when no check could be run at all, with the
+/// first error, and counts as a failed reachability probe.
+async fn run_health_checks(
+    checks: &[HealthCheckSpec],
+    executor: &dyn RemoteExecutor,
+) -> (HealthCheckResult, Option<String>) {
+    let mut outcomes = Vec::with_capacity(checks.len());
+    let mut first_error = None;
+    let mut any_ran = false;
+
+    for check in checks {
+        let started = std::time::Instant::now();
+        let (exit_code, message) = match executor
+            .run_with_timeout(&check.command, check.timeout())
+            .await
+        {
+            Ok(result) => {
+                any_ran = true;
+                let expected = check.expected_exit();
+                let message = if result.status == expected {
+                    check
+                        .expected_output
+                        .as_ref()
+                        .filter(|text| !result.stdout.contains(text.as_str()))
+                        .map(|text| format!("output does not contain '{text}'"))
+                } else {
+                    Some(format!(
+                        "exited with status {}, expected {expected}",
+                        result.status
+                    ))
+                };
+                (Some(result.status), message)
+            }
+            Err(e) => {
+                first_error.get_or_insert_with(|| e.to_string());
+                (None, Some(e.to_string()))
+            }
+        };
+
+        outcomes.push(CheckOutcome {
+            command: check.command.clone(),
+            passed: message.is_none(),
+            exit_code,
+            message,
+            duration: started.elapsed(),
+            checked_at: Utc::now(),
+        });
+    }
+
+    let result = HealthCheckResult {
+        healthy: outcomes.iter().all(|check| check.passed),
+        checks: outcomes,
+        checked_at: Utc::now(),
+    };
+    (result, if any_ran { None } else { first_error })
+}
+
+/// Check the host has the policy's free disk space before an update
+///
+/// Returns warnings for the update result: each shortfall in a dry run, or
+/// that `df` couldn't be used, which never stops an update. A real update
+/// with too little space fails with the first shortfall; the error comes
+/// with every checked mount point as its output.
+async fn check_free_space(
+    minimums: &[(String, u64)],
+    dry_run: bool,
+    executor: &dyn RemoteExecutor,
+    timeout: Duration,
+    host: &str,
+) -> Result<Vec<String>, (PackageError, String)> {
+    let checks = match check_disk_space(executor, minimums, timeout).await {
+        Ok(checks) => checks,
+        Err(e) => {
+            warn!(host, error = %e, "disk space check failed, updating anyway");
+            return Ok(vec![format!("disk space not checked: {e}")]);
+        }
+    };
+
+    let mut short = checks.iter().filter(|check| !check.is_sufficient());
+    if dry_run {
+        return Ok(short.map(|check| check.to_error().to_string()).collect());
+    }
+    match short.next() {
+        Some(check) => {
+            let output = checks
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n");
+            warn!(host, %check, "refusing update, not enough free disk space");
+            Err((check.to_error(), output))
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Run update hooks in order through `executor`, stopping at the first failure
+///
+/// Each hook emits a `HookExecuted` event. A hook fails if it exits non-zero,
+/// exceeds `timeout`, or cannot be run; the error names the hook and carries
+/// its stderr.
+async fn run_hooks(
+    stage: &'static str,
+    hooks: &[String],
+    executor: &dyn RemoteExecutor,
+    timeout: Duration,
+    host: &str,
+    event_tx: &broadcast::Sender<WsEvent>,
+) -> Result<(), CoreError> {
+    for hook in hooks {
+        info!(host, stage, hook = %hook, "running update hook");
+        let outcome = match executor.run_with_timeout(hook, timeout).await {
+            Ok(result) if result.success() => Ok(()),
+            Ok(result) if result.stderr.trim().is_empty() => {
+                Err(format!("exited with status {}", result.status))
+            }
+            Ok(result) => Err(result.stderr.trim().to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        let _ = event_tx.send(WsEvent::HookExecuted {
+            host: host.to_string(),
+            hook: hook.clone(),
+            success: outcome.is_ok(),
+        });
+
+        if let Err(message) = outcome {
+            warn!(host, stage, hook = %hook, error = %message, "update hook failed");
+            return Err(CoreError::HookFailed {
+                stage,
+                hook: hook.clone(),
+                message,
+            });
+        }
+    }
+    Ok(())
 }
 
 impl Actor for HostActor {
@@ -132,20 +1573,65 @@ impl Actor for HostActor {
         info!(host = %args.config.name, id = %actor_ref.id(), "HostActor starting");
 
         let event = WsEvent::HostConnected {
-            host: args.config.name.clone(),
+            host: args.config.name.to_string(),
         };
         let _ = args.event_tx.send(event);
 
-        Ok(Self {
+        let mut actor = Self {
             config: args.config,
             state: HostState::Idle,
             pending_context: None,
             failed_context: None,
+            inventory: InventoryCollector::new(args.executor.clone(), INVENTORY_CACHE_TTL),
+            stale_inventory: Vec::new(),
             executor: args.executor,
+            command_history: args.command_history,
+            executor_stats: args.executor_stats,
             package_manager: args.package_manager,
+            compose_manager: args.compose_manager,
             event_tx: args.event_tx,
             last_updated: None,
-        })
+            running_update: None,
+            next_update_id: 0,
+            reachable: true,
+            last_seen: None,
+            probe_failures: 0,
+            breaker: CircuitBreaker::default(),
+            probe_in_flight: false,
+            probe_timer: None,
+            retry: None,
+            needs_restart: None,
+            sudo_available: None,
+            update_history: VecDeque::new(),
+            last_health_check: None,
+            last_check: None,
+            last_inventory: None,
+            inventory_diff: None,
+            owner: None,
+            reserved: false,
+            paused: false,
+            transitions: VecDeque::new(),
+            warnings: Vec::new(),
+            facts: Facts::new(),
+            facts_probed_at: None,
+        };
+        actor.start_probe_timer(actor_ref.downgrade());
+        actor.spawn_sudo_check(actor_ref.downgrade());
+
+        Ok(actor)
+    }
+
+    /// Handle every message inside this host's span, so log lines from the
+    /// executor and package managers carry the `host` field too
+    fn on_message(
+        &mut self,
+        msg: kameo::message::BoxMessage<Self>,
+        actor_ref: ActorRef<Self>,
+        tx: Option<kameo::reply::BoxReplySender>,
+        stop: &mut bool,
+    ) -> impl Future<Output = Result<(), Box<dyn ReplyError>>> + Send {
+        let span = self.span();
+        async move { msg.handle_dyn(self, actor_ref, tx, stop).await }.instrument(span)
     }
 
     async fn on_stop(
@@ -159,8 +1645,16 @@ impl Actor for HostActor {
             "HostActor stopping"
         );
 
+        if let Some(running) = self.running_update.take() {
+            running.abort.abort();
+        }
+        if let Some(timer) = self.probe_timer.take() {
+            timer.abort();
+        }
+        self.cancel_retry();
+
         let event = WsEvent::HostDisconnected {
-            host: self.config.name.clone(),
+            host: self.config.name.to_string(),
             reason: format!("{reason:?}"),
         };
         let _ = self.event_tx.send(event);
@@ -178,114 +1672,419 @@ impl Message<QueryInventory> for HostActor {
 
     async fn handle(
         &mut self,
-        _msg: QueryInventory,
-        _ctx: &mut Context<Self, Self::Reply>,
+        msg: QueryInventory,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        // Validate state; people may always look at a paused host
+        self.check_owner(msg.initiator)?;
+        self.check_paused(msg.initiator, true)?;
+        if self.state.is_busy() {
+            return Err(CoreError::InvalidTransition {
+                from: self.state,
+                to: HostState::Querying,
+            });
+        }
+
+        self.query_upgradable(
+            msg.refresh,
+            msg.initiator,
+            "QueryInventory",
+            ctx.actor_ref().downgrade(),
+        )
+        .await
+    }
+}
+
+impl Message<CollectInventory> for HostActor {
+    type Reply = Result<HostInventory, CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: CollectInventory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.check_breaker()?;
+        // The first collection finds out whether osquery is installed
+        if !self.facts.contains_key(&FactKey::Osquery) {
+            self.facts_probed_at = None;
+            self.refresh_facts().await;
+        }
+        if msg.require_osquery
+            && let Some(found) = self.facts.get(&FactKey::Osquery)
+            && (found == "none" || !version_supported(found))
+        {
+            return Err(CoreError::OsqueryNotInstalled {
+                host: self.config.name.to_string(),
+                installed: self.osquery_version().map(str::to_string),
+            });
+        }
+        for table in std::mem::take(&mut self.stale_inventory) {
+            self.inventory.invalidate_matching(table).await;
+        }
+        // Read-only osquery collection, so no state transition is needed
+        let inventory = self
+            .inventory
+            .collect_full()
+            .await
+            .map_err(|e| CoreError::InventoryError(e.to_string()))?;
+
+        let system = &inventory.system;
+        for (key, value) in [
+            (FactKey::Kernel, &system.kernel_version),
+            (FactKey::Arch, &system.arch),
+        ] {
+            if !value.is_empty() {
+                self.facts.insert(key, value.clone());
+            }
+        }
+
+        // Only the latest inventory is kept, to diff against the next one
+        if let Some(previous) = self.last_inventory.replace(inventory.clone()) {
+            let diff = InventoryDiff::between(&previous, &inventory, DEFAULT_DISK_DELTA_THRESHOLD);
+            if !diff.is_empty() {
+                let summary = diff.summary();
+                info!(host = %self.config.name, %summary, "inventory changed");
+                let _ = self.event_tx.send(WsEvent::InventoryChanged {
+                    host: self.config.name.to_string(),
+                    summary,
+                });
+            }
+            self.inventory_diff = Some(diff);
+        }
+
+        Ok(inventory)
+    }
+}
+
+impl Message<InstallOsquery> for HostActor {
+    type Reply = DelegatedReply<Result<OsqueryInstall, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: InstallOsquery,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if let Err(e) = self
+            .check_owner(msg.initiator)
+            .and_then(|()| self.check_breaker())
+        {
+            return ctx.reply(Err(e));
+        }
+
+        self.refresh_facts().await;
+        if let Some(version) = self.osquery_version().filter(|v| version_supported(v)) {
+            return ctx.reply(Ok(OsqueryInstall {
+                version: Some(version.to_string()),
+                installed: false,
+            }));
+        }
+
+        let manager = self.package_manager.clone();
+        if let Err(e) = self.ensure_sudo(manager.as_ref()).await {
+            return ctx.reply(Err(CoreError::PackageError(e.to_string())));
+        }
+
+        // Busy like the operation the host would otherwise run next
+        let previous = self.state;
+        let busy = if previous == HostState::PendingUpdates {
+            HostState::Updating
+        } else {
+            HostState::Querying
+        };
+        if let Err(e) = self.begin(busy, msg.initiator, "InstallOsquery") {
+            return ctx.reply(Err(e));
+        }
+
+        let executor = self.executor.clone();
+        let actor_ref = ctx.actor_ref().downgrade();
+        let (delegated, reply) = ctx.reply_sender();
+        tokio::spawn(
+            async move {
+                let result = match manager.install_osquery().await {
+                    Ok(_) => Ok(read_osquery_version(executor.as_ref()).await),
+                    Err(e) => Err(e),
+                };
+                if let Some(actor_ref) = actor_ref.upgrade() {
+                    let _ = actor_ref
+                        .tell(OsqueryInstallFinished {
+                            previous,
+                            result,
+                            reply,
+                        })
+                        .await;
+                }
+            }
+            .instrument(self.span()),
+        );
+        delegated
+    }
+}
+
+impl Message<OsqueryInstallFinished> for HostActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: OsqueryInstallFinished,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let reply = match msg.result {
+            Ok(version) => {
+                info!(host = %self.config.name, version = ?version, "osquery installed");
+                self.facts.insert(
+                    FactKey::Osquery,
+                    version.clone().unwrap_or_else(|| "none".to_string()),
+                );
+                let _ = self.transition_to(msg.previous, "OsqueryInstallFinished");
+                Ok(OsqueryInstall {
+                    version,
+                    installed: true,
+                })
+            }
+            Err(e) => {
+                let error = format!("osquery install failed: {e}");
+                self.fail_with_error(
+                    &error,
+                    e.output(),
+                    FailureKind::from(&e),
+                    "OsqueryInstallFinished",
+                );
+                Err(CoreError::PackageError(error))
+            }
+        };
+        if let Some(tx) = msg.reply {
+            tx.send(reply);
+        }
+    }
+}
+
+impl Message<GetInventoryDiff> for HostActor {
+    type Reply = Option<InventoryDiff>;
+
+    async fn handle(
+        &mut self,
+        _msg: GetInventoryDiff,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.inventory_diff.clone()
+    }
+}
+
+impl Message<StartUpdate> for HostActor {
+    type Reply = DelegatedReply<Result<UpdateResult, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: StartUpdate,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if let Err(e) = self
+            .check_owner(msg.initiator)
+            .and_then(|()| self.check_paused(msg.initiator, msg.force))
+        {
+            return ctx.reply(Err(e));
+        }
+
+        // Must be in PendingUpdates or Idle to start update
+        if !self.state.can_start_operation() {
+            return ctx.reply(Err(CoreError::InvalidTransition {
+                from: self.state,
+                to: HostState::Updating,
+            }));
+        }
+
+        if let Err(e) = self.check_breaker() {
+            return ctx.reply(Err(e));
+        }
+
+        // Reject bad stack and package requests before touching the state machine
+        if let Err(e) = self.check_selected_packages(&msg) {
+            return ctx.reply(Err(e));
+        }
+        let manager = match msg.stack {
+            Some(ref stack) => match self.stack_manager(stack, msg.dry_run).await {
+                Ok(manager) => manager,
+                Err(e) => return ctx.reply(Err(e)),
+            },
+            None => self.package_manager.clone(),
+        };
+
+        if let Err(e) = self.ensure_sudo(manager.as_ref()).await {
+            let error = e.to_string();
+            self.record_update(&msg, Err(&error), false);
+            self.fail_with_error(&error, None, FailureKind::from(&e), "StartUpdate");
+            return ctx.reply(Err(CoreError::PackageError(error)));
+        }
+
+        if let Err(e) = self.begin(HostState::Updating, msg.initiator, "StartUpdate") {
+            return ctx.reply(Err(e));
+        }
+
+        // Run the upgrade in the background so the actor stays responsive
+        // (status queries, cancellation) while the package manager works.
+        let actor_ref = ctx.actor_ref().downgrade();
+        let (delegated, reply) = ctx.reply_sender();
+        self.spawn_update(msg, manager, reply, actor_ref);
+
+        delegated
+    }
+}
+
+impl Message<PreviewUpdate> for HostActor {
+    type Reply = DelegatedReply<Result<UpdatePreview, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: PreviewUpdate,
+        ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        // Validate state
-        if self.state.is_busy() {
-            return Err(CoreError::InvalidTransition {
-                from: self.state,
-                to: HostState::Querying,
-            });
+        // A preview never changes the state
+        if matches!(self.state, HostState::Updating | HostState::Rebooting) {
+            return ctx.reply(Err(CoreError::HostBusy(format!(
+                "{} is {}",
+                self.config.name, self.state
+            ))));
+        }
+        if let Err(e) = self.check_breaker() {
+            return ctx.reply(Err(e));
         }
 
-        self.transition_to(HostState::Querying)?;
-
-        // Query upgradable packages
-        match self.package_manager.list_upgradable().await {
-            Ok(packages) => {
-                #[allow(clippy::cast_possible_truncation)]
-                let count = packages.len() as u32;
-                let names: Vec<String> = packages.into_iter().map(|p| p.name).collect();
+        let mut blockers = self.update_blockers();
+        let policy = &self.config.policy;
+        let scope = msg.scope.unwrap_or(policy.default_scope);
+        // An update outside the window still runs, so it is only a warning
+        let mut warnings = Vec::new();
+        if let Some(window) = &policy.maintenance_window
+            && !window.is_open_at(Local::now().naive_local())
+        {
+            warnings.push(format!("outside the maintenance window {window}"));
+        }
+        let reboot_pending = self.state == HostState::WaitingReboot
+            || self
+                .needs_restart
+                .as_ref()
+                .is_some_and(|restart| restart.reboot_needed);
+        let min_free_space = policy.min_free_space();
+        let check_timeout = policy.timeouts.operation_timeouts().query;
+        let manager = self.package_manager.clone();
+        let executor = self.executor.clone();
+        let host = self.config.name.to_string();
 
-                if count > 0 {
-                    self.pending_context = Some(PendingUpdatesContext {
-                        package_count: count,
-                        packages: names.clone(),
-                        queried_at: Utc::now(),
-                    });
-                    self.transition_to(HostState::PendingUpdates)?;
-                } else {
-                    self.transition_to(HostState::Idle)?;
+        // Simulate in the background so the actor stays responsive; no lock
+        // retries, as those report over the event stream
+        ctx.spawn(
+            async move {
+                let (simulated, checks) = tokio::join!(
+                    run_upgrade(manager.as_ref(), None, &[], scope, true),
+                    check_disk_space(executor.as_ref(), &min_free_space, check_timeout),
+                );
+                let simulated = simulated.map_err(|e| CoreError::PackageError(e.to_string()))?;
+                match checks {
+                    Ok(checks) => blockers.extend(
+                        checks
+                            .iter()
+                            .filter(|check| !check.is_sufficient())
+                            .map(|check| check.to_error().to_string()),
+                    ),
+                    Err(e) => warnings.push(format!("disk space not checked: {e}")),
                 }
 
-                Ok(InventoryResult {
-                    pending_updates: count,
-                    packages: names,
+                let reboot_likely = reboot_pending
+                    || simulated
+                        .upgraded_packages
+                        .iter()
+                        .any(|name| is_reboot_package(name));
+                Ok(UpdatePreview {
+                    host,
+                    scope,
+                    packages: simulated.upgraded_packages,
+                    upgraded_count: simulated.upgraded_count,
+                    new_count: simulated.new_count,
+                    removed_count: simulated.removed_count,
+                    download_bytes: simulated.download_bytes,
+                    reboot_likely,
+                    blockers,
+                    warnings,
+                    previewed_at: Utc::now(),
                 })
             }
-            Err(e) => {
-                let error_msg = e.to_string();
-                self.fail_with_error(&error_msg);
-                Err(CoreError::InventoryError(error_msg))
-            }
-        }
+            .instrument(self.span()),
+        )
     }
 }
 
-impl Message<StartUpdate> for HostActor {
-    type Reply = Result<UpdateResult, CoreError>;
+impl Message<UpdateFinished> for HostActor {
+    type Reply = ();
 
     async fn handle(
         &mut self,
-        msg: StartUpdate,
-        _ctx: &mut Context<Self, Self::Reply>,
+        msg: UpdateFinished,
+        ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        // Must be in PendingUpdates or Idle to start update
-        if !self.state.can_start_operation() {
-            return Err(CoreError::InvalidTransition {
-                from: self.state,
-                to: HostState::Updating,
-            });
+        // Ignore results from updates that were cancelled in the meantime
+        let Some(running) = self.running_update.take_if(|r| r.id == msg.id) else {
+            return;
+        };
+
+        // The update a reservation was made for ends it
+        if self
+            .owner
+            .as_ref()
+            .is_some_and(|owner| owner.initiator == running.request.initiator)
+        {
+            self.reserved = false;
         }
 
-        self.transition_to(HostState::Updating)?;
+        let result = self.finish_update(msg, running.request, ctx.actor_ref().downgrade());
 
-        let result = if msg.dry_run {
-            self.package_manager.upgrade_dry_run().await
-        } else {
-            self.package_manager.upgrade_all().await
+        if let Some(reply) = running.reply {
+            reply.send(result);
+        }
+    }
+}
+
+impl Message<CancelUpdate> for HostActor {
+    type Reply = Result<(), CoreError>;
+
+    async fn handle(
+        &mut self,
+        _msg: CancelUpdate,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let Some(running) = self.running_update.take() else {
+            return Err(CoreError::NotUpdating(format!(
+                "{} is {}",
+                self.config.name, self.state
+            )));
         };
 
-        match result {
-            Ok(pkg_result) => {
-                // Check if reboot is required
-                let reboot_required = self
-                    .package_manager
-                    .reboot_required()
-                    .await
-                    .unwrap_or(false);
+        running.abort.abort();
 
-                if reboot_required && !msg.dry_run {
-                    self.transition_to(HostState::WaitingReboot)?;
-                } else {
-                    self.last_updated = Some(Utc::now());
-                    self.pending_context = None;
-                    self.transition_to(HostState::Idle)?;
-                }
+        if let Err(e) = running.manager.cancel_upgrade().await {
+            warn!(
+                host = %self.config.name,
+                error = %e,
+                "failed to terminate remote upgrade process"
+            );
+        }
 
-                // Emit completion event
-                let event = WsEvent::UpdateCompleted {
-                    host: self.config.name.clone(),
-                    result: format!(
-                        "upgraded {} packages, reboot_required={}",
-                        pkg_result.upgraded_count, reboot_required
-                    ),
-                };
-                let _ = self.event_tx.send(event);
+        self.record_update(&running.request, Err("cancelled by operator"), false);
+        self.fail_with_error(
+            "cancelled by operator",
+            None,
+            FailureKind::Internal,
+            "CancelUpdate",
+        );
 
-                Ok(UpdateResult {
-                    success: pkg_result.success,
-                    upgraded_count: pkg_result.upgraded_count,
-                    reboot_required,
-                })
-            }
-            Err(e) => {
-                let error_msg = e.to_string();
-                self.fail_with_error(&error_msg);
-                Err(CoreError::PackageError(error_msg))
-            }
+        if let Some(reply) = running.reply {
+            reply.send(Err(CoreError::Cancelled(
+                "cancelled by operator".to_string(),
+            )));
         }
+
+        info!(host = %self.config.name, "update cancelled");
+
+        Ok(())
     }
 }
 
@@ -313,19 +2112,34 @@ impl Message<RebootIfRequired> for HostActor {
             return Ok(false);
         }
 
-        self.transition_to(HostState::Rebooting)?;
+        self.check_owner(Initiator::ManualApi)?;
+        self.check_breaker()?;
+        self.package_manager
+            .prepare()
+            .await
+            .map_err(|e| CoreError::PackageError(e.to_string()))?;
+        self.begin(
+            HostState::Rebooting,
+            Initiator::ManualApi,
+            "RebootIfRequired",
+        )?;
 
         // Execute reboot command
-        match self.executor.run("sudo reboot").await {
+        let escalation = self.package_manager.escalation();
+        match self
+            .executor
+            .run_privileged(&ShellCommand::new("reboot"), &escalation)
+            .await
+        {
             Ok(_) => {
                 // After reboot, we need to verify
                 // In practice, we'd wait for SSH to come back
-                self.transition_to(HostState::Verifying)?;
+                self.transition_to(HostState::Verifying, "RebootIfRequired")?;
                 Ok(true)
             }
             Err(e) => {
                 let error_msg = e.to_string();
-                self.fail_with_error(&error_msg);
+                self.fail_with_error(&error_msg, None, FailureKind::from(&e), "RebootIfRequired");
                 Err(CoreError::SshError(error_msg))
             }
         }
@@ -343,37 +2157,205 @@ impl Message<HealthCheck> for HostActor {
         // Health check can be done from Verifying state or any non-busy state
         let is_verifying = self.state == HostState::Verifying;
 
-        // Simple health check: can we run a command?
-        match self.executor.run("echo ok").await {
-            Ok(output) => {
-                let healthy = output.stdout.trim() == "ok";
+        let default_checks = [HealthCheckSpec::connectivity()];
+        let checks = if self.config.policy.health_checks.is_empty() {
+            &default_checks[..]
+        } else {
+            &self.config.policy.health_checks[..]
+        };
+        let (result, unreachable) = run_health_checks(checks, self.executor.as_ref()).await;
+
+        // The host answered if any check ran, whatever its outcome
+        self.record_probe(match unreachable {
+            Some(error) => Err(error),
+            None => Ok(()),
+        });
 
-                if is_verifying {
-                    if healthy {
-                        self.last_updated = Some(Utc::now());
-                        self.pending_context = None;
-                        self.transition_to(HostState::Idle)?;
-                    } else {
-                        self.fail_with_error("health check failed after reboot");
-                    }
+        if is_verifying {
+            match result.first_failure() {
+                None => {
+                    self.last_updated = Some(Utc::now());
+                    self.pending_context = None;
+                    self.last_check = None;
+                    self.needs_restart = None;
+                    self.facts
+                        .insert(FactKey::RebootRequired, false.to_string());
+                    self.facts_probed_at = None;
+                    self.transition_to(HostState::Idle, "HealthCheck")?;
                 }
+                Some(check) => {
+                    let reason = check.message.as_deref().unwrap_or("failed");
+                    self.fail_with_error(
+                        format!(
+                            "health check '{}' failed after reboot: {reason}",
+                            check.command
+                        ),
+                        None,
+                        FailureKind::PackageOperation,
+                        "HealthCheck",
+                    );
+                }
+            }
+        }
 
-                Ok(HealthCheckResult {
-                    healthy,
-                    message: if healthy {
-                        None
-                    } else {
-                        Some("unexpected output".to_string())
+        self.last_health_check = Some(result.clone());
+        Ok(result)
+    }
+}
+
+impl Message<AutoRetry> for HostActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: AutoRetry,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        // The host may have been retried manually or paused since the timer
+        // was set
+        if self.state != HostState::Failed || self.paused {
+            return;
+        }
+        let Some(sequence) = self.retry.as_mut() else {
+            return;
+        };
+        if sequence.attempts.len() + 1 != msg.attempt as usize {
+            return;
+        }
+        // Wait out an open breaker instead of spending an attempt on it
+        if let BreakerState::Open(retry_at) = self.breaker.state(Utc::now())
+            && self.config.policy.circuit_breaker_threshold().is_some()
+        {
+            let delay = (retry_at - Utc::now()).to_std().unwrap_or_default();
+            sequence.timer = Some(schedule_auto_retry(
+                msg.attempt,
+                delay,
+                ctx.actor_ref().downgrade(),
+            ));
+            if let Some(context) = self.failed_context.as_mut() {
+                context.next_retry_at = Some(retry_at);
+            }
+            info!(host = %self.config.name, attempt = msg.attempt, %retry_at, "automatic retry deferred by circuit breaker");
+            return;
+        }
+        sequence.timer = None;
+        sequence.current = Some(Utc::now());
+        let operation = sequence.operation.clone();
+
+        info!(host = %self.config.name, attempt = msg.attempt, "automatic retry starting");
+        let _ = self.event_tx.send(WsEvent::RetryStarted {
+            host: self.config.name.to_string(),
+            attempt: msg.attempt,
+        });
+
+        if self.transition_to(HostState::Idle, "AutoRetry").is_err() {
+            return;
+        }
+        self.failed_context = None;
+
+        let actor_ref = ctx.actor_ref().downgrade();
+        // A failed query schedules the next attempt itself
+        if self
+            .query_upgradable(false, Initiator::AutoRetry, "AutoRetry", actor_ref.clone())
+            .await
+            .is_err()
+        {
+            return;
+        }
+
+        match operation {
+            RetryOperation::Update(mut request) if self.state == HostState::PendingUpdates => {
+                request.initiator = Initiator::AutoRetry;
+                let manager = match request.stack {
+                    Some(ref stack) => match self.stack_manager(stack, request.dry_run).await {
+                        Ok(manager) => manager,
+                        Err(e) => {
+                            self.fail_with_error(
+                                e.to_string(),
+                                None,
+                                FailureKind::from(&e),
+                                "AutoRetry",
+                            );
+                            return;
+                        }
                     },
-                })
+                    None => self.package_manager.clone(),
+                };
+                if self
+                    .begin(HostState::Updating, Initiator::AutoRetry, "AutoRetry")
+                    .is_ok()
+                {
+                    self.spawn_update(request, manager, None, actor_ref);
+                }
             }
-            Err(e) => {
-                if is_verifying {
-                    let error_msg = e.to_string();
-                    self.fail_with_error(&error_msg);
+            // Queries are done, and an update with nothing left to install is too
+            _ => {
+                self.retry = None;
+                info!(host = %self.config.name, "automatic retry succeeded");
+            }
+        }
+    }
+}
+
+impl Message<Probe> for HostActor {
+    type Reply = ();
+
+    async fn handle(&mut self, _msg: Probe, ctx: &mut Context<Self, Self::Reply>) -> Self::Reply {
+        // Stay out of the way of operations that already talk to the host,
+        // and leave an open breaker alone until its cooldown has passed
+        if self.probe_in_flight
+            || self.paused
+            || self.state.is_busy()
+            || self.running_update.is_some()
+            || self.check_breaker().is_err()
+        {
+            return;
+        }
+
+        self.probe_in_flight = true;
+        let executor = self.executor.clone();
+        let actor_ref = ctx.actor_ref().downgrade();
+
+        tokio::spawn(
+            async move {
+                let result = match executor.run_with_timeout("echo ok", PROBE_TIMEOUT).await {
+                    Ok(output) if output.stdout.trim() == "ok" => Ok(()),
+                    Ok(_) => Err("unexpected probe output".to_string()),
+                    Err(e) => Err(e.to_string()),
+                };
+
+                if let Some(actor_ref) = actor_ref.upgrade() {
+                    let _ = actor_ref.tell(ProbeFinished { result }).await;
                 }
-                Err(CoreError::SshError(e.to_string()))
             }
+            .instrument(self.span()),
+        );
+    }
+}
+
+impl Message<ProbeFinished> for HostActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: ProbeFinished,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.probe_in_flight = false;
+        self.record_probe(msg.result);
+    }
+}
+
+impl Message<SudoChecked> for HostActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: SudoChecked,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if msg.available.is_some() {
+            self.sudo_available = msg.available;
         }
     }
 }
@@ -381,8 +2363,20 @@ impl Message<HealthCheck> for HostActor {
 impl Message<Retry> for HostActor {
     type Reply = Result<(), CoreError>;
 
-    async fn handle(&mut self, _msg: Retry, _ctx: &mut Context<Self, Self::Reply>) -> Self::Reply {
+    async fn handle(&mut self, _msg: Retry, ctx: &mut Context<Self, Self::Reply>) -> Self::Reply {
+        // The operator vouches for the host, so try it again right away
+        let breaker_reset = self.breaker.record_success();
+        if breaker_reset {
+            info!(host = %self.config.name, "circuit breaker reset by retry");
+            let _ = self.event_tx.send(WsEvent::CircuitClosed {
+                host: self.config.name.to_string(),
+            });
+        }
+
         if self.state != HostState::Failed {
+            if breaker_reset {
+                return Ok(());
+            }
             return Err(CoreError::InvalidTransition {
                 from: self.state,
                 to: HostState::Idle,
@@ -393,9 +2387,15 @@ impl Message<Retry> for HostActor {
             ctx.increment_retry();
         }
 
-        self.transition_to(HostState::Idle)?;
+        // A manual retry starts over with a fresh retry budget
+        self.cancel_retry();
+        self.transition_to(HostState::Idle, "Retry")?;
         self.failed_context = None;
 
+        // The operator may have fixed sudoers in the meantime
+        self.sudo_available = None;
+        self.spawn_sudo_check(ctx.actor_ref().downgrade());
+
         info!(host = %self.config.name, "host recovered from failed state");
 
         Ok(())
@@ -411,9 +2411,10 @@ impl Message<Acknowledge> for HostActor {
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
         if self.state != HostState::Failed {
-            return Err(CoreError::HostFailed(
-                "can only acknowledge hosts in Failed state".to_string(),
-            ));
+            return Err(CoreError::NotFailed(format!(
+                "{} is {}",
+                self.config.name, self.state
+            )));
         }
 
         if let Some(ref mut ctx) = self.failed_context {
@@ -423,12 +2424,103 @@ impl Message<Acknowledge> for HostActor {
                 error = %ctx.error,
                 "failure acknowledged"
             );
+            let _ = self.event_tx.send(WsEvent::HostAcknowledged {
+                host: self.config.name.to_string(),
+            });
         }
 
         Ok(())
     }
 }
 
+impl Message<Snapshot> for HostActor {
+    type Reply = HostSnapshot;
+
+    async fn handle(
+        &mut self,
+        _msg: Snapshot,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        HostSnapshot {
+            name: self.config.name.clone(),
+            state: self.state,
+            last_check: self.last_check.as_ref().map(|check| CheckSnapshot {
+                packages: check.packages.clone(),
+                checked_at: check.checked_at,
+            }),
+            failed: self.failed_context.clone(),
+            last_updated: self.last_updated,
+            needs_restart: self.needs_restart.clone(),
+            facts: self.facts.clone(),
+            update_history: self.update_history.iter().cloned().collect(),
+            paused: self.paused,
+        }
+    }
+}
+
+impl Message<Restore> for HostActor {
+    type Reply = bool;
+
+    async fn handle(&mut self, msg: Restore, _ctx: &mut Context<Self, Self::Reply>) -> Self::Reply {
+        if self.state != HostState::Idle || self.last_check.is_some() {
+            debug!(host = %self.config.name, state = %self.state, "not restoring an active host");
+            return false;
+        }
+
+        let snapshot = msg.0;
+        let (state, failed) = snapshot.restored_state();
+        self.last_check = snapshot.last_check.map(|check| {
+            #[allow(clippy::cast_possible_truncation)]
+            let pending = check.packages.len() as u32;
+            #[allow(clippy::cast_possible_truncation)]
+            let security = check.packages.iter().filter(|p| p.security).count() as u32;
+            UpdateCheck {
+                pending,
+                security,
+                packages: check.packages,
+                checked_at: check.checked_at,
+            }
+        });
+        self.pending_context = self
+            .last_check
+            .as_ref()
+            .filter(|_| state == HostState::PendingUpdates)
+            .map(|check| PendingUpdatesContext {
+                package_count: check.pending,
+                packages: check.packages.iter().map(|p| p.name.clone()).collect(),
+                security_count: check.security,
+                queried_at: check.checked_at,
+            });
+        self.failed_context = failed;
+        self.last_updated = snapshot.last_updated.max(self.last_updated);
+        self.needs_restart = snapshot.needs_restart;
+        // Facts probed since the start are newer
+        let mut facts = snapshot.facts;
+        facts.append(&mut self.facts);
+        self.facts = facts;
+        let mut history: VecDeque<_> = snapshot.update_history.into();
+        history.append(&mut self.update_history);
+        self.update_history = history;
+        self.trim_update_history();
+        // A pause holds whatever state the host comes back in
+        self.paused |= snapshot.paused;
+
+        info!(host = %self.config.name, %state, paused = self.paused, "restored host state from snapshot");
+        if state != HostState::Idle {
+            self.state = state;
+            let error = self.failed_context.as_ref().map(|c| c.error.clone());
+            self.record_transition(HostState::Idle, "Restore", error);
+            let _ = self.event_tx.send(WsEvent::HostStateChanged {
+                host: self.config.name.to_string(),
+                from: HostState::Idle.to_string(),
+                to: state.to_string(),
+                failure_kind: self.failed_context.as_ref().map(|c| c.kind.to_string()),
+            });
+        }
+        true
+    }
+}
+
 impl Message<GetState> for HostActor {
     type Reply = HostState;
 
@@ -453,9 +2545,190 @@ impl Message<GetStatus> for HostActor {
             name: self.config.name.clone(),
             state: self.state,
             last_updated: self.last_updated,
-            pending_updates: self.pending_context.as_ref().map(|c| c.package_count),
+            pending_updates: self.last_check.as_ref().map(|c| c.pending),
+            security_updates: self.last_check.as_ref().map(|c| c.security),
+            upgradable_packages: self.last_check.as_ref().map(|c| c.packages.clone()),
+            last_checked: self.last_check.as_ref().map(|c| c.checked_at),
             error: self.failed_context.as_ref().map(|c| c.error.clone()),
             tags: self.config.tags.clone(),
+            reachable: self.reachable,
+            last_seen: self.last_seen,
+            circuit_open_until: self
+                .breaker
+                .open_until()
+                .filter(|_| self.config.policy.circuit_breaker_threshold().is_some()),
+            failure: self.failed_context.clone(),
+            distro: self.package_manager.distro().cloned(),
+            os: self.os(),
+            facts: self.facts(),
+            needs_restart: self.needs_restart.clone(),
+            sudo_available: self.sudo_available,
+            last_health_check: self.last_health_check.clone(),
+            owner: self.owner.clone(),
+            paused: self.paused,
+            warnings: self.warnings.clone(),
+            recent_transitions: self
+                .transitions
+                .iter()
+                .skip(self.transitions.len().saturating_sub(RECENT_TRANSITIONS))
+                .cloned()
+                .collect(),
+        }
+    }
+}
+
+impl Message<ReserveForUpdate> for HostActor {
+    type Reply = Result<(), CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: ReserveForUpdate,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.check_owner(msg.initiator)?;
+        self.check_paused(msg.initiator, false)?;
+        if !self.state.can_start_operation() {
+            return Err(CoreError::InvalidTransition {
+                from: self.state,
+                to: HostState::Querying,
+            });
+        }
+
+        if self.owner.is_none() {
+            self.owner = Some(OperationOwner::new(msg.initiator));
+        }
+        self.reserved = true;
+        debug!(host = %self.config.name, initiator = %msg.initiator, "host reserved");
+        Ok(())
+    }
+}
+
+impl Message<SetPaused> for HostActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: SetPaused,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if self.paused == msg.paused {
+            return;
+        }
+        self.paused = msg.paused;
+        if msg.paused {
+            // A retry scheduled before the pause would run regardless
+            self.cancel_retry();
+            if let Some(context) = self.failed_context.as_mut() {
+                context.next_retry_at = None;
+            }
+        }
+        info!(host = %self.config.name, paused = msg.paused, "host pause changed");
+    }
+}
+
+impl Message<ReleaseReservation> for HostActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: ReleaseReservation,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if !self.reserved
+            || self
+                .owner
+                .as_ref()
+                .is_none_or(|owner| owner.initiator != msg.initiator)
+        {
+            return;
+        }
+        self.reserved = false;
+        self.release_finished_operation();
+        debug!(host = %self.config.name, initiator = %msg.initiator, "reservation released");
+    }
+}
+
+impl Message<GetCommandHistory> for HostActor {
+    type Reply = Vec<CommandRecord>;
+
+    async fn handle(
+        &mut self,
+        _msg: GetCommandHistory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.command_history.snapshot()
+    }
+}
+
+impl Message<GetExecutorStats> for HostActor {
+    type Reply = Option<ExecutorStatsSnapshot>;
+
+    async fn handle(
+        &mut self,
+        _msg: GetExecutorStats,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let stats = self.executor_stats.snapshot();
+        (stats.commands > 0).then_some(stats)
+    }
+}
+
+impl Message<GetTransitionHistory> for HostActor {
+    type Reply = Vec<StateTransition>;
+
+    async fn handle(
+        &mut self,
+        _msg: GetTransitionHistory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.transitions.iter().cloned().collect()
+    }
+}
+
+impl Message<GetUpdateHistory> for HostActor {
+    type Reply = Vec<UpdateHistoryEntry>;
+
+    async fn handle(
+        &mut self,
+        msg: GetUpdateHistory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.update_history
+            .iter()
+            .rev()
+            .take(msg.limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+impl Message<UpdateConfig> for HostActor {
+    type Reply = Result<(), CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: UpdateConfig,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        if msg.config.name != self.config.name {
+            return Err(CoreError::ConfigError(format!(
+                "config for '{}' sent to host '{}'",
+                msg.config.name, self.config.name
+            )));
+        }
+
+        let interval_changed =
+            msg.config.policy.health_check_interval() != self.config.policy.health_check_interval();
+        self.config = msg.config;
+        self.command_history
+            .set_capacity(self.config.policy.command_history_len());
+        self.trim_update_history();
+        if interval_changed {
+            self.start_probe_timer(ctx.actor_ref().downgrade());
         }
+
+        info!(host = %self.config.name, "host configuration updated");
+
+        Ok(())
     }
 }
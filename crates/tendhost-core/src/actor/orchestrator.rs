@@ -2,28 +2,54 @@
 //!
 //! Manages registry of `HostActors` and coordinates fleet-wide commands.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use kameo::actor::{ActorRef, WeakActorRef};
+use kameo::actor::{ActorId, ActorRef, WeakActorRef};
 use kameo::error::ActorStopReason;
 use kameo::message::{Context, Message};
 use kameo::prelude::*;
-use tokio::sync::broadcast;
-use tracing::{error, info, warn};
-
-use tendhost_api::events::WsEvent;
+use tokio::sync::{Semaphore, broadcast};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{Instrument, error, info, warn};
+
+use tendhost_api::events::{EventEnvelope, FleetPhase, WsEvent};
+use tendhost_api::requests::UpdateScope;
+use tendhost_api::responses::{
+    AuditEntry, BulkRegisterReport, FleetDryRunReport, FleetSummary, HostDryRun, HostRegistration,
+    RegistrationStatus, UpdateHistoryEntry, UpdatePreview,
+};
+use tendhost_exec::recording::{CommandHistory, CommandRecord, RecordingExecutor};
+use tendhost_exec::stats::{ExecutorStats, ExecutorStatsSnapshot, InstrumentedExecutor};
 use tendhost_exec::traits::RemoteExecutor;
+use tendhost_inventory::{HostInventory, InventoryDiff};
 use tendhost_pkg::traits::PackageManager;
 
 use crate::actor::host::{HostActor, HostActorArgs};
-use crate::config::HostConfig;
+use crate::audit::AuditLog;
+use crate::config::{FieldError, FleetFilter, FleetUpdateConfig, HostConfig};
+use crate::dependencies::{self, DependencyMap};
 use crate::error::CoreError;
+use crate::events::{DEFAULT_COALESCE_WINDOW, DEFAULT_SUBSCRIBER_QUEUE_SIZE, EventHub};
+use crate::facts::Facts;
+use crate::host_name::HostName;
 use crate::message::{
-    Acknowledge, AcknowledgeHost, FleetUpdateProgress, GetHostStatus, HostStatus, InventoryResult,
-    ListHosts, QueryHostInventory, QueryInventory, RegisterHost, Retry, RetryHost, StartUpdate,
-    TriggerFleetUpdate, TriggerHostUpdate, UnregisterHost,
+    Acknowledge, AcknowledgeHost, CancelHostUpdate, CancelUpdate, CollectHostInventory,
+    CollectInventory, FleetDryRun, FleetHostOutcome, FleetUpdateProgress, GetCommandHistory,
+    GetEventHub, GetExecutorStats, GetFleetSummary, GetHostCommandHistory, GetHostExecutorStats,
+    GetHostInventoryDiff, GetHostStatus, GetHostTransitionHistory, GetHostUpdateHistory,
+    GetInventoryDiff, GetRecentEvents, GetTransitionHistory, GetUpdateHistory, HostStatus,
+    InstallHostOsquery, InstallOsquery, InventoryResult, ListBusyHosts, ListHostConfigs, ListHosts,
+    OsqueryInstall, PauseHost, PreviewHostUpdate, PreviewUpdate, QueryHostInventory,
+    QueryInventory, RegisterHost, RegisterHosts, ReleaseReservation, ReplaceHostConfig,
+    ReserveForUpdate, Restore, RestoreHosts, ResumeHost, Retry, RetryHost, SaveSnapshots,
+    SetPaused, Snapshot, StartUpdate, SubscribeEvents, Traced, TriggerFleetUpdate,
+    TriggerHostUpdate, UnregisterHost, UpdateConfig, UpdateHostConfig,
 };
+use crate::snapshot::SnapshotStore;
+use crate::state::{HostState, Initiator, StateTransition};
 
 /// Factory trait for creating `HostActor` dependencies
 ///
@@ -31,7 +57,14 @@ use crate::message::{
 #[async_trait::async_trait]
 pub trait HostActorFactory: Send + Sync {
     /// Create a remote executor for the given host config
-    async fn create_executor(&self, config: &HostConfig) -> Arc<dyn RemoteExecutor>;
+    ///
+    /// # Errors
+    /// Returns `CoreError::ConfigError` if the config can't be turned into
+    /// an executor, e.g. because its key can't be loaded
+    async fn create_executor(
+        &self,
+        config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError>;
 
     /// Create a package manager for the given host config and executor
     async fn create_package_manager(
@@ -39,6 +72,23 @@ pub trait HostActorFactory: Send + Sync {
         config: &HostConfig,
         executor: Arc<dyn RemoteExecutor>,
     ) -> Arc<dyn PackageManager>;
+
+    /// Create a docker compose manager if the host has compose stacks
+    async fn create_compose_manager(
+        &self,
+        _config: &HostConfig,
+        _executor: Arc<dyn RemoteExecutor>,
+    ) -> Option<Arc<dyn PackageManager>> {
+        None
+    }
+
+    /// Check the config against this factory's environment
+    ///
+    /// Runs after [`HostConfig::validate`], for checks only the factory can
+    /// make, such as whether the SSH key it would open exists.
+    fn check_config(&self, _config: &HostConfig) -> Vec<FieldError> {
+        Vec::new()
+    }
 }
 
 /// Arguments for spawning an `OrchestratorActor`
@@ -47,6 +97,18 @@ pub struct OrchestratorActorArgs {
     pub event_channel_capacity: usize,
     /// Factory for creating host dependencies
     pub host_factory: Arc<dyn HostActorFactory>,
+    /// Audit log for internally triggered operations
+    pub audit_log: Option<Arc<AuditLog>>,
+    /// Where host snapshots are kept across a graceful restart
+    pub snapshot_store: Option<Arc<SnapshotStore>>,
+    /// How host actors that stop on their own are restarted
+    pub restart_policy: RestartPolicy,
+    /// Window in which the event hub coalesces progress events
+    pub coalesce_window: Duration,
+    /// Events the event hub buffers per subscriber
+    pub subscriber_queue_size: usize,
+    /// Hosts of a [`RegisterHosts`] batch prepared at the same time
+    pub registration_concurrency: usize,
 }
 
 impl Default for OrchestratorActorArgs {
@@ -54,16 +116,62 @@ impl Default for OrchestratorActorArgs {
         Self {
             event_channel_capacity: 1024,
             host_factory: Arc::new(NoOpHostFactory),
+            audit_log: None,
+            snapshot_store: None,
+            restart_policy: RestartPolicy::default(),
+            coalesce_window: DEFAULT_COALESCE_WINDOW,
+            subscriber_queue_size: DEFAULT_SUBSCRIBER_QUEUE_SIZE,
+            registration_concurrency: DEFAULT_REGISTRATION_CONCURRENCY,
+        }
+    }
+}
+
+/// Default number of hosts of a batch prepared at the same time
+pub const DEFAULT_REGISTRATION_CONCURRENCY: usize = 8;
+
+/// Longest wait for a host's snapshot at shutdown
+const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How the orchestrator restarts host actors that stop without being asked
+///
+/// Restarts back off exponentially. Once `max_restarts` in a row have
+/// been used up the host stays crashed until it is retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestartPolicy {
+    /// Automatic restarts in a row before the host is left crashed
+    pub max_restarts: u32,
+    /// Delay before the first restart, doubled for each one after it
+    pub backoff: Duration,
+    /// A restarted actor that runs this long starts counting anew
+    pub reset_after: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 3,
+            backoff: Duration::from_secs(1),
+            reset_after: Duration::from_secs(600),
         }
     }
 }
 
+impl RestartPolicy {
+    /// Delay before the restart following `restarts` earlier ones
+    fn delay(&self, restarts: u32) -> Duration {
+        self.backoff.saturating_mul(2u32.saturating_pow(restarts))
+    }
+}
+
 /// No-op factory for testing
 struct NoOpHostFactory;
 
 #[async_trait::async_trait]
 impl HostActorFactory for NoOpHostFactory {
-    async fn create_executor(&self, _config: &HostConfig) -> Arc<dyn RemoteExecutor> {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
         panic!("NoOpHostFactory should not be used in production")
     }
 
@@ -76,16 +184,101 @@ impl HostActorFactory for NoOpHostFactory {
     }
 }
 
+/// How long a cached host status may answer [`GetFleetSummary`] when no
+/// event about the host arrived in between
+const STATUS_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// A host status as last fetched from its actor
+struct CachedStatus {
+    status: HostStatus,
+    fetched_at: Instant,
+}
+
+/// Sent by the event listener when a host's status may have changed
+///
+/// `None` invalidates every host, after the listener missed events.
+struct HostStatusChanged(Option<HostName>);
+
+/// A host whose actor stopped without being asked to
+struct CrashedHost {
+    /// Why the actor stopped
+    reason: String,
+    /// Automatic restarts in a row before this crash
+    restarts: u32,
+    /// The pending automatic restart, if any are left
+    respawn: Option<JoinHandle<()>>,
+}
+
+/// Automatic restarts of a host in a row, and when the last one happened
+#[derive(Clone, Copy)]
+struct Restarts {
+    count: u32,
+    last_at: Instant,
+}
+
+/// Sent when a crashed host's backoff has passed
+struct RespawnHost(HostName);
+
+/// Sent by a [`RegisterHosts`] batch for each host it has prepared
+///
+/// Replies whether the host was added; it isn't if the name was
+/// registered while the host was being prepared.
+struct AddPreparedHost(HostActorArgs);
+
+/// Hosts of a fleet update batch
+type FleetBatch = Vec<(HostName, ActorRef<HostActor>)>;
+
+/// The order a fleet update runs in
+struct FleetPlan {
+    /// Hosts left out because they are paused
+    paused: Vec<HostName>,
+    /// Batches to run one after another
+    batches: Vec<(FleetPhase, FleetBatch)>,
+}
+
+impl FleetPlan {
+    /// Host names of each batch
+    fn order(&self) -> Vec<Vec<String>> {
+        self.batches
+            .iter()
+            .map(|(_, batch)| batch.iter().map(|(name, _)| name.to_string()).collect())
+            .collect()
+    }
+}
+
 /// Fleet orchestrator managing all host actors
 pub struct OrchestratorActor {
     /// Registry of host actors by hostname
-    hosts: HashMap<String, ActorRef<HostActor>>,
+    hosts: HashMap<HostName, ActorRef<HostActor>>,
     /// Host configurations
-    configs: HashMap<String, HostConfig>,
+    configs: HashMap<HostName, HostConfig>,
+    /// Host statuses fetched since the host's last event
+    status_cache: HashMap<HostName, CachedStatus>,
+    /// Registered hosts whose actor crashed and has not been respawned yet
+    crashed: HashMap<HostName, CrashedHost>,
+    /// Automatic restarts per host, kept across respawns
+    restarts: HashMap<HostName, Restarts>,
+    /// Hosts automation leaves alone, kept across respawns and config
+    /// changes
+    paused: HashSet<HostName>,
+    /// How crashed host actors are restarted
+    restart_policy: RestartPolicy,
+    /// This orchestrator, which host actors are linked to
+    actor_ref: WeakActorRef<Self>,
     /// Event broadcast sender
     event_tx: broadcast::Sender<WsEvent>,
+    /// Fan-out of `event_tx` to subscribers, with recent history
+    events: EventHub,
     /// Factory for creating host dependencies
     host_factory: Arc<dyn HostActorFactory>,
+    /// Audit log for internally triggered operations
+    audit_log: Option<Arc<AuditLog>>,
+    /// Where host snapshots are kept across a graceful restart
+    snapshot_store: Option<Arc<SnapshotStore>>,
+    /// Id of the last fleet update started
+    last_fleet_id: u64,
+    /// Hosts of a [`RegisterHosts`] batch prepared at the same time
+    registration_concurrency: usize,
 }
 
 impl OrchestratorActor {
@@ -98,7 +291,231 @@ impl OrchestratorActor {
     /// Get number of managed hosts
     #[must_use]
     pub fn host_count(&self) -> usize {
-        self.hosts.len()
+        self.configs.len()
+    }
+
+    /// The actor of a registered host that is running
+    fn host_ref(&self, name: &HostName) -> Result<&ActorRef<HostActor>, CoreError> {
+        if let Some(crashed) = self.crashed.get(name) {
+            return Err(CoreError::HostCrashed {
+                host: name.to_string(),
+                reason: crashed.reason.clone(),
+            });
+        }
+        self.hosts
+            .get(name)
+            .ok_or_else(|| CoreError::HostNotFound(name.to_string()))
+    }
+
+    /// Statuses standing in for crashed hosts, which cannot be asked
+    fn crashed_statuses(&self) -> impl Iterator<Item = HostStatus> + '_ {
+        self.crashed.iter().map(|(name, crashed)| {
+            crashed_status(
+                name,
+                self.configs.get(name),
+                &crashed.reason,
+                self.paused.contains(name),
+            )
+        })
+    }
+
+    /// Run config validation and the factory's checks, reporting all failures
+    fn validate_config(&self, config: &HostConfig) -> Result<(), CoreError> {
+        self.validate_in_batch(config, &DependencyMap::new())
+    }
+
+    /// Validate a config registered along with the hosts of `batch`
+    ///
+    /// Dependencies of the batch count as registered, so hosts of one
+    /// batch can't form a cycle either.
+    fn validate_in_batch(
+        &self,
+        config: &HostConfig,
+        batch: &DependencyMap,
+    ) -> Result<(), CoreError> {
+        let mut errors = config.validate().err().unwrap_or_default();
+        errors.extend(self.host_factory.check_config(config));
+        let mut graph = self.dependency_map();
+        graph.extend(batch.clone());
+        graph.insert(config.name.clone(), config.depends_on.clone());
+        if let Some(cycle) = dependencies::find_cycle(&graph) {
+            errors.push(FieldError::new(
+                "depends_on",
+                format!("would form a dependency cycle: {cycle}"),
+            ));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(CoreError::InvalidHostConfig(errors))
+        }
+    }
+
+    /// What every registered host depends on
+    fn dependency_map(&self) -> DependencyMap {
+        self.configs
+            .iter()
+            .map(|(name, config)| (name.clone(), config.depends_on.clone()))
+            .collect()
+    }
+
+    /// The batches of a fleet update: canaries first, then the other hosts
+    /// by dependency layer, each layer split into `batch_size` chunks
+    ///
+    /// Layers come from all registered hosts, so a dependency through a
+    /// host the filter leaves out still orders the update.
+    ///
+    /// # Errors
+    /// Returns `CoreError::ConfigError` if a canary is missing from the
+    /// update or is a dependency of another host in it, or if the hosts
+    /// depend on each other in a cycle.
+    fn plan_fleet_update(
+        &self,
+        config: &FleetUpdateConfig,
+        facts: &HashMap<HostName, Facts>,
+    ) -> Result<FleetPlan, CoreError> {
+        let (paused, hosts) = self.split_paused(self.fleet_hosts(config.filter.as_ref(), facts));
+        let (canaries, main): (Vec<_>, Vec<_>) = hosts
+            .into_iter()
+            .partition(|(name, _)| config.canary_hosts.contains(name));
+
+        // A mistyped canary would otherwise silently skip the canary phase
+        if let Some(missing) = config
+            .canary_hosts
+            .iter()
+            .find(|name| !canaries.iter().any(|(host, _)| host == *name))
+        {
+            let reason = if paused.contains(missing) {
+                "is paused"
+            } else {
+                "is not part of the fleet update"
+            };
+            return Err(CoreError::ConfigError(format!(
+                "canary host '{missing}' {reason}"
+            )));
+        }
+
+        let graph = self.dependency_map();
+        // Canaries go first, so none may be needed by another updated host
+        for (canary, _) in &canaries {
+            let dependents = dependencies::dependents_of(&graph, canary);
+            if let Some((dependent, _)) = canaries
+                .iter()
+                .chain(&main)
+                .find(|(host, _)| dependents.contains(host))
+            {
+                return Err(CoreError::ConfigError(format!(
+                    "canary host '{canary}' is a dependency of '{dependent}' and can't be updated before it"
+                )));
+            }
+        }
+
+        let layers = dependencies::update_layers(&graph)
+            .map_err(|cycle| CoreError::ConfigError(format!("dependency cycle: {cycle}")))?;
+        let mut main: HashMap<HostName, ActorRef<HostActor>> = main.into_iter().collect();
+        let mut batches = Vec::new();
+        if !canaries.is_empty() {
+            batches.push((FleetPhase::Canary, canaries));
+        }
+        for layer in layers {
+            let layer: Vec<_> = layer
+                .into_iter()
+                .filter_map(|name| main.remove_entry(&name))
+                .collect();
+            for batch in layer.chunks(config.batch_size.max(1)) {
+                batches.push((FleetPhase::Main, batch.to_vec()));
+            }
+        }
+        Ok(FleetPlan { paused, batches })
+    }
+
+    /// Registered hosts matching a fleet filter, sorted by name
+    ///
+    /// A host matches when it is not excluded, has at least one of the
+    /// tags if any are given, and has every fact filtered for in `facts`.
+    fn fleet_hosts(
+        &self,
+        filter: Option<&FleetFilter>,
+        facts: &HashMap<HostName, Facts>,
+    ) -> Vec<(HostName, ActorRef<HostActor>)> {
+        let mut hosts: Vec<_> = self
+            .hosts
+            .iter()
+            .filter(|(name, _)| {
+                let Some(filter) = filter else {
+                    return true;
+                };
+                if filter.exclude_hosts.contains(name) {
+                    return false;
+                }
+                if !filter.fact_filters.is_empty()
+                    && !facts
+                        .get(*name)
+                        .is_some_and(|facts| filter.fact_filters.iter().all(|f| f.matches(facts)))
+                {
+                    return false;
+                }
+                filter.tags.is_empty()
+                    || self
+                        .configs
+                        .get(*name)
+                        .is_none_or(|hc| filter.tags.iter().any(|t| hc.tags.contains(t)))
+            })
+            .map(|(name, actor)| (name.clone(), actor.clone()))
+            .collect();
+        // Name order keeps runs reproducible
+        hosts.sort_by(|(a, _), (b, _)| a.cmp(b));
+        hosts
+    }
+
+    /// Separate paused hosts from the others, keeping their order
+    fn split_paused(
+        &self,
+        hosts: Vec<(HostName, ActorRef<HostActor>)>,
+    ) -> (Vec<HostName>, Vec<(HostName, ActorRef<HostActor>)>) {
+        let (paused, active): (Vec<_>, Vec<_>) = hosts
+            .into_iter()
+            .partition(|(name, _)| self.paused.contains(name));
+        (paused.into_iter().map(|(name, _)| name).collect(), active)
+    }
+
+    /// The facts of every host, if `filter` matches on facts at all
+    async fn fleet_facts(&mut self, filter: Option<&FleetFilter>) -> HashMap<HostName, Facts> {
+        if filter.is_none_or(|f| f.fact_filters.is_empty()) {
+            return HashMap::new();
+        }
+        self.cached_statuses()
+            .await
+            .into_iter()
+            .map(|status| (status.name, status.facts))
+            .collect()
+    }
+
+    /// Status of every host, fetching only those without a fresh cached one
+    async fn cached_statuses(&mut self) -> Vec<HostStatus> {
+        let mut statuses: Vec<HostStatus> = self.crashed_statuses().collect();
+        for (name, actor_ref) in &self.hosts {
+            if let Some(cached) = self.status_cache.get(name)
+                && cached.fetched_at.elapsed() < STATUS_CACHE_TTL
+            {
+                statuses.push(cached.status.clone());
+                continue;
+            }
+            match actor_ref.ask(crate::message::GetStatus).await {
+                Ok(status) => {
+                    self.status_cache.insert(
+                        name.clone(),
+                        CachedStatus {
+                            status: status.clone(),
+                            fetched_at: Instant::now(),
+                        },
+                    );
+                    statuses.push(status);
+                }
+                Err(e) => warn!(host = %name, error = %e, "failed to get host status"),
+            }
+        }
+        statuses
     }
 
     /// Spawn a `HostActor` for the given config
@@ -106,24 +523,197 @@ impl OrchestratorActor {
         &mut self,
         config: HostConfig,
     ) -> Result<ActorRef<HostActor>, CoreError> {
-        let executor = self.host_factory.create_executor(&config).await;
-        let package_manager = self
-            .host_factory
+        let args =
+            Self::host_actor_args(self.host_factory.clone(), self.event_tx.clone(), config).await?;
+        Ok(self.spawn_from_args(args).await)
+    }
+
+    /// Build a `HostActor`'s dependencies without borrowing the orchestrator,
+    /// so several hosts can be prepared concurrently
+    async fn host_actor_args(
+        host_factory: Arc<dyn HostActorFactory>,
+        event_tx: broadcast::Sender<WsEvent>,
+        config: HostConfig,
+    ) -> Result<HostActorArgs, CoreError> {
+        let command_history = Arc::new(CommandHistory::new(config.policy.command_history_len()));
+        let executor_stats = Arc::new(ExecutorStats::default());
+        let executor: Arc<dyn RemoteExecutor> = Arc::new(InstrumentedExecutor::new(
+            Arc::new(RecordingExecutor::new(
+                host_factory.create_executor(&config).await?,
+                command_history.clone(),
+            )),
+            executor_stats.clone(),
+        ));
+        let package_manager = host_factory
             .create_package_manager(&config, executor.clone())
             .await;
+        let compose_manager = host_factory
+            .create_compose_manager(&config, executor.clone())
+            .await;
 
-        let args = HostActorArgs {
-            config: config.clone(),
+        Ok(HostActorArgs {
+            config,
             executor,
             package_manager,
-            event_tx: self.event_tx.clone(),
-        };
+            compose_manager,
+            event_tx,
+            command_history,
+            executor_stats,
+        })
+    }
 
+    /// Spawn a `HostActor` linked to the orchestrator, so its death is noticed
+    async fn spawn_from_args(&self, args: HostActorArgs) -> ActorRef<HostActor> {
+        let name = args.config.name.clone();
         let actor_ref = HostActor::spawn(args);
+        if self.paused.contains(&name) {
+            let _ = actor_ref.tell(SetPaused { paused: true }).await;
+        }
+        if let Some(orchestrator) = self.actor_ref.upgrade() {
+            orchestrator.link(&actor_ref).await;
+        }
+
+        info!(host = %name, "spawned HostActor");
+
+        actor_ref
+    }
+
+    /// Bring a crashed host back from its stored config
+    async fn respawn_host(&mut self, name: &HostName) -> Result<(), CoreError> {
+        let config = self
+            .configs
+            .get(name)
+            .cloned()
+            .ok_or_else(|| CoreError::HostNotFound(name.to_string()))?;
+        if let Some(respawn) = self.crashed.remove(name).and_then(|c| c.respawn) {
+            respawn.abort();
+        }
+        let actor_ref = self.spawn_host_actor(config).await?;
+        self.hosts.insert(name.clone(), actor_ref);
+        self.status_cache.remove(name);
+        info!(host = %name, "respawned crashed HostActor");
+        Ok(())
+    }
+
+    /// Write a snapshot of every running host to the snapshot store,
+    /// returning how many were written
+    ///
+    /// Hosts that don't answer in time are left out with a warning, so a
+    /// stuck host doesn't hold up the shutdown.
+    async fn save_snapshots(&self, store: &SnapshotStore) -> Result<usize, CoreError> {
+        let mut pending = JoinSet::new();
+        for (name, actor_ref) in &self.hosts {
+            let (name, actor_ref) = (name.clone(), actor_ref.clone());
+            pending.spawn(async move {
+                (
+                    name,
+                    tokio::time::timeout(SNAPSHOT_TIMEOUT, actor_ref.ask(Snapshot)).await,
+                )
+            });
+        }
+
+        let mut snapshots = Vec::with_capacity(pending.len());
+        while let Some(joined) = pending.join_next().await {
+            match joined {
+                Ok((_, Ok(Ok(snapshot)))) => snapshots.push(snapshot),
+                Ok((name, Ok(Err(e)))) => {
+                    warn!(host = %name, error = %e, "failed to snapshot host");
+                }
+                Ok((name, Err(_))) => warn!(host = %name, "host did not answer snapshot in time"),
+                Err(e) => error!(error = %e, "failed to snapshot host"),
+            }
+        }
+        snapshots.sort_by(|a, b| a.name.cmp(&b.name));
 
-        info!(host = %config.name, "spawned HostActor");
+        store.save(&snapshots).await?;
+        info!(
+            hosts = snapshots.len(),
+            path = %store.path().display(),
+            "saved host snapshots"
+        );
+        Ok(snapshots.len())
+    }
+
+    /// Take a dead host actor out of the registry and schedule its restart
+    fn host_crashed(&mut self, name: HostName, reason: &ActorStopReason) {
+        self.hosts.remove(&name);
+        self.status_cache.remove(&name);
+
+        let policy = self.restart_policy;
+        let restarts = self
+            .restarts
+            .get(&name)
+            .filter(|r| r.last_at.elapsed() < policy.reset_after)
+            .map_or(0, |r| r.count);
+        let respawn = (restarts < policy.max_restarts).then(|| {
+            let delay = policy.delay(restarts);
+            let orchestrator = self.actor_ref.clone();
+            let host = name.clone();
+            let task = tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                if let Some(orchestrator) = orchestrator.upgrade() {
+                    let _ = orchestrator.tell(RespawnHost(host)).await;
+                }
+            });
+            (task, delay)
+        });
+
+        let outcome = match &respawn {
+            Some((_, delay)) => format!("restarting in {}s", delay.as_secs_f64()),
+            None => "retry to respawn".to_string(),
+        };
+        error!(
+            host = %name,
+            reason = %reason,
+            restarts,
+            "HostActor crashed, {outcome}"
+        );
+        let _ = self.event_tx.send(WsEvent::HostDisconnected {
+            host: name.to_string(),
+            reason: format!("host actor crashed ({reason}); {outcome}"),
+        });
+        self.crashed.insert(
+            name,
+            CrashedHost {
+                reason: reason.to_string(),
+                restarts,
+                respawn: respawn.map(|(task, _)| task),
+            },
+        );
+    }
+}
 
-        Ok(actor_ref)
+/// The status reported for a host whose actor crashed
+fn crashed_status(
+    name: &HostName,
+    config: Option<&HostConfig>,
+    reason: &str,
+    paused: bool,
+) -> HostStatus {
+    HostStatus {
+        name: name.clone(),
+        state: HostState::Failed,
+        last_updated: None,
+        pending_updates: None,
+        security_updates: None,
+        upgradable_packages: None,
+        last_checked: None,
+        error: Some(format!("host actor crashed ({reason}); retry to respawn")),
+        tags: config.map(|c| c.tags.clone()).unwrap_or_default(),
+        reachable: false,
+        last_seen: None,
+        circuit_open_until: None,
+        failure: None,
+        distro: None,
+        os: None,
+        facts: Facts::new(),
+        needs_restart: None,
+        sudo_available: None,
+        last_health_check: None,
+        owner: None,
+        paused,
+        warnings: Vec::new(),
+        recent_transitions: Vec::new(),
     }
 }
 
@@ -132,18 +722,75 @@ impl Actor for OrchestratorActor {
     type Error = CoreError;
 
     async fn on_start(args: Self::Args, actor_ref: ActorRef<Self>) -> Result<Self, Self::Error> {
-        let (event_tx, _) = broadcast::channel(args.event_channel_capacity);
+        let (event_tx, _) = broadcast::channel::<WsEvent>(args.event_channel_capacity);
+
+        let hub = EventHub::spawn(
+            event_tx.subscribe(),
+            args.coalesce_window,
+            args.subscriber_queue_size,
+        );
 
         info!(id = %actor_ref.id(), "OrchestratorActor starting");
 
+        // Host events mark cached statuses stale
+        let mut events = event_tx.subscribe();
+        let orchestrator = actor_ref.downgrade();
+        tokio::spawn(async move {
+            loop {
+                let changed = match events.recv().await {
+                    Ok(event) => match event.host() {
+                        Some(host) => Some(HostName::from(host)),
+                        None => continue,
+                    },
+                    Err(broadcast::error::RecvError::Lagged(_)) => None,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let Some(orchestrator) = orchestrator.upgrade() else {
+                    break;
+                };
+                if orchestrator.tell(HostStatusChanged(changed)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
         Ok(Self {
             hosts: HashMap::new(),
             configs: HashMap::new(),
+            status_cache: HashMap::new(),
+            crashed: HashMap::new(),
+            restarts: HashMap::new(),
+            paused: HashSet::new(),
+            restart_policy: args.restart_policy,
+            actor_ref: actor_ref.downgrade(),
             event_tx,
+            events: hub,
             host_factory: args.host_factory,
+            audit_log: args.audit_log,
+            snapshot_store: args.snapshot_store,
+            last_fleet_id: 0,
+            registration_concurrency: args.registration_concurrency.max(1),
         })
     }
 
+    async fn on_link_died(
+        &mut self,
+        _actor_ref: WeakActorRef<Self>,
+        id: ActorId,
+        reason: ActorStopReason,
+    ) -> Result<ControlFlow<ActorStopReason>, Self::Error> {
+        // Actors of unregistered or reconfigured hosts are no longer listed
+        let crashed = self
+            .hosts
+            .iter()
+            .find(|(_, actor_ref)| actor_ref.id() == id)
+            .map(|(name, _)| name.clone());
+        if let Some(name) = crashed {
+            self.host_crashed(name, &reason);
+        }
+        Ok(ControlFlow::Continue(()))
+    }
+
     async fn on_stop(
         &mut self,
         _actor_ref: WeakActorRef<Self>,
@@ -151,6 +798,10 @@ impl Actor for OrchestratorActor {
     ) -> Result<(), Self::Error> {
         info!(reason = ?reason, "OrchestratorActor stopping");
 
+        for respawn in self.crashed.values().filter_map(|c| c.respawn.as_ref()) {
+            respawn.abort();
+        }
+
         // Stop all host actors
         for (name, actor_ref) in &self.hosts {
             info!(host = %name, "stopping HostActor");
@@ -175,8 +826,9 @@ impl Message<RegisterHost> for OrchestratorActor {
     ) -> Self::Reply {
         let name = msg.config.name.clone();
 
-        if self.hosts.contains_key(&name) {
-            return Err(CoreError::HostAlreadyExists(name));
+        self.validate_config(&msg.config)?;
+        if self.configs.contains_key(&name) {
+            return Err(CoreError::HostAlreadyExists(name.to_string()));
         }
 
         let actor_ref = self.spawn_host_actor(msg.config.clone()).await?;
@@ -187,6 +839,120 @@ impl Message<RegisterHost> for OrchestratorActor {
     }
 }
 
+impl Message<RegisterHosts> for OrchestratorActor {
+    type Reply = DelegatedReply<Result<BulkRegisterReport, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: RegisterHosts,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let mut seen = HashSet::new();
+        let mut duplicates: Vec<&str> = msg
+            .configs
+            .iter()
+            .filter(|config| !seen.insert(config.name.as_str()))
+            .map(|config| config.name.as_str())
+            .collect();
+        if !duplicates.is_empty() {
+            duplicates.sort_unstable();
+            duplicates.dedup();
+            return ctx.reply(Err(CoreError::ConfigError(format!(
+                "duplicate host names in batch: {}",
+                duplicates.join(", ")
+            ))));
+        }
+
+        let mut results: Vec<HostRegistration> = Vec::with_capacity(msg.configs.len());
+        let mut batch = DependencyMap::new();
+        let mut pending = JoinSet::new();
+        let permits = Arc::new(Semaphore::new(self.registration_concurrency));
+        for (index, config) in msg.configs.into_iter().enumerate() {
+            let mut result = HostRegistration {
+                name: config.name.to_string(),
+                status: RegistrationStatus::Error,
+                error: None,
+            };
+            if self.configs.contains_key(&config.name) {
+                result.status = RegistrationStatus::AlreadyExists;
+            } else if let Err(e) = self.validate_in_batch(&config, &batch) {
+                result.error = Some(e.to_string());
+            } else {
+                batch.insert(config.name.clone(), config.depends_on.clone());
+                // Stays an error unless its dependencies get built
+                result.error = Some("host actor could not be started".to_string());
+                let (factory, event_tx) = (self.host_factory.clone(), self.event_tx.clone());
+                let permits = permits.clone();
+                pending.spawn(async move {
+                    let _permit = permits.acquire_owned().await;
+                    (
+                        index,
+                        Self::host_actor_args(factory, event_tx, config).await,
+                    )
+                });
+            }
+            results.push(result);
+        }
+
+        // Hosts are prepared outside the orchestrator, so it keeps answering
+        // (and lists hosts added so far) while a large batch registers
+        let orchestrator = self.actor_ref.clone();
+        ctx.spawn(async move {
+            let total = pending.len();
+            let mut registered = 0;
+            while let Some(joined) = pending.join_next().await {
+                let (index, args) = match joined {
+                    Ok((index, Ok(args))) => (index, args),
+                    Ok((index, Err(e))) => {
+                        results[index].error = Some(e.to_string());
+                        continue;
+                    }
+                    Err(e) => {
+                        error!(error = %e, "failed to prepare host actor");
+                        continue;
+                    }
+                };
+                let Some(orchestrator) = orchestrator.upgrade() else {
+                    break;
+                };
+                match orchestrator.ask(AddPreparedHost(args)).await {
+                    Ok(true) => {
+                        registered += 1;
+                        results[index].status = RegistrationStatus::Created;
+                        results[index].error = None;
+                        info!(registered, total, "registered {registered}/{total} hosts");
+                    }
+                    Ok(false) => {
+                        results[index].status = RegistrationStatus::AlreadyExists;
+                        results[index].error = None;
+                    }
+                    Err(e) => error!(error = %e, "failed to add prepared host"),
+                }
+            }
+            Ok(BulkRegisterReport::new(results))
+        })
+    }
+}
+
+impl Message<AddPreparedHost> for OrchestratorActor {
+    type Reply = bool;
+
+    async fn handle(
+        &mut self,
+        msg: AddPreparedHost,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let config = msg.0.config.clone();
+        if self.configs.contains_key(&config.name) {
+            return false;
+        }
+        let actor_ref = self.spawn_from_args(msg.0).await;
+        self.hosts.insert(config.name.clone(), actor_ref);
+        self.configs.insert(config.name.clone(), config);
+        true
+    }
+}
+
 impl Message<UnregisterHost> for OrchestratorActor {
     type Reply = Result<(), CoreError>;
 
@@ -197,121 +963,648 @@ impl Message<UnregisterHost> for OrchestratorActor {
     ) -> Self::Reply {
         let name = &msg.hostname;
 
+        if self.configs.remove(name).is_none() {
+            return Err(CoreError::HostNotFound(name.to_string()));
+        }
+        self.status_cache.remove(name);
+        self.restarts.remove(name);
+        self.paused.remove(name);
+        if let Some(respawn) = self.crashed.remove(name).and_then(|c| c.respawn) {
+            respawn.abort();
+        }
         if let Some(actor_ref) = self.hosts.remove(name) {
-            self.configs.remove(name);
             actor_ref.stop_gracefully().await.ok();
-            info!(host = %name, "unregistered host");
-            Ok(())
-        } else {
-            Err(CoreError::HostNotFound(name.clone()))
         }
+        info!(host = %name, "unregistered host");
+        Ok(())
     }
 }
 
-impl Message<GetHostStatus> for OrchestratorActor {
+impl Message<UpdateHostConfig> for OrchestratorActor {
     type Reply = Result<HostStatus, CoreError>;
 
     async fn handle(
         &mut self,
-        msg: GetHostStatus,
+        msg: UpdateHostConfig,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let actor_ref = self
-            .hosts
+        let current = self
+            .configs
             .get(&msg.hostname)
-            .ok_or_else(|| CoreError::HostNotFound(msg.hostname.clone()))?;
-
-        actor_ref
-            .ask(crate::message::GetStatus)
-            .await
-            .map_err(|e| CoreError::ActorError(e.to_string()))
+            .ok_or_else(|| CoreError::HostNotFound(msg.hostname.to_string()))?;
+        let updated = msg.patch.apply(current)?;
+        self.apply_host_config(updated).await
     }
 }
 
-impl Message<ListHosts> for OrchestratorActor {
-    type Reply = Vec<HostStatus>;
+impl Message<ReplaceHostConfig> for OrchestratorActor {
+    type Reply = Result<HostStatus, CoreError>;
 
     async fn handle(
         &mut self,
-        _msg: ListHosts,
+        msg: ReplaceHostConfig,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let mut statuses = Vec::with_capacity(self.hosts.len());
+        self.apply_host_config(msg.config).await
+    }
+}
 
-        for (name, actor_ref) in &self.hosts {
-            match actor_ref.ask(crate::message::GetStatus).await {
-                Ok(status) => statuses.push(status),
-                Err(e) => {
-                    warn!(host = %name, error = %e, "failed to get host status");
-                }
+impl OrchestratorActor {
+    /// Give a registered host a new configuration
+    ///
+    /// Connection changes restart the `HostActor`; other changes are applied in place.
+    async fn apply_host_config(&mut self, updated: HostConfig) -> Result<HostStatus, CoreError> {
+        let name = updated.name.clone();
+        let current = self
+            .configs
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| CoreError::HostNotFound(name.to_string()))?;
+        let actor_ref = self.host_ref(&name)?.clone();
+
+        self.validate_config(&updated)?;
+
+        if current.requires_restart(&updated) {
+            // Restarting mid-operation would abandon a running update
+            let state = actor_ref
+                .ask(crate::message::GetState)
+                .await
+                .map_err(|e| CoreError::ActorError(e.to_string()))?;
+            if state.is_busy() {
+                return Err(CoreError::HostBusy(format!(
+                    "cannot change connection settings of '{name}' while {state}"
+                )));
             }
+
+            // Built first, so a config the factory rejects leaves the old
+            // actor running
+            let args = Self::host_actor_args(
+                self.host_factory.clone(),
+                self.event_tx.clone(),
+                updated.clone(),
+            )
+            .await?;
+            actor_ref.stop_gracefully().await.ok();
+            actor_ref.wait_for_shutdown().await;
+
+            let new_ref = self.spawn_from_args(args).await;
+            self.hosts.insert(name.clone(), new_ref);
+            info!(host = %name, "restarted HostActor with new connection settings");
+        } else {
+            actor_ref
+                .ask(UpdateConfig {
+                    config: updated.clone(),
+                })
+                .await
+                .map_err(|e| CoreError::ActorError(e.to_string()))?;
         }
 
-        statuses
+        self.configs.insert(name.clone(), updated);
+        self.status_cache.remove(&name);
+
+        self.host_ref(&name)?
+            .ask(crate::message::GetStatus)
+            .await
+            .map_err(|e| CoreError::ActorError(e.to_string()))
     }
 }
 
-impl Message<QueryHostInventory> for OrchestratorActor {
-    type Reply = Result<InventoryResult, CoreError>;
+impl Message<GetHostStatus> for OrchestratorActor {
+    type Reply = Result<HostStatus, CoreError>;
 
     async fn handle(
         &mut self,
-        msg: QueryHostInventory,
+        msg: GetHostStatus,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let actor_ref = self
-            .hosts
-            .get(&msg.hostname)
-            .ok_or_else(|| CoreError::HostNotFound(msg.hostname.clone()))?;
+        let actor_ref = self.host_ref(&msg.hostname)?;
 
-        match actor_ref.ask(QueryInventory).await {
-            Ok(inner_result) => Ok(inner_result),
-            Err(e) => Err(CoreError::ActorError(e.to_string())),
-        }
+        actor_ref
+            .ask(crate::message::GetStatus)
+            .await
+            .map_err(|e| CoreError::ActorError(e.to_string()))
     }
 }
 
-impl Message<TriggerHostUpdate> for OrchestratorActor {
-    type Reply = Result<crate::message::UpdateResult, CoreError>;
+impl Message<GetHostCommandHistory> for OrchestratorActor {
+    type Reply = Result<Vec<CommandRecord>, CoreError>;
 
     async fn handle(
         &mut self,
-        msg: TriggerHostUpdate,
+        msg: GetHostCommandHistory,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let actor_ref = self
-            .hosts
-            .get(&msg.hostname)
-            .ok_or_else(|| CoreError::HostNotFound(msg.hostname.clone()))?;
+        let actor_ref = self.host_ref(&msg.hostname)?;
 
-        match actor_ref
-            .ask(StartUpdate {
-                dry_run: msg.dry_run,
-            })
+        actor_ref
+            .ask(GetCommandHistory)
             .await
-        {
-            Ok(inner_result) => Ok(inner_result),
-            Err(e) => Err(CoreError::ActorError(e.to_string())),
-        }
+            .map_err(|e| CoreError::ActorError(e.to_string()))
     }
 }
 
-impl Message<RetryHost> for OrchestratorActor {
-    type Reply = Result<(), CoreError>;
+impl Message<GetHostExecutorStats> for OrchestratorActor {
+    type Reply = Result<Option<ExecutorStatsSnapshot>, CoreError>;
 
     async fn handle(
         &mut self,
-        msg: RetryHost,
+        msg: GetHostExecutorStats,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let actor_ref = self
-            .hosts
-            .get(&msg.hostname)
-            .ok_or_else(|| CoreError::HostNotFound(msg.hostname.clone()))?;
+        let actor_ref = self.host_ref(&msg.hostname)?;
 
-        match actor_ref.ask(Retry).await {
-            Ok(inner_result) => Ok(inner_result),
-            Err(e) => Err(CoreError::ActorError(e.to_string())),
+        actor_ref
+            .ask(GetExecutorStats)
+            .await
+            .map_err(|e| CoreError::ActorError(e.to_string()))
+    }
+}
+
+impl Message<GetHostTransitionHistory> for OrchestratorActor {
+    type Reply = Result<Vec<StateTransition>, CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: GetHostTransitionHistory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = self.host_ref(&msg.hostname)?;
+
+        actor_ref
+            .ask(GetTransitionHistory)
+            .await
+            .map_err(|e| CoreError::ActorError(e.to_string()))
+    }
+}
+
+impl Message<GetHostUpdateHistory> for OrchestratorActor {
+    type Reply = Result<Vec<UpdateHistoryEntry>, CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: GetHostUpdateHistory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = self.host_ref(&msg.hostname)?;
+
+        actor_ref
+            .ask(GetUpdateHistory { limit: msg.limit })
+            .await
+            .map_err(|e| CoreError::ActorError(e.to_string()))
+    }
+}
+
+impl Message<GetHostInventoryDiff> for OrchestratorActor {
+    type Reply = Result<Option<InventoryDiff>, CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: GetHostInventoryDiff,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = self.host_ref(&msg.hostname)?;
+
+        actor_ref
+            .ask(GetInventoryDiff)
+            .await
+            .map_err(|e| CoreError::ActorError(e.to_string()))
+    }
+}
+
+impl Message<ListBusyHosts> for OrchestratorActor {
+    type Reply = Vec<HostName>;
+
+    async fn handle(
+        &mut self,
+        _msg: ListBusyHosts,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let mut busy = Vec::new();
+        for (name, actor_ref) in &self.hosts {
+            match actor_ref.ask(crate::message::GetState).await {
+                Ok(state) if state.blocks_shutdown() => busy.push(name.clone()),
+                Ok(_) => {}
+                Err(e) => warn!(host = %name, error = %e, "failed to get host state"),
+            }
+        }
+        busy.sort();
+        busy
+    }
+}
+
+impl Message<SaveSnapshots> for OrchestratorActor {
+    type Reply = Result<usize, CoreError>;
+
+    async fn handle(
+        &mut self,
+        _msg: SaveSnapshots,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        match &self.snapshot_store {
+            Some(store) => self.save_snapshots(store).await,
+            None => Ok(0),
+        }
+    }
+}
+
+impl Message<RestoreHosts> for OrchestratorActor {
+    type Reply = Vec<HostName>;
+
+    async fn handle(
+        &mut self,
+        _msg: RestoreHosts,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let Some(store) = &self.snapshot_store else {
+            return Vec::new();
+        };
+        let snapshots = match store.take().await {
+            Ok(snapshots) => snapshots,
+            Err(e) => {
+                warn!(error = %e, "failed to read host snapshots");
+                return Vec::new();
+            }
+        };
+
+        let total = snapshots.len();
+        let mut restored = Vec::new();
+        for snapshot in snapshots {
+            let name = snapshot.name.clone();
+            let paused = snapshot.paused;
+            let Some(actor_ref) = self.hosts.get(&name) else {
+                info!(host = %name, "dropping snapshot of unregistered host");
+                continue;
+            };
+            match actor_ref.ask(Restore(snapshot)).await {
+                Ok(true) => {
+                    if paused {
+                        self.paused.insert(name.clone());
+                    }
+                    self.status_cache.remove(&name);
+                    restored.push(name);
+                }
+                Ok(false) => {}
+                Err(e) => warn!(host = %name, error = %e, "failed to restore host"),
+            }
+        }
+        if total > 0 {
+            info!(
+                restored = restored.len(),
+                total, "restored hosts from snapshots"
+            );
+        }
+        restored.sort();
+        restored
+    }
+}
+
+impl Message<SubscribeEvents> for OrchestratorActor {
+    type Reply = broadcast::Receiver<WsEvent>;
+
+    async fn handle(
+        &mut self,
+        _msg: SubscribeEvents,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.event_tx.subscribe()
+    }
+}
+
+impl Message<GetEventHub> for OrchestratorActor {
+    type Reply = EventHub;
+
+    async fn handle(
+        &mut self,
+        _msg: GetEventHub,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.events.clone()
+    }
+}
+
+impl Message<GetRecentEvents> for OrchestratorActor {
+    type Reply = Vec<EventEnvelope>;
+
+    async fn handle(
+        &mut self,
+        msg: GetRecentEvents,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.events.recent_per_host(msg.per_host)
+    }
+}
+
+impl Message<ListHosts> for OrchestratorActor {
+    type Reply = Vec<HostStatus>;
+
+    async fn handle(
+        &mut self,
+        _msg: ListHosts,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let mut statuses: Vec<HostStatus> = self.crashed_statuses().collect();
+
+        for (name, actor_ref) in &self.hosts {
+            match actor_ref.ask(crate::message::GetStatus).await {
+                Ok(status) => statuses.push(status),
+                Err(e) => {
+                    warn!(host = %name, error = %e, "failed to get host status");
+                }
+            }
+        }
+
+        statuses
+    }
+}
+
+impl<M> Message<Traced<M>> for OrchestratorActor
+where
+    M: Send + 'static,
+    OrchestratorActor: Message<M>,
+{
+    type Reply = <OrchestratorActor as Message<M>>::Reply;
+
+    async fn handle(
+        &mut self,
+        traced: Traced<M>,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let Traced { msg, span } = traced;
+        <Self as Message<M>>::handle(self, msg, ctx)
+            .instrument(span)
+            .await
+    }
+}
+
+impl Message<ListHostConfigs> for OrchestratorActor {
+    type Reply = Vec<HostConfig>;
+
+    async fn handle(
+        &mut self,
+        _msg: ListHostConfigs,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let mut configs: Vec<HostConfig> = self.configs.values().cloned().collect();
+        configs.sort_by(|a, b| a.name.cmp(&b.name));
+        configs
+    }
+}
+
+impl Message<HostStatusChanged> for OrchestratorActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: HostStatusChanged,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        match msg.0 {
+            Some(host) => {
+                self.status_cache.remove(&host);
+            }
+            None => self.status_cache.clear(),
+        }
+    }
+}
+
+impl Message<RespawnHost> for OrchestratorActor {
+    type Reply = ();
+
+    async fn handle(
+        &mut self,
+        msg: RespawnHost,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let name = msg.0;
+        // Unregistered or retried in the meantime
+        let Some(restarts) = self.crashed.get(&name).map(|c| c.restarts) else {
+            return;
+        };
+        self.restarts.insert(
+            name.clone(),
+            Restarts {
+                count: restarts + 1,
+                last_at: Instant::now(),
+            },
+        );
+        if let Err(e) = self.respawn_host(&name).await {
+            error!(host = %name, error = %e, "failed to respawn HostActor");
+        }
+    }
+}
+
+impl Message<GetFleetSummary> for OrchestratorActor {
+    type Reply = Result<FleetSummary, CoreError>;
+
+    async fn handle(
+        &mut self,
+        _msg: GetFleetSummary,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        Ok(summarize_fleet(&self.cached_statuses().await))
+    }
+}
+
+/// Aggregate host statuses into a [`FleetSummary`]
+fn summarize_fleet(statuses: &[HostStatus]) -> FleetSummary {
+    let mut summary = FleetSummary {
+        total_hosts: statuses.len(),
+        ..FleetSummary::default()
+    };
+    for status in statuses {
+        *summary.states.entry(status.state.to_string()).or_default() += 1;
+        summary.pending_updates += status.pending_updates.unwrap_or(0);
+        let needs_reboot = status
+            .needs_restart
+            .as_ref()
+            .is_some_and(|r| r.reboot_needed);
+        if status.state == HostState::WaitingReboot || needs_reboot {
+            summary.reboot_pending += 1;
+        }
+        if status.state == HostState::Failed {
+            summary.failed_hosts.push(status.name.to_string());
+        }
+        summary.oldest_last_updated = match (summary.oldest_last_updated, status.last_updated) {
+            (Some(oldest), Some(updated)) => Some(oldest.min(updated)),
+            (oldest, updated) => oldest.or(updated),
+        };
+    }
+    summary.failed_hosts.sort();
+    summary
+}
+
+impl Message<QueryHostInventory> for OrchestratorActor {
+    type Reply = Result<InventoryResult, CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: QueryHostInventory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = self.host_ref(&msg.hostname)?;
+
+        match actor_ref
+            .ask(QueryInventory {
+                refresh: msg.refresh,
+                initiator: Initiator::ManualApi,
+            })
+            .await
+        {
+            Ok(inner_result) => Ok(inner_result),
+            Err(e) => Err(CoreError::ActorError(e.to_string())),
+        }
+    }
+}
+
+impl Message<CollectHostInventory> for OrchestratorActor {
+    type Reply = Result<HostInventory, CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: CollectHostInventory,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = self.host_ref(&msg.hostname)?;
+
+        match actor_ref
+            .ask(CollectInventory {
+                require_osquery: msg.require_osquery,
+            })
+            .await
+        {
+            Ok(inventory) => Ok(inventory),
+            Err(SendError::HandlerError(e @ CoreError::OsqueryNotInstalled { .. })) => Err(e),
+            Err(e) => Err(CoreError::ActorError(e.to_string())),
+        }
+    }
+}
+
+impl Message<InstallHostOsquery> for OrchestratorActor {
+    type Reply = DelegatedReply<Result<OsqueryInstall, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: InstallHostOsquery,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = match self.host_ref(&msg.hostname) {
+            Ok(actor_ref) => actor_ref.clone(),
+            Err(e) => return ctx.reply(Err(e)),
+        };
+
+        // Installing takes a while; other hosts are served in the meantime
+        ctx.spawn(async move {
+            match actor_ref
+                .ask(InstallOsquery {
+                    initiator: Initiator::ManualApi,
+                })
+                .await
+            {
+                Ok(install) => Ok(install),
+                Err(SendError::HandlerError(e)) => Err(e),
+                Err(e) => Err(CoreError::ActorError(e.to_string())),
+            }
+        })
+    }
+}
+
+impl Message<TriggerHostUpdate> for OrchestratorActor {
+    type Reply = DelegatedReply<Result<crate::message::UpdateResult, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: TriggerHostUpdate,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = match self.host_ref(&msg.hostname) {
+            Ok(actor_ref) => actor_ref.clone(),
+            Err(e) => return ctx.reply(Err(e)),
+        };
+
+        // Wait for the update outside the orchestrator so other hosts
+        // (and cancellation) can be served in the meantime
+        ctx.spawn(async move {
+            match actor_ref
+                .ask(StartUpdate {
+                    dry_run: msg.dry_run,
+                    scope: msg.scope,
+                    stack: msg.stack,
+                    packages: msg.packages,
+                    initiator: Initiator::ManualApi,
+                    force: msg.force,
+                    skip_cleanup: msg.skip_cleanup,
+                })
+                .await
+            {
+                Ok(inner_result) => Ok(inner_result),
+                Err(SendError::HandlerError(e)) => Err(e),
+                Err(e) => Err(CoreError::ActorError(e.to_string())),
+            }
+        })
+    }
+}
+
+impl Message<PreviewHostUpdate> for OrchestratorActor {
+    type Reply = DelegatedReply<Result<UpdatePreview, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: PreviewHostUpdate,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = match self.host_ref(&msg.hostname) {
+            Ok(actor_ref) => actor_ref.clone(),
+            Err(e) => return ctx.reply(Err(e)),
+        };
+
+        // The simulation can take a while; other hosts are served meanwhile
+        ctx.spawn(async move {
+            match actor_ref.ask(PreviewUpdate { scope: msg.scope }).await {
+                Ok(preview) => Ok(preview),
+                Err(SendError::HandlerError(e)) => Err(e),
+                Err(e) => Err(CoreError::ActorError(e.to_string())),
+            }
+        })
+    }
+}
+
+impl Message<CancelHostUpdate> for OrchestratorActor {
+    type Reply = Result<(), CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: CancelHostUpdate,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let actor_ref = self.host_ref(&msg.hostname)?;
+
+        match actor_ref.ask(CancelUpdate).await {
+            Ok(inner_result) => Ok(inner_result),
+            Err(SendError::HandlerError(e)) => Err(e),
+            Err(e) => Err(CoreError::ActorError(e.to_string())),
+        }
+    }
+}
+
+impl Message<RetryHost> for OrchestratorActor {
+    type Reply = Result<(), CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: RetryHost,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        // Retrying a crashed host respawns it with a fresh restart budget
+        if self.crashed.contains_key(&msg.hostname) {
+            self.restarts.remove(&msg.hostname);
+            return self.respawn_host(&msg.hostname).await;
+        }
+        let actor_ref = self.host_ref(&msg.hostname)?;
+
+        match actor_ref.ask(Retry).await {
+            Ok(inner_result) => Ok(inner_result),
+            Err(SendError::HandlerError(e)) => Err(e),
+            Err(e) => Err(CoreError::ActorError(e.to_string())),
         }
     }
 }
@@ -324,115 +1617,444 @@ impl Message<AcknowledgeHost> for OrchestratorActor {
         msg: AcknowledgeHost,
         _ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
-        let actor_ref = self
-            .hosts
-            .get(&msg.hostname)
-            .ok_or_else(|| CoreError::HostNotFound(msg.hostname.clone()))?;
+        let actor_ref = self.host_ref(&msg.hostname)?;
 
         match actor_ref.ask(Acknowledge).await {
             Ok(inner_result) => Ok(inner_result),
+            Err(SendError::HandlerError(e)) => Err(e),
             Err(e) => Err(CoreError::ActorError(e.to_string())),
         }
     }
 }
 
-impl Message<TriggerFleetUpdate> for OrchestratorActor {
-    type Reply = Result<FleetUpdateProgress, CoreError>;
+impl OrchestratorActor {
+    /// Pause or resume automation on a registered host
+    ///
+    /// Crashed hosts are paused too, once respawned.
+    async fn set_paused(&mut self, name: &HostName, paused: bool) -> Result<(), CoreError> {
+        if !self.configs.contains_key(name) {
+            return Err(CoreError::HostNotFound(name.to_string()));
+        }
+        let changed = if paused {
+            self.paused.insert(name.clone())
+        } else {
+            self.paused.remove(name)
+        };
+        if !changed {
+            return Ok(());
+        }
+
+        if let Some(actor_ref) = self.hosts.get(name) {
+            actor_ref
+                .ask(SetPaused { paused })
+                .await
+                .map_err(|e| CoreError::ActorError(e.to_string()))?;
+        }
+        self.status_cache.remove(name);
+        info!(host = %name, "host {}", if paused { "paused" } else { "resumed" });
+        let _ = self.event_tx.send(WsEvent::HostPauseChanged {
+            host: name.to_string(),
+            paused,
+        });
+        Ok(())
+    }
+}
+
+impl Message<PauseHost> for OrchestratorActor {
+    type Reply = Result<(), CoreError>;
 
     async fn handle(
         &mut self,
-        msg: TriggerFleetUpdate,
+        msg: PauseHost,
         _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.set_paused(&msg.hostname, true).await
+    }
+}
+
+impl Message<ResumeHost> for OrchestratorActor {
+    type Reply = Result<(), CoreError>;
+
+    async fn handle(
+        &mut self,
+        msg: ResumeHost,
+        _ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        self.set_paused(&msg.hostname, false).await
+    }
+}
+
+impl Message<FleetDryRun> for OrchestratorActor {
+    type Reply = DelegatedReply<Result<FleetDryRunReport, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: FleetDryRun,
+        ctx: &mut Context<Self, Self::Reply>,
     ) -> Self::Reply {
         let config = msg.config;
+        let facts = self.fleet_facts(config.filter.as_ref()).await;
+        // A fleet update would skip paused hosts, so there's nothing to predict
+        let plan = match self.plan_fleet_update(&config, &facts) {
+            Ok(plan) => plan,
+            Err(e) => return ctx.reply(Err(e)),
+        };
 
-        // Filter hosts based on config
-        let hosts_to_update: Vec<_> = self
-            .hosts
-            .iter()
-            .filter(|(name, _)| {
-                if let Some(ref filter) = config.filter {
-                    // Check exclusions
-                    if filter.exclude_hosts.contains(name) {
-                        return false;
-                    }
-                    // Check tags (if specified, host must have at least one)
-                    if !filter.tags.is_empty()
-                        && let Some(hc) = self.configs.get(*name)
-                        && !filter.tags.iter().any(|t| hc.tags.contains(t))
-                    {
-                        return false;
-                    }
+        ctx.spawn(async move {
+            let mut results = Vec::new();
+            for (_, batch) in &plan.batches {
+                let handles: Vec<_> = batch
+                    .iter()
+                    .map(|(name, actor_ref)| {
+                        let name = name.clone();
+                        let actor = actor_ref.clone();
+                        let scope = config.scope;
+                        tokio::spawn(async move { dry_run_host(name, actor, scope).await })
+                    })
+                    .collect();
+                for ((name, _), handle) in batch.iter().zip(handles) {
+                    results.push(handle.await.unwrap_or_else(|e| HostDryRun {
+                        host: name.to_string(),
+                        pending_updates: 0,
+                        security_updates: 0,
+                        packages: Vec::new(),
+                        error: Some(format!("task panicked: {e}")),
+                        warnings: Vec::new(),
+                    }));
                 }
-                true
-            })
-            .map(|(name, actor)| (name.clone(), actor.clone()))
-            .collect();
+            }
 
-        let total = hosts_to_update.len();
-        let mut completed = 0;
-        let mut failed = 0;
+            let mut report = FleetDryRunReport::new(results);
+            report.batches = plan.order();
+            info!(
+                total_hosts = report.total_hosts,
+                with_updates = report.hosts_with_updates,
+                failed = report.failed_hosts,
+                "fleet dry run finished"
+            );
+            Ok(report)
+        })
+    }
+}
 
-        info!(
-            total_hosts = total,
-            batch_size = config.batch_size,
-            "starting fleet update"
-        );
+/// Query a host and simulate its update
+async fn dry_run_host(
+    host: HostName,
+    actor: ActorRef<HostActor>,
+    scope: Option<UpdateScope>,
+) -> HostDryRun {
+    let mut result = HostDryRun {
+        host: host.to_string(),
+        pending_updates: 0,
+        security_updates: 0,
+        packages: Vec::new(),
+        error: None,
+        warnings: Vec::new(),
+    };
+
+    let inventory = match actor.ask(QueryInventory::default()).await {
+        Ok(inventory) => inventory,
+        Err(e) => {
+            result.error = Some(e.to_string());
+            return result;
+        }
+    };
+    result.security_updates = inventory.security_updates;
+    result.packages = inventory.packages;
+    // A host without updates is left `Idle` and has nothing to simulate
+    if inventory.pending_updates == 0 {
+        return result;
+    }
+
+    match actor
+        .ask(StartUpdate {
+            dry_run: true,
+            scope,
+            stack: None,
+            packages: Vec::new(),
+            initiator: Initiator::ManualApi,
+            force: false,
+            skip_cleanup: false,
+        })
+        .await
+    {
+        Ok(update) => {
+            result.pending_updates = update.upgraded_count;
+            result.warnings = update.warnings;
+        }
+        Err(e) => {
+            result.pending_updates = inventory.pending_updates;
+            result.error = Some(e.to_string());
+        }
+    }
+    result
+}
 
-        // Process in batches
-        for batch in hosts_to_update.chunks(config.batch_size) {
-            let mut handles = Vec::new();
+/// Update one batch of a fleet update in parallel and wait for all of it
+///
+/// Each host is reserved for the fleet update before it is queried, so a
+/// manual operation can neither slip in between the query and the update
+/// nor fail with a confusing state transition error.
+async fn update_fleet_batch(
+    batch: &[(HostName, ActorRef<HostActor>)],
+    phase: FleetPhase,
+    initiator: Initiator,
+    config: &FleetUpdateConfig,
+    audit_log: Option<&Arc<AuditLog>>,
+    event_tx: &broadcast::Sender<WsEvent>,
+) -> Vec<FleetHostOutcome> {
+    let mut handles = Vec::new();
+
+    for (name, actor_ref) in batch {
+        let actor = actor_ref.clone();
+        let host_name = name.clone();
+        let dry_run = config.dry_run;
+        let scope = config.scope;
+
+        if let Some(audit_log) = audit_log {
+            let entry = AuditEntry {
+                timestamp: chrono::Utc::now(),
+                operation: "update".to_string(),
+                hosts: vec![host_name.to_string()],
+                params: serde_json::json!({ "dry_run": dry_run, "scope": scope, "phase": phase }),
+                source: "fleet".to_string(),
+                remote_addr: None,
+                identity: None,
+                status: None,
+            };
+            if let Err(e) = audit_log.record(&entry).await {
+                warn!(host = %host_name, error = %e, "failed to write audit entry");
+            }
+        }
 
-            for (name, actor_ref) in batch {
-                let actor = actor_ref.clone();
-                let host_name = name.clone();
-                let dry_run = config.dry_run;
+        let handle = tokio::spawn(async move {
+            match actor.ask(ReserveForUpdate { initiator }).await {
+                Ok(()) => {}
+                Err(SendError::HandlerError(e)) => return Err(SendError::HandlerError(e)),
+                Err(e) => {
+                    return Err(SendError::HandlerError(CoreError::ActorError(
+                        e.to_string(),
+                    )));
+                }
+            }
 
-                let handle = tokio::spawn(async move {
-                    // First query inventory, then update
-                    let _ = actor.ask(QueryInventory).await;
-                    actor.ask(StartUpdate { dry_run }).await
-                });
+            // First query inventory, then update; a host whose circuit
+            // breaker is open is skipped without being contacted
+            let result = match actor
+                .ask(QueryInventory {
+                    refresh: false,
+                    initiator,
+                })
+                .await
+            {
+                Err(SendError::HandlerError(e @ CoreError::HostUnreachable { .. })) => {
+                    Err(SendError::HandlerError(e))
+                }
+                _ => {
+                    actor
+                        .ask(StartUpdate {
+                            dry_run,
+                            scope,
+                            stack: None,
+                            packages: Vec::new(),
+                            initiator,
+                            force: false,
+                            skip_cleanup: false,
+                        })
+                        .await
+                }
+            };
+            // Hosts left without an update still hold the reservation
+            let _ = actor.tell(ReleaseReservation { initiator }).await;
+            result
+        });
+
+        handles.push((host_name, handle));
+    }
 
-                handles.push((host_name, handle));
+    // Wait for batch to complete
+    let mut outcomes = Vec::with_capacity(handles.len());
+    for (name, handle) in handles {
+        let mut skipped = false;
+        let error = match handle.await {
+            Ok(Ok(_)) => {
+                info!(host = %name, %phase, "update completed");
+                None
+            }
+            Ok(Err(SendError::HandlerError(e @ CoreError::HostUnreachable { .. }))) => {
+                warn!(host = %name, %phase, error = %e, "host skipped");
+                skipped = true;
+                Some(e.to_string())
+            }
+            Ok(Err(e)) => {
+                error!(host = %name, %phase, error = %e, "update failed");
+                Some(e.to_string())
             }
+            Err(e) => {
+                error!(host = %name, %phase, error = %e, "task panicked");
+                Some(format!("task panicked: {e}"))
+            }
+        };
 
-            // Wait for batch to complete
-            for (name, handle) in handles {
-                match handle.await {
-                    Ok(Ok(_)) => {
-                        completed += 1;
-                        info!(host = %name, "update completed");
-                    }
-                    Ok(Err(e)) => {
-                        failed += 1;
-                        error!(host = %name, error = %e, "update failed");
-                    }
-                    Err(e) => {
-                        failed += 1;
-                        error!(host = %name, error = %e, "task panicked");
+        let _ = event_tx.send(WsEvent::FleetHostFinished {
+            host: name.to_string(),
+            phase,
+            success: error.is_none(),
+            skipped,
+            error: error.clone(),
+        });
+        outcomes.push(FleetHostOutcome {
+            host: name,
+            phase,
+            success: error.is_none(),
+            skipped,
+            error,
+        });
+    }
+    outcomes
+}
+
+impl Message<TriggerFleetUpdate> for OrchestratorActor {
+    type Reply = DelegatedReply<Result<FleetUpdateProgress, CoreError>>;
+
+    async fn handle(
+        &mut self,
+        msg: TriggerFleetUpdate,
+        ctx: &mut Context<Self, Self::Reply>,
+    ) -> Self::Reply {
+        let TriggerFleetUpdate { config, scheduled } = msg;
+        let facts = self.fleet_facts(config.filter.as_ref()).await;
+        let FleetPlan { paused, batches } = match self.plan_fleet_update(&config, &facts) {
+            Ok(plan) => plan,
+            Err(e) => return ctx.reply(Err(e)),
+        };
+
+        let audit_log = self.audit_log.clone();
+        let event_tx = self.event_tx.clone();
+        self.last_fleet_id += 1;
+        let id = self.last_fleet_id;
+        let initiator = if scheduled {
+            Initiator::Scheduler(id)
+        } else {
+            Initiator::FleetUpdate(id)
+        };
+
+        // Batches run outside the orchestrator so hosts stay reachable
+        // (status, cancellation) while the fleet update progresses
+        ctx.spawn(async move {
+            let count_phase = |wanted: FleetPhase| -> usize {
+                batches
+                    .iter()
+                    .filter(|(phase, _)| *phase == wanted)
+                    .map(|(_, batch)| batch.len())
+                    .sum()
+            };
+            let main = count_phase(FleetPhase::Main);
+            let canaries = count_phase(FleetPhase::Canary);
+            let total = canaries + main + paused.len();
+            let mut outcomes = Vec::with_capacity(total);
+            let mut skipped = 0;
+
+            info!(
+                fleet_update = id,
+                total_hosts = total,
+                canaries,
+                batches = batches.len(),
+                batch_size = config.batch_size,
+                "starting fleet update"
+            );
+
+            for (phase, batch) in &batches {
+                let phase = *phase;
+                // Delay between batches (none before the first)
+                if !outcomes.is_empty() && !config.delay_between_batches.is_zero() {
+                    tokio::time::sleep(config.delay_between_batches).await;
+                }
+
+                outcomes.extend(
+                    update_fleet_batch(
+                        batch,
+                        phase,
+                        initiator,
+                        &config,
+                        audit_log.as_ref(),
+                        &event_tx,
+                    )
+                    .await,
+                );
+
+                // A skipped canary proved nothing, so it halts the update too
+                if phase == FleetPhase::Canary && config.halt_on_canary_failure {
+                    let failed_canaries: Vec<String> = outcomes
+                        .iter()
+                        .filter(|o| !o.success)
+                        .map(|o| o.host.to_string())
+                        .collect();
+                    if !failed_canaries.is_empty() {
+                        skipped = main;
+                        warn!(
+                            failed = ?failed_canaries,
+                            skipped,
+                            "canary update failed, halting fleet update"
+                        );
+                        let _ = event_tx.send(WsEvent::FleetUpdateHalted {
+                            failed_canaries,
+                            skipped_hosts: skipped,
+                        });
+                        break;
                     }
                 }
             }
 
-            // Delay between batches (skip for last batch)
-            if !config.delay_between_batches.is_zero() && completed + failed < total {
-                tokio::time::sleep(config.delay_between_batches).await;
+            // Paused hosts are reported as skipped without being contacted
+            for host in paused {
+                warn!(fleet_update = id, host = %host, "paused host skipped");
+                let error = Some(CoreError::HostPaused(host.to_string()).to_string());
+                let _ = event_tx.send(WsEvent::FleetHostFinished {
+                    host: host.to_string(),
+                    phase: FleetPhase::Main,
+                    success: false,
+                    skipped: true,
+                    error: error.clone(),
+                });
+                outcomes.push(FleetHostOutcome {
+                    host,
+                    phase: FleetPhase::Main,
+                    success: false,
+                    skipped: true,
+                    error,
+                });
             }
-        }
 
-        info!(
-            total = total,
-            completed = completed,
-            failed = failed,
-            "fleet update finished"
-        );
-
-        Ok(FleetUpdateProgress {
-            total_hosts: total,
-            completed,
-            failed,
-            in_progress: 0,
+            let completed = outcomes.iter().filter(|o| o.success).count();
+            let unreachable = outcomes.iter().filter(|o| o.skipped).count();
+            let failed = outcomes.len() - completed - unreachable;
+            skipped += unreachable;
+            info!(
+                fleet_update = id,
+                total = total,
+                completed = completed,
+                failed = failed,
+                skipped = skipped,
+                "fleet update finished"
+            );
+            let _ = event_tx.send(WsEvent::FleetUpdateFinished {
+                total_hosts: total,
+                completed,
+                failed,
+                skipped,
+                dry_run: config.dry_run,
+            });
+
+            Ok(FleetUpdateProgress {
+                id,
+                total_hosts: total,
+                completed,
+                failed,
+                in_progress: 0,
+                skipped,
+                hosts: outcomes,
+            })
         })
     }
 }
@@ -0,0 +1,300 @@
+//! Append-only audit log of mutating operations
+//!
+//! Entries are stored as JSON lines. When the active file would grow past
+//! the configured size it is rotated to `<path>.1`, shifting older files up
+//! to `<path>.<max_files>`.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use tokio::fs::{self, OpenOptions};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use tendhost_api::responses::AuditEntry;
+
+use crate::error::CoreError;
+
+/// Default size at which the active log file is rotated (10 MiB)
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Default number of rotated files kept besides the active one
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// Filter for reading audit entries back
+#[derive(Debug, Clone)]
+pub struct AuditQuery {
+    /// Only entries touching this host
+    pub host: Option<String>,
+    /// Only entries at or after this time
+    pub since: Option<DateTime<Utc>>,
+    /// Maximum number of entries to return
+    pub limit: usize,
+}
+
+impl Default for AuditQuery {
+    fn default() -> Self {
+        Self {
+            host: None,
+            since: None,
+            limit: 100,
+        }
+    }
+}
+
+impl AuditQuery {
+    /// Whether `entry` passes this filter (ignoring `limit`)
+    fn matches(&self, entry: &AuditEntry) -> bool {
+        if let Some(ref host) = self.host
+            // Entries written before names were normalized may be mixed-case
+            && !entry.hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+        {
+            return false;
+        }
+        if let Some(since) = self.since
+            && entry.timestamp < since
+        {
+            return false;
+        }
+        true
+    }
+}
+
+/// JSON lines audit log with size-based rotation
+pub struct AuditLog {
+    /// Active log file
+    path: PathBuf,
+    /// Rotate once the active file would exceed this many bytes (0 disables rotation)
+    max_size: u64,
+    /// Number of rotated files to keep
+    max_files: usize,
+    /// Serializes appends and rotation
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Create an audit log writing to `path` with default rotation settings
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size: DEFAULT_MAX_SIZE,
+            max_files: DEFAULT_MAX_FILES,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Set rotation size and the number of rotated files to keep
+    #[must_use]
+    pub fn with_rotation(mut self, max_size: u64, max_files: usize) -> Self {
+        self.max_size = max_size;
+        self.max_files = max_files;
+        self
+    }
+
+    /// Path of the active log file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Path of the `n`th rotated file
+    fn rotated_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{n}"));
+        PathBuf::from(name)
+    }
+
+    /// Append an entry
+    ///
+    /// # Errors
+    /// Returns `CoreError::AuditError` if the entry cannot be serialized or written
+    pub async fn record(&self, entry: &AuditEntry) -> Result<(), CoreError> {
+        let mut line =
+            serde_json::to_string(entry).map_err(|e| CoreError::AuditError(e.to_string()))?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().await;
+
+        if let Some(parent) = self.path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent).await.map_err(audit_io_error)?;
+        }
+
+        let current_size = match fs::metadata(&self.path).await {
+            Ok(meta) => meta.len(),
+            Err(_) => 0,
+        };
+        if self.max_size > 0 && current_size > 0 && current_size + line.len() as u64 > self.max_size
+        {
+            self.rotate().await?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(audit_io_error)?;
+        file.write_all(line.as_bytes())
+            .await
+            .map_err(audit_io_error)?;
+        file.flush().await.map_err(audit_io_error)?;
+
+        Ok(())
+    }
+
+    /// Shift rotated files up by one and move the active file to `<path>.1`
+    async fn rotate(&self) -> Result<(), CoreError> {
+        if self.max_files == 0 {
+            return fs::remove_file(&self.path).await.map_err(audit_io_error);
+        }
+
+        // The oldest file falls off the end
+        let _ = fs::remove_file(self.rotated_path(self.max_files)).await;
+        for n in (1..self.max_files).rev() {
+            let from = self.rotated_path(n);
+            if fs::try_exists(&from).await.unwrap_or(false) {
+                fs::rename(&from, self.rotated_path(n + 1))
+                    .await
+                    .map_err(audit_io_error)?;
+            }
+        }
+        fs::rename(&self.path, self.rotated_path(1))
+            .await
+            .map_err(audit_io_error)?;
+
+        debug!(path = %self.path.display(), "rotated audit log");
+        Ok(())
+    }
+
+    /// Read matching entries, newest first
+    ///
+    /// Rotated files are searched after the active one. Lines that fail to
+    /// parse are skipped.
+    ///
+    /// # Errors
+    /// Returns `CoreError::AuditError` if a log file exists but cannot be read
+    pub async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEntry>, CoreError> {
+        let _guard = self.lock.lock().await;
+
+        let files = std::iter::once(self.path.clone())
+            .chain((1..=self.max_files).map(|n| self.rotated_path(n)));
+
+        let mut entries = Vec::new();
+        for path in files {
+            let content = match fs::read_to_string(&path).await {
+                Ok(content) => content,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(audit_io_error(e)),
+            };
+
+            for line in content.lines().rev() {
+                if entries.len() >= query.limit {
+                    return Ok(entries);
+                }
+                match serde_json::from_str::<AuditEntry>(line) {
+                    Ok(entry) if query.matches(&entry) => entries.push(entry),
+                    Ok(_) => {}
+                    Err(e) => debug!(error = %e, "skipping malformed audit line"),
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+}
+
+fn audit_io_error(e: std::io::Error) -> CoreError {
+    CoreError::AuditError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log_path(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("tendhost-audit-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir.join("audit.jsonl")
+    }
+
+    fn entry(operation: &str, host: &str) -> AuditEntry {
+        AuditEntry {
+            timestamp: Utc::now(),
+            operation: operation.to_string(),
+            hosts: vec![host.to_string()],
+            params: serde_json::json!({ "dry_run": false }),
+            source: "api".to_string(),
+            remote_addr: Some("127.0.0.1:50000".to_string()),
+            identity: None,
+            status: Some(202),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_record_and_query_newest_first() {
+        let log = AuditLog::new(temp_log_path("query"));
+
+        log.record(&entry("update", "web-1")).await.unwrap();
+        log.record(&entry("reboot", "web-1")).await.unwrap();
+        log.record(&entry("retry", "db-1")).await.unwrap();
+
+        let all = log.query(&AuditQuery::default()).await.unwrap();
+        let ops: Vec<&str> = all.iter().map(|e| e.operation.as_str()).collect();
+        assert_eq!(ops, vec!["retry", "reboot", "update"]);
+
+        let web = log
+            .query(&AuditQuery {
+                host: Some("web-1".to_string()),
+                limit: 1,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(web.len(), 1);
+        assert_eq!(web[0].operation, "reboot");
+
+        let mixed_case = log
+            .query(&AuditQuery {
+                host: Some("WEB-1".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(mixed_case.len(), 2);
+
+        let future = log
+            .query(&AuditQuery {
+                since: Some(Utc::now() + chrono::Duration::hours(1)),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(future.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_rotation_caps_file_count() {
+        let path = temp_log_path("rotate");
+        // Small enough that every entry triggers a rotation
+        let log = AuditLog::new(&path).with_rotation(64, 2);
+
+        for op in ["one", "two", "three", "four"] {
+            log.record(&entry(op, "web-1")).await.unwrap();
+        }
+
+        assert!(path.exists());
+        assert!(log.rotated_path(1).exists());
+        assert!(log.rotated_path(2).exists());
+        assert!(!log.rotated_path(3).exists());
+
+        // Oldest entry was dropped with the third rotated file
+        let all = log.query(&AuditQuery::default()).await.unwrap();
+        let ops: Vec<&str> = all.iter().map(|e| e.operation.as_str()).collect();
+        assert_eq!(ops, vec!["four", "three", "two"]);
+    }
+}
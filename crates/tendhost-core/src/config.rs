@@ -1,27 +1,101 @@
 //! Configuration types for hosts and fleet operations
 
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
 use std::time::Duration;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use tendhost_api::requests::UpdateScope;
+use tendhost_exec::recording::DEFAULT_HISTORY_SIZE;
+use tendhost_pkg::{
+    DEFAULT_METADATA_MAX_AGE, LockWait, OperationTimeouts, PrivilegeEscalation, PrunePolicy,
+};
+
+use crate::error::CoreError;
+use crate::facts::FactFilter;
+use crate::host_name::HostName;
+pub use crate::host_name::MAX_HOST_NAME_LEN;
+use crate::state::FailureKind;
+
+/// Maximum length of a host address
+pub const MAX_ADDR_LEN: usize = 253;
+
+/// SSH port used when neither `port` nor `addr` names one
+pub const DEFAULT_SSH_PORT: u16 = 22;
+
+/// Number of finished updates kept per host unless the policy says otherwise
+pub const DEFAULT_UPDATE_HISTORY_SIZE: usize = 50;
+
+/// Lines of a failed command's output kept unless the policy says otherwise
+pub const DEFAULT_FAILURE_OUTPUT_LINES: usize = 100;
+
+/// Free space in MiB required before an update unless the policy says
+/// otherwise
+pub const DEFAULT_MIN_FREE_SPACE_MB: &[(&str, u64)] =
+    &[("/", 1024), ("/boot", 500), ("/var", 1024)];
+
+/// Maximum number of tags per host
+pub const MAX_TAGS: usize = 32;
+
+/// Maximum length of a single tag
+pub const MAX_TAG_LEN: usize = 64;
 
 /// Configuration for a single managed host
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct HostConfig {
     /// Unique hostname identifier
-    pub name: String,
-    /// IP address or hostname for SSH connection
+    pub name: HostName,
+    /// IP address or hostname for SSH connection, optionally as `host:port`
     pub addr: String,
+    /// SSH port; overrides a port in `addr` (default 22)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
     /// SSH user (defaults to root)
     #[serde(default = "default_user")]
     pub user: String,
     /// Path to SSH private key (optional, falls back to ssh-agent)
     pub ssh_key: Option<String>,
+    /// Environment variable holding the passphrase of `ssh_key`, for
+    /// encrypted keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssh_key_passphrase_env: Option<String>,
+    /// How long establishing the SSH connection may take, written as
+    /// `connect_timeout_secs` (defaults to the executor's timeout)
+    #[serde(
+        default,
+        rename = "connect_timeout_secs",
+        with = "option_secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub connect_timeout: Option<Duration>,
+    /// Most commands run over the SSH connection at once (defaults to the
+    /// executor's limit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_ssh_channels: Option<usize>,
+    /// Bastion to tunnel the SSH connection through: the name of another
+    /// configured host, or an inline `[user@]host[:port]` spec
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jump_host: Option<String>,
     /// Docker compose directories to manage
     #[serde(default)]
     pub compose_paths: Vec<String>,
+    /// Env file to use instead of `.env`, by compose directory; relative
+    /// paths are taken from the compose directory
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub compose_env_files: BTreeMap<String, String>,
     /// Tags for filtering and grouping
     #[serde(default)]
     pub tags: Vec<String>,
+    /// Hosts this one needs running, such as an NFS server it mounts;
+    /// fleet updates only reach them after this host
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<HostName>,
+    /// Environment variables set for every command run on the host, such
+    /// as `http_proxy` for hosts that reach their mirrors through a proxy
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
     /// Host-specific policy settings
     #[serde(default)]
     pub policy: HostPolicy,
@@ -31,22 +105,477 @@ fn default_user() -> String {
     "root".to_string()
 }
 
+/// Serde for optional durations written as whole seconds
+mod option_secs {
+    use super::{Deserialize, Deserializer, Duration, Serialize, Serializer};
+
+    #[allow(clippy::ref_option)]
+    pub fn serialize<S: Serializer>(value: &Option<Duration>, s: S) -> Result<S::Ok, S::Error> {
+        value.map(|d| d.as_secs()).serialize(s)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Duration>, D::Error> {
+        Ok(Option::<u64>::deserialize(d)?.map(Duration::from_secs))
+    }
+}
+
 /// Policy settings for host operations
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct HostPolicy {
     /// Automatically reboot when kernel updates require it
     #[serde(default = "default_auto_reboot")]
     pub auto_reboot: bool,
     /// Time window when updates are allowed
     pub maintenance_window: Option<MaintenanceWindow>,
+    /// Scope used when an update request doesn't specify one
+    #[serde(default)]
+    pub default_scope: UpdateScope,
+    /// Seconds between reachability probes while idle (default 60, 0 disables)
+    #[serde(default)]
+    pub health_check_interval_secs: Option<u64>,
+    /// Consecutive failed probes before the host is marked unreachable (default 3)
+    #[serde(default)]
+    pub unreachable_after: Option<u32>,
+    /// Consecutive connection failures before operations fail fast without
+    /// contacting the host (default 5, 0 disables)
+    #[serde(default)]
+    pub circuit_breaker_after: Option<u32>,
+    /// Seconds operations fail fast before the host is tried again
+    /// (default 300)
+    #[serde(default)]
+    pub circuit_breaker_cooldown_secs: Option<u64>,
+    /// Package manager command timeouts
+    #[serde(default)]
+    pub timeouts: TimeoutPolicy,
+    /// Docker image cleanup after compose updates (off by default)
+    #[serde(default)]
+    pub image_prune: ImagePrunePolicy,
+    /// Seconds package lists are reused before a query refreshes them
+    /// (default 900, 0 always refreshes)
+    #[serde(default)]
+    pub metadata_max_age_secs: Option<u64>,
+    /// Shell commands run in order before an update; a failure aborts it
+    #[serde(default)]
+    pub pre_update_hooks: Vec<String>,
+    /// Shell commands run in order after an update; a failure is reported
+    /// as a warning of the update, which still succeeds
+    #[serde(default)]
+    pub post_update_hooks: Vec<String>,
+    /// Run post-update hooks when the update failed (default true)
+    #[serde(default)]
+    pub run_post_on_failure: Option<bool>,
+    /// Seconds each hook may run before it counts as failed (default 300)
+    #[serde(default)]
+    pub hook_timeout_secs: Option<u64>,
+    /// Automatic retries after transient failures
+    #[serde(default)]
+    pub auto_retry: AutoRetryPolicy,
+    /// Number of recent commands kept for debugging (default 50)
+    #[serde(default)]
+    pub command_history_size: Option<usize>,
+    /// Number of finished updates kept in the host's update history (default 50)
+    #[serde(default)]
+    pub update_history_size: Option<usize>,
+    /// Lines of a failed package manager command's output kept for
+    /// diagnosis (default 100)
+    #[serde(default)]
+    pub failure_output_lines: Option<usize>,
+    /// Restart services left running outdated code after an update, when no
+    /// reboot is needed
+    #[serde(default)]
+    pub auto_restart_services: bool,
+    /// Remove packages nothing needs anymore, such as old kernels, after a
+    /// successful system update
+    #[serde(default)]
+    pub autoremove: bool,
+    /// Delete downloaded package files after a successful system update
+    #[serde(default)]
+    pub clean_cache: bool,
+    /// Commands run in order to verify the host after a reboot; when empty,
+    /// only checks that a command can be run
+    #[serde(default)]
+    pub health_checks: Vec<HealthCheckSpec>,
+    /// Free space in MiB each mount point needs before a package update
+    /// starts (default 1024 on `/` and `/var`, 500 on `/boot`); an empty
+    /// table disables the check
+    ///
+    /// ```toml
+    /// [host.policy.min_free_space_mb]
+    /// "/boot" = 300
+    /// "/var" = 2048
+    /// ```
+    #[serde(default)]
+    pub min_free_space_mb: Option<BTreeMap<String, u64>>,
+    /// How commands that need root are run (default `sudo`); ignored when
+    /// the SSH user is root. Docker compose commands only use it when set.
+    ///
+    /// ```toml
+    /// [host.policy]
+    /// escalation = "doas"   # or "none", "sudo", { custom = "pfexec" }
+    /// ```
+    #[serde(default)]
+    pub escalation: Option<PrivilegeEscalation>,
+    /// Seconds an update waits for another package manager run (e.g.
+    /// unattended-upgrades) to release its lock before failing (default
+    /// 300, 0 fails at once)
+    #[serde(default)]
+    pub lock_wait_secs: Option<u64>,
+}
+
+/// A command whose result shows whether the host is healthy
+///
+/// ```toml
+/// [[hosts.policy.health_checks]]
+/// command = "systemctl is-active nginx"
+/// expected_output = "active"
+/// timeout_secs = 10
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthCheckSpec {
+    /// Shell command to run
+    pub command: String,
+    /// Exit code the command must return (default 0)
+    #[serde(default)]
+    pub expected_exit_code: Option<i32>,
+    /// Text that must appear in the command's stdout
+    #[serde(default)]
+    pub expected_output: Option<String>,
+    /// Seconds the command may run before the check fails (default 30)
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Default seconds a health check command may run
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT_SECS: u64 = 30;
+
+impl HealthCheckSpec {
+    /// Check that only verifies a command can be run, used when a policy
+    /// lists no health checks
+    #[must_use]
+    pub fn connectivity() -> Self {
+        Self {
+            command: "echo ok".to_string(),
+            expected_exit_code: None,
+            expected_output: Some("ok".to_string()),
+            timeout_secs: None,
+        }
+    }
+
+    /// Exit code the command must return
+    #[must_use]
+    pub fn expected_exit(&self) -> i32 {
+        self.expected_exit_code.unwrap_or(0)
+    }
+
+    /// Time limit for the command
+    #[must_use]
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.timeout_secs
+                .unwrap_or(DEFAULT_HEALTH_CHECK_TIMEOUT_SECS),
+        )
+    }
+}
+
+/// Package manager command timeouts in seconds
+///
+/// Unset fields use the [`OperationTimeouts`] defaults (5m/30m/2m).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeoutPolicy {
+    /// Refreshing package lists
+    #[serde(default)]
+    pub update_lists_secs: Option<u64>,
+    /// Applying upgrades
+    #[serde(default)]
+    pub upgrade_secs: Option<u64>,
+    /// Read-only queries and dry runs
+    #[serde(default)]
+    pub query_secs: Option<u64>,
+}
+
+impl TimeoutPolicy {
+    /// Resolve into the timeouts passed to package managers
+    #[must_use]
+    pub fn operation_timeouts(&self) -> OperationTimeouts {
+        let defaults = OperationTimeouts::default();
+        OperationTimeouts {
+            update_lists: self
+                .update_lists_secs
+                .map_or(defaults.update_lists, Duration::from_secs),
+            upgrade: self
+                .upgrade_secs
+                .map_or(defaults.upgrade, Duration::from_secs),
+            query: self.query_secs.map_or(defaults.query, Duration::from_secs),
+        }
+    }
+}
+
+/// Age of unused images pruned when `older_than_secs` is unset: one week
+pub const DEFAULT_PRUNE_OLDER_THAN_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Which Docker images to remove after a successful compose update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneMode {
+    /// Keep all images
+    #[default]
+    Off,
+    /// Untagged images left behind by pulls
+    Dangling,
+    /// Every image no container uses, subject to `older_than_secs`
+    Unused,
+}
+
+/// Docker image cleanup after compose updates
+///
+/// Images are only pruned when every service came up, so a failed update
+/// can still be rolled back to the previous images.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImagePrunePolicy {
+    /// What to prune (default `off`)
+    #[serde(default)]
+    pub mode: Option<PruneMode>,
+    /// With `unused`, only prune images created at least this long ago
+    /// (default one week, 0 prunes all unused images)
+    #[serde(default)]
+    pub older_than_secs: Option<u64>,
+}
+
+impl ImagePrunePolicy {
+    /// Resolve into the policy passed to the compose manager
+    #[must_use]
+    pub fn prune_policy(&self) -> PrunePolicy {
+        match self.mode.unwrap_or_default() {
+            PruneMode::Off => PrunePolicy::Off,
+            PruneMode::Dangling => PrunePolicy::Dangling,
+            PruneMode::Unused => PrunePolicy::UnusedOlderThan(Duration::from_secs(
+                self.older_than_secs
+                    .unwrap_or(DEFAULT_PRUNE_OLDER_THAN_SECS),
+            )),
+        }
+    }
+}
+
+/// Automatic retry settings for failed hosts
+///
+/// Unset fields use the `DEFAULT_RETRY_*` values; retries are off unless
+/// `enabled` is set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutoRetryPolicy {
+    /// Retry failed queries and updates automatically (default false)
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    /// Attempts before the host stays failed (default 3)
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+    /// Seconds before the first attempt, doubled for each later one (default 30)
+    #[serde(default)]
+    pub initial_backoff_secs: Option<u64>,
+    /// Upper bound on the delay between attempts (default 600)
+    #[serde(default)]
+    pub max_backoff_secs: Option<u64>,
+    /// Also retry failed package operations, which otherwise wait for a
+    /// person to look at them (default false)
+    #[serde(default)]
+    pub retry_command_failures: Option<bool>,
+}
+
+/// Default number of automatic retry attempts
+pub const DEFAULT_RETRY_MAX_RETRIES: u32 = 3;
+
+/// Default seconds before the first automatic retry
+pub const DEFAULT_RETRY_INITIAL_BACKOFF_SECS: u64 = 30;
+
+/// Default upper bound in seconds on the delay between retries
+pub const DEFAULT_RETRY_MAX_BACKOFF_SECS: u64 = 600;
+
+/// Shortest delay before retrying a package lock conflict, giving the
+/// process holding the lock time to finish
+pub const LOCK_CONFLICT_MIN_BACKOFF: Duration = Duration::from_secs(60);
+
+impl AutoRetryPolicy {
+    /// Whether automatic retries are enabled
+    #[must_use]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(false)
+    }
+
+    /// Attempts allowed before giving up
+    #[must_use]
+    pub fn max_attempts(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_RETRY_MAX_RETRIES)
+    }
+
+    /// Whether a failure of this kind should be retried
+    #[must_use]
+    pub fn retries(&self, kind: FailureKind) -> bool {
+        match kind {
+            FailureKind::Connection | FailureKind::PackageLock | FailureKind::Timeout => true,
+            FailureKind::PackageOperation => self.retry_command_failures.unwrap_or(false),
+            FailureKind::Authentication | FailureKind::Internal => false,
+        }
+    }
+
+    /// Delay before the given attempt (1-based) after a failure of `kind`
+    #[must_use]
+    pub fn backoff(&self, attempt: u32, kind: FailureKind) -> Duration {
+        let initial = self
+            .initial_backoff_secs
+            .unwrap_or(DEFAULT_RETRY_INITIAL_BACKOFF_SECS);
+        let max = self
+            .max_backoff_secs
+            .unwrap_or(DEFAULT_RETRY_MAX_BACKOFF_SECS);
+        let factor = 1u64
+            .checked_shl(attempt.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        let delay = Duration::from_secs(initial.saturating_mul(factor).min(max));
+
+        if kind == FailureKind::PackageLock {
+            delay.max(LOCK_CONFLICT_MIN_BACKOFF)
+        } else {
+            delay
+        }
+    }
 }
 
 fn default_auto_reboot() -> bool {
     true
 }
 
+/// Default seconds between reachability probes
+pub const DEFAULT_HEALTH_CHECK_INTERVAL_SECS: u64 = 60;
+
+/// Default number of failed probes before a host counts as unreachable
+pub const DEFAULT_UNREACHABLE_AFTER: u32 = 3;
+
+/// Default number of connection failures that open a host's circuit breaker
+pub const DEFAULT_CIRCUIT_BREAKER_AFTER: u32 = 5;
+
+/// Default seconds an open circuit breaker fails operations fast
+pub const DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS: u64 = 300;
+
+/// Default seconds an update hook may run
+pub const DEFAULT_HOOK_TIMEOUT_SECS: u64 = 300;
+
+/// Default seconds an update waits for a held package manager lock
+pub const DEFAULT_LOCK_WAIT_SECS: u64 = 300;
+
+impl HostPolicy {
+    /// Interval between reachability probes, or `None` when disabled
+    #[must_use]
+    pub fn health_check_interval(&self) -> Option<Duration> {
+        match self
+            .health_check_interval_secs
+            .unwrap_or(DEFAULT_HEALTH_CHECK_INTERVAL_SECS)
+        {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
+    }
+
+    /// Consecutive probe failures before a host counts as unreachable
+    #[must_use]
+    pub fn unreachable_threshold(&self) -> u32 {
+        self.unreachable_after
+            .unwrap_or(DEFAULT_UNREACHABLE_AFTER)
+            .max(1)
+    }
+
+    /// Connection failures that open the circuit breaker, or `None` when disabled
+    #[must_use]
+    pub fn circuit_breaker_threshold(&self) -> Option<u32> {
+        match self
+            .circuit_breaker_after
+            .unwrap_or(DEFAULT_CIRCUIT_BREAKER_AFTER)
+        {
+            0 => None,
+            count => Some(count),
+        }
+    }
+
+    /// How long an open circuit breaker fails operations fast
+    #[must_use]
+    pub fn circuit_breaker_cooldown(&self) -> Duration {
+        Duration::from_secs(
+            self.circuit_breaker_cooldown_secs
+                .unwrap_or(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS),
+        )
+    }
+
+    /// How long package lists are reused before a query refreshes them
+    #[must_use]
+    pub fn metadata_max_age(&self) -> Duration {
+        self.metadata_max_age_secs
+            .map_or(DEFAULT_METADATA_MAX_AGE, Duration::from_secs)
+    }
+
+    /// Time limit for each update hook
+    #[must_use]
+    pub fn hook_timeout(&self) -> Duration {
+        Duration::from_secs(self.hook_timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS))
+    }
+
+    /// Whether post-update hooks also run after a failed update
+    #[must_use]
+    pub fn runs_post_hooks_on_failure(&self) -> bool {
+        self.run_post_on_failure.unwrap_or(true)
+    }
+
+    /// Number of recent commands kept in the host's command history
+    #[must_use]
+    pub fn command_history_len(&self) -> usize {
+        self.command_history_size.unwrap_or(DEFAULT_HISTORY_SIZE)
+    }
+
+    /// Number of finished updates kept in the host's update history
+    #[must_use]
+    pub fn update_history_len(&self) -> usize {
+        self.update_history_size
+            .unwrap_or(DEFAULT_UPDATE_HISTORY_SIZE)
+    }
+
+    /// Lines of a failed command's output kept in the failure details
+    #[must_use]
+    pub fn failure_output_len(&self) -> usize {
+        self.failure_output_lines
+            .unwrap_or(DEFAULT_FAILURE_OUTPUT_LINES)
+    }
+
+    /// How package manager commands are run as root
+    #[must_use]
+    pub fn escalation(&self) -> PrivilegeEscalation {
+        self.escalation.clone().unwrap_or_default()
+    }
+
+    /// How long an update waits for a held package manager lock
+    #[must_use]
+    pub fn lock_wait(&self) -> LockWait {
+        LockWait::up_to(Duration::from_secs(
+            self.lock_wait_secs.unwrap_or(DEFAULT_LOCK_WAIT_SECS),
+        ))
+    }
+
+    /// Free bytes required on each mount point before a package update
+    #[must_use]
+    pub fn min_free_space(&self) -> Vec<(String, u64)> {
+        const MIB: u64 = 1024 * 1024;
+        match &self.min_free_space_mb {
+            Some(minimums) => minimums
+                .iter()
+                .map(|(mount, mb)| (mount.clone(), mb.saturating_mul(MIB)))
+                .collect(),
+            None => DEFAULT_MIN_FREE_SPACE_MB
+                .iter()
+                .map(|(mount, mb)| ((*mount).to_string(), mb * MIB))
+                .collect(),
+        }
+    }
+}
+
 /// Time window for maintenance operations
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct MaintenanceWindow {
     /// Start time in `HH:MM` format
     pub start: String,
@@ -56,6 +585,71 @@ pub struct MaintenanceWindow {
     pub days: Vec<String>,
 }
 
+impl MaintenanceWindow {
+    /// Check the times are `HH:MM` and the days are weekday names such as
+    /// `Sat` or `saturday`
+    ///
+    /// Fields are named relative to the window, e.g. `days[1]`.
+    #[must_use]
+    pub fn errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        for (field, time) in [("start", &self.start), ("end", &self.end)] {
+            if chrono::NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+                errors.push(FieldError::new(
+                    field,
+                    format!("expected `HH:MM`, got `{time}`"),
+                ));
+            }
+        }
+        for (i, day) in self.days.iter().enumerate() {
+            if day.parse::<chrono::Weekday>().is_err() {
+                errors.push(FieldError::new(
+                    format!("days[{i}]"),
+                    format!("unknown day `{day}`"),
+                ));
+            }
+        }
+        errors
+    }
+
+    /// Whether the window is open at wall-clock time `at`
+    ///
+    /// A window ending before it starts runs past midnight and belongs to
+    /// the day it starts on. Without days it is open every day. A window
+    /// that doesn't parse, which [`errors`](Self::errors) reports, is
+    /// always open.
+    #[must_use]
+    pub fn is_open_at(&self, at: chrono::NaiveDateTime) -> bool {
+        use chrono::Datelike;
+
+        let parse = |time: &str| chrono::NaiveTime::parse_from_str(time, "%H:%M");
+        let (Ok(start), Ok(end)) = (parse(&self.start), parse(&self.end)) else {
+            return true;
+        };
+        let days: Vec<chrono::Weekday> = self.days.iter().filter_map(|d| d.parse().ok()).collect();
+        let active = |day: chrono::Weekday| days.is_empty() || days.contains(&day);
+
+        let time = at.time();
+        if start < end {
+            active(at.weekday()) && start <= time && time < end
+        } else if time >= start {
+            active(at.weekday())
+        } else {
+            time < end && active(at.weekday().pred())
+        }
+    }
+}
+
+impl fmt::Display for MaintenanceWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)?;
+        if !self.days.is_empty() {
+            write!(f, " on {}", self.days.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 /// Fleet update configuration
 #[derive(Debug, Clone)]
 pub struct FleetUpdateConfig {
@@ -67,6 +661,13 @@ pub struct FleetUpdateConfig {
     pub filter: Option<FleetFilter>,
     /// Whether to perform a dry run
     pub dry_run: bool,
+    /// Overrides each host's default scope when set
+    pub scope: Option<UpdateScope>,
+    /// Hosts updated first, together in one batch regardless of `batch_size`;
+    /// the remaining hosts follow in name order
+    pub canary_hosts: Vec<HostName>,
+    /// Skip the remaining hosts if any canary fails
+    pub halt_on_canary_failure: bool,
 }
 
 impl Default for FleetUpdateConfig {
@@ -76,6 +677,9 @@ impl Default for FleetUpdateConfig {
             delay_between_batches: Duration::from_secs(30),
             filter: None,
             dry_run: false,
+            scope: None,
+            canary_hosts: Vec::new(),
+            halt_on_canary_failure: true,
         }
     }
 }
@@ -88,5 +692,1471 @@ pub struct FleetFilter {
     /// Only include hosts in these groups
     pub groups: Vec<String>,
     /// Exclude these specific hosts
-    pub exclude_hosts: Vec<String>,
+    pub exclude_hosts: Vec<HostName>,
+    /// Only include hosts with all of these facts
+    pub fact_filters: Vec<FactFilter>,
+}
+
+/// Partial update for an existing [`HostConfig`]
+///
+/// Fields left as `None` keep their current value.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostConfigPatch {
+    /// Hostname (must match the existing name; renaming is not supported)
+    #[serde(default)]
+    pub name: Option<HostName>,
+    /// New address for SSH connection
+    #[serde(default)]
+    pub addr: Option<String>,
+    /// New SSH port
+    #[serde(default)]
+    pub port: Option<u16>,
+    /// New SSH user
+    #[serde(default)]
+    pub user: Option<String>,
+    /// New SSH private key path
+    #[serde(default)]
+    pub ssh_key: Option<String>,
+    /// New environment variable holding the key passphrase
+    #[serde(default)]
+    pub ssh_key_passphrase_env: Option<String>,
+    /// New SSH connect timeout
+    #[serde(
+        default,
+        rename = "connect_timeout_secs",
+        with = "option_secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub connect_timeout: Option<Duration>,
+    /// New limit on concurrent SSH commands
+    #[serde(default)]
+    pub max_ssh_channels: Option<usize>,
+    /// New jump host
+    #[serde(default)]
+    pub jump_host: Option<String>,
+    /// Replacement docker compose directories
+    #[serde(default)]
+    pub compose_paths: Option<Vec<String>>,
+    /// Replacement env files by compose directory
+    #[serde(default)]
+    pub compose_env_files: Option<BTreeMap<String, String>>,
+    /// Replacement tags
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    /// Replacement dependencies
+    #[serde(default)]
+    pub depends_on: Option<Vec<HostName>>,
+    /// Replacement environment variables
+    #[serde(default)]
+    pub env: Option<BTreeMap<String, String>>,
+    /// Policy field updates
+    #[serde(default)]
+    pub policy: Option<HostPolicyPatch>,
+}
+
+/// Partial update for [`HostPolicy`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HostPolicyPatch {
+    /// Automatically reboot when kernel updates require it
+    #[serde(default)]
+    pub auto_reboot: Option<bool>,
+    /// Time window when updates are allowed
+    #[serde(default)]
+    pub maintenance_window: Option<MaintenanceWindow>,
+    /// Scope used when an update request doesn't specify one
+    #[serde(default)]
+    pub default_scope: Option<UpdateScope>,
+    /// Seconds between reachability probes (0 disables)
+    #[serde(default)]
+    pub health_check_interval_secs: Option<u64>,
+    /// Consecutive failed probes before the host is marked unreachable
+    #[serde(default)]
+    pub unreachable_after: Option<u32>,
+    /// Consecutive connection failures before operations fail fast (0 disables)
+    #[serde(default)]
+    pub circuit_breaker_after: Option<u32>,
+    /// Seconds an open circuit breaker fails operations fast
+    #[serde(default)]
+    pub circuit_breaker_cooldown_secs: Option<u64>,
+    /// Package manager timeouts; set fields replace the current values
+    #[serde(default)]
+    pub timeouts: Option<TimeoutPolicy>,
+    /// Docker image cleanup; set fields replace the current values
+    #[serde(default)]
+    pub image_prune: Option<ImagePrunePolicy>,
+    /// Seconds package lists are reused (0 always refreshes)
+    #[serde(default)]
+    pub metadata_max_age_secs: Option<u64>,
+    /// Replacement pre-update hooks
+    #[serde(default)]
+    pub pre_update_hooks: Option<Vec<String>>,
+    /// Replacement post-update hooks
+    #[serde(default)]
+    pub post_update_hooks: Option<Vec<String>>,
+    /// Run post-update hooks when the update failed
+    #[serde(default)]
+    pub run_post_on_failure: Option<bool>,
+    /// Seconds each hook may run
+    #[serde(default)]
+    pub hook_timeout_secs: Option<u64>,
+    /// Automatic retry settings; set fields replace the current values
+    #[serde(default)]
+    pub auto_retry: Option<AutoRetryPolicy>,
+    /// Number of recent commands kept for debugging
+    #[serde(default)]
+    pub command_history_size: Option<usize>,
+    /// Number of finished updates kept in the update history
+    #[serde(default)]
+    pub update_history_size: Option<usize>,
+    /// Lines of a failed command's output kept
+    #[serde(default)]
+    pub failure_output_lines: Option<usize>,
+    /// Restart outdated services after updates that need no reboot
+    #[serde(default)]
+    pub auto_restart_services: Option<bool>,
+    /// Autoremove after successful system updates
+    #[serde(default)]
+    pub autoremove: Option<bool>,
+    /// Clean the package cache after successful system updates
+    #[serde(default)]
+    pub clean_cache: Option<bool>,
+    /// Replacement health checks
+    #[serde(default)]
+    pub health_checks: Option<Vec<HealthCheckSpec>>,
+    /// Replacement free space minimums in MiB
+    #[serde(default)]
+    pub min_free_space_mb: Option<BTreeMap<String, u64>>,
+    /// Replacement privilege escalation command
+    #[serde(default)]
+    pub escalation: Option<PrivilegeEscalation>,
+    /// Seconds an update waits for a held package manager lock (0 fails at once)
+    #[serde(default)]
+    pub lock_wait_secs: Option<u64>,
+}
+
+impl HostConfigPatch {
+    /// Apply this patch to `current`, returning the updated configuration
+    ///
+    /// # Errors
+    /// Returns `CoreError::ConfigError` if the patch attempts to rename the host
+    pub fn apply(&self, current: &HostConfig) -> Result<HostConfig, CoreError> {
+        if let Some(ref name) = self.name
+            && name != &current.name
+        {
+            return Err(CoreError::ConfigError(format!(
+                "cannot rename host '{}' to '{name}'",
+                current.name
+            )));
+        }
+
+        let mut config = current.clone();
+        if let Some(ref addr) = self.addr {
+            config.addr.clone_from(addr);
+        }
+        if let Some(port) = self.port {
+            config.port = Some(port);
+        }
+        if let Some(ref user) = self.user {
+            config.user.clone_from(user);
+        }
+        if let Some(ref ssh_key) = self.ssh_key {
+            config.ssh_key = Some(ssh_key.clone());
+        }
+        if let Some(ref var) = self.ssh_key_passphrase_env {
+            config.ssh_key_passphrase_env = Some(var.clone());
+        }
+        if let Some(timeout) = self.connect_timeout {
+            config.connect_timeout = Some(timeout);
+        }
+        if let Some(max_channels) = self.max_ssh_channels {
+            config.max_ssh_channels = Some(max_channels);
+        }
+        if let Some(ref jump_host) = self.jump_host {
+            config.jump_host = Some(jump_host.clone());
+        }
+        if let Some(ref compose_paths) = self.compose_paths {
+            config.compose_paths.clone_from(compose_paths);
+        }
+        if let Some(ref compose_env_files) = self.compose_env_files {
+            config.compose_env_files.clone_from(compose_env_files);
+        }
+        if let Some(ref tags) = self.tags {
+            config.tags.clone_from(tags);
+        }
+        if let Some(ref depends_on) = self.depends_on {
+            config.depends_on.clone_from(depends_on);
+        }
+        if let Some(ref env) = self.env {
+            config.env.clone_from(env);
+        }
+        if let Some(ref policy) = self.policy {
+            if let Some(auto_reboot) = policy.auto_reboot {
+                config.policy.auto_reboot = auto_reboot;
+            }
+            if let Some(ref window) = policy.maintenance_window {
+                config.policy.maintenance_window = Some(window.clone());
+            }
+            if let Some(scope) = policy.default_scope {
+                config.policy.default_scope = scope;
+            }
+            if let Some(secs) = policy.health_check_interval_secs {
+                config.policy.health_check_interval_secs = Some(secs);
+            }
+            if let Some(count) = policy.unreachable_after {
+                config.policy.unreachable_after = Some(count);
+            }
+            if let Some(count) = policy.circuit_breaker_after {
+                config.policy.circuit_breaker_after = Some(count);
+            }
+            if let Some(secs) = policy.circuit_breaker_cooldown_secs {
+                config.policy.circuit_breaker_cooldown_secs = Some(secs);
+            }
+            if let Some(timeouts) = policy.timeouts {
+                let current = &mut config.policy.timeouts;
+                current.update_lists_secs =
+                    timeouts.update_lists_secs.or(current.update_lists_secs);
+                current.upgrade_secs = timeouts.upgrade_secs.or(current.upgrade_secs);
+                current.query_secs = timeouts.query_secs.or(current.query_secs);
+            }
+            if let Some(prune) = policy.image_prune {
+                let current = &mut config.policy.image_prune;
+                current.mode = prune.mode.or(current.mode);
+                current.older_than_secs = prune.older_than_secs.or(current.older_than_secs);
+            }
+            if let Some(secs) = policy.metadata_max_age_secs {
+                config.policy.metadata_max_age_secs = Some(secs);
+            }
+            if let Some(ref hooks) = policy.pre_update_hooks {
+                config.policy.pre_update_hooks.clone_from(hooks);
+            }
+            if let Some(ref hooks) = policy.post_update_hooks {
+                config.policy.post_update_hooks.clone_from(hooks);
+            }
+            if let Some(run) = policy.run_post_on_failure {
+                config.policy.run_post_on_failure = Some(run);
+            }
+            if let Some(secs) = policy.hook_timeout_secs {
+                config.policy.hook_timeout_secs = Some(secs);
+            }
+            if let Some(size) = policy.command_history_size {
+                config.policy.command_history_size = Some(size);
+            }
+            if let Some(size) = policy.update_history_size {
+                config.policy.update_history_size = Some(size);
+            }
+            if let Some(lines) = policy.failure_output_lines {
+                config.policy.failure_output_lines = Some(lines);
+            }
+            if let Some(auto_restart) = policy.auto_restart_services {
+                config.policy.auto_restart_services = auto_restart;
+            }
+            if let Some(autoremove) = policy.autoremove {
+                config.policy.autoremove = autoremove;
+            }
+            if let Some(clean_cache) = policy.clean_cache {
+                config.policy.clean_cache = clean_cache;
+            }
+            if let Some(ref checks) = policy.health_checks {
+                config.policy.health_checks.clone_from(checks);
+            }
+            if let Some(ref minimums) = policy.min_free_space_mb {
+                config.policy.min_free_space_mb = Some(minimums.clone());
+            }
+            if let Some(ref escalation) = policy.escalation {
+                config.policy.escalation = Some(escalation.clone());
+            }
+            if let Some(secs) = policy.lock_wait_secs {
+                config.policy.lock_wait_secs = Some(secs);
+            }
+            if let Some(retry) = policy.auto_retry {
+                let current = &mut config.policy.auto_retry;
+                current.enabled = retry.enabled.or(current.enabled);
+                current.max_retries = retry.max_retries.or(current.max_retries);
+                current.initial_backoff_secs =
+                    retry.initial_backoff_secs.or(current.initial_backoff_secs);
+                current.max_backoff_secs = retry.max_backoff_secs.or(current.max_backoff_secs);
+                current.retry_command_failures = retry
+                    .retry_command_failures
+                    .or(current.retry_command_failures);
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+impl HostConfig {
+    /// Whether switching from `self` to `other` requires a new executor
+    ///
+    /// Connection details, environment variables, compose paths, command
+    /// timeouts, image pruning, the metadata max age, the lock wait and the
+    /// privilege escalation are baked into the executor and package managers
+    /// at spawn time, so changing them means restarting the host actor.
+    /// Tags and the rest of the policy can be updated in place.
+    #[must_use]
+    pub fn requires_restart(&self, other: &HostConfig) -> bool {
+        self.addr != other.addr
+            || self.port != other.port
+            || self.user != other.user
+            || self.ssh_key != other.ssh_key
+            || self.ssh_key_passphrase_env != other.ssh_key_passphrase_env
+            || self.connect_timeout != other.connect_timeout
+            || self.max_ssh_channels != other.max_ssh_channels
+            || self.jump_host != other.jump_host
+            || self.compose_paths != other.compose_paths
+            || self.compose_env_files != other.compose_env_files
+            || self.env != other.env
+            || self.policy.timeouts != other.policy.timeouts
+            || self.policy.image_prune != other.policy.image_prune
+            || self.policy.metadata_max_age_secs != other.policy.metadata_max_age_secs
+            || self.policy.lock_wait_secs != other.policy.lock_wait_secs
+            || self.policy.escalation != other.policy.escalation
+    }
+
+    /// SSH host and port to connect to
+    ///
+    /// `addr` may carry a port as `host:port` (or `[v6addr]:port`); an
+    /// explicit `port` takes precedence over it.
+    #[must_use]
+    pub fn endpoint(&self) -> (&str, u16) {
+        let (host, addr_port) = split_addr(&self.addr).unwrap_or((&self.addr, None));
+        (host, self.port.or(addr_port).unwrap_or(DEFAULT_SSH_PORT))
+    }
+
+    /// Find the bastion named by `jump_host`
+    ///
+    /// A name of another host in `hosts` refers to that host, connection
+    /// details and key included; anything else is parsed as an inline
+    /// spec. Only one level of jumping is supported, so a configured jump
+    /// host may not have a jump host of its own.
+    ///
+    /// # Errors
+    /// Returns a `jump_host` field error if the host would jump through
+    /// itself or through another jump host, or the spec does not parse
+    pub fn resolve_jump<'a>(
+        &self,
+        hosts: &'a [HostConfig],
+    ) -> Result<Option<JumpHost<'a>>, FieldError> {
+        let Some(name) = &self.jump_host else {
+            return Ok(None);
+        };
+        if name == self.name.as_str() {
+            return Err(FieldError::new("jump_host", "must not be the host itself"));
+        }
+        match hosts.iter().find(|host| host.name.as_str() == name) {
+            Some(host) if host.jump_host.is_some() => Err(FieldError::new(
+                "jump_host",
+                format!("'{name}' has a jump host itself; only one level is supported"),
+            )),
+            Some(host) => Ok(Some(JumpHost::Configured(host))),
+            None => JumpSpec::parse(name)
+                .map(|spec| Some(JumpHost::Inline(spec)))
+                .map_err(|message| FieldError::new("jump_host", message)),
+        }
+    }
+
+    /// Check the configuration for values the daemon cannot work with
+    ///
+    /// Collects every violation instead of stopping at the first. Host names
+    /// end up in URL paths, so they are restricted to letters, digits, `-`,
+    /// `_` and `.`; tags are used in comma-separated filters and may not
+    /// contain commas or whitespace. File paths are not checked; see
+    /// [`check_key_file`].
+    ///
+    /// # Errors
+    /// Returns every invalid field if any check fails
+    pub fn validate(&self) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        if let Err(message) = self.name.validate() {
+            errors.push(FieldError::new("name", message));
+        }
+
+        if self.addr.trim().is_empty() {
+            errors.push(FieldError::new("addr", "must not be empty"));
+        } else if self.addr.len() > MAX_ADDR_LEN {
+            errors.push(FieldError::new(
+                "addr",
+                format!("must be at most {MAX_ADDR_LEN} characters"),
+            ));
+        } else if self.addr.contains(char::is_whitespace) {
+            errors.push(FieldError::new("addr", "must not contain whitespace"));
+        } else if let Err(message) = split_addr(&self.addr) {
+            errors.push(FieldError::new("addr", message));
+        }
+
+        if self.port == Some(0) {
+            errors.push(FieldError::new("port", "must be between 1 and 65535"));
+        }
+        if self.connect_timeout == Some(Duration::ZERO) {
+            errors.push(FieldError::new(
+                "connect_timeout_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.max_ssh_channels == Some(0) {
+            errors.push(FieldError::new("max_ssh_channels", "must be at least 1"));
+        }
+        if let Some(jump) = &self.jump_host {
+            if jump.contains(char::is_whitespace) {
+                errors.push(FieldError::new("jump_host", "must not contain whitespace"));
+            } else if jump == self.name.as_str() {
+                errors.push(FieldError::new("jump_host", "must not be the host itself"));
+            } else if let Err(message) = JumpSpec::parse(jump) {
+                errors.push(FieldError::new("jump_host", message));
+            }
+        }
+
+        if self.user.trim().is_empty() {
+            errors.push(FieldError::new("user", "must not be empty"));
+        } else if self.user.contains(char::is_whitespace) {
+            errors.push(FieldError::new("user", "must not contain whitespace"));
+        }
+
+        if let Some(key) = &self.ssh_key {
+            if key.trim().is_empty() {
+                errors.push(FieldError::new("ssh_key", "must not be empty when set"));
+            } else if has_control_chars(key) {
+                errors.push(FieldError::new(
+                    "ssh_key",
+                    "must not contain control characters",
+                ));
+            }
+        }
+
+        if let Some(var) = &self.ssh_key_passphrase_env {
+            if self.ssh_key.is_none() {
+                errors.push(FieldError::new(
+                    "ssh_key_passphrase_env",
+                    "requires ssh_key to be set",
+                ));
+            } else if var.is_empty() || var.contains(['=', '\0']) || has_control_chars(var) {
+                errors.push(FieldError::new(
+                    "ssh_key_passphrase_env",
+                    "must be a non-empty environment variable name without '=' or control characters",
+                ));
+            }
+        }
+
+        for (i, path) in self.compose_paths.iter().enumerate() {
+            if path.trim().is_empty() {
+                errors.push(FieldError::new(
+                    format!("compose_paths[{i}]"),
+                    "must not be empty",
+                ));
+            } else if has_control_chars(path) {
+                errors.push(FieldError::new(
+                    format!("compose_paths[{i}]"),
+                    "must not contain control characters",
+                ));
+            }
+        }
+        for (dir, env_file) in &self.compose_env_files {
+            let field = format!("compose_env_files[{dir}]");
+            if !self.compose_paths.contains(dir) {
+                errors.push(FieldError::new(field, "must be one of compose_paths"));
+            } else if env_file.trim().is_empty() {
+                errors.push(FieldError::new(field, "must not be empty"));
+            } else if has_control_chars(env_file) {
+                errors.push(FieldError::new(
+                    field,
+                    "must not contain control characters",
+                ));
+            }
+        }
+
+        if self.tags.len() > MAX_TAGS {
+            errors.push(FieldError::new(
+                "tags",
+                format!(
+                    "at most {MAX_TAGS} tags are allowed, got {}",
+                    self.tags.len()
+                ),
+            ));
+        }
+        for (i, tag) in self.tags.iter().enumerate() {
+            if let Err(message) = check_tag(tag) {
+                errors.push(FieldError::new(format!("tags[{i}]"), message));
+            }
+        }
+
+        for (i, dependency) in self.depends_on.iter().enumerate() {
+            let field = format!("depends_on[{i}]");
+            if dependency == &self.name {
+                errors.push(FieldError::new(field, "must not be the host itself"));
+            } else if let Err(message) = dependency.validate() {
+                errors.push(FieldError::new(field, message));
+            } else if self.depends_on[..i].contains(dependency) {
+                errors.push(FieldError::new(field, "is listed twice"));
+            }
+        }
+
+        for (name, value) in &self.env {
+            let field = format!("env[{name}]");
+            if !tendhost_exec::is_env_name(name) {
+                errors.push(FieldError::new(
+                    field,
+                    "must be letters, digits and '_', not starting with a digit",
+                ));
+            } else if has_control_chars(value) {
+                errors.push(FieldError::new(
+                    field,
+                    "must not contain control characters",
+                ));
+            }
+        }
+
+        for (i, check) in self.policy.health_checks.iter().enumerate() {
+            if check.command.trim().is_empty() {
+                errors.push(FieldError::new(
+                    format!("policy.health_checks[{i}].command"),
+                    "must not be empty",
+                ));
+            }
+            if check.timeout_secs == Some(0) {
+                errors.push(FieldError::new(
+                    format!("policy.health_checks[{i}].timeout_secs"),
+                    "must be greater than 0",
+                ));
+            }
+        }
+
+        for mount in self
+            .policy
+            .min_free_space_mb
+            .iter()
+            .flat_map(BTreeMap::keys)
+        {
+            if !mount.starts_with('/') {
+                errors.push(FieldError::new(
+                    format!("policy.min_free_space_mb.{mount}"),
+                    "must be an absolute path",
+                ));
+            }
+        }
+
+        if let Some(Err(message)) = self.policy.escalation.as_ref().map(|e| e.validate()) {
+            errors.push(FieldError::new("policy.escalation", message));
+        }
+
+        for error in self
+            .policy
+            .maintenance_window
+            .iter()
+            .flat_map(MaintenanceWindow::errors)
+        {
+            errors.push(FieldError::new(
+                format!("policy.maintenance_window.{}", error.field),
+                error.message,
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// A single invalid field in a host configuration
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// Field name, with an index for list entries (e.g. `tags[2]`)
+    pub field: String,
+    /// What is wrong with the value
+    pub message: String,
+}
+
+impl FieldError {
+    /// Create an error for `field`
+    #[must_use]
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Bastion a host's SSH connection is tunnelled through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JumpHost<'a> {
+    /// Another configured host, connected to with its own settings
+    Configured(&'a HostConfig),
+    /// A bastion that is not managed itself
+    Inline(JumpSpec),
+}
+
+/// Inline jump host, written as `[user@]host[:port]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpSpec {
+    /// Login user; the target's user when unset
+    pub user: Option<String>,
+    /// Bastion address
+    pub host: String,
+    /// SSH port (default 22)
+    pub port: u16,
+}
+
+impl JumpSpec {
+    /// Parse `[user@]host[:port]`; IPv6 addresses with a port need brackets
+    ///
+    /// # Errors
+    /// Returns a message if the user or host is empty or the port is invalid
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let (user, addr) = match spec.rsplit_once('@') {
+            Some(("", _)) => {
+                return Err("user before '@' must not be empty".to_string());
+            }
+            Some((user, addr)) => (Some(user.to_string()), addr),
+            None => (None, spec),
+        };
+        let (host, port) = split_addr(addr)?;
+        if host.is_empty() {
+            return Err("host must not be empty".to_string());
+        }
+        Ok(Self {
+            user,
+            host: host.to_string(),
+            port: port.unwrap_or(DEFAULT_SSH_PORT),
+        })
+    }
+}
+
+/// Check that an SSH key path points to a readable file on this machine
+///
+/// Kept separate from [`HostConfig::validate`] because only the machine
+/// that opens the key can meaningfully check it.
+///
+/// # Errors
+/// Returns a `ssh_key` field error if the file does not exist
+pub fn check_key_file(path: &str) -> Result<(), FieldError> {
+    if Path::new(path).is_file() {
+        Ok(())
+    } else {
+        Err(FieldError::new(
+            "ssh_key",
+            format!("file '{path}' does not exist"),
+        ))
+    }
+}
+
+/// Check that the environment variable holding a key's passphrase is set
+///
+/// Like [`check_key_file`], this only means something in the process that
+/// opens the key.
+///
+/// # Errors
+/// Returns a `ssh_key_passphrase_env` field error if `var` is not set
+pub fn check_passphrase_env(var: &str) -> Result<(), FieldError> {
+    if std::env::var_os(var).is_some() {
+        Ok(())
+    } else {
+        Err(FieldError::new(
+            "ssh_key_passphrase_env",
+            format!("environment variable '{var}' is not set"),
+        ))
+    }
+}
+
+/// Split `host:port` or `[v6addr]:port`; bare IPv6 addresses have no port
+fn split_addr(addr: &str) -> Result<(&str, Option<u16>), String> {
+    let parse_port = |port: &str| match port.parse::<u16>() {
+        Ok(port) if port > 0 => Ok(Some(port)),
+        _ => Err(format!("invalid port '{port}'")),
+    };
+
+    if let Some(rest) = addr.strip_prefix('[') {
+        let Some((host, after)) = rest.split_once(']') else {
+            return Err("missing ']' after IPv6 address".to_string());
+        };
+        return match after.strip_prefix(':') {
+            Some(port) => Ok((host, parse_port(port)?)),
+            None if after.is_empty() => Ok((host, None)),
+            None => Err(format!("unexpected '{after}' after IPv6 address")),
+        };
+    }
+
+    match addr.split_once(':') {
+        Some((host, port)) if !port.contains(':') => Ok((host, parse_port(port)?)),
+        _ => Ok((addr, None)),
+    }
+}
+
+/// Whether a path contains newlines, tabs or other control characters
+///
+/// Such paths are never intended and would survive quoting into a remote
+/// command line only to confuse its output.
+fn has_control_chars(path: &str) -> bool {
+    path.chars().any(char::is_control)
+}
+
+fn check_tag(tag: &str) -> Result<(), String> {
+    if tag.is_empty() {
+        return Err("must not be empty".to_string());
+    }
+    if tag.len() > MAX_TAG_LEN {
+        return Err(format!("must be at most {MAX_TAG_LEN} characters"));
+    }
+    if tag.contains(|c: char| c == ',' || c.is_whitespace()) {
+        return Err("must not contain commas or whitespace".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_config() -> HostConfig {
+        HostConfig {
+            name: "web-1".into(),
+            addr: "10.0.0.1".to_string(),
+            port: None,
+            user: "root".to_string(),
+            ssh_key: None,
+            ssh_key_passphrase_env: None,
+            connect_timeout: None,
+            max_ssh_channels: None,
+            jump_host: None,
+            compose_paths: vec![],
+            compose_env_files: BTreeMap::new(),
+            tags: vec!["prod".to_string()],
+            depends_on: vec![],
+            env: BTreeMap::new(),
+            policy: HostPolicy::default(),
+        }
+    }
+
+    #[test]
+    fn test_patch_hooks() {
+        let current = sample_config();
+        assert!(current.policy.runs_post_hooks_on_failure());
+        assert_eq!(
+            current.policy.hook_timeout(),
+            Duration::from_secs(DEFAULT_HOOK_TIMEOUT_SECS)
+        );
+
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                pre_update_hooks: Some(vec!["haproxy-drain web-1".to_string()]),
+                run_post_on_failure: Some(false),
+                hook_timeout_secs: Some(30),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.policy.pre_update_hooks, vec!["haproxy-drain web-1"]);
+        assert!(updated.policy.post_update_hooks.is_empty());
+        assert!(!updated.policy.runs_post_hooks_on_failure());
+        assert_eq!(updated.policy.hook_timeout(), Duration::from_secs(30));
+        assert!(!current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_patch_tags_only() {
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            tags: Some(vec!["staging".to_string()]),
+            ..Default::default()
+        };
+
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.tags, vec!["staging"]);
+        assert_eq!(updated.addr, current.addr);
+        assert!(!current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_patch_connection_requires_restart() {
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            user: Some("admin".to_string()),
+            ssh_key: Some("/root/.ssh/id_ed25519".to_string()),
+            ssh_key_passphrase_env: None,
+            ..Default::default()
+        };
+
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.user, "admin");
+        assert!(current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_endpoint() {
+        let mut config = sample_config();
+        assert_eq!(config.endpoint(), ("10.0.0.1", 22));
+
+        config.addr = "nas.lan:2222".to_string();
+        assert_eq!(config.endpoint(), ("nas.lan", 2222));
+        config.port = Some(2200);
+        assert_eq!(config.endpoint(), ("nas.lan", 2200));
+
+        config.port = None;
+        config.addr = "fe80::1".to_string();
+        assert_eq!(config.endpoint(), ("fe80::1", 22));
+        config.addr = "[fe80::1]:8022".to_string();
+        assert_eq!(config.endpoint(), ("fe80::1", 8022));
+    }
+
+    #[test]
+    fn test_patch_port_requires_restart() {
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            port: Some(2222),
+            connect_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.endpoint(), ("10.0.0.1", 2222));
+        assert_eq!(updated.connect_timeout, Some(Duration::from_secs(5)));
+        assert!(current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_patch_escalation_requires_restart() {
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                escalation: Some(PrivilegeEscalation::Doas),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.policy.escalation(), PrivilegeEscalation::Doas);
+        assert!(current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_connection_fields_are_optional() {
+        let config: HostConfig =
+            serde_json::from_str(r#"{"name": "nas", "addr": "nas.lan"}"#).unwrap();
+        assert_eq!(config.endpoint(), ("nas.lan", 22));
+        assert_eq!(config.connect_timeout, None);
+
+        let config: HostConfig = serde_json::from_str(
+            r#"{"name": "nas", "addr": "nas.lan", "port": 2222, "connect_timeout_secs": 5}"#,
+        )
+        .unwrap();
+        assert_eq!(config.endpoint(), ("nas.lan", 2222));
+        assert_eq!(config.connect_timeout, Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_patch_rejects_rename() {
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            name: Some("web-2".into()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            patch.apply(&current),
+            Err(CoreError::ConfigError(_))
+        ));
+
+        // Same name is a no-op rather than a rename
+        let patch = HostConfigPatch {
+            name: Some("web-1".into()),
+            ..Default::default()
+        };
+        assert!(patch.apply(&current).is_ok());
+    }
+
+    #[test]
+    fn test_policy_default_scope() {
+        let policy: HostPolicy = serde_json::from_str(r#"{"auto_reboot": false}"#).unwrap();
+        assert_eq!(policy.default_scope, UpdateScope::All);
+
+        let policy: HostPolicy =
+            serde_json::from_str(r#"{"default_scope": "security_only"}"#).unwrap();
+        assert_eq!(policy.default_scope, UpdateScope::SecurityOnly);
+
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                default_scope: Some(UpdateScope::SecurityOnly),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updated = patch.apply(&sample_config()).unwrap();
+        assert_eq!(updated.policy.default_scope, UpdateScope::SecurityOnly);
+    }
+
+    #[test]
+    fn test_policy_health_check_defaults() {
+        let policy = HostPolicy::default();
+        assert_eq!(
+            policy.health_check_interval(),
+            Some(Duration::from_secs(DEFAULT_HEALTH_CHECK_INTERVAL_SECS))
+        );
+        assert_eq!(policy.unreachable_threshold(), DEFAULT_UNREACHABLE_AFTER);
+
+        let policy: HostPolicy =
+            serde_json::from_str(r#"{"health_check_interval_secs": 0, "unreachable_after": 0}"#)
+                .unwrap();
+        assert_eq!(policy.health_check_interval(), None);
+        assert_eq!(policy.unreachable_threshold(), 1);
+    }
+
+    #[test]
+    fn test_policy_min_free_space() {
+        const MIB: u64 = 1024 * 1024;
+        let defaults = HostPolicy::default().min_free_space();
+        assert_eq!(
+            defaults,
+            [
+                ("/".to_string(), 1024 * MIB),
+                ("/boot".to_string(), 500 * MIB),
+                ("/var".to_string(), 1024 * MIB),
+            ]
+        );
+
+        let policy: HostPolicy =
+            serde_json::from_str(r#"{"min_free_space_mb": {"/boot": 300}}"#).unwrap();
+        assert_eq!(policy.min_free_space(), [("/boot".to_string(), 300 * MIB)]);
+
+        // An empty table turns the check off
+        let policy: HostPolicy = serde_json::from_str(r#"{"min_free_space_mb": {}}"#).unwrap();
+        assert!(policy.min_free_space().is_empty());
+
+        let mut config = sample_config();
+        config.policy.min_free_space_mb = Some(BTreeMap::from([("boot".to_string(), 1)]));
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors[0].field, "policy.min_free_space_mb.boot");
+    }
+
+    #[test]
+    fn test_policy_escalation() {
+        assert_eq!(
+            HostPolicy::default().escalation(),
+            PrivilegeEscalation::Sudo
+        );
+        let policy: HostPolicy = serde_json::from_str(r#"{"escalation": "doas"}"#).unwrap();
+        assert_eq!(policy.escalation(), PrivilegeEscalation::Doas);
+
+        let mut config = sample_config();
+        config.policy.escalation = Some(PrivilegeEscalation::Custom("pfexec".to_string()));
+        assert!(config.validate().is_ok());
+        config.policy.escalation = Some(PrivilegeEscalation::Custom("sudo -n; reboot".to_string()));
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors[0].field, "policy.escalation");
+        assert_eq!(
+            errors[0].message,
+            "custom prefix must not contain shell metacharacters"
+        );
+    }
+
+    #[test]
+    fn test_policy_circuit_breaker() {
+        let policy = HostPolicy::default();
+        assert_eq!(
+            policy.circuit_breaker_threshold(),
+            Some(DEFAULT_CIRCUIT_BREAKER_AFTER)
+        );
+        assert_eq!(
+            policy.circuit_breaker_cooldown(),
+            Duration::from_secs(DEFAULT_CIRCUIT_BREAKER_COOLDOWN_SECS)
+        );
+
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                circuit_breaker_after: Some(0),
+                circuit_breaker_cooldown_secs: Some(60),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.policy.circuit_breaker_threshold(), None);
+        assert_eq!(
+            updated.policy.circuit_breaker_cooldown(),
+            Duration::from_secs(60)
+        );
+        // The actor reads the breaker settings on every failure
+        assert!(!current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_timeout_policy() {
+        let policy: HostPolicy =
+            serde_json::from_str(r#"{"timeouts": {"upgrade_secs": 3600}}"#).unwrap();
+        let timeouts = policy.timeouts.operation_timeouts();
+        assert_eq!(timeouts.upgrade, Duration::from_secs(3600));
+        assert_eq!(timeouts.query, OperationTimeouts::default().query);
+
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                timeouts: Some(TimeoutPolicy {
+                    query_secs: Some(30),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.policy.timeouts.query_secs, Some(30));
+        assert!(current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_image_prune_policy() {
+        let current = sample_config();
+        assert_eq!(current.policy.image_prune.prune_policy(), PrunePolicy::Off);
+
+        let policy: HostPolicy =
+            serde_json::from_str(r#"{"image_prune": {"mode": "unused"}}"#).unwrap();
+        assert_eq!(
+            policy.image_prune.prune_policy(),
+            PrunePolicy::UnusedOlderThan(Duration::from_secs(DEFAULT_PRUNE_OLDER_THAN_SECS))
+        );
+
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                image_prune: Some(ImagePrunePolicy {
+                    mode: Some(PruneMode::Dangling),
+                    older_than_secs: None,
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(
+            updated.policy.image_prune.prune_policy(),
+            PrunePolicy::Dangling
+        );
+        // The compose manager is built with the policy at spawn time
+        assert!(current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_metadata_max_age() {
+        let current = sample_config();
+        assert_eq!(current.policy.metadata_max_age(), DEFAULT_METADATA_MAX_AGE);
+
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                metadata_max_age_secs: Some(0),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.policy.metadata_max_age(), Duration::ZERO);
+        // The package manager keeps its max age until the actor restarts
+        assert!(current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_lock_wait() {
+        let current = sample_config();
+        assert_eq!(
+            current.policy.lock_wait().budget,
+            Duration::from_secs(DEFAULT_LOCK_WAIT_SECS)
+        );
+
+        let policy: HostPolicy = serde_json::from_str(r#"{"lock_wait_secs": 0}"#).unwrap();
+        assert!(policy.lock_wait().budget.is_zero());
+
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                lock_wait_secs: Some(60),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updated = patch.apply(&current).unwrap();
+        assert_eq!(updated.policy.lock_wait().budget, Duration::from_secs(60));
+        assert!(current.requires_restart(&updated));
+    }
+    #[test]
+    fn test_auto_retry_backoff() {
+        let policy: HostPolicy = serde_json::from_str(
+            r#"{"auto_retry": {"enabled": true, "initial_backoff_secs": 10, "max_backoff_secs": 35}}"#,
+        )
+        .unwrap();
+        let retry = policy.auto_retry;
+        assert!(retry.is_enabled());
+        assert_eq!(retry.max_attempts(), DEFAULT_RETRY_MAX_RETRIES);
+        assert_eq!(
+            retry.backoff(1, FailureKind::Connection),
+            Duration::from_secs(10)
+        );
+        assert_eq!(
+            retry.backoff(2, FailureKind::Connection),
+            Duration::from_secs(20)
+        );
+        assert_eq!(
+            retry.backoff(3, FailureKind::Connection),
+            Duration::from_secs(35)
+        );
+        assert_eq!(
+            retry.backoff(500, FailureKind::Connection),
+            Duration::from_secs(35)
+        );
+        assert_eq!(
+            retry.backoff(1, FailureKind::PackageLock),
+            LOCK_CONFLICT_MIN_BACKOFF
+        );
+
+        assert!(retry.retries(FailureKind::Connection));
+        assert!(retry.retries(FailureKind::PackageLock));
+        assert!(retry.retries(FailureKind::Timeout));
+        assert!(!retry.retries(FailureKind::PackageOperation));
+        assert!(!retry.retries(FailureKind::Authentication));
+        assert!(!retry.retries(FailureKind::Internal));
+        assert!(!HostPolicy::default().auto_retry.is_enabled());
+    }
+
+    #[test]
+    fn test_patch_auto_retry() {
+        let current = sample_config();
+        let patch = HostConfigPatch {
+            policy: Some(HostPolicyPatch {
+                auto_retry: Some(AutoRetryPolicy {
+                    enabled: Some(true),
+                    max_retries: Some(5),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let updated = patch.apply(&current).unwrap();
+        assert!(updated.policy.auto_retry.is_enabled());
+        assert_eq!(updated.policy.auto_retry.max_attempts(), 5);
+        assert!(!current.requires_restart(&updated));
+    }
+
+    #[test]
+    fn test_validate_accepts_valid_config() {
+        let mut config = sample_config();
+        config.name = "nas_01.lan".into();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate_reports_every_field() {
+        let mut config = sample_config();
+        config.name = "web/1".into();
+        config.addr = "  ".to_string();
+        config.tags = vec!["prod".to_string(), "a,b".to_string(), "x".repeat(100)];
+
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["name", "addr", "tags[1]", "tags[2]"]);
+
+        config = sample_config();
+        config.name = "x".repeat(MAX_HOST_NAME_LEN + 1).into();
+        config.tags = (0..=MAX_TAGS).map(|i| format!("t{i}")).collect();
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["name", "tags"]);
+
+        config = sample_config();
+        config.addr = "nas.lan:ssh".to_string();
+        config.port = Some(0);
+        config.connect_timeout = Some(Duration::ZERO);
+        config.max_ssh_channels = Some(0);
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            ["addr", "port", "connect_timeout_secs", "max_ssh_channels"]
+        );
+
+        for jump in ["", "web-1", "bastion .lan", "@bastion", "ops@bastion:0"] {
+            config = sample_config();
+            config.jump_host = Some(jump.to_string());
+            let errors = config.validate().unwrap_err();
+            assert_eq!(errors[0].field, "jump_host", "{jump:?} should be rejected");
+        }
+
+        config = sample_config();
+        config.policy.health_checks = vec![HealthCheckSpec {
+            command: " ".to_string(),
+            expected_exit_code: None,
+            expected_output: None,
+            timeout_secs: Some(0),
+        }];
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            [
+                "policy.health_checks[0].command",
+                "policy.health_checks[0].timeout_secs"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_ssh_key_passphrase_env() {
+        let mut config = sample_config();
+        config.ssh_key = None;
+        config.ssh_key_passphrase_env = Some("WEB_KEY_PASSPHRASE".to_string());
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors[0].field, "ssh_key_passphrase_env");
+        assert!(errors[0].message.contains("requires ssh_key"));
+
+        config.ssh_key = Some("/root/.ssh/id_ed25519".to_string());
+        assert!(config.validate().is_ok());
+        for bad in ["", "A=B", "NUL\0"] {
+            config.ssh_key_passphrase_env = Some(bad.to_string());
+            assert!(config.validate().is_err(), "{bad:?} accepted");
+        }
+    }
+
+    #[test]
+    fn test_validate_rejects_control_characters_in_paths() {
+        let mut config = sample_config();
+        config.ssh_key = Some("~/.ssh/id_ed25519\n".to_string());
+        config.compose_paths = vec![
+            "/tmp/a b;echo pwned".to_string(),
+            "/opt/stacks\tweb".to_string(),
+            "/opt/stacks/\u{1b}[2Jweb".to_string(),
+        ];
+
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|e| e.field.as_str()).collect();
+        // Spaces and shell metacharacters are quoted, not rejected
+        assert_eq!(fields, ["ssh_key", "compose_paths[1]", "compose_paths[2]"]);
+    }
+
+    #[test]
+    fn test_validate_compose_env_files() {
+        let mut config = sample_config();
+        config.compose_paths = vec!["/opt/stacks/app".to_string()];
+        config.compose_env_files = BTreeMap::from([
+            ("/opt/stacks/app".to_string(), ".env.prod".to_string()),
+            ("/opt/stacks/gone".to_string(), "/etc/gone.env".to_string()),
+        ]);
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "compose_env_files[/opt/stacks/gone]");
+        assert_eq!(errors[0].message, "must be one of compose_paths");
+
+        config.compose_env_files.remove("/opt/stacks/gone");
+        assert!(config.validate().is_ok());
+        config
+            .compose_env_files
+            .insert("/opt/stacks/app".to_string(), " ".to_string());
+        assert!(config.validate().is_err());
+
+        // Env files are baked into the compose manager
+        let mut other = config.clone();
+        other.compose_env_files.clear();
+        assert!(config.requires_restart(&other));
+    }
+
+    #[test]
+    fn test_validate_env() {
+        let mut config = sample_config();
+        config.env = BTreeMap::from([
+            ("http_proxy".to_string(), "http://proxy:3128".to_string()),
+            ("NO-PROXY".to_string(), "localhost".to_string()),
+            ("GREETING".to_string(), "two\nlines".to_string()),
+        ]);
+
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["env[GREETING]", "env[NO-PROXY]"]);
+
+        config.env.retain(|name, _| name == "http_proxy");
+        assert!(config.validate().is_ok());
+        // Variables are baked into the executor
+        assert!(config.requires_restart(&sample_config()));
+    }
+
+    #[test]
+    fn test_validate_maintenance_window() {
+        let mut config = sample_config();
+        config.policy.maintenance_window = Some(MaintenanceWindow {
+            start: "02:00".to_string(),
+            end: "6am".to_string(),
+            days: vec![
+                "Sat".to_string(),
+                "sunday".to_string(),
+                "Caturday".to_string(),
+            ],
+        });
+
+        let errors = config.validate().unwrap_err();
+        let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            [
+                "policy.maintenance_window.end: expected `HH:MM`, got `6am`",
+                "policy.maintenance_window.days[2]: unknown day `Caturday`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_maintenance_window_is_open_at() {
+        let at = |day: u32, time: &str| {
+            // 2026-01-03 is a Saturday
+            chrono::NaiveDate::from_ymd_opt(2026, 1, day)
+                .unwrap()
+                .and_time(chrono::NaiveTime::parse_from_str(time, "%H:%M").unwrap())
+        };
+        let window = |start: &str, end: &str, days: &[&str]| MaintenanceWindow {
+            start: start.to_string(),
+            end: end.to_string(),
+            days: days.iter().map(ToString::to_string).collect(),
+        };
+
+        let weekend = window("02:00", "06:00", &["Sat", "Sun"]);
+        assert_eq!(weekend.to_string(), "02:00-06:00 on Sat, Sun");
+        assert!(weekend.is_open_at(at(3, "02:00")));
+        assert!(weekend.is_open_at(at(4, "05:59")));
+        assert!(!weekend.is_open_at(at(3, "06:00")));
+        assert!(!weekend.is_open_at(at(5, "03:00")));
+
+        // Friday night into Saturday belongs to Friday
+        let overnight = window("22:00", "02:00", &["Fri"]);
+        assert!(overnight.is_open_at(at(2, "23:00")));
+        assert!(overnight.is_open_at(at(3, "01:00")));
+        assert!(!overnight.is_open_at(at(3, "23:00")));
+
+        assert!(window("01:00", "03:00", &[]).is_open_at(at(6, "02:00")));
+        assert!(window("6am", "03:00", &["Sat"]).is_open_at(at(5, "12:00")));
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        let err = serde_json::from_str::<HostConfig>(
+            r#"{"name": "web", "addr": "10.0.0.1", "policy": {"maintenence_window": null}}"#,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unknown field `maintenence_window`"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_validate_depends_on() {
+        let mut config = sample_config();
+        config.depends_on = vec![
+            "nfs".into(),
+            "Web-1".into(),
+            "bad/name".into(),
+            "NFS".into(),
+        ];
+
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<_> = errors
+            .iter()
+            .map(|e| (e.field.as_str(), e.message.as_str()))
+            .collect();
+        assert_eq!(fields[0], ("depends_on[1]", "must not be the host itself"));
+        assert_eq!(fields[1].0, "depends_on[2]");
+        assert_eq!(fields[2], ("depends_on[3]", "is listed twice"));
+
+        config.depends_on.truncate(1);
+        assert!(config.validate().is_ok());
+        // Only the orchestrator looks at dependencies
+        assert!(!config.requires_restart(&sample_config()));
+    }
+
+    #[test]
+    fn test_health_check_spec_defaults() {
+        let policy: HostPolicy = serde_json::from_str(
+            r#"{"health_checks": [
+                {"command": "systemctl is-active nginx", "expected_output": "active"},
+                {"command": "docker ps", "expected_exit_code": 1, "timeout_secs": 5}
+            ]}"#,
+        )
+        .unwrap();
+
+        let [nginx, docker] = policy.health_checks.as_slice() else {
+            panic!("expected two checks");
+        };
+        assert_eq!(nginx.expected_exit(), 0);
+        assert_eq!(
+            nginx.timeout(),
+            Duration::from_secs(DEFAULT_HEALTH_CHECK_TIMEOUT_SECS)
+        );
+        assert_eq!(docker.expected_exit(), 1);
+        assert_eq!(docker.timeout(), Duration::from_secs(5));
+        assert!(HostPolicy::default().health_checks.is_empty());
+    }
+
+    #[test]
+    fn test_jump_spec_parse() {
+        let spec = JumpSpec::parse("ops@bastion.lan:2222").unwrap();
+        assert_eq!(spec.user.as_deref(), Some("ops"));
+        assert_eq!(spec.host, "bastion.lan");
+        assert_eq!(spec.port, 2222);
+
+        let spec = JumpSpec::parse("[fd00::1]:2222").unwrap();
+        assert_eq!(spec.user, None);
+        assert_eq!(spec.host, "fd00::1");
+        assert_eq!(spec.port, 2222);
+        assert_eq!(JumpSpec::parse("10.0.0.9").unwrap().port, DEFAULT_SSH_PORT);
+
+        assert!(JumpSpec::parse("@bastion").is_err());
+        assert!(JumpSpec::parse("ops@").is_err());
+        assert!(JumpSpec::parse("bastion:ssh").is_err());
+    }
+
+    #[test]
+    fn test_resolve_jump() {
+        let mut bastion = sample_config();
+        bastion.name = "bastion".into();
+        let mut target = sample_config();
+        let hosts = vec![bastion.clone(), target.clone()];
+
+        assert_eq!(target.resolve_jump(&hosts).unwrap(), None);
+
+        target.jump_host = Some("bastion".to_string());
+        assert_eq!(
+            target.resolve_jump(&hosts).unwrap(),
+            Some(JumpHost::Configured(&hosts[0]))
+        );
+
+        // Names of hosts that are not configured are addresses
+        target.jump_host = Some("ops@gw.example.com".to_string());
+        let Some(JumpHost::Inline(spec)) = target.resolve_jump(&hosts).unwrap() else {
+            panic!("expected an inline jump host");
+        };
+        assert_eq!(spec.host, "gw.example.com");
+        assert_eq!(spec.user.as_deref(), Some("ops"));
+
+        target.jump_host = Some("web-1".to_string());
+        assert!(target.resolve_jump(&hosts).is_err());
+
+        // One level only
+        bastion.jump_host = Some("gw.example.com".to_string());
+        let hosts = vec![bastion, target.clone()];
+        target.jump_host = Some("bastion".to_string());
+        let err = target.resolve_jump(&hosts).unwrap_err();
+        assert_eq!(err.field, "jump_host");
+        assert!(err.message.contains("only one level"));
+    }
+
+    #[test]
+    fn test_check_key_file() {
+        assert!(check_key_file("/nonexistent/id_ed25519").is_err());
+        assert!(check_key_file(concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml")).is_ok());
+    }
 }
@@ -0,0 +1,233 @@
+//! Update order of hosts that depend on each other
+//!
+//! A host lists the hosts it needs running in `depends_on`: the NFS server
+//! it mounts, the database its services use. Rebooting a dependency while
+//! its dependents are still being updated pulls the ground from under
+//! them, so fleet updates reach dependents first and dependencies last,
+//! never both in the same batch. [`update_layers`] sorts hosts into layers
+//! in that order; a cycle has no such order and is reported as its path.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::host_name::HostName;
+
+/// Hosts and the hosts each depends on
+pub type DependencyMap = BTreeMap<HostName, Vec<HostName>>;
+
+/// Hosts depending on each other in a circle, as the path around it
+///
+/// The first host is repeated at the end, e.g. `a -> b -> a`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyCycle(pub Vec<HostName>);
+
+impl fmt::Display for DependencyCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<&str> = self.0.iter().map(HostName::as_str).collect();
+        f.write_str(&path.join(" -> "))
+    }
+}
+
+/// Sort hosts into layers to update one after another
+///
+/// Every host is in an earlier layer than the hosts it depends on, and
+/// each layer is as early as that allows: the first holds the hosts
+/// nothing depends on. Hosts within a layer are sorted by name.
+/// Dependencies on hosts missing from the map are ignored.
+///
+/// # Errors
+/// Returns a cycle if some hosts depend on each other.
+pub fn update_layers(dependencies: &DependencyMap) -> Result<Vec<Vec<HostName>>, DependencyCycle> {
+    // How many hosts still to be placed depend on each host
+    let mut dependents: BTreeMap<&HostName, usize> =
+        dependencies.keys().map(|host| (host, 0)).collect();
+    for dependency in dependencies
+        .values()
+        .flat_map(|deps| known(dependencies, deps))
+    {
+        if let Some(count) = dependents.get_mut(dependency) {
+            *count += 1;
+        }
+    }
+
+    let mut layers = Vec::new();
+    while !dependents.is_empty() {
+        let layer: Vec<HostName> = dependents
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(host, _)| (*host).clone())
+            .collect();
+        if layer.is_empty() {
+            // Every host left is depended on by another one left
+            let remaining: DependencyMap = dependents
+                .keys()
+                .map(|host| ((*host).clone(), dependencies[*host].clone()))
+                .collect();
+            return Err(find_cycle(&remaining).expect("hosts left over form a cycle"));
+        }
+        for host in &layer {
+            dependents.remove(host);
+            for dependency in known(dependencies, &dependencies[host]) {
+                if let Some(count) = dependents.get_mut(dependency) {
+                    *count -= 1;
+                }
+            }
+        }
+        layers.push(layer);
+    }
+    Ok(layers)
+}
+
+/// Find a cycle among the hosts, if there is one
+///
+/// Dependencies on hosts missing from the map are ignored.
+#[must_use]
+pub fn find_cycle(dependencies: &DependencyMap) -> Option<DependencyCycle> {
+    let mut done = BTreeSet::new();
+    let mut path = Vec::new();
+    dependencies
+        .keys()
+        .find_map(|host| visit(dependencies, host, &mut path, &mut done))
+}
+
+/// Depth-first search from `host`, with `path` the hosts leading to it
+fn visit<'a>(
+    dependencies: &'a DependencyMap,
+    host: &'a HostName,
+    path: &mut Vec<&'a HostName>,
+    done: &mut BTreeSet<&'a HostName>,
+) -> Option<DependencyCycle> {
+    if done.contains(host) {
+        return None;
+    }
+    if let Some(start) = path.iter().position(|on_path| *on_path == host) {
+        let mut cycle: Vec<HostName> = path[start..].iter().map(|h| (*h).clone()).collect();
+        cycle.push(host.clone());
+        return Some(DependencyCycle(cycle));
+    }
+
+    path.push(host);
+    for dependency in known(dependencies, &dependencies[host]) {
+        if let Some(cycle) = visit(dependencies, dependency, path, done) {
+            return Some(cycle);
+        }
+    }
+    path.pop();
+    done.insert(host);
+    None
+}
+
+/// Every host that depends on `host`, directly or through others
+#[must_use]
+pub fn dependents_of<'a>(
+    dependencies: &'a DependencyMap,
+    host: &HostName,
+) -> BTreeSet<&'a HostName> {
+    let mut found = BTreeSet::new();
+    let mut queue = vec![host];
+    while let Some(current) = queue.pop() {
+        for (dependent, deps) in dependencies {
+            if deps.contains(current) && found.insert(dependent) {
+                queue.push(dependent);
+            }
+        }
+    }
+    found
+}
+
+/// The dependencies that are hosts of the map
+fn known<'a>(
+    dependencies: &'a DependencyMap,
+    deps: &'a [HostName],
+) -> impl Iterator<Item = &'a HostName> {
+    deps.iter().filter(|dep| dependencies.contains_key(*dep))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map(edges: &[(&str, &[&str])]) -> DependencyMap {
+        edges
+            .iter()
+            .map(|(host, deps)| {
+                (
+                    HostName::new(host),
+                    deps.iter().map(|dep| HostName::new(dep)).collect(),
+                )
+            })
+            .collect()
+    }
+
+    fn names(layers: &[Vec<HostName>]) -> Vec<Vec<&str>> {
+        layers
+            .iter()
+            .map(|layer| layer.iter().map(HostName::as_str).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_hosts_without_dependencies_share_a_layer() {
+        let hosts = map(&[("web-2", &[]), ("db", &[]), ("web-1", &[])]);
+        let layers = update_layers(&hosts).unwrap();
+        assert_eq!(names(&layers), [["db", "web-1", "web-2"]]);
+        assert!(update_layers(&DependencyMap::new()).unwrap().is_empty());
+        assert_eq!(find_cycle(&hosts), None);
+    }
+
+    #[test]
+    fn test_chain_updates_dependencies_last() {
+        let hosts = map(&[("app", &["db"]), ("db", &["nfs"]), ("nfs", &[])]);
+        let layers = update_layers(&hosts).unwrap();
+        assert_eq!(names(&layers), [["app"], ["db"], ["nfs"]]);
+
+        let dependents: Vec<&str> = dependents_of(&hosts, &HostName::new("nfs"))
+            .into_iter()
+            .map(HostName::as_str)
+            .collect();
+        assert_eq!(dependents, ["app", "db"]);
+    }
+
+    #[test]
+    fn test_diamond() {
+        let hosts = map(&[
+            ("app", &["cache", "db"]),
+            ("cache", &["nfs"]),
+            ("db", &["nfs"]),
+            ("nfs", &[]),
+            ("tools", &["nfs"]),
+        ]);
+        let layers = update_layers(&hosts).unwrap();
+        // `tools` could go with `cache` and `db`, but goes as early as it can
+        assert_eq!(
+            names(&layers),
+            [vec!["app", "tools"], vec!["cache", "db"], vec!["nfs"]]
+        );
+    }
+
+    #[test]
+    fn test_unknown_dependencies_are_ignored() {
+        let hosts = map(&[("app", &["gone"]), ("db", &[])]);
+        let layers = update_layers(&hosts).unwrap();
+        assert_eq!(names(&layers), [["app", "db"]]);
+    }
+
+    #[test]
+    fn test_cycles_are_reported_as_paths() {
+        let hosts = map(&[
+            ("app", &["db"]),
+            ("db", &["nfs"]),
+            ("nfs", &["app"]),
+            ("web", &["app"]),
+        ]);
+        let cycle = update_layers(&hosts).unwrap_err();
+        assert_eq!(cycle.to_string(), "app -> db -> nfs -> app");
+        assert_eq!(find_cycle(&hosts), Some(cycle));
+
+        let hosts = map(&[("a", &["b"]), ("b", &["a"]), ("c", &[])]);
+        assert_eq!(
+            update_layers(&hosts).unwrap_err().to_string(),
+            "a -> b -> a"
+        );
+    }
+}
@@ -1,8 +1,10 @@
 //! Core error types for tendhost-core
 
+use chrono::{DateTime, Utc};
 use thiserror::Error;
 
-use crate::state::HostState;
+use crate::config::FieldError;
+use crate::state::{HostState, OperationOwner};
 
 /// Errors that can occur in core actor operations
 #[derive(Error, Debug, Clone)]
@@ -15,6 +17,31 @@ pub enum CoreError {
     #[error("host already exists: {0}")]
     HostAlreadyExists(String),
 
+    /// Host is busy with an operation that cannot be interrupted
+    #[error("host is busy: {0}")]
+    HostBusy(String),
+
+    /// Host is busy with or reserved for an operation someone else started
+    #[error("host busy: {0}")]
+    HostOwned(OperationOwner),
+
+    /// Host is paused, so automation leaves it alone and manual updates
+    /// need to be forced
+    #[error("host is paused: {0}")]
+    HostPaused(String),
+
+    /// Operation requires a running update but none is in progress
+    #[error("host is not updating: {0}")]
+    NotUpdating(String),
+
+    /// Operation requires a failed host, e.g. acknowledging its failure
+    #[error("host has not failed: {0}")]
+    NotFailed(String),
+
+    /// Operation was cancelled before it completed
+    #[error("operation cancelled: {0}")]
+    Cancelled(String),
+
     /// Invalid state transition attempted
     #[error("invalid state transition from {from:?} to {to:?}")]
     InvalidTransition {
@@ -36,10 +63,53 @@ pub enum CoreError {
     #[error("inventory query failed: {0}")]
     InventoryError(String),
 
+    /// The inventory asked for needs osquery, which the host lacks or has
+    /// in too old a version
+    #[error(
+        "{} (version required: {}+)",
+        osquery_status(host, installed.as_deref()),
+        tendhost_inventory::MIN_OSQUERY_VERSION
+    )]
+    OsqueryNotInstalled {
+        /// Host name
+        host: String,
+        /// Version found on the host, if any
+        installed: Option<String>,
+    },
+
+    /// The host's circuit breaker is open, so it was not contacted
+    #[error("host {host} is unreachable; retrying at {retry_at}")]
+    HostUnreachable {
+        /// Host name
+        host: String,
+        /// When the host will be tried again
+        retry_at: DateTime<Utc>,
+    },
+
+    /// The host's actor stopped unexpectedly and has not been respawned
+    #[error("host actor for {host} crashed ({reason}); retry to respawn")]
+    HostCrashed {
+        /// Host name
+        host: String,
+        /// Why the actor stopped
+        reason: String,
+    },
+
     /// Host is in failed state and cannot process request
     #[error("host is in failed state: {0}")]
     HostFailed(String),
 
+    /// An update hook exited non-zero, timed out, or could not be run
+    #[error("{stage} hook '{hook}' failed: {message}")]
+    HookFailed {
+        /// `pre-update` or `post-update`
+        stage: &'static str,
+        /// The hook command
+        hook: String,
+        /// Hook stderr or the execution error
+        message: String,
+    },
+
     /// Operation timed out
     #[error("operation timeout")]
     Timeout,
@@ -51,4 +121,31 @@ pub enum CoreError {
     /// Configuration error
     #[error("configuration error: {0}")]
     ConfigError(String),
+
+    /// Host configuration failed validation
+    #[error("invalid host configuration: {}", join_field_errors(.0))]
+    InvalidHostConfig(Vec<FieldError>),
+
+    /// Audit log could not be written or read
+    #[error("audit log error: {0}")]
+    AuditError(String),
+
+    /// Host snapshots could not be written or read
+    #[error("host snapshot error: {0}")]
+    SnapshotError(String),
+}
+
+fn osquery_status(host: &str, installed: Option<&str>) -> String {
+    match installed {
+        Some(version) => format!("osquery {version} on {host} is too old"),
+        None => format!("osquery not installed on {host}"),
+    }
+}
+
+fn join_field_errors(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
 }
@@ -0,0 +1,497 @@
+//! Event fan-out to subscribers
+//!
+//! The orchestrator publishes every event on a broadcast channel. Package
+//! managers can emit progress for each line of output, which floods slow
+//! clients, so the [`EventHub`] sits between the channel and subscribers:
+//!
+//! - `UpdateProgress` events for the same host/package are coalesced within
+//!   a short window, keeping only the latest
+//! - every delivered event carries a sequence number
+//! - each subscriber gets its own bounded queue; when it overflows the
+//!   subscriber receives a synthetic `EventsDropped` event instead of
+//!   holding up everyone else. The notice reuses the sequence number just
+//!   before the event it precedes, so sequence numbers never go backwards
+//!   for the subscriber and never skip for the others
+//! - the most recent events are kept for clients that page through them
+//!   instead of subscribing, or that replay what they missed before
+//!   subscribing
+//! - events no subscriber received are counted per host; the broadcast
+//!   channel can't tell, as the hub itself always listens on it
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{Instant, sleep_until};
+use tracing::{debug, warn};
+
+use kameo::Reply;
+use tendhost_api::events::{EventEnvelope, WsEvent, recent_per_host};
+use tendhost_api::responses::HostEventStats;
+
+/// Default window in which progress events for one package are coalesced
+pub const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(250);
+
+/// Default number of events buffered per subscriber
+pub const DEFAULT_SUBSCRIBER_QUEUE_SIZE: usize = 256;
+
+/// Number of recent events kept for [`EventHub::history`]
+pub const EVENT_HISTORY_SIZE: usize = 1000;
+
+/// A subscriber's queue and the number of events it has missed
+struct Subscriber {
+    tx: mpsc::Sender<EventEnvelope>,
+    /// Events not delivered since the last `EventsDropped` notice
+    dropped: u64,
+}
+
+struct Inner {
+    subscribers: Mutex<Vec<Subscriber>>,
+    queue_size: usize,
+    next_seq: AtomicU64,
+    /// Most recent events in sequence order, oldest first
+    history: Mutex<VecDeque<EventEnvelope>>,
+    /// Emitted and undelivered event counts by host
+    host_stats: Mutex<HashMap<String, HostEventStats>>,
+}
+
+/// Fan-out layer between the orchestrator's event channel and subscribers
+#[derive(Clone, Reply)]
+pub struct EventHub {
+    inner: Arc<Inner>,
+}
+
+impl EventHub {
+    /// Create a hub with no event source
+    ///
+    /// Events are only delivered once a source is attached with [`EventHub::spawn`].
+    fn new(queue_size: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                subscribers: Mutex::new(Vec::new()),
+                // mpsc channels cannot be zero-sized
+                queue_size: queue_size.max(1),
+                next_seq: AtomicU64::new(1),
+                history: Mutex::new(VecDeque::with_capacity(EVENT_HISTORY_SIZE)),
+                host_stats: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Start fanning out events received from `rx`
+    ///
+    /// A zero `coalesce_window` disables coalescing. The background task
+    /// ends when the broadcast channel closes, closing every subscriber.
+    #[must_use]
+    pub fn spawn(
+        rx: broadcast::Receiver<WsEvent>,
+        coalesce_window: Duration,
+        queue_size: usize,
+    ) -> Self {
+        let hub = Self::new(queue_size);
+        tokio::spawn(hub.clone().run(rx, coalesce_window));
+        hub
+    }
+
+    /// Register a new subscriber
+    #[must_use]
+    pub fn subscribe(&self) -> mpsc::Receiver<EventEnvelope> {
+        let (tx, rx) = mpsc::channel(self.inner.queue_size);
+        self.lock_subscribers().push(Subscriber { tx, dropped: 0 });
+        rx
+    }
+
+    /// Number of connected subscribers
+    #[must_use]
+    pub fn subscriber_count(&self) -> usize {
+        self.lock_subscribers().len()
+    }
+
+    /// The last [`EVENT_HISTORY_SIZE`] delivered events, oldest first
+    ///
+    /// Per-subscriber `EventsDropped` notices are not included.
+    #[must_use]
+    pub fn history(&self) -> Vec<EventEnvelope> {
+        self.lock_history().iter().cloned().collect()
+    }
+
+    /// The last `per_host` events of each host still in the history, in
+    /// sequence order
+    #[must_use]
+    pub fn recent_per_host(&self, per_host: usize) -> Vec<EventEnvelope> {
+        recent_per_host(self.lock_history().make_contiguous(), per_host)
+    }
+
+    /// Event counters of every host that emitted events, sorted by host
+    #[must_use]
+    pub fn host_stats(&self) -> Vec<HostEventStats> {
+        let mut stats: Vec<HostEventStats> = self.lock_host_stats().values().cloned().collect();
+        stats.sort_by(|a, b| a.host.cmp(&b.host));
+        stats
+    }
+
+    /// Disconnect every subscriber, ending their streams
+    pub fn close(&self) {
+        self.lock_subscribers().clear();
+    }
+
+    fn lock_subscribers(&self) -> std::sync::MutexGuard<'_, Vec<Subscriber>> {
+        // A panic while holding the lock cannot leave the list inconsistent
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn lock_history(&self) -> std::sync::MutexGuard<'_, VecDeque<EventEnvelope>> {
+        self.inner
+            .history
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn lock_host_stats(&self) -> std::sync::MutexGuard<'_, HashMap<String, HostEventStats>> {
+        self.inner
+            .host_stats
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn next_seq(&self) -> u64 {
+        self.inner.next_seq.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Deliver one event to every subscriber, dropping closed ones
+    fn publish(&self, event: WsEvent) {
+        let envelope = EventEnvelope::new(self.next_seq(), event);
+        let host = envelope.event.host().map(str::to_string);
+        let mut delivered = false;
+
+        {
+            let mut history = self.lock_history();
+            if history.len() == EVENT_HISTORY_SIZE {
+                history.pop_front();
+            }
+            history.push_back(envelope.clone());
+        }
+
+        let mut subscribers = self.lock_subscribers();
+        subscribers.retain_mut(|sub| {
+            if sub.dropped > 0 {
+                match sub.tx.try_reserve() {
+                    Ok(permit) => {
+                        permit.send(EventEnvelope::new(
+                            envelope.seq.saturating_sub(1),
+                            WsEvent::EventsDropped { count: sub.dropped },
+                        ));
+                        sub.dropped = 0;
+                    }
+                    Err(mpsc::error::TrySendError::Full(())) => {
+                        sub.dropped += 1;
+                        return true;
+                    }
+                    Err(mpsc::error::TrySendError::Closed(())) => return false,
+                }
+            }
+
+            match sub.tx.try_send(envelope.clone()) {
+                Ok(()) => {
+                    delivered = true;
+                    true
+                }
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    sub.dropped += 1;
+                    true
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => false,
+            }
+        });
+        drop(subscribers);
+
+        if let Some(host) = host {
+            let mut stats = self.lock_host_stats();
+            let stats = stats.entry(host.clone()).or_insert_with(|| HostEventStats {
+                host,
+                ..HostEventStats::default()
+            });
+            stats.emitted += 1;
+            if !delivered {
+                stats.undelivered += 1;
+            }
+        }
+    }
+
+    /// Count events the hub itself missed against every subscriber
+    fn record_lag(&self, count: u64) {
+        for sub in self.lock_subscribers().iter_mut() {
+            sub.dropped += count;
+        }
+    }
+
+    async fn run(self, mut rx: broadcast::Receiver<WsEvent>, window: Duration) {
+        let mut pending = PendingProgress::default();
+
+        loop {
+            let deadline = pending.next_deadline();
+            tokio::select! {
+                received = rx.recv() => match received {
+                    Ok(event) if window.is_zero() => self.publish(event),
+                    Ok(event @ WsEvent::UpdateProgress { .. }) => {
+                        pending.insert(event, Instant::now() + window);
+                    }
+                    Ok(event) => {
+                        // Keep ordering: progress seen before this event goes first
+                        for progress in pending.drain_all() {
+                            self.publish(progress);
+                        }
+                        self.publish(event);
+                    }
+                    Err(broadcast::error::RecvError::Lagged(count)) => {
+                        warn!(count, "event hub lagged behind orchestrator");
+                        self.record_lag(count);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                () = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                    for progress in pending.drain_due(Instant::now()) {
+                        self.publish(progress);
+                    }
+                }
+            }
+        }
+
+        for progress in pending.drain_all() {
+            self.publish(progress);
+        }
+        self.close();
+        debug!("event source closed, event hub stopping");
+    }
+}
+
+/// Progress events waiting for their coalescing window to elapse
+#[derive(Default)]
+struct PendingProgress {
+    /// Latest event per host/package, in order of first arrival
+    entries: Vec<(Instant, WsEvent)>,
+}
+
+impl PendingProgress {
+    /// Buffer `event`, replacing an earlier one for the same host/package
+    fn insert(&mut self, event: WsEvent, deadline: Instant) {
+        let existing = self
+            .entries
+            .iter_mut()
+            .find(|(_, pending)| same_progress_key(pending, &event));
+        match existing {
+            Some((_, pending)) => *pending = event,
+            None => self.entries.push((deadline, event)),
+        }
+    }
+
+    /// Earliest deadline; entries are pushed with increasing deadlines
+    fn next_deadline(&self) -> Option<Instant> {
+        self.entries.first().map(|(deadline, _)| *deadline)
+    }
+
+    fn drain_due(&mut self, now: Instant) -> Vec<WsEvent> {
+        let due = self
+            .entries
+            .iter()
+            .take_while(|(deadline, _)| *deadline <= now)
+            .count();
+        self.entries.drain(..due).map(|(_, event)| event).collect()
+    }
+
+    fn drain_all(&mut self) -> Vec<WsEvent> {
+        self.entries.drain(..).map(|(_, event)| event).collect()
+    }
+}
+
+fn same_progress_key(a: &WsEvent, b: &WsEvent) -> bool {
+    match (a, b) {
+        (
+            WsEvent::UpdateProgress {
+                host: host_a,
+                package: package_a,
+                ..
+            },
+            WsEvent::UpdateProgress {
+                host: host_b,
+                package: package_b,
+                ..
+            },
+        ) => host_a == host_b && package_a == package_b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(package: &str, progress: u8) -> WsEvent {
+        WsEvent::UpdateProgress {
+            host: "web-1".to_string(),
+            package: package.to_string(),
+            progress,
+        }
+    }
+
+    fn connected(host: &str) -> WsEvent {
+        WsEvent::HostConnected {
+            host: host.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_progress_is_coalesced() {
+        let (tx, rx) = broadcast::channel(16);
+        let hub = EventHub::spawn(rx, Duration::from_secs(10), 16);
+        let mut sub = hub.subscribe();
+
+        tx.send(progress("openssl", 10)).unwrap();
+        tx.send(progress("curl", 50)).unwrap();
+        tx.send(progress("openssl", 90)).unwrap();
+        // A non-progress event flushes pending progress ahead of itself
+        tx.send(connected("web-1")).unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..3 {
+            received.push(sub.recv().await.unwrap());
+        }
+
+        assert!(matches!(
+            &received[0].event,
+            WsEvent::UpdateProgress { package, progress: 90, .. } if package == "openssl"
+        ));
+        assert!(matches!(
+            &received[1].event,
+            WsEvent::UpdateProgress { package, progress: 50, .. } if package == "curl"
+        ));
+        assert!(matches!(received[2].event, WsEvent::HostConnected { .. }));
+        assert!(received.windows(2).all(|w| w[0].seq < w[1].seq));
+    }
+
+    #[tokio::test]
+    async fn test_overflow_reports_dropped_events() {
+        let hub = EventHub::new(2);
+        let mut slow = hub.subscribe();
+        let mut fast = hub.subscribe();
+
+        for n in 0..5 {
+            hub.publish(connected(&format!("host-{n}")));
+            while fast.try_recv().is_ok() {}
+        }
+
+        assert!(
+            matches!(slow.try_recv().unwrap().event, WsEvent::HostConnected { host } if host == "host-0")
+        );
+        assert!(
+            matches!(slow.try_recv().unwrap().event, WsEvent::HostConnected { host } if host == "host-1")
+        );
+        assert!(slow.try_recv().is_err());
+
+        hub.publish(connected("host-5"));
+        let notice = slow.try_recv().unwrap();
+        assert!(matches!(notice.event, WsEvent::EventsDropped { count: 3 }));
+        let event = slow.try_recv().unwrap();
+        assert!(matches!(event.event, WsEvent::HostConnected { host } if host == "host-5"));
+        // The notice sorts before the event without taking a number of its own
+        assert_eq!(notice.seq + 1, event.seq);
+
+        // The fast subscriber never missed anything, nor any sequence number
+        let last = fast.try_recv().unwrap();
+        assert!(matches!(last.event, WsEvent::HostConnected { host } if host == "host-5"));
+        assert_eq!(last.seq, 6);
+    }
+
+    #[tokio::test]
+    async fn test_sequence_numbers_never_go_backwards() {
+        let hub = EventHub::new(2);
+        let mut slow = hub.subscribe();
+        let mut fast = hub.subscribe();
+
+        let mut slow_seqs = Vec::new();
+        let mut fast_seqs = Vec::new();
+        for n in 0..20 {
+            hub.publish(connected(&format!("host-{n}")));
+            while let Ok(envelope) = fast.try_recv() {
+                fast_seqs.push(envelope.seq);
+            }
+            // Drain the slow subscriber only now and then
+            if n % 5 == 4 {
+                while let Ok(envelope) = slow.try_recv() {
+                    slow_seqs.push(envelope.seq);
+                }
+            }
+        }
+
+        assert!(slow_seqs.windows(2).all(|w| w[0] <= w[1]), "{slow_seqs:?}");
+        assert_eq!(fast_seqs, (1..=20).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_history_keeps_recent_events_without_drop_notices() {
+        let hub = EventHub::new(1);
+        let _slow = hub.subscribe();
+
+        for n in 0..EVENT_HISTORY_SIZE + 2 {
+            hub.publish(connected(&format!("host-{n}")));
+        }
+
+        let history = hub.history();
+        assert_eq!(history.len(), EVENT_HISTORY_SIZE);
+        assert!(matches!(&history[0].event, WsEvent::HostConnected { host } if host == "host-2"));
+        assert!(history.windows(2).all(|w| w[0].seq < w[1].seq));
+        assert!(
+            !history
+                .iter()
+                .any(|e| matches!(e.event, WsEvent::EventsDropped { .. }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_undelivered_events_are_counted_and_kept_for_replay() {
+        let hub = EventHub::new(4);
+        hub.publish(connected("web-1"));
+        hub.publish(connected("db-1"));
+        hub.publish(WsEvent::EventsDropped { count: 1 });
+
+        let mut sub = hub.subscribe();
+        hub.publish(connected("web-1"));
+        assert_eq!(sub.try_recv().unwrap().seq, 4);
+
+        assert_eq!(
+            hub.host_stats(),
+            [
+                HostEventStats {
+                    host: "db-1".to_string(),
+                    emitted: 1,
+                    undelivered: 1,
+                },
+                HostEventStats {
+                    host: "web-1".to_string(),
+                    emitted: 2,
+                    undelivered: 1,
+                },
+            ]
+        );
+
+        let recent: Vec<u64> = hub.recent_per_host(1).iter().map(|e| e.seq).collect();
+        assert_eq!(recent, [2, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_closed_subscribers_are_removed() {
+        let hub = EventHub::new(4);
+        let sub = hub.subscribe();
+        let _other = hub.subscribe();
+        assert_eq!(hub.subscriber_count(), 2);
+
+        drop(sub);
+        hub.publish(connected("web-1"));
+        assert_eq!(hub.subscriber_count(), 1);
+    }
+}
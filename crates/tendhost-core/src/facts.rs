@@ -0,0 +1,223 @@
+//! Host facts
+//!
+//! Facts are what tendhost finds out about a host by itself, as opposed
+//! to the tags it is given: the distribution, kernel, architecture and so
+//! on. Hosts gather them from what they already look at (package manager
+//! detection, inventory, updates) plus one cheap probe command run with a
+//! package query, and keep them until the next refresh. They can be
+//! matched by the host list and fleet updates, e.g. `os_id:debian`.
+//!
+//! Keys are a fixed set so filters can be checked up front; values are
+//! plain strings, with booleans as `true` and `false`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use tendhost_inventory::osquery::parse_version;
+
+/// A fact tendhost knows how to find out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FactKey {
+    /// Distribution ID from os-release, e.g. `debian`
+    OsId,
+    /// Distribution version from os-release, e.g. `12`
+    OsVersion,
+    /// Running kernel release, e.g. `6.1.0-21-amd64`
+    Kernel,
+    /// Machine architecture, e.g. `x86_64`
+    Arch,
+    /// Virtualization the host runs under, e.g. `kvm`, or `none`
+    Virtualization,
+    /// Whether the `docker` command is installed
+    Docker,
+    /// Installed osquery version, e.g. `5.12.1`, or `none`
+    Osquery,
+    /// Whether the last update left a reboot pending
+    RebootRequired,
+}
+
+impl FactKey {
+    /// Every fact, in display order
+    pub const ALL: [Self; 8] = [
+        Self::OsId,
+        Self::OsVersion,
+        Self::Kernel,
+        Self::Arch,
+        Self::Virtualization,
+        Self::Docker,
+        Self::Osquery,
+        Self::RebootRequired,
+    ];
+
+    /// The key as written in filters and responses
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::OsId => "os_id",
+            Self::OsVersion => "os_version",
+            Self::Kernel => "kernel",
+            Self::Arch => "arch",
+            Self::Virtualization => "virtualization",
+            Self::Docker => "docker",
+            Self::Osquery => "osquery",
+            Self::RebootRequired => "reboot_required",
+        }
+    }
+}
+
+impl fmt::Display for FactKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for FactKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|key| key.as_str() == s)
+            .ok_or_else(|| {
+                let known: Vec<_> = Self::ALL.iter().map(|key| key.as_str()).collect();
+                format!("unknown fact '{s}', expected one of {}", known.join(", "))
+            })
+    }
+}
+
+/// Facts of a host by key
+pub type Facts = BTreeMap<FactKey, String>;
+
+/// A fact a host must have, e.g. `os_id:debian`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FactFilter {
+    pub key: FactKey,
+    pub value: String,
+}
+
+impl FactFilter {
+    /// Whether `facts` has the fact with exactly this value
+    ///
+    /// A host whose fact is not known yet does not match.
+    #[must_use]
+    pub fn matches(&self, facts: &Facts) -> bool {
+        facts.get(&self.key) == Some(&self.value)
+    }
+}
+
+impl fmt::Display for FactFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.key, self.value)
+    }
+}
+
+impl FromStr for FactFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((key, value)) = s.split_once(':').filter(|(_, v)| !v.trim().is_empty()) else {
+            return Err(format!("fact filter '{s}' must be written as key:value"));
+        };
+        Ok(Self {
+            key: key.trim().parse()?,
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+/// Command printing the facts that need a look at the host, one
+/// `key=value` line each
+pub const FACTS_PROBE: &str = "printf 'kernel=%s\\narch=%s\\nvirtualization=%s\\n' \
+     \"$(uname -r)\" \"$(uname -m)\" \"$(systemd-detect-virt 2>/dev/null)\"; \
+     if command -v docker >/dev/null 2>&1; then echo docker=true; else echo docker=false; fi; \
+     printf 'osquery=%s\\n' \"$(osqueryi --version 2>/dev/null || echo none)\"";
+
+/// The facts in the output of [`FACTS_PROBE`]
+///
+/// Lines that aren't a known `key=value` pair and empty values, e.g. from
+/// a host without `systemd-detect-virt`, are skipped. osquery's version
+/// is taken from its `--version` banner.
+#[must_use]
+pub fn parse_probe(output: &str) -> Facts {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once('=')?;
+            let value = value.trim();
+            if value.is_empty() {
+                return None;
+            }
+            let key = key.trim().parse().ok()?;
+            let value = match key {
+                FactKey::Osquery if value != "none" => parse_version(value)?,
+                _ => value.to_string(),
+            };
+            Some((key, value))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_round_trip() {
+        for key in FactKey::ALL {
+            assert_eq!(key.to_string().parse::<FactKey>(), Ok(key));
+            assert_eq!(
+                serde_json::to_value(key).unwrap(),
+                serde_json::json!(key.as_str())
+            );
+        }
+        let err = "distro".parse::<FactKey>().unwrap_err();
+        assert!(err.contains("os_id, os_version"), "{err}");
+    }
+
+    #[test]
+    fn test_filters() {
+        let filter: FactFilter = "os_id:debian".parse().unwrap();
+        assert_eq!(filter.key, FactKey::OsId);
+        assert_eq!(filter.to_string(), "os_id:debian");
+
+        let mut facts = Facts::new();
+        assert!(!filter.matches(&facts));
+        facts.insert(FactKey::OsId, "debian".to_string());
+        assert!(filter.matches(&facts));
+        facts.insert(FactKey::OsId, "debian-like".to_string());
+        assert!(!filter.matches(&facts));
+
+        assert!("os_id".parse::<FactFilter>().is_err());
+        assert!("os_id:".parse::<FactFilter>().is_err());
+        assert!("distro:debian".parse::<FactFilter>().is_err());
+        // Only the first colon separates, so values may contain more
+        let filter: FactFilter = "kernel:6.1:rc1".parse().unwrap();
+        assert_eq!(filter.value, "6.1:rc1");
+    }
+
+    #[test]
+    fn test_parse_probe() {
+        let facts = parse_probe(
+            "kernel=6.1.0-21-amd64\narch=x86_64\nvirtualization=\ndocker=true\nok\nshell=bash\n\
+             osquery=osqueryi version 5.12.1\n",
+        );
+        assert_eq!(
+            facts,
+            Facts::from([
+                (FactKey::Kernel, "6.1.0-21-amd64".to_string()),
+                (FactKey::Arch, "x86_64".to_string()),
+                (FactKey::Docker, "true".to_string()),
+                (FactKey::Osquery, "5.12.1".to_string()),
+            ])
+        );
+        assert_eq!(
+            parse_probe("osquery=none\n")
+                .get(&FactKey::Osquery)
+                .map(String::as_str),
+            Some("none")
+        );
+    }
+}
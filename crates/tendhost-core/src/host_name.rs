@@ -0,0 +1,178 @@
+//! Host identity
+//!
+//! Host names arrive from the config file, API registrations and SSH
+//! config imports, spelled however their author typed them. Two spellings
+//! of one name must never become two actors fighting over the same
+//! machine, so names are normalized to lowercase when they enter the
+//! daemon and compared only in that form.
+
+use std::fmt;
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+/// Longest accepted host name, as for a DNS label
+pub const MAX_HOST_NAME_LEN: usize = 63;
+
+/// A host's name, normalized to lowercase
+///
+/// Constructing one never fails so that invalid names still reach
+/// [`HostConfig::validate`](crate::HostConfig::validate), which reports
+/// every invalid field at once; use [`HostName::parse`] to reject them
+/// up front.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub struct HostName(String);
+
+impl HostName {
+    /// Normalize `name`
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self(name.to_ascii_lowercase())
+    }
+
+    /// Normalize and validate `name`
+    ///
+    /// # Errors
+    /// Returns why the name is not usable, see [`HostName::validate`].
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let name = Self::new(name);
+        name.validate()?;
+        Ok(name)
+    }
+
+    /// Check the name can be used in URL paths and filters
+    ///
+    /// Names are limited to letters, digits, `-`, `_` and `.`, start with
+    /// a letter or digit and are at most [`MAX_HOST_NAME_LEN`] long.
+    ///
+    /// # Errors
+    /// Returns a description of the first problem found.
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.as_str();
+        if name.is_empty() {
+            return Err("must not be empty".to_string());
+        }
+        if name.len() > MAX_HOST_NAME_LEN {
+            return Err(format!("must be at most {MAX_HOST_NAME_LEN} characters"));
+        }
+        if !name.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+            return Err("must start with a letter or digit".to_string());
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        {
+            return Err(format!(
+                "invalid character '{c}' (allowed: letters, digits, '-', '_', '.')"
+            ));
+        }
+        Ok(())
+    }
+
+    /// The normalized name
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether `raw` is already in normalized form
+    #[must_use]
+    pub fn is_normalized(raw: &str) -> bool {
+        Self::new(raw).0 == raw
+    }
+}
+
+impl fmt::Display for HostName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for HostName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for HostName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for HostName {
+    fn from(name: String) -> Self {
+        Self::new(&name)
+    }
+}
+
+impl From<&str> for HostName {
+    fn from(name: &str) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<&String> for HostName {
+    fn from(name: &String) -> Self {
+        Self::new(name)
+    }
+}
+
+impl From<HostName> for String {
+    fn from(name: HostName) -> Self {
+        name.0
+    }
+}
+
+/// Names compare with other spellings case-insensitively
+impl PartialEq<str> for HostName {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == HostName::new(other).0
+    }
+}
+
+impl PartialEq<&str> for HostName {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl PartialEq<String> for HostName {
+    fn eq(&self, other: &String) -> bool {
+        self == other.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spellings_of_one_name_are_equal() {
+        let name = HostName::new("Web-1");
+        assert_eq!(name.as_str(), "web-1");
+        assert_eq!(name, HostName::from("wEb-1".to_string()));
+        assert_eq!(name, "WEB-1");
+        assert!(HostName::is_normalized("web-1"));
+        assert!(!HostName::is_normalized("Web-1"));
+    }
+
+    #[test]
+    fn test_serde_normalizes() {
+        let name: HostName = serde_json::from_str(r#""DB.Lan""#).unwrap();
+        assert_eq!(name.to_string(), "db.lan");
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#""db.lan""#);
+    }
+
+    #[test]
+    fn test_parse_validates() {
+        assert_eq!(HostName::parse("Web-1").unwrap(), "web-1");
+        assert!(HostName::parse("").is_err());
+        assert!(HostName::parse("-web").is_err());
+        assert!(HostName::parse("web/1").is_err());
+        assert!(HostName::parse(&"a".repeat(MAX_HOST_NAME_LEN + 1)).is_err());
+    }
+}
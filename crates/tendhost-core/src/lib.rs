@@ -4,19 +4,53 @@
 //! Contains message types, state machines, and fleet logic.
 
 pub mod actor;
+pub mod audit;
 pub mod config;
+pub mod dependencies;
 pub mod error;
+pub mod events;
+pub mod facts;
+pub mod host_name;
 pub mod message;
+pub mod responses;
+pub mod snapshot;
 pub mod state;
+#[cfg(feature = "test-util")]
+pub mod testing;
 
 pub use actor::host::{HostActor, HostActorArgs};
-pub use actor::orchestrator::{HostActorFactory, OrchestratorActor, OrchestratorActorArgs};
-pub use config::{FleetFilter, FleetUpdateConfig, HostConfig, HostPolicy, MaintenanceWindow};
+pub use actor::orchestrator::{
+    DEFAULT_REGISTRATION_CONCURRENCY, HostActorFactory, OrchestratorActor, OrchestratorActorArgs,
+    RestartPolicy,
+};
+pub use audit::{AuditLog, AuditQuery};
+pub use config::{
+    AutoRetryPolicy, FieldError, FleetFilter, FleetUpdateConfig, HealthCheckSpec, HostConfig,
+    HostConfigPatch, HostPolicy, HostPolicyPatch, ImagePrunePolicy, JumpHost, JumpSpec,
+    MaintenanceWindow, PruneMode, TimeoutPolicy, check_key_file, check_passphrase_env,
+};
+pub use dependencies::{DependencyCycle, DependencyMap};
 pub use error::CoreError;
+pub use events::EventHub;
+pub use facts::{FactFilter, FactKey, Facts};
+pub use host_name::HostName;
 pub use message::{
-    Acknowledge, AcknowledgeHost, FleetUpdateProgress, GetHostStatus, GetState, GetStatus,
-    HealthCheck, HealthCheckResult, HostStatus, InventoryResult, ListHosts, QueryHostInventory,
-    QueryInventory, RebootIfRequired, RegisterHost, Retry, RetryHost, StartUpdate,
-    TriggerFleetUpdate, TriggerHostUpdate, UnregisterHost, UpdateResult,
+    Acknowledge, AcknowledgeHost, CancelHostUpdate, CancelUpdate, CheckOutcome,
+    CollectHostInventory, CollectInventory, FleetDryRun, FleetHostOutcome, FleetUpdateProgress,
+    GetCommandHistory, GetEventHub, GetExecutorStats, GetFleetSummary, GetHostCommandHistory,
+    GetHostExecutorStats, GetHostInventoryDiff, GetHostStatus, GetHostTransitionHistory,
+    GetHostUpdateHistory, GetInventoryDiff, GetRecentEvents, GetState, GetStatus,
+    GetTransitionHistory, GetUpdateHistory, HealthCheck, HealthCheckResult, HostStatus,
+    InstallHostOsquery, InstallOsquery, InventoryResult, ListBusyHosts, ListHostConfigs, ListHosts,
+    OsqueryInstall, PauseHost, PreviewHostUpdate, PreviewUpdate, QueryHostInventory,
+    QueryInventory, RebootIfRequired, RegisterHost, RegisterHosts, ReleaseReservation,
+    ReplaceHostConfig, ReserveForUpdate, Restore, RestoreHosts, ResumeHost, Retry, RetryHost,
+    SaveSnapshots, SetPaused, Snapshot, StartUpdate, SubscribeEvents, Traced, TriggerFleetUpdate,
+    TriggerHostUpdate, UnregisterHost, UpdateConfig, UpdateHostConfig, UpdateResult,
+};
+pub use snapshot::{HostSnapshot, SnapshotStore};
+pub use state::{
+    BreakerState, CircuitBreaker, FailedStateContext, FailureKind, HostState, Initiator,
+    MultiSourceInventory, OperationOwner, PendingUpdatesContext, RetryAttempt, SourceListing,
+    StateTransition,
 };
-pub use state::{FailedStateContext, HostState, PendingUpdatesContext};
@@ -2,36 +2,146 @@
 //!
 //! Message handlers are implemented in their respective actor modules.
 
+use std::time::Duration;
+
 use chrono::{DateTime, Utc};
 use kameo_macros::Reply;
+use tracing::Span;
+
+use tendhost_api::events::FleetPhase;
+use tendhost_api::requests::UpdateScope;
+use tendhost_pkg::types::{DistroInfo, RestartRequirement, UpgradablePackage};
 
-use crate::config::{FleetUpdateConfig, HostConfig};
-use crate::state::HostState;
+use crate::config::{FleetUpdateConfig, HostConfig, HostConfigPatch};
+use crate::facts::{FactKey, Facts};
+use crate::host_name::HostName;
+use crate::snapshot::HostSnapshot;
+use crate::state::{
+    FailedStateContext, FailureKind, HostState, Initiator, OperationOwner, StateTransition,
+};
 
 // ============================================================================
 // HostActor Messages
 // ============================================================================
 
 /// Query host inventory via osquery
-#[derive(Debug)]
-pub struct QueryInventory;
+#[derive(Debug, Default)]
+pub struct QueryInventory {
+    /// Refresh the package lists first, even if they are recent
+    pub refresh: bool,
+    /// Who asked; refused if the host is owned by someone else
+    pub initiator: Initiator,
+}
 
 /// Inventory query result
 #[derive(Debug, Clone, Reply)]
 pub struct InventoryResult {
     /// Number of packages with pending updates
     pub pending_updates: u32,
+    /// Number of pending updates that fix security issues
+    pub security_updates: u32,
     /// Package names with updates available
     pub packages: Vec<String>,
 }
 
+/// Collect full host inventory (system, hardware, packages, ports, services)
+#[derive(Debug, Default)]
+pub struct CollectInventory {
+    /// Fail with [`CoreError::OsqueryNotInstalled`](crate::CoreError::OsqueryNotInstalled)
+    /// instead of falling back to shell inventory when the host lacks a
+    /// supported osquery
+    pub require_osquery: bool,
+}
+
+/// Install osquery from its upstream repository
+///
+/// Runs like an update: the host must be idle or have updates pending, is
+/// busy meanwhile and returns to where it was. Does nothing if a supported
+/// osquery is already installed.
+#[derive(Debug, Default)]
+pub struct InstallOsquery {
+    /// Who asked; refused if the host is owned by someone else
+    pub initiator: Initiator,
+}
+
+/// Outcome of [`InstallOsquery`]
+#[derive(Debug, Clone, Reply)]
+pub struct OsqueryInstall {
+    /// osquery version on the host afterwards, if it could be read
+    pub version: Option<String>,
+    /// `false` if a supported version was already installed
+    pub installed: bool,
+}
+
 /// Start package update process
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct StartUpdate {
     /// If true, only simulate the update
     pub dry_run: bool,
+    /// Which packages to upgrade (defaults to the host policy's scope)
+    pub scope: Option<UpdateScope>,
+    /// Update only this docker compose stack instead of system packages
+    pub stack: Option<String>,
+    /// Upgrade only these packages instead of the whole scope
+    ///
+    /// Each must be among the host's upgradable packages.
+    pub packages: Vec<String>,
+    /// Who asked; refused if the host is owned by someone else
+    pub initiator: Initiator,
+    /// Update a paused host anyway; only honoured for manual requests
+    pub force: bool,
+    /// Leave out the policy's autoremove and cache cleaning this time
+    pub skip_cleanup: bool,
+}
+
+/// Simulate an update of the host's system packages without changing it
+///
+/// Answered in every state but `Updating` and `Rebooting`, pending updates
+/// included. No state transition, event or history entry comes of it.
+#[derive(Debug, Clone, Default)]
+pub struct PreviewUpdate {
+    /// Which packages to simulate (defaults to the host policy's scope)
+    pub scope: Option<UpdateScope>,
+}
+
+/// Reserve an `Idle` or `PendingUpdates` host for an update
+///
+/// Until the initiator's update finishes or the reservation is released,
+/// queries and updates from anyone else fail with
+/// [`CoreError::HostOwned`](crate::error::CoreError::HostOwned). Reserving
+/// again with the same initiator succeeds.
+#[derive(Debug)]
+pub struct ReserveForUpdate {
+    /// Who the host is reserved for
+    pub initiator: Initiator,
 }
 
+/// Give up a reservation made with [`ReserveForUpdate`]
+///
+/// Ignored unless `initiator` holds the reservation.
+#[derive(Debug)]
+pub struct ReleaseReservation {
+    /// Who the host was reserved for
+    pub initiator: Initiator,
+}
+
+/// Pause or resume automation on a host
+///
+/// A paused host is left alone by fleet updates, automatic retries and
+/// reachability probes; manual updates need to be forced.
+#[derive(Debug)]
+pub struct SetPaused {
+    /// Whether the host is paused
+    pub paused: bool,
+}
+
+/// Cancel the running package update
+///
+/// Aborts the update task, terminates the remote upgrade process and moves
+/// the host to `Failed` so the usual retry path applies.
+#[derive(Debug)]
+pub struct CancelUpdate;
+
 /// Update operation result
 #[derive(Debug, Clone, Reply)]
 pub struct UpdateResult {
@@ -41,25 +151,86 @@ pub struct UpdateResult {
     pub upgraded_count: u32,
     /// Whether a reboot is required
     pub reboot_required: bool,
+    /// Reboot and service restarts the update left pending
+    pub restart: RestartRequirement,
+    /// Services restarted automatically after the update
+    pub restarted_services: Vec<String>,
+    /// Problems found that didn't stop the update, such as a dry run
+    /// finding too little free disk space
+    pub warnings: Vec<String>,
 }
 
 /// Trigger reboot if kernel/services require it
 #[derive(Debug)]
 pub struct RebootIfRequired;
 
-/// Perform health check after operations
+/// Run the policy's health checks
+///
+/// In `Verifying` the result decides whether the host returns to `Idle` or
+/// fails.
 #[derive(Debug)]
 pub struct HealthCheck;
 
 /// Health check result
 #[derive(Debug, Clone, Reply)]
 pub struct HealthCheckResult {
-    /// Whether the host is healthy
+    /// Whether every check passed
     pub healthy: bool,
-    /// Optional message with details
+    /// Outcome of each configured check, in order
+    pub checks: Vec<CheckOutcome>,
+    /// When the checks finished
+    pub checked_at: DateTime<Utc>,
+}
+
+impl HealthCheckResult {
+    /// First check that didn't pass
+    #[must_use]
+    pub fn first_failure(&self) -> Option<&CheckOutcome> {
+        self.checks.iter().find(|check| !check.passed)
+    }
+}
+
+/// Outcome of a single health check command
+#[derive(Debug, Clone)]
+pub struct CheckOutcome {
+    /// Command that was run
+    pub command: String,
+    /// Whether the exit code and output matched the expectations
+    pub passed: bool,
+    /// Exit code, if the command ran to completion
+    pub exit_code: Option<i32>,
+    /// Why the check failed
     pub message: Option<String>,
+    /// How long the command took
+    pub duration: Duration,
+    /// When the command finished
+    pub checked_at: DateTime<Utc>,
 }
 
+/// Get the host's recent commands, oldest first
+#[derive(Debug)]
+pub struct GetCommandHistory;
+
+/// Get statistics of the commands the host's executor has run; `None`
+/// until it has run one
+#[derive(Debug)]
+pub struct GetExecutorStats;
+
+/// Get the host's finished updates, newest first
+#[derive(Debug)]
+pub struct GetUpdateHistory {
+    /// Maximum number of records to return
+    pub limit: Option<usize>,
+}
+
+/// Get the host's recorded state transitions, oldest first
+#[derive(Debug)]
+pub struct GetTransitionHistory;
+
+/// Get what changed between the host's last two inventory collections
+#[derive(Debug)]
+pub struct GetInventoryDiff;
+
 /// Retry failed operation (transitions `Failed` -> `Idle`)
 #[derive(Debug)]
 pub struct Retry;
@@ -76,6 +247,25 @@ pub struct GetState;
 #[derive(Debug)]
 pub struct GetStatus;
 
+/// Capture what the host knows, to restore after a daemon restart
+#[derive(Debug)]
+pub struct Snapshot;
+
+/// Resume from a snapshot taken before the daemon restarted
+///
+/// Replies whether it was applied; it isn't once the host has changed
+/// state or queried packages since it started, as that is newer. See
+/// [`HostSnapshot::restored_state`] for the state it resumes in.
+#[derive(Debug)]
+pub struct Restore(pub HostSnapshot);
+
+/// Replace the host configuration in place (tags, policy)
+#[derive(Debug)]
+pub struct UpdateConfig {
+    /// New host configuration
+    pub config: HostConfig,
+}
+
 // ============================================================================
 // OrchestratorActor Messages
 // ============================================================================
@@ -87,39 +277,266 @@ pub struct RegisterHost {
     pub config: HostConfig,
 }
 
+/// Register several hosts at once
+///
+/// The whole batch is rejected if it names a host twice. Otherwise each
+/// host is registered independently and reported in the returned
+/// [`BulkRegisterReport`](tendhost_api::responses::BulkRegisterReport).
+#[derive(Debug)]
+pub struct RegisterHosts {
+    /// Host configurations
+    pub configs: Vec<HostConfig>,
+}
+
 /// Unregister a host from the orchestrator
 #[derive(Debug)]
 pub struct UnregisterHost {
     /// Hostname to remove
-    pub hostname: String,
+    pub hostname: HostName,
+}
+
+/// Apply a partial configuration update to a registered host
+///
+/// Connection changes restart the `HostActor`; other changes are applied in place.
+#[derive(Debug)]
+pub struct UpdateHostConfig {
+    /// Hostname to update
+    pub hostname: HostName,
+    /// Fields to change
+    pub patch: HostConfigPatch,
+}
+
+/// Replace the whole configuration of a registered host
+///
+/// Goes through the same path as [`UpdateHostConfig`]: connection changes
+/// restart the `HostActor` and are refused while it is busy. Unlike a patch,
+/// optional settings missing from `config` are cleared.
+#[derive(Debug)]
+pub struct ReplaceHostConfig {
+    /// New configuration; its name selects the host
+    pub config: HostConfig,
 }
 
 /// Get status of a specific host
 #[derive(Debug)]
 pub struct GetHostStatus {
     /// Hostname to query
-    pub hostname: String,
+    pub hostname: HostName,
+}
+
+/// Get the recent commands run on a specific host
+#[derive(Debug)]
+pub struct GetHostCommandHistory {
+    /// Hostname to query
+    pub hostname: HostName,
+}
+
+/// Get the executor statistics of a specific host
+#[derive(Debug)]
+pub struct GetHostExecutorStats {
+    /// Hostname to query
+    pub hostname: HostName,
+}
+
+/// Get the recorded state transitions of a specific host, oldest first
+#[derive(Debug)]
+pub struct GetHostTransitionHistory {
+    /// Hostname to query
+    pub hostname: HostName,
+}
+
+/// Get the finished updates of a specific host, newest first
+#[derive(Debug)]
+pub struct GetHostUpdateHistory {
+    /// Hostname to query
+    pub hostname: HostName,
+    /// Maximum number of records to return
+    pub limit: Option<usize>,
+}
+
+/// Get what changed between a host's last two inventory collections
+#[derive(Debug)]
+pub struct GetHostInventoryDiff {
+    /// Hostname to query
+    pub hostname: HostName,
 }
 
 /// List all managed hosts
 #[derive(Debug)]
 pub struct ListHosts;
 
+/// Get the configuration of every registered host, sorted by name
+#[derive(Debug)]
+pub struct ListHostConfigs;
+
+/// An orchestrator message handled inside the sender's tracing span
+///
+/// Actors run in their own tasks, so the span of the code asking (such as
+/// an HTTP request with its `request_id`) is lost once the message is
+/// queued. Wrapping the message keeps the orchestrator's log lines
+/// attributable to the request that caused them.
+#[derive(Debug)]
+pub struct Traced<M> {
+    /// Message to handle
+    pub msg: M,
+    /// Span the handler runs in
+    pub span: Span,
+}
+
+impl<M> Traced<M> {
+    /// Wrap `msg` in the current span
+    #[must_use]
+    pub fn new(msg: M) -> Self {
+        Self {
+            msg,
+            span: Span::current(),
+        }
+    }
+}
+
+/// Summarize the state of every managed host
+///
+/// Answered from recently fetched statuses where possible, so it is cheap
+/// to poll.
+#[derive(Debug)]
+pub struct GetFleetSummary;
+
+/// Write a snapshot of every running host to the snapshot store
+///
+/// Sent at shutdown, once busy hosts have settled and before the host
+/// actors stop. Replies with the number of hosts written; does nothing
+/// without a snapshot store.
+#[derive(Debug)]
+pub struct SaveSnapshots;
+
+/// Restore registered hosts from the snapshots of the last graceful
+/// shutdown
+///
+/// Replies with the names of the hosts restored. Snapshots of hosts that
+/// aren't registered are dropped; the stored snapshots are used up either
+/// way. Does nothing without a snapshot store.
+#[derive(Debug)]
+pub struct RestoreHosts;
+
+/// List hosts whose current operation must not be interrupted by shutdown
+///
+/// Replies with host names; see [`HostState::blocks_shutdown`].
+#[derive(Debug)]
+pub struct ListBusyHosts;
+
+/// Subscribe to the orchestrator's raw event stream
+///
+/// Replies with a broadcast receiver; most consumers should go through an
+/// [`EventHub`](crate::events::EventHub) instead of reading it directly.
+#[derive(Debug)]
+pub struct SubscribeEvents;
+
+/// Get the [`EventHub`](crate::events::EventHub) fanning out the
+/// orchestrator's events
+#[derive(Debug)]
+pub struct GetEventHub;
+
+/// Get the last `per_host` events of each host, oldest first
+///
+/// For clients that subscribe late and want to replay what they missed.
+/// Only events still in the hub's history are returned.
+#[derive(Debug)]
+pub struct GetRecentEvents {
+    /// Events returned per host
+    pub per_host: usize,
+}
+
 /// Host status response
 #[derive(Debug, Clone, Reply)]
 pub struct HostStatus {
     /// Host name
-    pub name: String,
+    pub name: HostName,
     /// Current state
     pub state: HostState,
     /// Last successful update timestamp
     pub last_updated: Option<DateTime<Utc>>,
-    /// Number of pending updates (if known)
+    /// Number of pending updates found by the last query, if one has run
+    /// since the last update
     pub pending_updates: Option<u32>,
+    /// Number of pending security updates (if known)
+    pub security_updates: Option<u32>,
+    /// Packages found by the last query, with versions; `None` like
+    /// `pending_updates`
+    pub upgradable_packages: Option<Vec<UpgradablePackage>>,
+    /// When upgradable packages were last queried successfully
+    pub last_checked: Option<DateTime<Utc>>,
     /// Error message if in failed state
     pub error: Option<String>,
     /// Tags assigned to host
     pub tags: Vec<String>,
+    /// Whether the last reachability probes succeeded
+    pub reachable: bool,
+    /// Last time the host answered a probe
+    pub last_seen: Option<DateTime<Utc>>,
+    /// Until when operations fail fast because the host kept refusing
+    /// connections; once past, the next attempt is a trial
+    pub circuit_open_until: Option<DateTime<Utc>>,
+    /// Failure details while in the failed state
+    pub failure: Option<FailedStateContext>,
+    /// Detected distribution, if the package manager knows it
+    pub distro: Option<DistroInfo>,
+    /// Operating system, e.g. "Debian GNU/Linux 12", from the detected
+    /// distribution or else the last collected inventory
+    pub os: Option<String>,
+    /// What tendhost found out about the host by itself so far
+    pub facts: Facts,
+    /// Reboot or service restarts still needed after the last update
+    pub needs_restart: Option<RestartRequirement>,
+    /// Whether `sudo`, or the host's other escalation command, works
+    /// without a password for the SSH user; `None` until checked, or when
+    /// the package manager runs commands without escalation
+    pub sudo_available: Option<bool>,
+    /// Result of the last health check, if one has run
+    pub last_health_check: Option<HealthCheckResult>,
+    /// Who the host is busy with or reserved for, if anyone
+    pub owner: Option<OperationOwner>,
+    /// Whether automation leaves the host alone
+    pub paused: bool,
+    /// Package sources that failed in the last query, as "source: error",
+    /// while others answered
+    pub warnings: Vec<String>,
+    /// The host's latest state transitions, oldest first
+    pub recent_transitions: Vec<StateTransition>,
+}
+
+impl HostStatus {
+    /// Whether the failure has been acknowledged; `None` unless failed
+    #[must_use]
+    pub fn acknowledged(&self) -> Option<bool> {
+        self.failure.as_ref().map(|f| f.acknowledged)
+    }
+
+    /// When the host failed; `None` unless failed
+    #[must_use]
+    pub fn failed_at(&self) -> Option<DateTime<Utc>> {
+        self.failure.as_ref().map(|f| f.failed_at)
+    }
+
+    /// Retries since the failure; `None` unless failed
+    #[must_use]
+    pub fn retry_count(&self) -> Option<u32> {
+        self.failure.as_ref().map(|f| f.retry_count)
+    }
+
+    /// Installed osquery version; `None` if it is missing or not probed yet
+    #[must_use]
+    pub fn osquery_version(&self) -> Option<&str> {
+        self.facts
+            .get(&FactKey::Osquery)
+            .map(String::as_str)
+            .filter(|version| *version != "none")
+    }
+
+    /// Class of the failure; `None` unless failed
+    #[must_use]
+    pub fn failure_kind(&self) -> Option<FailureKind> {
+        self.failure.as_ref().map(|f| f.kind)
+    }
 }
 
 /// Trigger fleet-wide update
@@ -127,11 +544,25 @@ pub struct HostStatus {
 pub struct TriggerFleetUpdate {
     /// Update configuration
     pub config: FleetUpdateConfig,
+    /// Whether a schedule started the update, so its hosts name the
+    /// scheduler as their owner instead of a person
+    pub scheduled: bool,
+}
+
+/// Dry-run a fleet update on every matching host and aggregate the results
+///
+/// Hosts are checked `batch_size` at a time without the batch delay.
+#[derive(Debug)]
+pub struct FleetDryRun {
+    /// Update configuration; `dry_run` is implied
+    pub config: FleetUpdateConfig,
 }
 
 /// Fleet update progress
 #[derive(Debug, Clone, Reply)]
 pub struct FleetUpdateProgress {
+    /// Identifies the fleet update in host owners and logs
+    pub id: u64,
     /// Total hosts in update batch
     pub total_hosts: usize,
     /// Hosts that completed successfully
@@ -140,34 +571,117 @@ pub struct FleetUpdateProgress {
     pub failed: usize,
     /// Hosts currently updating
     pub in_progress: usize,
+    /// Hosts not updated because a canary failed, their circuit breaker
+    /// was open or they are paused
+    pub skipped: usize,
+    /// Outcome of each host that was updated, in update order
+    pub hosts: Vec<FleetHostOutcome>,
+}
+
+/// Outcome of one host in a fleet update
+#[derive(Debug, Clone)]
+pub struct FleetHostOutcome {
+    /// Host name
+    pub host: HostName,
+    /// Whether the host was a canary
+    pub phase: FleetPhase,
+    /// Whether the update succeeded
+    pub success: bool,
+    /// Whether the host was skipped without being contacted, because its
+    /// circuit breaker was open or it is paused
+    pub skipped: bool,
+    /// Error the update failed with
+    pub error: Option<String>,
 }
 
 /// Query inventory for a specific host
 #[derive(Debug)]
 pub struct QueryHostInventory {
     /// Hostname to query
-    pub hostname: String,
+    pub hostname: HostName,
+    /// Refresh the package lists first, even if they are recent
+    pub refresh: bool,
+}
+
+/// Collect full inventory for a specific host
+#[derive(Debug)]
+pub struct CollectHostInventory {
+    /// Hostname to query
+    pub hostname: HostName,
+    /// See [`CollectInventory::require_osquery`]
+    pub require_osquery: bool,
+}
+
+/// Install osquery on a specific host
+#[derive(Debug)]
+pub struct InstallHostOsquery {
+    /// Hostname to install osquery on
+    pub hostname: HostName,
 }
 
 /// Trigger update for a specific host
 #[derive(Debug)]
 pub struct TriggerHostUpdate {
     /// Hostname to update
-    pub hostname: String,
+    pub hostname: HostName,
     /// Whether to perform a dry run
     pub dry_run: bool,
+    /// Which packages to upgrade (defaults to the host policy's scope)
+    pub scope: Option<UpdateScope>,
+    /// Update only this docker compose stack instead of system packages
+    pub stack: Option<String>,
+    /// Upgrade only these packages instead of the whole scope
+    pub packages: Vec<String>,
+    /// Update the host even if it is paused
+    pub force: bool,
+    /// Leave out the policy's autoremove and cache cleaning
+    pub skip_cleanup: bool,
+}
+
+/// Simulate an update of a specific host without changing it, see
+/// [`PreviewUpdate`]
+#[derive(Debug)]
+pub struct PreviewHostUpdate {
+    /// Hostname to preview
+    pub hostname: HostName,
+    /// Which packages to simulate (defaults to the host policy's scope)
+    pub scope: Option<UpdateScope>,
+}
+
+/// Cancel the running update on a specific host
+#[derive(Debug)]
+pub struct CancelHostUpdate {
+    /// Hostname whose update should be cancelled
+    pub hostname: HostName,
 }
 
 /// Retry a failed host
 #[derive(Debug)]
 pub struct RetryHost {
     /// Hostname to retry
-    pub hostname: String,
+    pub hostname: HostName,
 }
 
 /// Acknowledge a failed host
 #[derive(Debug)]
 pub struct AcknowledgeHost {
     /// Hostname to acknowledge
-    pub hostname: String,
+    pub hostname: HostName,
+}
+
+/// Pause automation on a host until it is resumed
+///
+/// Pausing a paused host does nothing. The host stays paused when its actor
+/// restarts, its configuration changes or the daemon restarts gracefully.
+#[derive(Debug)]
+pub struct PauseHost {
+    /// Hostname to pause
+    pub hostname: HostName,
+}
+
+/// Resume automation on a paused host
+#[derive(Debug)]
+pub struct ResumeHost {
+    /// Hostname to resume
+    pub hostname: HostName,
 }
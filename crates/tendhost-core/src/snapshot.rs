@@ -0,0 +1,336 @@
+//! Warm-state snapshots of host actors
+//!
+//! On a graceful shutdown the orchestrator asks every host actor for a
+//! [`HostSnapshot`] and writes them to a [`SnapshotStore`]. After the next
+//! start it hands each re-registered host its snapshot, so pending updates,
+//! failures, pauses and update history survive a daemon upgrade instead of waiting
+//! for the next inventory cycle.
+//!
+//! The file is removed once read, so a daemon that crashed doesn't bring
+//! back the state of an older run. Snapshots carry a schema number: fields
+//! added in later schemas default when missing, so snapshots written by an
+//! older daemon still load, while one written by a newer daemon is ignored.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use kameo_macros::Reply;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::warn;
+
+use tendhost_api::responses::UpdateHistoryEntry;
+use tendhost_pkg::types::{RestartRequirement, UpgradablePackage};
+
+use crate::error::CoreError;
+use crate::facts::Facts;
+use crate::host_name::HostName;
+use crate::state::{FailedStateContext, FailureKind, HostState};
+
+/// Schema of the snapshots this daemon writes
+pub const SNAPSHOT_SCHEMA: u32 = 1;
+
+/// Error a host interrupted in the middle of an update is left failed with
+pub const INTERRUPTED_BY_RESTART: &str = "update interrupted by daemon restart";
+
+/// Upgradable packages found by a host's last query
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckSnapshot {
+    /// Packages with an update available
+    pub packages: Vec<UpgradablePackage>,
+    /// When the query finished
+    pub checked_at: DateTime<Utc>,
+}
+
+/// What a host actor knew when the daemon stopped
+#[derive(Debug, Clone, Serialize, Deserialize, Reply)]
+pub struct HostSnapshot {
+    /// Host the snapshot belongs to
+    pub name: HostName,
+    /// State when the snapshot was taken; see [`HostSnapshot::restored_state`]
+    #[serde(default)]
+    pub state: HostState,
+    /// Result of the last successful upgradable-packages query
+    #[serde(default)]
+    pub last_check: Option<CheckSnapshot>,
+    /// Context of the failure, if the host was failed
+    #[serde(default)]
+    pub failed: Option<FailedStateContext>,
+    /// Last successful update
+    #[serde(default)]
+    pub last_updated: Option<DateTime<Utc>>,
+    /// Reboot or service restarts still needed after the last update
+    #[serde(default)]
+    pub needs_restart: Option<RestartRequirement>,
+    /// Facts probed from the host
+    #[serde(default)]
+    pub facts: Facts,
+    /// Finished updates, oldest first
+    #[serde(default)]
+    pub update_history: Vec<UpdateHistoryEntry>,
+    /// Whether an operator paused automation on the host
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl HostSnapshot {
+    /// The state and failure context a host resumes with
+    ///
+    /// Busy states are never restored. A host stopped while updating may
+    /// have half-upgraded packages, so it comes back failed; other busy
+    /// states, and pending updates without the packages to show for them,
+    /// come back idle. A scheduled automatic retry is not carried over.
+    #[must_use]
+    pub fn restored_state(&self) -> (HostState, Option<FailedStateContext>) {
+        match (self.state, &self.failed) {
+            (HostState::Updating, _) => (
+                HostState::Failed,
+                Some(
+                    FailedStateContext::new(HostState::Updating, INTERRUPTED_BY_RESTART)
+                        .with_kind(FailureKind::Internal),
+                ),
+            ),
+            (HostState::Failed, Some(failed)) => {
+                let mut failed = failed.clone();
+                failed.next_retry_at = None;
+                (HostState::Failed, Some(failed))
+            }
+            (HostState::PendingUpdates, _)
+                if self
+                    .last_check
+                    .as_ref()
+                    .is_some_and(|check| !check.packages.is_empty()) =>
+            {
+                (HostState::PendingUpdates, None)
+            }
+            (HostState::WaitingReboot, _) => (HostState::WaitingReboot, None),
+            _ => (HostState::Idle, None),
+        }
+    }
+}
+
+/// Snapshot file as written
+#[derive(Serialize)]
+struct SnapshotFile<'a> {
+    schema: u32,
+    taken_at: DateTime<Utc>,
+    hosts: &'a [HostSnapshot],
+}
+
+/// Snapshot file as read, before each host is checked on its own
+#[derive(Deserialize)]
+struct RawSnapshotFile {
+    schema: u32,
+    #[serde(default)]
+    hosts: Vec<serde_json::Value>,
+}
+
+/// JSON file holding the host snapshots between two daemon runs
+pub struct SnapshotStore {
+    path: PathBuf,
+}
+
+impl SnapshotStore {
+    /// Create a store writing to `path`
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Path of the snapshot file
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Replace the stored snapshots with `hosts`
+    ///
+    /// The file is written next to its destination and renamed over it,
+    /// so a crash while writing leaves no half-written snapshot.
+    ///
+    /// # Errors
+    /// Returns `CoreError::SnapshotError` if the file cannot be written
+    pub async fn save(&self, hosts: &[HostSnapshot]) -> Result<(), CoreError> {
+        let file = SnapshotFile {
+            schema: SNAPSHOT_SCHEMA,
+            taken_at: Utc::now(),
+            hosts,
+        };
+        let json =
+            serde_json::to_vec(&file).map_err(|e| CoreError::SnapshotError(e.to_string()))?;
+
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(snapshot_io_error)?;
+        }
+        let mut partial = self.path.clone().into_os_string();
+        partial.push(".tmp");
+        fs::write(&partial, json).await.map_err(snapshot_io_error)?;
+        fs::rename(&partial, &self.path)
+            .await
+            .map_err(snapshot_io_error)
+    }
+
+    /// Read the stored snapshots and remove the file
+    ///
+    /// A missing file reads as no snapshots. Snapshots that don't parse
+    /// are skipped with a warning, as is the whole file if a newer daemon
+    /// wrote it.
+    ///
+    /// # Errors
+    /// Returns `CoreError::SnapshotError` if the file exists but cannot be
+    /// read or removed
+    pub async fn take(&self) -> Result<Vec<HostSnapshot>, CoreError> {
+        let content = match fs::read_to_string(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(snapshot_io_error(e)),
+        };
+        fs::remove_file(&self.path)
+            .await
+            .map_err(snapshot_io_error)?;
+        Ok(parse_snapshots(&content))
+    }
+}
+
+/// The host snapshots in the content of a snapshot file
+fn parse_snapshots(content: &str) -> Vec<HostSnapshot> {
+    let file: RawSnapshotFile = match serde_json::from_str(content) {
+        Ok(file) => file,
+        Err(e) => {
+            warn!(error = %e, "ignoring unreadable host snapshot file");
+            return Vec::new();
+        }
+    };
+    if file.schema > SNAPSHOT_SCHEMA {
+        warn!(
+            schema = file.schema,
+            supported = SNAPSHOT_SCHEMA,
+            "ignoring host snapshots written by a newer daemon"
+        );
+        return Vec::new();
+    }
+
+    file.hosts
+        .into_iter()
+        .filter_map(|host| match serde_json::from_value::<HostSnapshot>(host) {
+            Ok(snapshot) => Some(snapshot),
+            Err(e) => {
+                warn!(error = %e, "skipping unreadable host snapshot");
+                None
+            }
+        })
+        .collect()
+}
+
+fn snapshot_io_error(e: std::io::Error) -> CoreError {
+    CoreError::SnapshotError(e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::facts::FactKey;
+
+    fn snapshot(state: HostState) -> HostSnapshot {
+        HostSnapshot {
+            name: "web-1".into(),
+            state,
+            last_check: Some(CheckSnapshot {
+                packages: vec![UpgradablePackage::new("curl", "8.5.0-1", "8.5.0-2")],
+                checked_at: Utc::now(),
+            }),
+            failed: None,
+            last_updated: Some(Utc::now()),
+            needs_restart: None,
+            facts: Facts::from([(FactKey::Kernel, "6.1.0-18-amd64".to_string())]),
+            update_history: Vec::new(),
+            paused: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_round_trip_through_the_store() {
+        let dir = std::env::temp_dir().join(format!("tendhost-snapshot-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = SnapshotStore::new(dir.join("snapshot.json"));
+        assert!(store.take().await.unwrap().is_empty());
+
+        let mut failed = snapshot(HostState::Failed);
+        failed.name = "db-1".into();
+        failed.failed = Some(
+            FailedStateContext::new(HostState::Querying, "connection refused")
+                .with_kind(FailureKind::Connection),
+        );
+        store
+            .save(&[snapshot(HostState::PendingUpdates), failed])
+            .await
+            .unwrap();
+
+        let restored = store.take().await.unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored[0].state, HostState::PendingUpdates);
+        assert_eq!(
+            restored[0].last_check.as_ref().unwrap().packages[0].name,
+            "curl"
+        );
+        assert_eq!(restored[0].facts[&FactKey::Kernel], "6.1.0-18-amd64");
+        let failure = restored[1].failed.as_ref().unwrap();
+        assert_eq!(failure.error, "connection refused");
+        assert_eq!(failure.kind, FailureKind::Connection);
+
+        // Taken snapshots are gone
+        assert!(store.take().await.unwrap().is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_busy_states_are_not_restored() {
+        let (state, failed) = snapshot(HostState::Updating).restored_state();
+        assert_eq!(state, HostState::Failed);
+        assert_eq!(failed.unwrap().error, INTERRUPTED_BY_RESTART);
+
+        for busy in [
+            HostState::Querying,
+            HostState::Rebooting,
+            HostState::Verifying,
+        ] {
+            let (state, failed) = snapshot(busy).restored_state();
+            assert_eq!(state, HostState::Idle, "{busy}");
+            assert!(failed.is_none());
+        }
+
+        let mut pending = snapshot(HostState::PendingUpdates);
+        assert_eq!(pending.restored_state().0, HostState::PendingUpdates);
+        pending.last_check = None;
+        assert_eq!(pending.restored_state().0, HostState::Idle);
+    }
+
+    #[test]
+    fn test_older_snapshots_load_with_defaults() {
+        let content = r#"{
+            "schema": 1,
+            "hosts": [
+                { "name": "web-1", "state": "failed",
+                  "failed": { "previous_state": "updating", "error": "dpkg was interrupted",
+                              "failed_at": "2026-01-01T00:00:00Z" } },
+                { "name": "db-1", "state": "no_such_state" },
+                { "name": "nfs", "unknown_field": true }
+            ]
+        }"#;
+        let snapshots = parse_snapshots(content);
+        let names: Vec<_> = snapshots.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["web-1", "nfs"]);
+
+        let failed = snapshots[0].failed.as_ref().unwrap();
+        assert_eq!(failed.kind, FailureKind::Internal);
+        assert!(failed.attempts.is_empty());
+        assert_eq!(snapshots[1].state, HostState::Idle);
+        assert!(snapshots[1].last_check.is_none());
+
+        let newer = r#"{ "schema": 99, "hosts": [{ "name": "web-1" }] }"#;
+        assert!(parse_snapshots(newer).is_empty());
+        assert!(parse_snapshots("not json").is_empty());
+    }
+}
@@ -1,10 +1,16 @@
 //! Host state machine types
 
 use std::fmt;
+use std::time::Duration;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use kameo_macros::Reply;
 use serde::{Deserialize, Serialize};
+use tendhost_exec::error::ExecError;
+use tendhost_pkg::error::PackageError;
+use tendhost_pkg::types::UpgradablePackage;
+
+use crate::error::CoreError;
 
 /// States for a `HostActor` state machine
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Reply, Default)]
@@ -30,6 +36,18 @@ pub enum HostState {
 }
 
 impl HostState {
+    /// Every state, in state machine order
+    pub const ALL: [Self; 8] = [
+        Self::Idle,
+        Self::Querying,
+        Self::PendingUpdates,
+        Self::Updating,
+        Self::WaitingReboot,
+        Self::Rebooting,
+        Self::Verifying,
+        Self::Failed,
+    ];
+
     /// Check if transition to target state is valid
     ///
     /// Validates against the state machine defined in `GOALS.md`.
@@ -45,7 +63,8 @@ impl HostState {
                 | (Querying | Updating | Rebooting | Verifying | Failed, Idle)
                 | (Querying, PendingUpdates | Failed)
                 | (PendingUpdates, Updating)
-                | (Updating, WaitingReboot | Failed)
+                // A dry run leaves the updates it simulated pending
+                | (Updating, PendingUpdates | WaitingReboot | Failed)
                 | (WaitingReboot, Rebooting)
                 | (Rebooting, Verifying | Failed)
                 | (Verifying, Failed)
@@ -61,6 +80,15 @@ impl HostState {
         )
     }
 
+    /// Whether stopping the host actor now could interrupt the package manager
+    ///
+    /// Reboots are in the host's hands once issued, so `WaitingReboot` and
+    /// `Rebooting` are safe to stop.
+    #[must_use]
+    pub fn blocks_shutdown(&self) -> bool {
+        matches!(self, Self::Querying | Self::Updating)
+    }
+
     /// Whether operations can be started from this state
     #[must_use]
     pub fn can_start_operation(&self) -> bool {
@@ -91,12 +119,206 @@ pub struct PendingUpdatesContext {
     pub package_count: u32,
     /// Names of packages with updates
     pub packages: Vec<String>,
+    /// Number of those updates that fix security issues
+    pub security_count: u32,
     /// When the inventory was queried
     pub queried_at: DateTime<Utc>,
 }
 
-/// Failed state details with recovery information
+/// What one package source of a host listed as upgradable
 #[derive(Debug, Clone)]
+pub struct SourceListing {
+    /// Source name, e.g. "apt" or "docker-compose"
+    pub source: String,
+    /// Upgradable packages, or why the source couldn't list them
+    pub result: Result<Vec<UpgradablePackage>, PackageError>,
+}
+
+/// Upgradable packages from every package source of a host
+///
+/// A host with compose stacks has its system package manager and
+/// docker compose as sources. Sources fail independently: the query only
+/// fails when none of them answered, and the others' failures are kept
+/// as warnings.
+#[derive(Debug, Clone, Default)]
+pub struct MultiSourceInventory {
+    /// Results in query order, the system package manager first
+    pub sources: Vec<SourceListing>,
+}
+
+impl MultiSourceInventory {
+    /// Add the result of querying `source`
+    pub fn push(
+        &mut self,
+        source: impl Into<String>,
+        result: Result<Vec<UpgradablePackage>, PackageError>,
+    ) {
+        self.sources.push(SourceListing {
+            source: source.into(),
+            result,
+        });
+    }
+
+    /// Packages from every source that answered
+    #[must_use]
+    pub fn packages(&self) -> Vec<UpgradablePackage> {
+        self.sources
+            .iter()
+            .filter_map(|s| s.result.as_ref().ok())
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Whether every source failed, so nothing is known about the host
+    #[must_use]
+    pub fn all_failed(&self) -> bool {
+        !self.sources.is_empty() && self.sources.iter().all(|s| s.result.is_err())
+    }
+
+    /// Error of the first source that failed
+    #[must_use]
+    pub fn first_error(&self) -> Option<&PackageError> {
+        self.sources.iter().find_map(|s| s.result.as_ref().err())
+    }
+
+    /// One "source: error" line per failed source
+    #[must_use]
+    pub fn warnings(&self) -> Vec<String> {
+        self.sources
+            .iter()
+            .filter_map(|s| match &s.result {
+                Ok(_) => None,
+                Err(e) => Some(format!("{}: {e}", s.source)),
+            })
+            .collect()
+    }
+
+    /// E.g. "apt ok (4 updates), docker-compose error: daemon unreachable"
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = self
+            .sources
+            .iter()
+            .map(|s| match &s.result {
+                Ok(packages) => format!("{} ok ({} updates)", s.source, packages.len()),
+                Err(e) => format!("{} error: {e}", s.source),
+            })
+            .collect();
+        parts.join(", ")
+    }
+}
+
+/// Broad class of a failure, deciding whether it is retried automatically
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+    /// The host or a repository could not be reached
+    Connection,
+    /// The host refused the SSH login or key; retrying could lock the account
+    Authentication,
+    /// Another process holds the package manager lock
+    #[serde(alias = "lock_conflict")]
+    PackageLock,
+    /// A package operation, hook or check ran and failed; needs a look
+    #[serde(alias = "command_failed")]
+    PackageOperation,
+    /// A command or connection took longer than allowed
+    Timeout,
+    /// Configuration, parse or daemon errors that a retry won't fix
+    #[serde(alias = "permanent")]
+    Internal,
+}
+
+impl FailureKind {
+    /// Every failure kind
+    pub const ALL: [Self; 6] = [
+        Self::Connection,
+        Self::Authentication,
+        Self::PackageLock,
+        Self::PackageOperation,
+        Self::Timeout,
+        Self::Internal,
+    ];
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Self::Connection => "connection",
+            Self::Authentication => "authentication",
+            Self::PackageLock => "package_lock",
+            Self::PackageOperation => "package_operation",
+            Self::Timeout => "timeout",
+            Self::Internal => "internal",
+        };
+        write!(f, "{s}")
+    }
+}
+
+impl From<&ExecError> for FailureKind {
+    fn from(err: &ExecError) -> Self {
+        match err {
+            ExecError::ConnectionFailed(_)
+            | ExecError::TargetUnreachable { .. }
+            | ExecError::NotConnected
+            | ExecError::IoError(_) => Self::Connection,
+            ExecError::JumpHostFailed { source, .. } => Self::from(source.as_ref()),
+            ExecError::AuthenticationFailed(_) | ExecError::SshKeyError(_) => Self::Authentication,
+            ExecError::ConnectTimeout { .. } | ExecError::Timeout { .. } => Self::Timeout,
+            ExecError::CommandFailed { .. } => Self::PackageOperation,
+            ExecError::SpawnError(_) | ExecError::ConfigError(_) => Self::Internal,
+        }
+    }
+}
+
+impl From<&PackageError> for FailureKind {
+    fn from(err: &PackageError) -> Self {
+        match err {
+            PackageError::ExecutionError(_) | PackageError::RepositoryUnavailable(_) => {
+                Self::Connection
+            }
+            PackageError::AuthenticationFailed(_) | PackageError::PermissionDenied(_) => {
+                Self::Authentication
+            }
+            PackageError::LockConflict(_) => Self::PackageLock,
+            PackageError::Timeout { .. } => Self::Timeout,
+            PackageError::CommandFailed { .. }
+            | PackageError::PackageNotFound(_)
+            | PackageError::ComposeConfigInvalid { .. }
+            | PackageError::InsufficientDiskSpace { .. } => Self::PackageOperation,
+            _ => Self::Internal,
+        }
+    }
+}
+
+impl From<&CoreError> for FailureKind {
+    /// Best-effort classification; package errors should be classified from
+    /// the [`PackageError`] before they are flattened into a message
+    fn from(err: &CoreError) -> Self {
+        match err {
+            CoreError::SshError(_) | CoreError::HostUnreachable { .. } => Self::Connection,
+            CoreError::PackageError(_) | CoreError::HookFailed { .. } => Self::PackageOperation,
+            CoreError::Timeout => Self::Timeout,
+            _ => Self::Internal,
+        }
+    }
+}
+
+/// One automatic retry of a failed operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryAttempt {
+    /// When the attempt started
+    pub attempted_at: DateTime<Utc>,
+    /// Error the attempt failed with
+    pub error: String,
+}
+
+/// Failed state details with recovery information
+///
+/// Serialized into host snapshots; fields other than the state, error and
+/// time default when missing, so snapshots of older daemons still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FailedStateContext {
     /// State before failure occurred
     pub previous_state: HostState,
@@ -105,9 +327,27 @@ pub struct FailedStateContext {
     /// When the failure occurred
     pub failed_at: DateTime<Utc>,
     /// Number of retry attempts
+    #[serde(default)]
     pub retry_count: u32,
     /// Whether operator has acknowledged the failure
+    #[serde(default)]
     pub acknowledged: bool,
+    /// Class of the failure
+    #[serde(default = "default_failure_kind")]
+    pub kind: FailureKind,
+    /// Failed automatic retries, oldest first
+    #[serde(default)]
+    pub attempts: Vec<RetryAttempt>,
+    /// When the next automatic retry runs, if one is scheduled
+    #[serde(default)]
+    pub next_retry_at: Option<DateTime<Utc>>,
+    /// End of the failed command's stdout and stderr
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+fn default_failure_kind() -> FailureKind {
+    FailureKind::Internal
 }
 
 impl FailedStateContext {
@@ -120,9 +360,29 @@ impl FailedStateContext {
             failed_at: Utc::now(),
             retry_count: 0,
             acknowledged: false,
+            kind: FailureKind::Internal,
+            attempts: Vec::new(),
+            next_retry_at: None,
+            output: None,
         }
     }
 
+    /// Keep the last `max_lines` lines of the failed command's output
+    #[must_use]
+    pub fn with_output(mut self, output: &str, max_lines: usize) -> Self {
+        let lines: Vec<&str> = output.lines().collect();
+        let tail = lines[lines.len().saturating_sub(max_lines)..].join("\n");
+        self.output = (!tail.trim().is_empty()).then_some(tail);
+        self
+    }
+
+    /// Set the failure class
+    #[must_use]
+    pub fn with_kind(mut self, kind: FailureKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     /// Increment the retry counter
     pub fn increment_retry(&mut self) {
         self.retry_count += 1;
@@ -134,10 +394,260 @@ impl FailedStateContext {
     }
 }
 
+/// Who started an operation on a host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Initiator {
+    /// A request through the API, e.g. from the CLI or TUI
+    #[default]
+    ManualApi,
+    /// A fleet update, by its id
+    FleetUpdate(u64),
+    /// A fleet update started by a schedule, by its fleet update id
+    Scheduler(u64),
+    /// An automatic retry of a failed operation
+    AutoRetry,
+}
+
+impl fmt::Display for Initiator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ManualApi => write!(f, "manual request"),
+            Self::FleetUpdate(id) => write!(f, "fleet update {id}"),
+            Self::Scheduler(id) => write!(f, "scheduler (fleet update {id})"),
+            Self::AutoRetry => write!(f, "automatic retry"),
+        }
+    }
+}
+
+/// The operation a host is busy with or reserved for
+///
+/// Operations started by anyone else are refused while the host has an
+/// owner, naming the owner instead of failing with a state transition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationOwner {
+    /// Who started the operation
+    pub initiator: Initiator,
+    /// When the operation started or the host was reserved
+    pub started_at: DateTime<Utc>,
+}
+
+impl OperationOwner {
+    /// An operation by `initiator` starting now
+    #[must_use]
+    pub fn new(initiator: Initiator) -> Self {
+        Self {
+            initiator,
+            started_at: Utc::now(),
+        }
+    }
+}
+
+impl fmt::Display for OperationOwner {
+    /// E.g. "fleet update 42 started 3m ago"
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = (Utc::now() - self.started_at).num_seconds().max(0);
+        let ago = match secs {
+            0..60 => format!("{secs}s"),
+            60..3600 => format!("{}m", secs / 60),
+            3600..172_800 => format!("{}h", secs / 3600),
+            _ => format!("{}d", secs / 86400),
+        };
+        write!(f, "{} started {ago} ago", self.initiator)
+    }
+}
+
+/// One recorded change of a host's state
+///
+/// Kept so an operator can reconstruct how a host ended up where it is,
+/// e.g. why it is `Failed`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateTransition {
+    /// State before the change
+    pub from: HostState,
+    /// State after the change
+    pub to: HostState,
+    /// When the change happened
+    pub at: DateTime<Utc>,
+    /// Name of the message being handled, e.g. `QueryInventory`
+    pub trigger: String,
+    /// Who the operation belonged to, if anyone, e.g. "fleet update 3"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initiator: Option<String>,
+    /// Why the host failed, for transitions into `Failed`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Fails operations fast while a host keeps refusing connections
+///
+/// The breaker opens after a number of consecutive connection failures and
+/// stays open for a cooldown. Once the cooldown has passed it is half-open:
+/// the next attempt is let through as a trial, whose failure reopens the
+/// breaker at once and whose success closes it.
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreaker {
+    /// Consecutive connection failures
+    failures: u32,
+    /// End of the current cooldown, while open or half-open
+    open_until: Option<DateTime<Utc>>,
+}
+
+/// Where a [`CircuitBreaker`] stands at a given time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Operations run normally
+    Closed,
+    /// Operations fail fast until the given time
+    Open(DateTime<Utc>),
+    /// The cooldown has passed; the next attempt is a trial
+    HalfOpen,
+}
+
+impl CircuitBreaker {
+    /// State of the breaker at `now`
+    #[must_use]
+    pub fn state(&self, now: DateTime<Utc>) -> BreakerState {
+        match self.open_until {
+            None => BreakerState::Closed,
+            Some(until) if now < until => BreakerState::Open(until),
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+
+    /// Consecutive connection failures counted so far
+    #[must_use]
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// End of the current cooldown, unless the breaker is closed
+    #[must_use]
+    pub fn open_until(&self) -> Option<DateTime<Utc>> {
+        self.open_until
+    }
+
+    /// Count a connection failure at `now`
+    ///
+    /// `threshold` is the number of failures that opens the breaker, `None`
+    /// when breaking is disabled. Returns the end of the new cooldown if
+    /// this failure opened or reopened the breaker.
+    pub fn record_failure(
+        &mut self,
+        now: DateTime<Utc>,
+        threshold: Option<u32>,
+        cooldown: Duration,
+    ) -> Option<DateTime<Utc>> {
+        self.failures = self.failures.saturating_add(1);
+        let Some(threshold) = threshold else {
+            self.open_until = None;
+            return None;
+        };
+        if self.open_until.is_none() && self.failures < threshold {
+            return None;
+        }
+
+        let until = TimeDelta::from_std(cooldown)
+            .ok()
+            .and_then(|cooldown| now.checked_add_signed(cooldown))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC);
+        self.open_until = Some(until);
+        Some(until)
+    }
+
+    /// Count a successful contact, or reset the breaker by hand
+    ///
+    /// Returns whether this closed an open or half-open breaker.
+    pub fn record_success(&mut self) -> bool {
+        self.failures = 0;
+        self.open_until.take().is_some()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_multi_source_inventory() {
+        let unreachable = || PackageError::ExecutionError("daemon unreachable".to_string());
+        let packages = |n: usize| {
+            (0..n)
+                .map(|i| UpgradablePackage::new(format!("pkg{i}"), "1", "2"))
+                .collect()
+        };
+
+        let mut both = MultiSourceInventory::default();
+        both.push("apt", Ok(packages(4)));
+        both.push("docker-compose", Ok(packages(1)));
+        assert_eq!(both.packages().len(), 5);
+        assert!(!both.all_failed());
+        assert!(both.warnings().is_empty());
+        assert_eq!(
+            both.summary(),
+            "apt ok (4 updates), docker-compose ok (1 updates)"
+        );
+
+        let mut partial = MultiSourceInventory::default();
+        partial.push("apt", Ok(packages(4)));
+        partial.push("docker-compose", Err(unreachable()));
+        assert_eq!(partial.packages().len(), 4);
+        assert!(!partial.all_failed());
+        assert_eq!(partial.warnings().len(), 1);
+        assert!(partial.warnings()[0].starts_with("docker-compose: "));
+        assert!(
+            partial
+                .summary()
+                .starts_with("apt ok (4 updates), docker-compose error: ")
+        );
+
+        let mut none = MultiSourceInventory::default();
+        none.push("apt", Err(unreachable()));
+        none.push("docker-compose", Err(unreachable()));
+        assert!(none.all_failed());
+        assert!(none.packages().is_empty());
+        assert_eq!(none.warnings().len(), 2);
+        assert!(none.first_error().is_some());
+
+        assert!(!MultiSourceInventory::default().all_failed());
+    }
+
+    #[test]
+    fn test_operation_owner_display() {
+        let mut owner = OperationOwner::new(Initiator::FleetUpdate(42));
+        owner.started_at -= TimeDelta::seconds(200);
+        assert_eq!(owner.to_string(), "fleet update 42 started 3m ago");
+
+        owner.initiator = Initiator::ManualApi;
+        owner.started_at = Utc::now() - TimeDelta::seconds(5);
+        assert_eq!(owner.to_string(), "manual request started 5s ago");
+    }
+
+    #[test]
+    fn test_failed_context_keeps_output_tail() {
+        let output: String = (1..=150).map(|i| format!("line {i}\n")).collect();
+        let context =
+            FailedStateContext::new(HostState::Updating, "failed").with_output(&output, 100);
+        let kept = context.output.unwrap();
+        assert_eq!(kept.lines().count(), 100);
+        assert!(kept.starts_with("line 51\n"));
+        assert!(kept.ends_with("line 150"));
+
+        let context =
+            FailedStateContext::new(HostState::Updating, "failed").with_output(&output, 0);
+        assert!(context.output.is_none());
+        let context = FailedStateContext::new(HostState::Updating, "failed").with_output("\n", 10);
+        assert!(context.output.is_none());
+    }
+
+    #[test]
+    fn test_blocks_shutdown() {
+        assert!(HostState::Updating.blocks_shutdown());
+        assert!(HostState::Querying.blocks_shutdown());
+        assert!(!HostState::WaitingReboot.blocks_shutdown());
+        assert!(!HostState::Rebooting.blocks_shutdown());
+        assert!(!HostState::Idle.blocks_shutdown());
+    }
+
     #[test]
     fn test_valid_transitions() {
         use HostState::{
@@ -150,6 +660,7 @@ mod tests {
         assert!(PendingUpdates.can_transition_to(Updating));
         assert!(Updating.can_transition_to(WaitingReboot));
         assert!(Updating.can_transition_to(Idle));
+        assert!(Updating.can_transition_to(PendingUpdates)); // after a dry run
         assert!(WaitingReboot.can_transition_to(Rebooting));
         assert!(Rebooting.can_transition_to(Verifying));
         assert!(Verifying.can_transition_to(Idle));
@@ -174,6 +685,7 @@ mod tests {
         assert!(!Idle.can_transition_to(Updating)); // must query first
         assert!(!Querying.can_transition_to(Rebooting));
         assert!(!PendingUpdates.can_transition_to(Verifying));
+        assert!(!Querying.can_transition_to(Updating));
         assert!(!Idle.can_transition_to(Idle)); // no self-transition
     }
 
@@ -194,9 +706,156 @@ mod tests {
         assert!(Verifying.is_busy());
     }
 
+    #[test]
+    fn test_failure_kind_reads_api_v1_names() {
+        let parse = |name: &str| serde_json::from_value::<FailureKind>(name.into()).unwrap();
+        assert_eq!(parse("lock_conflict"), FailureKind::PackageLock);
+        assert_eq!(parse("command_failed"), FailureKind::PackageOperation);
+        assert_eq!(parse("permanent"), FailureKind::Internal);
+        for kind in FailureKind::ALL {
+            assert_eq!(parse(&kind.to_string()), kind);
+        }
+    }
+
+    #[test]
+    fn test_failure_kind_classification() {
+        let kind = |e: PackageError| FailureKind::from(&e);
+        assert_eq!(
+            kind(PackageError::ExecutionError("connection reset".into())),
+            FailureKind::Connection
+        );
+        assert_eq!(
+            kind(PackageError::LockConflict("dpkg frontend lock".into())),
+            FailureKind::PackageLock
+        );
+        assert_eq!(
+            kind(PackageError::CommandFailed {
+                status: 100,
+                message: "E: broken packages".into(),
+                output: String::new(),
+            }),
+            FailureKind::PackageOperation
+        );
+        assert_eq!(
+            kind(PackageError::Timeout {
+                operation: "upgrade".into(),
+                timeout: Duration::from_secs(60),
+            }),
+            FailureKind::Timeout
+        );
+        assert_eq!(
+            kind(PackageError::AuthenticationFailed("publickey".into())),
+            FailureKind::Authentication
+        );
+        assert_eq!(
+            kind(PackageError::ParseError("garbage".into())),
+            FailureKind::Internal
+        );
+
+        let exec = |e: ExecError| FailureKind::from(&e);
+        assert_eq!(
+            exec(ExecError::ConnectionFailed("refused".into())),
+            FailureKind::Connection
+        );
+        assert_eq!(
+            exec(ExecError::ConnectTimeout {
+                timeout: Duration::from_secs(10)
+            }),
+            FailureKind::Timeout
+        );
+        assert_eq!(
+            exec(ExecError::JumpHostFailed {
+                jump: "bastion".into(),
+                source: Box::new(ExecError::AuthenticationFailed("publickey".into())),
+            }),
+            FailureKind::Authentication
+        );
+    }
+
+    #[test]
+    fn test_core_error_classification() {
+        let kind = |e: CoreError| FailureKind::from(&e);
+        assert_eq!(
+            kind(CoreError::SshError("connection reset".into())),
+            FailureKind::Connection
+        );
+        assert_eq!(
+            kind(CoreError::HostUnreachable {
+                host: "web".into(),
+                retry_at: Utc::now(),
+            }),
+            FailureKind::Connection
+        );
+        assert_eq!(
+            kind(CoreError::HookFailed {
+                stage: "pre-update",
+                hook: "false".into(),
+                message: "exit 1".into(),
+            }),
+            FailureKind::PackageOperation
+        );
+        assert_eq!(kind(CoreError::Timeout), FailureKind::Timeout);
+        assert_eq!(
+            kind(CoreError::ConfigError("bad".into())),
+            FailureKind::Internal
+        );
+        assert_eq!(FailureKind::PackageLock.to_string(), "package_lock");
+        assert_eq!(
+            serde_json::to_value(FailureKind::PackageOperation).unwrap(),
+            serde_json::json!("package_operation")
+        );
+    }
+
     #[test]
     fn test_display() {
         assert_eq!(HostState::Idle.to_string(), "idle");
         assert_eq!(HostState::PendingUpdates.to_string(), "pending_updates");
     }
+
+    #[test]
+    fn test_circuit_breaker_opens_after_threshold() {
+        let cooldown = Duration::from_secs(60);
+        let now = Utc::now();
+        let mut breaker = CircuitBreaker::default();
+
+        assert_eq!(breaker.record_failure(now, Some(3), cooldown), None);
+        assert_eq!(breaker.record_failure(now, Some(3), cooldown), None);
+        assert_eq!(breaker.state(now), BreakerState::Closed);
+
+        let until = breaker.record_failure(now, Some(3), cooldown).unwrap();
+        assert_eq!(until, now + TimeDelta::seconds(60));
+        assert_eq!(breaker.state(now), BreakerState::Open(until));
+        assert_eq!(breaker.failures(), 3);
+    }
+
+    #[test]
+    fn test_circuit_breaker_half_open_trial() {
+        let cooldown = Duration::from_secs(60);
+        let now = Utc::now();
+        let mut breaker = CircuitBreaker::default();
+        breaker.record_failure(now, Some(1), cooldown).unwrap();
+
+        let later = now + TimeDelta::seconds(61);
+        assert_eq!(breaker.state(later), BreakerState::HalfOpen);
+
+        // A failed trial reopens the breaker without counting up to the threshold
+        let until = breaker.record_failure(later, Some(5), cooldown).unwrap();
+        assert_eq!(breaker.state(later), BreakerState::Open(until));
+
+        // A successful one closes it
+        assert!(breaker.record_success());
+        assert_eq!(breaker.state(later), BreakerState::Closed);
+        assert_eq!(breaker.failures(), 0);
+        assert!(!breaker.record_success());
+    }
+
+    #[test]
+    fn test_circuit_breaker_disabled() {
+        let now = Utc::now();
+        let mut breaker = CircuitBreaker::default();
+        for _ in 0..10 {
+            assert_eq!(breaker.record_failure(now, None, Duration::ZERO), None);
+        }
+        assert_eq!(breaker.state(now), BreakerState::Closed);
+    }
 }
@@ -0,0 +1,509 @@
+//! Test doubles for code built on the actors
+//!
+//! Enabled by the `test-util` feature. [`TestHostFactory`] spawns hosts
+//! that never connect anywhere: every command succeeds and `vim` and `curl`
+//! are always upgradable, so tests can drive a host through a query and an
+//! update without a machine behind it. [`MockExecutor`] and
+//! [`MockPackageManager`] can be set up to fail, hang or count calls for
+//! tests that need more than that.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tendhost_api::events::WsEvent;
+use tendhost_exec::error::ExecError;
+use tendhost_exec::result::CommandResult;
+use tendhost_exec::traits::RemoteExecutor;
+use tendhost_pkg::error::PackageError;
+use tendhost_pkg::escalation::PrivilegeEscalation;
+use tendhost_pkg::traits::PackageManager;
+use tendhost_pkg::types::{
+    PackageManagerType, RestartRequirement, UpdateResult, UpgradablePackage,
+};
+use tokio::sync::broadcast;
+
+use crate::actor::host::HostActorArgs;
+use crate::actor::orchestrator::HostActorFactory;
+use crate::config::{HostConfig, HostPolicy};
+use crate::error::CoreError;
+
+/// Executor running nothing
+///
+/// Every command succeeds printing "ok" unless it was given a reply with
+/// [`MockExecutor::reply`]. Commands are recorded, and the executor can
+/// refuse connections like a host that is down.
+#[derive(Default)]
+pub struct MockExecutor {
+    /// Status and output by command prefix; the first match answers
+    replies: Mutex<Vec<(String, i32, String)>>,
+    /// Commands run so far, refused ones excluded
+    commands: Mutex<Vec<String>>,
+    /// Refuse every connection while set
+    down: AtomicBool,
+    /// Connections still to refuse before commands run again
+    refusals: AtomicUsize,
+}
+
+impl MockExecutor {
+    /// Answer commands starting with `prefix` with `status` and `output`
+    #[must_use]
+    pub fn with_reply(self, prefix: &str, status: i32, output: &str) -> Self {
+        self.reply(prefix, status, output);
+        self
+    }
+
+    /// Refuse the next `times` connections
+    #[must_use]
+    pub fn refusing(self, times: usize) -> Self {
+        self.refusals.store(times, Ordering::SeqCst);
+        self
+    }
+
+    /// Answer commands starting with `prefix` with `status` and `output`
+    ///
+    /// `output` is printed to stdout on success and to stderr otherwise.
+    /// A reply set for the same prefix before is replaced in place.
+    pub fn reply(&self, prefix: &str, status: i32, output: &str) {
+        let mut replies = self.replies.lock().unwrap();
+        let reply = (prefix.to_string(), status, output.to_string());
+        match replies.iter_mut().find(|(p, ..)| p == prefix) {
+            Some(existing) => *existing = reply,
+            None => replies.push(reply),
+        }
+    }
+
+    /// Take the host down, or bring it back up
+    pub fn set_down(&self, down: bool) {
+        self.down.store(down, Ordering::SeqCst);
+    }
+
+    /// Commands run so far
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    /// Forget the commands run so far
+    pub fn clear_commands(&self) {
+        self.commands.lock().unwrap().clear();
+    }
+}
+
+#[async_trait]
+impl RemoteExecutor for MockExecutor {
+    async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+        let refused = self.down.load(Ordering::SeqCst)
+            || self
+                .refusals
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+        if refused {
+            return Err(ExecError::ConnectionFailed(
+                "connection refused".to_string(),
+            ));
+        }
+
+        self.commands.lock().unwrap().push(cmd.to_string());
+        let (status, output) = self
+            .replies
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(prefix, ..)| cmd.starts_with(prefix.as_str()))
+            .map_or((0, "ok".to_string()), |(_, status, output)| {
+                (*status, output.clone())
+            });
+        let (stdout, stderr) = if status == 0 {
+            (output, String::new())
+        } else {
+            (String::new(), output)
+        };
+        Ok(CommandResult {
+            status,
+            stdout,
+            stderr,
+            duration: Duration::from_millis(1),
+        })
+    }
+
+    async fn run_with_timeout(
+        &self,
+        cmd: &str,
+        _timeout: Duration,
+    ) -> Result<CommandResult, ExecError> {
+        self.run(cmd).await
+    }
+
+    fn executor_type(&self) -> &'static str {
+        "mock"
+    }
+}
+
+/// Package manager with a fixed list of upgradable packages
+///
+/// Upgrades succeed, counting the packages they would install, unless the
+/// manager is built with [`MockPackageManager::failing_upgrades`]. The
+/// builders add what a test needs beyond that: compose stacks, services
+/// to restart, packages to autoremove, an escalation or an osquery install.
+#[derive(Default)]
+pub struct MockPackageManager {
+    /// Upgradable packages, each from 0.9.0 to 1.0.0
+    pub packages: Vec<String>,
+    /// Those of `packages` with security fixes
+    pub security_packages: Vec<String>,
+    /// Reported after every update
+    pub reboot_required: bool,
+    /// Services reported as needing a restart after every update
+    pub services_needing_restart: Vec<String>,
+    /// Removed by every autoremove
+    pub autoremovable: Vec<String>,
+    /// Compose stacks, upgraded one package each
+    pub stacks: Vec<String>,
+    /// Stack whose upgrade reports failure, with its error
+    pub broken_stack: Option<(String, String)>,
+    /// Reported type; apt when unset
+    pub manager_type: Option<PackageManagerType>,
+    /// Reported escalation; none when unset
+    pub escalation: Option<PrivilegeEscalation>,
+    /// Returned by every listing instead of the packages
+    pub list_error: Option<PackageError>,
+    /// Listings left that panic, shared so factories can arm it later
+    pub list_panics: Arc<AtomicUsize>,
+    /// Returned by upgrades instead of a result
+    pub upgrade_error: Option<PackageError>,
+    /// Upgrades left that fail with `upgrade_error`
+    pub upgrade_failures: AtomicUsize,
+    /// Whether upgrades never finish on their own
+    pub hang_upgrades: bool,
+    /// Run when osquery is installed; installing is unsupported without it
+    #[allow(clippy::type_complexity)]
+    pub on_osquery_install: Option<Box<dyn Fn() + Send + Sync>>,
+    /// Upgrades started so far, dry runs included
+    pub upgrade_calls: AtomicUsize,
+    /// Package list refreshes so far
+    pub refreshes: AtomicUsize,
+    /// Upgrades cancelled so far
+    pub cancel_calls: AtomicUsize,
+    /// Autoremoves so far
+    pub autoremove_calls: AtomicUsize,
+    /// Package cache cleanings so far
+    pub clean_calls: AtomicUsize,
+    /// osquery installs so far
+    pub osquery_installs: AtomicUsize,
+}
+
+impl MockPackageManager {
+    /// Manager with `packages` upgradable
+    #[must_use]
+    pub fn new(packages: &[&str]) -> Self {
+        Self {
+            packages: strings(packages),
+            ..Self::default()
+        }
+    }
+
+    /// Mark `packages` as security fixes
+    #[must_use]
+    pub fn with_security(mut self, packages: &[&str]) -> Self {
+        self.security_packages = strings(packages);
+        self
+    }
+
+    /// Report that the host needs a reboot after every update
+    #[must_use]
+    pub fn with_reboot_required(mut self) -> Self {
+        self.reboot_required = true;
+        self
+    }
+
+    /// Report `services` as needing a restart after every update
+    #[must_use]
+    pub fn with_services_to_restart(mut self, services: &[&str]) -> Self {
+        self.services_needing_restart = strings(services);
+        self
+    }
+
+    /// Remove `packages` on every autoremove
+    #[must_use]
+    pub fn with_autoremovable(mut self, packages: &[&str]) -> Self {
+        self.autoremovable = strings(packages);
+        self
+    }
+
+    /// Manage compose `stacks`, reporting as docker-compose
+    #[must_use]
+    pub fn with_stacks(mut self, stacks: &[&str]) -> Self {
+        self.stacks = strings(stacks);
+        self.with_manager_type(PackageManagerType::DockerCompose)
+    }
+
+    /// Report failure with `error` when `stack` is upgraded
+    #[must_use]
+    pub fn with_broken_stack(mut self, stack: &str, error: &str) -> Self {
+        self.broken_stack = Some((stack.to_string(), error.to_string()));
+        self
+    }
+
+    /// Report `manager_type` instead of apt
+    #[must_use]
+    pub fn with_manager_type(mut self, manager_type: PackageManagerType) -> Self {
+        self.manager_type = Some(manager_type);
+        self
+    }
+
+    /// Run commands that need root through `escalation`
+    #[must_use]
+    pub fn with_escalation(mut self, escalation: PrivilegeEscalation) -> Self {
+        self.escalation = Some(escalation);
+        self
+    }
+
+    /// Fail every listing with `error`
+    #[must_use]
+    pub fn failing_lists(mut self, error: PackageError) -> Self {
+        self.list_error = Some(error);
+        self
+    }
+
+    /// Panic while listing as long as `panics` is above zero, counting it down
+    #[must_use]
+    pub fn panicking_lists(mut self, panics: Arc<AtomicUsize>) -> Self {
+        self.list_panics = panics;
+        self
+    }
+
+    /// Fail every upgrade with `error`
+    #[must_use]
+    pub fn failing_upgrades(self, error: PackageError) -> Self {
+        self.failing_upgrades_times(error, usize::MAX)
+    }
+
+    /// Fail the next `times` upgrades with `error`
+    #[must_use]
+    pub fn failing_upgrades_times(mut self, error: PackageError, times: usize) -> Self {
+        self.upgrade_error = Some(error);
+        self.upgrade_failures = AtomicUsize::new(times);
+        self
+    }
+
+    /// Never finish an upgrade until it is cancelled
+    #[must_use]
+    pub fn with_hanging_upgrades(mut self) -> Self {
+        self.hang_upgrades = true;
+        self
+    }
+
+    /// Support installing osquery, running `install` when asked to
+    #[must_use]
+    pub fn installing_osquery(mut self, install: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_osquery_install = Some(Box::new(install));
+        self
+    }
+
+    /// Number of upgrades started so far
+    pub fn upgrades(&self) -> usize {
+        self.upgrade_calls.load(Ordering::SeqCst)
+    }
+
+    /// Count an upgrade of `packages`, failing it if configured to
+    async fn upgrade(&self, packages: &[String]) -> Result<UpdateResult, PackageError> {
+        self.upgrade_calls.fetch_add(1, Ordering::SeqCst);
+        if self.hang_upgrades {
+            std::future::pending::<()>().await;
+        }
+        if let Some(error) = &self.upgrade_error
+            && self
+                .upgrade_failures
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+        {
+            return Err(error.clone());
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        let mut result = UpdateResult::success(packages.len() as u32);
+        result.upgraded_packages = packages.to_vec();
+        Ok(result)
+    }
+}
+
+/// Owned copies of `values`
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(ToString::to_string).collect()
+}
+
+#[async_trait]
+impl PackageManager for MockPackageManager {
+    async fn list_upgradable(&self) -> Result<Vec<UpgradablePackage>, PackageError> {
+        let panics = self
+            .list_panics
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+        assert!(panics.is_err(), "package manager crashed");
+        if let Some(error) = &self.list_error {
+            return Err(error.clone());
+        }
+        Ok(self
+            .packages
+            .iter()
+            .map(|name| {
+                UpgradablePackage::new(name.clone(), "0.9.0".to_string(), "1.0.0".to_string())
+                    .with_security(self.security_packages.contains(name))
+            })
+            .collect())
+    }
+
+    async fn update_package_lists(&self) -> Result<(), PackageError> {
+        self.refreshes.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn upgrade_all(&self) -> Result<UpdateResult, PackageError> {
+        self.upgrade(&self.packages).await
+    }
+
+    async fn upgrade_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        self.upgrade_all().await
+    }
+
+    async fn upgrade_security(&self) -> Result<UpdateResult, PackageError> {
+        self.upgrade(&self.security_packages).await
+    }
+
+    async fn upgrade_packages(&self, packages: &[String]) -> Result<UpdateResult, PackageError> {
+        self.upgrade(packages).await
+    }
+
+    async fn list_stacks(&self) -> Result<Vec<String>, PackageError> {
+        Ok(self.stacks.clone())
+    }
+
+    async fn upgrade_stack(&self, stack: &str) -> Result<UpdateResult, PackageError> {
+        if let Some((broken, error)) = &self.broken_stack
+            && broken == stack
+        {
+            return Ok(UpdateResult::failed(error.clone()));
+        }
+        self.upgrade(&[stack.to_string()]).await
+    }
+
+    async fn reboot_required(&self) -> Result<bool, PackageError> {
+        Ok(self.reboot_required)
+    }
+
+    async fn restart_requirement(&self) -> Result<RestartRequirement, PackageError> {
+        let triggered_by = if self.services_needing_restart.is_empty() {
+            Vec::new()
+        } else {
+            self.packages.clone()
+        };
+        Ok(RestartRequirement {
+            reboot_needed: self.reboot_required,
+            services_needing_restart: self.services_needing_restart.clone(),
+            triggered_by,
+        })
+    }
+
+    async fn cancel_upgrade(&self) -> Result<(), PackageError> {
+        self.cancel_calls.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn autoremove(&self) -> Result<Vec<String>, PackageError> {
+        self.autoremove_calls.fetch_add(1, Ordering::SeqCst);
+        Ok(self.autoremovable.clone())
+    }
+
+    async fn clean_cache(&self) -> Result<(), PackageError> {
+        self.clean_calls.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn install_osquery(&self) -> Result<String, PackageError> {
+        let Some(install) = &self.on_osquery_install else {
+            return Err(PackageError::Unsupported(format!(
+                "{} cannot install osquery",
+                self.manager_type()
+            )));
+        };
+        self.osquery_installs.fetch_add(1, Ordering::SeqCst);
+        install();
+        Ok("Setting up osquery (5.12.1-1.linux) ...".to_string())
+    }
+
+    fn escalation(&self) -> PrivilegeEscalation {
+        self.escalation.clone().unwrap_or(PrivilegeEscalation::None)
+    }
+
+    fn manager_type(&self) -> PackageManagerType {
+        self.manager_type.unwrap_or(PackageManagerType::Apt)
+    }
+
+    async fn is_available(&self) -> bool {
+        true
+    }
+}
+
+/// Factory creating hosts from [`MockExecutor`] and a [`MockPackageManager`]
+/// with `vim` and `curl` upgradable
+pub struct TestHostFactory;
+
+#[async_trait]
+impl HostActorFactory for TestHostFactory {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        Ok(Arc::new(MockExecutor::default()))
+    }
+
+    async fn create_package_manager(
+        &self,
+        _config: &HostConfig,
+        _executor: Arc<dyn RemoteExecutor>,
+    ) -> Arc<dyn PackageManager> {
+        Arc::new(MockPackageManager::new(&["vim", "curl"]))
+    }
+}
+
+/// Configuration of a host `name` at 127.0.0.1, tagged `test`
+#[must_use]
+pub fn test_config(name: &str) -> HostConfig {
+    HostConfig {
+        name: name.into(),
+        addr: "127.0.0.1".to_string(),
+        port: None,
+        user: "root".to_string(),
+        ssh_key: None,
+        ssh_key_passphrase_env: None,
+        connect_timeout: None,
+        max_ssh_channels: None,
+        jump_host: None,
+        compose_paths: vec![],
+        compose_env_files: BTreeMap::new(),
+        tags: vec!["test".to_string()],
+        depends_on: vec![],
+        env: BTreeMap::new(),
+        policy: HostPolicy::default(),
+    }
+}
+
+/// Arguments for a host without compose stacks, sending events to `event_tx`
+#[must_use]
+pub fn host_args(
+    config: HostConfig,
+    executor: Arc<dyn RemoteExecutor>,
+    package_manager: Arc<dyn PackageManager>,
+    event_tx: broadcast::Sender<WsEvent>,
+) -> HostActorArgs {
+    HostActorArgs {
+        config,
+        executor,
+        package_manager,
+        compose_manager: None,
+        event_tx,
+        command_history: Arc::default(),
+        executor_stats: Arc::default(),
+    }
+}
@@ -1,91 +1,159 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Duration;
 
 use async_trait::async_trait;
-use kameo::actor::Spawn;
+use kameo::actor::{ActorRef, Spawn};
 use tokio::sync::broadcast;
 
+use tendhost_api::events::{EventEnvelope, FleetPhase, WsEvent};
+use tendhost_api::requests::UpdateScope;
+use tendhost_api::responses::RegistrationStatus;
+use tendhost_core::facts::FACTS_PROBE;
+use tendhost_core::testing::{
+    MockExecutor, MockPackageManager, TestHostFactory, host_args, test_config,
+};
 use tendhost_core::*;
-use tendhost_exec::error::ExecError;
 use tendhost_exec::result::CommandResult;
 use tendhost_exec::traits::RemoteExecutor;
 use tendhost_pkg::error::PackageError;
+use tendhost_pkg::escalation::PrivilegeEscalation;
 use tendhost_pkg::traits::PackageManager;
-use tendhost_pkg::types::{PackageManagerType, UpdateResult as PkgUpdateResult, UpgradablePackage};
+use tendhost_pkg::types::PackageManagerType;
 
-// Mock implementations
-struct MockExecutor;
+/// Package manager whose upgrade always hits its timeout
+fn timing_out_manager() -> MockPackageManager {
+    MockPackageManager::new(&["vim"]).failing_upgrades(PackageError::Timeout {
+        operation: "upgrade".to_string(),
+        timeout: Duration::from_secs(30 * 60),
+    })
+}
 
-#[async_trait]
-impl RemoteExecutor for MockExecutor {
-    async fn run(&self, _cmd: &str) -> Result<CommandResult, ExecError> {
-        Ok(CommandResult {
-            status: 0,
-            stdout: "ok".to_string(),
+/// Package manager whose upgrade always finds the dpkg lock held
+fn locked_manager() -> MockPackageManager {
+    MockPackageManager::new(&["vim"]).failing_upgrades(PackageError::LockConflict(
+        "E: Could not get lock /var/lib/dpkg/lock-frontend. \
+         It is held by process 4211 (unattended-upgr)"
+            .to_string(),
+    ))
+}
+
+/// Package manager whose upgrade exits non-zero after printing a long log
+fn broken_upgrade_manager() -> MockPackageManager {
+    let stdout: String = (1..=200).map(|i| format!("Unpacking pkg-{i}\n")).collect();
+    MockPackageManager::new(&["libc6"]).failing_upgrades(PackageError::command_failed(
+        &CommandResult {
+            status: 100,
+            stdout: format!("{stdout}E: Sub-process /usr/bin/dpkg returned an error code (1)\n"),
             stderr: String::new(),
-            duration: Duration::from_millis(1),
-        })
-    }
+            duration: Duration::from_secs(1),
+        },
+    ))
+}
 
-    async fn run_with_timeout(
-        &self,
-        cmd: &str,
-        _timeout: Duration,
-    ) -> Result<CommandResult, ExecError> {
-        self.run(cmd).await
-    }
+/// Package manager error for a connection dropped mid-upgrade
+fn connection_reset() -> PackageError {
+    PackageError::ExecutionError("connection reset by peer".to_string())
+}
 
-    fn executor_type(&self) -> &'static str {
-        "mock"
-    }
+/// Executor failing hooks and health checks marked with `--fail`
+fn hook_executor() -> MockExecutor {
+    MockExecutor::default()
+        .with_reply("lb drain --fail", 1, "backend still has connections\n")
+        .with_reply("lb enable --fail", 1, "backend still has connections\n")
+        .with_reply("curl -f localhost", 1, "")
 }
 
-struct MockPackageManager {
-    packages: Vec<String>,
-    reboot_required: bool,
+/// Executor reporting a nearly full `/boot` to `df`
+fn full_boot_executor() -> MockExecutor {
+    MockExecutor::default().with_reply(
+        "df ",
+        0,
+        "Mounted on      Avail\n/         21474836480\n/boot       125829120\n",
+    )
 }
 
-#[async_trait]
-impl PackageManager for MockPackageManager {
-    async fn list_upgradable(&self) -> Result<Vec<UpgradablePackage>, PackageError> {
-        Ok(self
-            .packages
-            .iter()
-            .map(|name| {
-                UpgradablePackage::new(name.clone(), "0.9.0".to_string(), "1.0.0".to_string())
-            })
-            .collect())
-    }
+/// Executor answering the facts probe for a kvm guest
+fn facts_executor(arch: &str, docker: bool) -> MockExecutor {
+    MockExecutor::default().with_reply(
+        FACTS_PROBE,
+        0,
+        &format!("kernel=6.1.0-21\narch={arch}\nvirtualization=kvm\ndocker={docker}\n"),
+    )
+}
 
-    async fn upgrade_all(&self) -> Result<PkgUpdateResult, PackageError> {
-        #[allow(clippy::cast_possible_truncation)]
-        let count = self.packages.len() as u32;
-        Ok(PkgUpdateResult::success(count))
-    }
+/// Factory that counts how many executors it has created
+#[derive(Default)]
+struct CountingHostFactory {
+    executors_created: AtomicUsize,
+}
 
-    async fn upgrade_dry_run(&self) -> Result<PkgUpdateResult, PackageError> {
-        self.upgrade_all().await
+#[async_trait]
+impl HostActorFactory for CountingHostFactory {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        self.executors_created.fetch_add(1, Ordering::SeqCst);
+        Ok(Arc::new(MockExecutor::default()))
     }
 
-    async fn reboot_required(&self) -> Result<bool, PackageError> {
-        Ok(self.reboot_required)
+    async fn create_package_manager(
+        &self,
+        config: &HostConfig,
+        executor: Arc<dyn RemoteExecutor>,
+    ) -> Arc<dyn PackageManager> {
+        TestHostFactory
+            .create_package_manager(config, executor)
+            .await
     }
+}
+
+/// Factory whose executors take `delay` to connect, tracking how many
+/// connect at once
+struct ConnectingHostFactory {
+    delay: Duration,
+    connecting: AtomicUsize,
+    most_connecting: AtomicUsize,
+}
 
-    fn manager_type(&self) -> PackageManagerType {
-        PackageManagerType::Apt
+#[async_trait]
+impl HostActorFactory for ConnectingHostFactory {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        let now = self.connecting.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most_connecting.fetch_max(now, Ordering::SeqCst);
+        tokio::time::sleep(self.delay).await;
+        self.connecting.fetch_sub(1, Ordering::SeqCst);
+        Ok(Arc::new(MockExecutor::default()))
     }
 
-    async fn is_available(&self) -> bool {
-        true
+    async fn create_package_manager(
+        &self,
+        config: &HostConfig,
+        executor: Arc<dyn RemoteExecutor>,
+    ) -> Arc<dyn PackageManager> {
+        TestHostFactory
+            .create_package_manager(config, executor)
+            .await
     }
 }
 
-struct TestHostFactory;
+/// Factory whose package managers panic while listing upgrades, `panics` times
+#[derive(Default)]
+struct CrashingHostFactory {
+    panics: Arc<AtomicUsize>,
+}
 
 #[async_trait]
-impl HostActorFactory for TestHostFactory {
-    async fn create_executor(&self, _config: &HostConfig) -> Arc<dyn RemoteExecutor> {
-        Arc::new(MockExecutor)
+impl HostActorFactory for CrashingHostFactory {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        Ok(Arc::new(MockExecutor::default()))
     }
 
     async fn create_package_manager(
@@ -93,40 +161,42 @@ impl HostActorFactory for TestHostFactory {
         _config: &HostConfig,
         _executor: Arc<dyn RemoteExecutor>,
     ) -> Arc<dyn PackageManager> {
-        Arc::new(MockPackageManager {
-            packages: vec!["vim".to_string(), "curl".to_string()],
-            reboot_required: false,
-        })
+        Arc::new(MockPackageManager::new(&[]).panicking_lists(self.panics.clone()))
     }
 }
 
+/// Spawn a host `test-host` from `executor` and `manager`, with its events
+fn spawn_host(
+    executor: Arc<dyn RemoteExecutor>,
+    manager: Arc<dyn PackageManager>,
+) -> (ActorRef<HostActor>, broadcast::Receiver<WsEvent>) {
+    spawn_host_with(test_config("test-host"), executor, manager)
+}
+
+/// Spawn a host from `config`, `executor` and `manager`, with its events
+fn spawn_host_with(
+    config: HostConfig,
+    executor: Arc<dyn RemoteExecutor>,
+    manager: Arc<dyn PackageManager>,
+) -> (ActorRef<HostActor>, broadcast::Receiver<WsEvent>) {
+    let (tx, rx) = broadcast::channel(100);
+    (
+        HostActor::spawn(host_args(config, executor, manager, tx)),
+        rx,
+    )
+}
+
 #[tokio::test]
 async fn test_host_actor_query_inventory() {
-    let (tx, _rx) = broadcast::channel(100);
-
-    let config = HostConfig {
-        name: "test-host".to_string(),
-        addr: "127.0.0.1".to_string(),
-        user: "root".to_string(),
-        ssh_key: None,
-        compose_paths: vec![],
-        tags: vec![],
-        policy: HostPolicy::default(),
-    };
+    let config = test_config("test-host");
 
-    let args = HostActorArgs {
+    let (actor_ref, _rx) = spawn_host_with(
         config,
-        executor: Arc::new(MockExecutor),
-        package_manager: Arc::new(MockPackageManager {
-            packages: vec!["vim".to_string(), "curl".to_string()],
-            reboot_required: false,
-        }),
-        event_tx: tx,
-    };
-
-    let actor_ref = HostActor::spawn(args);
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim", "curl"])),
+    );
 
-    let inventory = actor_ref.ask(QueryInventory).await.unwrap();
+    let inventory = actor_ref.ask(QueryInventory::default()).await.unwrap();
 
     assert_eq!(inventory.pending_updates, 2);
     assert_eq!(inventory.packages, vec!["vim", "curl"]);
@@ -134,24 +204,137 @@ async fn test_host_actor_query_inventory() {
     actor_ref.stop_gracefully().await.unwrap();
 }
 
+#[tokio::test]
+async fn test_query_inventory_refresh_updates_lists() {
+    let manager = Arc::new(MockPackageManager::new(&[]));
+    let (actor_ref, _rx) = spawn_host(Arc::new(MockExecutor::default()), manager.clone());
+
+    // A plain query leaves the decision to the manager's max age
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    assert_eq!(manager.refreshes.load(Ordering::SeqCst), 0);
+
+    actor_ref
+        .ask(QueryInventory {
+            refresh: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(manager.refreshes.load(Ordering::SeqCst), 1);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+/// Package source listing `packages`, or failing when it has none
+fn source_manager(kind: PackageManagerType, packages: Option<Vec<&str>>) -> MockPackageManager {
+    let manager =
+        MockPackageManager::new(packages.as_deref().unwrap_or_default()).with_manager_type(kind);
+    if packages.is_some() {
+        manager
+    } else {
+        manager.failing_lists(PackageError::ExecutionError(format!(
+            "{kind} daemon unreachable"
+        )))
+    }
+}
+
+/// Query a host whose apt and compose sources list `apt` and `compose`
+async fn query_sources(
+    apt: Option<Vec<&'static str>>,
+    compose: Option<Vec<&'static str>>,
+) -> (Option<InventoryResult>, HostStatus, Option<(String, bool)>) {
+    let (tx, mut rx) = broadcast::channel(100);
+    let mut args = host_args(
+        test_config("test-host"),
+        Arc::new(MockExecutor::default()),
+        Arc::new(source_manager(PackageManagerType::Apt, apt)),
+        tx,
+    );
+    args.compose_manager = Some(Arc::new(source_manager(
+        PackageManagerType::DockerCompose,
+        compose,
+    )));
+    let actor_ref = HostActor::spawn(args);
+
+    let result = actor_ref.ask(QueryInventory::default()).await.ok();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    let mut queried = None;
+    while let Ok(event) = rx.try_recv() {
+        if let WsEvent::InventoryQueried {
+            summary, partial, ..
+        } = event
+        {
+            queried = Some((summary, partial));
+        }
+    }
+    actor_ref.stop_gracefully().await.unwrap();
+    (result, status, queried)
+}
+
+#[tokio::test]
+async fn test_host_actor_queries_every_package_source() {
+    let (result, status, queried) =
+        query_sources(Some(vec!["curl", "vim"]), Some(vec!["web"])).await;
+    assert_eq!(result.unwrap().pending_updates, 3);
+    assert_eq!(status.state, HostState::PendingUpdates);
+    assert!(status.warnings.is_empty());
+    assert_eq!(
+        queried,
+        Some((
+            "inventory: apt ok (2 updates), docker-compose ok (1 updates)".to_string(),
+            false
+        ))
+    );
+}
+
+#[tokio::test]
+async fn test_host_actor_partial_source_failure_is_a_warning() {
+    let (result, status, queried) = query_sources(Some(vec!["curl", "vim"]), None).await;
+    assert_eq!(result.unwrap().packages, ["curl", "vim"]);
+    assert_eq!(status.state, HostState::PendingUpdates);
+    assert_eq!(
+        status.warnings,
+        ["docker-compose: execution error: docker-compose daemon unreachable"]
+    );
+    let (summary, partial) = queried.unwrap();
+    assert!(partial);
+    assert_eq!(
+        summary,
+        "inventory: apt ok (2 updates), docker-compose error: execution error: docker-compose daemon unreachable"
+    );
+
+    // A working compose source alone is enough too
+    let (result, status, _) = query_sources(None, Some(vec![])).await;
+    assert_eq!(result.unwrap().pending_updates, 0);
+    assert_eq!(status.state, HostState::Idle);
+    assert_eq!(status.warnings.len(), 1);
+    assert!(status.warnings[0].starts_with("apt: "));
+}
+
+#[tokio::test]
+async fn test_host_actor_fails_when_every_source_fails() {
+    let (result, status, queried) = query_sources(None, None).await;
+    assert!(result.is_none());
+    assert_eq!(status.state, HostState::Failed);
+    assert!(status.warnings.is_empty());
+    assert!(queried.is_none());
+    let error = status.error.unwrap();
+    assert!(error.contains("apt: execution error: apt daemon unreachable"));
+    assert!(error.contains("docker-compose: execution error"));
+}
+
 #[tokio::test]
 async fn test_orchestrator_register_host() {
     let args = OrchestratorActorArgs {
         event_channel_capacity: 100,
         host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
     };
 
     let orchestrator = OrchestratorActor::spawn(args);
 
-    let config = HostConfig {
-        name: "test-host".to_string(),
-        addr: "127.0.0.1".to_string(),
-        user: "root".to_string(),
-        ssh_key: None,
-        compose_paths: vec![],
-        tags: vec!["test".to_string()],
-        policy: HostPolicy::default(),
-    };
+    let config = test_config("test-host");
 
     orchestrator.ask(RegisterHost { config }).await.unwrap();
 
@@ -161,3 +344,3308 @@ async fn test_orchestrator_register_host() {
 
     orchestrator.stop_gracefully().await.unwrap();
 }
+
+#[tokio::test]
+async fn test_orchestrator_update_config_in_place() {
+    let factory = Arc::new(CountingHostFactory::default());
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: factory.clone(),
+        audit_log: None,
+        ..Default::default()
+    });
+
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("test-host"),
+        })
+        .await
+        .unwrap();
+    // Put the host into PendingUpdates so we can tell whether the actor was replaced
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "test-host".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+
+    let status = orchestrator
+        .ask(UpdateHostConfig {
+            hostname: "test-host".into(),
+            patch: HostConfigPatch {
+                tags: Some(vec!["prod".to_string()]),
+                policy: Some(HostPolicyPatch {
+                    auto_reboot: Some(false),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(status.tags, vec!["prod"]);
+    assert_eq!(status.state, HostState::PendingUpdates);
+    assert_eq!(factory.executors_created.load(Ordering::SeqCst), 1);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_replays_events_emitted_without_subscribers() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        coalesce_window: Duration::ZERO,
+        ..Default::default()
+    });
+    let hub = orchestrator.ask(GetEventHub).await.unwrap();
+
+    // Nobody is subscribed while both hosts are queried
+    for name in ["web-1", "db-1"] {
+        orchestrator
+            .ask(RegisterHost {
+                config: test_config(name),
+            })
+            .await
+            .unwrap();
+        orchestrator
+            .ask(QueryHostInventory {
+                hostname: name.into(),
+                refresh: false,
+            })
+            .await
+            .unwrap();
+    }
+    let recent = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let recent = orchestrator
+                .ask(GetRecentEvents { per_host: 2 })
+                .await
+                .unwrap();
+            if recent.len() == 4 {
+                break recent;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("events reach the history");
+    assert!(recent.windows(2).all(|w| w[0].seq < w[1].seq));
+    let hosts: Vec<_> = recent.iter().filter_map(|e| e.event.host()).collect();
+    assert_eq!(hosts, ["web-1", "web-1", "db-1", "db-1"]);
+
+    let stats = hub.host_stats();
+    assert_eq!(stats.len(), 2);
+    assert!(
+        stats
+            .iter()
+            .all(|s| s.emitted > 0 && s.undelivered == s.emitted)
+    );
+
+    // Once subscribed, new events are delivered and come after the replay
+    let mut sub = hub.subscribe();
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("cache-1"),
+        })
+        .await
+        .unwrap();
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "cache-1".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+    let live = tokio::time::timeout(Duration::from_secs(5), sub.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(live.event.host(), Some("cache-1"));
+    assert!(live.seq > recent.last().unwrap().seq);
+    let cache = hub
+        .host_stats()
+        .into_iter()
+        .find(|s| s.host == "cache-1")
+        .unwrap();
+    assert_eq!(cache.undelivered, 0);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_update_config_restarts_on_connection_change() {
+    let factory = Arc::new(CountingHostFactory::default());
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: factory.clone(),
+        audit_log: None,
+        ..Default::default()
+    });
+
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("test-host"),
+        })
+        .await
+        .unwrap();
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "test-host".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+
+    let status = orchestrator
+        .ask(UpdateHostConfig {
+            hostname: "test-host".into(),
+            patch: HostConfigPatch {
+                addr: Some("10.0.0.5".to_string()),
+                user: Some("admin".to_string()),
+                ..Default::default()
+            },
+        })
+        .await
+        .unwrap();
+
+    // A fresh actor starts in Idle with a newly created executor
+    assert_eq!(status.state, HostState::Idle);
+    assert_eq!(factory.executors_created.load(Ordering::SeqCst), 2);
+
+    // The restarted actor still answers for the same host
+    let hosts = orchestrator.ask(ListHosts).await.unwrap();
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].name, "test-host");
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_update_config_rejects_rename() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("test-host"),
+        })
+        .await
+        .unwrap();
+
+    let result = orchestrator
+        .ask(UpdateHostConfig {
+            hostname: "test-host".into(),
+            patch: HostConfigPatch {
+                name: Some("renamed".into()),
+                ..Default::default()
+            },
+        })
+        .await;
+
+    assert!(matches!(
+        result,
+        Err(kameo::error::SendError::HandlerError(
+            CoreError::ConfigError(_)
+        ))
+    ));
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_host_names_are_case_insensitive() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("Web-1"),
+        })
+        .await
+        .unwrap();
+
+    let result = orchestrator
+        .ask(RegisterHost {
+            config: test_config("WEB-1"),
+        })
+        .await;
+    match result {
+        Err(kameo::error::SendError::HandlerError(CoreError::HostAlreadyExists(name))) => {
+            assert_eq!(name, "web-1");
+        }
+        other => panic!("expected HostAlreadyExists, got {other:?}"),
+    }
+
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "wEb-1".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(status.name.as_str(), "web-1");
+
+    // Respelling the name in a patch is not a rename
+    orchestrator
+        .ask(UpdateHostConfig {
+            hostname: "web-1".into(),
+            patch: HostConfigPatch {
+                name: Some("Web-1".into()),
+                ..Default::default()
+            },
+        })
+        .await
+        .unwrap();
+
+    let hosts = orchestrator.ask(ListHosts).await.unwrap();
+    assert_eq!(hosts.len(), 1);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_replace_config_clears_unset_fields() {
+    let factory = Arc::new(CountingHostFactory::default());
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: factory.clone(),
+        audit_log: None,
+        ..Default::default()
+    });
+
+    let mut config = test_config("test-host");
+    config.policy.hook_timeout_secs = Some(30);
+    orchestrator.ask(RegisterHost { config }).await.unwrap();
+
+    let mut replacement = test_config("test-host");
+    replacement.tags = vec!["prod".to_string()];
+    let status = orchestrator
+        .ask(ReplaceHostConfig {
+            config: replacement.clone(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(status.tags, vec!["prod"]);
+    // Policy-only changes keep the running actor
+    assert_eq!(factory.executors_created.load(Ordering::SeqCst), 1);
+
+    let configs = orchestrator.ask(ListHostConfigs).await.unwrap();
+    assert_eq!(configs, vec![replacement]);
+    assert_eq!(configs[0].policy.hook_timeout_secs, None);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_update_runs_in_background() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim", "curl"])),
+    );
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(result.success);
+    assert_eq!(result.upgraded_count, 2);
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert!(status.last_updated.is_some());
+    // The counts from before the update no longer apply
+    assert_eq!(status.pending_updates, None);
+    assert_eq!(status.last_checked, None);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_updates_selected_packages() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim", "curl"])),
+    );
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    // Packages that are not upgradable are refused before anything runs
+    let result = actor_ref
+        .ask(StartUpdate {
+            packages: vec!["curl".to_string(), "nginx".to_string()],
+            ..Default::default()
+        })
+        .await;
+    match result {
+        Err(kameo::error::SendError::HandlerError(CoreError::ConfigError(msg))) => {
+            assert!(msg.ends_with(": nginx"), "{msg}");
+        }
+        other => panic!("expected config error, got {other:?}"),
+    }
+    assert_eq!(
+        actor_ref.ask(GetState).await.unwrap(),
+        HostState::PendingUpdates
+    );
+
+    let result = actor_ref
+        .ask(StartUpdate {
+            packages: vec!["curl".to_string()],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(result.upgraded_count, 1);
+
+    // The refused request leaves no history entry
+    let history = actor_ref
+        .ask(GetUpdateHistory { limit: None })
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].packages, vec!["curl".to_string()]);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_refreshes_updated_inventory_tables() {
+    let executor = Arc::new(MockExecutor::default().with_reply("osqueryi", 0, "[]"));
+    let queries = || -> Vec<String> {
+        executor
+            .commands()
+            .into_iter()
+            .filter(|cmd| cmd.starts_with("osqueryi"))
+            .collect()
+    };
+
+    let (actor_ref, _rx) = spawn_host(
+        executor.clone(),
+        Arc::new(MockPackageManager::new(&["vim"])),
+    );
+    actor_ref.ask(CollectInventory::default()).await.unwrap();
+    let collected = queries().len();
+
+    // Without an update the next collection is answered from the cache
+    actor_ref.ask(CollectInventory::default()).await.unwrap();
+    assert_eq!(queries().len(), collected);
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    actor_ref.ask(StartUpdate::default()).await.unwrap();
+    executor.clear_commands();
+    actor_ref.ask(CollectInventory::default()).await.unwrap();
+
+    let queries = queries();
+    assert_eq!(queries.len(), 2, "{queries:?}");
+    assert!(queries.iter().any(|q| q.contains("FROM deb_packages")));
+    assert!(queries.iter().any(|q| q.contains("FROM kernel_info")));
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_installs_osquery() {
+    let version = "osqueryi version 5.12.1";
+    let executor = Arc::new(
+        MockExecutor::default()
+            .with_reply(FACTS_PROBE, 0, "docker=false\nosquery=none\n")
+            .with_reply("osqueryi --version", 127, "")
+            .with_reply("osqueryi", 0, "[]"),
+    );
+    let installed = executor.clone();
+    let manager = Arc::new(MockPackageManager::new(&[]).installing_osquery(move || {
+        installed.reply(
+            FACTS_PROBE,
+            0,
+            &format!("docker=false\nosquery={version}\n"),
+        );
+        installed.reply("osqueryi --version", 0, version);
+    }));
+
+    let (actor_ref, _rx) = spawn_host(executor.clone(), manager.clone());
+
+    // The shell backend still answers when osquery isn't needed
+    actor_ref.ask(CollectInventory::default()).await.unwrap();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(
+        status.facts.get(&FactKey::Osquery).map(String::as_str),
+        Some("none")
+    );
+    assert_eq!(status.osquery_version(), None);
+    match actor_ref
+        .ask(CollectInventory {
+            require_osquery: true,
+        })
+        .await
+    {
+        Err(kameo::error::SendError::HandlerError(CoreError::OsqueryNotInstalled {
+            host,
+            installed: None,
+        })) => assert_eq!(host, "test-host"),
+        other => panic!("expected OsqueryNotInstalled, got {other:?}"),
+    }
+
+    let install = actor_ref.ask(InstallOsquery::default()).await.unwrap();
+    assert!(install.installed);
+    assert_eq!(install.version.as_deref(), Some("5.12.1"));
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert_eq!(status.osquery_version(), Some("5.12.1"));
+
+    // A supported version is left alone
+    let again = actor_ref.ask(InstallOsquery::default()).await.unwrap();
+    assert!(!again.installed);
+    assert_eq!(manager.osquery_installs.load(Ordering::SeqCst), 1);
+
+    actor_ref
+        .ask(CollectInventory {
+            require_osquery: true,
+        })
+        .await
+        .unwrap();
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_dry_run_keeps_pending_updates() {
+    let (actor_ref, mut rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim", "curl"])),
+    );
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    let dry_run = StartUpdate {
+        dry_run: true,
+        scope: None,
+        stack: None,
+        ..Default::default()
+    };
+    let result = actor_ref.ask(dry_run).await.unwrap();
+    assert_eq!(result.upgraded_count, 2);
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::PendingUpdates);
+    assert_eq!(status.last_updated, None);
+    assert_eq!(status.pending_updates, Some(2));
+
+    let mut completions = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        if let WsEvent::UpdateCompleted {
+            dry_run, result, ..
+        } = event
+        {
+            completions.push((dry_run, result));
+        }
+    }
+    assert_eq!(completions.len(), 1);
+    assert!(completions[0].0);
+    assert!(completions[0].1.starts_with("would upgrade 2 packages"));
+
+    // The real update can follow without querying again
+    actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert!(status.last_updated.is_some());
+    let mut real = false;
+    while let Ok(event) = rx.try_recv() {
+        real |= matches!(event, WsEvent::UpdateCompleted { dry_run: false, .. });
+    }
+    assert!(real, "the real update is reported as such");
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_remembers_empty_query() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&[])),
+    );
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.pending_updates, None);
+    assert_eq!(status.last_checked, None);
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    // Nothing to update is still a known count, unlike never having asked
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert_eq!(status.pending_updates, Some(0));
+    assert_eq!(status.security_updates, Some(0));
+    assert!(status.last_checked.is_some());
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_cancel_update() {
+    let package_manager =
+        Arc::new(MockPackageManager::new(&["linux-image"]).with_hanging_upgrades());
+
+    let (actor_ref, mut rx) =
+        spawn_host(Arc::new(MockExecutor::default()), package_manager.clone());
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    let update = tokio::spawn({
+        let actor_ref = actor_ref.clone();
+        async move {
+            actor_ref
+                .ask(StartUpdate {
+                    dry_run: false,
+                    scope: None,
+                    stack: None,
+                    ..Default::default()
+                })
+                .await
+        }
+    });
+
+    // The actor keeps answering while the update runs
+    while actor_ref.ask(GetState).await.unwrap() != HostState::Updating {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    actor_ref.ask(CancelUpdate).await.unwrap();
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    assert_eq!(status.error.as_deref(), Some("cancelled by operator"));
+    assert_eq!(status.acknowledged(), Some(false));
+    let failure = status
+        .failure
+        .expect("failed host reports its failure context");
+    assert_eq!(failure.previous_state, HostState::Updating);
+    assert!(!failure.acknowledged);
+    assert_eq!(package_manager.cancel_calls.load(Ordering::SeqCst), 1);
+
+    let result = tokio::time::timeout(Duration::from_secs(1), update)
+        .await
+        .expect("pending update should be answered")
+        .unwrap();
+    assert!(matches!(
+        result,
+        Err(kameo::error::SendError::HandlerError(CoreError::Cancelled(
+            _
+        )))
+    ));
+
+    actor_ref.ask(Acknowledge).await.unwrap();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.acknowledged(), Some(true));
+    assert_eq!(status.retry_count(), Some(0));
+    assert!(status.failed_at().is_some());
+    let mut acknowledged = false;
+    while let Ok(event) = rx.try_recv() {
+        acknowledged |= matches!(&event, WsEvent::HostAcknowledged { host } if host == "test-host");
+    }
+    assert!(acknowledged, "acknowledging emits an event");
+
+    // The usual retry path applies after cancellation
+    actor_ref.ask(Retry).await.unwrap();
+    assert_eq!(actor_ref.ask(GetState).await.unwrap(), HostState::Idle);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_cancel_when_not_updating() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["linux-image"]).with_hanging_upgrades()),
+    );
+
+    let result = actor_ref.ask(CancelUpdate).await;
+    assert!(matches!(
+        result,
+        Err(kameo::error::SendError::HandlerError(
+            CoreError::NotUpdating(_)
+        ))
+    ));
+    assert_eq!(actor_ref.ask(GetState).await.unwrap(), HostState::Idle);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_security_only_update() {
+    let mut config = test_config("test-host");
+    config.policy.default_scope = UpdateScope::SecurityOnly;
+
+    let (actor_ref, _rx) = spawn_host_with(
+        config,
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim", "openssl"]).with_security(&["openssl"])),
+    );
+
+    let inventory = actor_ref.ask(QueryInventory::default()).await.unwrap();
+    assert_eq!(inventory.pending_updates, 2);
+    assert_eq!(inventory.security_updates, 1);
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.security_updates, Some(1));
+
+    // No scope in the request falls back to the host policy
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(result.upgraded_count, 1);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_reachability_probe() {
+    let mut config = test_config("test-host");
+    config.policy.health_check_interval_secs = Some(1);
+    config.policy.unreachable_after = Some(1);
+
+    let executor = Arc::new(MockExecutor::default());
+    executor.set_down(true);
+    let (actor_ref, mut rx) = spawn_host_with(
+        config,
+        executor.clone(),
+        Arc::new(MockPackageManager::new(&[])),
+    );
+
+    // Wait for the first probe to fail
+    let disconnected = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(WsEvent::HostDisconnected { reason, .. }) = rx.recv().await {
+                break reason;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(disconnected.contains("connection refused"));
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(!status.reachable);
+    assert!(status.last_seen.is_none());
+    // Probes never touch the state machine
+    assert_eq!(status.state, HostState::Idle);
+
+    executor.set_down(false);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(WsEvent::HostConnected { .. }) = rx.recv().await {
+                break;
+            }
+        }
+    })
+    .await
+    .unwrap();
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(status.reachable);
+    assert!(status.last_seen.is_some());
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+fn breaker_args(
+    executor: Arc<dyn RemoteExecutor>,
+    event_tx: broadcast::Sender<WsEvent>,
+) -> HostActorArgs {
+    let mut config = test_config("test-host");
+    config.policy.health_check_interval_secs = Some(1);
+    config.policy.circuit_breaker_after = Some(2);
+    config.policy.circuit_breaker_cooldown_secs = Some(1);
+    host_args(
+        config,
+        executor,
+        Arc::new(MockPackageManager::new(&[])),
+        event_tx,
+    )
+}
+
+async fn wait_for_circuit_opened(rx: &mut broadcast::Receiver<WsEvent>) -> u32 {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(WsEvent::CircuitOpened { failures, .. }) = rx.recv().await {
+                break failures;
+            }
+        }
+    })
+    .await
+    .expect("circuit never opened")
+}
+
+#[tokio::test]
+async fn test_host_actor_circuit_breaker_opens_and_recovers() {
+    let (tx, mut rx) = broadcast::channel(100);
+    let actor_ref = HostActor::spawn(breaker_args(
+        Arc::new(MockExecutor::default().refusing(2)),
+        tx,
+    ));
+
+    assert_eq!(wait_for_circuit_opened(&mut rx).await, 2);
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(!status.reachable);
+    assert!(status.circuit_open_until.is_some());
+    // The breaker never touches the state machine
+    assert_eq!(status.state, HostState::Idle);
+
+    // Open: operations fail fast without reaching the host
+    match actor_ref.ask(QueryInventory::default()).await {
+        Err(kameo::error::SendError::HandlerError(CoreError::HostUnreachable { host, .. })) => {
+            assert_eq!(host, "test-host")
+        }
+        other => panic!("expected HostUnreachable, got {other:?}"),
+    }
+
+    // After the cooldown a single half-open probe succeeds and closes it
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(WsEvent::CircuitClosed { .. }) = rx.recv().await {
+                break;
+            }
+        }
+    })
+    .await
+    .expect("circuit never closed");
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(status.reachable);
+    assert!(status.circuit_open_until.is_none());
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_retry_resets_open_circuit() {
+    let (tx, mut rx) = broadcast::channel(100);
+    let actor_ref = HostActor::spawn(breaker_args(
+        Arc::new(MockExecutor::default().refusing(usize::MAX)),
+        tx,
+    ));
+
+    wait_for_circuit_opened(&mut rx).await;
+    assert!(
+        actor_ref
+            .ask(StartUpdate {
+                dry_run: false,
+                scope: None,
+                stack: None,
+                ..Default::default()
+            })
+            .await
+            .is_err()
+    );
+
+    // A manual retry closes the breaker even though the host never failed
+    actor_ref.ask(Retry).await.unwrap();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(status.circuit_open_until.is_none());
+    assert_eq!(status.state, HostState::Idle);
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_upgrade_timeout_fails_host() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(timing_out_manager()),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await;
+    assert!(result.is_err());
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    assert_eq!(status.error.as_deref(), Some("upgrade timed out after 30m"));
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_waits_for_package_manager_lock() {
+    let mut config = test_config("test-host");
+    config.policy.lock_wait_secs = Some(1);
+    let manager = Arc::new(locked_manager());
+    let (actor_ref, mut rx) =
+        spawn_host_with(config, Arc::new(MockExecutor::default()), manager.clone());
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref.ask(StartUpdate::default()).await;
+    assert!(result.is_err());
+
+    // Tried again once the first wait was over, then gave up
+    assert_eq!(manager.upgrades(), 2);
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    let error = status.error.unwrap();
+    assert!(
+        error.contains("still held by process 4211 (unattended-upgr) after waiting 1s"),
+        "{error}"
+    );
+
+    let mut waits = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        if let WsEvent::WaitingForLock { holder, .. } = event {
+            waits.push(holder);
+        }
+    }
+    assert_eq!(waits, [Some("process 4211 (unattended-upgr)".to_string())]);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_cleans_up_after_update() {
+    let manager = Arc::new(
+        MockPackageManager::new(&["linux-image-amd64"])
+            .with_autoremovable(&["linux-image-6.1.0-18-amd64"]),
+    );
+
+    let mut config = test_config("test-host");
+    config.policy.autoremove = true;
+    config.policy.clean_cache = true;
+    let (actor_ref, _rx) =
+        spawn_host_with(config, Arc::new(MockExecutor::default()), manager.clone());
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref.ask(StartUpdate::default()).await.unwrap();
+    assert!(result.success);
+    assert!(
+        !result.warnings.iter().any(|w| w.contains("autoremove")),
+        "{:?}",
+        result.warnings
+    );
+    assert_eq!(manager.autoremove_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(manager.clean_calls.load(Ordering::SeqCst), 1);
+
+    let history = actor_ref
+        .ask(GetUpdateHistory { limit: None })
+        .await
+        .unwrap();
+    assert_eq!(
+        history[0].autoremoved_packages,
+        vec!["linux-image-6.1.0-18-amd64".to_string()]
+    );
+
+    // Dry runs and requests that skip cleanup leave the host as it is
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    for request in [
+        StartUpdate {
+            dry_run: true,
+            ..Default::default()
+        },
+        StartUpdate {
+            skip_cleanup: true,
+            ..Default::default()
+        },
+    ] {
+        actor_ref.ask(request).await.unwrap();
+    }
+    assert_eq!(manager.autoremove_calls.load(Ordering::SeqCst), 1);
+    assert_eq!(manager.clean_calls.load(Ordering::SeqCst), 1);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_keeps_failed_command_output() {
+    let mut config = test_config("test-host");
+    config.policy.failure_output_lines = Some(20);
+    let (actor_ref, _rx) = spawn_host_with(
+        config,
+        Arc::new(MockExecutor::default()),
+        Arc::new(broken_upgrade_manager()),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await;
+    assert!(result.is_err());
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    let output = status.failure.and_then(|f| f.output).unwrap();
+    assert_eq!(output.lines().count(), 20);
+    assert!(output.starts_with("Unpacking pkg-182\n"));
+    assert!(output.ends_with("returned an error code (1)"));
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_checks_free_disk_space_before_update() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(full_boot_executor()),
+        Arc::new(MockPackageManager::new(&["linux-image-amd64"]).with_reboot_required()),
+    );
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    // A dry run warns about the shortfall and still simulates the update
+    let dry_run = actor_ref
+        .ask(StartUpdate {
+            dry_run: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(dry_run.upgraded_count, 1);
+    assert_eq!(
+        dry_run.warnings,
+        ["insufficient disk space on /boot: 120 MB available, 500 MB required"]
+    );
+
+    // The real update is refused before anything runs
+    let err = actor_ref
+        .ask(StartUpdate::default())
+        .await
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("insufficient disk space on /boot"),
+        "unexpected error: {err}"
+    );
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    let failure = status.failure.unwrap();
+    assert_eq!(failure.kind, FailureKind::PackageOperation);
+    assert_eq!(
+        failure.output.unwrap(),
+        "/: 20.0 GB available, 1.0 GB required\n\
+         /boot: 120 MB available, 500 MB required\n\
+         /var (on /): 20.0 GB available, 1.0 GB required"
+    );
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_preview_changes_nothing() {
+    let (actor_ref, mut rx) = spawn_host(
+        Arc::new(full_boot_executor()),
+        Arc::new(MockPackageManager::new(&["curl", "linux-image-amd64"])),
+    );
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    while rx.try_recv().is_ok() {}
+    let transitions = actor_ref.ask(GetTransitionHistory).await.unwrap().len();
+
+    let preview = actor_ref.ask(PreviewUpdate::default()).await.unwrap();
+    assert_eq!(preview.host, "test-host");
+    assert_eq!(preview.scope, UpdateScope::All);
+    assert_eq!(preview.packages, ["curl", "linux-image-amd64"]);
+    assert_eq!(preview.upgraded_count, 2);
+    assert!(preview.reboot_likely, "a kernel upgrade needs a reboot");
+    // Too little space on /boot would refuse the real update
+    assert_eq!(
+        preview.blockers,
+        ["insufficient disk space on /boot: 120 MB available, 500 MB required"]
+    );
+
+    // Still pending, with nothing recorded or announced
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::PendingUpdates);
+    assert_eq!(status.pending_updates, Some(2));
+    assert_eq!(status.last_updated, None);
+    assert_eq!(
+        actor_ref.ask(GetTransitionHistory).await.unwrap().len(),
+        transitions
+    );
+    assert!(
+        actor_ref
+            .ask(GetUpdateHistory { limit: None })
+            .await
+            .unwrap()
+            .is_empty()
+    );
+    assert!(
+        matches!(rx.try_recv(), Err(broadcast::error::TryRecvError::Empty)),
+        "a preview emits no events"
+    );
+
+    // A paused host is previewed, with the pause as a blocker
+    actor_ref.tell(SetPaused { paused: true }).await.unwrap();
+    let preview = actor_ref.ask(PreviewUpdate::default()).await.unwrap();
+    assert!(preview.is_blocked());
+    assert!(
+        preview.blockers[0].starts_with("host is paused"),
+        "{:?}",
+        preview.blockers
+    );
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_preview_refused_while_updating() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["linux-image"]).with_hanging_upgrades()),
+    );
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    actor_ref.tell(StartUpdate::default()).await.unwrap();
+    while actor_ref.ask(GetState).await.unwrap() != HostState::Updating {
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    let result = actor_ref.ask(PreviewUpdate::default()).await;
+    assert!(matches!(
+        result,
+        Err(kameo::error::SendError::HandlerError(CoreError::HostBusy(
+            _
+        )))
+    ));
+    assert_eq!(actor_ref.ask(GetState).await.unwrap(), HostState::Updating);
+
+    actor_ref.ask(CancelUpdate).await.unwrap();
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_single_stack_update() {
+    let (tx, _rx) = broadcast::channel(100);
+
+    let mut args = host_args(
+        test_config("test-host"),
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim"]).with_reboot_required()),
+        tx,
+    );
+    args.compose_manager = Some(Arc::new(
+        MockPackageManager::new(&[])
+            .with_stacks(&["monitoring", "media", "broken"])
+            .with_broken_stack(
+                "broken",
+                "/opt/stacks/broken: up failed: port 443 is already allocated",
+            ),
+    ));
+
+    let actor_ref = HostActor::spawn(args);
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    // Unknown stacks are rejected without leaving PendingUpdates
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: Some("nextcloud".to_string()),
+            ..Default::default()
+        })
+        .await;
+    match result {
+        Err(kameo::error::SendError::HandlerError(CoreError::ConfigError(msg))) => {
+            assert!(msg.contains("monitoring, media"), "{msg}");
+        }
+        other => panic!("expected config error, got {other:?}"),
+    }
+    assert_eq!(
+        actor_ref.ask(GetState).await.unwrap(),
+        HostState::PendingUpdates
+    );
+
+    // Only the stack is updated, so the package manager's reboot flag is ignored
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: Some("monitoring".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(result.upgraded_count, 1);
+    assert!(!result.reboot_required);
+
+    // A stack that fails to come up fails the host with compose's error
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref
+        .ask(StartUpdate {
+            stack: Some("broken".to_string()),
+            ..Default::default()
+        })
+        .await;
+    assert!(result.is_err(), "{result:?}");
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    assert_eq!(
+        status.error.as_deref(),
+        Some("/opt/stacks/broken: up failed: port 443 is already allocated")
+    );
+    assert_eq!(
+        status.failure.map(|f| f.kind),
+        Some(FailureKind::PackageOperation)
+    );
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+fn hook_config(pre: &[&str], post: &[&str]) -> HostConfig {
+    let mut config = test_config("test-host");
+    config.policy.pre_update_hooks = pre.iter().map(ToString::to_string).collect();
+    config.policy.post_update_hooks = post.iter().map(ToString::to_string).collect();
+    // Only the hooks should run
+    config.policy.min_free_space_mb = Some(Default::default());
+    config
+}
+
+#[tokio::test]
+async fn test_host_actor_runs_update_hooks_in_order() {
+    let executor = Arc::new(hook_executor());
+
+    let (actor_ref, mut rx) = spawn_host_with(
+        hook_config(&["lb drain web", "lb wait web"], &["lb enable web"]),
+        executor.clone(),
+        Arc::new(MockPackageManager::new(&["vim"])),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    executor.clear_commands();
+    actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        executor.commands(),
+        vec!["lb drain web", "lb wait web", "lb enable web"]
+    );
+    assert_eq!(actor_ref.ask(GetState).await.unwrap(), HostState::Idle);
+
+    let mut hooks = Vec::new();
+    while let Ok(event) = rx.try_recv() {
+        if let WsEvent::HookExecuted { hook, success, .. } = event {
+            hooks.push((hook, success));
+        }
+    }
+    assert_eq!(hooks.len(), 3);
+    assert!(hooks.iter().all(|(_, success)| *success));
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_failing_pre_hook_aborts_update() {
+    let executor = Arc::new(hook_executor());
+
+    let (actor_ref, _rx) = spawn_host_with(
+        hook_config(&["lb drain --fail web", "never runs"], &["lb enable web"]),
+        executor.clone(),
+        Arc::new(MockPackageManager::new(&["vim"])),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    executor.clear_commands();
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await;
+
+    match result {
+        Err(kameo::error::SendError::HandlerError(CoreError::HookFailed {
+            stage, hook, ..
+        })) => {
+            assert_eq!(stage, "pre-update");
+            assert_eq!(hook, "lb drain --fail web");
+        }
+        other => panic!("expected hook failure, got {other:?}"),
+    }
+
+    // Post-update hooks still run by default
+    assert_eq!(
+        executor.commands(),
+        vec!["lb drain --fail web", "lb enable web"]
+    );
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    assert_eq!(
+        status.error.as_deref(),
+        Some("pre-update hook 'lb drain --fail web' failed: backend still has connections")
+    );
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_failing_post_hook_is_a_warning() {
+    let executor = Arc::new(hook_executor());
+
+    let (actor_ref, _rx) = spawn_host_with(
+        hook_config(&[], &["lb enable --fail web"]),
+        executor.clone(),
+        Arc::new(MockPackageManager::new(&["vim"])),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref.ask(StartUpdate::default()).await.unwrap();
+
+    // The packages were upgraded, so the update still succeeded
+    assert!(result.success);
+    assert_eq!(result.upgraded_count, 1);
+    assert_eq!(
+        result.warnings,
+        ["post-update hook 'lb enable --fail web' failed: backend still has connections"]
+    );
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert!(status.failure.is_none());
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+fn auto_retry_config(max_retries: u32) -> HostConfig {
+    let mut config = test_config("test-host");
+    config.policy.auto_retry = AutoRetryPolicy {
+        enabled: Some(true),
+        max_retries: Some(max_retries),
+        initial_backoff_secs: Some(0),
+        ..Default::default()
+    };
+    config
+}
+
+/// Poll until the host reaches `state`
+async fn wait_for_state(actor_ref: &ActorRef<HostActor>, state: HostState) {
+    for _ in 0..100 {
+        if actor_ref.ask(GetState).await.unwrap() == state {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("host never reached {state}");
+}
+
+#[tokio::test]
+async fn test_host_actor_auto_retry_recovers_transient_failure() {
+    let manager = Arc::new(
+        MockPackageManager::new(&["openssl"]).failing_upgrades_times(connection_reset(), 1),
+    );
+
+    let (actor_ref, mut rx) = spawn_host_with(
+        auto_retry_config(3),
+        Arc::new(MockExecutor::default()),
+        manager.clone(),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await;
+    assert!(result.is_err());
+
+    wait_for_state(&actor_ref, HostState::Idle).await;
+    assert_eq!(manager.upgrade_calls.load(Ordering::SeqCst), 2);
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(status.failure.is_none());
+    assert!(status.last_updated.is_some());
+
+    let mut scheduled = false;
+    let mut started = false;
+    while let Ok(event) = rx.try_recv() {
+        match event {
+            WsEvent::RetryScheduled {
+                attempt: 1,
+                max_retries: 3,
+                ..
+            } => scheduled = true,
+            WsEvent::RetryStarted { attempt: 1, .. } => started = true,
+            _ => {}
+        }
+    }
+    assert!(scheduled && started);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_auto_retry_exhausted_then_manual_retry() {
+    let manager =
+        Arc::new(MockPackageManager::new(&["openssl"]).failing_upgrades(connection_reset()));
+
+    let (actor_ref, mut rx) = spawn_host_with(
+        auto_retry_config(1),
+        Arc::new(MockExecutor::default()),
+        manager.clone(),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let _ = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await;
+
+    // The single retry fails too, after which the host stays failed
+    let mut exhausted = false;
+    let mut failed_kinds = Vec::new();
+    while !exhausted {
+        let event = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .expect("retries never exhausted")
+            .unwrap();
+        if let WsEvent::HostStateChanged {
+            to, failure_kind, ..
+        } = &event
+            && to == "failed"
+        {
+            failed_kinds.push(failure_kind.clone());
+        }
+        exhausted = matches!(event, WsEvent::RetriesExhausted { attempts: 1, .. });
+    }
+    assert_eq!(manager.upgrade_calls.load(Ordering::SeqCst), 2);
+    // Both failures are announced with their kind
+    assert_eq!(
+        failed_kinds,
+        [
+            Some("connection".to_string()),
+            Some("connection".to_string())
+        ]
+    );
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    let failure = status.failure.unwrap();
+    assert_eq!(failure.kind, FailureKind::Connection);
+    assert_eq!(failure.retry_count, 1);
+    assert_eq!(failure.attempts.len(), 1);
+    assert!(failure.attempts[0].error.contains("connection reset"));
+    assert!(failure.next_retry_at.is_none());
+
+    // Manual retry still recovers the host and clears the history
+    actor_ref.ask(Retry).await.unwrap();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert!(status.failure.is_none());
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_records_transitions_across_retries() {
+    let (actor_ref, mut rx) = spawn_host_with(
+        auto_retry_config(1),
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["openssl"]).failing_upgrades(connection_reset())),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let _ = actor_ref.ask(StartUpdate::default()).await;
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(2), rx.recv())
+            .await
+            .expect("retries never exhausted")
+            .unwrap();
+        if matches!(event, WsEvent::RetriesExhausted { .. }) {
+            break;
+        }
+    }
+    actor_ref.ask(Retry).await.unwrap();
+
+    let history = actor_ref.ask(GetTransitionHistory).await.unwrap();
+    let steps: Vec<_> = history
+        .iter()
+        .map(|t| (t.from, t.to, t.trigger.as_str(), t.initiator.as_deref()))
+        .collect();
+    let manual = Some("manual request");
+    let auto = Some("automatic retry");
+    use HostState::*;
+    assert_eq!(
+        steps,
+        [
+            (Idle, Querying, "QueryInventory", manual),
+            (Querying, PendingUpdates, "QueryInventory", manual),
+            (PendingUpdates, Updating, "StartUpdate", manual),
+            (Updating, Failed, "UpdateFinished", manual),
+            (Failed, Idle, "AutoRetry", None),
+            (Idle, Querying, "AutoRetry", auto),
+            (Querying, PendingUpdates, "AutoRetry", auto),
+            (PendingUpdates, Updating, "AutoRetry", auto),
+            (Updating, Failed, "UpdateFinished", auto),
+            (Failed, Idle, "Retry", None),
+        ]
+    );
+    assert!(
+        history[3]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("connection reset")
+    );
+    assert!(
+        history
+            .iter()
+            .filter(|t| t.to != Failed)
+            .all(|t| t.error.is_none())
+    );
+    assert!(history.windows(2).all(|w| w[0].at <= w[1].at));
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.recent_transitions, history[history.len() - 5..]);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_bounds_transition_history() {
+    let (tx, _rx) = broadcast::channel(300);
+    let actor_ref = HostActor::spawn(host_args(
+        test_config("test-host"),
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&[])),
+        tx,
+    ));
+
+    // Each query goes to Querying and back
+    for _ in 0..60 {
+        actor_ref.ask(QueryInventory::default()).await.unwrap();
+    }
+
+    let history = actor_ref.ask(GetTransitionHistory).await.unwrap();
+    assert_eq!(history.len(), 100);
+    assert_eq!(history[0].from, HostState::Idle);
+    assert_eq!(history[0].to, HostState::Querying);
+    assert_eq!(history[99].to, HostState::Idle);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_records_command_history() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+
+    let config = hook_config(&["DEPLOY_TOKEN=abc123 notify start"], &["notify done"]);
+    orchestrator.ask(RegisterHost { config }).await.unwrap();
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "test-host".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+    orchestrator
+        .ask(TriggerHostUpdate {
+            hostname: "test-host".into(),
+            dry_run: false,
+            scope: None,
+            stack: None,
+            packages: Vec::new(),
+            force: false,
+            skip_cleanup: false,
+        })
+        .await
+        .unwrap();
+
+    let history = orchestrator
+        .ask(GetHostCommandHistory {
+            hostname: "test-host".into(),
+        })
+        .await
+        .unwrap();
+    let commands: Vec<&str> = history.iter().map(|r| r.command.as_str()).collect();
+    assert!(commands.contains(&"DEPLOY_TOKEN=*** notify start"));
+    assert!(commands.contains(&"notify done"));
+    assert!(history.iter().all(|r| r.status == Some(0)));
+
+    // The same commands are counted in the executor statistics
+    let stats = orchestrator
+        .ask(GetHostExecutorStats {
+            hostname: "test-host".into(),
+        })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(stats.commands >= history.len() as u64);
+    assert_eq!(stats.failures, 0);
+    assert_eq!(stats.consecutive_connection_failures, 0);
+    assert!(stats.p95 >= stats.p50);
+    assert_eq!(stats.last_connect, None);
+
+    let missing = orchestrator
+        .ask(GetHostCommandHistory {
+            hostname: "missing".into(),
+        })
+        .await;
+    assert!(missing.is_err());
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_respawns_crashed_host_actor() {
+    let factory = Arc::new(CrashingHostFactory::default());
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: factory.clone(),
+        restart_policy: RestartPolicy {
+            backoff: Duration::from_millis(10),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let mut events = orchestrator.ask(SubscribeEvents).await.unwrap();
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("web-1"),
+        })
+        .await
+        .unwrap();
+
+    factory.panics.store(1, Ordering::SeqCst);
+    let query = QueryHostInventory {
+        hostname: "web-1".into(),
+        refresh: false,
+    };
+    assert!(orchestrator.ask(query).await.is_err());
+
+    let reason = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Ok(WsEvent::HostDisconnected { reason, .. }) = events.recv().await
+                && reason.starts_with("host actor crashed")
+            {
+                break reason;
+            }
+        }
+    })
+    .await
+    .expect("crash should be announced");
+    assert!(reason.contains("restarting in"), "{reason}");
+
+    // Back from its stored config after the backoff
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let query = QueryHostInventory {
+                hostname: "web-1".into(),
+                refresh: false,
+            };
+            if orchestrator.ask(query).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("host actor should be respawned");
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "web-1".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(status.tags, vec!["test".to_string()]);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_leaves_host_crashed_until_retried() {
+    let factory = Arc::new(CrashingHostFactory::default());
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: factory.clone(),
+        restart_policy: RestartPolicy {
+            max_restarts: 0,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("web-1"),
+        })
+        .await
+        .unwrap();
+
+    factory.panics.store(1, Ordering::SeqCst);
+    let query = QueryHostInventory {
+        hostname: "web-1".into(),
+        refresh: false,
+    };
+    assert!(orchestrator.ask(query).await.is_err());
+
+    let result = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let result = orchestrator
+                .ask(GetHostStatus {
+                    hostname: "web-1".into(),
+                })
+                .await;
+            if !matches!(
+                result,
+                Err(kameo::error::SendError::HandlerError(
+                    CoreError::ActorError(_)
+                ))
+            ) {
+                break result;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    let Err(kameo::error::SendError::HandlerError(CoreError::HostCrashed { host, .. })) = result
+    else {
+        panic!("expected crashed host, got {result:?}");
+    };
+    assert_eq!(host, "web-1");
+
+    // Still listed, as failed
+    let hosts = orchestrator.ask(ListHosts).await.unwrap();
+    assert_eq!(hosts.len(), 1);
+    assert_eq!(hosts[0].state, HostState::Failed);
+    assert!(
+        hosts[0]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("retry to respawn")
+    );
+
+    orchestrator
+        .ask(RetryHost {
+            hostname: "web-1".into(),
+        })
+        .await
+        .unwrap();
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "web-1".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(status.state, HostState::Idle);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_rejects_invalid_host_config() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+
+    let mut config = test_config("bad/name");
+    config.addr = String::new();
+    let result = orchestrator.ask(RegisterHost { config }).await;
+    let Err(kameo::error::SendError::HandlerError(CoreError::InvalidHostConfig(errors))) = result
+    else {
+        panic!("expected validation error, got {result:?}");
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(orchestrator.ask(ListHosts).await.unwrap().is_empty());
+
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("web-1"),
+        })
+        .await
+        .unwrap();
+    let result = orchestrator
+        .ask(UpdateHostConfig {
+            hostname: "web-1".into(),
+            patch: HostConfigPatch {
+                tags: Some(vec![String::new()]),
+                ..Default::default()
+            },
+        })
+        .await;
+    assert!(matches!(
+        result,
+        Err(kameo::error::SendError::HandlerError(
+            CoreError::InvalidHostConfig(_)
+        ))
+    ));
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_registers_hosts_in_bulk() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("web-1"),
+        })
+        .await
+        .unwrap();
+
+    let result = orchestrator
+        .ask(RegisterHosts {
+            configs: vec![
+                test_config("db-1"),
+                test_config("web-2"),
+                test_config("db-1"),
+            ],
+        })
+        .await;
+    let Err(kameo::error::SendError::HandlerError(CoreError::ConfigError(message))) = result else {
+        panic!("expected duplicate error, got {result:?}");
+    };
+    assert!(message.contains("db-1"));
+    assert_eq!(orchestrator.ask(ListHosts).await.unwrap().len(), 1);
+
+    let mut invalid = test_config("bad/name");
+    invalid.addr = String::new();
+    let report = orchestrator
+        .ask(RegisterHosts {
+            configs: vec![
+                test_config("web-2"),
+                test_config("web-1"),
+                invalid,
+                test_config("db-1"),
+            ],
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(
+        (report.created, report.already_exists, report.failed),
+        (2, 1, 1)
+    );
+    let statuses: Vec<_> = report
+        .results
+        .iter()
+        .map(|r| (r.name.as_str(), r.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("web-2", RegistrationStatus::Created),
+            ("web-1", RegistrationStatus::AlreadyExists),
+            ("bad/name", RegistrationStatus::Error),
+            ("db-1", RegistrationStatus::Created),
+        ]
+    );
+    assert!(report.results[2].error.is_some());
+
+    let mut hosts: Vec<_> = orchestrator
+        .ask(ListHosts)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|h| h.name)
+        .collect();
+    hosts.sort();
+    assert_eq!(hosts, ["db-1", "web-1", "web-2"]);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_prepares_bulk_hosts_concurrently() {
+    let delay = Duration::from_millis(100);
+    let factory = Arc::new(ConnectingHostFactory {
+        delay,
+        connecting: AtomicUsize::new(0),
+        most_connecting: AtomicUsize::new(0),
+    });
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: factory.clone(),
+        audit_log: None,
+        registration_concurrency: 4,
+        ..Default::default()
+    });
+
+    let configs: Vec<_> = (0..16).map(|i| test_config(&format!("web-{i}"))).collect();
+    let started = std::time::Instant::now();
+    let registration = tokio::spawn({
+        let orchestrator = orchestrator.clone();
+        async move { orchestrator.ask(RegisterHosts { configs }).await }
+    });
+
+    // The orchestrator keeps answering while hosts connect
+    tokio::time::sleep(delay + delay / 2).await;
+    let partial = orchestrator.ask(ListHosts).await.unwrap().len();
+    assert!(partial > 0 && partial < 16, "{partial} hosts registered");
+
+    let report = registration.await.unwrap().unwrap();
+    let elapsed = started.elapsed();
+    assert_eq!(report.created, 16);
+    assert_eq!(factory.most_connecting.load(Ordering::SeqCst), 4);
+    // Four rounds of four, not sixteen one after the other
+    assert!(elapsed < delay * 8, "took {elapsed:?}");
+    assert_eq!(orchestrator.ask(ListHosts).await.unwrap().len(), 16);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_fleet_summary_follows_host_events() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+    for name in ["web-1", "web-2", "web-3"] {
+        orchestrator
+            .ask(RegisterHost {
+                config: test_config(name),
+            })
+            .await
+            .unwrap();
+    }
+
+    let summary = orchestrator.ask(GetFleetSummary).await.unwrap();
+    assert_eq!(summary.total_hosts, 3);
+    assert_eq!(summary.count("idle"), 3);
+    assert_eq!(summary.pending_updates, 0);
+    assert!(summary.failed_hosts.is_empty());
+    assert!(summary.oldest_last_updated.is_none());
+
+    // The cached idle status is replaced once the state change event lands
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "web-2".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+    let mut summary = orchestrator.ask(GetFleetSummary).await.unwrap();
+    for _ in 0..50 {
+        if summary.pending_updates > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        summary = orchestrator.ask(GetFleetSummary).await.unwrap();
+    }
+    assert_eq!(summary.count("idle"), 2);
+    assert_eq!(summary.count("pending_updates"), 1);
+    assert_eq!(summary.pending_updates, 2);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_service_restarts_do_not_wait_for_reboot() {
+    for auto_restart in [false, true] {
+        let executor = Arc::new(hook_executor());
+        let mut config = test_config("test-host");
+        config.policy.auto_restart_services = auto_restart;
+
+        let (actor_ref, _rx) = spawn_host_with(
+            config,
+            executor.clone(),
+            Arc::new(
+                MockPackageManager::new(&["openssl"])
+                    .with_services_to_restart(&["nginx.service", "x;reboot"])
+                    .with_escalation(PrivilegeEscalation::Sudo),
+            ),
+        );
+        actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+        let result = actor_ref
+            .ask(StartUpdate {
+                dry_run: false,
+                scope: None,
+                stack: None,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert!(!result.reboot_required);
+        assert_eq!(result.restart.services_needing_restart.len(), 2);
+
+        // Only a kernel-level requirement leads to WaitingReboot
+        let status = actor_ref.ask(GetStatus).await.unwrap();
+        assert_eq!(status.state, HostState::Idle);
+        let needs_restart = status.needs_restart.unwrap();
+        assert_eq!(needs_restart.triggered_by, ["openssl"]);
+
+        let restart_commands: Vec<_> = executor
+            .commands()
+            .into_iter()
+            .filter(|cmd| cmd.contains("systemctl"))
+            .collect();
+        if auto_restart {
+            // The unit name that is unsafe for the shell is never run
+            assert_eq!(
+                restart_commands,
+                ["sudo -n systemctl restart nginx.service"]
+            );
+            assert_eq!(result.restarted_services, ["nginx.service"]);
+            assert_eq!(needs_restart.services_needing_restart, ["x;reboot"]);
+        } else {
+            assert!(restart_commands.is_empty());
+            assert!(result.restarted_services.is_empty());
+            assert_eq!(needs_restart.services_needing_restart.len(), 2);
+        }
+
+        actor_ref.stop_gracefully().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_host_actor_fails_fast_without_passwordless_escalation() {
+    for (escalation, tool) in [
+        (PrivilegeEscalation::Sudo, "sudo"),
+        (PrivilegeEscalation::Doas, "doas"),
+    ] {
+        let executor = Arc::new(MockExecutor::default().with_reply(
+            &format!("{tool} -n "),
+            1,
+            &format!("{tool}: a password is required\n"),
+        ));
+        let (actor_ref, _rx) = spawn_host(
+            executor.clone(),
+            Arc::new(MockPackageManager::new(&["curl"]).with_escalation(escalation)),
+        );
+        actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+        let update = StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        };
+        let err = actor_ref.ask(update.clone()).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains(&format!("passwordless {tool} not available for user root")),
+            "unexpected error: {err}"
+        );
+        let status = actor_ref.ask(GetStatus).await.unwrap();
+        assert_eq!(status.state, HostState::Failed);
+        assert_eq!(status.sudo_available, Some(false));
+
+        // Retrying re-checks, so a fixed sudoers or doas.conf takes effect
+        executor.reply(&format!("{tool} -n "), 0, "ok");
+        actor_ref.ask(Retry).await.unwrap();
+        for _ in 0..100 {
+            if actor_ref.ask(GetStatus).await.unwrap().sudo_available == Some(true) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(
+            actor_ref.ask(GetStatus).await.unwrap().sudo_available,
+            Some(true)
+        );
+
+        actor_ref.ask(QueryInventory::default()).await.unwrap();
+        let result = actor_ref.ask(update).await.unwrap();
+        assert_eq!(result.upgraded_count, 1);
+
+        actor_ref.stop_gracefully().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_host_actor_reboots_through_escalation() {
+    for (escalation, reboot) in [
+        (PrivilegeEscalation::None, "reboot"),
+        (PrivilegeEscalation::Doas, "doas -n reboot"),
+        (
+            PrivilegeEscalation::Custom("pfexec".to_string()),
+            "pfexec reboot",
+        ),
+    ] {
+        let executor = Arc::new(hook_executor());
+        let mut config = test_config("test-host");
+        config.policy.auto_reboot = true;
+        config.policy.min_free_space_mb = Some(Default::default());
+        let (actor_ref, _rx) = spawn_host_with(
+            config,
+            executor.clone(),
+            Arc::new(
+                MockPackageManager::new(&["curl"])
+                    .with_escalation(escalation)
+                    .with_reboot_required(),
+            ),
+        );
+
+        actor_ref.ask(QueryInventory::default()).await.unwrap();
+        actor_ref.ask(StartUpdate::default()).await.unwrap();
+        assert!(actor_ref.ask(RebootIfRequired).await.unwrap());
+
+        let commands = executor.commands();
+        assert!(commands.iter().any(|cmd| cmd == reboot), "{commands:?}");
+        assert!(
+            !commands.iter().any(|cmd| cmd.contains("sudo")),
+            "{commands:?}"
+        );
+
+        actor_ref.stop_gracefully().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn test_host_actor_keeps_bounded_update_history() {
+    let (tx, _rx) = broadcast::channel(100);
+
+    let mut config = test_config("test-host");
+    config.policy.update_history_size = Some(2);
+    let actor_ref = HostActor::spawn(host_args(
+        config,
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim"])),
+        tx.clone(),
+    ));
+
+    let mut pending = false;
+    for dry_run in [false, true, false] {
+        // A dry run leaves the host in PendingUpdates, ready for the update
+        if !pending {
+            actor_ref.ask(QueryInventory::default()).await.unwrap();
+        }
+        pending = dry_run;
+        actor_ref
+            .ask(StartUpdate {
+                dry_run,
+                scope: None,
+                stack: None,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    let history = actor_ref
+        .ask(GetUpdateHistory { limit: None })
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 2);
+    assert!(!history[0].dry_run);
+    assert!(history[1].dry_run);
+    assert!(history[0].success);
+    assert_eq!(history[0].upgraded_count, 1);
+    assert_eq!(history[0].scope, UpdateScope::All);
+
+    let latest = actor_ref
+        .ask(GetUpdateHistory { limit: Some(1) })
+        .await
+        .unwrap();
+    assert_eq!(latest.len(), 1);
+    actor_ref.stop_gracefully().await.unwrap();
+
+    // Failed updates are recorded with their error
+    let actor_ref = HostActor::spawn(host_args(
+        test_config("failing-host"),
+        Arc::new(MockExecutor::default()),
+        Arc::new(timing_out_manager()),
+        tx,
+    ));
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let result = actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await;
+    assert!(result.is_err());
+
+    let history = actor_ref
+        .ask(GetUpdateHistory { limit: None })
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    assert!(!history[0].success);
+    assert_eq!(
+        history[0].error.as_deref(),
+        Some("upgrade timed out after 30m")
+    );
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_fleet_dry_run_aggregates_hosts() {
+    let args = OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    };
+    let orchestrator = OrchestratorActor::spawn(args);
+
+    for name in ["web-2", "web-1", "db"] {
+        let mut config = test_config(name);
+        if name == "db" {
+            config.tags = vec!["database".to_string()];
+        }
+        orchestrator.ask(RegisterHost { config }).await.unwrap();
+    }
+
+    let report = orchestrator
+        .ask(FleetDryRun {
+            config: FleetUpdateConfig {
+                filter: Some(FleetFilter {
+                    tags: vec!["test".to_string()],
+                    groups: vec![],
+                    exclude_hosts: vec![],
+                    fact_filters: vec![],
+                }),
+                dry_run: true,
+                ..FleetUpdateConfig::default()
+            },
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(report.total_hosts, 2);
+    assert_eq!(report.hosts_with_updates, 2);
+    assert_eq!(report.failed_hosts, 0);
+    assert_eq!(report.total_updates, 4);
+    let hosts: Vec<_> = report.hosts.iter().map(|h| h.host.as_str()).collect();
+    assert_eq!(hosts, ["web-1", "web-2"]);
+    let packages: Vec<_> = report
+        .packages
+        .iter()
+        .map(|p| (p.name.as_str(), p.host_count))
+        .collect();
+    assert_eq!(packages, [("curl", 2), ("vim", 2)]);
+
+    // Nothing was actually updated
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "web-1".into(),
+        })
+        .await
+        .unwrap();
+    assert!(status.last_updated.is_none());
+    assert_eq!(status.pending_updates, Some(2));
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_failed_health_check_after_reboot() {
+    let executor = Arc::new(hook_executor());
+    let mut config = test_config("test-host");
+    config.policy.auto_reboot = true;
+    config.policy.health_checks = vec![
+        HealthCheckSpec {
+            command: "docker ps".to_string(),
+            expected_exit_code: None,
+            expected_output: None,
+            timeout_secs: None,
+        },
+        HealthCheckSpec {
+            command: "curl -f localhost || fail".to_string(),
+            expected_exit_code: None,
+            expected_output: None,
+            timeout_secs: Some(5),
+        },
+    ];
+    let (actor_ref, _rx) = spawn_host_with(
+        config,
+        executor.clone(),
+        Arc::new(MockPackageManager::new(&["linux-image-amd64"]).with_reboot_required()),
+    );
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(
+        actor_ref.ask(GetState).await.unwrap(),
+        HostState::WaitingReboot
+    );
+    assert!(actor_ref.ask(RebootIfRequired).await.unwrap());
+    assert_eq!(actor_ref.ask(GetState).await.unwrap(), HostState::Verifying);
+
+    let result = actor_ref.ask(HealthCheck).await.unwrap();
+    assert!(!result.healthy);
+    let outcomes: Vec<_> = result
+        .checks
+        .iter()
+        .map(|c| (c.command.as_str(), c.passed, c.exit_code))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("docker ps", true, Some(0)),
+            ("curl -f localhost || fail", false, Some(1))
+        ]
+    );
+
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    assert_eq!(
+        status.error.as_deref(),
+        Some(
+            "health check 'curl -f localhost || fail' failed after reboot: \
+             exited with status 1, expected 0"
+        )
+    );
+    assert_eq!(status.last_health_check.unwrap().checks.len(), 2);
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+/// Factory whose host named "canary" fails every upgrade
+struct BrokenCanaryFactory;
+
+#[async_trait]
+impl HostActorFactory for BrokenCanaryFactory {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        Ok(Arc::new(MockExecutor::default()))
+    }
+
+    async fn create_package_manager(
+        &self,
+        config: &HostConfig,
+        executor: Arc<dyn RemoteExecutor>,
+    ) -> Arc<dyn PackageManager> {
+        if config.name == "canary" {
+            Arc::new(timing_out_manager())
+        } else {
+            TestHostFactory
+                .create_package_manager(config, executor)
+                .await
+        }
+    }
+}
+
+async fn canary_fleet(
+    halt_on_canary_failure: bool,
+) -> (ActorRef<OrchestratorActor>, FleetUpdateProgress) {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(BrokenCanaryFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+    for name in ["web-2", "canary", "web-1"] {
+        orchestrator
+            .ask(RegisterHost {
+                config: test_config(name),
+            })
+            .await
+            .unwrap();
+    }
+
+    let progress = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig {
+                batch_size: 1,
+                delay_between_batches: Duration::ZERO,
+                canary_hosts: vec!["canary".into()],
+                halt_on_canary_failure,
+                ..FleetUpdateConfig::default()
+            },
+            scheduled: false,
+        })
+        .await
+        .unwrap();
+    (orchestrator, progress)
+}
+
+#[tokio::test]
+async fn test_fleet_update_halts_after_failed_canary() {
+    let (orchestrator, progress) = canary_fleet(true).await;
+
+    assert_eq!(progress.total_hosts, 3);
+    assert_eq!(progress.failed, 1);
+    assert_eq!(progress.completed, 0);
+    assert_eq!(progress.skipped, 2);
+    let outcomes: Vec<_> = progress
+        .hosts
+        .iter()
+        .map(|o| (o.host.as_str(), o.phase, o.success))
+        .collect();
+    assert_eq!(outcomes, [("canary", FleetPhase::Canary, false)]);
+
+    // The main batch never started
+    for hostname in ["web-1", "web-2"] {
+        let status = orchestrator
+            .ask(GetHostStatus {
+                hostname: hostname.into(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(status.state, HostState::Idle);
+        assert!(status.last_updated.is_none());
+        assert!(status.pending_updates.is_none());
+    }
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_fleet_update_continues_after_canary_in_name_order() {
+    let (orchestrator, progress) = canary_fleet(false).await;
+
+    assert_eq!(progress.failed, 1);
+    assert_eq!(progress.completed, 2);
+    assert_eq!(progress.skipped, 0);
+    let outcomes: Vec<_> = progress
+        .hosts
+        .iter()
+        .map(|o| (o.host.as_str(), o.phase, o.success))
+        .collect();
+    assert_eq!(
+        outcomes,
+        [
+            ("canary", FleetPhase::Canary, false),
+            ("web-1", FleetPhase::Main, true),
+            ("web-2", FleetPhase::Main, true)
+        ]
+    );
+
+    let err = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig {
+                canary_hosts: vec!["canray".into()],
+                ..FleetUpdateConfig::default()
+            },
+            scheduled: false,
+        })
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("canary host 'canray'"), "{err}");
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_reserved_host_refuses_other_initiators() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&["vim"])),
+    );
+    let fleet = Initiator::FleetUpdate(42);
+
+    actor_ref
+        .ask(ReserveForUpdate { initiator: fleet })
+        .await
+        .unwrap();
+
+    // A manual query slipping in before the fleet's own is refused by name
+    let err = actor_ref.ask(QueryInventory::default()).await.unwrap_err();
+    assert!(
+        err.to_string()
+            .starts_with("host busy: fleet update 42 started"),
+        "{err}"
+    );
+
+    actor_ref
+        .ask(QueryInventory {
+            refresh: false,
+            initiator: fleet,
+        })
+        .await
+        .unwrap();
+
+    // Between the fleet's query and update a manual update is refused too
+    let err = actor_ref.ask(StartUpdate::default()).await.unwrap_err();
+    assert!(err.to_string().contains("fleet update 42"), "{err}");
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::PendingUpdates);
+    assert_eq!(status.owner.map(|o| o.initiator), Some(fleet));
+
+    actor_ref
+        .ask(StartUpdate {
+            initiator: fleet,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // The fleet's update ended the reservation
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert!(status.owner.is_none());
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_released_reservation_frees_host_without_updates() {
+    let (actor_ref, _rx) = spawn_host(
+        Arc::new(MockExecutor::default()),
+        Arc::new(MockPackageManager::new(&[])),
+    );
+    let fleet = Initiator::FleetUpdate(7);
+
+    actor_ref
+        .ask(ReserveForUpdate { initiator: fleet })
+        .await
+        .unwrap();
+    actor_ref
+        .ask(QueryInventory {
+            refresh: false,
+            initiator: fleet,
+        })
+        .await
+        .unwrap();
+
+    // Nothing to update, but the host stays reserved until released
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert_eq!(status.owner.map(|o| o.initiator), Some(fleet));
+
+    // Only the owner can release it
+    actor_ref
+        .ask(ReleaseReservation {
+            initiator: Initiator::FleetUpdate(8),
+        })
+        .await
+        .unwrap();
+    assert!(actor_ref.ask(GetStatus).await.unwrap().owner.is_some());
+
+    actor_ref
+        .ask(ReleaseReservation { initiator: fleet })
+        .await
+        .unwrap();
+    assert!(actor_ref.ask(GetStatus).await.unwrap().owner.is_none());
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+/// Factory whose host named "slow" never finishes an upgrade
+struct SlowHostFactory;
+
+#[async_trait]
+impl HostActorFactory for SlowHostFactory {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        Ok(Arc::new(MockExecutor::default()))
+    }
+
+    async fn create_package_manager(
+        &self,
+        config: &HostConfig,
+        executor: Arc<dyn RemoteExecutor>,
+    ) -> Arc<dyn PackageManager> {
+        if config.name == "slow" {
+            Arc::new(MockPackageManager::new(&["linux-image"]).with_hanging_upgrades())
+        } else {
+            TestHostFactory
+                .create_package_manager(config, executor)
+                .await
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_fleet_update_names_manual_owner_of_busy_host() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(SlowHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+    for name in ["slow", "web-1"] {
+        orchestrator
+            .ask(RegisterHost {
+                config: test_config(name),
+            })
+            .await
+            .unwrap();
+    }
+
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "slow".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+    let manual = tokio::spawn({
+        let orchestrator = orchestrator.clone();
+        async move {
+            orchestrator
+                .ask(TriggerHostUpdate {
+                    hostname: "slow".into(),
+                    dry_run: false,
+                    scope: None,
+                    stack: None,
+                    packages: Vec::new(),
+                    force: false,
+                    skip_cleanup: false,
+                })
+                .await
+        }
+    });
+    let status = loop {
+        let status = orchestrator
+            .ask(GetHostStatus {
+                hostname: "slow".into(),
+            })
+            .await
+            .unwrap();
+        if status.state == HostState::Updating {
+            break status;
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    };
+    assert_eq!(
+        status.owner.map(|o| o.initiator),
+        Some(Initiator::ManualApi)
+    );
+
+    let progress = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig {
+                delay_between_batches: Duration::ZERO,
+                ..FleetUpdateConfig::default()
+            },
+            scheduled: false,
+        })
+        .await
+        .unwrap();
+    assert_eq!(progress.completed, 1);
+    assert_eq!(progress.failed, 1);
+    let slow = progress
+        .hosts
+        .iter()
+        .find(|o| o.host.as_str() == "slow")
+        .unwrap();
+    let error = slow.error.as_deref().unwrap();
+    assert!(
+        error.starts_with("host busy: manual request started"),
+        "{error}"
+    );
+
+    // The manual update is untouched and still owns the host
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "slow".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(status.state, HostState::Updating);
+    assert_eq!(
+        status.owner.map(|o| o.initiator),
+        Some(Initiator::ManualApi)
+    );
+
+    // The fleet released the host it updated
+    let updated = orchestrator
+        .ask(GetHostStatus {
+            hostname: "web-1".into(),
+        })
+        .await
+        .unwrap();
+    assert!(updated.owner.is_none());
+
+    orchestrator
+        .ask(CancelHostUpdate {
+            hostname: "slow".into(),
+        })
+        .await
+        .unwrap();
+    assert!(manual.await.unwrap().is_err());
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+async fn paused_fleet() -> (
+    ActorRef<OrchestratorActor>,
+    tokio::sync::mpsc::Receiver<EventEnvelope>,
+) {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        coalesce_window: Duration::ZERO,
+        ..Default::default()
+    });
+    let events = orchestrator.ask(GetEventHub).await.unwrap().subscribe();
+    for name in ["web-1", "web-2"] {
+        orchestrator
+            .ask(RegisterHost {
+                config: test_config(name),
+            })
+            .await
+            .unwrap();
+    }
+    orchestrator
+        .ask(PauseHost {
+            hostname: "web-2".into(),
+        })
+        .await
+        .unwrap();
+    (orchestrator, events)
+}
+
+#[tokio::test]
+async fn test_scheduled_fleet_update_names_the_scheduler() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        ..Default::default()
+    });
+    orchestrator
+        .ask(RegisterHost {
+            config: test_config("web-1"),
+        })
+        .await
+        .unwrap();
+
+    let progress = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig::default(),
+            scheduled: true,
+        })
+        .await
+        .unwrap();
+    assert_eq!(progress.completed, 1);
+
+    let history = orchestrator
+        .ask(GetHostTransitionHistory {
+            hostname: "web-1".into(),
+        })
+        .await
+        .unwrap();
+    let updating = history
+        .iter()
+        .find(|t| t.to == HostState::Updating)
+        .unwrap();
+    assert_eq!(
+        updating.initiator.as_deref(),
+        Some(format!("scheduler (fleet update {})", progress.id).as_str())
+    );
+
+    orchestrator.stop_gracefully().await.unwrap();
+    orchestrator.wait_for_shutdown().await;
+}
+
+#[tokio::test]
+async fn test_fleet_operations_skip_paused_hosts() {
+    let (orchestrator, mut events) = paused_fleet().await;
+
+    let report = orchestrator
+        .ask(FleetDryRun {
+            config: FleetUpdateConfig {
+                dry_run: true,
+                ..FleetUpdateConfig::default()
+            },
+        })
+        .await
+        .unwrap();
+    let hosts: Vec<_> = report.hosts.iter().map(|h| h.host.as_str()).collect();
+    assert_eq!(hosts, ["web-1"]);
+
+    let progress = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig::default(),
+            scheduled: false,
+        })
+        .await
+        .unwrap();
+    assert_eq!(progress.total_hosts, 2);
+    assert_eq!(progress.completed, 1);
+    assert_eq!(progress.skipped, 1);
+    let outcomes: Vec<_> = progress
+        .hosts
+        .iter()
+        .map(|o| (o.host.as_str(), o.success, o.skipped))
+        .collect();
+    assert_eq!(outcomes, [("web-1", true, false), ("web-2", false, true)]);
+
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "web-2".into(),
+        })
+        .await
+        .unwrap();
+    assert!(status.paused);
+    assert!(status.last_updated.is_none());
+    assert!(status.pending_updates.is_none());
+
+    let mut paused_event = false;
+    let mut skipped_event = false;
+    while let Ok(Some(envelope)) =
+        tokio::time::timeout(Duration::from_millis(200), events.recv()).await
+    {
+        match envelope.event {
+            WsEvent::HostPauseChanged { host, paused: true } if host == "web-2" => {
+                paused_event = true;
+            }
+            WsEvent::FleetHostFinished { host, skipped, .. } if host == "web-2" => {
+                skipped_event = skipped;
+            }
+            _ => {}
+        }
+    }
+    assert!(paused_event && skipped_event);
+
+    // A paused canary can't vouch for the rest of the fleet
+    let err = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig {
+                canary_hosts: vec!["web-2".into()],
+                ..FleetUpdateConfig::default()
+            },
+            scheduled: false,
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("canary host 'web-2' is paused"),
+        "{err}"
+    );
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_fleet_update_orders_dependents_before_dependencies() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(TestHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+    for (name, depends_on) in [
+        ("app-1", &["nfs"][..]),
+        ("app-2", &["nfs", "db"]),
+        ("db", &["nfs"]),
+        ("nfs", &[]),
+        ("web", &[]),
+    ] {
+        let mut config = test_config(name);
+        config.depends_on = depends_on.iter().map(|dep| HostName::new(dep)).collect();
+        orchestrator.ask(RegisterHost { config }).await.unwrap();
+    }
+
+    let config = FleetUpdateConfig {
+        batch_size: 2,
+        ..FleetUpdateConfig::default()
+    };
+    let report = orchestrator
+        .ask(FleetDryRun {
+            config: FleetUpdateConfig {
+                dry_run: true,
+                ..config.clone()
+            },
+        })
+        .await
+        .unwrap();
+    // The first layer is capped at two hosts per batch
+    assert_eq!(
+        report.batches,
+        [vec!["app-1", "app-2"], vec!["web"], vec!["db"], vec!["nfs"]]
+    );
+
+    let progress = orchestrator
+        .ask(TriggerFleetUpdate {
+            config,
+            scheduled: false,
+        })
+        .await
+        .unwrap();
+    let order: Vec<_> = progress.hosts.iter().map(|o| o.host.as_str()).collect();
+    assert_eq!(order, ["app-1", "app-2", "web", "db", "nfs"]);
+
+    // A canary goes first, which would take it down under its dependents
+    let err = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig {
+                canary_hosts: vec!["db".into()],
+                ..FleetUpdateConfig::default()
+            },
+            scheduled: false,
+        })
+        .await
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("canary host 'db' is a dependency of 'app-2'"),
+        "{err}"
+    );
+
+    let result = orchestrator
+        .ask(UpdateHostConfig {
+            hostname: "nfs".into(),
+            patch: HostConfigPatch {
+                depends_on: Some(vec!["app-1".into()]),
+                ..Default::default()
+            },
+        })
+        .await;
+    let Err(kameo::error::SendError::HandlerError(CoreError::InvalidHostConfig(errors))) = result
+    else {
+        panic!("expected a dependency cycle, got {result:?}");
+    };
+    assert_eq!(errors[0].field, "depends_on");
+    assert_eq!(
+        errors[0].message,
+        "would form a dependency cycle: app-1 -> nfs -> app-1"
+    );
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_paused_host_needs_forced_manual_update() {
+    let (orchestrator, _events) = paused_fleet().await;
+    let update = |force| TriggerHostUpdate {
+        hostname: "web-2".into(),
+        dry_run: false,
+        scope: None,
+        stack: None,
+        packages: Vec::new(),
+        force,
+        skip_cleanup: false,
+    };
+
+    // People may still look at a paused host
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "web-2".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+    let err = orchestrator.ask(update(false)).await.unwrap_err();
+    assert!(err.to_string().contains("host is paused: web-2"), "{err}");
+    orchestrator.ask(update(true)).await.unwrap();
+
+    // The pause outlives a restart of the host actor
+    orchestrator
+        .ask(UpdateHostConfig {
+            hostname: "web-2".into(),
+            patch: HostConfigPatch {
+                addr: Some("10.0.0.5".to_string()),
+                ..Default::default()
+            },
+        })
+        .await
+        .unwrap();
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "web-2".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+    assert!(orchestrator.ask(update(false)).await.is_err());
+
+    orchestrator
+        .ask(ResumeHost {
+            hostname: "web-2".into(),
+        })
+        .await
+        .unwrap();
+    orchestrator.ask(update(false)).await.unwrap();
+    let progress = orchestrator
+        .ask(TriggerFleetUpdate {
+            config: FleetUpdateConfig::default(),
+            scheduled: false,
+        })
+        .await
+        .unwrap();
+    assert_eq!(progress.skipped, 0);
+
+    let err = orchestrator
+        .ask(PauseHost {
+            hostname: "ghost".into(),
+        })
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("ghost"), "{err}");
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_paused_host_is_not_retried_automatically() {
+    let manager =
+        Arc::new(MockPackageManager::new(&["openssl"]).failing_upgrades(connection_reset()));
+    let mut config = auto_retry_config(3);
+    config.policy.auto_retry.initial_backoff_secs = Some(60);
+    let (actor_ref, mut rx) =
+        spawn_host_with(config, Arc::new(MockExecutor::default()), manager.clone());
+    let update = |force| StartUpdate {
+        force,
+        ..Default::default()
+    };
+
+    // A retry scheduled before the pause is called off
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    assert!(actor_ref.ask(update(false)).await.is_err());
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(status.failure.unwrap().next_retry_at.is_some());
+    actor_ref.tell(SetPaused { paused: true }).await.unwrap();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert!(status.paused);
+    assert!(status.failure.unwrap().next_retry_at.is_none());
+
+    // A forced update that fails while paused schedules none
+    actor_ref.ask(Retry).await.unwrap();
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    assert!(actor_ref.ask(update(true)).await.is_err());
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::Failed);
+    assert!(status.failure.unwrap().next_retry_at.is_none());
+    assert_eq!(manager.upgrade_calls.load(Ordering::SeqCst), 2);
+
+    let mut scheduled = 0;
+    while let Ok(event) = rx.try_recv() {
+        match event {
+            WsEvent::RetryScheduled { .. } => scheduled += 1,
+            WsEvent::RetryStarted { .. } | WsEvent::RetriesExhausted { .. } => {
+                panic!("unexpected {event:?}");
+            }
+            _ => {}
+        }
+    }
+    assert_eq!(scheduled, 1);
+
+    // Automated reservations are refused too
+    let err = actor_ref
+        .ask(ReserveForUpdate {
+            initiator: Initiator::Scheduler(1),
+        })
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("host is paused"), "{err}");
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_paused_host_is_not_probed() {
+    let mut config = test_config("test-host");
+    config.policy.health_check_interval_secs = Some(1);
+    config.policy.unreachable_after = Some(1);
+    let executor = Arc::new(MockExecutor::default());
+    executor.set_down(true);
+    let (actor_ref, mut rx) = spawn_host_with(
+        config,
+        executor.clone(),
+        Arc::new(MockPackageManager::new(&[])),
+    );
+
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !matches!(rx.recv().await, Ok(WsEvent::HostDisconnected { .. })) {}
+    })
+    .await
+    .unwrap();
+
+    // The host comes back, but nobody looks while it is paused
+    actor_ref.tell(SetPaused { paused: true }).await.unwrap();
+    executor.set_down(false);
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert!(!actor_ref.ask(GetStatus).await.unwrap().reachable);
+
+    actor_ref.tell(SetPaused { paused: false }).await.unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !matches!(rx.recv().await, Ok(WsEvent::HostConnected { .. })) {}
+    })
+    .await
+    .unwrap();
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_host_actor_collects_facts() {
+    let executor = Arc::new(facts_executor("x86_64", true));
+    let (actor_ref, _rx) = spawn_host(executor.clone(), Arc::new(MockPackageManager::new(&[])));
+
+    // Nothing is known before the host was looked at
+    assert!(actor_ref.ask(GetStatus).await.unwrap().facts.is_empty());
+
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let facts = actor_ref.ask(GetStatus).await.unwrap().facts;
+    assert_eq!(
+        facts,
+        Facts::from([
+            (FactKey::Kernel, "6.1.0-21".to_string()),
+            (FactKey::Arch, "x86_64".to_string()),
+            (FactKey::Virtualization, "kvm".to_string()),
+            (FactKey::Docker, "true".to_string()),
+        ])
+    );
+
+    // Fresh facts aren't probed again
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    let probes = executor
+        .commands()
+        .iter()
+        .filter(|cmd| *cmd == FACTS_PROBE)
+        .count();
+    assert_eq!(probes, 1);
+    actor_ref.stop_gracefully().await.unwrap();
+
+    // An update records whether it left a reboot pending
+    let (actor_ref, _rx) = spawn_host(
+        executor,
+        Arc::new(MockPackageManager::new(&["linux-image-amd64"]).with_reboot_required()),
+    );
+    actor_ref.ask(QueryInventory::default()).await.unwrap();
+    actor_ref
+        .ask(StartUpdate {
+            dry_run: false,
+            scope: None,
+            stack: None,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let status = actor_ref.ask(GetStatus).await.unwrap();
+    assert_eq!(status.state, HostState::WaitingReboot);
+    assert_eq!(status.facts[&FactKey::RebootRequired], "true");
+    assert_eq!(status.facts[&FactKey::Arch], "x86_64");
+
+    actor_ref.stop_gracefully().await.unwrap();
+}
+
+/// Factory whose hosts named `pi-*` run on aarch64, and `*-docker` have
+/// docker installed
+struct FactsHostFactory;
+
+#[async_trait]
+impl HostActorFactory for FactsHostFactory {
+    async fn create_executor(
+        &self,
+        config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        let arch = if config.name.starts_with("pi-") {
+            "aarch64"
+        } else {
+            "x86_64"
+        };
+        Ok(Arc::new(facts_executor(
+            arch,
+            config.name.ends_with("-docker"),
+        )))
+    }
+
+    async fn create_package_manager(
+        &self,
+        config: &HostConfig,
+        executor: Arc<dyn RemoteExecutor>,
+    ) -> Arc<dyn PackageManager> {
+        TestHostFactory
+            .create_package_manager(config, executor)
+            .await
+    }
+}
+
+#[tokio::test]
+async fn test_orchestrator_fleet_filters_by_fact() {
+    let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+        event_channel_capacity: 100,
+        host_factory: Arc::new(FactsHostFactory),
+        audit_log: None,
+        ..Default::default()
+    });
+    for name in ["pi-1", "pi-2-docker", "web-docker", "unqueried"] {
+        orchestrator
+            .ask(RegisterHost {
+                config: test_config(name),
+            })
+            .await
+            .unwrap();
+    }
+    for name in ["pi-1", "pi-2-docker", "web-docker"] {
+        orchestrator
+            .ask(QueryHostInventory {
+                hostname: name.into(),
+                refresh: false,
+            })
+            .await
+            .unwrap();
+    }
+
+    let dry_run = |facts: &[&str]| FleetDryRun {
+        config: FleetUpdateConfig {
+            filter: Some(FleetFilter {
+                fact_filters: facts.iter().map(|f| f.parse().unwrap()).collect(),
+                ..FleetFilter::default()
+            }),
+            dry_run: true,
+            ..FleetUpdateConfig::default()
+        },
+    };
+    let hosts = |report: tendhost_api::responses::FleetDryRunReport| -> Vec<String> {
+        report.hosts.into_iter().map(|h| h.host).collect()
+    };
+
+    let report = orchestrator.ask(dry_run(&["arch:aarch64"])).await.unwrap();
+    assert_eq!(hosts(report), ["pi-1", "pi-2-docker"]);
+
+    // Several facts must all match
+    let report = orchestrator
+        .ask(dry_run(&["arch:aarch64", "docker:true"]))
+        .await
+        .unwrap();
+    assert_eq!(hosts(report), ["pi-2-docker"]);
+
+    // A host whose facts aren't known yet never matches
+    let report = orchestrator.ask(dry_run(&["arch:x86_64"])).await.unwrap();
+    assert_eq!(hosts(report), ["web-docker"]);
+
+    orchestrator.stop_gracefully().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_orchestrator_restores_hosts_after_restart() {
+    let dir = std::env::temp_dir().join(format!("tendhost-warm-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let store = Arc::new(SnapshotStore::new(dir.join("snapshot.json")));
+    let spawn = || {
+        OrchestratorActor::spawn(OrchestratorActorArgs {
+            event_channel_capacity: 100,
+            host_factory: Arc::new(TestHostFactory),
+            snapshot_store: Some(store.clone()),
+            ..Default::default()
+        })
+    };
+    let register = |orchestrator: ActorRef<OrchestratorActor>, names: &'static [&'static str]| async move {
+        let configs = names.iter().map(|name| test_config(name)).collect();
+        orchestrator.ask(RegisterHosts { configs }).await.unwrap();
+    };
+
+    let orchestrator = spawn();
+    register(orchestrator.clone(), &["web-1", "web-2", "web-3"]).await;
+    orchestrator
+        .ask(QueryHostInventory {
+            hostname: "web-1".into(),
+            refresh: false,
+        })
+        .await
+        .unwrap();
+    orchestrator
+        .ask(PauseHost {
+            hostname: "web-2".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(orchestrator.ask(SaveSnapshots).await.unwrap(), 3);
+    orchestrator.stop_gracefully().await.unwrap();
+    orchestrator.wait_for_shutdown().await;
+    assert!(store.path().exists());
+
+    // The old host is gone after the upgrade; its snapshot is dropped
+    let orchestrator = spawn();
+    register(orchestrator.clone(), &["web-1", "web-2", "db-1"]).await;
+    let restored = orchestrator.ask(RestoreHosts).await.unwrap();
+    assert_eq!(restored, [HostName::from("web-1"), HostName::from("web-2")]);
+
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "web-1".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(status.state, HostState::PendingUpdates);
+    assert_eq!(status.pending_updates, Some(2));
+    assert!(status.last_checked.is_some());
+    let summary = orchestrator.ask(GetFleetSummary).await.unwrap();
+    assert_eq!(summary.pending_updates, 2);
+
+    // The pause survives even though the host was idle
+    let status = orchestrator
+        .ask(GetHostStatus {
+            hostname: "web-2".into(),
+        })
+        .await
+        .unwrap();
+    assert_eq!(status.state, HostState::Idle);
+    assert!(status.paused);
+    let plan = orchestrator
+        .ask(FleetDryRun {
+            config: FleetUpdateConfig::default(),
+        })
+        .await
+        .unwrap();
+    // Fleet updates leave it out too
+    assert!(
+        !plan.batches.iter().flatten().any(|host| host == "web-2"),
+        "{:?}",
+        plan.batches
+    );
+
+    // The snapshot is used up, so restoring again changes nothing
+    assert!(!store.path().exists());
+    assert!(orchestrator.ask(RestoreHosts).await.unwrap().is_empty());
+
+    orchestrator.stop_gracefully().await.unwrap();
+    orchestrator.wait_for_shutdown().await;
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,377 @@
+//! Building shell command lines from untrusted arguments
+//!
+//! Executors take a single command string that a POSIX shell interprets.
+//! Paths, service names and other values that come from configuration or
+//! command output must be quoted before they are put into that string, or
+//! a value like `/opt/my stacks` splits into two words and `a;reboot` runs
+//! a second command.
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// Quote `arg` so POSIX `sh` reads it back as exactly one word
+///
+/// Arguments made only of characters with no special meaning to the shell
+/// are returned unchanged. Anything else is wrapped in single quotes, with
+/// embedded single quotes written as `'\''`.
+#[must_use]
+pub fn quote(arg: &str) -> Cow<'_, str> {
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        return Cow::Borrowed(arg);
+    }
+
+    let mut quoted = String::with_capacity(arg.len() + 2);
+    quoted.push('\'');
+    for c in arg.chars() {
+        if c == '\'' {
+            quoted.push_str("'\\''");
+        } else {
+            quoted.push(c);
+        }
+    }
+    quoted.push('\'');
+    Cow::Owned(quoted)
+}
+
+fn is_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric()
+        || matches!(c, '_' | '-' | '.' | '/' | ':' | ',' | '=' | '+' | '@' | '%')
+}
+
+/// Whether `name` is a variable name the shell can assign
+///
+/// Letters, digits and `_`, not starting with a digit.
+#[must_use]
+pub fn is_env_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `cmd` with `vars` exported before it
+///
+/// Unlike the assignments of [`ShellCommand::with_env`], which only reach
+/// the first program, exported variables reach every part of a compound
+/// line like `a && b | c`. Values are quoted; names must satisfy
+/// [`is_env_name`]. Without variables `cmd` is returned unchanged.
+#[must_use]
+pub fn with_exports<I, N, V>(vars: I, cmd: &str) -> String
+where
+    I: IntoIterator<Item = (N, V)>,
+    N: AsRef<str>,
+    V: AsRef<str>,
+{
+    let mut export = ShellCommand::new("export");
+    for (name, value) in vars {
+        let name = name.as_ref();
+        debug_assert!(is_env_name(name), "invalid variable name {name:?}");
+        export.push(&format!("{name}={}", quote(value.as_ref())));
+    }
+    if export.as_str() == "export" {
+        cmd.to_string()
+    } else {
+        format!("{export}; {cmd}")
+    }
+}
+
+/// `lines` as one script that stops at the first failing line
+///
+/// The lines are joined under `set -e`. They are shell syntax and are not
+/// quoted, so they must be trusted or built with [`ShellCommand`].
+#[must_use]
+pub fn script(lines: &[&str]) -> String {
+    std::iter::once("set -e")
+        .chain(lines.iter().copied())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A POSIX shell command line built one argument at a time
+///
+/// Every argument is quoted with [`quote`]. Operators such as pipes and
+/// redirections are added with [`raw`](Self::raw), which must only be
+/// given trusted, constant fragments.
+///
+/// # Example
+/// ```
+/// use tendhost_exec::ShellCommand;
+///
+/// let cmd = ShellCommand::new("docker")
+///     .args(["compose", "-f", "/opt/my stacks/docker-compose.yml", "ps", "-q"])
+///     .raw("|")
+///     .arg("wc")
+///     .arg("-l");
+/// assert_eq!(
+///     cmd.as_str(),
+///     "docker compose -f '/opt/my stacks/docker-compose.yml' ps -q | wc -l"
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShellCommand {
+    line: String,
+}
+
+impl ShellCommand {
+    /// Start a command running `program`
+    #[must_use]
+    pub fn new(program: &str) -> Self {
+        Self {
+            line: quote(program).into_owned(),
+        }
+    }
+
+    /// Start a command running `program` with `vars` set for it alone
+    ///
+    /// Each `(name, value)` pair becomes a leading `name=value`
+    /// assignment with the value quoted. Names are written as given and
+    /// must be valid shell identifiers.
+    #[must_use]
+    pub fn with_env<I, N, V>(vars: I, program: &str) -> Self
+    where
+        I: IntoIterator<Item = (N, V)>,
+        N: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut cmd = Self {
+            line: String::new(),
+        };
+        for (name, value) in vars {
+            let name = name.as_ref();
+            debug_assert!(
+                !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'),
+                "invalid variable name {name:?}"
+            );
+            cmd.push(&format!("{name}={}", quote(value.as_ref())));
+        }
+        cmd.push(&quote(program));
+        cmd
+    }
+
+    /// `sh -c` running `lines` as one [`script`]
+    ///
+    /// A single command, so the whole script can run through one
+    /// [`PrivilegeEscalation`](crate::PrivilegeEscalation).
+    #[must_use]
+    pub fn script(lines: &[&str]) -> Self {
+        Self::new("sh").arg("-c").arg(script(lines))
+    }
+
+    /// Append one quoted argument
+    #[must_use]
+    pub fn arg(mut self, arg: impl AsRef<str>) -> Self {
+        self.push(&quote(arg.as_ref()));
+        self
+    }
+
+    /// Append several quoted arguments
+    #[must_use]
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for arg in args {
+            self.push(&quote(arg.as_ref()));
+        }
+        self
+    }
+
+    /// Append `NAME="$NAME"` for each of `names`
+    ///
+    /// After `env`, this hands variables of the invoking shell to a
+    /// program whose environment would otherwise be reset, without their
+    /// values appearing in the command line. Names must satisfy
+    /// [`is_env_name`].
+    #[must_use]
+    pub fn pass_env<I, N>(mut self, names: I) -> Self
+    where
+        I: IntoIterator<Item = N>,
+        N: AsRef<str>,
+    {
+        for name in names {
+            let name = name.as_ref();
+            debug_assert!(is_env_name(name), "invalid variable name {name:?}");
+            self.push(&format!("{name}=\"${name}\""));
+        }
+        self
+    }
+
+    /// Append `fragment` without quoting
+    ///
+    /// For shell syntax like `|`, `2>&1` or `||`. Never pass values that
+    /// come from configuration or command output here.
+    #[must_use]
+    pub fn raw(mut self, fragment: &str) -> Self {
+        self.push(fragment);
+        self
+    }
+
+    /// The command line built so far
+    #[must_use]
+    pub fn as_str(&self) -> &str {
+        &self.line
+    }
+
+    /// Consume the builder, returning the command line
+    #[must_use]
+    pub fn build(self) -> String {
+        self.line
+    }
+
+    fn push(&mut self, word: &str) {
+        if !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.line.push_str(word);
+    }
+}
+
+impl fmt::Display for ShellCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.line)
+    }
+}
+
+impl From<ShellCommand> for String {
+    fn from(cmd: ShellCommand) -> Self {
+        cmd.line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_leaves_plain_words_alone() {
+        assert_eq!(quote("openssl"), "openssl");
+        assert_eq!(quote("/opt/stacks/web-1"), "/opt/stacks/web-1");
+        assert_eq!(
+            quote("Dpkg::Options::=--force-confdef"),
+            "Dpkg::Options::=--force-confdef"
+        );
+        assert!(matches!(quote("user@host"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_quote_special_characters() {
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("/opt/my stacks"), "'/opt/my stacks'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+        assert_eq!(quote("\"web\""), "'\"web\"'");
+        assert_eq!(quote("$HOME"), "'$HOME'");
+        assert_eq!(quote("`id`"), "'`id`'");
+        assert_eq!(quote("a;reboot"), "'a;reboot'");
+        assert_eq!(quote("line\nbreak"), "'line\nbreak'");
+        assert_eq!(quote("*"), "'*'");
+    }
+
+    #[test]
+    fn test_builder_quotes_arguments_but_not_raw_fragments() {
+        let cmd = ShellCommand::new("sudo")
+            .args(["-n", "systemctl", "restart"])
+            .arg("my service")
+            .raw("2>&1");
+        assert_eq!(cmd.as_str(), "sudo -n systemctl restart 'my service' 2>&1");
+        assert_eq!(cmd.to_string(), cmd.clone().build());
+    }
+
+    #[test]
+    fn test_script_runs_lines_under_set_e() {
+        assert_eq!(
+            script(&["echo one", "echo two"]),
+            "set -e\necho one\necho two"
+        );
+        assert_eq!(
+            ShellCommand::script(&["mkdir -p /etc/apt/keyrings", "apt-get update"]).as_str(),
+            "sh -c 'set -e\nmkdir -p /etc/apt/keyrings\napt-get update'"
+        );
+    }
+
+    #[test]
+    fn test_with_env_quotes_values_only() {
+        let cmd = ShellCommand::with_env([("LC_ALL", "C"), ("GREETING", "a b")], "apt").arg("list");
+        assert_eq!(cmd.as_str(), "LC_ALL=C GREETING='a b' apt list");
+
+        let cmd = ShellCommand::with_env(std::iter::empty::<(&str, &str)>(), "apt");
+        assert_eq!(cmd.as_str(), "apt");
+    }
+
+    #[test]
+    fn test_env_names() {
+        assert!(is_env_name("http_proxy"));
+        assert!(is_env_name("_PRIVATE2"));
+        assert!(!is_env_name(""));
+        assert!(!is_env_name("2FAST"));
+        assert!(!is_env_name("NO-PROXY"));
+        assert!(!is_env_name("A=B"));
+    }
+
+    #[test]
+    fn test_with_exports_prefixes_the_whole_line() {
+        let line = with_exports(
+            [
+                ("http_proxy", "http://proxy:3128"),
+                ("GREETING", "it's a b"),
+            ],
+            "apt-get update && apt-get -s upgrade",
+        );
+        assert_eq!(
+            line,
+            "export http_proxy=http://proxy:3128 GREETING='it'\\''s a b'; \
+             apt-get update && apt-get -s upgrade"
+        );
+        assert_eq!(
+            with_exports(std::iter::empty::<(&str, &str)>(), "uptime"),
+            "uptime"
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_exports_reach_every_command_of_the_line() {
+        use crate::local::LocalExecutor;
+        use crate::traits::RemoteExecutor;
+
+        let line = with_exports(
+            [("TENDHOST_TEST", "a b; c")],
+            "printenv TENDHOST_TEST && printenv TENDHOST_TEST",
+        );
+        let result = LocalExecutor::new().run(&line).await.unwrap();
+        assert!(result.success(), "{line}: {}", result.stderr);
+        assert_eq!(result.stdout, "a b; c\na b; c\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_with_env_sets_variables_for_the_command() {
+        use crate::local::LocalExecutor;
+        use crate::traits::RemoteExecutor;
+
+        let cmd = ShellCommand::with_env([("TENDHOST_TEST", "it's set")], "printenv")
+            .arg("TENDHOST_TEST");
+        let result = LocalExecutor::new().run(cmd.as_str()).await.unwrap();
+        assert!(result.success(), "{cmd}: {}", result.stderr);
+        assert_eq!(result.stdout, "it's set\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_quoted_arguments_round_trip_through_sh() {
+        use crate::local::LocalExecutor;
+        use crate::traits::RemoteExecutor;
+
+        let executor = LocalExecutor::new();
+        for arg in [
+            "/tmp/a b;echo pwned",
+            "it's \"quoted\"",
+            "$HOME `id` $(id)",
+            "two\nlines",
+            "",
+        ] {
+            let cmd = ShellCommand::new("printf").arg("%s|").arg(arg);
+            let result = executor.run(cmd.as_str()).await.unwrap();
+            assert!(result.success(), "{cmd}: {}", result.stderr);
+            assert_eq!(result.stdout, format!("{arg}|"), "{cmd}");
+        }
+    }
+}
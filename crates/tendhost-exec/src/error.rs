@@ -24,6 +24,13 @@ pub enum ExecError {
         stderr: String,
     },
 
+    /// The SSH connection was not established in time
+    #[error("connection timed out after {timeout:?}")]
+    ConnectTimeout {
+        /// Connect timeout that was exceeded
+        timeout: Duration,
+    },
+
     /// Command timed out
     #[error("command timed out after {timeout:?}")]
     Timeout {
@@ -31,6 +38,24 @@ pub enum ExecError {
         timeout: Duration,
     },
 
+    /// The jump host could not be reached or refused the login
+    #[error("jump host {jump} failed: {source}")]
+    JumpHostFailed {
+        /// Jump host address
+        jump: String,
+        /// What went wrong connecting to it
+        source: Box<ExecError>,
+    },
+
+    /// The jump host was reached but could not forward to the target
+    #[error("reached jump host {jump}, but not the target through it: {message}")]
+    TargetUnreachable {
+        /// Jump host address
+        jump: String,
+        /// Why the tunnel or the target's handshake failed
+        message: String,
+    },
+
     /// SSH key error
     #[error("SSH key error: {0}")]
     SshKeyError(String),
@@ -56,9 +81,31 @@ impl ExecError {
     /// Check if error is retryable
     #[must_use]
     pub fn is_retryable(&self) -> bool {
-        matches!(
-            self,
-            ExecError::ConnectionFailed(_) | ExecError::Timeout { .. }
-        )
+        match self {
+            ExecError::JumpHostFailed { source, .. } => source.is_retryable(),
+            _ => matches!(
+                self,
+                ExecError::ConnectionFailed(_)
+                    | ExecError::ConnectTimeout { .. }
+                    | ExecError::Timeout { .. }
+                    | ExecError::TargetUnreachable { .. }
+            ),
+        }
+    }
+
+    /// Check if the host could not be reached at all, as opposed to
+    /// refusing the login or a command failing on it
+    #[must_use]
+    pub fn is_connection_failure(&self) -> bool {
+        match self {
+            ExecError::JumpHostFailed { source, .. } => source.is_connection_failure(),
+            _ => matches!(
+                self,
+                ExecError::ConnectionFailed(_)
+                    | ExecError::ConnectTimeout { .. }
+                    | ExecError::TargetUnreachable { .. }
+                    | ExecError::NotConnected
+            ),
+        }
     }
 }
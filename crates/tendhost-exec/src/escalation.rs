@@ -0,0 +1,223 @@
+//! Running commands as root
+//!
+//! Package managers, reboots and service restarts need root. Most hosts
+//! get it through `sudo`, some through `doas`, and a host logged into as
+//! root needs no prefix at all. Commands are always run non-interactively
+//! (`-n`): a password prompt would otherwise wait until the command times
+//! out.
+
+use std::fmt;
+
+use crate::command::ShellCommand;
+use serde::{Deserialize, Serialize};
+
+/// Characters a custom prefix must not contain, as the shell would
+/// interpret them instead of passing them on
+const SHELL_METACHARACTERS: &[char] = &[
+    ';', '&', '|', '<', '>', '$', '`', '\\', '"', '\'', '(', ')', '{', '}', '[', ']', '*', '?',
+    '!', '#', '~',
+];
+
+/// How commands that need root are run
+///
+/// In TOML: `escalation = "doas"`, or `escalation = { custom = "pfexec" }`
+/// for any other prefix.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivilegeEscalation {
+    /// No prefix, for hosts logged into as root
+    None,
+    /// `sudo -n`
+    #[default]
+    Sudo,
+    /// `doas -n`
+    Doas,
+    /// Any other prefix, split on whitespace, e.g. `pfexec`
+    Custom(String),
+}
+
+impl PrivilegeEscalation {
+    /// The words put before a command
+    #[must_use]
+    pub fn words(&self) -> Vec<&str> {
+        match self {
+            Self::None => Vec::new(),
+            Self::Sudo => vec!["sudo", "-n"],
+            Self::Doas => vec!["doas", "-n"],
+            Self::Custom(prefix) => prefix.split_whitespace().collect(),
+        }
+    }
+
+    /// Whether commands run without a prefix
+    #[must_use]
+    pub fn is_none(&self) -> bool {
+        self.words().is_empty()
+    }
+
+    /// Start a command running `program` as root
+    #[must_use]
+    pub fn command(&self, program: &str) -> ShellCommand {
+        match self.words().split_first() {
+            Some((first, rest)) => ShellCommand::new(first)
+                .args(rest.iter().copied())
+                .arg(program),
+            None => ShellCommand::new(program),
+        }
+    }
+
+    /// `cmd` run as root, e.g. `sudo -n needrestart -b`
+    #[must_use]
+    pub fn wrap(&self, cmd: &ShellCommand) -> ShellCommand {
+        match self.words().split_first() {
+            Some((first, rest)) => ShellCommand::new(first)
+                .args(rest.iter().copied())
+                .raw(cmd.as_str()),
+            None => cmd.clone(),
+        }
+    }
+
+    /// `cmd` run as root with the shell variables `keep` passed on
+    ///
+    /// sudo and doas reset the environment, so variables exported for every
+    /// command, like a proxy for apt, would not reach `cmd`. They are handed
+    /// over through `env`, e.g. `sudo -n env http_proxy="$http_proxy" cmd`.
+    #[must_use]
+    pub fn wrap_keeping(&self, cmd: &ShellCommand, keep: &[String]) -> ShellCommand {
+        if self.is_none() || keep.is_empty() {
+            return self.wrap(cmd);
+        }
+        self.wrap(&ShellCommand::new("env").pass_env(keep).raw(cmd.as_str()))
+    }
+
+    /// Name of the escalation tool for messages, e.g. `doas`
+    #[must_use]
+    pub fn tool(&self) -> &str {
+        self.words().first().copied().unwrap_or("none")
+    }
+
+    /// Check a custom prefix is a plain command
+    ///
+    /// # Errors
+    /// Returns a message if the prefix is empty or contains characters the
+    /// shell would interpret, such as `;` or `$(`.
+    pub fn validate(&self) -> Result<(), String> {
+        let Self::Custom(prefix) = self else {
+            return Ok(());
+        };
+        if prefix.trim().is_empty() {
+            return Err("custom prefix must not be empty".to_string());
+        }
+        if prefix
+            .chars()
+            .any(|c| c.is_control() || SHELL_METACHARACTERS.contains(&c))
+        {
+            return Err("custom prefix must not contain shell metacharacters".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PrivilegeEscalation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Sudo => f.write_str("sudo"),
+            Self::Doas => f.write_str("doas"),
+            Self::Custom(prefix) => f.write_str(prefix.trim()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+        let reboot = |escalation: PrivilegeEscalation| escalation.command("reboot").build();
+        assert_eq!(reboot(PrivilegeEscalation::None), "reboot");
+        assert_eq!(reboot(PrivilegeEscalation::Sudo), "sudo -n reboot");
+        assert_eq!(reboot(PrivilegeEscalation::Doas), "doas -n reboot");
+        assert_eq!(
+            reboot(PrivilegeEscalation::Custom(" pfexec  -P all ".to_string())),
+            "pfexec -P all reboot"
+        );
+
+        let needrestart = ShellCommand::new("needrestart").arg("-b");
+        assert_eq!(
+            PrivilegeEscalation::Sudo.wrap(&needrestart).as_str(),
+            "sudo -n needrestart -b"
+        );
+        assert_eq!(PrivilegeEscalation::None.wrap(&needrestart), needrestart);
+
+        assert_eq!(
+            PrivilegeEscalation::Doas.wrap(&needrestart).as_str(),
+            "doas -n needrestart -b"
+        );
+        assert!(PrivilegeEscalation::Custom("  ".to_string()).is_none());
+    }
+
+    #[test]
+    fn test_wrap_keeping_passes_variables_by_reference() {
+        let update = ShellCommand::new("apt").arg("update");
+        let keep = ["http_proxy".to_string(), "no_proxy".to_string()];
+        assert_eq!(
+            PrivilegeEscalation::Sudo
+                .wrap_keeping(&update, &keep)
+                .as_str(),
+            r#"sudo -n env http_proxy="$http_proxy" no_proxy="$no_proxy" apt update"#
+        );
+        assert_eq!(
+            PrivilegeEscalation::Doas
+                .wrap_keeping(&update, &keep)
+                .as_str(),
+            r#"doas -n env http_proxy="$http_proxy" no_proxy="$no_proxy" apt update"#
+        );
+        // Exported variables reach unescalated commands anyway
+        assert_eq!(
+            PrivilegeEscalation::None.wrap_keeping(&update, &keep),
+            update
+        );
+        assert_eq!(
+            PrivilegeEscalation::Sudo
+                .wrap_keeping(&update, &[])
+                .as_str(),
+            "sudo -n apt update"
+        );
+    }
+
+    #[test]
+    fn test_validate_rejects_shell_metacharacters() {
+        for prefix in [
+            "sudo; rm -rf /",
+            "$(evil)",
+            "doas `x`",
+            "run0 > /dev/null",
+            "a\nb",
+        ] {
+            let escalation = PrivilegeEscalation::Custom(prefix.to_string());
+            assert!(escalation.validate().is_err(), "{prefix:?} accepted");
+        }
+        assert!(
+            PrivilegeEscalation::Custom(String::new())
+                .validate()
+                .is_err()
+        );
+        assert!(
+            PrivilegeEscalation::Custom("sudo -n -u root".to_string())
+                .validate()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_serde_forms() {
+        let parse = |json: &str| serde_json::from_str::<PrivilegeEscalation>(json).unwrap();
+        assert_eq!(parse(r#""doas""#), PrivilegeEscalation::Doas);
+        assert_eq!(parse(r#""none""#), PrivilegeEscalation::None);
+        assert_eq!(
+            parse(r#"{"custom": "pfexec"}"#),
+            PrivilegeEscalation::Custom("pfexec".to_string())
+        );
+    }
+}
@@ -14,16 +14,24 @@
 //! }
 //! ```
 
+pub mod command;
 pub mod error;
+pub mod escalation;
 pub mod keys;
 pub mod local;
+pub mod recording;
 pub mod result;
 pub mod ssh;
+pub mod stats;
 pub mod traits;
 
+pub use command::{ShellCommand, is_env_name, quote, script, with_exports};
 pub use error::ExecError;
-pub use keys::{KeySource, ResolvedKey};
-pub use local::LocalExecutor;
-pub use result::{CommandResult, ConnectionInfo};
+pub use escalation::PrivilegeEscalation;
+pub use keys::{KeyFormat, KeySource, PassphraseSource, ResolvedKey, remove_temp_keys};
+pub use local::{LocalExecutor, Shell};
+pub use recording::{CommandHistory, CommandRecord, RecordingExecutor};
+pub use result::{CommandResult, ConnectionInfo, DEFAULT_CONNECT_TIMEOUT, DEFAULT_MAX_CHANNELS};
 pub use ssh::{SshExecutor, SshExecutorBuilder};
+pub use stats::{ExecutorStats, ExecutorStatsSnapshot, InstrumentedExecutor, RollingDurations};
 pub use traits::{RemoteExecutor, RemoteExecutorExt};
@@ -1,5 +1,6 @@
 //! Local command execution using `tokio::process`
 
+use std::collections::BTreeMap;
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
@@ -11,17 +12,105 @@ use crate::error::ExecError;
 use crate::result::CommandResult;
 use crate::traits::RemoteExecutor;
 
+/// Shell used to interpret local commands
+///
+/// Commands go through a shell so pipes and redirections work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Shell {
+    /// POSIX `sh -c`
+    Sh,
+    /// Windows `cmd /C`
+    Cmd,
+    /// PowerShell, preferring `pwsh` over Windows PowerShell
+    PowerShell,
+}
+
+impl Shell {
+    /// Shell for the platform the executor runs on
+    ///
+    /// `sh` on unix. On Windows, PowerShell when it is on `PATH` and
+    /// `cmd` otherwise.
+    #[must_use]
+    pub fn platform_default() -> Self {
+        if cfg!(windows) {
+            if powershell_program().is_some() {
+                Shell::PowerShell
+            } else {
+                Shell::Cmd
+            }
+        } else {
+            Shell::Sh
+        }
+    }
+
+    /// Program and arguments that run `cmd` in this shell
+    #[must_use]
+    pub fn command_line(self, cmd: &str) -> (String, Vec<String>) {
+        let (program, args): (String, &[&str]) = match self {
+            Shell::Sh => ("sh".to_string(), &["-c"]),
+            Shell::Cmd => ("cmd".to_string(), &["/C"]),
+            Shell::PowerShell => (
+                powershell_program().unwrap_or("powershell").to_string(),
+                &["-NoLogo", "-NoProfile", "-NonInteractive", "-Command"],
+            ),
+        };
+        let mut args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        args.push(cmd.to_string());
+        (program, args)
+    }
+}
+
+/// First PowerShell executable found on `PATH`
+fn powershell_program() -> Option<&'static str> {
+    let path = std::env::var_os("PATH")?;
+    ["pwsh", "powershell"].into_iter().find(|name| {
+        std::env::split_paths(&path)
+            .any(|dir| dir.join(name).is_file() || dir.join(format!("{name}.exe")).is_file())
+    })
+}
+
 /// Local command executor
 ///
-/// Executes commands on the local machine using `tokio::process::Command`.
+/// Executes commands on the local machine using `tokio::process::Command`,
+/// through [`Shell::platform_default`] unless another shell is chosen with
+/// [`with_shell`](Self::with_shell).
 #[derive(Debug, Clone)]
-pub struct LocalExecutor;
+pub struct LocalExecutor {
+    shell: Shell,
+    /// Variables set for every command
+    env: BTreeMap<String, String>,
+}
 
 impl LocalExecutor {
     /// Create a new local executor
     #[must_use]
     pub fn new() -> Self {
-        Self
+        Self {
+            shell: Shell::platform_default(),
+            env: BTreeMap::new(),
+        }
+    }
+
+    /// Use `shell` to interpret commands
+    #[must_use]
+    pub fn with_shell(mut self, shell: Shell) -> Self {
+        self.shell = shell;
+        self
+    }
+
+    /// Shell commands are run through
+    #[must_use]
+    pub fn shell(&self) -> Shell {
+        self.shell
+    }
+
+    /// Set `env` for every command, on top of the daemon's own environment
+    ///
+    /// Values are never logged.
+    #[must_use]
+    pub fn with_env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
     }
 
     /// Internal method to execute command
@@ -32,9 +121,10 @@ impl LocalExecutor {
         debug!(command = %cmd, "executing local command");
 
         // Use shell to support pipes, redirections, etc.
-        let child = Command::new("sh")
-            .arg("-c")
-            .arg(cmd)
+        let (program, args) = self.shell.command_line(cmd);
+        let child = Command::new(program)
+            .args(args)
+            .envs(&self.env)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .spawn()
@@ -118,6 +208,10 @@ impl RemoteExecutor for LocalExecutor {
         }
     }
 
+    fn env_names(&self) -> Vec<String> {
+        self.env.keys().cloned().collect()
+    }
+
     fn executor_type(&self) -> &'static str {
         "local"
     }
@@ -155,6 +249,64 @@ mod tests {
         assert!(matches!(result, Err(ExecError::Timeout { .. })));
     }
 
+    #[test]
+    fn test_command_line_per_shell() {
+        assert_eq!(
+            Shell::Sh.command_line("echo hi"),
+            (
+                "sh".to_string(),
+                vec!["-c".to_string(), "echo hi".to_string()]
+            )
+        );
+        assert_eq!(
+            Shell::Cmd.command_line("echo hi"),
+            (
+                "cmd".to_string(),
+                vec!["/C".to_string(), "echo hi".to_string()]
+            )
+        );
+
+        let (program, args) = Shell::PowerShell.command_line("echo hi");
+        assert!(program == "pwsh" || program == "powershell");
+        assert_eq!(args.first().map(String::as_str), Some("-NoLogo"));
+        assert_eq!(args[args.len() - 2..], ["-Command", "echo hi"]);
+    }
+
+    #[test]
+    fn test_platform_default_shell() {
+        let shell = Shell::platform_default();
+        if cfg!(windows) {
+            assert_ne!(shell, Shell::Sh);
+        } else {
+            assert_eq!(shell, Shell::Sh);
+        }
+        assert_eq!(LocalExecutor::new().shell(), shell);
+        assert_eq!(
+            LocalExecutor::new().with_shell(Shell::Cmd).shell(),
+            Shell::Cmd
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_env_is_set_for_every_command() {
+        let executor = LocalExecutor::new().with_env(BTreeMap::from([(
+            "TENDHOST_PROXY".to_string(),
+            "http://proxy.internal:3128".to_string(),
+        )]));
+        let result = executor
+            .run("echo \"$TENDHOST_PROXY\" | tr a-z A-Z")
+            .await
+            .unwrap();
+        assert_eq!(result.stdout.trim(), "HTTP://PROXY.INTERNAL:3128");
+
+        let result = LocalExecutor::new()
+            .run("echo \"${TENDHOST_PROXY:-unset}\"")
+            .await
+            .unwrap();
+        assert_eq!(result.stdout.trim(), "unset");
+    }
+
     #[tokio::test]
     async fn test_run_with_stderr() {
         let executor = LocalExecutor::new();
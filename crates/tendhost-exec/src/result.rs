@@ -35,6 +35,12 @@ impl CommandResult {
     }
 }
 
+/// How long establishing an SSH connection may take by default
+pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How many commands may run on one SSH connection at once by default
+pub const DEFAULT_MAX_CHANNELS: usize = 4;
+
 /// Connection information for SSH
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConnectionInfo {
@@ -47,12 +53,30 @@ pub struct ConnectionInfo {
     pub user: String,
     /// Optional SSH key path
     pub ssh_key: Option<String>,
+    /// Timeout for the TCP connection and SSH handshake
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: Duration,
+    /// Most channels open on the connection at once; further commands wait
+    #[serde(default = "default_max_channels")]
+    pub max_channels: usize,
+    /// Jump host the connection is tunnelled through, like `ProxyJump`;
+    /// its own `jump` is ignored
+    #[serde(default)]
+    pub jump: Option<Box<ConnectionInfo>>,
 }
 
 fn default_port() -> u16 {
     22
 }
 
+fn default_connect_timeout() -> Duration {
+    DEFAULT_CONNECT_TIMEOUT
+}
+
+fn default_max_channels() -> usize {
+    DEFAULT_MAX_CHANNELS
+}
+
 impl ConnectionInfo {
     /// Create new connection info
     pub fn new(host: impl Into<String>, user: impl Into<String>) -> Self {
@@ -61,6 +85,9 @@ impl ConnectionInfo {
             port: 22,
             user: user.into(),
             ssh_key: None,
+            connect_timeout: DEFAULT_CONNECT_TIMEOUT,
+            max_channels: DEFAULT_MAX_CHANNELS,
+            jump: None,
         }
     }
 
@@ -77,4 +104,25 @@ impl ConnectionInfo {
         self.port = port;
         self
     }
+
+    /// Set the connect timeout
+    #[must_use]
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Set how many commands may run on the connection at once
+    #[must_use]
+    pub fn with_max_channels(mut self, max_channels: usize) -> Self {
+        self.max_channels = max_channels;
+        self
+    }
+
+    /// Connect through the jump host `jump`
+    #[must_use]
+    pub fn with_jump(mut self, jump: ConnectionInfo) -> Self {
+        self.jump = Some(Box::new(jump));
+        self
+    }
 }
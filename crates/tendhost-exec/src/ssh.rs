@@ -1,18 +1,20 @@
 //! SSH command execution using russh crate
 
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
+use russh::keys::PrivateKeyWithHashAlg;
 use russh::keys::ssh_key;
-use russh::keys::{PrivateKeyWithHashAlg, load_secret_key};
 use russh::{ChannelMsg, Disconnect, client};
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
 use tokio::time::timeout;
 use tracing::{debug, error, info, instrument};
 
+use crate::command::with_exports;
 use crate::error::ExecError;
-use crate::keys::{KeySource, ResolvedKey};
+use crate::keys::{KeySource, PassphraseSource, ResolvedKey, load_private_key};
 use crate::result::{CommandResult, ConnectionInfo};
 use crate::traits::RemoteExecutor;
 
@@ -33,17 +35,44 @@ impl client::Handler for SshClientHandler {
     }
 }
 
+/// Shared handle to an established SSH session
+type Session = Arc<client::Handle<SshClientHandler>>;
+
+/// Key for logging into the jump host, when it differs from the target's
+#[derive(Debug)]
+struct JumpKey {
+    /// Resolved SSH key
+    key: ResolvedKey,
+    /// Where the key's passphrase is read from, for encrypted keys
+    passphrase: Option<PassphraseSource>,
+}
+
 /// SSH command executor
 ///
 /// Manages an SSH session for remote command execution.
-/// Connections are established on first use.
+/// Connections are established on first use and re-established once the
+/// session has closed. Each command runs on its own channel of the shared
+/// session, so a long upgrade doesn't hold up a health check; at most
+/// [`ConnectionInfo::max_channels`] run at once.
 pub struct SshExecutor {
     /// Connection configuration
     conn_info: ConnectionInfo,
     /// Resolved SSH key
     key: ResolvedKey,
-    /// SSH session (initialized on first use)
-    session: Mutex<Option<client::Handle<SshClientHandler>>>,
+    /// Where the key's passphrase is read from, for encrypted keys
+    passphrase: Option<PassphraseSource>,
+    /// Key for [`ConnectionInfo::jump`]; the target's key if unset
+    jump_key: Option<JumpKey>,
+    /// SSH session (initialized on first use); locked only while connecting
+    session: Mutex<Option<Session>>,
+    /// Jump host session carrying the tunnel to the target, if any
+    bastion: Mutex<Option<Session>>,
+    /// Permits for open channels on the session
+    channels: Semaphore,
+    /// Variables exported for every command
+    env: BTreeMap<String, String>,
+    /// Time the last successful connection took, including the login
+    last_connect: std::sync::Mutex<Option<Duration>>,
 }
 
 impl std::fmt::Debug for SshExecutor {
@@ -70,102 +99,183 @@ impl SshExecutor {
             .resolve()
             .map_err(|e| ExecError::SshKeyError(e.to_string()))?;
 
+        let channels = Semaphore::new(conn_info.max_channels.max(1));
         Ok(Self {
             conn_info,
             key,
+            passphrase: key_source.passphrase().cloned(),
+            jump_key: None,
             session: Mutex::new(None),
+            bastion: Mutex::new(None),
+            channels,
+            env: BTreeMap::new(),
+            last_connect: std::sync::Mutex::new(None),
         })
     }
 
+    /// Log into the jump host with `key_source` instead of the target's key
+    ///
+    /// # Errors
+    /// Returns `ExecError::SshKeyError` if key resolution fails
+    pub fn with_jump_key(mut self, key_source: &KeySource) -> Result<Self, ExecError> {
+        let key = key_source
+            .resolve()
+            .map_err(|e| ExecError::SshKeyError(e.to_string()))?;
+        self.jump_key = Some(JumpKey {
+            key,
+            passphrase: key_source.passphrase().cloned(),
+        });
+        Ok(self)
+    }
+
+    /// Export `env` for every command
+    ///
+    /// SSH servers only accept the variables their `AcceptEnv` lists, so
+    /// the variables are exported at the start of each command line
+    /// instead of being sent with the channel. Values are never logged.
+    #[must_use]
+    pub fn with_env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
     /// Get connection info
     pub fn connection_info(&self) -> &ConnectionInfo {
         &self.conn_info
     }
 
-    /// Connect to the remote host
+    /// The line sent to the host to run `cmd`, with the variables exported
+    #[must_use]
+    pub fn command_line(&self, cmd: &str) -> String {
+        with_exports(&self.env, cmd)
+    }
+
+    /// Connect to the remote host, returning the open session
+    ///
+    /// Concurrent callers wait for a single connection attempt.
     #[instrument(skip(self), fields(host = %self.conn_info.host))]
-    async fn connect(&self) -> Result<(), ExecError> {
+    async fn connect(&self) -> Result<Session, ExecError> {
         let mut session_lock = self.session.lock().await;
 
-        if session_lock.is_some() {
-            return Ok(());
+        if let Some(session) = session_lock.as_ref() {
+            if !session.is_closed() {
+                return Ok(session.clone());
+            }
+            debug!(host = %self.conn_info.host, "SSH session closed, reconnecting");
+            *session_lock = None;
         }
 
         info!(
             host = %self.conn_info.host,
             port = self.conn_info.port,
             user = %self.conn_info.user,
+            jump = ?self.conn_info.jump.as_ref().map(|jump| &jump.host),
             "connecting to SSH"
         );
 
-        // Configure client
-        let config = client::Config::default();
-        let config = Arc::new(config);
+        let started = Instant::now();
+        let mut session = match self.conn_info.jump.as_deref() {
+            Some(jump) => self.connect_through(jump).await?,
+            None => dial(&self.conn_info).await?,
+        };
+        authenticate(
+            &mut session,
+            &self.conn_info.user,
+            &self.key,
+            self.passphrase.as_ref(),
+        )
+        .await?;
+
+        let took = started.elapsed();
+        info!(host = %self.conn_info.host, ?took, "SSH connected and authenticated");
+        *self
+            .last_connect
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(took);
 
-        // Create handler
-        let handler = SshClientHandler;
+        let session = Arc::new(session);
+        *session_lock = Some(session.clone());
+        Ok(session)
+    }
 
-        // Connect
-        let mut session = client::connect(
-            config,
-            (&self.conn_info.host[..], self.conn_info.port),
-            handler,
-        )
-        .await
-        .map_err(|e| ExecError::ConnectionFailed(e.to_string()))?;
-
-        // Authenticate
-        if self.key.use_agent() {
-            // SSH agent authentication - try loading keys from agent
-            // For now, fall through to try key-based auth or fail
-            // TODO: Implement proper SSH agent support with pageant
-            return Err(ExecError::AuthenticationFailed(
-                "SSH agent authentication not yet implemented".to_string(),
-            ));
-        } else if let Some(key_path) = self.key.path() {
-            // Load private key and authenticate
-            let key_pair = load_secret_key(key_path, None)
-                .map_err(|e| ExecError::SshKeyError(e.to_string()))?;
-
-            let hash_alg = session
-                .best_supported_rsa_hash()
-                .await
-                .ok()
-                .flatten()
-                .flatten();
-            let auth_res = session
-                .authenticate_publickey(
-                    &self.conn_info.user,
-                    PrivateKeyWithHashAlg::new(Arc::new(key_pair), hash_alg),
-                )
-                .await
-                .map_err(|e| ExecError::AuthenticationFailed(e.to_string()))?;
+    /// Log into `jump` and tunnel to the target, returning the target
+    /// session before authentication
+    ///
+    /// The jump session is kept for as long as the tunnel is in use.
+    /// Failures name the hop that failed: the jump host itself, or the
+    /// target behind it.
+    async fn connect_through(
+        &self,
+        jump: &ConnectionInfo,
+    ) -> Result<client::Handle<SshClientHandler>, ExecError> {
+        let jump_addr = format!("{}:{}", jump.host, jump.port);
+        let jump_failed = |source: ExecError| ExecError::JumpHostFailed {
+            jump: jump_addr.clone(),
+            source: Box::new(source),
+        };
 
-            if !auth_res.success() {
-                return Err(ExecError::AuthenticationFailed(
-                    "Public key authentication failed".to_string(),
-                ));
-            }
-        } else {
-            return Err(ExecError::AuthenticationFailed(
-                "No authentication method available".to_string(),
-            ));
-        }
+        let mut bastion = dial(jump).await.map_err(jump_failed)?;
+        let (key, passphrase) = match &self.jump_key {
+            Some(jump_key) => (&jump_key.key, jump_key.passphrase.as_ref()),
+            None => (&self.key, self.passphrase.as_ref()),
+        };
+        authenticate(&mut bastion, &jump.user, key, passphrase)
+            .await
+            .map_err(jump_failed)?;
+        debug!(jump = %jump_addr, "jump host connected, opening tunnel");
 
-        info!(host = %self.conn_info.host, "SSH connected and authenticated");
+        let target = &self.conn_info;
+        let connect_timeout = target.connect_timeout;
+        let unreachable = |message: String| ExecError::TargetUnreachable {
+            jump: jump_addr.clone(),
+            message,
+        };
+        let session = timeout(connect_timeout, async {
+            let channel = bastion
+                .channel_open_direct_tcpip(
+                    target.host.clone(),
+                    u32::from(target.port),
+                    "127.0.0.1",
+                    0,
+                )
+                .await?;
+            client::connect_stream(
+                Arc::new(client::Config::default()),
+                channel.into_stream(),
+                SshClientHandler,
+            )
+            .await
+        })
+        .await
+        .map_err(|_| unreachable(format!("timed out after {connect_timeout:?}")))?
+        .map_err(|e| unreachable(e.to_string()))?;
 
-        *session_lock = Some(session);
-        Ok(())
+        *self.bastion.lock().await = Some(Arc::new(bastion));
+        Ok(session)
     }
 
     /// Execute command on remote host
-    #[instrument(skip(self, cmd), fields(host = %self.conn_info.host))]
-    async fn execute_remote(&self, cmd: &str) -> Result<CommandResult, ExecError> {
-        let mut session_lock = self.session.lock().await;
-
-        let session = session_lock.as_mut().ok_or(ExecError::NotConnected)?;
-
-        debug!(command = %cmd, "executing remote command");
+    ///
+    /// Waits for a free channel first; the wait counts towards the caller's
+    /// timeout.
+    #[instrument(skip(self, session, cmd), fields(host = %self.conn_info.host))]
+    async fn execute_remote(
+        &self,
+        session: &Session,
+        cmd: &str,
+    ) -> Result<CommandResult, ExecError> {
+        let waiting = Instant::now();
+        let _permit = self
+            .channels
+            .acquire()
+            .await
+            .map_err(|_| ExecError::NotConnected)?;
+        debug!(
+            command = %cmd,
+            waited = ?waiting.elapsed(),
+            free_channels = self.channels.available_permits(),
+            "executing remote command"
+        );
 
         let start = Instant::now();
 
@@ -175,9 +285,10 @@ impl SshExecutor {
             .await
             .map_err(|e| ExecError::IoError(e.to_string()))?;
 
-        // Execute command
+        // Execute command; only `cmd` is logged, as the variables may hold
+        // credentials
         channel
-            .exec(true, cmd)
+            .exec(true, self.command_line(cmd))
             .await
             .map_err(|e| ExecError::IoError(e.to_string()))?;
 
@@ -193,11 +304,9 @@ impl SshExecutor {
                 Some(ChannelMsg::Data { data }) => {
                     stdout.extend_from_slice(&data);
                 }
-                Some(ChannelMsg::ExtendedData { data, ext }) => {
-                    if ext == 1 {
-                        // stderr
-                        stderr.extend_from_slice(&data);
-                    }
+                // Extended data stream 1 is stderr
+                Some(ChannelMsg::ExtendedData { data, ext: 1 }) => {
+                    stderr.extend_from_slice(&data);
                 }
                 Some(ChannelMsg::ExitStatus { exit_status }) => {
                     status = exit_status.cast_signed();
@@ -240,16 +349,91 @@ impl SshExecutor {
                 .map_err(|e| ExecError::IoError(e.to_string()))?;
             info!(host = %self.conn_info.host, "SSH disconnected");
         }
+        if let Some(bastion) = self.bastion.lock().await.take() {
+            bastion
+                .disconnect(Disconnect::ByApplication, "", "English")
+                .await
+                .map_err(|e| ExecError::IoError(e.to_string()))?;
+        }
         Ok(())
     }
 }
 
+/// Open a TCP connection to `info` and run the SSH handshake
+///
+/// An unresponsive address would otherwise hang until the OS gives up on
+/// the TCP handshake, so the whole attempt is bounded by the connect
+/// timeout.
+async fn dial(info: &ConnectionInfo) -> Result<client::Handle<SshClientHandler>, ExecError> {
+    let config = Arc::new(client::Config::default());
+    timeout(
+        info.connect_timeout,
+        client::connect(config, (&info.host[..], info.port), SshClientHandler),
+    )
+    .await
+    .map_err(|_| ExecError::ConnectTimeout {
+        timeout: info.connect_timeout,
+    })?
+    .map_err(|e| ExecError::ConnectionFailed(e.to_string()))
+}
+
+/// Log into `session` as `user` with `key`
+async fn authenticate(
+    session: &mut client::Handle<SshClientHandler>,
+    user: &str,
+    key: &ResolvedKey,
+    passphrase: Option<&PassphraseSource>,
+) -> Result<(), ExecError> {
+    if key.use_agent() {
+        // SSH agent authentication - try loading keys from agent
+        // For now, fall through to try key-based auth or fail
+        // TODO: Implement proper SSH agent support with pageant
+        return Err(ExecError::AuthenticationFailed(
+            "SSH agent authentication not yet implemented".to_string(),
+        ));
+    }
+    let Some(key_path) = key.path() else {
+        return Err(ExecError::AuthenticationFailed(
+            "No authentication method available".to_string(),
+        ));
+    };
+
+    // Load private key and authenticate
+    let passphrase = passphrase
+        .map(PassphraseSource::read)
+        .transpose()
+        .map_err(|e| ExecError::SshKeyError(e.to_string()))?;
+    let key_pair = load_private_key(key_path, passphrase.as_deref())
+        .map_err(|e| ExecError::SshKeyError(e.to_string()))?;
+
+    let hash_alg = session
+        .best_supported_rsa_hash()
+        .await
+        .ok()
+        .flatten()
+        .flatten();
+    let auth_res = session
+        .authenticate_publickey(
+            user,
+            PrivateKeyWithHashAlg::new(Arc::new(key_pair), hash_alg),
+        )
+        .await
+        .map_err(|e| ExecError::AuthenticationFailed(e.to_string()))?;
+
+    if !auth_res.success() {
+        return Err(ExecError::AuthenticationFailed(
+            "Public key authentication failed".to_string(),
+        ));
+    }
+    Ok(())
+}
+
 #[async_trait]
 impl RemoteExecutor for SshExecutor {
     #[instrument(skip(self), fields(host = %self.conn_info.host))]
     async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
-        self.connect().await?;
-        self.execute_remote(cmd).await
+        let session = self.connect().await?;
+        self.execute_remote(&session, cmd).await
     }
 
     #[instrument(skip(self), fields(host = %self.conn_info.host))]
@@ -263,10 +447,10 @@ impl RemoteExecutor for SshExecutor {
         debug!(command = %cmd, timeout = ?timeout_duration, "executing with timeout");
 
         // Ensure connection first (outside of timeout)
-        self.connect().await?;
+        let session = self.connect().await?;
 
         // Execute with timeout
-        let result = timeout(timeout_duration, self.execute_remote(cmd)).await;
+        let result = timeout(timeout_duration, self.execute_remote(&session, cmd)).await;
 
         match result {
             Ok(Ok(cmd_result)) => Ok(cmd_result),
@@ -286,11 +470,24 @@ impl RemoteExecutor for SshExecutor {
     }
 
     fn is_connected(&self) -> bool {
-        // Check if session is Some
         // Note: This is a synchronous check, the actual connection
-        // state can only be verified by trying to use the connection
+        // state can only be verified by trying to use the connection.
+        // The lock is only busy while connecting.
         let session_opt = self.session.try_lock();
-        session_opt.map(|s| s.is_some()).unwrap_or(false)
+        session_opt
+            .map(|s| s.as_ref().is_some_and(|s| !s.is_closed()))
+            .unwrap_or(false)
+    }
+
+    fn last_connect_duration(&self) -> Option<Duration> {
+        *self
+            .last_connect
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn env_names(&self) -> Vec<String> {
+        self.env.keys().cloned().collect()
     }
 
     fn executor_type(&self) -> &'static str {
@@ -302,6 +499,9 @@ impl RemoteExecutor for SshExecutor {
 pub struct SshExecutorBuilder {
     conn_info: ConnectionInfo,
     key_source: KeySource,
+    passphrase: Option<PassphraseSource>,
+    jump_key_source: Option<KeySource>,
+    env: BTreeMap<String, String>,
 }
 
 impl SshExecutorBuilder {
@@ -310,6 +510,9 @@ impl SshExecutorBuilder {
         Self {
             conn_info: ConnectionInfo::new(host, user),
             key_source: KeySource::Agent, // Default to agent
+            passphrase: None,
+            jump_key_source: None,
+            env: BTreeMap::new(),
         }
     }
 
@@ -320,6 +523,24 @@ impl SshExecutorBuilder {
         self
     }
 
+    /// Read the key file's passphrase from environment variable `var_name`
+    ///
+    /// Only applies to keys set with [`with_key_path`](Self::with_key_path).
+    #[must_use]
+    pub fn with_key_passphrase_env(mut self, var_name: impl Into<String>) -> Self {
+        self.passphrase = Some(PassphraseSource::Env(var_name.into()));
+        self
+    }
+
+    /// Read the key file's passphrase from the file at `path`
+    ///
+    /// Only applies to keys set with [`with_key_path`](Self::with_key_path).
+    #[must_use]
+    pub fn with_key_passphrase_file(mut self, path: impl Into<std::path::PathBuf>) -> Self {
+        self.passphrase = Some(PassphraseSource::File(path.into()));
+        self
+    }
+
     /// Use SSH agent
     #[must_use]
     pub fn with_agent(mut self) -> Self {
@@ -341,17 +562,506 @@ impl SshExecutorBuilder {
         self
     }
 
+    /// Set the timeout for establishing the connection
+    #[must_use]
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.conn_info.connect_timeout = timeout;
+        if let Some(jump) = self.conn_info.jump.as_mut() {
+            jump.connect_timeout = timeout;
+        }
+        self
+    }
+
+    /// Set how many commands may run on the connection at once
+    #[must_use]
+    pub fn with_max_channels(mut self, max_channels: usize) -> Self {
+        self.conn_info.max_channels = max_channels;
+        self
+    }
+
+    /// Connect through the jump host `host` as `user`, like `ProxyJump`
+    ///
+    /// The jump host is logged into with `key_source`, which may differ
+    /// from the target's key. It shares the target's connect timeout.
+    #[must_use]
+    pub fn with_jump_host(
+        mut self,
+        host: impl Into<String>,
+        user: impl Into<String>,
+        key_source: KeySource,
+    ) -> Self {
+        let jump =
+            ConnectionInfo::new(host, user).with_connect_timeout(self.conn_info.connect_timeout);
+        self.conn_info.jump = Some(Box::new(jump));
+        self.jump_key_source = Some(key_source);
+        self
+    }
+
+    /// Export `env` for every command, see [`SshExecutor::with_env`]
+    #[must_use]
+    pub fn with_env(mut self, env: BTreeMap<String, String>) -> Self {
+        self.env = env;
+        self
+    }
+
+    /// Set the jump host's port
+    ///
+    /// Only applies after [`with_jump_host`](Self::with_jump_host).
+    #[must_use]
+    pub fn with_jump_port(mut self, port: u16) -> Self {
+        if let Some(jump) = self.conn_info.jump.as_mut() {
+            jump.port = port;
+        }
+        self
+    }
+
     /// Build the executor
     ///
     /// # Errors
-    /// Returns `ExecError::SshKeyError` if key resolution fails
+    /// Returns `ExecError::SshKeyError` if key resolution fails, or a
+    /// passphrase was set for a key that isn't read from a file
     pub fn build(self) -> Result<SshExecutor, ExecError> {
-        SshExecutor::new(self.conn_info, &self.key_source)
+        let key_source = match (self.key_source, self.passphrase) {
+            (KeySource::Path(path), Some(passphrase)) => {
+                KeySource::PathWithPassphrase { path, passphrase }
+            }
+            (_, Some(_)) => {
+                return Err(ExecError::SshKeyError(
+                    "a key passphrase needs a key file path".to_string(),
+                ));
+            }
+            (key_source, None) => key_source,
+        };
+        let executor = SshExecutor::new(self.conn_info, &key_source)?.with_env(self.env);
+        match self.jump_key_source {
+            Some(jump_key_source) => executor.with_jump_key(&jump_key_source),
+            None => Ok(executor),
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::result::DEFAULT_MAX_CHANNELS;
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        // Accepts TCP connections but never sends an SSH banner
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+
+        let executor = SshExecutorBuilder::new("127.0.0.1", "root")
+            .with_port(port)
+            .with_connect_timeout(Duration::from_millis(200))
+            .build()
+            .unwrap();
+
+        let err = executor.run("true").await.unwrap_err();
+        assert!(
+            matches!(err, ExecError::ConnectTimeout { timeout } if timeout == Duration::from_millis(200)),
+            "unexpected error: {err}"
+        );
+        assert!(err.is_retryable());
+        server.abort();
+    }
+
+    mod server {
+        //! Minimal in-process SSH server whose only command is `sleep <secs>`
+        //!
+        //! It also forwards `direct-tcpip` channels, so it can serve as a
+        //! jump host.
+
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use russh::keys::ssh_key::rand_core::OsRng;
+        use russh::keys::ssh_key::{Algorithm, LineEnding, PrivateKey};
+        use russh::server::{self, Auth, Msg, Session};
+        use russh::{Channel, ChannelId, CryptoVec};
+
+        use super::*;
+
+        /// Commands running now and the most that ever ran at once
+        #[derive(Clone, Default)]
+        pub struct Load {
+            running: Arc<AtomicUsize>,
+            pub peak: Arc<AtomicUsize>,
+        }
+
+        #[derive(Clone)]
+        struct SleepHandler {
+            load: Load,
+            /// Only key accepted for logins, or any key if unset
+            allowed: Option<ssh_key::PublicKey>,
+        }
+
+        impl server::Handler for SleepHandler {
+            type Error = russh::Error;
+
+            async fn auth_publickey(
+                &mut self,
+                _user: &str,
+                key: &ssh_key::PublicKey,
+            ) -> Result<Auth, Self::Error> {
+                if self.allowed.as_ref().is_none_or(|allowed| allowed == key) {
+                    Ok(Auth::Accept)
+                } else {
+                    Ok(Auth::reject())
+                }
+            }
+
+            async fn channel_open_direct_tcpip(
+                &mut self,
+                channel: Channel<Msg>,
+                host_to_connect: &str,
+                port_to_connect: u32,
+                _originator_address: &str,
+                _originator_port: u32,
+                _session: &mut Session,
+            ) -> Result<bool, Self::Error> {
+                let Ok(port) = u16::try_from(port_to_connect) else {
+                    return Ok(false);
+                };
+                let Ok(mut target) = tokio::net::TcpStream::connect((host_to_connect, port)).await
+                else {
+                    return Ok(false);
+                };
+                tokio::spawn(async move {
+                    let mut stream = channel.into_stream();
+                    let _ = tokio::io::copy_bidirectional(&mut stream, &mut target).await;
+                });
+                Ok(true)
+            }
+
+            async fn channel_open_session(
+                &mut self,
+                _channel: Channel<Msg>,
+                _session: &mut Session,
+            ) -> Result<bool, Self::Error> {
+                Ok(true)
+            }
+
+            async fn exec_request(
+                &mut self,
+                channel: ChannelId,
+                data: &[u8],
+                session: &mut Session,
+            ) -> Result<(), Self::Error> {
+                let secs: f64 = String::from_utf8_lossy(data)
+                    .strip_prefix("sleep ")
+                    .and_then(|secs| secs.parse().ok())
+                    .unwrap_or(0.0);
+                session.channel_success(channel)?;
+
+                let handle = session.handle();
+                let load = self.load.clone();
+                tokio::spawn(async move {
+                    let now = load.running.fetch_add(1, Ordering::SeqCst) + 1;
+                    load.peak.fetch_max(now, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+                    load.running.fetch_sub(1, Ordering::SeqCst);
+
+                    let _ = handle.data(channel, CryptoVec::from("done\n")).await;
+                    let _ = handle.exit_status_request(channel, 0).await;
+                    let _ = handle.eof(channel).await;
+                    let _ = handle.close(channel).await;
+                });
+                Ok(())
+            }
+        }
+
+        /// Start the server, returning its port
+        pub async fn spawn(load: Load) -> u16 {
+            spawn_for(load, None).await
+        }
+
+        /// Start a server that only accepts logins with `allowed`
+        pub async fn spawn_for(load: Load, allowed: Option<ssh_key::PublicKey>) -> u16 {
+            let config = Arc::new(server::Config {
+                keys: vec![PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap()],
+                auth_rejection_time: Duration::ZERO,
+                auth_rejection_time_initial: Some(Duration::ZERO),
+                ..Default::default()
+            });
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let port = listener.local_addr().unwrap().port();
+
+            tokio::spawn(async move {
+                while let Ok((socket, _)) = listener.accept().await {
+                    let handler = SleepHandler {
+                        load: load.clone(),
+                        allowed: allowed.clone(),
+                    };
+                    let config = config.clone();
+                    tokio::spawn(async move {
+                        if let Ok(session) = server::run_stream(config, socket, handler).await {
+                            let _ = session.await;
+                        }
+                    });
+                }
+            });
+            port
+        }
+
+        /// Write a fresh client key with owner-only permissions
+        /// Write a fresh client key, encrypted when `passphrase` is set
+        pub fn write_client_key(name: &str, passphrase: Option<&str>) -> std::path::PathBuf {
+            let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+            if let Some(passphrase) = passphrase {
+                key = key.encrypt(&mut OsRng, passphrase).unwrap();
+            }
+            write_private_file(name, key.to_openssh(LineEnding::LF).unwrap().as_bytes())
+        }
+
+        /// Write a fresh unencrypted client key, returning its path and
+        /// public key
+        pub fn write_client_key_pair(name: &str) -> (std::path::PathBuf, ssh_key::PublicKey) {
+            let key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+            let path = write_private_file(name, key.to_openssh(LineEnding::LF).unwrap().as_bytes());
+            (path, key.public_key().clone())
+        }
+
+        /// A local port nothing listens on
+        pub async fn closed_port() -> u16 {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        }
+
+        /// Write `contents` to a temp file only the owner can read
+        pub fn write_private_file(name: &str, contents: &[u8]) -> std::path::PathBuf {
+            use std::io::Write;
+            #[cfg(unix)]
+            use std::os::unix::fs::OpenOptionsExt;
+
+            let path = std::env::temp_dir()
+                .join(format!("tendhost-ssh-test-{}-{name}", std::process::id()));
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create(true).truncate(true);
+            #[cfg(unix)]
+            options.mode(0o600);
+            options.open(&path).unwrap().write_all(contents).unwrap();
+            path
+        }
+    }
+
+    async fn run_two_sleeps(max_channels: usize, name: &str) -> usize {
+        let load = server::Load::default();
+        let port = server::spawn(load.clone()).await;
+        let key = server::write_client_key(name, None);
+        let executor = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_port(port)
+            .with_key_path(&key)
+            .with_max_channels(max_channels)
+            .build()
+            .unwrap();
+
+        assert_eq!(executor.last_connect_duration(), None);
+        let (a, b) = tokio::join!(executor.run("sleep 0.3"), executor.run("sleep 0.3"));
+        let _ = std::fs::remove_file(&key);
+        assert!(executor.last_connect_duration().is_some());
+
+        for result in [a.unwrap(), b.unwrap()] {
+            assert!(result.success());
+            assert_eq!(result.stdout, "done\n");
+        }
+        assert!(executor.is_connected());
+        load.peak.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_commands_share_the_session_concurrently() {
+        assert_eq!(run_two_sleeps(DEFAULT_MAX_CHANNELS, "shared").await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_channel_cap_serializes_commands() {
+        assert_eq!(run_two_sleeps(1, "capped").await, 1);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_key_authenticates_with_passphrase_file() {
+        let port = server::spawn(server::Load::default()).await;
+        let key = server::write_client_key("encrypted", Some("s3cret"));
+        let passphrase = server::write_private_file("encrypted-passphrase", b"s3cret\n");
+
+        let executor = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_port(port)
+            .with_key_path(&key)
+            .with_key_passphrase_file(&passphrase)
+            .build()
+            .unwrap();
+        let result = executor.run("sleep 0").await;
+
+        let without_passphrase = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_port(port)
+            .with_key_path(&key)
+            .build()
+            .unwrap();
+        let err = without_passphrase.run("sleep 0").await.unwrap_err();
+
+        let _ = std::fs::remove_file(&key);
+        let _ = std::fs::remove_file(&passphrase);
+        assert!(result.unwrap().success());
+        assert!(
+            matches!(&err, ExecError::SshKeyError(msg) if msg.contains("is encrypted")),
+            "unexpected error: {err}"
+        );
+    }
+
+    #[test]
+    fn test_env_is_exported_before_each_command() {
+        let executor = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_env(BTreeMap::from([
+                ("https_proxy".to_string(), "http://proxy:3128".to_string()),
+                ("no_proxy".to_string(), "localhost, .internal".to_string()),
+            ]))
+            .build()
+            .unwrap();
+        assert_eq!(
+            executor.command_line("sudo -n apt-get update"),
+            "export https_proxy=http://proxy:3128 no_proxy='localhost, .internal'; \
+             sudo -n apt-get update"
+        );
+
+        let plain = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .build()
+            .unwrap();
+        assert_eq!(plain.command_line("uptime"), "uptime");
+    }
+
+    #[test]
+    fn test_passphrase_needs_a_key_path() {
+        let err = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_key_passphrase_env("TENDHOST_KEY_PASSPHRASE")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, ExecError::SshKeyError(_)));
+    }
+
+    #[tokio::test]
+    async fn test_jump_host_tunnels_with_its_own_key() {
+        let (target_key, target_public) = server::write_client_key_pair("jump-target");
+        let (jump_key, jump_public) = server::write_client_key_pair("jump-bastion");
+        let target_port = server::spawn_for(server::Load::default(), Some(target_public)).await;
+        let jump_port = server::spawn_for(server::Load::default(), Some(jump_public)).await;
+
+        let executor = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_port(target_port)
+            .with_key_path(&target_key)
+            .with_jump_host("127.0.0.1", "jump", KeySource::Path(jump_key.clone()))
+            .with_jump_port(jump_port)
+            .build()
+            .unwrap();
+        let result = executor.run("sleep 0").await;
+
+        // Each hop only accepts its own key
+        let same_key = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_port(target_port)
+            .with_key_path(&target_key)
+            .with_jump_host("127.0.0.1", "jump", KeySource::Path(target_key.clone()))
+            .with_jump_port(jump_port)
+            .build()
+            .unwrap();
+        let err = same_key.run("sleep 0").await.unwrap_err();
+
+        let _ = std::fs::remove_file(&target_key);
+        let _ = std::fs::remove_file(&jump_key);
+        let result = result.unwrap();
+        assert!(result.success());
+        assert_eq!(result.stdout, "done\n");
+        assert!(executor.is_connected());
+        assert!(
+            matches!(&err, ExecError::JumpHostFailed { source, .. }
+                if matches!(**source, ExecError::AuthenticationFailed(_))),
+            "unexpected error: {err}"
+        );
+        assert!(!err.is_retryable());
+    }
+
+    #[tokio::test]
+    async fn test_jump_host_failures_name_the_hop() {
+        let key = server::write_client_key("jump-hops", None);
+        let jump_port = server::spawn(server::Load::default()).await;
+        let closed = server::closed_port().await;
+
+        let jump_down = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_port(jump_port)
+            .with_key_path(&key)
+            .with_jump_host("127.0.0.1", "jump", KeySource::Path(key.clone()))
+            .with_jump_port(closed)
+            .build()
+            .unwrap();
+        let jump_err = jump_down.run("sleep 0").await.unwrap_err();
+
+        let target_down = SshExecutorBuilder::new("127.0.0.1", "tend")
+            .with_port(closed)
+            .with_key_path(&key)
+            .with_jump_host("127.0.0.1", "jump", KeySource::Path(key.clone()))
+            .with_jump_port(jump_port)
+            .build()
+            .unwrap();
+        let target_err = target_down.run("sleep 0").await.unwrap_err();
+
+        let _ = std::fs::remove_file(&key);
+        assert!(
+            matches!(&jump_err, ExecError::JumpHostFailed { source, .. }
+                if matches!(**source, ExecError::ConnectionFailed(_))),
+            "unexpected error: {jump_err}"
+        );
+        assert!(jump_err.is_retryable());
+        assert!(
+            matches!(target_err, ExecError::TargetUnreachable { .. }),
+            "unexpected error: {target_err}"
+        );
+        assert!(target_err.to_string().starts_with(&format!(
+            "reached jump host 127.0.0.1:{jump_port}, but not the target"
+        )));
+        assert!(target_err.is_retryable());
+    }
+
+    /// Run against a real OpenSSH pair, e.g.
+    ///
+    /// ```sh
+    /// docker network create tendhost-jump
+    /// docker run -d --name target --network tendhost-jump \
+    ///     -e PUBLIC_KEY="$(cat ~/.ssh/id_ed25519.pub)" -e USER_NAME=tend \
+    ///     lscr.io/linuxserver/openssh-server
+    /// docker run -d --name bastion --network tendhost-jump -p 2222:2222 \
+    ///     -e PUBLIC_KEY="$(cat ~/.ssh/id_ed25519.pub)" -e USER_NAME=tend \
+    ///     -e DOCKER_MODS=linuxserver/mods:openssh-server-ssh-tunnel \
+    ///     lscr.io/linuxserver/openssh-server
+    /// TENDHOST_TEST_SSH_KEY=~/.ssh/id_ed25519 cargo test -p tendhost-exec -- --ignored
+    /// ```
+    #[tokio::test]
+    #[ignore = "requires a dockerized sshd pair"]
+    async fn test_jump_host_against_openssh() {
+        let env = |name: &str, default: &str| std::env::var(name).unwrap_or(default.to_string());
+        let key = std::path::PathBuf::from(env("TENDHOST_TEST_SSH_KEY", "id_ed25519"));
+        let user = env("TENDHOST_TEST_SSH_USER", "tend");
+        let port = env("TENDHOST_TEST_JUMP_PORT", "2222").parse().unwrap();
+
+        let executor = SshExecutorBuilder::new(env("TENDHOST_TEST_TARGET", "target"), &user)
+            .with_port(2222)
+            .with_key_path(&key)
+            .with_jump_host(
+                env("TENDHOST_TEST_JUMP", "127.0.0.1"),
+                &user,
+                KeySource::Path(key.clone()),
+            )
+            .with_jump_port(port)
+            .build()
+            .unwrap();
+        let result = executor.run("hostname").await.unwrap();
+        assert!(result.success());
+        assert_eq!(result.stdout.trim(), "target");
+    }
+
     // These tests require an SSH server - marked as ignored
     #[tokio::test]
     #[ignore = "requires SSH server"]
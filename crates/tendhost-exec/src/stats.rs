@@ -0,0 +1,347 @@
+//! Per-host command statistics
+//!
+//! [`InstrumentedExecutor`] wraps another executor and counts its commands,
+//! failures and durations in a shared [`ExecutorStats`], so a host whose
+//! connection is slow or flaky shows it before an update fails on it.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ExecError;
+use crate::result::CommandResult;
+use crate::traits::RemoteExecutor;
+
+/// Number of recent commands the duration percentiles cover
+pub const DEFAULT_STATS_WINDOW: usize = 100;
+
+/// The last `capacity` durations, in a fixed-size ring buffer
+#[derive(Debug, Clone)]
+pub struct RollingDurations {
+    samples: Vec<Duration>,
+    /// Slot the next sample overwrites once full
+    next: usize,
+    capacity: usize,
+}
+
+impl RollingDurations {
+    /// Create a window of at most `capacity` durations (at least one)
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            samples: Vec::with_capacity(capacity),
+            next: 0,
+            capacity,
+        }
+    }
+
+    /// Add a duration, replacing the oldest once full
+    pub fn push(&mut self, duration: Duration) {
+        if self.samples.len() < self.capacity {
+            self.samples.push(duration);
+        } else {
+            self.samples[self.next] = duration;
+        }
+        self.next = (self.next + 1) % self.capacity;
+    }
+
+    /// Number of durations held
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Whether no duration has been added yet
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The `percent`th percentile by nearest rank, e.g. 95 for p95
+    ///
+    /// This is always one of the held durations: the smallest one that at
+    /// least `percent`% of them don't exceed. `None` while empty.
+    #[must_use]
+    pub fn percentile(&self, percent: u8) -> Option<Duration> {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        let rank = (usize::from(percent.min(100)) * sorted.len()).div_ceil(100);
+        sorted.get(rank.max(1) - 1).copied()
+    }
+}
+
+/// Statistics of one host's commands at a moment
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutorStatsSnapshot {
+    /// Commands run since the executor was created
+    pub commands: u64,
+    /// Commands that could not be run or timed out
+    pub failures: u64,
+    /// Median duration of the recent commands
+    pub p50: Option<Duration>,
+    /// 95th percentile duration of the recent commands
+    pub p95: Option<Duration>,
+    /// Number of recent commands the percentiles cover
+    pub window: usize,
+    /// How long the last successful connection took to establish
+    pub last_connect: Option<Duration>,
+    /// Commands in a row that failed to reach the host
+    pub consecutive_connection_failures: u32,
+}
+
+#[derive(Debug)]
+struct StatsInner {
+    commands: u64,
+    failures: u64,
+    durations: RollingDurations,
+    last_connect: Option<Duration>,
+    consecutive_connection_failures: u32,
+}
+
+/// Shared statistics an [`InstrumentedExecutor`] records into
+#[derive(Debug)]
+pub struct ExecutorStats {
+    inner: Mutex<StatsInner>,
+}
+
+impl Default for ExecutorStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_STATS_WINDOW)
+    }
+}
+
+impl ExecutorStats {
+    /// Create statistics whose percentiles cover the last `window` commands
+    #[must_use]
+    pub fn new(window: usize) -> Self {
+        Self {
+            inner: Mutex::new(StatsInner {
+                commands: 0,
+                failures: 0,
+                durations: RollingDurations::new(window),
+                last_connect: None,
+                consecutive_connection_failures: 0,
+            }),
+        }
+    }
+
+    /// Count a finished command
+    ///
+    /// Commands that reached the host, whatever their exit status, end a
+    /// run of connection failures; other failures such as timeouts leave
+    /// it as it is.
+    pub fn record(
+        &self,
+        duration: Duration,
+        result: &Result<CommandResult, ExecError>,
+        last_connect: Option<Duration>,
+    ) {
+        let mut inner = self.lock();
+        inner.commands = inner.commands.saturating_add(1);
+        inner.durations.push(duration);
+        if last_connect.is_some() {
+            inner.last_connect = last_connect;
+        }
+        match result {
+            Ok(_) => inner.consecutive_connection_failures = 0,
+            Err(e) => {
+                inner.failures = inner.failures.saturating_add(1);
+                if e.is_connection_failure() {
+                    inner.consecutive_connection_failures =
+                        inner.consecutive_connection_failures.saturating_add(1);
+                }
+            }
+        }
+    }
+
+    /// Copy of the statistics so far
+    #[must_use]
+    pub fn snapshot(&self) -> ExecutorStatsSnapshot {
+        let inner = self.lock();
+        ExecutorStatsSnapshot {
+            commands: inner.commands,
+            failures: inner.failures,
+            p50: inner.durations.percentile(50),
+            p95: inner.durations.percentile(95),
+            window: inner.durations.len(),
+            last_connect: inner.last_connect,
+            consecutive_connection_failures: inner.consecutive_connection_failures,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StatsInner> {
+        // Counters are plain data, so a poisoned lock is still usable
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Executor that records statistics of every command it delegates
+pub struct InstrumentedExecutor {
+    inner: Arc<dyn RemoteExecutor>,
+    stats: Arc<ExecutorStats>,
+}
+
+impl InstrumentedExecutor {
+    /// Wrap `inner`, recording into `stats`
+    #[must_use]
+    pub fn new(inner: Arc<dyn RemoteExecutor>, stats: Arc<ExecutorStats>) -> Self {
+        Self { inner, stats }
+    }
+
+    /// Statistics this executor records into
+    #[must_use]
+    pub fn stats(&self) -> &Arc<ExecutorStats> {
+        &self.stats
+    }
+
+    fn record(&self, started: Instant, result: &Result<CommandResult, ExecError>) {
+        self.stats.record(
+            started.elapsed(),
+            result,
+            self.inner.last_connect_duration(),
+        );
+    }
+}
+
+#[async_trait]
+impl RemoteExecutor for InstrumentedExecutor {
+    async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+        let started = Instant::now();
+        let result = self.inner.run(cmd).await;
+        self.record(started, &result);
+        result
+    }
+
+    async fn run_with_timeout(
+        &self,
+        cmd: &str,
+        timeout: Duration,
+    ) -> Result<CommandResult, ExecError> {
+        let started = Instant::now();
+        let result = self.inner.run_with_timeout(cmd, timeout).await;
+        self.record(started, &result);
+        result
+    }
+
+    fn is_connected(&self) -> bool {
+        self.inner.is_connected()
+    }
+
+    fn last_connect_duration(&self) -> Option<Duration> {
+        self.inner.last_connect_duration()
+    }
+
+    fn env_names(&self) -> Vec<String> {
+        self.inner.env_names()
+    }
+
+    fn executor_type(&self) -> &'static str {
+        self.inner.executor_type()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails commands named `refused` and `slow` like an SSH executor would
+    struct FlakyExecutor;
+
+    #[async_trait]
+    impl RemoteExecutor for FlakyExecutor {
+        async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+            match cmd {
+                "refused" => Err(ExecError::ConnectionFailed("refused".to_string())),
+                "slow" => Err(ExecError::Timeout {
+                    timeout: Duration::from_secs(1),
+                }),
+                _ => Ok(CommandResult {
+                    status: i32::from(cmd == "false"),
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    duration: Duration::from_millis(1),
+                }),
+            }
+        }
+
+        async fn run_with_timeout(
+            &self,
+            cmd: &str,
+            _timeout: Duration,
+        ) -> Result<CommandResult, ExecError> {
+            self.run(cmd).await
+        }
+
+        fn last_connect_duration(&self) -> Option<Duration> {
+            Some(Duration::from_millis(120))
+        }
+
+        fn executor_type(&self) -> &'static str {
+            "flaky"
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let mut window = RollingDurations::new(10);
+        assert_eq!(window.percentile(50), None);
+
+        window.push(Duration::from_millis(7));
+        assert_eq!(window.percentile(0), Some(Duration::from_millis(7)));
+        assert_eq!(window.percentile(95), Some(Duration::from_millis(7)));
+
+        for ms in [5, 1, 9, 3, 2, 8, 4, 10, 6] {
+            window.push(Duration::from_millis(ms));
+        }
+        assert_eq!(window.percentile(50), Some(Duration::from_millis(5)));
+        assert_eq!(window.percentile(95), Some(Duration::from_millis(10)));
+        assert_eq!(window.percentile(100), Some(Duration::from_millis(10)));
+        assert_eq!(window.percentile(10), Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_window_drops_the_oldest() {
+        let mut window = RollingDurations::new(3);
+        for ms in [100, 200, 1, 2, 3] {
+            window.push(Duration::from_millis(ms));
+        }
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.percentile(100), Some(Duration::from_millis(3)));
+        assert_eq!(window.percentile(1), Some(Duration::from_millis(1)));
+
+        // A zero capacity still keeps the latest duration
+        let mut window = RollingDurations::new(0);
+        window.push(Duration::from_millis(1));
+        window.push(Duration::from_millis(2));
+        assert_eq!(window.percentile(50), Some(Duration::from_millis(2)));
+    }
+
+    #[tokio::test]
+    async fn test_counts_commands_and_failures() {
+        let stats = Arc::new(ExecutorStats::default());
+        let executor = InstrumentedExecutor::new(Arc::new(FlakyExecutor), stats.clone());
+        assert_eq!(stats.snapshot(), ExecutorStatsSnapshot::default());
+
+        executor.run("refused").await.unwrap_err();
+        executor.run("refused").await.unwrap_err();
+        executor.run("slow").await.unwrap_err();
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.commands, 3);
+        assert_eq!(snapshot.failures, 3);
+        assert_eq!(snapshot.consecutive_connection_failures, 2);
+        assert_eq!(snapshot.window, 3);
+        assert_eq!(snapshot.last_connect, Some(Duration::from_millis(120)));
+
+        // A failing command still reached the host
+        executor.run("false").await.unwrap();
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.failures, 3);
+        assert_eq!(snapshot.consecutive_connection_failures, 0);
+        assert!(snapshot.p50.is_some() && snapshot.p95 >= snapshot.p50);
+    }
+}
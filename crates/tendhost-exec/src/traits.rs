@@ -4,7 +4,9 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 
+use crate::command::{ShellCommand, script};
 use crate::error::ExecError;
+use crate::escalation::PrivilegeEscalation;
 use crate::result::CommandResult;
 
 /// Trait for executing commands locally or remotely
@@ -57,6 +59,22 @@ pub trait RemoteExecutor: Send + Sync {
         true
     }
 
+    /// How long the last successful connection took to establish
+    ///
+    /// `None` for executors that don't connect, and before the first
+    /// connection.
+    fn last_connect_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Names of the variables set for every command
+    ///
+    /// Privileged commands pass these on, since sudo and doas reset the
+    /// environment.
+    fn env_names(&self) -> Vec<String> {
+        Vec::new()
+    }
+
     /// Get executor type name for logging
     fn executor_type(&self) -> &'static str;
 }
@@ -98,7 +116,141 @@ pub trait RemoteExecutorExt: RemoteExecutor {
         }
         Ok(results)
     }
+
+    /// Run `cmd` as root through `escalation`, e.g. `sudo -n reboot`
+    ///
+    /// The executor's variables are passed on, see
+    /// [`PrivilegeEscalation::wrap_keeping`].
+    async fn run_privileged(
+        &self,
+        cmd: &ShellCommand,
+        escalation: &PrivilegeEscalation,
+    ) -> Result<CommandResult, ExecError> {
+        let cmd = escalation.wrap_keeping(cmd, &self.env_names());
+        self.run(cmd.as_str()).await
+    }
+
+    /// Run `cmd` as root through `escalation`, with a timeout
+    async fn run_privileged_with_timeout(
+        &self,
+        cmd: &ShellCommand,
+        escalation: &PrivilegeEscalation,
+        timeout: Duration,
+    ) -> Result<CommandResult, ExecError> {
+        let cmd = escalation.wrap_keeping(cmd, &self.env_names());
+        self.run_with_timeout(cmd.as_str(), timeout).await
+    }
+
+    /// Run `lines` as one shell script that stops at the first failing line
+    ///
+    /// The lines are joined under `set -e` in a single invocation, so the
+    /// result is that of the failing line, or of the last one.
+    async fn run_script(&self, lines: &[&str]) -> Result<CommandResult, ExecError> {
+        self.run(&script(lines)).await
+    }
 }
 
 #[async_trait]
-impl<T: RemoteExecutor> RemoteExecutorExt for T {}
+impl<T: RemoteExecutor + ?Sized> RemoteExecutorExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local::LocalExecutor;
+
+    /// Replies with the command it was given as stdout
+    struct EchoExecutor;
+
+    #[async_trait]
+    impl RemoteExecutor for EchoExecutor {
+        async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+            Ok(CommandResult {
+                status: 0,
+                stdout: cmd.to_string(),
+                stderr: String::new(),
+                duration: Duration::from_millis(1),
+            })
+        }
+
+        async fn run_with_timeout(
+            &self,
+            cmd: &str,
+            _timeout: Duration,
+        ) -> Result<CommandResult, ExecError> {
+            self.run(cmd).await
+        }
+
+        fn executor_type(&self) -> &'static str {
+            "echo"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_privileged_prefixes_the_escalation() {
+        let restart = ShellCommand::new("systemctl")
+            .arg("restart")
+            .arg("my service");
+        for (escalation, expected) in [
+            (
+                PrivilegeEscalation::Sudo,
+                "sudo -n systemctl restart 'my service'",
+            ),
+            (
+                PrivilegeEscalation::Doas,
+                "doas -n systemctl restart 'my service'",
+            ),
+            (
+                PrivilegeEscalation::Custom("pfexec -P all".to_string()),
+                "pfexec -P all systemctl restart 'my service'",
+            ),
+            (PrivilegeEscalation::None, "systemctl restart 'my service'"),
+        ] {
+            let result = EchoExecutor
+                .run_privileged(&restart, &escalation)
+                .await
+                .unwrap();
+            assert_eq!(result.stdout, expected);
+            let result = EchoExecutor
+                .run_privileged_with_timeout(&restart, &escalation, Duration::from_secs(1))
+                .await
+                .unwrap();
+            assert_eq!(result.stdout, expected);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_privileged_keeps_the_executor_env() {
+        let executor = LocalExecutor::new().with_env(std::collections::BTreeMap::from([(
+            "http_proxy".to_string(),
+            "http://proxy.lab:3128".to_string(),
+        )]));
+        // Stands in for sudo resetting the environment
+        let reset = PrivilegeEscalation::Custom("env -i".to_string());
+
+        let result = executor
+            .run_privileged(&ShellCommand::new("printenv").arg("http_proxy"), &reset)
+            .await
+            .unwrap();
+        assert_eq!(result.stdout.trim(), "http://proxy.lab:3128");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_run_script_stops_at_the_first_failure() {
+        let executor = LocalExecutor::new();
+        let result = executor
+            .run_script(&["echo one", "false", "echo two"])
+            .await
+            .unwrap();
+        assert_eq!(result.status, 1);
+        assert_eq!(result.stdout.trim(), "one");
+
+        let result = executor
+            .run_script(&["echo one", "echo two"])
+            .await
+            .unwrap();
+        assert!(result.success());
+        assert_eq!(result.stdout, "one\ntwo\n");
+    }
+}
@@ -0,0 +1,75 @@
+//! Inventory collection backends
+//!
+//! A backend knows how to gather each inventory section from a host.
+//! [`OsqueryBackend`] is preferred when `osqueryi` is installed;
+//! [`ShellBackend`] falls back to standard shell utilities.
+
+pub mod osquery;
+pub mod shell;
+
+use async_trait::async_trait;
+
+use crate::error::InventoryError;
+use crate::types::{
+    Container, HardwareInfo, Image, ListeningPort, Package, SystemInfo, SystemdService,
+};
+
+pub use osquery::OsqueryBackend;
+pub use shell::ShellBackend;
+
+/// Source of host inventory data
+#[async_trait]
+pub trait CollectionBackend: Send + Sync {
+    /// Backend name for logging
+    fn name(&self) -> &'static str;
+
+    /// Drop cached results read from `table`, returning how many were dropped
+    ///
+    /// A trailing `*` matches table name prefixes, e.g. `docker_*`. Backends
+    /// without a cache have nothing to drop.
+    async fn invalidate_matching(&self, _table: &str) -> usize {
+        0
+    }
+
+    /// Get system information
+    ///
+    /// # Errors
+    /// Returns an error if the underlying commands fail or required data is missing.
+    async fn get_system_info(&self) -> Result<SystemInfo, InventoryError>;
+
+    /// Get hardware information
+    ///
+    /// # Errors
+    /// Returns an error if the underlying commands fail or required data is missing.
+    async fn get_hardware_info(&self) -> Result<HardwareInfo, InventoryError>;
+
+    /// Get installed packages
+    ///
+    /// # Errors
+    /// Returns an error if the commands fail or no package database is available.
+    async fn get_packages(&self) -> Result<Vec<Package>, InventoryError>;
+
+    /// Get Docker containers
+    ///
+    /// # Errors
+    /// Returns an error if Docker is not available or the backend doesn't support it.
+    async fn get_docker_containers(&self) -> Result<Vec<Container>, InventoryError>;
+
+    /// Get Docker images
+    ///
+    /// # Errors
+    /// Returns an error if Docker is not available or the backend doesn't support it.
+    async fn get_docker_images(&self) -> Result<Vec<Image>, InventoryError>;
+
+    /// Get listening ports with their owning process names
+    ///
+    /// # Errors
+    /// Returns an error if the commands fail or the backend doesn't support it.
+    async fn get_listening_ports(&self) -> Result<Vec<ListeningPort>, InventoryError>;
+
+    /// Get systemd services
+    ///
+    /// # Errors
+    /// Returns an error if systemd is not available or the backend doesn't support it.
+    async fn get_services(&self) -> Result<Vec<SystemdService>, InventoryError>;
+}
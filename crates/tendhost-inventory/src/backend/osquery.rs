@@ -0,0 +1,519 @@
+//! osquery-based collection backend
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+use tendhost_exec::traits::RemoteExecutor;
+use tracing::{debug, info, instrument};
+
+use crate::backend::CollectionBackend;
+use crate::error::InventoryError;
+use crate::osquery::OsqueryClient;
+use crate::query::queries;
+use crate::types::{
+    Container, CpuInfo, DiskInfo, HardwareInfo, Image, ListeningPort, MemoryInfo, NetworkInterface,
+    Package, PackageSource, SystemInfo, SystemdService,
+};
+
+/// Collection backend querying osquery tables via `osqueryi`
+pub struct OsqueryBackend {
+    client: OsqueryClient,
+}
+
+impl OsqueryBackend {
+    /// Create a new osquery backend
+    pub fn new(executor: Arc<dyn RemoteExecutor>, cache_ttl: Duration) -> Self {
+        Self {
+            client: OsqueryClient::new(executor, cache_ttl),
+        }
+    }
+
+    /// Set query timeout
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+
+    /// Get the underlying osquery client
+    #[must_use]
+    pub fn client(&self) -> &OsqueryClient {
+        &self.client
+    }
+}
+
+#[async_trait]
+impl CollectionBackend for OsqueryBackend {
+    fn name(&self) -> &'static str {
+        "osquery"
+    }
+
+    async fn invalidate_matching(&self, table: &str) -> usize {
+        self.client.invalidate_matching(table).await
+    }
+
+    /// Get system information
+    ///
+    /// # Errors
+    /// Returns an error if osquery queries fail or if required data is missing.
+    #[instrument(skip(self))]
+    async fn get_system_info(&self) -> Result<SystemInfo, InventoryError> {
+        debug!("collecting system info");
+
+        #[derive(Deserialize)]
+        struct OsVersionRow {
+            name: String,
+            version: String,
+            codename: Option<String>,
+            platform: String,
+            arch: String,
+        }
+
+        #[derive(Deserialize)]
+        struct SystemInfoRow {
+            hostname: String,
+        }
+
+        #[derive(Deserialize)]
+        struct UptimeRow {
+            total_seconds: String,
+        }
+
+        #[derive(Deserialize)]
+        struct KernelRow {
+            version: String,
+        }
+
+        // Independent tables, each its own osqueryi round trip
+        let (os_query, sys_query, uptime_query, kernel_query) = (
+            queries::os_version(),
+            queries::system_info(),
+            queries::uptime(),
+            queries::kernel_info(),
+        );
+        let (os_rows, sys_rows, uptime_rows, kernel_rows) = tokio::try_join!(
+            self.client.query_cached::<OsVersionRow>(&os_query, None),
+            self.client.query_cached::<SystemInfoRow>(&sys_query, None),
+            self.client.query_cached::<UptimeRow>(&uptime_query, None),
+            self.client.query_cached::<KernelRow>(&kernel_query, None),
+        )?;
+
+        let os = os_rows
+            .into_iter()
+            .next()
+            .ok_or_else(|| InventoryError::ParseError("no os_version data".to_string()))?;
+
+        let sys = sys_rows
+            .into_iter()
+            .next()
+            .ok_or_else(|| InventoryError::ParseError("no system_info data".to_string()))?;
+
+        let uptime_seconds = uptime_rows
+            .into_iter()
+            .next()
+            .and_then(|r| r.total_seconds.parse().ok())
+            .unwrap_or(0);
+
+        let kernel_version = kernel_rows
+            .into_iter()
+            .next()
+            .map(|r| r.version)
+            .unwrap_or_default();
+
+        Ok(SystemInfo {
+            hostname: sys.hostname,
+            os_name: os.name,
+            os_version: os.version,
+            os_codename: os.codename,
+            platform: os.platform,
+            arch: os.arch,
+            uptime_seconds,
+            uuid: None, // Could get from system_info if available
+            kernel_version,
+            collected_at: Utc::now(),
+        })
+    }
+
+    /// Get hardware information
+    ///
+    /// # Errors
+    /// Returns an error if osquery queries fail or if required data is missing.
+    #[allow(clippy::too_many_lines)]
+    #[instrument(skip(self))]
+    async fn get_hardware_info(&self) -> Result<HardwareInfo, InventoryError> {
+        debug!("collecting hardware info");
+
+        #[derive(Deserialize)]
+        struct CpuRow {
+            model: String,
+            vendor: String,
+            physical_cores: String,
+            logical_cores: String,
+            mhz: String,
+        }
+
+        #[derive(Deserialize)]
+        struct MemoryRow {
+            total: String,
+            free: String,
+            used: String,
+            swap_total: String,
+            swap_free: String,
+        }
+
+        #[derive(Deserialize)]
+        struct MountRow {
+            device: String,
+            path: String,
+            #[serde(rename = "type")]
+            fs_type: String,
+            blocks: String,
+            blocks_free: String,
+            #[serde(rename = "blocks_size")]
+            block_size: String,
+        }
+
+        // One row per address
+        #[derive(Deserialize)]
+        struct InterfaceRow {
+            interface: String,
+            mac: String,
+            address: Option<String>,
+        }
+
+        let (cpu_query, mem_query, mount_query, iface_query) = (
+            queries::cpu_info(),
+            queries::memory_info(),
+            queries::mounts(),
+            queries::network_interfaces(),
+        );
+        let (cpu_rows, mem_rows, mount_rows, iface_rows) = tokio::try_join!(
+            self.client.query_cached::<CpuRow>(&cpu_query, None),
+            self.client.query_cached::<MemoryRow>(&mem_query, None),
+            self.client.query_cached::<MountRow>(&mount_query, None),
+            self.client.query_cached::<InterfaceRow>(&iface_query, None),
+        )?;
+
+        let cpu_row = cpu_rows
+            .into_iter()
+            .next()
+            .ok_or_else(|| InventoryError::ParseError("no cpu_info data".to_string()))?;
+
+        let cpu = CpuInfo {
+            model: cpu_row.model,
+            physical_cores: cpu_row.physical_cores.parse().unwrap_or(0),
+            logical_cores: cpu_row.logical_cores.parse().unwrap_or(0),
+            speed_mhz: cpu_row.mhz.parse().unwrap_or(0),
+            vendor: cpu_row.vendor,
+        };
+
+        let mem_row = mem_rows
+            .into_iter()
+            .next()
+            .ok_or_else(|| InventoryError::ParseError("no memory_info data".to_string()))?;
+
+        let memory = MemoryInfo {
+            total_bytes: mem_row.total.parse().unwrap_or(0),
+            free_bytes: mem_row.free.parse().unwrap_or(0),
+            used_bytes: mem_row.used.parse().unwrap_or(0),
+            swap_total_bytes: mem_row.swap_total.parse().unwrap_or(0),
+            swap_free_bytes: mem_row.swap_free.parse().unwrap_or(0),
+        };
+
+        let mut disks = Vec::new();
+
+        for mount in mount_rows {
+            let block_size: u64 = mount.block_size.parse().unwrap_or(4096);
+            let total_blocks: u64 = mount.blocks.parse().unwrap_or(0);
+            let free_blocks: u64 = mount.blocks_free.parse().unwrap_or(0);
+
+            disks.push(DiskInfo {
+                device: mount.device,
+                mount_point: mount.path,
+                filesystem: mount.fs_type,
+                total_bytes: total_blocks * block_size,
+                free_bytes: free_blocks * block_size,
+                used_bytes: (total_blocks - free_blocks) * block_size,
+            });
+        }
+
+        let mut network_interfaces: Vec<NetworkInterface> = Vec::new();
+
+        for row in iface_rows {
+            let idx = if let Some(idx) = network_interfaces
+                .iter()
+                .position(|i| i.name == row.interface)
+            {
+                idx
+            } else {
+                network_interfaces.push(NetworkInterface {
+                    name: row.interface,
+                    mac: row.mac,
+                    ipv4: Vec::new(),
+                    ipv6: Vec::new(),
+                });
+                network_interfaces.len() - 1
+            };
+
+            if let Some(address) = row.address.filter(|a| !a.is_empty()) {
+                let iface = &mut network_interfaces[idx];
+                if address.contains(':') {
+                    iface.ipv6.push(address);
+                } else {
+                    iface.ipv4.push(address);
+                }
+            }
+        }
+
+        Ok(HardwareInfo {
+            cpu,
+            memory,
+            disks,
+            network_interfaces,
+        })
+    }
+
+    /// Get installed packages
+    ///
+    /// # Errors
+    /// Returns an error if osquery queries fail or if no package manager is available.
+    #[instrument(skip(self))]
+    async fn get_packages(&self) -> Result<Vec<Package>, InventoryError> {
+        debug!("collecting packages");
+
+        let mut packages = Vec::new();
+
+        // Try deb_packages first
+        #[derive(Deserialize)]
+        struct DebRow {
+            name: String,
+            version: String,
+            arch: String,
+            install_time: Option<String>,
+        }
+
+        match self
+            .client
+            .query_cached::<DebRow>(&queries::deb_packages(), None)
+            .await
+        {
+            Ok(rows) => {
+                for row in rows {
+                    packages.push(Package {
+                        name: row.name,
+                        version: row.version,
+                        arch: row.arch,
+                        source: PackageSource::Deb,
+                        install_time: row.install_time.and_then(|t| {
+                            t.parse::<i64>()
+                                .ok()
+                                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                        }),
+                        size_bytes: None,
+                    });
+                }
+            }
+            Err(InventoryError::TableNotAvailable(_)) => {
+                // Try rpm_packages
+                #[derive(Deserialize)]
+                struct RpmRow {
+                    name: String,
+                    version: String,
+                    arch: String,
+                    install_time: Option<String>,
+                }
+
+                match self
+                    .client
+                    .query_cached::<RpmRow>(&queries::rpm_packages(), None)
+                    .await
+                {
+                    Ok(rows) => {
+                        for row in rows {
+                            packages.push(Package {
+                                name: row.name,
+                                version: row.version,
+                                arch: row.arch,
+                                source: PackageSource::Rpm,
+                                install_time: row.install_time.and_then(|t| {
+                                    t.parse::<i64>()
+                                        .ok()
+                                        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                                }),
+                                size_bytes: None,
+                            });
+                        }
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        }
+
+        info!(count = packages.len(), "collected packages");
+
+        Ok(packages)
+    }
+
+    /// Get Docker containers
+    ///
+    /// # Errors
+    /// Returns an error if osquery queries fail or Docker is not available.
+    #[instrument(skip(self))]
+    async fn get_docker_containers(&self) -> Result<Vec<Container>, InventoryError> {
+        debug!("collecting docker containers");
+
+        #[derive(Deserialize)]
+        struct ContainerRow {
+            id: String,
+            name: String,
+            image: String,
+            state: String,
+            status: String,
+            created: String,
+        }
+
+        let rows: Vec<ContainerRow> = self
+            .client
+            .query_cached(&queries::docker_containers(), None)
+            .await?;
+
+        let containers = rows
+            .into_iter()
+            .map(|r| Container {
+                id: r.id,
+                name: r.name,
+                image: r.image,
+                state: r.state,
+                status: r.status,
+                created: r
+                    .created
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                    .unwrap_or_else(Utc::now),
+                ports: Vec::new(),  // Would need docker_container_ports table
+                mounts: Vec::new(), // Would need docker_container_mounts table
+            })
+            .collect();
+
+        Ok(containers)
+    }
+
+    /// Get Docker images
+    ///
+    /// # Errors
+    /// Returns an error if osquery queries fail or Docker is not available.
+    #[instrument(skip(self))]
+    async fn get_docker_images(&self) -> Result<Vec<Image>, InventoryError> {
+        debug!("collecting docker images");
+
+        #[derive(Deserialize)]
+        struct ImageRow {
+            id: String,
+            tags: String,
+            created: String,
+            size: String,
+        }
+
+        let rows: Vec<ImageRow> = self
+            .client
+            .query_cached(&queries::docker_images(), None)
+            .await?;
+
+        let images = rows
+            .into_iter()
+            .map(|r| Image {
+                id: r.id,
+                tags: r.tags.split(',').map(|s| s.trim().to_string()).collect(),
+                created: r
+                    .created
+                    .parse::<i64>()
+                    .ok()
+                    .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                    .unwrap_or_else(Utc::now),
+                size_bytes: r.size.parse().unwrap_or(0),
+            })
+            .collect();
+
+        Ok(images)
+    }
+
+    /// Get listening ports with their owning process names
+    ///
+    /// # Errors
+    /// Returns an error if osquery queries fail.
+    #[instrument(skip(self))]
+    async fn get_listening_ports(&self) -> Result<Vec<ListeningPort>, InventoryError> {
+        debug!("collecting listening ports");
+
+        #[derive(Deserialize)]
+        struct PortRow {
+            pid: String,
+            port: String,
+            protocol: String,
+            address: String,
+            process_name: Option<String>,
+        }
+
+        let rows: Vec<PortRow> = self
+            .client
+            .query_cached(&queries::ports_with_processes(), None)
+            .await?;
+
+        let ports = rows
+            .into_iter()
+            .filter_map(|r| {
+                // Unix sockets and unbound entries report port 0 or -1
+                let port: u16 = r.port.parse().ok().filter(|p| *p > 0)?;
+                Some(ListeningPort {
+                    pid: r.pid.parse().unwrap_or(0),
+                    port,
+                    protocol: ListeningPort::protocol_name(&r.protocol),
+                    process_name: r.process_name.filter(|n| !n.is_empty()),
+                    address: r.address,
+                })
+            })
+            .collect::<Vec<_>>();
+
+        info!(count = ports.len(), "collected listening ports");
+
+        Ok(ports)
+    }
+
+    /// Get systemd services
+    ///
+    /// # Errors
+    /// Returns an error if osquery queries fail or systemd is not available.
+    #[instrument(skip(self))]
+    async fn get_services(&self) -> Result<Vec<SystemdService>, InventoryError> {
+        debug!("collecting systemd services");
+
+        #[derive(Deserialize)]
+        struct UnitRow {
+            id: String,
+            #[serde(default)]
+            description: String,
+            active_state: String,
+            sub_state: String,
+        }
+
+        let rows: Vec<UnitRow> = self
+            .client
+            .query_cached(&queries::systemd_services(), None)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| SystemdService {
+                name: r.id,
+                description: r.description,
+                active_state: r.active_state,
+                sub_state: r.sub_state,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,564 @@
+//! Shell-based collection backend
+//!
+//! Gathers basic inventory with standard utilities for hosts without osquery.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::Deserialize;
+use tendhost_exec::traits::RemoteExecutor;
+use tracing::{debug, info, instrument};
+
+use crate::backend::CollectionBackend;
+use crate::error::InventoryError;
+use crate::types::{
+    Container, CpuInfo, DiskInfo, HardwareInfo, Image, ListeningPort, MemoryInfo, NetworkInterface,
+    Package, PackageSource, SystemInfo, SystemdService,
+};
+
+/// Filesystems that aren't interesting for disk usage
+const DF_CMD: &str = "df -B1 --output=source,target,fstype,size,avail,used \
+                      -x tmpfs -x devtmpfs -x overlay -x squashfs";
+
+const DPKG_CMD: &str = r"dpkg-query -W -f='${Package}\t${Version}\t${Architecture}\n'";
+
+const RPM_CMD: &str = r"rpm -qa --qf '%{NAME}\t%{VERSION}-%{RELEASE}\t%{ARCH}\t%{INSTALLTIME}\n'";
+
+/// Inventory sections only the osquery backend collects
+pub const OSQUERY_ONLY_SECTIONS: [&str; 4] = [
+    "docker_containers",
+    "docker_images",
+    "listening_ports",
+    "services",
+];
+
+/// Collection backend using plain shell commands
+pub struct ShellBackend {
+    executor: Arc<dyn RemoteExecutor>,
+}
+
+impl ShellBackend {
+    /// Create a new shell backend
+    pub fn new(executor: Arc<dyn RemoteExecutor>) -> Self {
+        Self { executor }
+    }
+
+    /// Run a command and return stdout, failing on non-zero exit
+    async fn run_checked(&self, cmd: &str) -> Result<String, InventoryError> {
+        let result = self
+            .executor
+            .run(cmd)
+            .await
+            .map_err(|e| InventoryError::ExecutionError(e.to_string()))?;
+
+        if !result.success() {
+            return Err(InventoryError::QueryFailed(format!(
+                "`{cmd}` exited with {}: {}",
+                result.status,
+                result.stderr.trim()
+            )));
+        }
+
+        Ok(result.stdout)
+    }
+
+    /// Check whether a command is available on the host
+    async fn has_command(&self, cmd: &str) -> bool {
+        self.executor
+            .run(&format!("which {cmd}"))
+            .await
+            .is_ok_and(|r| r.success())
+    }
+}
+
+#[async_trait]
+impl CollectionBackend for ShellBackend {
+    fn name(&self) -> &'static str {
+        "shell"
+    }
+
+    #[instrument(skip(self))]
+    async fn get_system_info(&self) -> Result<SystemInfo, InventoryError> {
+        debug!("collecting system info via shell");
+
+        let os_release = parse_os_release(&self.run_checked("cat /etc/os-release").await?);
+
+        // nodename, kernel release and machine, always printed in this order
+        let uname = self.run_checked("uname -n -r -m").await?;
+        let mut parts = uname.split_whitespace();
+        let (hostname, kernel_version, arch) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(h), Some(k), Some(a)) => (h.to_string(), k.to_string(), a.to_string()),
+            _ => {
+                return Err(InventoryError::ParseError(format!(
+                    "unexpected uname output: {uname}"
+                )));
+            }
+        };
+
+        let uptime_seconds = self
+            .run_checked("cat /proc/uptime")
+            .await
+            .map(|s| parse_uptime(&s))
+            .unwrap_or(0);
+
+        let field = |key: &str| os_release.get(key).cloned();
+
+        Ok(SystemInfo {
+            hostname,
+            os_name: field("NAME").unwrap_or_default(),
+            os_version: field("VERSION_ID").unwrap_or_default(),
+            os_codename: field("VERSION_CODENAME").filter(|c| !c.is_empty()),
+            platform: field("ID").unwrap_or_else(|| "linux".to_string()),
+            arch,
+            uptime_seconds,
+            uuid: None,
+            kernel_version,
+            collected_at: Utc::now(),
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn get_hardware_info(&self) -> Result<HardwareInfo, InventoryError> {
+        debug!("collecting hardware info via shell");
+
+        let (cpuinfo, nproc, meminfo, df, ip_addr) = tokio::join!(
+            self.run_checked("cat /proc/cpuinfo"),
+            self.run_checked("nproc"),
+            self.run_checked("cat /proc/meminfo"),
+            self.run_checked(DF_CMD),
+            self.run_checked("ip -j addr"),
+        );
+        let nproc = nproc.ok().and_then(|s| s.trim().parse().ok());
+        let cpu = parse_cpuinfo(&cpuinfo?, nproc);
+
+        let memory = parse_meminfo(&meminfo?);
+        let disks = parse_df(&df?);
+        let network_interfaces = parse_ip_addr(&ip_addr?)?;
+
+        Ok(HardwareInfo {
+            cpu,
+            memory,
+            disks,
+            network_interfaces,
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn get_packages(&self) -> Result<Vec<Package>, InventoryError> {
+        debug!("collecting packages via shell");
+
+        let packages = if self.has_command("dpkg-query").await {
+            parse_dpkg(&self.run_checked(DPKG_CMD).await?)
+        } else if self.has_command("rpm").await {
+            parse_rpm(&self.run_checked(RPM_CMD).await?)
+        } else {
+            return Err(InventoryError::TableNotAvailable(
+                "no dpkg-query or rpm on host".to_string(),
+            ));
+        };
+
+        info!(count = packages.len(), "collected packages");
+
+        Ok(packages)
+    }
+
+    async fn get_docker_containers(&self) -> Result<Vec<Container>, InventoryError> {
+        Err(self.unsupported("docker_containers"))
+    }
+
+    async fn get_docker_images(&self) -> Result<Vec<Image>, InventoryError> {
+        Err(self.unsupported("docker_images"))
+    }
+
+    async fn get_listening_ports(&self) -> Result<Vec<ListeningPort>, InventoryError> {
+        Err(self.unsupported("listening_ports"))
+    }
+
+    async fn get_services(&self) -> Result<Vec<SystemdService>, InventoryError> {
+        Err(self.unsupported("services"))
+    }
+}
+
+impl ShellBackend {
+    fn unsupported(&self, section: &'static str) -> InventoryError {
+        InventoryError::Unsupported {
+            backend: self.name(),
+            section,
+        }
+    }
+}
+
+// ============================================================================
+// Parsers
+// ============================================================================
+
+/// Parse `/etc/os-release` into key/value pairs with quotes removed
+#[must_use]
+pub fn parse_os_release(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| {
+            let v = v.trim().trim_matches('"').trim_matches('\'');
+            (k.trim().to_string(), v.to_string())
+        })
+        .collect()
+}
+
+/// Parse `/proc/uptime` (seconds since boot as the first field)
+#[must_use]
+pub fn parse_uptime(content: &str) -> u64 {
+    content
+        .split_whitespace()
+        .next()
+        .and_then(|s| s.parse::<f64>().ok())
+        .map_or(0, |secs| {
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let secs = secs as u64;
+            secs
+        })
+}
+
+/// Parse `/proc/cpuinfo`, using `nproc` for the logical core count when known
+#[must_use]
+pub fn parse_cpuinfo(content: &str, nproc: Option<u32>) -> CpuInfo {
+    let mut model = String::new();
+    let mut vendor = String::new();
+    let mut speed_mhz = 0;
+    let mut physical_cores = 0;
+    let mut processors = 0;
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "processor" => processors += 1,
+            "model name" if model.is_empty() => model = value.to_string(),
+            "vendor_id" if vendor.is_empty() => vendor = value.to_string(),
+            "cpu MHz" if speed_mhz == 0 => {
+                speed_mhz = value
+                    .split('.')
+                    .next()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0);
+            }
+            "cpu cores" if physical_cores == 0 => physical_cores = value.parse().unwrap_or(0),
+            _ => {}
+        }
+    }
+
+    let logical_cores = nproc.unwrap_or(processors);
+
+    CpuInfo {
+        model,
+        physical_cores: if physical_cores == 0 {
+            logical_cores
+        } else {
+            physical_cores
+        },
+        logical_cores,
+        speed_mhz,
+        vendor,
+    }
+}
+
+/// Parse `/proc/meminfo` (values in kB)
+#[must_use]
+pub fn parse_meminfo(content: &str) -> MemoryInfo {
+    let values: HashMap<&str, u64> = content
+        .lines()
+        .filter_map(|l| l.split_once(':'))
+        .filter_map(|(k, v)| {
+            let kb = v.split_whitespace().next()?.parse::<u64>().ok()?;
+            Some((k.trim(), kb * 1024))
+        })
+        .collect();
+
+    let total_bytes = values.get("MemTotal").copied().unwrap_or(0);
+    // MemAvailable accounts for reclaimable caches; fall back to MemFree on old kernels
+    let free_bytes = values
+        .get("MemAvailable")
+        .or_else(|| values.get("MemFree"))
+        .copied()
+        .unwrap_or(0);
+
+    MemoryInfo {
+        total_bytes,
+        free_bytes,
+        used_bytes: total_bytes.saturating_sub(free_bytes),
+        swap_total_bytes: values.get("SwapTotal").copied().unwrap_or(0),
+        swap_free_bytes: values.get("SwapFree").copied().unwrap_or(0),
+    }
+}
+
+/// Parse `df -B1 --output=source,target,fstype,size,avail,used`
+#[must_use]
+pub fn parse_df(content: &str) -> Vec<DiskInfo> {
+    content
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            Some(DiskInfo {
+                device: fields[0].to_string(),
+                mount_point: fields[1].to_string(),
+                filesystem: fields[2].to_string(),
+                total_bytes: fields[3].parse().unwrap_or(0),
+                free_bytes: fields[4].parse().unwrap_or(0),
+                used_bytes: fields[5].parse().unwrap_or(0),
+            })
+        })
+        .collect()
+}
+
+/// Parse `ip -j addr` JSON output
+///
+/// # Errors
+/// Returns `InventoryError::ParseError` if the output isn't valid JSON.
+pub fn parse_ip_addr(content: &str) -> Result<Vec<NetworkInterface>, InventoryError> {
+    #[derive(Deserialize)]
+    struct Link {
+        ifname: String,
+        #[serde(default)]
+        address: String,
+        #[serde(default)]
+        addr_info: Vec<AddrInfo>,
+    }
+
+    #[derive(Deserialize)]
+    struct AddrInfo {
+        family: String,
+        local: String,
+    }
+
+    let links: Vec<Link> =
+        serde_json::from_str(content).map_err(|e| InventoryError::ParseError(e.to_string()))?;
+
+    Ok(links
+        .into_iter()
+        .map(|link| {
+            let addr_of = |family: &str| {
+                link.addr_info
+                    .iter()
+                    .filter(|a| a.family == family)
+                    .map(|a| a.local.clone())
+                    .collect()
+            };
+            NetworkInterface {
+                ipv4: addr_of("inet"),
+                ipv6: addr_of("inet6"),
+                name: link.ifname,
+                mac: link.address,
+            }
+        })
+        .collect())
+}
+
+/// Parse tab-separated `dpkg-query -W` output (name, version, arch)
+#[must_use]
+pub fn parse_dpkg(content: &str) -> Vec<Package> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let name = fields.next().filter(|n| !n.is_empty())?;
+            let version = fields.next()?;
+            let arch = fields.next().unwrap_or_default();
+            Some(Package {
+                name: name.to_string(),
+                version: version.to_string(),
+                arch: arch.to_string(),
+                source: PackageSource::Deb,
+                install_time: None,
+                size_bytes: None,
+            })
+        })
+        .collect()
+}
+
+/// Parse tab-separated `rpm -qa --qf` output (name, version-release, arch, install time)
+#[must_use]
+pub fn parse_rpm(content: &str) -> Vec<Package> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let name = fields.next().filter(|n| !n.is_empty())?;
+            let version = fields.next()?;
+            let arch = fields.next().unwrap_or_default();
+            let install_time = fields
+                .next()
+                .and_then(|t| t.trim().parse::<i64>().ok())
+                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0));
+            Some(Package {
+                name: name.to_string(),
+                version: version.to_string(),
+                // gpg-pubkey pseudo packages have no architecture
+                arch: if arch == "(none)" { "noarch" } else { arch }.to_string(),
+                source: PackageSource::Rpm,
+                install_time,
+                size_bytes: None,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEBIAN_OS_RELEASE: &str = r#"PRETTY_NAME="Debian GNU/Linux 12 (bookworm)"
+NAME="Debian GNU/Linux"
+VERSION_ID="12"
+VERSION="12 (bookworm)"
+VERSION_CODENAME=bookworm
+ID=debian
+HOME_URL="https://www.debian.org/"
+"#;
+
+    const FEDORA_OS_RELEASE: &str = r#"NAME="Fedora Linux"
+VERSION="40 (Server Edition)"
+ID=fedora
+VERSION_ID=40
+VERSION_CODENAME=""
+PLATFORM_ID="platform:f40"
+PRETTY_NAME="Fedora Linux 40 (Server Edition)"
+"#;
+
+    #[test]
+    fn test_parse_os_release_debian() {
+        let fields = parse_os_release(DEBIAN_OS_RELEASE);
+        assert_eq!(fields["NAME"], "Debian GNU/Linux");
+        assert_eq!(fields["VERSION_ID"], "12");
+        assert_eq!(fields["VERSION_CODENAME"], "bookworm");
+        assert_eq!(fields["ID"], "debian");
+    }
+
+    #[test]
+    fn test_parse_os_release_fedora() {
+        let fields = parse_os_release(FEDORA_OS_RELEASE);
+        assert_eq!(fields["NAME"], "Fedora Linux");
+        assert_eq!(fields["VERSION_ID"], "40");
+        assert_eq!(fields["VERSION_CODENAME"], "");
+        assert_eq!(fields["ID"], "fedora");
+    }
+
+    #[test]
+    fn test_parse_uptime() {
+        assert_eq!(parse_uptime("350735.47 234388.90\n"), 350_735);
+        assert_eq!(parse_uptime(""), 0);
+    }
+
+    #[test]
+    fn test_parse_cpuinfo() {
+        let content = "processor\t: 0\n\
+                       vendor_id\t: GenuineIntel\n\
+                       model name\t: Intel(R) Xeon(R) CPU E5-2680 v4 @ 2.40GHz\n\
+                       cpu MHz\t\t: 2399.998\n\
+                       cpu cores\t: 2\n\
+                       \n\
+                       processor\t: 1\n\
+                       vendor_id\t: GenuineIntel\n\
+                       model name\t: Intel(R) Xeon(R) CPU E5-2680 v4 @ 2.40GHz\n\
+                       cpu MHz\t\t: 2399.998\n\
+                       cpu cores\t: 2\n";
+
+        let cpu = parse_cpuinfo(content, None);
+        assert_eq!(cpu.vendor, "GenuineIntel");
+        assert!(cpu.model.starts_with("Intel(R) Xeon(R)"));
+        assert_eq!(cpu.speed_mhz, 2399);
+        assert_eq!(cpu.physical_cores, 2);
+        assert_eq!(cpu.logical_cores, 2);
+
+        assert_eq!(parse_cpuinfo(content, Some(4)).logical_cores, 4);
+    }
+
+    #[test]
+    fn test_parse_meminfo() {
+        let content = "MemTotal:        8039424 kB\n\
+                       MemFree:          512000 kB\n\
+                       MemAvailable:    4019712 kB\n\
+                       SwapTotal:       1048572 kB\n\
+                       SwapFree:        1048572 kB\n";
+
+        let mem = parse_meminfo(content);
+        assert_eq!(mem.total_bytes, 8_039_424 * 1024);
+        assert_eq!(mem.free_bytes, 4_019_712 * 1024);
+        assert_eq!(mem.used_bytes, (8_039_424 - 4_019_712) * 1024);
+        assert_eq!(mem.swap_total_bytes, 1_048_572 * 1024);
+    }
+
+    #[test]
+    fn test_parse_df() {
+        let content = "Filesystem     Mounted on Type        1B-blocks       Avail        Used\n\
+                       /dev/sda1      /          ext4      31526391808 18239074304 11652247552\n\
+                       /dev/sda15     /boot/efi  vfat        129718272   117642752    12075520\n";
+
+        let disks = parse_df(content);
+        assert_eq!(disks.len(), 2);
+        assert_eq!(disks[0].device, "/dev/sda1");
+        assert_eq!(disks[0].mount_point, "/");
+        assert_eq!(disks[0].filesystem, "ext4");
+        assert_eq!(disks[0].total_bytes, 31_526_391_808);
+        assert_eq!(disks[0].free_bytes, 18_239_074_304);
+        assert_eq!(disks[1].mount_point, "/boot/efi");
+    }
+
+    #[test]
+    fn test_parse_ip_addr() {
+        let content = r#"[
+            {"ifindex":1,"ifname":"lo","address":"00:00:00:00:00:00",
+             "addr_info":[{"family":"inet","local":"127.0.0.1","prefixlen":8},
+                          {"family":"inet6","local":"::1","prefixlen":128}]},
+            {"ifindex":2,"ifname":"eth0","address":"52:54:00:12:34:56",
+             "addr_info":[{"family":"inet","local":"192.168.1.10","prefixlen":24}]},
+            {"ifindex":3,"ifname":"wg0","addr_info":[]}
+        ]"#;
+
+        let ifaces = parse_ip_addr(content).unwrap();
+        assert_eq!(ifaces.len(), 3);
+        assert_eq!(ifaces[0].ipv6, vec!["::1"]);
+        assert_eq!(ifaces[1].name, "eth0");
+        assert_eq!(ifaces[1].mac, "52:54:00:12:34:56");
+        assert_eq!(ifaces[1].ipv4, vec!["192.168.1.10"]);
+        assert!(ifaces[2].mac.is_empty());
+
+        assert!(parse_ip_addr("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_dpkg() {
+        let content = "adduser\t3.134\tall\n\
+                       libc6\t2.36-9+deb12u4\tamd64\n\
+                       openssh-server\t1:9.2p1-2+deb12u2\tamd64\n";
+
+        let packages = parse_dpkg(content);
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[2].name, "openssh-server");
+        assert_eq!(packages[2].version, "1:9.2p1-2+deb12u2");
+        assert_eq!(packages[2].arch, "amd64");
+        assert_eq!(packages[2].source, PackageSource::Deb);
+    }
+
+    #[test]
+    fn test_parse_rpm() {
+        let content = "bash\t5.2.26-3.fc40\tx86_64\t1713350000\n\
+                       gpg-pubkey\ta15b79cc-63d04c2c\t(none)\t1713350100\n";
+
+        let packages = parse_rpm(content);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "bash");
+        assert_eq!(packages[0].version, "5.2.26-3.fc40");
+        assert_eq!(packages[0].source, PackageSource::Rpm);
+        assert!(packages[0].install_time.is_some());
+        assert_eq!(packages[1].arch, "noarch");
+    }
+}
@@ -1,81 +1,259 @@
 //! High-level inventory collection API
 
+use std::future::Future;
 use std::sync::Arc;
 use std::time::Duration;
 
 use chrono::Utc;
-use serde::Deserialize;
 use tendhost_exec::traits::RemoteExecutor;
+use tokio::sync::{OnceCell, Semaphore};
 use tracing::{debug, info, instrument, warn};
 
+use crate::backend::{CollectionBackend, OsqueryBackend, ShellBackend};
 use crate::error::InventoryError;
-use crate::osquery::OsqueryClient;
-use crate::query::queries;
 use crate::types::{
-    Container, CpuInfo, DiskInfo, HardwareInfo, HostInventory, Image, MemoryInfo, NetworkInterface,
-    Package, PackageSource, SystemInfo,
+    Container, HardwareInfo, HostInventory, Image, ListeningPort, Package, SystemInfo,
+    SystemdService,
 };
 
+/// Sections collected by [`InventoryCollector::collect`] running at once by default
+pub const DEFAULT_MAX_CONCURRENCY: usize = 4;
+
+/// Which inventory sections to collect, and how many at once
+///
+/// Sections that are not collected keep their empty defaults in the
+/// returned [`HostInventory`]. Package lists are by far the slowest:
+///
+/// ```
+/// use tendhost_inventory::CollectOptions;
+///
+/// let options = CollectOptions {
+///     packages: false,
+///     ..CollectOptions::all()
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollectOptions {
+    /// OS, kernel, hostname and uptime
+    pub system: bool,
+    /// CPU, memory, disks and network interfaces
+    pub hardware: bool,
+    /// Installed packages
+    pub packages: bool,
+    /// Docker containers and images
+    pub docker: bool,
+    /// Listening ports
+    pub ports: bool,
+    /// Systemd services
+    pub services: bool,
+    /// Sections collected at once; queries within a section may overlap
+    /// too, bounded by the executor (e.g. SSH channels per connection)
+    pub max_concurrency: usize,
+}
+
+impl CollectOptions {
+    /// Every section
+    #[must_use]
+    pub fn all() -> Self {
+        Self {
+            system: true,
+            hardware: true,
+            packages: true,
+            docker: true,
+            ports: true,
+            services: true,
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+        }
+    }
+}
+
+impl Default for CollectOptions {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+/// Run `section` if `enabled`, once one of `permits` is free
+async fn bounded<T>(
+    permits: &Semaphore,
+    enabled: bool,
+    section: impl Future<Output = T>,
+) -> Option<T> {
+    if !enabled {
+        return None;
+    }
+    // Never closed, so acquiring only waits
+    let _permit = permits.acquire().await.ok()?;
+    Some(section.await)
+}
+
 /// Inventory collector
 ///
-/// High-level API for collecting host inventory data.
+/// High-level API for collecting host inventory data. Uses osquery when
+/// `osqueryi` is installed on the host, otherwise falls back to plain shell
+/// commands. The host is probed for osquery until it is found.
 pub struct InventoryCollector {
-    client: OsqueryClient,
+    executor: Arc<dyn RemoteExecutor>,
+    cache_ttl: Duration,
+    timeout: Option<Duration>,
+    backend: OnceCell<Arc<dyn CollectionBackend>>,
 }
 
 impl InventoryCollector {
     /// Create a new inventory collector
     pub fn new(executor: Arc<dyn RemoteExecutor>, cache_ttl: Duration) -> Self {
         Self {
-            client: OsqueryClient::new(executor, cache_ttl),
+            executor,
+            cache_ttl,
+            timeout: None,
+            backend: OnceCell::new(),
         }
     }
 
     /// Set query timeout
     #[must_use]
     pub fn with_timeout(mut self, timeout: Duration) -> Self {
-        self.client = self.client.with_timeout(timeout);
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Use a specific backend instead of probing the host
+    #[must_use]
+    pub fn with_backend(mut self, backend: Arc<dyn CollectionBackend>) -> Self {
+        self.backend = OnceCell::new_with(Some(backend));
         self
     }
 
+    /// Get the active backend, probing for osquery until it is found
+    ///
+    /// Only osquery is kept once found. When the probe fails, whether
+    /// `osqueryi` is missing or the host could not be reached, this
+    /// collection uses the shell backend and the next one probes again.
+    async fn backend(&self) -> Arc<dyn CollectionBackend> {
+        if let Some(backend) = self.backend.get() {
+            return backend.clone();
+        }
+
+        let has_osquery = self
+            .executor
+            .run("which osqueryi")
+            .await
+            .is_ok_and(|r| r.success());
+
+        if !has_osquery {
+            info!("osqueryi not found, using shell inventory backend");
+            return Arc::new(ShellBackend::new(self.executor.clone()));
+        }
+
+        self.backend
+            .get_or_init(|| async {
+                let mut backend = OsqueryBackend::new(self.executor.clone(), self.cache_ttl);
+                if let Some(timeout) = self.timeout {
+                    backend = backend.with_timeout(timeout);
+                }
+                Arc::new(backend) as Arc<dyn CollectionBackend>
+            })
+            .await
+            .clone()
+    }
+
+    /// Drop cached results read from `table`, returning how many were dropped
+    ///
+    /// A trailing `*` matches table name prefixes, e.g. `docker_*`. Call it
+    /// after changing the host, so the next collection reads fresh data.
+    pub async fn invalidate_matching(&self, table: &str) -> usize {
+        match self.backend.get() {
+            Some(backend) => backend.invalidate_matching(table).await,
+            None => 0,
+        }
+    }
+
+    /// Name of the active backend (`osquery` or `shell`)
+    pub async fn backend_name(&self) -> &'static str {
+        self.backend().await.name()
+    }
+
     /// Collect full inventory
     ///
+    /// Same as [`collect`](Self::collect) with [`CollectOptions::all`].
+    ///
     /// # Errors
     /// Returns an error if inventory collection fails completely. Partial failures
     /// are logged as warnings and the collection continues.
-    #[instrument(skip(self))]
     pub async fn collect_full(&self) -> Result<HostInventory, InventoryError> {
-        info!("collecting full inventory");
+        self.collect(CollectOptions::all()).await
+    }
+
+    /// Collect the sections picked by `options`
+    ///
+    /// Each section is a separate round trip to the host, so they run
+    /// concurrently, at most `options.max_concurrency` at a time.
+    ///
+    /// # Errors
+    /// Returns an error if inventory collection fails completely. Partial failures
+    /// are logged as warnings and the collection continues.
+    #[instrument(skip(self))]
+    pub async fn collect(&self, options: CollectOptions) -> Result<HostInventory, InventoryError> {
+        let backend = self.backend().await;
+        info!(backend = backend.name(), "collecting inventory");
+
+        let permits = Semaphore::new(options.max_concurrency.max(1));
+        let (system, hardware, packages, containers, images, ports, services) = tokio::join!(
+            bounded(&permits, options.system, backend.get_system_info()),
+            bounded(&permits, options.hardware, backend.get_hardware_info()),
+            bounded(&permits, options.packages, backend.get_packages()),
+            bounded(&permits, options.docker, backend.get_docker_containers()),
+            bounded(&permits, options.docker, backend.get_docker_images()),
+            bounded(&permits, options.ports, backend.get_listening_ports()),
+            bounded(&permits, options.services, backend.get_services()),
+        );
 
         let mut inventory = HostInventory::new();
 
-        // Collect system info
-        match self.get_system_info().await {
-            Ok(info) => inventory.system = info,
-            Err(e) => warn!(error = %e, "failed to collect system info"),
+        match system {
+            Some(Ok(info)) => inventory.system = info,
+            Some(Err(e)) => warn!(error = %e, "failed to collect system info"),
+            None => {}
         }
 
-        // Collect hardware info
-        match self.get_hardware_info().await {
-            Ok(info) => inventory.hardware = info,
-            Err(e) => warn!(error = %e, "failed to collect hardware info"),
+        match hardware {
+            Some(Ok(info)) => inventory.hardware = info,
+            Some(Err(e)) => warn!(error = %e, "failed to collect hardware info"),
+            None => {}
         }
 
-        // Collect packages
-        match self.get_packages().await {
-            Ok(packages) => inventory.packages = packages,
-            Err(e) => warn!(error = %e, "failed to collect packages"),
+        match packages {
+            Some(Ok(packages)) => inventory.packages = packages,
+            Some(Err(e)) => warn!(error = %e, "failed to collect packages"),
+            None => {}
         }
 
-        // Collect Docker info (if available)
-        match self.get_docker_containers().await {
-            Ok(containers) => inventory.docker_containers = containers,
-            Err(e) => debug!(error = %e, "docker containers not available"),
+        // Docker is optional on the host
+        match containers {
+            Some(Ok(containers)) => inventory.docker_containers = containers,
+            Some(Err(e)) => debug!(error = %e, "docker containers not available"),
+            None => {}
         }
 
-        match self.get_docker_images().await {
-            Ok(images) => inventory.docker_images = images,
-            Err(e) => debug!(error = %e, "docker images not available"),
+        match images {
+            Some(Ok(images)) => inventory.docker_images = images,
+            Some(Err(e)) => debug!(error = %e, "docker images not available"),
+            None => {}
+        }
+
+        match ports {
+            Some(Ok(ports)) => inventory.listening_ports = ports,
+            Some(Err(e @ InventoryError::Unsupported { .. })) => {
+                debug!(error = %e, "listening ports not available");
+            }
+            Some(Err(e)) => warn!(error = %e, "failed to collect listening ports"),
+            None => {}
+        }
+
+        match services {
+            Some(Ok(services)) => inventory.services = services,
+            Some(Err(e)) => debug!(error = %e, "systemd services not available"),
+            None => {}
         }
 
         inventory.collected_at = Utc::now();
@@ -88,377 +266,438 @@ impl InventoryCollector {
     /// Get system information
     ///
     /// # Errors
-    /// Returns an error if osquery queries fail or if required data is missing.
-    #[instrument(skip(self))]
+    /// Returns an error if the backend queries fail or if required data is missing.
     pub async fn get_system_info(&self) -> Result<SystemInfo, InventoryError> {
-        debug!("collecting system info");
-
-        // Get OS version
-        #[derive(Deserialize)]
-        struct OsVersionRow {
-            name: String,
-            version: String,
-            codename: Option<String>,
-            platform: String,
-            arch: String,
-        }
-
-        let os_rows: Vec<OsVersionRow> = self.client.query(&queries::os_version()).await?;
-        let os = os_rows
-            .into_iter()
-            .next()
-            .ok_or_else(|| InventoryError::ParseError("no os_version data".to_string()))?;
+        self.backend().await.get_system_info().await
+    }
 
-        // Get hostname
-        #[derive(Deserialize)]
-        struct SystemInfoRow {
-            hostname: String,
-        }
+    /// Get hardware information
+    ///
+    /// # Errors
+    /// Returns an error if the backend queries fail or if required data is missing.
+    pub async fn get_hardware_info(&self) -> Result<HardwareInfo, InventoryError> {
+        self.backend().await.get_hardware_info().await
+    }
 
-        let sys_rows: Vec<SystemInfoRow> = self.client.query(&queries::system_info()).await?;
-        let sys = sys_rows
-            .into_iter()
-            .next()
-            .ok_or_else(|| InventoryError::ParseError("no system_info data".to_string()))?;
+    /// Get installed packages
+    ///
+    /// # Errors
+    /// Returns an error if the backend queries fail or if no package manager is available.
+    pub async fn get_packages(&self) -> Result<Vec<Package>, InventoryError> {
+        self.backend().await.get_packages().await
+    }
 
-        // Get uptime
-        #[derive(Deserialize)]
-        struct UptimeRow {
-            total_seconds: String,
-        }
+    /// Get Docker containers
+    ///
+    /// # Errors
+    /// Returns an error if the backend queries fail or Docker is not available.
+    pub async fn get_docker_containers(&self) -> Result<Vec<Container>, InventoryError> {
+        self.backend().await.get_docker_containers().await
+    }
 
-        let uptime_rows: Vec<UptimeRow> = self.client.query(&queries::uptime()).await?;
-        let uptime_seconds = uptime_rows
-            .into_iter()
-            .next()
-            .and_then(|r| r.total_seconds.parse().ok())
-            .unwrap_or(0);
-
-        // Get kernel version
-        #[derive(Deserialize)]
-        struct KernelRow {
-            version: String,
-        }
+    /// Get Docker images
+    ///
+    /// # Errors
+    /// Returns an error if the backend queries fail or Docker is not available.
+    pub async fn get_docker_images(&self) -> Result<Vec<Image>, InventoryError> {
+        self.backend().await.get_docker_images().await
+    }
 
-        let kernel_rows: Vec<KernelRow> = self.client.query(&queries::kernel_info()).await?;
-        let kernel_version = kernel_rows
-            .into_iter()
-            .next()
-            .map(|r| r.version)
-            .unwrap_or_default();
-
-        Ok(SystemInfo {
-            hostname: sys.hostname,
-            os_name: os.name,
-            os_version: os.version,
-            os_codename: os.codename,
-            platform: os.platform,
-            arch: os.arch,
-            uptime_seconds,
-            uuid: None, // Could get from system_info if available
-            kernel_version,
-            collected_at: Utc::now(),
-        })
+    /// Get listening ports with their owning process names
+    ///
+    /// # Errors
+    /// Returns an error if the backend queries fail.
+    pub async fn get_listening_ports(&self) -> Result<Vec<ListeningPort>, InventoryError> {
+        self.backend().await.get_listening_ports().await
     }
 
-    /// Get hardware information
+    /// Get systemd services
     ///
     /// # Errors
-    /// Returns an error if osquery queries fail or if required data is missing.
-    #[allow(clippy::too_many_lines)]
-    #[instrument(skip(self))]
-    pub async fn get_hardware_info(&self) -> Result<HardwareInfo, InventoryError> {
-        debug!("collecting hardware info");
-
-        // Get CPU info
-        #[derive(Deserialize)]
-        struct CpuRow {
-            model: String,
-            vendor: String,
-            physical_cores: String,
-            logical_cores: String,
-            mhz: String,
+    /// Returns an error if the backend queries fail or systemd is not available.
+    pub async fn get_services(&self) -> Result<Vec<SystemdService>, InventoryError> {
+        self.backend().await.get_services().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PackageSource;
+    use async_trait::async_trait;
+    use tendhost_exec::LocalExecutor;
+    use tendhost_exec::error::ExecError;
+    use tendhost_exec::result::CommandResult;
+
+    /// Executor answering osquery commands with canned JSON by table name
+    struct ScriptedExecutor {
+        responses: Vec<(&'static str, &'static str)>,
+    }
+
+    #[async_trait]
+    impl RemoteExecutor for ScriptedExecutor {
+        async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+            let stdout = if cmd.starts_with("which") {
+                "/usr/bin/osqueryi"
+            } else {
+                self.responses
+                    .iter()
+                    .find(|(table, _)| cmd.contains(&format!("FROM {table}")))
+                    .map_or("[]", |(_, json)| json)
+            };
+
+            Ok(CommandResult {
+                status: 0,
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+                duration: Duration::from_millis(1),
+            })
         }
 
-        let cpu_rows: Vec<CpuRow> = self.client.query(&queries::cpu_info()).await?;
-        let cpu_row = cpu_rows
-            .into_iter()
-            .next()
-            .ok_or_else(|| InventoryError::ParseError("no cpu_info data".to_string()))?;
-
-        let cpu = CpuInfo {
-            model: cpu_row.model,
-            physical_cores: cpu_row.physical_cores.parse().unwrap_or(0),
-            logical_cores: cpu_row.logical_cores.parse().unwrap_or(0),
-            speed_mhz: cpu_row.mhz.parse().unwrap_or(0),
-            vendor: cpu_row.vendor,
-        };
-
-        // Get memory info
-        #[derive(Deserialize)]
-        struct MemoryRow {
-            total: String,
-            free: String,
-            used: String,
-            swap_total: String,
-            swap_free: String,
+        async fn run_with_timeout(
+            &self,
+            cmd: &str,
+            _timeout: Duration,
+        ) -> Result<CommandResult, ExecError> {
+            self.run(cmd).await
         }
 
-        let mem_rows: Vec<MemoryRow> = self.client.query(&queries::memory_info()).await?;
-        let mem_row = mem_rows
-            .into_iter()
-            .next()
-            .ok_or_else(|| InventoryError::ParseError("no memory_info data".to_string()))?;
-
-        let memory = MemoryInfo {
-            total_bytes: mem_row.total.parse().unwrap_or(0),
-            free_bytes: mem_row.free.parse().unwrap_or(0),
-            used_bytes: mem_row.used.parse().unwrap_or(0),
-            swap_total_bytes: mem_row.swap_total.parse().unwrap_or(0),
-            swap_free_bytes: mem_row.swap_free.parse().unwrap_or(0),
-        };
-
-        // Get disk info from mounts
-        #[derive(Deserialize)]
-        struct MountRow {
-            device: String,
-            path: String,
-            #[serde(rename = "type")]
-            fs_type: String,
-            blocks: String,
-            blocks_free: String,
-            #[serde(rename = "blocks_size")]
-            block_size: String,
+        fn executor_type(&self) -> &'static str {
+            "scripted"
         }
+    }
 
-        let mount_rows: Vec<MountRow> = self.client.query(&queries::mounts()).await?;
-        let mut disks = Vec::new();
-
-        for mount in mount_rows {
-            let block_size: u64 = mount.block_size.parse().unwrap_or(4096);
-            let total_blocks: u64 = mount.blocks.parse().unwrap_or(0);
-            let free_blocks: u64 = mount.blocks_free.parse().unwrap_or(0);
-
-            disks.push(DiskInfo {
-                device: mount.device,
-                mount_point: mount.path,
-                filesystem: mount.fs_type,
-                total_bytes: total_blocks * block_size,
-                free_bytes: free_blocks * block_size,
-                used_bytes: (total_blocks - free_blocks) * block_size,
-            });
+    /// Executor for a host without osquery, answering by command prefix
+    struct ShellOnlyExecutor;
+
+    #[async_trait]
+    impl RemoteExecutor for ShellOnlyExecutor {
+        async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+            let (status, stdout) = match cmd {
+                "which osqueryi" | "which dpkg-query" => (1, ""),
+                "which rpm" => (0, "/usr/bin/rpm"),
+                "cat /etc/os-release" => (0, "NAME=\"Fedora Linux\"\nID=fedora\nVERSION_ID=40\n"),
+                "uname -n -r -m" => (0, "fedora-vm 6.8.5-301.fc40.x86_64 x86_64\n"),
+                "cat /proc/uptime" => (0, "120.5 200.1\n"),
+                c if c.starts_with("rpm -qa") => (0, "bash\t5.2.26-3.fc40\tx86_64\t1713350000\n"),
+                _ => (127, ""),
+            };
+
+            Ok(CommandResult {
+                status,
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+                duration: Duration::from_millis(1),
+            })
         }
 
-        // Get network interfaces
-        #[derive(Deserialize)]
-        struct InterfaceRow {
-            interface: String,
-            mac: String,
+        async fn run_with_timeout(
+            &self,
+            cmd: &str,
+            _timeout: Duration,
+        ) -> Result<CommandResult, ExecError> {
+            self.run(cmd).await
         }
 
-        #[derive(Deserialize)]
-        struct AddressRow {
-            interface: String,
-            address: String,
+        fn executor_type(&self) -> &'static str {
+            "shell-only"
         }
+    }
 
-        let iface_rows: Vec<InterfaceRow> =
-            self.client.query(&queries::interface_details()).await?;
-        let addr_rows: Vec<AddressRow> = self.client.query(&queries::interface_addresses()).await?;
+    /// osquery host where every query takes `delay`
+    struct SlowExecutor {
+        delay: Duration,
+        commands: std::sync::Mutex<Vec<String>>,
+    }
 
-        let mut network_interfaces = Vec::new();
+    impl SlowExecutor {
+        fn new(delay: Duration) -> Self {
+            Self {
+                delay,
+                commands: std::sync::Mutex::default(),
+            }
+        }
+    }
 
-        for iface in iface_rows {
-            let addrs: Vec<&AddressRow> = addr_rows
-                .iter()
-                .filter(|a| a.interface == iface.interface)
-                .collect();
+    #[async_trait]
+    impl RemoteExecutor for SlowExecutor {
+        async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+            let stdout = if cmd.starts_with("which") {
+                "/usr/bin/osqueryi"
+            } else {
+                self.commands.lock().unwrap().push(cmd.to_string());
+                tokio::time::sleep(self.delay).await;
+                "[]"
+            };
+
+            Ok(CommandResult {
+                status: 0,
+                stdout: stdout.to_string(),
+                stderr: String::new(),
+                duration: self.delay,
+            })
+        }
 
-            let ipv4: Vec<String> = addrs
-                .iter()
-                .filter(|a| !a.address.contains(':'))
-                .map(|a| a.address.clone())
-                .collect();
+        async fn run_with_timeout(
+            &self,
+            cmd: &str,
+            _timeout: Duration,
+        ) -> Result<CommandResult, ExecError> {
+            self.run(cmd).await
+        }
 
-            let ipv6: Vec<String> = addrs
-                .iter()
-                .filter(|a| a.address.contains(':'))
-                .map(|a| a.address.clone())
-                .collect();
-
-            network_interfaces.push(NetworkInterface {
-                name: iface.interface,
-                mac: iface.mac,
-                ipv4,
-                ipv6,
-            });
+        fn executor_type(&self) -> &'static str {
+            "slow"
         }
+    }
 
-        Ok(HardwareInfo {
-            cpu,
-            memory,
-            disks,
-            network_interfaces,
-        })
+    #[tokio::test]
+    async fn test_collect_runs_queries_concurrently() {
+        let delay = Duration::from_millis(100);
+        let executor = Arc::new(SlowExecutor::new(delay));
+        let collector = InventoryCollector::new(executor.clone(), Duration::from_secs(60));
+        collector.backend_name().await;
+
+        let started = std::time::Instant::now();
+        collector
+            .collect(CollectOptions {
+                max_concurrency: 8,
+                ..CollectOptions::all()
+            })
+            .await
+            .unwrap();
+        let elapsed = started.elapsed();
+
+        // 13 queries in all; one after another they would take 1.3s
+        let queries = executor.commands.lock().unwrap().len();
+        assert_eq!(queries, 13);
+        assert!(elapsed >= delay);
+        assert!(elapsed < delay * 3, "took {elapsed:?}");
     }
 
-    /// Get installed packages
-    ///
-    /// # Errors
-    /// Returns an error if osquery queries fail or if no package manager is available.
-    #[instrument(skip(self))]
-    pub async fn get_packages(&self) -> Result<Vec<Package>, InventoryError> {
-        debug!("collecting packages");
+    #[tokio::test]
+    async fn test_collect_bounds_concurrent_sections() {
+        let delay = Duration::from_millis(100);
+        let collector =
+            InventoryCollector::new(Arc::new(SlowExecutor::new(delay)), Duration::from_secs(60));
+        collector.backend_name().await;
+
+        // Ports and services only, one section at a time
+        let started = std::time::Instant::now();
+        collector
+            .collect(CollectOptions {
+                system: false,
+                hardware: false,
+                packages: false,
+                docker: false,
+                ports: true,
+                services: true,
+                max_concurrency: 1,
+            })
+            .await
+            .unwrap();
+        assert!(started.elapsed() >= delay * 2);
+    }
 
-        let mut packages = Vec::new();
+    #[tokio::test]
+    async fn test_collect_skips_unselected_sections() {
+        let executor = Arc::new(SlowExecutor::new(Duration::ZERO));
+        let collector = InventoryCollector::new(executor.clone(), Duration::from_secs(60));
+
+        let inventory = collector
+            .collect(CollectOptions {
+                packages: false,
+                docker: false,
+                ..CollectOptions::all()
+            })
+            .await
+            .unwrap();
+
+        assert!(inventory.packages.is_empty());
+        let commands = executor.commands.lock().unwrap();
+        assert!(commands.iter().any(|c| c.contains("FROM cpu_info")));
+        assert!(!commands.iter().any(|c| c.contains("packages")));
+        assert!(!commands.iter().any(|c| c.contains("docker")));
+    }
 
-        // Try deb_packages first
-        #[derive(Deserialize)]
-        struct DebRow {
-            name: String,
-            version: String,
-            arch: String,
-            install_time: Option<String>,
-        }
+    #[tokio::test]
+    async fn test_invalidation_requeries_only_matching_tables() {
+        let executor = Arc::new(SlowExecutor::new(Duration::ZERO));
+        let collector = InventoryCollector::new(executor.clone(), Duration::from_secs(60));
+        collector.collect_full().await.unwrap();
+        let first = executor.commands.lock().unwrap().len();
+
+        // Everything is cached still, even the short-lived memory info
+        collector.collect_full().await.unwrap();
+        assert_eq!(executor.commands.lock().unwrap().len(), first);
+
+        assert_eq!(collector.invalidate_matching("deb_packages").await, 1);
+        assert_eq!(collector.invalidate_matching("docker_*").await, 2);
+        assert_eq!(collector.invalidate_matching("docker_*").await, 0);
+        executor.commands.lock().unwrap().clear();
+        collector.collect_full().await.unwrap();
+
+        let commands = executor.commands.lock().unwrap();
+        assert_eq!(commands.len(), 3, "{commands:?}");
+        assert!(commands.iter().any(|c| c.contains("FROM deb_packages")));
+        assert!(
+            commands
+                .iter()
+                .any(|c| c.contains("FROM docker_containers"))
+        );
+        assert!(commands.iter().any(|c| c.contains("FROM docker_images")));
+    }
 
-        match self.client.query::<DebRow>(&queries::deb_packages()).await {
-            Ok(rows) => {
-                for row in rows {
-                    packages.push(Package {
-                        name: row.name,
-                        version: row.version,
-                        arch: row.arch,
-                        source: PackageSource::Deb,
-                        install_time: row.install_time.and_then(|t| {
-                            t.parse::<i64>()
-                                .ok()
-                                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
-                        }),
-                        size_bytes: None,
-                    });
-                }
-            }
-            Err(InventoryError::TableNotAvailable(_)) => {
-                // Try rpm_packages
-                #[derive(Deserialize)]
-                struct RpmRow {
-                    name: String,
-                    version: String,
-                    arch: String,
-                    install_time: Option<String>,
-                }
+    #[tokio::test]
+    async fn test_backend_selection() {
+        let collector =
+            InventoryCollector::new(Arc::new(ShellOnlyExecutor), Duration::from_secs(60));
+        assert_eq!(collector.backend_name().await, "shell");
+
+        let collector = InventoryCollector::new(
+            Arc::new(ScriptedExecutor { responses: vec![] }),
+            Duration::from_secs(60),
+        );
+        assert_eq!(collector.backend_name().await, "osquery");
+    }
+
+    /// Host where `osqueryi` shows up after the first probe
+    struct LateOsqueryExecutor {
+        probes: std::sync::atomic::AtomicUsize,
+    }
 
-                match self.client.query::<RpmRow>(&queries::rpm_packages()).await {
-                    Ok(rows) => {
-                        for row in rows {
-                            packages.push(Package {
-                                name: row.name,
-                                version: row.version,
-                                arch: row.arch,
-                                source: PackageSource::Rpm,
-                                install_time: row.install_time.and_then(|t| {
-                                    t.parse::<i64>()
-                                        .ok()
-                                        .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
-                                }),
-                                size_bytes: None,
-                            });
-                        }
-                    }
-                    Err(e) => return Err(e),
+    #[async_trait]
+    impl RemoteExecutor for LateOsqueryExecutor {
+        async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+            if cmd == "which osqueryi" {
+                let probe = self
+                    .probes
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                if probe == 0 {
+                    return Err(ExecError::ConnectionFailed("connection reset".to_string()));
                 }
             }
-            Err(e) => return Err(e),
+            ScriptedExecutor { responses: vec![] }.run(cmd).await
         }
 
-        info!(count = packages.len(), "collected packages");
+        async fn run_with_timeout(
+            &self,
+            cmd: &str,
+            _timeout: Duration,
+        ) -> Result<CommandResult, ExecError> {
+            self.run(cmd).await
+        }
 
-        Ok(packages)
+        fn executor_type(&self) -> &'static str {
+            "late-osquery"
+        }
     }
 
-    /// Get Docker containers
-    ///
-    /// # Errors
-    /// Returns an error if osquery queries fail or Docker is not available.
-    #[instrument(skip(self))]
-    pub async fn get_docker_containers(&self) -> Result<Vec<Container>, InventoryError> {
-        debug!("collecting docker containers");
-
-        #[derive(Deserialize)]
-        struct ContainerRow {
-            id: String,
-            name: String,
-            image: String,
-            state: String,
-            status: String,
-            created: String,
-        }
+    #[tokio::test]
+    async fn test_failed_probe_is_retried_until_osquery_is_found() {
+        let executor = Arc::new(LateOsqueryExecutor {
+            probes: std::sync::atomic::AtomicUsize::new(0),
+        });
+        let collector = InventoryCollector::new(executor.clone(), Duration::from_secs(60));
+
+        assert_eq!(collector.backend_name().await, "shell");
+        assert_eq!(collector.backend_name().await, "osquery");
+        assert_eq!(collector.backend_name().await, "osquery");
+        assert_eq!(executor.probes.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
 
-        let rows: Vec<ContainerRow> = self.client.query(&queries::docker_containers()).await?;
-
-        let containers = rows
-            .into_iter()
-            .map(|r| Container {
-                id: r.id,
-                name: r.name,
-                image: r.image,
-                state: r.state,
-                status: r.status,
-                created: r
-                    .created
-                    .parse::<i64>()
-                    .ok()
-                    .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
-                    .unwrap_or_else(Utc::now),
-                ports: Vec::new(),  // Would need docker_container_ports table
-                mounts: Vec::new(), // Would need docker_container_mounts table
-            })
-            .collect();
+    #[tokio::test]
+    async fn test_collect_full_with_shell_fallback() {
+        let collector =
+            InventoryCollector::new(Arc::new(ShellOnlyExecutor), Duration::from_secs(60));
+
+        let inventory = collector.collect_full().await.unwrap();
+
+        assert_eq!(inventory.system.hostname, "fedora-vm");
+        assert_eq!(inventory.system.os_name, "Fedora Linux");
+        assert_eq!(inventory.system.kernel_version, "6.8.5-301.fc40.x86_64");
+        assert_eq!(inventory.system.uptime_seconds, 120);
+        assert_eq!(inventory.packages.len(), 1);
+        assert_eq!(inventory.packages[0].source, PackageSource::Rpm);
+        // Hardware commands fail on this host; collection still succeeds
+        assert!(inventory.hardware.disks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_listening_ports_with_process_names() {
+        let executor = Arc::new(ScriptedExecutor {
+            responses: vec![(
+                "listening_ports",
+                r#"[
+                    {"pid":"812","port":"22","protocol":"6","address":"0.0.0.0","process_name":"sshd"},
+                    {"pid":"990","port":"53","protocol":"17","address":"127.0.0.53","process_name":null},
+                    {"pid":"1200","port":"0","protocol":"0","address":"/run/x.sock","process_name":"x"}
+                ]"#,
+            )],
+        });
+        let collector = InventoryCollector::new(executor, Duration::from_secs(60));
+
+        let ports = collector.get_listening_ports().await.unwrap();
 
-        Ok(containers)
+        assert_eq!(ports.len(), 2);
+        assert_eq!(ports[0].port, 22);
+        assert_eq!(ports[0].protocol, "tcp");
+        assert_eq!(ports[0].process_name.as_deref(), Some("sshd"));
+        assert_eq!(ports[1].protocol, "udp");
+        assert_eq!(ports[1].process_name, None);
     }
 
-    /// Get Docker images
-    ///
-    /// # Errors
-    /// Returns an error if osquery queries fail or Docker is not available.
-    #[instrument(skip(self))]
-    pub async fn get_docker_images(&self) -> Result<Vec<Image>, InventoryError> {
-        debug!("collecting docker images");
-
-        #[derive(Deserialize)]
-        struct ImageRow {
-            id: String,
-            tags: String,
-            created: String,
-            size: String,
-        }
+    #[tokio::test]
+    async fn test_network_interfaces_grouped_by_name() {
+        let executor = Arc::new(ScriptedExecutor {
+            responses: vec![
+                (
+                    "cpu_info",
+                    r#"[{"model":"EPYC","vendor":"AMD","physical_cores":"4","logical_cores":"8","mhz":"3000"}]"#,
+                ),
+                (
+                    "memory_info",
+                    r#"[{"total":"100","free":"40","used":"60","swap_total":"0","swap_free":"0"}]"#,
+                ),
+                (
+                    "interface_details",
+                    r#"[
+                        {"interface":"eth0","mac":"aa:bb","type":"6","address":"10.0.0.2","mask":"255.0.0.0"},
+                        {"interface":"eth0","mac":"aa:bb","type":"6","address":"fe80::1","mask":"ffff::"},
+                        {"interface":"wg0","mac":"","type":"65534","address":null,"mask":null}
+                    ]"#,
+                ),
+            ],
+        });
+        let collector = InventoryCollector::new(executor, Duration::from_secs(60));
 
-        let rows: Vec<ImageRow> = self.client.query(&queries::docker_images()).await?;
-
-        let images = rows
-            .into_iter()
-            .map(|r| Image {
-                id: r.id,
-                tags: r.tags.split(',').map(|s| s.trim().to_string()).collect(),
-                created: r
-                    .created
-                    .parse::<i64>()
-                    .ok()
-                    .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
-                    .unwrap_or_else(Utc::now),
-                size_bytes: r.size.parse().unwrap_or(0),
-            })
-            .collect();
+        let hardware = collector.get_hardware_info().await.unwrap();
 
-        Ok(images)
+        assert_eq!(hardware.network_interfaces.len(), 2);
+        assert_eq!(hardware.network_interfaces[0].ipv4, vec!["10.0.0.2"]);
+        assert_eq!(hardware.network_interfaces[0].ipv6, vec!["fe80::1"]);
+        assert!(hardware.network_interfaces[1].ipv4.is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tendhost_exec::LocalExecutor;
+    #[tokio::test]
+    async fn test_get_services() {
+        let executor = Arc::new(ScriptedExecutor {
+            responses: vec![(
+                "systemd_units",
+                r#"[
+                    {"id":"nginx.service","description":"nginx","active_state":"active","sub_state":"running"},
+                    {"id":"backup.service","description":"Backup","active_state":"failed","sub_state":"failed"}
+                ]"#,
+            )],
+        });
+        let collector = InventoryCollector::new(executor, Duration::from_secs(60));
+
+        let services = collector.get_services().await.unwrap();
+
+        assert_eq!(services.len(), 2);
+        assert!(services[0].is_running());
+        assert!(services[1].is_failed());
+    }
 
     // These tests require osquery to be installed
     // Marked as ignore for CI
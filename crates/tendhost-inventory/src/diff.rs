@@ -0,0 +1,528 @@
+//! Differences between two inventory collections
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::types::{Container, HostInventory, Package};
+
+/// Smallest change in a disk's used space that is reported (1 GiB)
+pub const DEFAULT_DISK_DELTA_THRESHOLD: u64 = 1 << 30;
+
+/// What changed on a host between two inventory collections
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InventoryDiff {
+    /// When the earlier inventory was collected
+    pub from: DateTime<Utc>,
+    /// When the later inventory was collected
+    pub to: DateTime<Utc>,
+    /// Packages added, removed or changed version, by name
+    pub packages: Vec<PackageChange>,
+    /// Containers added, removed or running a different image, by name
+    pub containers: Vec<ContainerChange>,
+    /// Disks whose used space changed by at least the threshold
+    pub disks: Vec<DiskChange>,
+    /// Kernel version change, if the kernel changed
+    pub kernel: Option<KernelChange>,
+}
+
+/// A package that differs between two inventories
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum PackageChange {
+    /// Newly installed
+    Added { name: String, version: String },
+    /// No longer installed
+    Removed { name: String, version: String },
+    /// Installed in another version
+    VersionChanged {
+        name: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// A Docker container that differs between two inventories
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum ContainerChange {
+    /// New container
+    Added { name: String, image: String },
+    /// Container that no longer exists
+    Removed { name: String, image: String },
+    /// Container recreated from another image
+    ImageChanged {
+        name: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Change in a mounted disk's used space
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskChange {
+    /// Mount point
+    pub mount_point: String,
+    /// Used bytes in the earlier inventory
+    pub used_bytes_before: u64,
+    /// Used bytes in the later inventory
+    pub used_bytes_after: u64,
+}
+
+impl DiskChange {
+    /// Growth in used bytes; negative when space was freed
+    #[must_use]
+    pub fn delta_bytes(&self) -> i128 {
+        i128::from(self.used_bytes_after) - i128::from(self.used_bytes_before)
+    }
+}
+
+/// Kernel version change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KernelChange {
+    /// Earlier kernel version
+    pub from: String,
+    /// Later kernel version
+    pub to: String,
+}
+
+impl PackageChange {
+    /// Package name
+    #[must_use]
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Added { name, .. }
+            | Self::Removed { name, .. }
+            | Self::VersionChanged { name, .. } => name,
+        }
+    }
+}
+
+impl InventoryDiff {
+    /// Compare `previous` with `current`
+    ///
+    /// Disk usage changes smaller than `disk_threshold` bytes are left out,
+    /// as are disks present in only one of the inventories.
+    #[must_use]
+    pub fn between(previous: &HostInventory, current: &HostInventory, disk_threshold: u64) -> Self {
+        let kernel = (!previous.system.kernel_version.is_empty()
+            && !current.system.kernel_version.is_empty()
+            && previous.system.kernel_version != current.system.kernel_version)
+            .then(|| KernelChange {
+                from: previous.system.kernel_version.clone(),
+                to: current.system.kernel_version.clone(),
+            });
+
+        let disks = previous
+            .hardware
+            .disks
+            .iter()
+            .filter_map(|before| {
+                let after = current
+                    .hardware
+                    .disks
+                    .iter()
+                    .find(|d| d.mount_point == before.mount_point)?;
+                (before.used_bytes.abs_diff(after.used_bytes) >= disk_threshold).then(|| {
+                    DiskChange {
+                        mount_point: before.mount_point.clone(),
+                        used_bytes_before: before.used_bytes,
+                        used_bytes_after: after.used_bytes,
+                    }
+                })
+            })
+            .collect();
+
+        Self {
+            from: previous.collected_at,
+            to: current.collected_at,
+            packages: package_changes(&previous.packages, &current.packages),
+            containers: container_changes(&previous.docker_containers, &current.docker_containers),
+            disks,
+            kernel,
+        }
+    }
+
+    /// Whether nothing changed
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.packages.is_empty()
+            && self.containers.is_empty()
+            && self.disks.is_empty()
+            && self.kernel.is_none()
+    }
+
+    /// One-line description, e.g. `3 packages changed (2 updated, 1 added), kernel 6.1.0-17 → 6.1.0-18`
+    #[must_use]
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+
+        if !self.packages.is_empty() {
+            let (mut updated, mut added, mut removed) = (0, 0, 0);
+            for change in &self.packages {
+                match change {
+                    PackageChange::VersionChanged { .. } => updated += 1,
+                    PackageChange::Added { .. } => added += 1,
+                    PackageChange::Removed { .. } => removed += 1,
+                }
+            }
+            let details: Vec<String> =
+                [(updated, "updated"), (added, "added"), (removed, "removed")]
+                    .into_iter()
+                    .filter(|(n, _)| *n > 0)
+                    .map(|(n, what)| format!("{n} {what}"))
+                    .collect();
+            parts.push(format!(
+                "{} changed ({})",
+                plural(self.packages.len(), "package"),
+                details.join(", ")
+            ));
+        }
+        if let Some(kernel) = &self.kernel {
+            parts.push(format!("kernel {} → {}", kernel.from, kernel.to));
+        }
+        if !self.containers.is_empty() {
+            parts.push(format!(
+                "{} changed",
+                plural(self.containers.len(), "container")
+            ));
+        }
+        for disk in &self.disks {
+            let delta = disk.delta_bytes();
+            let sign = if delta < 0 { '-' } else { '+' };
+            parts.push(format!(
+                "{} {sign}{}",
+                disk.mount_point,
+                format_bytes(delta.unsigned_abs())
+            ));
+        }
+
+        if parts.is_empty() {
+            "no changes".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+fn package_changes(previous: &[Package], current: &[Package]) -> Vec<PackageChange> {
+    // Multi-arch packages share a name, so the architecture is part of the key
+    let index = |packages: &[Package]| -> BTreeMap<(String, String), String> {
+        packages
+            .iter()
+            .map(|p| ((p.name.clone(), p.arch.clone()), p.version.clone()))
+            .collect()
+    };
+    let before = index(previous);
+    let mut after = index(current);
+
+    let mut changes = Vec::new();
+    for ((name, arch), version) in before {
+        match after.remove(&(name.clone(), arch)) {
+            None => changes.push(PackageChange::Removed { name, version }),
+            Some(to) if to != version => changes.push(PackageChange::VersionChanged {
+                name,
+                from: version,
+                to,
+            }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(
+        after
+            .into_iter()
+            .map(|((name, _), version)| PackageChange::Added { name, version }),
+    );
+    changes.sort_by(|a, b| a.name().cmp(b.name()));
+    changes
+}
+
+fn container_changes(previous: &[Container], current: &[Container]) -> Vec<ContainerChange> {
+    let index = |containers: &[Container]| -> BTreeMap<String, String> {
+        containers
+            .iter()
+            .map(|c| (c.name.clone(), c.image.clone()))
+            .collect()
+    };
+    let before = index(previous);
+    let mut after = index(current);
+
+    let mut changes = Vec::new();
+    for (name, image) in before {
+        match after.remove(&name) {
+            None => changes.push(ContainerChange::Removed { name, image }),
+            Some(to) if to != image => changes.push(ContainerChange::ImageChanged {
+                name,
+                from: image,
+                to,
+            }),
+            Some(_) => {}
+        }
+    }
+    changes.extend(
+        after
+            .into_iter()
+            .map(|(name, image)| ContainerChange::Added { name, image }),
+    );
+    changes
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: u128) -> String {
+    const MIB: f64 = (1u64 << 20) as f64;
+    const GIB: f64 = (1u64 << 30) as f64;
+    let bytes = bytes as f64;
+    if bytes >= GIB {
+        format!("{:.1} GiB", bytes / GIB)
+    } else {
+        format!("{:.0} MiB", bytes / MIB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::types::{DiskInfo, PackageSource};
+
+    fn package(name: &str, version: &str) -> Package {
+        Package {
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: "amd64".to_string(),
+            source: PackageSource::Deb,
+            install_time: None,
+            size_bytes: None,
+        }
+    }
+
+    fn container(name: &str, image: &str) -> Container {
+        Container {
+            id: format!("{name}-id"),
+            name: name.to_string(),
+            image: image.to_string(),
+            state: "running".to_string(),
+            status: "Up 2 hours".to_string(),
+            created: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+            ports: Vec::new(),
+            mounts: Vec::new(),
+        }
+    }
+
+    fn disk(mount_point: &str, used_bytes: u64) -> DiskInfo {
+        DiskInfo {
+            device: "/dev/sda1".to_string(),
+            mount_point: mount_point.to_string(),
+            filesystem: "ext4".to_string(),
+            total_bytes: 100 << 30,
+            free_bytes: (100 << 30) - used_bytes,
+            used_bytes,
+        }
+    }
+
+    fn inventory(
+        kernel: &str,
+        packages: Vec<Package>,
+        containers: Vec<Container>,
+        disks: Vec<DiskInfo>,
+    ) -> HostInventory {
+        let mut inventory = HostInventory::new();
+        inventory.system.kernel_version = kernel.to_string();
+        inventory.packages = packages;
+        inventory.docker_containers = containers;
+        inventory.hardware.disks = disks;
+        inventory
+    }
+
+    #[test]
+    fn test_identical_inventories_have_no_changes() {
+        let a = inventory(
+            "6.1.0-17-amd64",
+            vec![package("curl", "7.88.1")],
+            vec![container("web", "nginx:1.25")],
+            vec![disk("/", 10 << 30)],
+        );
+        let diff = InventoryDiff::between(&a, &a.clone(), DEFAULT_DISK_DELTA_THRESHOLD);
+        assert!(diff.is_empty());
+        assert_eq!(diff.summary(), "no changes");
+    }
+
+    #[test]
+    fn test_package_changes() {
+        let before = inventory(
+            "",
+            vec![
+                package("curl", "7.88.1"),
+                package("vim", "9.0"),
+                package("telnet", "0.17"),
+            ],
+            vec![],
+            vec![],
+        );
+        let after = inventory(
+            "",
+            vec![
+                package("curl", "7.88.2"),
+                package("vim", "9.0"),
+                package("htop", "3.2"),
+            ],
+            vec![],
+            vec![],
+        );
+
+        let diff = InventoryDiff::between(&before, &after, DEFAULT_DISK_DELTA_THRESHOLD);
+        assert_eq!(
+            diff.packages,
+            [
+                PackageChange::VersionChanged {
+                    name: "curl".to_string(),
+                    from: "7.88.1".to_string(),
+                    to: "7.88.2".to_string(),
+                },
+                PackageChange::Added {
+                    name: "htop".to_string(),
+                    version: "3.2".to_string(),
+                },
+                PackageChange::Removed {
+                    name: "telnet".to_string(),
+                    version: "0.17".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            diff.summary(),
+            "3 packages changed (1 updated, 1 added, 1 removed)"
+        );
+    }
+
+    #[test]
+    fn test_multi_arch_packages_are_compared_per_arch() {
+        let mut i386 = package("libc6", "2.36-9");
+        i386.arch = "i386".to_string();
+        let before = inventory(
+            "",
+            vec![package("libc6", "2.36-9"), i386.clone()],
+            vec![],
+            vec![],
+        );
+        let after = inventory("", vec![package("libc6", "2.36-9")], vec![], vec![]);
+
+        let diff = InventoryDiff::between(&before, &after, DEFAULT_DISK_DELTA_THRESHOLD);
+        assert_eq!(
+            diff.packages,
+            [PackageChange::Removed {
+                name: "libc6".to_string(),
+                version: "2.36-9".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_container_changes() {
+        let before = inventory(
+            "",
+            vec![],
+            vec![container("web", "nginx:1.25"), container("old", "redis:7")],
+            vec![],
+        );
+        let after = inventory(
+            "",
+            vec![],
+            vec![
+                container("web", "nginx:1.27"),
+                container("db", "postgres:16"),
+            ],
+            vec![],
+        );
+
+        let diff = InventoryDiff::between(&before, &after, DEFAULT_DISK_DELTA_THRESHOLD);
+        assert_eq!(
+            diff.containers,
+            [
+                ContainerChange::Removed {
+                    name: "old".to_string(),
+                    image: "redis:7".to_string(),
+                },
+                ContainerChange::ImageChanged {
+                    name: "web".to_string(),
+                    from: "nginx:1.25".to_string(),
+                    to: "nginx:1.27".to_string(),
+                },
+                ContainerChange::Added {
+                    name: "db".to_string(),
+                    image: "postgres:16".to_string(),
+                },
+            ]
+        );
+        assert_eq!(diff.summary(), "3 containers changed");
+    }
+
+    #[test]
+    fn test_disk_changes_below_threshold_are_ignored() {
+        let before = inventory(
+            "",
+            vec![],
+            vec![],
+            vec![
+                disk("/", 10 << 30),
+                disk("/var", 20 << 30),
+                disk("/boot", 1 << 28),
+            ],
+        );
+        let after = inventory(
+            "",
+            vec![],
+            vec![],
+            vec![disk("/", (10 << 30) + (100 << 20)), disk("/var", 17 << 30)],
+        );
+
+        let diff = InventoryDiff::between(&before, &after, DEFAULT_DISK_DELTA_THRESHOLD);
+        assert_eq!(
+            diff.disks,
+            [DiskChange {
+                mount_point: "/var".to_string(),
+                used_bytes_before: 20 << 30,
+                used_bytes_after: 17 << 30,
+            }]
+        );
+        assert_eq!(diff.disks[0].delta_bytes(), -(3 << 30));
+        assert_eq!(diff.summary(), "/var -3.0 GiB");
+
+        let diff = InventoryDiff::between(&before, &after, 50 << 20);
+        assert_eq!(diff.summary(), "/ +100 MiB, /var -3.0 GiB");
+    }
+
+    #[test]
+    fn test_kernel_change() {
+        let before = inventory("6.1.0-17-amd64", vec![], vec![], vec![]);
+        let after = inventory("6.1.0-18-amd64", vec![], vec![], vec![]);
+
+        let diff = InventoryDiff::between(&before, &after, DEFAULT_DISK_DELTA_THRESHOLD);
+        assert_eq!(
+            diff.kernel,
+            Some(KernelChange {
+                from: "6.1.0-17-amd64".to_string(),
+                to: "6.1.0-18-amd64".to_string(),
+            })
+        );
+        assert_eq!(diff.summary(), "kernel 6.1.0-17-amd64 → 6.1.0-18-amd64");
+
+        // An uncollected kernel version is not a change
+        let unknown = inventory("", vec![], vec![], vec![]);
+        assert!(
+            InventoryDiff::between(&before, &unknown, DEFAULT_DISK_DELTA_THRESHOLD)
+                .kernel
+                .is_none()
+        );
+    }
+}
@@ -33,6 +33,15 @@ pub enum InventoryError {
     #[error("query timeout after {0:?}")]
     Timeout(std::time::Duration),
 
+    /// Section not supported by the active collection backend
+    #[error("not supported by {backend} backend: {section}")]
+    Unsupported {
+        /// Backend name
+        backend: &'static str,
+        /// Inventory section
+        section: &'static str,
+    },
+
     /// Cache error
     #[error("cache error: {0}")]
     CacheError(String),
@@ -16,14 +16,18 @@
 //! # }
 //! ```
 
+pub mod backend;
 pub mod collector;
+pub mod diff;
 pub mod error;
 pub mod osquery;
 pub mod query;
 pub mod types;
 
-pub use collector::InventoryCollector;
+pub use backend::{CollectionBackend, OsqueryBackend, ShellBackend};
+pub use collector::{CollectOptions, InventoryCollector};
+pub use diff::{ContainerChange, DiskChange, InventoryDiff, KernelChange, PackageChange};
 pub use error::InventoryError;
-pub use osquery::OsqueryClient;
+pub use osquery::{MIN_OSQUERY_VERSION, OsqueryClient};
 pub use query::{Query, queries};
 pub use types::*;
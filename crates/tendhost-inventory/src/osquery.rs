@@ -6,6 +6,7 @@ use std::time::{Duration, Instant};
 
 use serde::de::DeserializeOwned;
 use serde_json::Value;
+use tendhost_exec::ShellCommand;
 use tendhost_exec::traits::RemoteExecutor;
 use tokio::sync::RwLock;
 use tracing::{debug, instrument};
@@ -13,6 +14,37 @@ use tracing::{debug, instrument};
 use crate::error::InventoryError;
 use crate::query::Query;
 
+/// Oldest osquery release whose tables the inventory queries rely on
+pub const MIN_OSQUERY_VERSION: &str = "5.0";
+
+/// The version in `osqueryi --version` output, e.g. `5.12.1` from
+/// "osqueryi version 5.12.1"
+#[must_use]
+pub fn parse_version(output: &str) -> Option<String> {
+    output
+        .split_whitespace()
+        .find(|word| word.starts_with(|c: char| c.is_ascii_digit()) && word.contains('.'))
+        .map(str::to_string)
+}
+
+/// Whether `version` is at least [`MIN_OSQUERY_VERSION`]
+///
+/// Missing or non-numeric components count as zero, so `5` and `5.0.0`
+/// both qualify.
+#[must_use]
+pub fn version_supported(version: &str) -> bool {
+    let parts = |v: &str| -> Vec<u32> {
+        v.split(['.', '-'])
+            .take(3)
+            .map(|part| part.parse().unwrap_or(0))
+            .collect()
+    };
+    let (mut found, mut required) = (parts(version), parts(MIN_OSQUERY_VERSION));
+    found.resize(3, 0);
+    required.resize(3, 0);
+    found >= required
+}
+
 /// Cached query result
 #[derive(Debug, Clone)]
 struct CachedResult {
@@ -22,6 +54,8 @@ struct CachedResult {
     cached_at: Instant,
     /// Time-to-live
     ttl: Duration,
+    /// Tables the query read
+    tables: Vec<String>,
 }
 
 impl CachedResult {
@@ -97,8 +131,7 @@ impl OsqueryClient {
             ));
         }
 
-        // Build command - escape single quotes in SQL by replacing with '"'"'
-        let cmd = format!("osqueryi --json '{}'", sql.replace('\'', "'\"'\"'"));
+        let cmd = query_command(sql);
 
         // Execute with timeout
         let result = self
@@ -160,7 +193,8 @@ impl OsqueryClient {
     ///
     /// # Arguments
     /// * `query` - Query builder
-    /// * `ttl` - Cache time-to-live (None for default)
+    /// * `ttl` - Cache time-to-live (None for the query's own TTL, or else
+    ///   the default)
     ///
     /// # Returns
     /// * `Ok(Vec<T>)` - Deserialized results (cached or fresh)
@@ -200,12 +234,13 @@ impl OsqueryClient {
         let json = self.query_raw(&sql).await?;
 
         // Store in cache
-        let ttl = ttl.unwrap_or(self.default_ttl);
+        let ttl = ttl.or(query.cache_ttl()).unwrap_or(self.default_ttl);
         let cached = CachedResult {
             data: serde_json::to_string(&json)
                 .map_err(|e| InventoryError::CacheError(e.to_string()))?,
             cached_at: Instant::now(),
             ttl,
+            tables: query.tables().map(str::to_string).collect(),
         };
 
         {
@@ -231,6 +266,23 @@ impl OsqueryClient {
         debug!("cache cleared");
     }
 
+    /// Drop cached results read from `table`, returning how many were dropped
+    ///
+    /// A trailing `*` matches table name prefixes, so `docker_*` drops
+    /// containers and images alike.
+    pub async fn invalidate_matching(&self, table: &str) -> usize {
+        let matches = |name: &str| match table.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == table,
+        };
+        let mut cache = self.cache.write().await;
+        let before = cache.len();
+        cache.retain(|_, cached| !cached.tables.iter().any(|t| matches(t)));
+        let dropped = before - cache.len();
+        debug!(table, dropped, "cache invalidated");
+        dropped
+    }
+
     /// Get cache statistics
     #[must_use]
     pub async fn cache_stats(&self) -> (usize, usize) {
@@ -255,10 +307,44 @@ fn extract_table_name(sql: &str) -> Option<String> {
     }
 }
 
+/// `osqueryi` command line running `sql`, passed as a single argument
+fn query_command(sql: &str) -> String {
+    ShellCommand::new("osqueryi").arg("--json").arg(sql).build()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_query_command_quotes_sql() {
+        assert_eq!(
+            query_command("SELECT * FROM os_version WHERE name = 'Ubuntu'"),
+            "osqueryi --json 'SELECT * FROM os_version WHERE name = '\\''Ubuntu'\\'''"
+        );
+        assert_eq!(
+            query_command("SELECT * FROM file WHERE path = '/tmp/a b;echo pwned'"),
+            "osqueryi --json 'SELECT * FROM file WHERE path = '\\''/tmp/a b;echo pwned'\\'''"
+        );
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            parse_version("osqueryi version 5.12.1\n").as_deref(),
+            Some("5.12.1")
+        );
+        assert_eq!(parse_version("5.2.3").as_deref(), Some("5.2.3"));
+        assert_eq!(parse_version("sh: osqueryi: not found"), None);
+        assert_eq!(parse_version(""), None);
+
+        assert!(version_supported("5.12.1"));
+        assert!(version_supported("5"));
+        assert!(version_supported("10.0.0"));
+        assert!(!version_supported("4.9.0"));
+        assert!(!version_supported("garbage"));
+    }
+
     #[test]
     fn test_extract_table_name() {
         assert_eq!(
@@ -1,6 +1,7 @@
 //! SQL query builder for osquery
 
 use std::fmt;
+use std::time::Duration;
 
 /// SQL query builder
 ///
@@ -11,12 +12,18 @@ pub struct Query {
     select: Vec<String>,
     /// FROM clause
     from: String,
+    /// JOIN clauses
+    joins: Vec<String>,
+    /// Tables joined in, for cache invalidation
+    joined_tables: Vec<String>,
     /// WHERE clauses
     where_clauses: Vec<String>,
     /// ORDER BY clause
     order_by: Option<String>,
     /// LIMIT clause
     limit: Option<usize>,
+    /// How long results may be cached, overriding the client's default
+    ttl: Option<Duration>,
 }
 
 impl Query {
@@ -25,9 +32,12 @@ impl Query {
         Self {
             select: vec!["*".to_string()],
             from: table.into(),
+            joins: Vec::new(),
+            joined_tables: Vec::new(),
             where_clauses: Vec::new(),
             order_by: None,
             limit: None,
+            ttl: None,
         }
     }
 
@@ -38,6 +48,45 @@ impl Query {
         self
     }
 
+    /// Select a column under an alias
+    ///
+    /// Useful for joined queries where both tables have a column with the same
+    /// name. Appends to the current selection, replacing the default `*`.
+    #[must_use]
+    pub fn select_as(mut self, column: &str, alias: &str) -> Self {
+        if self.select.len() == 1 && self.select[0] == "*" {
+            self.select.clear();
+        }
+        self.select
+            .push(format!("{} AS {}", quote_ident(column), quote_ident(alias)));
+        self
+    }
+
+    /// Add an inner JOIN on `on_left = on_right`
+    #[must_use]
+    pub fn join(self, table: &str, on_left: &str, on_right: &str) -> Self {
+        self.push_join("JOIN", table, on_left, on_right)
+    }
+
+    /// Add a LEFT JOIN on `on_left = on_right`
+    ///
+    /// Rows from the base table are kept even when nothing matches.
+    #[must_use]
+    pub fn left_join(self, table: &str, on_left: &str, on_right: &str) -> Self {
+        self.push_join("LEFT JOIN", table, on_left, on_right)
+    }
+
+    fn push_join(mut self, kind: &str, table: &str, on_left: &str, on_right: &str) -> Self {
+        self.joined_tables.push(table.to_string());
+        self.joins.push(format!(
+            "{kind} {} ON {} = {}",
+            quote_ident(table),
+            quote_ident(on_left),
+            quote_ident(on_right)
+        ));
+        self
+    }
+
     /// Add WHERE clause
     #[must_use]
     pub fn where_eq(mut self, column: &str, value: &str) -> Self {
@@ -83,11 +132,34 @@ impl Query {
         self
     }
 
+    /// Cache results for `ttl` instead of the client's default
+    #[must_use]
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The cache TTL set with [`ttl`](Self::ttl)
+    #[must_use]
+    pub fn cache_ttl(&self) -> Option<Duration> {
+        self.ttl
+    }
+
+    /// Tables the query reads, the `FROM` table first
+    pub fn tables(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.from.as_str()).chain(self.joined_tables.iter().map(String::as_str))
+    }
+
     /// Build the SQL string
     #[must_use]
     pub fn build(&self) -> String {
         let mut sql = format!("SELECT {} FROM {}", self.select.join(", "), self.from);
 
+        for join in &self.joins {
+            sql.push(' ');
+            sql.push_str(join);
+        }
+
         if !self.where_clauses.is_empty() {
             sql.push_str(" WHERE ");
             sql.push_str(&self.where_clauses.join(" AND "));
@@ -107,6 +179,29 @@ impl Query {
     }
 }
 
+/// Quote a (possibly `table.column` qualified) identifier if needed
+///
+/// Plain identifiers are left untouched so generated SQL stays readable;
+/// anything else is wrapped in double quotes with embedded quotes doubled.
+fn quote_ident(ident: &str) -> String {
+    ident
+        .split('.')
+        .map(|part| {
+            let plain = part
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if plain {
+                part.to_string()
+            } else {
+                format!("\"{}\"", part.replace('"', "\"\""))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
 impl fmt::Display for Query {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.build())
@@ -114,43 +209,70 @@ impl fmt::Display for Query {
 }
 
 /// Predefined queries for common inventory tasks
+///
+/// Each sets a cache TTL suited to how often its data changes. Packages
+/// and the kernel only change with updates, which invalidate them.
 pub mod queries {
+    use std::time::Duration;
+
     use super::Query;
 
+    /// Facts that only change with an update or reboot
+    const STATIC_TTL: Duration = Duration::from_secs(3600);
+
+    /// Installed packages, invalidated after updates
+    const PACKAGES_TTL: Duration = Duration::from_secs(900);
+
+    /// Containers, ports and services, which come and go
+    const RUNTIME_TTL: Duration = Duration::from_secs(60);
+
+    /// Counters that are stale almost at once
+    const VOLATILE_TTL: Duration = Duration::from_secs(5);
+
     /// Query for system information
     #[must_use]
     pub fn system_info() -> Query {
-        Query::new("system_info").select(&[
-            "hostname",
-            "cpu_brand",
-            "cpu_physical_cores",
-            "cpu_logical_cores",
-            "physical_memory",
-        ])
+        Query::new("system_info")
+            .select(&[
+                "hostname",
+                "cpu_brand",
+                "cpu_physical_cores",
+                "cpu_logical_cores",
+                "physical_memory",
+            ])
+            .ttl(STATIC_TTL)
     }
 
     /// Query for OS version
     #[must_use]
     pub fn os_version() -> Query {
-        Query::new("os_version").select(&["name", "version", "codename", "platform", "arch"])
+        Query::new("os_version")
+            .select(&["name", "version", "codename", "platform", "arch"])
+            .ttl(STATIC_TTL)
     }
 
     /// Query for uptime
     #[must_use]
     pub fn uptime() -> Query {
-        Query::new("uptime").select(&["days", "hours", "minutes", "seconds", "total_seconds"])
+        Query::new("uptime")
+            .select(&["days", "hours", "minutes", "seconds", "total_seconds"])
+            .ttl(VOLATILE_TTL)
     }
 
     /// Query for Debian packages
     #[must_use]
     pub fn deb_packages() -> Query {
-        Query::new("deb_packages").select(&["name", "version", "arch", "install_time"])
+        Query::new("deb_packages")
+            .select(&["name", "version", "arch", "install_time"])
+            .ttl(PACKAGES_TTL)
     }
 
     /// Query for RPM packages
     #[must_use]
     pub fn rpm_packages() -> Query {
-        Query::new("rpm_packages").select(&["name", "version", "arch", "install_time"])
+        Query::new("rpm_packages")
+            .select(&["name", "version", "arch", "install_time"])
+            .ttl(PACKAGES_TTL)
     }
 
     /// Query for Docker containers
@@ -158,6 +280,7 @@ pub mod queries {
     pub fn docker_containers() -> Query {
         Query::new("docker_containers")
             .select(&["id", "name", "image", "state", "status", "created"])
+            .ttl(RUNTIME_TTL)
     }
 
     /// Query for Docker images
@@ -178,18 +301,21 @@ pub mod queries {
                 "max_mhz as mhz",
             ])
             .limit(1)
+            .ttl(STATIC_TTL)
     }
 
     /// Query for memory info
     #[must_use]
     pub fn memory_info() -> Query {
-        Query::new("memory_info").select(&[
-            "memory_total as total",
-            "memory_free as free",
-            "(memory_total - memory_free) as used",
-            "swap_total",
-            "swap_free",
-        ])
+        Query::new("memory_info")
+            .select(&[
+                "memory_total as total",
+                "memory_free as free",
+                "(memory_total - memory_free) as used",
+                "swap_total",
+                "swap_free",
+            ])
+            .ttl(VOLATILE_TTL)
     }
 
     /// Query for disk info
@@ -201,38 +327,74 @@ pub mod queries {
     /// Query for mounts
     #[must_use]
     pub fn mounts() -> Query {
-        Query::new("mounts").select(&[
-            "device",
-            "path",
-            "type",
-            "blocks",
-            "blocks_free",
-            "blocks_size",
-        ])
+        Query::new("mounts")
+            .select(&[
+                "device",
+                "path",
+                "type",
+                "blocks",
+                "blocks_free",
+                "blocks_size",
+            ])
+            .ttl(VOLATILE_TTL)
+    }
+
+    /// Query for network interfaces joined with their addresses
+    ///
+    /// Returns one row per address; interfaces without an address appear once
+    /// with a null `address`.
+    #[must_use]
+    pub fn network_interfaces() -> Query {
+        Query::new("interface_details")
+            .select_as("interface_details.interface", "interface")
+            .select_as("interface_details.mac", "mac")
+            .select_as("interface_details.type", "type")
+            .select_as("interface_addresses.address", "address")
+            .select_as("interface_addresses.mask", "mask")
+            .left_join(
+                "interface_addresses",
+                "interface_details.interface",
+                "interface_addresses.interface",
+            )
     }
 
-    /// Query for network interfaces
+    /// Query for listening ports
     #[must_use]
-    pub fn interface_addresses() -> Query {
-        Query::new("interface_addresses").select(&["interface", "address", "mask"])
+    pub fn listening_ports() -> Query {
+        Query::new("listening_ports")
+            .select(&["pid", "port", "protocol", "family", "address"])
+            .ttl(RUNTIME_TTL)
     }
 
-    /// Query for interface details
+    /// Query for listening ports with the owning process name
     #[must_use]
-    pub fn interface_details() -> Query {
-        Query::new("interface_details").select(&["interface", "mac", "type"])
+    pub fn ports_with_processes() -> Query {
+        Query::new("listening_ports")
+            .select_as("listening_ports.pid", "pid")
+            .select_as("listening_ports.port", "port")
+            .select_as("listening_ports.protocol", "protocol")
+            .select_as("listening_ports.address", "address")
+            .select_as("processes.name", "process_name")
+            .left_join("processes", "listening_ports.pid", "processes.pid")
+            .ttl(RUNTIME_TTL)
     }
 
-    /// Query for listening ports
+    /// Query for systemd service units
     #[must_use]
-    pub fn listening_ports() -> Query {
-        Query::new("listening_ports").select(&["pid", "port", "protocol", "family"])
+    pub fn systemd_services() -> Query {
+        Query::new("systemd_units")
+            .select(&["id", "description", "active_state", "sub_state"])
+            .where_like("id", "%.service")
+            .order_by("id", true)
+            .ttl(RUNTIME_TTL)
     }
 
     /// Query for kernel info
     #[must_use]
     pub fn kernel_info() -> Query {
-        Query::new("kernel_info").select(&["version", "arguments"])
+        Query::new("kernel_info")
+            .select(&["version", "arguments"])
+            .ttl(STATIC_TTL)
     }
 }
 
@@ -286,6 +448,73 @@ mod tests {
         assert!(sql.contains("WHERE arch IN ('amd64', 'arm64')"));
     }
 
+    #[test]
+    fn test_join() {
+        let sql = Query::new("listening_ports")
+            .select(&["listening_ports.port", "processes.name"])
+            .join("processes", "listening_ports.pid", "processes.pid")
+            .where_eq("listening_ports.protocol", "6")
+            .build();
+
+        assert_eq!(
+            sql,
+            "SELECT listening_ports.port, processes.name FROM listening_ports \
+             JOIN processes ON listening_ports.pid = processes.pid \
+             WHERE listening_ports.protocol = '6'"
+        );
+    }
+
+    #[test]
+    fn test_left_join_with_aliases() {
+        let sql = Query::new("a")
+            .select_as("a.id", "a_id")
+            .select_as("b.id", "b_id")
+            .left_join("b", "a.id", "b.a_id")
+            .build();
+
+        assert_eq!(
+            sql,
+            "SELECT a.id AS a_id, b.id AS b_id FROM a LEFT JOIN b ON a.id = b.a_id"
+        );
+    }
+
+    #[test]
+    fn test_join_injection_prevention() {
+        let sql = Query::new("a")
+            .select_as("a.id", "x\" FROM users; --")
+            .join("b; DROP TABLE a", "a.id", "b.id\" OR 1=1")
+            .build();
+
+        assert!(sql.contains(r#"AS "x"" FROM users; --""#));
+        assert!(sql.contains(r#"JOIN "b; DROP TABLE a" ON"#));
+        assert!(sql.contains(r#"= b."id"" OR 1=1""#));
+    }
+
+    #[test]
+    fn test_ports_with_processes_query() {
+        let sql = queries::ports_with_processes().build();
+        assert!(sql.contains("processes.name AS process_name"));
+        assert!(sql.contains("LEFT JOIN processes ON listening_ports.pid = processes.pid"));
+    }
+
+    #[test]
+    fn test_systemd_services_query() {
+        let sql = queries::systemd_services().build();
+        assert!(sql.contains("FROM systemd_units"));
+        assert!(sql.contains("WHERE id LIKE '%.service'"));
+    }
+
+    #[test]
+    fn test_query_tables_and_ttl() {
+        let query = queries::ports_with_processes();
+        assert_eq!(
+            query.tables().collect::<Vec<_>>(),
+            ["listening_ports", "processes"]
+        );
+        assert!(queries::uptime().cache_ttl() < queries::deb_packages().cache_ttl());
+        assert_eq!(Query::new("a").cache_ttl(), None);
+    }
+
     #[test]
     fn test_order_by() {
         let query = Query::new("deb_packages").order_by("name", true);
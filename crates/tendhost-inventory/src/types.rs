@@ -107,6 +107,65 @@ pub struct NetworkInterface {
     pub ipv6: Vec<String>,
 }
 
+/// Socket listening for connections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListeningPort {
+    /// Owning process ID
+    pub pid: u32,
+    /// Port number
+    pub port: u16,
+    /// Protocol (tcp/udp)
+    pub protocol: String,
+    /// Bound address
+    pub address: String,
+    /// Owning process name (if it could be resolved)
+    pub process_name: Option<String>,
+}
+
+impl ListeningPort {
+    /// Map an osquery IP protocol number to its name
+    #[must_use]
+    pub fn protocol_name(protocol: &str) -> String {
+        match protocol {
+            "6" => "tcp".to_string(),
+            "17" => "udp".to_string(),
+            "132" => "sctp".to_string(),
+            other => other.to_string(),
+        }
+    }
+}
+
+// ============================================================================
+// Services
+// ============================================================================
+
+/// Systemd service unit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SystemdService {
+    /// Unit name (e.g. `nginx.service`)
+    pub name: String,
+    /// Unit description
+    pub description: String,
+    /// Active state (active, inactive, failed, ...)
+    pub active_state: String,
+    /// Sub state (running, exited, dead, ...)
+    pub sub_state: String,
+}
+
+impl SystemdService {
+    /// Check if the service is currently running
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.active_state == "active" && self.sub_state == "running"
+    }
+
+    /// Check if the service is in failed state
+    #[must_use]
+    pub fn is_failed(&self) -> bool {
+        self.active_state == "failed"
+    }
+}
+
 // ============================================================================
 // Packages
 // ============================================================================
@@ -235,6 +294,12 @@ pub struct HostInventory {
     pub docker_containers: Vec<Container>,
     /// Docker images (if applicable)
     pub docker_images: Vec<Image>,
+    /// Listening network ports
+    #[serde(default)]
+    pub listening_ports: Vec<ListeningPort>,
+    /// Systemd services
+    #[serde(default)]
+    pub services: Vec<SystemdService>,
     /// When inventory was collected
     pub collected_at: DateTime<Utc>,
     /// Inventory version/schema
@@ -279,6 +344,8 @@ impl HostInventory {
             packages: Vec::new(),
             docker_containers: Vec::new(),
             docker_images: Vec::new(),
+            listening_ports: Vec::new(),
+            services: Vec::new(),
             collected_at: Utc::now(),
             version: "1.0".to_string(),
         }
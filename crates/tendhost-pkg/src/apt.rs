@@ -1,21 +1,40 @@
 //! APT package manager (Debian/Ubuntu)
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use tendhost_exec::traits::RemoteExecutor;
-use tracing::{debug, info, instrument};
+use tendhost_exec::ShellCommand;
+use tendhost_exec::result::CommandResult;
+use tendhost_exec::traits::{RemoteExecutor, RemoteExecutorExt};
+use tracing::{debug, info, instrument, warn};
 
 use crate::error::PackageError;
+use crate::escalation::PrivilegeEscalation;
+use crate::kernel::{KernelSource, kernel_status};
+use crate::lists::ListsRefresh;
+use crate::locale;
+use crate::osquery;
 use crate::traits::PackageManager;
-use crate::types::{PackageManagerType, UpdateResult, UpgradablePackage};
+use crate::types::{
+    DEFAULT_METADATA_MAX_AGE, DistroInfo, OperationTimeouts, PackageManagerType, ParseConfidence,
+    RestartRequirement, UpdateResult, UpgradablePackage,
+};
 
 /// APT package manager implementation
 pub struct AptManager {
     /// Remote executor for running commands
     executor: Arc<dyn RemoteExecutor>,
-    /// Whether to use sudo
-    use_sudo: bool,
+    /// How commands are run as root
+    escalation: PrivilegeEscalation,
+    /// Command timeouts
+    timeouts: OperationTimeouts,
+    /// Detected distribution
+    distro: Option<DistroInfo>,
+    /// Last `apt update`
+    lists: ListsRefresh,
+    /// How long dpkg waits for a held lock before failing
+    lock_timeout: Duration,
 }
 
 impl AptManager {
@@ -23,18 +42,144 @@ impl AptManager {
     ///
     /// # Arguments
     /// * `executor` - Remote executor for running apt commands
-    /// * `use_sudo` - Whether to prefix commands with sudo
-    pub fn new(executor: Arc<dyn RemoteExecutor>, use_sudo: bool) -> Self {
-        Self { executor, use_sudo }
+    /// * `escalation` - How to run commands as root
+    pub fn new(executor: Arc<dyn RemoteExecutor>, escalation: PrivilegeEscalation) -> Self {
+        Self {
+            executor,
+            escalation,
+            timeouts: OperationTimeouts::default(),
+            distro: None,
+            lists: ListsRefresh::new(DEFAULT_METADATA_MAX_AGE),
+            lock_timeout: Duration::ZERO,
+        }
     }
 
-    /// Build apt command with optional sudo
-    fn apt_cmd(&self, args: &str) -> String {
-        if self.use_sudo {
-            format!("sudo apt {args}")
-        } else {
-            format!("apt {args}")
+    /// Set command timeouts
+    #[must_use]
+    pub fn with_timeouts(mut self, timeouts: OperationTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Record the distribution this manager was selected for
+    #[must_use]
+    pub fn with_distro(mut self, distro: DistroInfo) -> Self {
+        self.distro = Some(distro);
+        self
+    }
+
+    /// Reuse package lists refreshed within `max_age` (zero always refreshes)
+    #[must_use]
+    pub fn with_metadata_max_age(mut self, max_age: Duration) -> Self {
+        self.lists = ListsRefresh::new(max_age);
+        self
+    }
+
+    /// Let each upgrade wait up to `timeout` for a held dpkg lock (zero
+    /// fails at once)
+    ///
+    /// Uses `DPkg::Lock::Timeout`, which apt older than 1.9.11 ignores; see
+    /// [`retry_while_locked`](crate::lock::retry_while_locked) for those.
+    #[must_use]
+    pub fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = timeout;
+        self
+    }
+
+    /// Build apt command through the escalation command, under the C locale
+    fn apt_cmd(&self, args: &[&str]) -> String {
+        locale::command("apt", &self.escalation, &[], &self.executor.env_names())
+            .args(args)
+            .build()
+    }
+
+    /// Build an apt command that never waits for interactive input
+    ///
+    /// Configuration file prompts from dpkg are answered with the default
+    /// action, keeping the locally modified file when there is no default.
+    fn noninteractive_apt_cmd(&self, args: &[&str]) -> String {
+        let env = [("DEBIAN_FRONTEND", "noninteractive")];
+        let mut command =
+            locale::command("apt", &self.escalation, &env, &self.executor.env_names())
+                .args(["-o", "Dpkg::Options::=--force-confdef"])
+                .args(["-o", "Dpkg::Options::=--force-confold"]);
+        if !self.lock_timeout.is_zero() {
+            let timeout = format!("DPkg::Lock::Timeout={}", self.lock_timeout.as_secs());
+            command = command.args(["-o", timeout.as_str()]);
+        }
+        command.args(args).build()
+    }
+
+    /// Whether the host needs a reboot, and the packages that caused it
+    ///
+    /// Packages flag a reboot by creating `/var/run/reboot-required`, but a
+    /// kernel installed with plain `dpkg -i` doesn't, so without the flag
+    /// the running kernel is compared with the newest installed one.
+    async fn reboot_check(&self) -> Result<(bool, Vec<String>), PackageError> {
+        let flag = self
+            .run(
+                "test -f /var/run/reboot-required",
+                self.timeouts.query,
+                "reboot check",
+            )
+            .await?;
+        if flag.success() {
+            let pkgs = self
+                .run(
+                    "cat /var/run/reboot-required.pkgs",
+                    self.timeouts.query,
+                    "reboot check",
+                )
+                .await?;
+            let mut triggered_by: Vec<String> = Vec::new();
+            if pkgs.success() {
+                for package in pkgs.stdout.lines().map(str::trim) {
+                    if !package.is_empty() && !triggered_by.iter().any(|p| p == package) {
+                        triggered_by.push(package.to_string());
+                    }
+                }
+            }
+            return Ok((true, triggered_by));
         }
+
+        let kernel = kernel_status(
+            self.executor.as_ref(),
+            KernelSource::Dpkg,
+            self.timeouts.query,
+        )
+        .await?;
+        Ok(match kernel {
+            Some(kernel) if kernel.reboot_needed() => {
+                (true, vec![KernelSource::Dpkg.package_name(&kernel.newest)])
+            }
+            _ => (false, Vec::new()),
+        })
+    }
+
+    /// Run a command, failing with `PackageError::Timeout` after `timeout`
+    async fn run(
+        &self,
+        cmd: &str,
+        timeout: Duration,
+        operation: &str,
+    ) -> Result<CommandResult, PackageError> {
+        self.executor
+            .run_with_timeout(cmd, timeout)
+            .await
+            .map_err(|e| PackageError::from_exec(operation, e))
+    }
+
+    /// Run `cmd` as root through the escalation command
+    async fn run_privileged(
+        &self,
+        cmd: &ShellCommand,
+        timeout: Duration,
+        operation: &str,
+    ) -> Result<CommandResult, PackageError> {
+        self.executor
+            .run_privileged_with_timeout(cmd, &self.escalation, timeout)
+            .await
+            .map_err(|e| PackageError::from_exec(operation, e))
     }
 
     /// Parse apt list --upgradable output
@@ -47,18 +192,19 @@ impl AptManager {
                 continue;
             }
 
-            // Parse: package/arch version repository [upgradable from: oldversion]
-            // Example: vim/now 2:8.2.2434-3+deb11u1 amd64 [upgradable from: 2:8.2.2434-3]
+            // Parse: package/suites version arch [upgradable from: oldversion]
+            // Example: vim/bookworm-security 2:9.0.1378-2+deb12u1 amd64 [upgradable from: 2:9.0.1378-2]
             let parts: Vec<&str> = line.split_whitespace().collect();
             if parts.len() >= 2 {
-                let name_arch = parts[0];
+                let name_suites = parts[0];
                 let new_version = parts[1];
+                let arch = parts.get(2).filter(|a| !a.starts_with('['));
 
-                // Split name/arch
-                let (name, arch) = if let Some(idx) = name_arch.find('/') {
-                    (&name_arch[..idx], Some(&name_arch[idx + 1..]))
+                // Split name/suites (suites are comma separated, e.g. "jammy-updates,jammy-security")
+                let (name, suites) = if let Some(idx) = name_suites.find('/') {
+                    (&name_suites[..idx], Some(&name_suites[idx + 1..]))
                 } else {
-                    (name_arch, None)
+                    (name_suites, None)
                 };
 
                 // Extract old version from [...]
@@ -75,7 +221,12 @@ impl AptManager {
 
                 let mut pkg = UpgradablePackage::new(name, current_version, new_version);
                 if let Some(a) = arch {
-                    pkg = pkg.with_arch(a);
+                    pkg = pkg.with_arch(*a);
+                }
+                if let Some(suites) = suites {
+                    pkg = pkg
+                        .with_security(Self::is_security_suite(suites))
+                        .with_repository(suites);
                 }
                 packages.push(pkg);
             }
@@ -84,48 +235,280 @@ impl AptManager {
         packages
     }
 
-    /// Parse apt upgrade output for results
-    fn parse_upgrade_output(_stdout: &str, stderr: &str) -> UpdateResult {
-        let mut upgraded = 0u32;
-        let mut new_pkgs = 0u32;
-        let mut removed = 0u32;
-
-        // Look for summary line in stderr
-        for line in stderr.lines() {
-            if line.contains("upgraded,") {
-                // Parse: "X upgraded, Y newly installed, Z to remove"
-                let parts: Vec<&str> = line.split(',').collect();
-                for part in parts {
-                    let part = part.trim();
-                    if let Some(n) = part.find(" upgraded")
-                        && let Ok(num) = part[..n].trim().parse::<u32>()
-                    {
-                        upgraded = num;
-                    }
-                    if let Some(n) = part.find(" newly installed")
-                        && let Ok(num) = part[..n].trim().parse::<u32>()
-                    {
-                        new_pkgs = num;
-                    }
-                    if let Some(n) = part.find(" to remove")
-                        && let Ok(num) = part[..n].trim().parse::<u32>()
-                    {
-                        removed = num;
-                    }
-                }
-            }
+    /// Whether any of the comma separated suites is a security pocket
+    ///
+    /// Matches the origins `unattended-upgrades` uses by default:
+    /// `<codename>-security` on Debian and Ubuntu.
+    fn is_security_suite(suites: &str) -> bool {
+        suites.split(',').any(|suite| suite.ends_with("-security"))
+    }
+
+    /// Build an `apt install --only-upgrade` command for the security updates in `packages`
+    ///
+    /// Returns `None` if there is nothing to upgrade.
+    fn security_upgrade_cmd(&self, packages: &[UpgradablePackage], flag: &str) -> Option<String> {
+        let names: Vec<&str> = packages
+            .iter()
+            .filter(|p| p.security && is_valid_package_name(&p.name))
+            .map(|p| p.name.as_str())
+            .collect();
+
+        if names.is_empty() {
+            return None;
+        }
+
+        Some(self.only_upgrade_cmd(&names, flag))
+    }
+
+    /// Build an `apt install --only-upgrade` command for the named packages
+    ///
+    /// Names that are not valid Debian package names are refused rather
+    /// than passed to the shell.
+    fn selected_upgrade_cmd(
+        &self,
+        packages: &[String],
+        flag: &str,
+    ) -> Result<String, PackageError> {
+        if let Some(name) = packages.iter().find(|name| !is_valid_package_name(name)) {
+            return Err(PackageError::PackageNotFound(name.clone()));
+        }
+        let names: Vec<&str> = packages.iter().map(String::as_str).collect();
+        Ok(self.only_upgrade_cmd(&names, flag))
+    }
+
+    /// `--only-upgrade` keeps apt from installing packages that are missing
+    fn only_upgrade_cmd(&self, names: &[&str], flag: &str) -> String {
+        let mut args = vec!["install", "--only-upgrade", flag];
+        args.extend(names);
+        self.noninteractive_apt_cmd(&args)
+    }
+
+    /// Run an upgrade command and collect its result
+    ///
+    /// `operation` names the upgrade in errors and logs.
+    async fn apply_upgrade(
+        &self,
+        cmd: &str,
+        operation: &str,
+    ) -> Result<UpdateResult, PackageError> {
+        let result = self.run(cmd, self.timeouts.upgrade, operation).await?;
+
+        if !result.success() {
+            return Err(Self::change_failed(result));
         }
 
+        let mut update_result = Self::parse_upgrade_output(&result.stdout, &result.stderr);
+
+        // Check if reboot is required
+        update_result =
+            update_result.with_restart(self.restart_requirement().await.unwrap_or_default());
+
+        info!(
+            upgraded = update_result.upgraded_count,
+            reboot_required = update_result.reboot_required,
+            services_needing_restart = update_result.restart.services_needing_restart.len(),
+            "apt {operation} completed"
+        );
+
+        Ok(update_result)
+    }
+
+    /// Error for a failed command that changes packages
+    fn change_failed(result: CommandResult) -> PackageError {
+        // Check for lock conflict
+        if result.stderr.contains("Could not get lock") {
+            return PackageError::LockConflict(result.stderr);
+        }
+        // Check for permission denied, including `sudo -n` refusing to prompt
+        if result.stderr.contains("Permission denied")
+            || result.stderr.contains("a password is required")
+        {
+            return PackageError::PermissionDenied(result.stderr);
+        }
+        PackageError::command_failed(&result)
+    }
+
+    /// Packages named in the `Removing` lines of `apt autoremove`
+    fn parse_autoremove(stdout: &str) -> Vec<String> {
+        Progress::parse(stdout.lines()).removed
+    }
+
+    /// Parse apt upgrade output for results
+    ///
+    /// Counts come from the "X upgraded, Y newly installed, Z to remove"
+    /// summary. Without one they are estimated from the per-package
+    /// `Unpacking`, `Setting up` and `Removing` lines, or from `Inst` and
+    /// `Remv` in simulated runs, and the result says so. The download size
+    /// comes from the "Need to get" line, when apt prints one.
+    fn parse_upgrade_output(stdout: &str, stderr: &str) -> UpdateResult {
+        let lines = || stdout.lines().chain(stderr.lines());
+        let progress = Progress::parse(lines());
+
+        let (counts, confidence) = if let Some(counts) = lines().find_map(Self::parse_summary) {
+            (counts, ParseConfidence::Summary)
+        } else if let Some(counts) = progress.counts() {
+            warn!("apt printed no summary, counting per-package lines");
+            (counts, ParseConfidence::Estimated)
+        } else {
+            warn!("apt output not recognised, reporting no changes");
+            ((0, 0, 0), ParseConfidence::Unknown)
+        };
+
         UpdateResult {
             success: true,
-            upgraded_count: upgraded,
-            new_count: new_pkgs,
-            removed_count: removed,
+            upgraded_count: counts.0,
+            new_count: counts.1,
+            removed_count: counts.2,
             reboot_required: false, // Will check separately
-            upgraded_packages: Vec::new(),
+            restart: RestartRequirement::default(),
+            upgraded_packages: progress.upgraded,
             error: None,
+            reclaimed_bytes: None,
+            download_bytes: lines().find_map(Self::parse_download_size),
+            autoremoved_packages: Vec::new(),
+            parse_confidence: confidence,
         }
     }
+
+    /// Parse "Need to get 1,024 kB of archives." into bytes
+    ///
+    /// With some archives already cached apt prints "Need to get 3,072 kB/5.1
+    /// MB of archives."; the first size is what is left to download.
+    fn parse_download_size(line: &str) -> Option<u64> {
+        let sizes = line
+            .trim()
+            .strip_prefix("Need to get ")?
+            .split(" of ")
+            .next()?;
+        let (number, unit) = sizes.split('/').next()?.trim().split_once(' ')?;
+        let number: f64 = number.replace(',', "").parse().ok()?;
+        // apt prints decimal units
+        let multiplier: f64 = match unit {
+            "B" => 1.0,
+            "kB" => 1e3,
+            "MB" => 1e6,
+            "GB" => 1e9,
+            _ => return None,
+        };
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Some((number * multiplier).round() as u64)
+    }
+
+    /// Parse "X upgraded, Y newly installed, Z to remove and W not upgraded."
+    ///
+    /// Returns the upgraded, new and removed counts.
+    fn parse_summary(line: &str) -> Option<(u32, u32, u32)> {
+        let count = |part: &str, label: &str| {
+            part.strip_suffix(label)
+                .and_then(|n| n.trim().parse::<u32>().ok())
+        };
+
+        let mut upgraded = None;
+        let mut new_pkgs = 0;
+        let mut removed = 0;
+        for part in line.split([',', '.']).flat_map(|part| part.split(" and ")) {
+            let part = part.trim();
+            if let Some(n) = count(part, " upgraded") {
+                upgraded = Some(n);
+            } else if let Some(n) = count(part, " newly installed") {
+                new_pkgs = n;
+            } else if let Some(n) = count(part, " to remove") {
+                removed = n;
+            }
+        }
+        upgraded.map(|upgraded| (upgraded, new_pkgs, removed))
+    }
+
+    /// Parse `needrestart -b` batch output
+    ///
+    /// Returns whether the running kernel is outdated and the services
+    /// that need a restart.
+    fn parse_needrestart(output: &str) -> (bool, Vec<String>) {
+        let mut kernel_outdated = false;
+        let mut services = Vec::new();
+
+        for line in output.lines() {
+            if let Some(status) = line.strip_prefix("NEEDRESTART-KSTA:") {
+                // 0 unknown, 1 current, 2 ABI-compatible upgrade, 3 version upgrade
+                kernel_outdated = status.trim().parse::<u8>().is_ok_and(|s| s >= 2);
+            } else if let Some(service) = line.strip_prefix("NEEDRESTART-SVC:") {
+                let service = service.trim();
+                if !service.is_empty() {
+                    services.push(service.to_string());
+                }
+            }
+        }
+
+        (kernel_outdated, services)
+    }
+}
+
+/// Debian package names: lowercase alphanumerics plus `+`, `-` and `.`
+/// Packages named in apt's per-package progress lines
+#[derive(Debug, Default)]
+struct Progress {
+    upgraded: Vec<String>,
+    new: Vec<String>,
+    removed: Vec<String>,
+    set_up: Vec<String>,
+}
+
+impl Progress {
+    fn parse<'a>(lines: impl Iterator<Item = &'a str>) -> Self {
+        let mut progress = Self::default();
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let (Some(verb), Some(name)) = (words.next(), words.next()) else {
+                continue;
+            };
+            let rest = words.collect::<Vec<_>>();
+            let list = match verb {
+                // "Unpacking curl (7.88.1-10+deb12u5) over (7.88.1-10+deb12u4) ..."
+                "Unpacking" if rest.contains(&"over") => &mut progress.upgraded,
+                "Unpacking" => &mut progress.new,
+                // "Inst curl [7.88.1-10+deb12u4] (7.88.1-10+deb12u5 Debian:12.5/stable [amd64])"
+                "Inst" if rest.first().is_some_and(|w| w.starts_with('[')) => {
+                    &mut progress.upgraded
+                }
+                "Inst" => &mut progress.new,
+                "Removing" | "Remv" => &mut progress.removed,
+                "Setting" if name == "up" => {
+                    let Some(name) = rest.first() else { continue };
+                    push_unique(&mut progress.set_up, name);
+                    continue;
+                }
+                _ => continue,
+            };
+            push_unique(list, name);
+        }
+        // Packages only configured, with no unpack line, count as upgraded
+        if progress.upgraded.is_empty() && progress.new.is_empty() {
+            progress.upgraded = std::mem::take(&mut progress.set_up);
+        }
+        progress
+    }
+
+    /// Upgraded, new and removed counts, if any package line was seen
+    fn counts(&self) -> Option<(u32, u32, u32)> {
+        if self.upgraded.is_empty() && self.new.is_empty() && self.removed.is_empty() {
+            return None;
+        }
+        let len = |list: &Vec<String>| u32::try_from(list.len()).unwrap_or(u32::MAX);
+        Some((len(&self.upgraded), len(&self.new), len(&self.removed)))
+    }
+}
+
+fn push_unique(list: &mut Vec<String>, name: &str) {
+    if !list.iter().any(|n| n == name) {
+        list.push(name.to_string());
+    }
+}
+
+fn is_valid_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '+' | '-' | '.'))
 }
 
 #[async_trait]
@@ -134,33 +517,21 @@ impl PackageManager for AptManager {
     async fn list_upgradable(&self) -> Result<Vec<UpgradablePackage>, PackageError> {
         debug!("listing upgradable packages");
 
-        // First update package lists
-        let update_cmd = self.apt_cmd("update -qq");
-        let update_result = self
-            .executor
-            .run(&update_cmd)
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
-
-        if !update_result.success() {
-            return Err(PackageError::RepositoryUnavailable(
-                update_result.stderr.clone(),
-            ));
+        if self.lists.is_fresh() {
+            debug!(
+                max_age_secs = self.lists.max_age().as_secs(),
+                "reusing package lists"
+            );
+        } else {
+            self.update_package_lists().await?;
         }
 
         // List upgradable packages
-        let cmd = self.apt_cmd("list --upgradable");
-        let result = self
-            .executor
-            .run(&cmd)
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+        let cmd = self.apt_cmd(&["list", "--upgradable"]);
+        let result = self.run(&cmd, self.timeouts.query, "query").await?;
 
         if !result.success() {
-            return Err(PackageError::CommandFailed {
-                status: result.status,
-                message: result.stderr,
-            });
+            return Err(PackageError::command_failed(&result));
         }
 
         let packages = Self::parse_upgradable(&result.stdout);
@@ -169,80 +540,201 @@ impl PackageManager for AptManager {
         Ok(packages)
     }
 
+    #[instrument(skip(self))]
+    async fn update_package_lists(&self) -> Result<(), PackageError> {
+        let cmd = self.apt_cmd(&["update", "-qq"]);
+        let result = self
+            .run(&cmd, self.timeouts.update_lists, "update lists")
+            .await?;
+        if !result.success() {
+            return Err(PackageError::RepositoryUnavailable(result.stderr));
+        }
+        self.lists.refreshed();
+        debug!("package lists updated");
+        Ok(())
+    }
+
     #[instrument(skip(self))]
     async fn upgrade_all(&self) -> Result<UpdateResult, PackageError> {
         info!("starting apt upgrade");
 
-        let cmd = self.apt_cmd("upgrade -y");
-        let result = self
-            .executor
-            .run(&cmd)
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+        let cmd = self.noninteractive_apt_cmd(&["upgrade", "-y"]);
+        self.apply_upgrade(&cmd, "upgrade").await
+    }
 
-        if !result.success() {
-            // Check for lock conflict
-            if result.stderr.contains("Could not get lock") {
-                return Err(PackageError::LockConflict(result.stderr));
-            }
-            // Check for permission denied
-            if result.stderr.contains("Permission denied") {
-                return Err(PackageError::PermissionDenied(result.stderr));
-            }
+    #[instrument(skip(self))]
+    async fn upgrade_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        debug!("starting apt dry run");
 
-            return Err(PackageError::CommandFailed {
-                status: result.status,
-                message: result.stderr.clone(),
-            });
+        let cmd = self.apt_cmd(&["upgrade", "--simulate"]);
+        let result = self.run(&cmd, self.timeouts.query, "dry run").await?;
+
+        if !result.success() {
+            return Err(PackageError::command_failed(&result));
         }
 
-        let mut update_result = Self::parse_upgrade_output(&result.stdout, &result.stderr);
+        let update_result = Self::parse_upgrade_output(&result.stdout, &result.stderr);
 
-        // Check if reboot is required
-        update_result.reboot_required = self.reboot_required().await.unwrap_or(false);
+        Ok(update_result)
+    }
+
+    #[instrument(skip(self))]
+    async fn upgrade_security(&self) -> Result<UpdateResult, PackageError> {
+        info!("starting apt security upgrade");
+
+        let packages = self.list_upgradable().await?;
+        let Some(cmd) = self.security_upgrade_cmd(&packages, "-y") else {
+            info!("no security updates available");
+            return Ok(UpdateResult::success(0));
+        };
+
+        self.apply_upgrade(&cmd, "security upgrade").await
+    }
+
+    #[instrument(skip(self))]
+    async fn upgrade_security_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        debug!("starting apt security dry run");
+
+        let packages = self.list_upgradable().await?;
+        let Some(cmd) = self.security_upgrade_cmd(&packages, "--simulate") else {
+            return Ok(UpdateResult::success(0));
+        };
 
+        let result = self.run(&cmd, self.timeouts.query, "dry run").await?;
+
+        if !result.success() {
+            return Err(PackageError::command_failed(&result));
+        }
+
+        Ok(Self::parse_upgrade_output(&result.stdout, &result.stderr))
+    }
+
+    #[instrument(skip(self))]
+    async fn upgrade_packages(&self, packages: &[String]) -> Result<UpdateResult, PackageError> {
         info!(
-            upgraded = update_result.upgraded_count,
-            reboot_required = update_result.reboot_required,
-            "apt upgrade completed"
+            count = packages.len(),
+            "starting apt upgrade of selected packages"
         );
 
-        Ok(update_result)
+        let cmd = self.selected_upgrade_cmd(packages, "-y")?;
+        self.apply_upgrade(&cmd, "selected upgrade").await
     }
 
     #[instrument(skip(self))]
-    async fn upgrade_dry_run(&self) -> Result<UpdateResult, PackageError> {
-        debug!("starting apt dry run");
+    async fn upgrade_packages_dry_run(
+        &self,
+        packages: &[String],
+    ) -> Result<UpdateResult, PackageError> {
+        debug!(
+            count = packages.len(),
+            "starting apt dry run of selected packages"
+        );
 
-        let cmd = self.apt_cmd("upgrade --simulate");
-        let result = self
-            .executor
-            .run(&cmd)
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+        let cmd = self.selected_upgrade_cmd(packages, "--simulate")?;
+        let result = self.run(&cmd, self.timeouts.query, "dry run").await?;
 
         if !result.success() {
-            return Err(PackageError::CommandFailed {
-                status: result.status,
-                message: result.stderr,
-            });
+            return Err(PackageError::command_failed(&result));
         }
 
-        let update_result = Self::parse_upgrade_output(&result.stdout, &result.stderr);
+        Ok(Self::parse_upgrade_output(&result.stdout, &result.stderr))
+    }
 
-        Ok(update_result)
+    #[instrument(skip(self))]
+    async fn autoremove(&self) -> Result<Vec<String>, PackageError> {
+        let cmd = self.noninteractive_apt_cmd(&["autoremove", "-y"]);
+        let result = self.run(&cmd, self.timeouts.upgrade, "autoremove").await?;
+        if !result.success() {
+            return Err(Self::change_failed(result));
+        }
+
+        let removed = Self::parse_autoremove(&result.stdout);
+        info!(removed = removed.len(), "apt autoremove completed");
+        Ok(removed)
+    }
+
+    #[instrument(skip(self))]
+    async fn clean_cache(&self) -> Result<(), PackageError> {
+        let cmd = self.apt_cmd(&["clean"]);
+        let result = self.run(&cmd, self.timeouts.upgrade, "clean").await?;
+        if !result.success() {
+            return Err(Self::change_failed(result));
+        }
+        debug!("apt cache cleaned");
+        Ok(())
     }
 
     #[instrument(skip(self))]
     async fn reboot_required(&self) -> Result<bool, PackageError> {
-        // Check for /var/run/reboot-required (Debian/Ubuntu standard)
+        Ok(self.reboot_check().await?.0)
+    }
+
+    #[instrument(skip(self))]
+    async fn restart_requirement(&self) -> Result<RestartRequirement, PackageError> {
+        let (reboot_needed, triggered_by) = self.reboot_check().await?;
+        let mut restart = RestartRequirement {
+            reboot_needed,
+            triggered_by,
+            ..RestartRequirement::default()
+        };
+
+        // needrestart is optional; without it only the reboot flag is known
+        let needrestart = ShellCommand::new("needrestart").arg("-b");
         let result = self
-            .executor
-            .run("test -f /var/run/reboot-required")
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+            .run_privileged(&needrestart, self.timeouts.query, "restart check")
+            .await?;
+        if result.success() {
+            let (kernel_outdated, services) = Self::parse_needrestart(&result.stdout);
+            restart.reboot_needed |= kernel_outdated;
+            restart.services_needing_restart = services;
+        } else {
+            debug!(status = result.status, "needrestart unavailable");
+        }
+
+        Ok(restart)
+    }
+
+    #[instrument(skip(self))]
+    async fn cancel_upgrade(&self) -> Result<(), PackageError> {
+        warn!("terminating running apt processes");
+
+        // apt forwards SIGTERM to dpkg and leaves the database consistent
+        let pkill = |program| {
+            self.escalation
+                .wrap(&ShellCommand::new("pkill").args(["-TERM", "-x", program]))
+        };
+        let cmd = format!("{}; {}", pkill("apt"), pkill("apt-get"));
+        let result = self.run(&cmd, self.timeouts.query, "cancel").await?;
+
+        // pkill exits with 1 when nothing matched
+        if result.status > 1 {
+            return Err(PackageError::command_failed(&result));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn install_osquery(&self) -> Result<String, PackageError> {
+        info!("installing osquery");
+
+        let cmd = ShellCommand::script(osquery::APT_INSTALL);
+        let result = self
+            .run_privileged(&cmd, self.timeouts.upgrade, "osquery install")
+            .await?;
+        if !result.success() {
+            return Err(PackageError::command_failed(&result));
+        }
 
-        Ok(result.success())
+        Ok(result.combined_output())
+    }
+
+    fn distro(&self) -> Option<&DistroInfo> {
+        self.distro.as_ref()
+    }
+
+    fn escalation(&self) -> PrivilegeEscalation {
+        self.escalation.clone()
     }
 
     fn manager_type(&self) -> PackageManagerType {
@@ -261,6 +753,7 @@ impl PackageManager for AptManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{ScriptedExecutor, output};
 
     #[test]
     fn test_parse_upgradable() {
@@ -276,6 +769,170 @@ curl/stable 7.74.0-1.3+deb11u14 amd64 [upgradable from: 7.74.0-1.3+deb11u7]";
         assert_eq!(packages[0].current_version, "2:8.2.2434-3");
     }
 
+    #[test]
+    fn test_parse_upgradable_security_suites() {
+        let output = r"Listing... Done
+openssl/bookworm-security 3.0.11-1~deb12u2 amd64 [upgradable from: 3.0.11-1~deb12u1]
+tzdata/bookworm-updates 2024a-0+deb12u1 all [upgradable from: 2023c-5]
+libc6/jammy-updates,jammy-security 2.35-0ubuntu3.6 amd64 [upgradable from: 2.35-0ubuntu3.5]";
+
+        let packages = AptManager::parse_upgradable(output);
+
+        assert_eq!(packages.len(), 3);
+        assert!(packages[0].security);
+        assert_eq!(packages[0].arch.as_deref(), Some("amd64"));
+        assert_eq!(packages[0].repository.as_deref(), Some("bookworm-security"));
+        assert!(!packages[1].security);
+        assert!(packages[2].security);
+    }
+
+    #[test]
+    fn test_security_upgrade_cmd() {
+        let manager = AptManager::new(
+            Arc::new(tendhost_exec::LocalExecutor::new()),
+            PrivilegeEscalation::Sudo,
+        );
+        let packages = vec![
+            UpgradablePackage::new("openssl", "3.0.11-1", "3.0.11-2").with_security(true),
+            UpgradablePackage::new("tzdata", "2023c-5", "2024a-0"),
+            UpgradablePackage::new("evil;rm -rf /", "1", "2").with_security(true),
+        ];
+
+        assert_eq!(
+            manager.security_upgrade_cmd(&packages, "-y").as_deref(),
+            Some(
+                "sudo -n env LC_ALL=C LANG=C DEBIAN_FRONTEND=noninteractive apt \
+                 -o Dpkg::Options::=--force-confdef -o Dpkg::Options::=--force-confold \
+                 install --only-upgrade -y openssl"
+            )
+        );
+        assert!(
+            manager
+                .security_upgrade_cmd(&packages[1..2], "-y")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_selected_upgrade_cmd() {
+        let manager = AptManager::new(
+            Arc::new(tendhost_exec::LocalExecutor::new()),
+            PrivilegeEscalation::None,
+        );
+        let packages = vec!["openssl".to_string(), "libc6".to_string()];
+
+        assert_eq!(
+            manager
+                .selected_upgrade_cmd(&packages, "--simulate")
+                .unwrap(),
+            "LC_ALL=C LANG=C DEBIAN_FRONTEND=noninteractive apt \
+             -o Dpkg::Options::=--force-confdef -o Dpkg::Options::=--force-confold \
+             install --only-upgrade --simulate openssl libc6"
+        );
+        assert!(matches!(
+            manager.selected_upgrade_cmd(&["curl;reboot".to_string()], "-y"),
+            Err(PackageError::PackageNotFound(name)) if name == "curl;reboot"
+        ));
+    }
+
+    #[test]
+    fn test_noninteractive_apt_cmd() {
+        let executor = Arc::new(tendhost_exec::LocalExecutor::new());
+
+        let manager = AptManager::new(executor.clone(), PrivilegeEscalation::Sudo);
+        assert_eq!(
+            manager.noninteractive_apt_cmd(&["upgrade", "-y"]),
+            "sudo -n env LC_ALL=C LANG=C DEBIAN_FRONTEND=noninteractive apt \
+             -o Dpkg::Options::=--force-confdef -o Dpkg::Options::=--force-confold upgrade -y"
+        );
+
+        let manager = AptManager::new(executor, PrivilegeEscalation::None);
+        assert_eq!(
+            manager.noninteractive_apt_cmd(&["upgrade", "-y"]),
+            "LC_ALL=C LANG=C DEBIAN_FRONTEND=noninteractive apt \
+             -o Dpkg::Options::=--force-confdef -o Dpkg::Options::=--force-confold upgrade -y"
+        );
+        let waiting = AptManager::new(
+            Arc::new(tendhost_exec::LocalExecutor::new()),
+            PrivilegeEscalation::None,
+        )
+        .with_lock_timeout(Duration::from_secs(30));
+        assert!(
+            waiting
+                .noninteractive_apt_cmd(&["upgrade", "-y"])
+                .ends_with("-o DPkg::Lock::Timeout=30 upgrade -y")
+        );
+        // Read-only commands only get the locale
+        assert_eq!(
+            manager.apt_cmd(&["list", "--upgradable"]),
+            "LC_ALL=C LANG=C apt list --upgradable"
+        );
+    }
+
+    #[test]
+    fn test_apt_cmd_forces_c_locale() {
+        let manager = AptManager::new(
+            Arc::new(tendhost_exec::LocalExecutor::new()),
+            PrivilegeEscalation::Sudo,
+        );
+        assert_eq!(
+            manager.apt_cmd(&["update"]),
+            "sudo -n env LC_ALL=C LANG=C apt update"
+        );
+    }
+
+    #[test]
+    fn test_apt_cmd_with_doas_and_custom_prefix() {
+        let executor = Arc::new(tendhost_exec::LocalExecutor::new());
+        let manager = AptManager::new(executor.clone(), PrivilegeEscalation::Doas);
+        assert_eq!(
+            manager.noninteractive_apt_cmd(&["upgrade", "-y"]),
+            "doas -n env LC_ALL=C LANG=C DEBIAN_FRONTEND=noninteractive apt \
+             -o Dpkg::Options::=--force-confdef -o Dpkg::Options::=--force-confold upgrade -y"
+        );
+
+        let manager = AptManager::new(executor, PrivilegeEscalation::Custom("pfexec".to_string()));
+        assert_eq!(
+            manager.apt_cmd(&["update"]),
+            "pfexec env LC_ALL=C LANG=C apt update"
+        );
+        assert_eq!(manager.escalation().tool(), "pfexec");
+    }
+
+    #[test]
+    fn test_with_timeouts() {
+        let timeouts = OperationTimeouts {
+            upgrade: Duration::from_secs(60),
+            ..OperationTimeouts::default()
+        };
+        let manager = AptManager::new(
+            Arc::new(tendhost_exec::LocalExecutor::new()),
+            PrivilegeEscalation::None,
+        )
+        .with_timeouts(timeouts);
+
+        assert_eq!(manager.timeouts, timeouts);
+    }
+
+    #[test]
+    fn test_parse_needrestart() {
+        let output = "NEEDRESTART-VER: 3.6
+NEEDRESTART-KCUR: 6.1.0-17-amd64
+NEEDRESTART-KEXP: 6.1.0-18-amd64
+NEEDRESTART-KSTA: 3
+NEEDRESTART-SVC: ssh.service
+NEEDRESTART-SVC: cron.service
+";
+        let (kernel_outdated, services) = AptManager::parse_needrestart(output);
+        assert!(kernel_outdated);
+        assert_eq!(services, ["ssh.service", "cron.service"]);
+
+        let (kernel_outdated, services) =
+            AptManager::parse_needrestart("NEEDRESTART-VER: 3.6\nNEEDRESTART-KSTA: 1\n");
+        assert!(!kernel_outdated);
+        assert!(services.is_empty());
+    }
+
     #[test]
     fn test_parse_upgrade_output() {
         let stderr = "5 upgraded, 2 newly installed, 1 to remove and 0 not upgraded";
@@ -285,5 +942,312 @@ curl/stable 7.74.0-1.3+deb11u14 amd64 [upgradable from: 7.74.0-1.3+deb11u7]";
         assert_eq!(result.upgraded_count, 5);
         assert_eq!(result.new_count, 2);
         assert_eq!(result.removed_count, 1);
+        assert_eq!(result.parse_confidence, ParseConfidence::Summary);
+    }
+
+    const APT_UPGRADE: &str = "Reading package lists...
+The following packages will be upgraded:
+  curl libcurl4
+2 upgraded, 1 newly installed, 0 to remove and 3 not upgraded.
+Need to get 1,024 kB of archives.
+Selecting previously unselected package linux-image-6.1.0-21-amd64.
+Preparing to unpack .../curl_7.88.1-10+deb12u5_amd64.deb ...
+Unpacking curl (7.88.1-10+deb12u5) over (7.88.1-10+deb12u4) ...
+Unpacking libcurl4:amd64 (7.88.1-10+deb12u5) over (7.88.1-10+deb12u4) ...
+Unpacking linux-image-6.1.0-21-amd64 (6.1.90-1) ...
+Setting up libcurl4:amd64 (7.88.1-10+deb12u5) ...
+Setting up curl (7.88.1-10+deb12u5) ...
+Setting up linux-image-6.1.0-21-amd64 (6.1.90-1) ...
+";
+
+    #[test]
+    fn test_parse_upgrade_output_from_stdout() {
+        let result = AptManager::parse_upgrade_output(APT_UPGRADE, "");
+
+        assert_eq!(result.upgraded_count, 2);
+        assert_eq!(result.new_count, 1);
+        assert_eq!(result.parse_confidence, ParseConfidence::Summary);
+        assert_eq!(result.upgraded_packages, ["curl", "libcurl4:amd64"]);
+        assert_eq!(result.download_bytes, Some(1_024_000));
+    }
+
+    #[test]
+    fn test_parse_download_size() {
+        let parse = AptManager::parse_download_size;
+        assert_eq!(parse("Need to get 0 B of archives."), Some(0));
+        assert_eq!(parse("Need to get 58.3 MB of archives."), Some(58_300_000));
+        assert_eq!(
+            parse("Need to get 3,072 kB/5,120 kB of archives."),
+            Some(3_072_000)
+        );
+        assert_eq!(
+            parse("After this operation, 12.3 MB of additional disk space"),
+            None
+        );
+        assert_eq!(parse("Need to get lots of archives."), None);
+    }
+
+    #[test]
+    fn test_parse_upgrade_output_without_summary_counts_packages() {
+        let stdout: String = APT_UPGRADE
+            .lines()
+            .filter(|line| !line.contains("upgraded,"))
+            .map(|line| format!("{line}\n"))
+            .collect();
+
+        let result = AptManager::parse_upgrade_output(&stdout, "");
+
+        assert_eq!(result.upgraded_count, 2);
+        assert_eq!(result.new_count, 1);
+        assert_eq!(result.removed_count, 0);
+        assert_eq!(result.parse_confidence, ParseConfidence::Estimated);
+
+        // Only configuration lines, e.g. after an interrupted run
+        let result = AptManager::parse_upgrade_output(
+            "Setting up curl (7.88.1-10+deb12u5) ...\nSetting up curl (7.88.1-10+deb12u5) ...\n",
+            "",
+        );
+        assert_eq!(result.upgraded_count, 1);
+        assert_eq!(result.upgraded_packages, ["curl"]);
+    }
+
+    #[test]
+    fn test_parse_simulated_upgrade_without_summary() {
+        let stdout = "Inst curl [7.88.1-10+deb12u4] (7.88.1-10+deb12u5 Debian:12.5/stable [amd64])
+Inst linux-image-6.1.0-21-amd64 (6.1.90-1 Debian-Security:12/stable-security [amd64])
+Remv linux-image-6.1.0-17-amd64 [6.1.69-1]
+Conf curl (7.88.1-10+deb12u5 Debian:12.5/stable [amd64])
+";
+        let result = AptManager::parse_upgrade_output(stdout, "");
+
+        assert_eq!(result.upgraded_count, 1);
+        assert_eq!(result.new_count, 1);
+        assert_eq!(result.removed_count, 1);
+        assert_eq!(result.parse_confidence, ParseConfidence::Estimated);
+    }
+
+    // Captured from a host whose login shell sets LANG=de_DE.UTF-8
+    const APT_UPGRADE_DE: &str = "Paketlisten werden gelesen…
+Die folgenden Pakete werden aktualisiert (Upgrade):
+  curl libcurl4
+2 aktualisiert, 0 neu installiert, 0 zu entfernen und 0 nicht aktualisiert.
+Es müssen 705 kB an Archiven heruntergeladen werden.
+Vorbereitung zum Entpacken von .../curl_7.88.1-10+deb12u5_amd64.deb …
+Entpacken von curl (7.88.1-10+deb12u5) über (7.88.1-10+deb12u4) …
+Entpacken von libcurl4:amd64 (7.88.1-10+deb12u5) über (7.88.1-10+deb12u4) …
+libcurl4:amd64 (7.88.1-10+deb12u5) wird eingerichtet …
+curl (7.88.1-10+deb12u5) wird eingerichtet …
+";
+
+    #[test]
+    fn test_parse_localized_upgrade_output_is_flagged() {
+        let result = AptManager::parse_upgrade_output(APT_UPGRADE_DE, "");
+
+        assert!(result.success);
+        assert_eq!(result.upgraded_count, 0);
+        assert_eq!(result.parse_confidence, ParseConfidence::Unknown);
+    }
+
+    const DPKG_KERNELS: &str = "ii  linux-image-6.1.0-18-amd64\nii  linux-image-6.1.0-21-amd64\n";
+
+    fn manager(script: Vec<(&'static str, Vec<CommandResult>)>) -> AptManager {
+        AptManager::new(
+            Arc::new(ScriptedExecutor::new(script)),
+            PrivilegeEscalation::None,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_reboot_flag_file_lists_packages() {
+        let apt = manager(vec![
+            ("test -f /var/run/reboot-required", vec![output(0, "", "")]),
+            (
+                "reboot-required.pkgs",
+                vec![output(0, "linux-image-6.1.0-21-amd64\nlibc6\nlibc6\n", "")],
+            ),
+        ]);
+        let (reboot, triggered_by) = apt.reboot_check().await.unwrap();
+        assert!(reboot);
+        assert_eq!(triggered_by, ["linux-image-6.1.0-21-amd64", "libc6"]);
+    }
+
+    #[tokio::test]
+    async fn test_kernel_installed_without_flag_file_needs_reboot() {
+        let apt = manager(vec![
+            ("test -f /var/run/reboot-required", vec![output(1, "", "")]),
+            ("uname -r", vec![output(0, "6.1.0-18-amd64\n", "")]),
+            ("dpkg-query", vec![output(0, DPKG_KERNELS, "")]),
+        ]);
+        let (reboot, triggered_by) = apt.reboot_check().await.unwrap();
+        assert!(reboot);
+        assert_eq!(triggered_by, ["linux-image-6.1.0-21-amd64"]);
+
+        let apt = manager(vec![
+            ("test -f /var/run/reboot-required", vec![output(1, "", "")]),
+            ("uname -r", vec![output(0, "6.1.0-21-amd64\n", "")]),
+            ("dpkg-query", vec![output(0, DPKG_KERNELS, "")]),
+        ]);
+        assert!(!apt.reboot_required().await.unwrap());
+    }
+
+    fn refreshes(executor: &ScriptedExecutor) -> usize {
+        executor
+            .commands
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|cmd| cmd.contains("apt update"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_recent_lists_are_reused() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![]));
+        let apt = AptManager::new(executor.clone(), PrivilegeEscalation::None);
+        apt.list_upgradable().await.unwrap();
+        apt.list_upgradable().await.unwrap();
+        assert_eq!(refreshes(&executor), 1);
+
+        // An explicit refresh always runs
+        apt.update_package_lists().await.unwrap();
+        assert_eq!(refreshes(&executor), 2);
+    }
+
+    #[tokio::test]
+    async fn test_zero_max_age_always_refreshes() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![]));
+        let apt = AptManager::new(executor.clone(), PrivilegeEscalation::None)
+            .with_metadata_max_age(Duration::ZERO);
+        apt.list_upgradable().await.unwrap();
+        apt.list_upgradable().await.unwrap();
+        assert_eq!(refreshes(&executor), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_refresh_is_retried() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "apt update",
+            vec![
+                output(100, "", "E: Failed to fetch http://deb.debian.org"),
+                output(0, "", ""),
+            ],
+        )]));
+        let apt = AptManager::new(executor.clone(), PrivilegeEscalation::None);
+        assert!(matches!(
+            apt.list_upgradable().await,
+            Err(PackageError::RepositoryUnavailable(_))
+        ));
+        apt.list_upgradable().await.unwrap();
+        assert_eq!(refreshes(&executor), 2);
+    }
+
+    #[tokio::test]
+    async fn test_host_env_reaches_escalated_apt_update() {
+        let executor =
+            Arc::new(ScriptedExecutor::new(vec![]).with_env_names(&["http_proxy", "https_proxy"]));
+        let apt = AptManager::new(executor.clone(), PrivilegeEscalation::Sudo);
+        apt.update_package_lists().await.unwrap();
+
+        let commands = executor.commands.lock().unwrap();
+        assert_eq!(
+            commands[0],
+            r#"sudo -n env LC_ALL=C LANG=C http_proxy="$http_proxy" https_proxy="$https_proxy" apt update -qq"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_install_osquery_adds_repository_first() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![]));
+        let apt = AptManager::new(executor.clone(), PrivilegeEscalation::Sudo);
+        apt.install_osquery().await.unwrap();
+
+        let commands = executor.commands.lock().unwrap();
+        assert_eq!(commands.len(), 1);
+        let cmd = &commands[0];
+        assert!(cmd.starts_with("sudo -n sh -c 'set -e\n"), "{cmd}");
+        let repo = cmd.find("https://pkg.osquery.io/deb deb main").unwrap();
+        let update = cmd.find("apt-get update").unwrap();
+        let install = cmd.find("apt-get install -y osquery").unwrap();
+        assert!(repo < update && update < install, "{cmd}");
+    }
+
+    #[tokio::test]
+    async fn test_failed_osquery_install_keeps_output() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "osquery",
+            vec![output(100, "", "E: Unable to locate package osquery")],
+        )]));
+        let apt = AptManager::new(executor, PrivilegeEscalation::None);
+        let err = apt.install_osquery().await.unwrap_err();
+        assert!(matches!(
+            err,
+            PackageError::CommandFailed { status: 100, .. }
+        ));
+        assert!(err.output().unwrap().contains("Unable to locate package"));
+    }
+
+    const AUTOREMOVE_OUTPUT: &str = "\
+Reading package lists...
+Building dependency tree...
+The following packages will be REMOVED:
+  linux-image-6.1.0-20-amd64 linux-image-6.1.0-21-amd64
+0 upgraded, 0 newly installed, 2 to remove and 0 not upgraded.
+After this operation, 794 MB disk space will be freed.
+(Reading database ... 41234 files and directories currently installed.)
+Removing linux-image-6.1.0-20-amd64 (6.1.85-1) ...
+Removing linux-image-6.1.0-21-amd64 (6.1.90-1) ...
+Processing triggers for initramfs-tools (0.142) ...
+";
+
+    #[test]
+    fn test_parse_autoremove() {
+        assert_eq!(
+            AptManager::parse_autoremove(AUTOREMOVE_OUTPUT),
+            ["linux-image-6.1.0-20-amd64", "linux-image-6.1.0-21-amd64"]
+        );
+        assert!(
+            AptManager::parse_autoremove(
+                "0 upgraded, 0 newly installed, 0 to remove and 0 not upgraded.\n"
+            )
+            .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_autoremove_and_clean_wait_for_the_lock() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "autoremove",
+            vec![output(0, AUTOREMOVE_OUTPUT, "")],
+        )]));
+        let apt = AptManager::new(executor.clone(), PrivilegeEscalation::None)
+            .with_lock_timeout(Duration::from_secs(120));
+        assert_eq!(apt.autoremove().await.unwrap().len(), 2);
+        apt.clean_cache().await.unwrap();
+
+        let commands = executor.commands.lock().unwrap();
+        assert!(
+            commands[0].contains("DPkg::Lock::Timeout=120"),
+            "{}",
+            commands[0]
+        );
+        assert!(commands[0].ends_with("autoremove -y"), "{}", commands[0]);
+        assert!(commands[1].ends_with("apt clean"), "{}", commands[1]);
+    }
+
+    #[tokio::test]
+    async fn test_autoremove_lock_conflict() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "autoremove",
+            vec![output(
+                100,
+                "",
+                "E: Could not get lock /var/lib/dpkg/lock-frontend. It is held by process 1234 (apt)",
+            )],
+        )]));
+        let apt = AptManager::new(executor, PrivilegeEscalation::None);
+        assert!(matches!(
+            apt.autoremove().await,
+            Err(PackageError::LockConflict(_))
+        ));
     }
 }
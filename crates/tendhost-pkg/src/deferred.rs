@@ -0,0 +1,229 @@
+//! Package managers detected on first use
+//!
+//! Picking a host's package manager takes several round trips (`whoami`,
+//! `/etc/os-release`, `which`), which adds up when a daemon with dozens of
+//! hosts starts. A [`DeferredPackageManager`] stands in for the real
+//! manager until something needs it, then detects it once.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use tokio::sync::OnceCell;
+
+use crate::error::PackageError;
+use crate::escalation::PrivilegeEscalation;
+use crate::traits::PackageManager;
+use crate::types::{
+    DistroInfo, PackageManagerType, RestartRequirement, UpdateResult, UpgradablePackage,
+};
+
+/// Detection of the real manager
+type Detect = Box<
+    dyn Fn() -> Pin<Box<dyn Future<Output = Result<Arc<dyn PackageManager>, PackageError>> + Send>>
+        + Send
+        + Sync,
+>;
+
+/// A package manager detected the first time it is used
+///
+/// Every operation detects the manager first if needed; concurrent
+/// operations wait for the same detection, and a failed detection is tried
+/// again by the next one. Until detection succeeds the manager reports no
+/// escalation, no distribution and [`PackageManagerType::Unknown`]; use
+/// [`prepare`](PackageManager::prepare) before relying on them.
+pub struct DeferredPackageManager {
+    detect: Detect,
+    manager: OnceCell<Arc<dyn PackageManager>>,
+}
+
+impl DeferredPackageManager {
+    /// Defer `detect` until the manager is first used
+    #[must_use]
+    pub fn new<F, Fut>(detect: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<dyn PackageManager>, PackageError>> + Send + 'static,
+    {
+        Self {
+            detect: Box::new(move || Box::pin(detect())),
+            manager: OnceCell::new(),
+        }
+    }
+
+    /// Whether the manager has been detected
+    #[must_use]
+    pub fn is_detected(&self) -> bool {
+        self.manager.initialized()
+    }
+
+    /// The detected manager, detecting it now if needed
+    async fn manager(&self) -> Result<&Arc<dyn PackageManager>, PackageError> {
+        self.manager.get_or_try_init(|| (self.detect)()).await
+    }
+}
+
+#[async_trait]
+impl PackageManager for DeferredPackageManager {
+    async fn list_upgradable(&self) -> Result<Vec<UpgradablePackage>, PackageError> {
+        self.manager().await?.list_upgradable().await
+    }
+
+    async fn update_package_lists(&self) -> Result<(), PackageError> {
+        self.manager().await?.update_package_lists().await
+    }
+
+    async fn upgrade_all(&self) -> Result<UpdateResult, PackageError> {
+        self.manager().await?.upgrade_all().await
+    }
+
+    async fn upgrade_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        self.manager().await?.upgrade_dry_run().await
+    }
+
+    async fn upgrade_security(&self) -> Result<UpdateResult, PackageError> {
+        self.manager().await?.upgrade_security().await
+    }
+
+    async fn upgrade_security_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        self.manager().await?.upgrade_security_dry_run().await
+    }
+
+    async fn upgrade_packages(&self, packages: &[String]) -> Result<UpdateResult, PackageError> {
+        self.manager().await?.upgrade_packages(packages).await
+    }
+
+    async fn upgrade_packages_dry_run(
+        &self,
+        packages: &[String],
+    ) -> Result<UpdateResult, PackageError> {
+        self.manager()
+            .await?
+            .upgrade_packages_dry_run(packages)
+            .await
+    }
+
+    async fn list_stacks(&self) -> Result<Vec<String>, PackageError> {
+        self.manager().await?.list_stacks().await
+    }
+
+    async fn upgrade_stack(&self, stack: &str) -> Result<UpdateResult, PackageError> {
+        self.manager().await?.upgrade_stack(stack).await
+    }
+
+    async fn reboot_required(&self) -> Result<bool, PackageError> {
+        self.manager().await?.reboot_required().await
+    }
+
+    async fn restart_requirement(&self) -> Result<RestartRequirement, PackageError> {
+        self.manager().await?.restart_requirement().await
+    }
+
+    async fn cancel_upgrade(&self) -> Result<(), PackageError> {
+        // Nothing can be upgrading through a manager never detected
+        match self.manager.get() {
+            Some(manager) => manager.cancel_upgrade().await,
+            None => Ok(()),
+        }
+    }
+
+    async fn autoremove(&self) -> Result<Vec<String>, PackageError> {
+        self.manager().await?.autoremove().await
+    }
+
+    async fn clean_cache(&self) -> Result<(), PackageError> {
+        self.manager().await?.clean_cache().await
+    }
+
+    async fn install_osquery(&self) -> Result<String, PackageError> {
+        self.manager().await?.install_osquery().await
+    }
+
+    async fn prepare(&self) -> Result<(), PackageError> {
+        self.manager().await?.prepare().await
+    }
+
+    fn escalation(&self) -> PrivilegeEscalation {
+        self.manager
+            .get()
+            .map_or(PrivilegeEscalation::None, |m| m.escalation())
+    }
+
+    fn manager_type(&self) -> PackageManagerType {
+        self.manager
+            .get()
+            .map_or(PackageManagerType::Unknown, |m| m.manager_type())
+    }
+
+    fn distro(&self) -> Option<&DistroInfo> {
+        self.manager.get()?.distro()
+    }
+
+    async fn is_available(&self) -> bool {
+        match self.manager().await {
+            Ok(manager) => manager.is_available().await,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::apt::AptManager;
+    use crate::testing::ScriptedExecutor;
+
+    /// A manager whose detection fails `failures` times before finding apt
+    fn deferred(failures: usize) -> (DeferredPackageManager, Arc<AtomicUsize>) {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = attempts.clone();
+        let manager = DeferredPackageManager::new(move || {
+            let attempt = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if attempt < failures {
+                    return Err(PackageError::ManagerNotFound("no os-release".to_string()));
+                }
+                let executor = Arc::new(ScriptedExecutor::new(Vec::new()));
+                let manager: Arc<dyn PackageManager> =
+                    Arc::new(AptManager::new(executor, PrivilegeEscalation::Doas));
+                Ok(manager)
+            }
+        });
+        (manager, attempts)
+    }
+
+    #[tokio::test]
+    async fn test_detects_once_on_first_use() {
+        let (manager, attempts) = deferred(0);
+        assert!(!manager.is_detected());
+        assert_eq!(manager.manager_type(), PackageManagerType::Unknown);
+        assert!(manager.escalation().is_none());
+        // Cancelling before any use doesn't detect
+        manager.cancel_upgrade().await.unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 0);
+
+        let (first, second) = tokio::join!(manager.list_upgradable(), manager.prepare());
+        first.unwrap();
+        second.unwrap();
+        manager.reboot_required().await.unwrap();
+
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+        assert_eq!(manager.manager_type(), PackageManagerType::Apt);
+        assert_eq!(manager.escalation(), PrivilegeEscalation::Doas);
+    }
+
+    #[tokio::test]
+    async fn test_failed_detection_is_retried() {
+        let (manager, attempts) = deferred(1);
+        let err = manager.prepare().await.unwrap_err();
+        assert!(matches!(err, PackageError::ManagerNotFound(_)));
+        assert!(!manager.is_detected());
+
+        manager.prepare().await.unwrap();
+        assert!(manager.is_detected());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,227 @@
+//! Distribution detection
+//!
+//! Hosts are classified from `/etc/os-release` rather than by probing for
+//! package manager binaries, since tools such as `apt-get` can be on `PATH`
+//! of hosts that don't use them (container toolchains, dev shells). Probing
+//! is kept only as a fallback when os-release is missing or names a
+//! distribution family we don't recognize.
+
+use tendhost_exec::traits::RemoteExecutor;
+
+use crate::error::PackageError;
+use crate::types::{DistroInfo, PackageManagerType};
+
+/// Reads os-release from its primary location or the vendor fallback
+const OS_RELEASE_CMD: &str = "cat /etc/os-release 2>/dev/null || cat /usr/lib/os-release";
+
+/// Binaries probed when os-release is not conclusive, in order
+const PROBED_MANAGERS: [(&str, PackageManagerType); 3] = [
+    ("apt-get", PackageManagerType::Apt),
+    ("dnf", PackageManagerType::Dnf),
+    ("yum", PackageManagerType::Dnf),
+];
+
+/// Fields of interest from an os-release file
+#[derive(Debug, Default)]
+struct OsRelease {
+    id: String,
+    id_like: Vec<String>,
+    version_id: String,
+    name: String,
+}
+
+impl OsRelease {
+    fn parse(content: &str) -> Self {
+        let mut release = Self::default();
+        for line in content.lines() {
+            let Some((key, value)) = line.trim().split_once('=') else {
+                continue;
+            };
+            let value = unquote(value.trim());
+            match key {
+                "ID" => release.id = value.to_lowercase(),
+                "ID_LIKE" => {
+                    release.id_like = value.split_whitespace().map(str::to_lowercase).collect();
+                }
+                "VERSION_ID" => release.version_id = value.to_string(),
+                "NAME" => release.name = value.to_string(),
+                _ => {}
+            }
+        }
+        release
+    }
+
+    /// Package manager for this distribution, checking `ID` before `ID_LIKE`
+    fn package_manager(&self) -> Option<PackageManagerType> {
+        std::iter::once(&self.id)
+            .chain(&self.id_like)
+            .find_map(|id| family_manager(id))
+    }
+
+    fn into_distro(self, package_manager: PackageManagerType) -> DistroInfo {
+        DistroInfo {
+            id: self.id,
+            name: self.name,
+            version_id: self.version_id,
+            package_manager,
+        }
+    }
+}
+
+/// Strip one pair of matching quotes from an os-release value
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|q| value.strip_prefix(*q).and_then(|v| v.strip_suffix(*q)))
+        .unwrap_or(value)
+}
+
+/// Package manager used by a distribution family ID
+fn family_manager(id: &str) -> Option<PackageManagerType> {
+    match id {
+        "debian" | "ubuntu" | "raspbian" | "linuxmint" | "pop" | "elementary" | "kali"
+        | "devuan" => Some(PackageManagerType::Apt),
+        "fedora" | "rhel" | "centos" | "rocky" | "almalinux" | "ol" | "amzn" => {
+            Some(PackageManagerType::Dnf)
+        }
+        _ => None,
+    }
+}
+
+/// Parse os-release content into distribution info
+///
+/// Returns `None` if neither `ID` nor `ID_LIKE` names a supported family.
+#[must_use]
+pub fn distro_from_os_release(content: &str) -> Option<DistroInfo> {
+    let release = OsRelease::parse(content);
+    let package_manager = release.package_manager()?;
+    Some(release.into_distro(package_manager))
+}
+
+/// Detect the distribution and package manager of a host
+///
+/// # Errors
+/// Returns `PackageError::ManagerNotFound` if os-release is inconclusive and
+/// no supported package manager binary is installed, or
+/// `PackageError::ExecutionError` if the host cannot be reached.
+pub async fn detect_distro(executor: &dyn RemoteExecutor) -> Result<DistroInfo, PackageError> {
+    let os_release = executor
+        .run(OS_RELEASE_CMD)
+        .await
+        .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+
+    let release = if os_release.success() {
+        OsRelease::parse(&os_release.stdout)
+    } else {
+        OsRelease::default()
+    };
+    if let Some(package_manager) = release.package_manager() {
+        return Ok(release.into_distro(package_manager));
+    }
+
+    for (binary, package_manager) in PROBED_MANAGERS {
+        let probe = executor
+            .run(&format!("which {binary}"))
+            .await
+            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+        if probe.success() {
+            let mut release = release;
+            if release.id.is_empty() {
+                release.id = "unknown".to_string();
+            }
+            return Ok(release.into_distro(package_manager));
+        }
+    }
+
+    Err(PackageError::ManagerNotFound(format!(
+        "unrecognized distribution '{}' and none of apt-get, dnf, yum found",
+        if release.id.is_empty() {
+            "unknown"
+        } else {
+            &release.id
+        }
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_debian() {
+        let content = r#"PRETTY_NAME="Debian GNU/Linux 12 (bookworm)"
+NAME="Debian GNU/Linux"
+VERSION_ID="12"
+VERSION="12 (bookworm)"
+VERSION_CODENAME=bookworm
+ID=debian
+"#;
+        let distro = distro_from_os_release(content).unwrap();
+        assert_eq!(distro.id, "debian");
+        assert_eq!(distro.name, "Debian GNU/Linux");
+        assert_eq!(distro.version_id, "12");
+        assert_eq!(distro.package_manager, PackageManagerType::Apt);
+    }
+
+    #[test]
+    fn test_ubuntu() {
+        let content = r#"PRETTY_NAME="Ubuntu 24.04.1 LTS"
+NAME="Ubuntu"
+VERSION_ID="24.04"
+ID=ubuntu
+ID_LIKE=debian
+"#;
+        let distro = distro_from_os_release(content).unwrap();
+        assert_eq!(distro.id, "ubuntu");
+        assert_eq!(distro.version_id, "24.04");
+        assert_eq!(distro.package_manager, PackageManagerType::Apt);
+    }
+
+    #[test]
+    fn test_fedora() {
+        let content = r#"NAME="Fedora Linux"
+VERSION="40 (Workstation Edition)"
+ID=fedora
+VERSION_ID=40
+PLATFORM_ID="platform:f40"
+"#;
+        let distro = distro_from_os_release(content).unwrap();
+        assert_eq!(distro.id, "fedora");
+        assert_eq!(distro.name, "Fedora Linux");
+        assert_eq!(distro.version_id, "40");
+        assert_eq!(distro.package_manager, PackageManagerType::Dnf);
+    }
+
+    #[test]
+    fn test_rocky() {
+        let content = r#"NAME="Rocky Linux"
+VERSION="9.4 (Blue Onyx)"
+ID="rocky"
+ID_LIKE="rhel centos fedora"
+VERSION_ID="9.4"
+"#;
+        let distro = distro_from_os_release(content).unwrap();
+        assert_eq!(distro.id, "rocky");
+        assert_eq!(distro.version_id, "9.4");
+        assert_eq!(distro.package_manager, PackageManagerType::Dnf);
+    }
+
+    #[test]
+    fn test_id_like_only_derivative() {
+        let content = r#"NAME="Zorin OS"
+ID=zorin
+ID_LIKE="ubuntu debian"
+VERSION_ID='17'
+"#;
+        let distro = distro_from_os_release(content).unwrap();
+        assert_eq!(distro.id, "zorin");
+        assert_eq!(distro.version_id, "17");
+        assert_eq!(distro.package_manager, PackageManagerType::Apt);
+    }
+
+    #[test]
+    fn test_unsupported_family() {
+        let content = "NAME=\"Arch Linux\"\nID=arch\n";
+        assert!(distro_from_os_release(content).is_none());
+    }
+}
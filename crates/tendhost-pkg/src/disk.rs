@@ -0,0 +1,310 @@
+//! Free disk space checks before updates
+//!
+//! An upgrade that runs out of space halfway leaves half-unpacked packages
+//! and a broken dpkg or rpm database behind; `/boot` fills up first since
+//! every kernel update adds an initramfs. Checking `df` before starting is
+//! cheap and turns that mess into a clear refusal.
+
+use std::fmt;
+use std::time::Duration;
+
+use tendhost_exec::traits::RemoteExecutor;
+use tracing::debug;
+
+use crate::error::PackageError;
+
+/// Free space of every mounted filesystem, in bytes
+const DF_COMMAND: &str = "df -B1 --output=target,avail";
+
+const MIB: u64 = 1024 * 1024;
+const GIB: u64 = 1024 * MIB;
+
+/// A mounted filesystem and its free space, from one `df` row
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountSpace {
+    /// Mount point
+    pub mount: String,
+    /// Bytes available to unprivileged users
+    pub available: u64,
+}
+
+/// Parse the output of `df -B1 --output=target,avail`
+///
+/// The header and rows whose size isn't a number (`-` for pseudo
+/// filesystems on some systems) are skipped. Mount points may contain
+/// spaces, so the size is taken from the end of the row.
+#[must_use]
+pub fn parse_df(output: &str) -> Vec<MountSpace> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (mount, available) = line.trim().rsplit_once(char::is_whitespace)?;
+            let mount = mount.trim_end();
+            if !mount.starts_with('/') {
+                return None;
+            }
+            Some(MountSpace {
+                mount: mount.to_string(),
+                available: available.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Free space required on a path and what the host has there
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiskCheck {
+    /// Checked path, as configured (e.g. `/boot`)
+    pub mount: String,
+    /// Mount point of the filesystem holding the path; differs from
+    /// `mount` when the path is not a filesystem of its own
+    pub filesystem: String,
+    /// Bytes available
+    pub available: u64,
+    /// Bytes required
+    pub required: u64,
+}
+
+impl DiskCheck {
+    /// Whether there is at least the required space
+    #[must_use]
+    pub fn is_sufficient(&self) -> bool {
+        self.available >= self.required
+    }
+
+    /// Error refusing an update for lack of space here
+    #[must_use]
+    pub fn to_error(&self) -> PackageError {
+        PackageError::InsufficientDiskSpace {
+            mount: self.mount.clone(),
+            available: self.available,
+            required: self.required,
+        }
+    }
+}
+
+impl fmt::Display for DiskCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mount)?;
+        if self.filesystem != self.mount {
+            write!(f, " (on {})", self.filesystem)?;
+        }
+        write!(
+            f,
+            ": {} available, {} required",
+            format_bytes(self.available),
+            format_bytes(self.required)
+        )
+    }
+}
+
+/// Compare `mounts` with the `(path, bytes)` minimums
+///
+/// Each path is checked against the filesystem it lives on, found by the
+/// longest mount point containing it. Paths no filesystem contains are
+/// left out, as there is nothing to compare.
+#[must_use]
+pub fn evaluate(mounts: &[MountSpace], minimums: &[(String, u64)]) -> Vec<DiskCheck> {
+    minimums
+        .iter()
+        .filter_map(|(path, required)| {
+            let fs = mounts
+                .iter()
+                .filter(|m| contains(&m.mount, path))
+                .max_by_key(|m| m.mount.len())?;
+            Some(DiskCheck {
+                mount: path.clone(),
+                filesystem: fs.mount.clone(),
+                available: fs.available,
+                required: *required,
+            })
+        })
+        .collect()
+}
+
+/// Whether `path` is `mount` or below it
+fn contains(mount: &str, path: &str) -> bool {
+    let path = path.trim_end_matches('/');
+    let mount = mount.trim_end_matches('/');
+    match path.strip_prefix(mount) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
+    }
+}
+
+/// Check the host has the `(path, bytes)` minimums free
+///
+/// Returns every check, sufficient or not; callers decide whether a
+/// shortfall refuses the update (see [`DiskCheck::to_error`]) or is only
+/// reported, as in dry runs. `df` exits non-zero when some filesystem
+/// can't be read but still reports the others, so its output is used
+/// whenever it has any rows.
+///
+/// # Errors
+/// Returns `PackageError::Timeout` or `PackageError::ExecutionError` if
+/// `df` could not be run, or `PackageError::CommandFailed` if it reported
+/// nothing, e.g. a `df` without `--output`.
+pub async fn check_disk_space(
+    executor: &dyn RemoteExecutor,
+    minimums: &[(String, u64)],
+    timeout: Duration,
+) -> Result<Vec<DiskCheck>, PackageError> {
+    if minimums.is_empty() {
+        return Ok(Vec::new());
+    }
+    let result = executor
+        .run_with_timeout(DF_COMMAND, timeout)
+        .await
+        .map_err(|e| PackageError::from_exec("disk space check", e))?;
+    let mounts = parse_df(&result.stdout);
+    if mounts.is_empty() {
+        return Err(PackageError::command_failed(&result));
+    }
+    let checks = evaluate(&mounts, minimums);
+    debug!(checks = checks.len(), "checked free disk space");
+    Ok(checks)
+}
+
+/// Format a byte count compactly, e.g. `512 MB` or `1.5 GB`
+///
+/// Units are binary, matching the `min_free_space_mb` host policy.
+#[must_use]
+pub fn format_bytes(bytes: u64) -> String {
+    if bytes >= GIB {
+        #[allow(clippy::cast_precision_loss)]
+        let gib = bytes as f64 / GIB as f64;
+        format!("{gib:.1} GB")
+    } else {
+        format!("{} MB", bytes / MIB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{ScriptedExecutor, output};
+
+    const DF: &str = "Mounted on        Avail\n\
+                      /          8589934592\n\
+                      /dev              0\n\
+                      /boot        104857600\n\
+                      /mnt/usb stick 2147483648\n\
+                      /proc             -\n";
+
+    fn minimums(entries: &[(&str, u64)]) -> Vec<(String, u64)> {
+        entries
+            .iter()
+            .map(|(path, mb)| ((*path).to_string(), mb * MIB))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_df_rows() {
+        assert_eq!(
+            parse_df(DF),
+            [
+                MountSpace {
+                    mount: "/".to_string(),
+                    available: 8 * GIB,
+                },
+                MountSpace {
+                    mount: "/dev".to_string(),
+                    available: 0,
+                },
+                MountSpace {
+                    mount: "/boot".to_string(),
+                    available: 100 * MIB,
+                },
+                MountSpace {
+                    mount: "/mnt/usb stick".to_string(),
+                    available: 2 * GIB,
+                },
+            ]
+        );
+        assert!(parse_df("").is_empty());
+        assert!(parse_df("df: unrecognized option '--output'\n").is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_uses_containing_filesystem() {
+        let mounts = parse_df(DF);
+        let checks = evaluate(
+            &mounts,
+            &minimums(&[("/boot", 500), ("/var", 1024), ("/bootstrap", 1)]),
+        );
+        let [boot, var, bootstrap] = checks.as_slice() else {
+            panic!("expected three checks: {checks:?}");
+        };
+
+        assert!(!boot.is_sufficient());
+        assert_eq!(boot.filesystem, "/boot");
+        assert_eq!(boot.to_string(), "/boot: 100 MB available, 500 MB required");
+
+        // Not a filesystem of its own: /var lives on /
+        assert!(var.is_sufficient());
+        assert_eq!(var.filesystem, "/");
+        assert_eq!(
+            var.to_string(),
+            "/var (on /): 8.0 GB available, 1.0 GB required"
+        );
+
+        // A shared prefix is not containment
+        assert_eq!(bootstrap.filesystem, "/");
+    }
+
+    #[test]
+    fn test_insufficient_space_error() {
+        let check = DiskCheck {
+            mount: "/boot".to_string(),
+            filesystem: "/boot".to_string(),
+            available: 120 * MIB,
+            required: 500 * MIB,
+        };
+        assert_eq!(
+            check.to_error().to_string(),
+            "insufficient disk space on /boot: 120 MB available, 500 MB required"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_disk_space_runs_df() {
+        let executor = ScriptedExecutor::new(vec![(
+            "df ",
+            vec![output(
+                1,
+                DF,
+                "df: /run/user/1000/doc: Operation not permitted",
+            )],
+        )]);
+        let checks = check_disk_space(
+            &executor,
+            &minimums(&[("/boot", 50)]),
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        assert_eq!(checks.len(), 1);
+        assert!(checks[0].is_sufficient());
+        assert_eq!(executor.commands.lock().unwrap().as_slice(), [DF_COMMAND]);
+
+        // Busybox df has no --output
+        let executor = ScriptedExecutor::new(vec![(
+            "df ",
+            vec![output(1, "", "df: unrecognized option '--output'")],
+        )]);
+        let err = check_disk_space(&executor, &minimums(&[("/", 1)]), Duration::from_secs(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, PackageError::CommandFailed { status: 1, .. }));
+
+        // Nothing to check runs nothing
+        let executor = ScriptedExecutor::new(Vec::new());
+        assert!(
+            check_disk_space(&executor, &[], Duration::from_secs(5))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert!(executor.commands.lock().unwrap().is_empty());
+    }
+}
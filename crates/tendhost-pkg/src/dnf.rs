@@ -1,35 +1,78 @@
 //! DNF package manager (Fedora/RHEL/CentOS)
 
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use tendhost_exec::traits::RemoteExecutor;
-use tracing::{debug, info, instrument};
+use tendhost_exec::ShellCommand;
+use tendhost_exec::result::CommandResult;
+use tendhost_exec::traits::{RemoteExecutor, RemoteExecutorExt};
+use tracing::{debug, info, instrument, warn};
 
 use crate::error::PackageError;
+use crate::escalation::PrivilegeEscalation;
+use crate::kernel::{KernelSource, kernel_status};
+use crate::lists::ListsRefresh;
+use crate::locale;
+use crate::osquery;
 use crate::traits::PackageManager;
-use crate::types::{PackageManagerType, UpdateResult, UpgradablePackage};
+use crate::types::{
+    DEFAULT_METADATA_MAX_AGE, DistroInfo, OperationTimeouts, PackageManagerType, ParseConfidence,
+    RestartRequirement, UpdateResult, UpgradablePackage,
+};
 
 /// DNF package manager implementation
 ///
 /// Falls back to `yum` if `dnf` is not available.
 pub struct DnfManager {
     executor: Arc<dyn RemoteExecutor>,
-    use_sudo: bool,
+    /// How commands are run as root
+    escalation: PrivilegeEscalation,
     /// Whether to use yum instead of dnf
     use_yum: bool,
+    /// Command timeouts
+    timeouts: OperationTimeouts,
+    /// Detected distribution
+    distro: Option<DistroInfo>,
+    /// Last `dnf makecache`
+    lists: ListsRefresh,
 }
 
 impl DnfManager {
     /// Create a new DNF manager
-    pub fn new(executor: Arc<dyn RemoteExecutor>, use_sudo: bool) -> Self {
+    pub fn new(executor: Arc<dyn RemoteExecutor>, escalation: PrivilegeEscalation) -> Self {
         Self {
             executor,
-            use_sudo,
+            escalation,
             use_yum: false,
+            timeouts: OperationTimeouts::default(),
+            distro: None,
+            lists: ListsRefresh::new(DEFAULT_METADATA_MAX_AGE),
         }
     }
 
+    /// Set command timeouts
+    #[must_use]
+    pub fn with_timeouts(mut self, timeouts: OperationTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Record the distribution this manager was selected for
+    #[must_use]
+    pub fn with_distro(mut self, distro: DistroInfo) -> Self {
+        self.distro = Some(distro);
+        self
+    }
+
+    /// Reuse package metadata refreshed within `max_age` (zero always refreshes)
+    #[must_use]
+    pub fn with_metadata_max_age(mut self, max_age: Duration) -> Self {
+        self.lists = ListsRefresh::new(max_age);
+        self
+    }
+
     /// Detect whether to use dnf or yum
     #[allow(dead_code)]
     async fn detect_tool(&mut self) -> Result<(), PackageError> {
@@ -61,14 +104,91 @@ impl DnfManager {
         Ok(())
     }
 
-    /// Build dnf/yum command with optional sudo
-    fn pkg_cmd(&self, args: &str) -> String {
+    /// Build dnf/yum command through the escalation command, under the C locale
+    fn pkg_cmd(&self, args: &[&str]) -> String {
         let tool = if self.use_yum { "yum" } else { "dnf" };
-        if self.use_sudo {
-            format!("sudo {tool} {args}")
-        } else {
-            format!("{tool} {args}")
+        locale::command(tool, &self.escalation, &[], &self.executor.env_names())
+            .args(args)
+            .build()
+    }
+
+    /// Build an `update` command for the named packages
+    ///
+    /// `update` only upgrades installed packages; names that dnf would read
+    /// as options are refused.
+    fn selected_update_cmd(&self, packages: &[String], flag: &str) -> Result<String, PackageError> {
+        if let Some(name) = packages
+            .iter()
+            .find(|name| name.is_empty() || name.starts_with('-'))
+        {
+            return Err(PackageError::PackageNotFound(name.clone()));
+        }
+        let mut args = vec!["update", flag];
+        args.extend(packages.iter().map(String::as_str));
+        Ok(self.pkg_cmd(&args))
+    }
+
+    /// Run an update command and collect its result
+    ///
+    /// `operation` names the update in errors and logs.
+    async fn apply_update(&self, cmd: &str, operation: &str) -> Result<UpdateResult, PackageError> {
+        let result = self.run(cmd, self.timeouts.upgrade, operation).await?;
+
+        if !result.success() {
+            return Err(Self::change_failed(&result));
+        }
+
+        let mut update_result = Self::parse_update_output(&result.stdout);
+        update_result =
+            update_result.with_restart(self.restart_requirement().await.unwrap_or_default());
+
+        info!(
+            upgraded = update_result.upgraded_count,
+            reboot_required = update_result.reboot_required,
+            services_needing_restart = update_result.restart.services_needing_restart.len(),
+            "dnf {operation} completed"
+        );
+
+        Ok(update_result)
+    }
+
+    /// Error for a failed command that changes packages
+    fn change_failed(result: &CommandResult) -> PackageError {
+        if result.stderr.contains("lock") {
+            return PackageError::LockConflict(result.stderr.clone());
         }
+        PackageError::command_failed(result)
+    }
+
+    /// Packages in the `Removed:` list of `dnf autoremove`
+    fn parse_autoremove(output: &str) -> Vec<String> {
+        Sections::parse(output).removed
+    }
+
+    /// Run a command, failing with `PackageError::Timeout` after `timeout`
+    async fn run(
+        &self,
+        cmd: &str,
+        timeout: Duration,
+        operation: &str,
+    ) -> Result<CommandResult, PackageError> {
+        self.executor
+            .run_with_timeout(cmd, timeout)
+            .await
+            .map_err(|e| PackageError::from_exec(operation, e))
+    }
+
+    /// Run `cmd` as root through the escalation command
+    async fn run_privileged(
+        &self,
+        cmd: &ShellCommand,
+        timeout: Duration,
+        operation: &str,
+    ) -> Result<CommandResult, PackageError> {
+        self.executor
+            .run_privileged_with_timeout(cmd, &self.escalation, timeout)
+            .await
+            .map_err(|e| PackageError::from_exec(operation, e))
     }
 
     /// Parse dnf check-update output
@@ -109,34 +229,224 @@ impl DnfManager {
         packages
     }
 
-    /// Parse update output
-    fn parse_update_output(output: &str) -> UpdateResult {
-        let mut upgraded = 0u32;
+    /// Parse `updateinfo list` output into the names of packages with security advisories
+    ///
+    /// Example line: `RHSA-2024:1234 Important/Sec. openssl-libs-1:3.0.7-25.el9_3.x86_64`
+    fn parse_security_advisories(output: &str) -> HashSet<String> {
+        output
+            .lines()
+            .filter_map(|line| {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() < 3 || !parts[1].to_ascii_lowercase().contains("sec") {
+                    return None;
+                }
 
-        // Look for "Complete!" or similar success indicator
-        let success = output.contains("Complete!") || output.contains("Updated:");
+                // Strip ".arch", then "-version-release" from the NEVRA
+                let nevra = parts[2];
+                let without_arch = nevra.rfind('.').map_or(nevra, |idx| &nevra[..idx]);
+                without_arch.rsplitn(3, '-').nth(2).map(str::to_string)
+            })
+            .collect()
+    }
 
-        // Count "Updated:" lines
-        for line in output.lines() {
-            if line.starts_with("Updated:") || line.starts_with("Upgraded:") {
-                upgraded += 1;
+    /// Names of upgradable packages covered by a security advisory
+    async fn security_package_names(&self) -> Result<HashSet<String>, PackageError> {
+        let args: &[&str] = if self.use_yum {
+            &["updateinfo", "list", "security"]
+        } else {
+            &["updateinfo", "list", "--security"]
+        };
+        let result = self
+            .run(&self.pkg_cmd(args), self.timeouts.query, "advisory query")
+            .await?;
+
+        if !result.success() {
+            return Err(PackageError::command_failed(&result));
+        }
+
+        Ok(Self::parse_security_advisories(&result.stdout))
+    }
+
+    /// Whether the host needs a reboot, and the packages that caused it
+    ///
+    /// `needs-restarting -r` exits 1 when a reboot is needed and 0 when not.
+    /// Minimal images don't ship it (dnf-utils), and a missing command must
+    /// not read as "reboot needed", so without it the running kernel is
+    /// compared with the newest installed one instead.
+    async fn reboot_check(&self) -> Result<(bool, Vec<String>), PackageError> {
+        let available = self
+            .executor
+            .command_exists("needs-restarting")
+            .await
+            .map_err(|e| PackageError::from_exec("reboot check", e))?;
+        if available {
+            let result = self
+                .run("needs-restarting -r", self.timeouts.query, "reboot check")
+                .await?;
+            match result.status {
+                0 => return Ok((false, Vec::new())),
+                1 => return Ok((true, Self::parse_reboot_packages(&result.stdout))),
+                127 => debug!("needs-restarting not found, comparing kernels"),
+                status => warn!(status, "needs-restarting -r failed, comparing kernels"),
             }
+        } else {
+            debug!("needs-restarting not installed, comparing kernels");
         }
 
+        let kernel = kernel_status(
+            self.executor.as_ref(),
+            KernelSource::Rpm,
+            self.timeouts.query,
+        )
+        .await?;
+        Ok(match kernel {
+            Some(kernel) if kernel.reboot_needed() => {
+                (true, vec![KernelSource::Rpm.package_name(&kernel.newest)])
+            }
+            _ => (false, Vec::new()),
+        })
+    }
+
+    /// Packages listed by `needs-restarting -r` as requiring a reboot
+    fn parse_reboot_packages(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("* "))
+            .map(|package| package.trim().to_string())
+            .filter(|package| !package.is_empty())
+            .collect()
+    }
+
+    /// Services listed by `needs-restarting -s`, one unit per line
+    fn parse_services(output: &str) -> Vec<String> {
+        output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.contains(' '))
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// Parse update output
+    ///
+    /// Counts come from the "Transaction Summary" block, which dry runs
+    /// print too. Without one they are estimated from the `Upgraded:`,
+    /// `Installed:` and `Removed:` lists printed after the transaction.
+    fn parse_update_output(output: &str) -> UpdateResult {
+        // Look for "Complete!" or similar success indicator
+        let success = output.contains("Complete!")
+            || output.contains("Upgraded:")
+            || output.contains("Updated:");
+
+        let sections = Sections::parse(output);
+        let (counts, confidence) = if let Some(counts) = Self::parse_summary(output) {
+            (counts, ParseConfidence::Summary)
+        } else if let Some(counts) = sections.counts() {
+            warn!("dnf printed no transaction summary, counting listed packages");
+            (counts, ParseConfidence::Estimated)
+        } else {
+            warn!("dnf output not recognised, reporting no changes");
+            ((0, 0, 0), ParseConfidence::Unknown)
+        };
+
         UpdateResult {
             success,
-            upgraded_count: upgraded,
-            new_count: 0,
-            removed_count: 0,
+            upgraded_count: counts.0,
+            new_count: counts.1,
+            removed_count: counts.2,
             reboot_required: false,
-            upgraded_packages: Vec::new(),
+            restart: RestartRequirement::default(),
+            upgraded_packages: sections.upgraded,
             error: if success {
                 None
             } else {
                 Some(output.to_string())
             },
+            reclaimed_bytes: None,
+            download_bytes: None,
+            autoremoved_packages: Vec::new(),
+            parse_confidence: confidence,
         }
     }
+
+    /// Parse the "Transaction Summary" block
+    ///
+    /// Handles both dnf 4 (`Upgrade  2 Packages`) and dnf 5
+    /// (`Upgrading:  2 packages`). Returns the upgraded, new and removed
+    /// counts.
+    fn parse_summary(output: &str) -> Option<(u32, u32, u32)> {
+        let lines = output
+            .lines()
+            .skip_while(|line| !line.trim_start().starts_with("Transaction Summary"))
+            .skip(1)
+            .skip_while(|line| line.starts_with('='));
+
+        let mut counts = None;
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [label, n, unit] = words[..] else { break };
+            let Ok(n) = n.parse::<u32>() else { break };
+            if !unit.to_ascii_lowercase().starts_with("package") {
+                break;
+            }
+            let (upgraded, new_pkgs, removed) = counts.get_or_insert((0, 0, 0));
+            match label.trim_end_matches(':') {
+                "Upgrade" | "Upgrading" | "Update" | "Updating" => *upgraded += n,
+                "Install" | "Installing" => *new_pkgs += n,
+                "Remove" | "Removing" => *removed += n,
+                _ => {}
+            }
+        }
+        counts
+    }
+}
+
+/// Packages listed after a dnf transaction
+#[derive(Debug, Default)]
+struct Sections {
+    upgraded: Vec<String>,
+    new: Vec<String>,
+    removed: Vec<String>,
+}
+
+impl Sections {
+    fn parse(output: &str) -> Self {
+        let mut sections = Self::default();
+        let mut current: Option<&mut Vec<String>> = None;
+        for line in output.lines() {
+            if !line.starts_with(' ') {
+                current = match line.trim_end() {
+                    "Upgraded:" | "Updated:" => Some(&mut sections.upgraded),
+                    "Installed:" => Some(&mut sections.new),
+                    "Removed:" => Some(&mut sections.removed),
+                    _ => None,
+                };
+                continue;
+            }
+            // Older releases put several packages on one line
+            if let Some(list) = current.as_deref_mut() {
+                list.extend(
+                    line.split_whitespace()
+                        .map(|nevra| package_name(nevra).to_string()),
+                );
+            }
+        }
+        sections
+    }
+
+    /// Upgraded, new and removed counts, if any section was found
+    fn counts(&self) -> Option<(u32, u32, u32)> {
+        if self.upgraded.is_empty() && self.new.is_empty() && self.removed.is_empty() {
+            return None;
+        }
+        let len = |list: &Vec<String>| u32::try_from(list.len()).unwrap_or(u32::MAX);
+        Some((len(&self.upgraded), len(&self.new), len(&self.removed)))
+    }
+}
+
+/// Name part of `name-version-release.arch`
+fn package_name(nevra: &str) -> &str {
+    nevra.rsplitn(3, '-').nth(2).unwrap_or(nevra)
 }
 
 #[async_trait]
@@ -145,59 +455,76 @@ impl PackageManager for DnfManager {
     async fn list_upgradable(&self) -> Result<Vec<UpgradablePackage>, PackageError> {
         debug!("listing upgradable packages");
 
-        let cmd = self.pkg_cmd("check-update");
-        let result = self
-            .executor
-            .run(&cmd)
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+        let reused = self.lists.is_fresh();
+        if reused {
+            debug!(
+                max_age_secs = self.lists.max_age().as_secs(),
+                "reusing package metadata"
+            );
+        } else {
+            self.update_package_lists().await?;
+        }
 
-        // dnf check-update returns exit code 100 when updates are available
-        // exit code 0 when no updates
+        // The metadata is current now, so check-update must not download it
+        // again; exit code 100 means updates are available, 0 none
+        let cmd = self.pkg_cmd(&["check-update", "--cacheonly"]);
+        let mut result = self.run(&cmd, self.timeouts.query, "check-update").await?;
+        if result.status != 0 && result.status != 100 && reused {
+            // The cache may have been cleaned since (`dnf clean all`)
+            debug!(
+                status = result.status,
+                "cached metadata unusable, refreshing"
+            );
+            self.lists.invalidate();
+            self.update_package_lists().await?;
+            result = self.run(&cmd, self.timeouts.query, "check-update").await?;
+        }
         if result.status != 0 && result.status != 100 {
-            return Err(PackageError::CommandFailed {
-                status: result.status,
-                message: result.stderr,
-            });
+            return Err(PackageError::command_failed(&result));
         }
 
-        let packages = Self::parse_upgradable(&result.stdout);
-        info!(count = packages.len(), "found upgradable packages");
+        let mut packages = Self::parse_upgradable(&result.stdout);
+
+        // Advisory metadata is optional (e.g. missing on some mirrors), so
+        // treat a failed lookup as "no security updates" instead of an error
+        match self.security_package_names().await {
+            Ok(security) => {
+                for pkg in &mut packages {
+                    pkg.security = security.contains(&pkg.name);
+                }
+            }
+            Err(e) => warn!(error = %e, "failed to query security advisories"),
+        }
+
+        info!(
+            count = packages.len(),
+            security = packages.iter().filter(|p| p.security).count(),
+            "found upgradable packages"
+        );
 
         Ok(packages)
     }
 
     #[instrument(skip(self))]
-    async fn upgrade_all(&self) -> Result<UpdateResult, PackageError> {
-        info!("starting dnf update");
-
-        let cmd = self.pkg_cmd("update -y");
+    async fn update_package_lists(&self) -> Result<(), PackageError> {
+        let cmd = self.pkg_cmd(&["makecache", "--refresh"]);
         let result = self
-            .executor
-            .run(&cmd)
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
-
+            .run(&cmd, self.timeouts.update_lists, "update lists")
+            .await?;
         if !result.success() {
-            if result.stderr.contains("lock") {
-                return Err(PackageError::LockConflict(result.stderr));
-            }
-            return Err(PackageError::CommandFailed {
-                status: result.status,
-                message: result.stderr,
-            });
+            return Err(PackageError::RepositoryUnavailable(result.stderr));
         }
+        self.lists.refreshed();
+        debug!("package metadata updated");
+        Ok(())
+    }
 
-        let mut update_result = Self::parse_update_output(&result.stdout);
-        update_result.reboot_required = self.reboot_required().await.unwrap_or(false);
-
-        info!(
-            upgraded = update_result.upgraded_count,
-            reboot_required = update_result.reboot_required,
-            "dnf update completed"
-        );
+    #[instrument(skip(self))]
+    async fn upgrade_all(&self) -> Result<UpdateResult, PackageError> {
+        info!("starting dnf update");
 
-        Ok(update_result)
+        let cmd = self.pkg_cmd(&["update", "-y"]);
+        self.apply_update(&cmd, "upgrade").await
     }
 
     #[instrument(skip(self))]
@@ -206,12 +533,8 @@ impl PackageManager for DnfManager {
 
         // dnf doesn't have a direct simulate flag like apt
         // Use --assumeno to simulate without installing
-        let cmd = self.pkg_cmd("update --assumeno");
-        let result = self
-            .executor
-            .run(&cmd)
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+        let cmd = self.pkg_cmd(&["update", "--assumeno"]);
+        let result = self.run(&cmd, self.timeouts.query, "dry run").await?;
 
         // --assumeno will "fail" but show what would be done
         let update_result = Self::parse_update_output(&result.stdout);
@@ -219,17 +542,147 @@ impl PackageManager for DnfManager {
         Ok(update_result)
     }
 
+    #[instrument(skip(self))]
+    async fn upgrade_security(&self) -> Result<UpdateResult, PackageError> {
+        info!("starting dnf security update");
+
+        let cmd = self.pkg_cmd(&["update", "-y", "--security"]);
+        self.apply_update(&cmd, "security upgrade").await
+    }
+
+    #[instrument(skip(self))]
+    async fn upgrade_security_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        debug!("starting dnf security dry run");
+
+        let cmd = self.pkg_cmd(&["update", "--assumeno", "--security"]);
+        let result = self.run(&cmd, self.timeouts.query, "dry run").await?;
+
+        // --assumeno will "fail" but show what would be done
+        Ok(Self::parse_update_output(&result.stdout))
+    }
+
+    #[instrument(skip(self))]
+    async fn upgrade_packages(&self, packages: &[String]) -> Result<UpdateResult, PackageError> {
+        info!(
+            count = packages.len(),
+            "starting dnf update of selected packages"
+        );
+
+        let cmd = self.selected_update_cmd(packages, "-y")?;
+        self.apply_update(&cmd, "selected upgrade").await
+    }
+
+    #[instrument(skip(self))]
+    async fn upgrade_packages_dry_run(
+        &self,
+        packages: &[String],
+    ) -> Result<UpdateResult, PackageError> {
+        debug!(
+            count = packages.len(),
+            "starting dnf dry run of selected packages"
+        );
+
+        let cmd = self.selected_update_cmd(packages, "--assumeno")?;
+        let result = self.run(&cmd, self.timeouts.query, "dry run").await?;
+
+        // --assumeno will "fail" but show what would be done
+        Ok(Self::parse_update_output(&result.stdout))
+    }
+
+    #[instrument(skip(self))]
+    async fn autoremove(&self) -> Result<Vec<String>, PackageError> {
+        let cmd = self.pkg_cmd(&["autoremove", "-y"]);
+        let result = self.run(&cmd, self.timeouts.upgrade, "autoremove").await?;
+        if !result.success() {
+            return Err(Self::change_failed(&result));
+        }
+
+        let removed = Self::parse_autoremove(&result.stdout);
+        info!(removed = removed.len(), "dnf autoremove completed");
+        Ok(removed)
+    }
+
+    #[instrument(skip(self))]
+    async fn clean_cache(&self) -> Result<(), PackageError> {
+        let cmd = self.pkg_cmd(&["clean", "packages"]);
+        let result = self.run(&cmd, self.timeouts.upgrade, "clean").await?;
+        if !result.success() {
+            return Err(Self::change_failed(&result));
+        }
+        debug!("dnf cache cleaned");
+        Ok(())
+    }
+
     #[instrument(skip(self))]
     async fn reboot_required(&self) -> Result<bool, PackageError> {
-        // Check if needs-restarting exists and reports reboot needed
+        Ok(self.reboot_check().await?.0)
+    }
+
+    #[instrument(skip(self))]
+    async fn restart_requirement(&self) -> Result<RestartRequirement, PackageError> {
+        let (reboot_needed, triggered_by) = self.reboot_check().await?;
+
+        let needs_restarting = ShellCommand::new("needs-restarting").arg("-s");
+        let services = self
+            .run_privileged(&needs_restarting, self.timeouts.query, "restart check")
+            .await?;
+        let services_needing_restart = if services.success() {
+            Self::parse_services(&services.stdout)
+        } else {
+            debug!(status = services.status, "needs-restarting -s unavailable");
+            Vec::new()
+        };
+
+        Ok(RestartRequirement {
+            reboot_needed,
+            services_needing_restart,
+            triggered_by,
+        })
+    }
+
+    #[instrument(skip(self))]
+    async fn cancel_upgrade(&self) -> Result<(), PackageError> {
+        warn!("terminating running dnf processes");
+
+        let tool = if self.use_yum { "yum" } else { "dnf" };
+        let pkill = ShellCommand::new("pkill").args(["-TERM", "-x", tool]);
         let result = self
-            .executor
-            .run("needs-restarting -r")
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+            .run_privileged(&pkill, self.timeouts.query, "cancel")
+            .await?;
 
-        // needs-restarting -r exits 1 if reboot required, 0 if not
-        Ok(!result.success())
+        // pkill exits with 1 when nothing matched
+        if result.status > 1 {
+            return Err(PackageError::command_failed(&result));
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    async fn install_osquery(&self) -> Result<String, PackageError> {
+        info!("installing osquery");
+
+        let tool = if self.use_yum { "yum" } else { "dnf" };
+        let install = format!("{tool} install -y osquery");
+        let mut lines = osquery::RPM_REPO_SETUP.to_vec();
+        lines.push(&install);
+        let cmd = ShellCommand::script(&lines);
+        let result = self
+            .run_privileged(&cmd, self.timeouts.upgrade, "osquery install")
+            .await?;
+        if !result.success() {
+            return Err(PackageError::command_failed(&result));
+        }
+
+        Ok(result.combined_output())
+    }
+
+    fn distro(&self) -> Option<&DistroInfo> {
+        self.distro.as_ref()
+    }
+
+    fn escalation(&self) -> PrivilegeEscalation {
+        self.escalation.clone()
     }
 
     fn manager_type(&self) -> PackageManagerType {
@@ -260,6 +713,7 @@ impl PackageManager for DnfManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{ScriptedExecutor, output};
 
     #[test]
     fn test_parse_upgradable() {
@@ -273,4 +727,374 @@ curl.x86_64         7.76.1-26.el9_0 baseos";
         assert_eq!(packages[0].name, "vim-enhanced");
         assert_eq!(packages[0].new_version, "2:8.2.2637-20.el9_1");
     }
+
+    #[test]
+    fn test_parse_security_advisories() {
+        let output = r"Last metadata expiration check: 0:12:02 ago.
+FEDORA-2024-1a2b3c4d5e Moderate/Sec.  curl-8.2.1-4.fc39.x86_64
+RHSA-2024:1234         Important/Sec. openssl-libs-1:3.0.7-25.el9_3.x86_64
+FEDORA-2024-9f8e7d6c5b bugfix         vim-enhanced-2:9.1.016-1.fc39.x86_64";
+
+        let names = DnfManager::parse_security_advisories(output);
+
+        assert_eq!(names.len(), 2);
+        assert!(names.contains("curl"));
+        assert!(names.contains("openssl-libs"));
+        assert!(!names.contains("vim-enhanced"));
+    }
+
+    #[test]
+    fn test_parse_needs_restarting() {
+        let reboot = r"Core libraries or services have been updated since boot-up:
+  * kernel
+  * glibc
+
+Reboot is required to fully utilize these updates.
+More information: https://access.redhat.com/solutions/27943";
+        assert_eq!(
+            DnfManager::parse_reboot_packages(reboot),
+            ["kernel", "glibc"]
+        );
+
+        let services = "sshd.service\nchronyd.service\n";
+        assert_eq!(
+            DnfManager::parse_services(services),
+            ["sshd.service", "chronyd.service"]
+        );
+    }
+
+    const DNF_UPGRADE: &str = "Dependencies resolved.
+================================================================================
+ Package            Architecture  Version                Repository        Size
+================================================================================
+Upgrading:
+ curl               x86_64        8.2.1-4.fc39           updates          345 k
+ libcurl            x86_64        8.2.1-4.fc39           updates          321 k
+Installing:
+ kernel             x86_64        6.8.11-300.fc40        updates          152 k
+
+Transaction Summary
+================================================================================
+Install  1 Package
+Upgrade  2 Packages
+
+Total download size: 818 k
+Downloading Packages:
+Running transaction
+Upgraded:
+  curl-8.2.1-4.fc39.x86_64                 libcurl-8.2.1-4.fc39.x86_64
+Installed:
+  kernel-6.8.11-300.fc40.x86_64
+
+Complete!
+";
+
+    #[test]
+    fn test_parse_update_output() {
+        let result = DnfManager::parse_update_output(DNF_UPGRADE);
+
+        assert!(result.success);
+        assert_eq!(result.upgraded_count, 2);
+        assert_eq!(result.new_count, 1);
+        assert_eq!(result.parse_confidence, ParseConfidence::Summary);
+        assert_eq!(result.upgraded_packages, ["curl", "libcurl"]);
+
+        let dnf5 = "Transaction Summary:
+ Upgrading:          3 packages
+ Replacing:          3 packages
+ Removing:           1 package
+
+Operation aborted by the user.
+";
+        let result = DnfManager::parse_update_output(dnf5);
+        assert_eq!(result.upgraded_count, 3);
+        assert_eq!(result.removed_count, 1);
+    }
+
+    #[test]
+    fn test_parse_update_output_without_summary_counts_sections() {
+        let output = DNF_UPGRADE
+            .split_once("Running transaction")
+            .map(|(_, after)| after)
+            .unwrap();
+
+        let result = DnfManager::parse_update_output(output);
+
+        assert_eq!(result.upgraded_count, 2);
+        assert_eq!(result.new_count, 1);
+        assert_eq!(result.parse_confidence, ParseConfidence::Estimated);
+
+        let result = DnfManager::parse_update_output("Abgeschlossen!\n");
+        assert_eq!(result.upgraded_count, 0);
+        assert_eq!(result.parse_confidence, ParseConfidence::Unknown);
+    }
+
+    #[test]
+    fn test_pkg_cmd_forces_c_locale() {
+        let executor = Arc::new(tendhost_exec::LocalExecutor::new());
+        assert_eq!(
+            DnfManager::new(executor.clone(), PrivilegeEscalation::Sudo).pkg_cmd(&["update", "-y"]),
+            "sudo -n env LC_ALL=C LANG=C dnf update -y"
+        );
+        assert_eq!(
+            DnfManager::new(executor.clone(), PrivilegeEscalation::None).pkg_cmd(&["check-update"]),
+            "LC_ALL=C LANG=C dnf check-update"
+        );
+        assert_eq!(
+            DnfManager::new(executor.clone(), PrivilegeEscalation::Doas)
+                .pkg_cmd(&["upgrade", "-y"]),
+            "doas -n env LC_ALL=C LANG=C dnf upgrade -y"
+        );
+        assert_eq!(
+            DnfManager::new(
+                executor,
+                PrivilegeEscalation::Custom("run0 --quiet".to_string())
+            )
+            .pkg_cmd(&["makecache"]),
+            "run0 --quiet env LC_ALL=C LANG=C dnf makecache"
+        );
+    }
+
+    #[test]
+    fn test_selected_update_cmd() {
+        let manager = DnfManager::new(
+            Arc::new(tendhost_exec::LocalExecutor::new()),
+            PrivilegeEscalation::None,
+        );
+        assert_eq!(
+            manager
+                .selected_update_cmd(&["openssl".to_string(), "curl".to_string()], "-y")
+                .unwrap(),
+            "LC_ALL=C LANG=C dnf update -y openssl curl"
+        );
+        assert!(
+            manager
+                .selected_update_cmd(&["--nogpgcheck".to_string()], "-y")
+                .is_err()
+        );
+    }
+
+    const RPM_KERNELS: &str = "6.8.9-300.fc40.x86_64\n6.8.11-300.fc40.x86_64\n";
+
+    fn manager(
+        script: Vec<(&'static str, Vec<CommandResult>)>,
+    ) -> (DnfManager, Arc<ScriptedExecutor>) {
+        let executor = Arc::new(ScriptedExecutor::new(script));
+        (
+            DnfManager::new(executor.clone(), PrivilegeEscalation::None),
+            executor,
+        )
+    }
+
+    fn ran(executor: &ScriptedExecutor, pattern: &str) -> bool {
+        executor
+            .commands
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|cmd| cmd.contains(pattern))
+    }
+
+    fn refreshes(executor: &ScriptedExecutor) -> usize {
+        executor
+            .commands
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|cmd| cmd.contains("makecache"))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_recent_metadata_is_reused() {
+        let (dnf, executor) = manager(vec![]);
+        dnf.list_upgradable().await.unwrap();
+        dnf.list_upgradable().await.unwrap();
+        assert_eq!(refreshes(&executor), 1);
+        assert!(ran(&executor, "check-update --cacheonly"));
+
+        let (dnf, executor) = manager(vec![]);
+        let dnf = dnf.with_metadata_max_age(Duration::ZERO);
+        dnf.list_upgradable().await.unwrap();
+        dnf.list_upgradable().await.unwrap();
+        assert_eq!(refreshes(&executor), 2);
+    }
+
+    #[tokio::test]
+    async fn test_missing_cache_is_refreshed() {
+        // The second check-update finds the cache gone (`dnf clean all`)
+        let (dnf, executor) = manager(vec![(
+            "check-update",
+            vec![
+                output(0, "", ""),
+                output(1, "", "Error: Cache-only enabled but no cache for 'fedora'"),
+                output(100, "vim.x86_64  2:9.1.0-1.fc40  updates\n", ""),
+            ],
+        )]);
+        dnf.list_upgradable().await.unwrap();
+        let packages = dnf.list_upgradable().await.unwrap();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(refreshes(&executor), 2);
+    }
+
+    #[tokio::test]
+    async fn test_needs_restarting_decides_when_installed() {
+        let (dnf, executor) = manager(vec![
+            (
+                "which needs-restarting",
+                vec![output(0, "/usr/bin/needs-restarting\n", "")],
+            ),
+            (
+                "needs-restarting -r",
+                vec![output(1, "  * kernel\n  * glibc\n", "")],
+            ),
+        ]);
+        let restart = dnf.restart_requirement().await.unwrap();
+        assert!(restart.reboot_needed);
+        assert_eq!(restart.triggered_by, ["kernel", "glibc"]);
+
+        let (dnf, executor_up_to_date) = manager(vec![
+            (
+                "which needs-restarting",
+                vec![output(0, "/usr/bin/needs-restarting\n", "")],
+            ),
+            ("needs-restarting -r", vec![output(0, "", "")]),
+            ("uname -r", vec![output(0, "6.8.9-300.fc40.x86_64\n", "")]),
+            ("rpm -q", vec![output(0, RPM_KERNELS, "")]),
+        ]);
+        assert!(!dnf.reboot_required().await.unwrap());
+        // A definite answer never falls back to the kernel comparison
+        assert!(!ran(&executor, "uname -r"));
+        assert!(!ran(&executor_up_to_date, "uname -r"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_needs_restarting_compares_kernels() {
+        let (dnf, executor) = manager(vec![
+            ("which needs-restarting", vec![output(1, "", "")]),
+            ("uname -r", vec![output(0, "6.8.9-300.fc40.x86_64\n", "")]),
+            ("rpm -q", vec![output(0, RPM_KERNELS, "")]),
+        ]);
+        let restart = dnf.restart_requirement().await.unwrap();
+        assert!(restart.reboot_needed);
+        assert_eq!(restart.triggered_by, ["kernel-core-6.8.11-300.fc40.x86_64"]);
+        assert!(!ran(&executor, "needs-restarting -r"));
+
+        // Already on the newest kernel
+        let (dnf, _) = manager(vec![
+            ("which needs-restarting", vec![output(1, "", "")]),
+            ("uname -r", vec![output(0, "6.8.11-300.fc40.x86_64\n", "")]),
+            ("rpm -q", vec![output(0, RPM_KERNELS, "")]),
+        ]);
+        assert!(!dnf.reboot_required().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_needs_restarting_not_found_is_not_a_reboot() {
+        // `which` found it, but running it says "command not found"
+        let (dnf, executor) = manager(vec![
+            (
+                "which needs-restarting",
+                vec![output(0, "/usr/bin/needs-restarting\n", "")],
+            ),
+            (
+                "needs-restarting -r",
+                vec![output(127, "", "needs-restarting: not found")],
+            ),
+            ("uname -r", vec![output(0, "6.8.11-300.fc40.x86_64\n", "")]),
+            ("rpm -q", vec![output(0, RPM_KERNELS, "")]),
+        ]);
+        assert!(!dnf.reboot_required().await.unwrap());
+        assert!(ran(&executor, "rpm -q"));
+
+        // No kernel package at all (containers): nothing known to need a reboot
+        let (dnf, _) = manager(vec![
+            ("which needs-restarting", vec![output(1, "", "")]),
+            ("uname -r", vec![output(0, "6.8.12-4-pve\n", "")]),
+            (
+                "rpm -q",
+                vec![output(
+                    1,
+                    "package kernel-core is not installed\npackage kernel is not installed\n",
+                    "",
+                )],
+            ),
+        ]);
+        assert!(!dnf.reboot_required().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_install_osquery_adds_repository_first() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![]));
+        let dnf = DnfManager::new(executor.clone(), PrivilegeEscalation::Doas);
+        let output = dnf.install_osquery().await.unwrap();
+        assert_eq!(output, "");
+
+        let commands = executor.commands.lock().unwrap();
+        assert_eq!(commands.len(), 1);
+        let cmd = &commands[0];
+        assert!(cmd.starts_with("doas -n sh -c 'set -e\n"), "{cmd}");
+        let repo = cmd.find("/etc/yum.repos.d/osquery-s3-rpm.repo").unwrap();
+        let install = cmd.find("dnf install -y osquery").unwrap();
+        assert!(repo < install, "{cmd}");
+    }
+
+    const AUTOREMOVE_OUTPUT: &str = "\
+Dependencies resolved.
+================================================================================
+ Package              Arch        Version                  Repository      Size
+================================================================================
+Removing:
+ kernel-core          x86_64      6.5.6-300.fc39           @updates       64 M
+ kernel-modules       x86_64      6.5.6-300.fc39           @updates       36 M
+
+Transaction Summary
+================================================================================
+Remove  2 Packages
+
+Freed space: 100 M
+Running transaction
+  Erasing          : kernel-modules-6.5.6-300.fc39.x86_64                   1/2
+  Erasing          : kernel-core-6.5.6-300.fc39.x86_64                      2/2
+
+Removed:
+  kernel-core-6.5.6-300.fc39.x86_64      kernel-modules-6.5.6-300.fc39.x86_64
+
+Complete!
+";
+
+    #[test]
+    fn test_parse_autoremove() {
+        assert_eq!(
+            DnfManager::parse_autoremove(AUTOREMOVE_OUTPUT),
+            ["kernel-core", "kernel-modules"]
+        );
+        assert!(
+            DnfManager::parse_autoremove("Dependencies resolved.\nNothing to do.\nComplete!\n")
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_autoremove_and_clean() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "autoremove",
+            vec![output(0, AUTOREMOVE_OUTPUT, "")],
+        )]));
+        let dnf = DnfManager::new(executor.clone(), PrivilegeEscalation::None);
+        assert_eq!(dnf.autoremove().await.unwrap().len(), 2);
+        dnf.clean_cache().await.unwrap();
+
+        let commands = executor.commands.lock().unwrap();
+        assert!(
+            commands[0].ends_with("dnf autoremove -y"),
+            "{}",
+            commands[0]
+        );
+        assert!(
+            commands[1].ends_with("dnf clean packages"),
+            "{}",
+            commands[1]
+        );
+    }
 }
@@ -1,15 +1,50 @@
 //! Docker Compose stack management
+//!
+//! Every compose command names the project directory, so the `.env` file
+//! next to the compose file and relative paths in it resolve as they do
+//! when compose is run from that directory, not from the SSH user's home.
 
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use async_trait::async_trait;
-use tendhost_exec::traits::RemoteExecutor;
-use tracing::{debug, error, info, instrument};
+use tendhost_exec::ShellCommand;
+use tendhost_exec::result::CommandResult;
+use tendhost_exec::traits::{RemoteExecutor, RemoteExecutorExt};
+use tracing::{debug, error, info, instrument, warn};
 
 use crate::error::PackageError;
+use crate::escalation::PrivilegeEscalation;
 use crate::traits::PackageManager;
-use crate::types::{PackageManagerType, UpdateResult, UpgradablePackage};
+use crate::types::{
+    OperationTimeouts, PackageManagerType, PrunePolicy, UpdateResult, UpgradablePackage,
+};
+
+/// `docker inspect` template printing a container's service and image ID
+const SERVICE_IMAGE_FORMAT: &str =
+    r#"{{index .Config.Labels "com.docker.compose.service"}} {{.Image}}"#;
+
+/// How compose commands are pointed at their project directory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProjectDirectory {
+    /// Pass `--project-directory <dir>`
+    #[default]
+    Flag,
+    /// Run `cd <dir> &&` first, for docker-compose releases that lack
+    /// `--project-directory`
+    ChangeDir,
+}
+
+/// Outcome of updating one compose directory
+#[derive(Debug, Default)]
+struct DirUpdate {
+    /// Services whose image changed, as `<dir>/<service>`
+    upgraded: Vec<String>,
+    /// Failed steps and services
+    errors: Vec<String>,
+}
 
 /// Docker Compose manager
 ///
@@ -18,10 +53,20 @@ pub struct DockerComposeManager {
     executor: Arc<dyn RemoteExecutor>,
     /// Directories containing docker-compose.yml files
     compose_dirs: Vec<PathBuf>,
+    /// Env files passed with `--env-file`, by compose directory
+    env_files: BTreeMap<PathBuf, PathBuf>,
+    /// How commands find their project directory
+    project_directory: ProjectDirectory,
     /// Whether to use "docker compose" (v2) or "docker-compose" (v1)
     use_v2: bool,
     /// Whether to pull images before updating
     pull_before_update: bool,
+    /// Command timeouts
+    timeouts: OperationTimeouts,
+    /// Image cleanup after successful updates
+    prune: PrunePolicy,
+    /// How docker is run as root; none when the user is in the docker group
+    escalation: PrivilegeEscalation,
 }
 
 impl DockerComposeManager {
@@ -46,11 +91,84 @@ impl DockerComposeManager {
         Ok(Self {
             executor,
             compose_dirs,
+            env_files: BTreeMap::new(),
+            project_directory: ProjectDirectory::default(),
             use_v2: true, // Will detect
             pull_before_update: true,
+            timeouts: OperationTimeouts::default(),
+            prune: PrunePolicy::Off,
+            escalation: PrivilegeEscalation::None,
         })
     }
 
+    /// Set command timeouts
+    #[must_use]
+    pub fn with_timeouts(mut self, timeouts: OperationTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Prune images after updates in which every service came up
+    #[must_use]
+    pub fn with_prune_policy(mut self, prune: PrunePolicy) -> Self {
+        self.prune = prune;
+        self
+    }
+
+    /// Run docker through `escalation` (by default it runs as the user)
+    #[must_use]
+    pub fn with_escalation(mut self, escalation: PrivilegeEscalation) -> Self {
+        self.escalation = escalation;
+        self
+    }
+
+    /// Read variables for `compose_dir` from `env_file` instead of its
+    /// `.env`; a relative path is taken from the compose directory
+    #[must_use]
+    pub fn with_env_file(
+        mut self,
+        compose_dir: impl Into<PathBuf>,
+        env_file: impl Into<PathBuf>,
+    ) -> Self {
+        let compose_dir = compose_dir.into();
+        let env_file = compose_dir.join(env_file.into());
+        self.env_files.insert(compose_dir, env_file);
+        self
+    }
+
+    /// Set how commands are pointed at their project directory
+    #[must_use]
+    pub fn with_project_directory(mut self, project_directory: ProjectDirectory) -> Self {
+        self.project_directory = project_directory;
+        self
+    }
+
+    /// Run a command, failing with `PackageError::Timeout` after `timeout`
+    async fn run(
+        &self,
+        cmd: &str,
+        timeout: Duration,
+        operation: &str,
+    ) -> Result<CommandResult, PackageError> {
+        self.executor
+            .run_with_timeout(cmd, timeout)
+            .await
+            .map_err(|e| PackageError::from_exec(operation, e))
+    }
+
+    /// Run `cmd` as root through the escalation command
+    async fn run_privileged(
+        &self,
+        cmd: &ShellCommand,
+        timeout: Duration,
+        operation: &str,
+    ) -> Result<CommandResult, PackageError> {
+        self.executor
+            .run_privileged_with_timeout(cmd, &self.escalation, timeout)
+            .await
+            .map_err(|e| PackageError::from_exec(operation, e))
+    }
+
     /// Detect docker compose version
     #[allow(dead_code)]
     async fn detect_version(&mut self) -> Result<(), PackageError> {
@@ -92,24 +210,205 @@ impl DockerComposeManager {
     }
 
     /// Build docker compose command
-    fn compose_cmd(&self, compose_dir: &Path, args: &str) -> String {
-        let cmd = if self.use_v2 {
-            "docker compose"
+    ///
+    /// The compose file comes first so `cancel_upgrade` can find running
+    /// commands by it. Paths and `args` are quoted, so directories with
+    /// spaces or shell metacharacters stay a single argument.
+    fn compose_cmd(&self, compose_dir: &Path, args: &[&str]) -> ShellCommand {
+        let program = if self.use_v2 {
+            "docker"
         } else {
             "docker-compose"
         };
+        let mut cmd = match self.project_directory {
+            ProjectDirectory::Flag => self.escalation.command(program),
+            ProjectDirectory::ChangeDir => ShellCommand::new("cd")
+                .arg(compose_dir.to_string_lossy())
+                .raw("&&")
+                .args(self.escalation.words())
+                .arg(program),
+        };
+        if self.use_v2 {
+            cmd = cmd.arg("compose");
+        }
+        cmd = cmd
+            .arg("-f")
+            .arg(compose_dir.join("docker-compose.yml").to_string_lossy());
+        if self.project_directory == ProjectDirectory::Flag {
+            cmd = cmd
+                .arg("--project-directory")
+                .arg(compose_dir.to_string_lossy());
+        }
+        if let Some(env_file) = self.env_files.get(compose_dir) {
+            cmd = cmd.arg("--env-file").arg(env_file.to_string_lossy());
+        }
+        cmd.args(args)
+    }
+
+    /// Stack name for a compose directory
+    ///
+    /// Matches the default compose project name: the directory basename.
+    fn stack_name(compose_dir: &Path) -> String {
+        compose_dir.file_name().map_or_else(
+            || compose_dir.display().to_string(),
+            |name| name.to_string_lossy().into_owned(),
+        )
+    }
+
+    /// Find the compose directory for `stack`
+    fn stack_dir(&self, stack: &str) -> Result<&Path, PackageError> {
+        self.compose_dirs
+            .iter()
+            .find(|dir| Self::stack_name(dir) == stack)
+            .map(PathBuf::as_path)
+            .ok_or_else(|| PackageError::UnknownStack {
+                stack: stack.to_string(),
+                valid: self
+                    .compose_dirs
+                    .iter()
+                    .map(|dir| Self::stack_name(dir))
+                    .collect(),
+            })
+    }
+
+    /// Pull and recreate the containers of one compose directory
+    ///
+    /// Services count as upgraded when their container runs a different
+    /// image afterwards. A service whose pull fails is reported and the
+    /// rest of the directory is still updated.
+    async fn upgrade_dir(&self, compose_dir: &Path) -> Result<DirUpdate, PackageError> {
         let dir = compose_dir.display();
-        format!("{cmd} -f {dir}/docker-compose.yml {args}")
+        let mut update = DirUpdate::default();
+
+        if !self.compose_file_exists(compose_dir).await? {
+            error!(dir = %dir, "compose file not found");
+            update.errors.push(format!("{dir}: compose file not found"));
+            return Ok(update);
+        }
+
+        // Validates the project before anything is pulled or recreated
+        let services = self.services(compose_dir).await?;
+        let before = self.service_images(compose_dir).await?;
+
+        // Pull each service separately so one bad image doesn't block the rest
+        if self.pull_before_update {
+            for service in services {
+                let pull_cmd = self.compose_cmd(compose_dir, &["pull", &service]);
+                let pull_result = self
+                    .run(pull_cmd.as_str(), self.timeouts.upgrade, "pull")
+                    .await?;
+
+                if !pull_result.success() {
+                    let reason = pull_result
+                        .stderr
+                        .lines()
+                        .rev()
+                        .find(|line| !line.trim().is_empty())
+                        .unwrap_or("pull failed")
+                        .trim();
+                    warn!(dir = %dir, service, reason, "image pull failed");
+                    update.errors.push(format!("{dir}/{service}: {reason}"));
+                }
+            }
+        }
+
+        // Recreate containers with new images
+        let up_cmd = self.compose_cmd(compose_dir, &["up", "-d", "--force-recreate"]);
+        let up_result = self
+            .run(up_cmd.as_str(), self.timeouts.upgrade, "up")
+            .await?;
+
+        if !up_result.success() {
+            update.errors.push(format!("{dir}: up failed"));
+            return Ok(update);
+        }
+
+        let after = self.service_images(compose_dir).await?;
+        update.upgraded = changed_services(&before, &after)
+            .map(|service| format!("{dir}/{service}"))
+            .collect();
+        Ok(update)
+    }
+
+    /// Services defined in a compose directory
+    ///
+    /// # Errors
+    /// Returns `PackageError::ComposeConfigInvalid` with compose's message
+    /// when the project doesn't resolve, e.g. for a missing variable.
+    async fn services(&self, compose_dir: &Path) -> Result<Vec<String>, PackageError> {
+        let cmd = self.compose_cmd(compose_dir, &["config", "--services"]);
+        let result = self.run(cmd.as_str(), self.timeouts.query, "query").await?;
+        if !result.success() {
+            let stderr = result.stderr.trim();
+            warn!(dir = %compose_dir.display(), stderr, "invalid compose project");
+            return Err(PackageError::ComposeConfigInvalid {
+                dir: compose_dir.display().to_string(),
+                stderr: stderr.to_string(),
+            });
+        }
+
+        Ok(result
+            .stdout
+            .lines()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(ToString::to_string)
+            .collect())
+    }
+
+    /// Image ID each service's containers currently run
+    ///
+    /// Services without containers are absent.
+    async fn service_images(
+        &self,
+        compose_dir: &Path,
+    ) -> Result<BTreeMap<String, String>, PackageError> {
+        let cmd = self
+            .compose_cmd(compose_dir, &["ps", "-q"])
+            .raw("|")
+            .args(["xargs", "-r"])
+            .args(self.escalation.words())
+            .args(["docker", "inspect", "--format"])
+            .arg(SERVICE_IMAGE_FORMAT);
+        let result = self.run(cmd.as_str(), self.timeouts.query, "query").await?;
+        if !result.success() {
+            return Err(PackageError::command_failed(&result));
+        }
+
+        Ok(parse_service_images(&result.stdout))
+    }
+
+    /// Prune images as the policy says, recording the space freed
+    ///
+    /// Skipped when any step of the update failed, so the images the
+    /// previous containers ran stay around for a rollback. A failed prune
+    /// is logged and does not fail the update.
+    async fn prune_images(&self, result: &mut UpdateResult) {
+        let Some(cmd) = prune_cmd(self.prune, &self.escalation) else {
+            return;
+        };
+        if !result.success {
+            info!("update had failures, skipping image prune");
+            return;
+        }
+
+        match self.run(cmd.as_str(), self.timeouts.upgrade, "prune").await {
+            Ok(output) if output.success() => {
+                result.reclaimed_bytes = parse_reclaimed_space(&output.stdout);
+                info!(reclaimed_bytes = ?result.reclaimed_bytes, "pruned docker images");
+            }
+            Ok(output) => warn!(stderr = %output.stderr.trim(), "docker image prune failed"),
+            Err(e) => warn!(error = %e, "docker image prune failed"),
+        }
     }
 
     /// Check if compose file exists
     async fn compose_file_exists(&self, compose_dir: &Path) -> Result<bool, PackageError> {
         let path = compose_dir.join("docker-compose.yml");
-        let result = self
-            .executor
-            .run(&format!("test -f {}", path.display()))
-            .await
-            .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+        let cmd = ShellCommand::new("test")
+            .arg("-f")
+            .arg(path.to_string_lossy());
+        let result = self.run(cmd.as_str(), self.timeouts.query, "query").await?;
         Ok(result.success())
     }
 }
@@ -129,49 +428,28 @@ impl PackageManager for DockerComposeManager {
                 continue;
             }
 
-            // Get list of services
-            let cmd = self.compose_cmd(compose_dir, "config --services");
-            let result = self
-                .executor
-                .run(&cmd)
-                .await
-                .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
-
-            if !result.success() {
-                continue;
-            }
-
             // For each service, check if image can be pulled
-            for service in result.stdout.lines() {
-                let service = service.trim();
-                if service.is_empty() {
-                    continue;
-                }
+            for service in self.services(compose_dir).await? {
+                let service = service.as_str();
 
                 // Get current image
-                let img_cmd = format!(
-                    "docker compose -f {}/docker-compose.yml ps -q {}",
-                    compose_dir.display(),
-                    service
-                );
+                let img_cmd = self.compose_cmd(compose_dir, &["ps", "-q", service]);
                 let img_result = self
-                    .executor
-                    .run(&img_cmd)
-                    .await
-                    .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+                    .run(img_cmd.as_str(), self.timeouts.query, "query")
+                    .await?;
 
                 if img_result.success() && !img_result.stdout.trim().is_empty() {
                     // Check if newer image available
-                    let check_cmd = format!(
-                        "docker compose -f {}/docker-compose.yml pull --dry-run {} 2>&1 || true",
-                        compose_dir.display(),
-                        service
-                    );
+                    let check_cmd = self
+                        .compose_cmd(compose_dir, &["pull", "--dry-run", service])
+                        .raw("2>&1 || true");
                     let check_result = self
-                        .executor
-                        .run(&check_cmd)
-                        .await
-                        .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+                        .run(
+                            check_cmd.as_str(),
+                            self.timeouts.update_lists,
+                            "registry check",
+                        )
+                        .await?;
 
                     if check_result.stdout.contains("Downloaded newer image") {
                         upgradable.push(UpgradablePackage::new(
@@ -192,66 +470,19 @@ impl PackageManager for DockerComposeManager {
     async fn upgrade_all(&self) -> Result<UpdateResult, PackageError> {
         info!("starting docker compose update");
 
-        let mut total_upgraded = 0u32;
-        let mut errors = Vec::new();
-
+        let mut total = DirUpdate::default();
         for compose_dir in &self.compose_dirs {
-            if !self.compose_file_exists(compose_dir).await? {
-                error!(dir = %compose_dir.display(), "compose file not found");
-                continue;
-            }
-
-            // Pull images if configured
-            if self.pull_before_update {
-                let pull_cmd = self.compose_cmd(compose_dir, "pull");
-                let pull_result = self
-                    .executor
-                    .run(&pull_cmd)
-                    .await
-                    .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
-
-                if !pull_result.success() {
-                    errors.push(format!("{}: pull failed", compose_dir.display()));
-                    continue;
-                }
-            }
-
-            // Recreate containers with new images
-            let up_cmd = self.compose_cmd(compose_dir, "up -d --force-recreate");
-            let up_result = self
-                .executor
-                .run(&up_cmd)
-                .await
-                .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
-
-            if up_result.success() {
-                // Count services in this compose file
-                let ps_cmd = self.compose_cmd(compose_dir, "ps -q");
-                let ps_result = self
-                    .executor
-                    .run(&ps_cmd)
-                    .await
-                    .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
-
-                if ps_result.success() {
-                    let count = u32::try_from(ps_result.stdout.lines().count()).unwrap_or(0);
-                    total_upgraded += count;
-                }
-            } else {
-                errors.push(format!("{}: up failed", compose_dir.display()));
-            }
+            let update = self.upgrade_dir(compose_dir).await?;
+            total.upgraded.extend(update.upgraded);
+            total.errors.extend(update.errors);
         }
 
-        let success = errors.is_empty();
-        let mut result = UpdateResult::success(total_upgraded);
-        result.success = success;
-        if !success {
-            result.error = Some(errors.join("; "));
-        }
+        let mut result = total.into_result();
+        self.prune_images(&mut result).await;
 
         info!(
-            upgraded = total_upgraded,
-            success = success,
+            upgraded = result.upgraded_count,
+            success = result.success,
             "docker compose update completed"
         );
 
@@ -268,14 +499,13 @@ impl PackageManager for DockerComposeManager {
             if !self.compose_file_exists(compose_dir).await? {
                 continue;
             }
+            self.services(compose_dir).await?;
 
             // Just check what would be pulled
-            let cmd = self.compose_cmd(compose_dir, "pull --dry-run");
+            let cmd = self.compose_cmd(compose_dir, &["pull", "--dry-run"]);
             let result = self
-                .executor
-                .run(&cmd)
-                .await
-                .map_err(|e| PackageError::ExecutionError(e.to_string()))?;
+                .run(cmd.as_str(), self.timeouts.update_lists, "dry run")
+                .await?;
 
             if result.success() {
                 // Count images that would be pulled
@@ -287,12 +517,66 @@ impl PackageManager for DockerComposeManager {
         Ok(UpdateResult::success(total_upgradable))
     }
 
+    async fn list_stacks(&self) -> Result<Vec<String>, PackageError> {
+        Ok(self
+            .compose_dirs
+            .iter()
+            .map(|dir| Self::stack_name(dir))
+            .collect())
+    }
+
+    #[instrument(skip(self))]
+    async fn upgrade_stack(&self, stack: &str) -> Result<UpdateResult, PackageError> {
+        let compose_dir = self.stack_dir(stack)?;
+        info!(stack, dir = %compose_dir.display(), "starting docker compose stack update");
+
+        let mut result = self.upgrade_dir(compose_dir).await?.into_result();
+        self.prune_images(&mut result).await;
+
+        info!(
+            stack,
+            upgraded = result.upgraded_count,
+            success = result.success,
+            "docker compose stack update completed"
+        );
+
+        Ok(result)
+    }
+
     #[instrument(skip(self))]
     async fn reboot_required(&self) -> Result<bool, PackageError> {
         // Docker containers don't require host reboot
         Ok(false)
     }
 
+    #[instrument(skip(self))]
+    async fn cancel_upgrade(&self) -> Result<(), PackageError> {
+        warn!("terminating running docker compose processes");
+
+        let cmd = if self.use_v2 {
+            "docker compose"
+        } else {
+            "docker-compose"
+        };
+        let pkill = ShellCommand::new("pkill")
+            .args(["-TERM", "-f"])
+            .arg(format!("{cmd} -f .* (pull|up)"));
+        let result = self
+            .run_privileged(&pkill, self.timeouts.query, "cancel")
+            .await?;
+
+        // pkill exits with 1 when nothing matched
+        if result.status > 1 {
+            return Err(PackageError::command_failed(&result));
+        }
+
+        Ok(())
+    }
+
+    fn escalation(&self) -> PrivilegeEscalation {
+        self.escalation.clone()
+    }
+
     fn manager_type(&self) -> PackageManagerType {
         PackageManagerType::DockerCompose
     }
@@ -306,11 +590,267 @@ impl PackageManager for DockerComposeManager {
     }
 }
 
+impl DirUpdate {
+    fn into_result(self) -> UpdateResult {
+        let mut result =
+            UpdateResult::success(u32::try_from(self.upgraded.len()).unwrap_or(u32::MAX));
+        result.upgraded_packages = self.upgraded;
+        if !self.errors.is_empty() {
+            result.success = false;
+            result.error = Some(self.errors.join("; "));
+        }
+        result
+    }
+}
+
+/// `docker image prune` invocation for `policy`, `None` when off
+fn prune_cmd(policy: PrunePolicy, escalation: &PrivilegeEscalation) -> Option<ShellCommand> {
+    let cmd = escalation.command("docker").args(["image", "prune", "-f"]);
+    match policy {
+        PrunePolicy::Off => None,
+        PrunePolicy::Dangling => Some(cmd),
+        PrunePolicy::UnusedOlderThan(age) if age.is_zero() => Some(cmd.arg("-a")),
+        PrunePolicy::UnusedOlderThan(age) => Some(
+            cmd.arg("-a")
+                .arg("--filter")
+                .arg(format!("until={}s", age.as_secs())),
+        ),
+    }
+}
+
+/// Parse the `Total reclaimed space: 1.234GB` line of `docker image prune`
+fn parse_reclaimed_space(output: &str) -> Option<u64> {
+    let size = output
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Total reclaimed space:"))?
+        .trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    // Docker prints decimal units; binary ones are accepted too
+    let multiplier: f64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "b" | "" => 1.0,
+        "kb" => 1e3,
+        "mb" => 1e6,
+        "gb" => 1e9,
+        "tb" => 1e12,
+        "pb" => 1e15,
+        "kib" => 1024.0,
+        "mib" => 1024.0 * 1024.0,
+        "gib" => 1024.0 * 1024.0 * 1024.0,
+        "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return None,
+    };
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    Some((number * multiplier).round() as u64)
+}
+
+/// Parse `<service> <image id>` lines from `docker inspect`
+fn parse_service_images(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (service, image) = line.trim().split_once(' ')?;
+            Some((service.to_string(), image.trim().to_string()))
+        })
+        .filter(|(service, image)| !service.is_empty() && !image.is_empty())
+        .collect()
+}
+
+/// Services running a different image than before, including new ones
+fn changed_services<'a>(
+    before: &'a BTreeMap<String, String>,
+    after: &'a BTreeMap<String, String>,
+) -> impl Iterator<Item = &'a String> {
+    after
+        .iter()
+        .filter(|(service, image)| before.get(*service) != Some(*image))
+        .map(|(service, _)| service)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{ScriptedExecutor, output};
     use tendhost_exec::LocalExecutor;
 
+    #[test]
+    fn test_changed_services() {
+        let before = parse_service_images("web sha256:aaa\ndb sha256:bbb\n");
+        let after = parse_service_images("web sha256:aaa\ndb sha256:ccc\ncache sha256:ddd\n");
+
+        let changed: Vec<_> = changed_services(&before, &after).collect();
+        assert_eq!(changed, ["cache", "db"]);
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_reports_services_with_new_images() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![
+            (
+                "config --services",
+                vec![output(0, "web\ndb\nworker\n", "")],
+            ),
+            (
+                "docker inspect",
+                vec![
+                    output(0, "web sha256:aaa\ndb sha256:bbb\nworker sha256:eee\n", ""),
+                    output(0, "web sha256:aaa\ndb sha256:ccc\nworker sha256:eee\n", ""),
+                ],
+            ),
+        ]));
+        let manager =
+            DockerComposeManager::new(executor.clone(), vec![PathBuf::from("/opt/app")]).unwrap();
+
+        let result = manager.upgrade_all().await.unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.upgraded_count, 1);
+        assert_eq!(result.upgraded_packages, ["/opt/app/db"]);
+        let commands = executor.commands.lock().unwrap();
+        assert!(commands.iter().any(|c| c.ends_with("pull worker")));
+    }
+
+    #[tokio::test]
+    async fn test_pull_failures_are_collected_per_service() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![
+            ("config --services", vec![output(0, "web\ndb\n", "")]),
+            (
+                "pull web",
+                vec![output(1, "", "Error: manifest for web:9 not found\n")],
+            ),
+            (
+                "docker inspect",
+                vec![
+                    output(0, "web sha256:aaa\ndb sha256:bbb\n", ""),
+                    output(0, "web sha256:aaa\ndb sha256:ccc\n", ""),
+                ],
+            ),
+        ]));
+        let manager =
+            DockerComposeManager::new(executor.clone(), vec![PathBuf::from("/opt/app")]).unwrap();
+
+        let result = manager.upgrade_stack("app").await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.upgraded_packages, ["/opt/app/db"]);
+        assert_eq!(
+            result.error.as_deref(),
+            Some("/opt/app/web: Error: manifest for web:9 not found")
+        );
+        let commands = executor.commands.lock().unwrap();
+        assert!(
+            commands
+                .iter()
+                .any(|c| c.contains("up -d --force-recreate"))
+        );
+    }
+
+    #[test]
+    fn test_parse_reclaimed_space() {
+        let output = "Deleted Images:\n\
+                      untagged: nginx@sha256:0a1b\n\
+                      deleted: sha256:2c3d\n\
+                      \n\
+                      Total reclaimed space: 1.234GB\n";
+        assert_eq!(parse_reclaimed_space(output), Some(1_234_000_000));
+        assert_eq!(
+            parse_reclaimed_space("Total reclaimed space: 0B\n"),
+            Some(0)
+        );
+        assert_eq!(
+            parse_reclaimed_space("Total reclaimed space: 512kB"),
+            Some(512_000)
+        );
+        assert_eq!(
+            parse_reclaimed_space("Total reclaimed space: 1.5MiB"),
+            Some(1_572_864)
+        );
+        assert_eq!(parse_reclaimed_space("Deleted Images:\n"), None);
+        assert_eq!(parse_reclaimed_space("Total reclaimed space: lots"), None);
+    }
+
+    #[test]
+    fn test_prune_cmd() {
+        let user = PrivilegeEscalation::None;
+        assert!(prune_cmd(PrunePolicy::Off, &user).is_none());
+        assert_eq!(
+            prune_cmd(PrunePolicy::Dangling, &user).unwrap().as_str(),
+            "docker image prune -f"
+        );
+        assert_eq!(
+            prune_cmd(PrunePolicy::UnusedOlderThan(Duration::ZERO), &user)
+                .unwrap()
+                .as_str(),
+            "docker image prune -f -a"
+        );
+        assert_eq!(
+            prune_cmd(
+                PrunePolicy::UnusedOlderThan(Duration::from_secs(86400)),
+                &user
+            )
+            .unwrap()
+            .as_str(),
+            "docker image prune -f -a --filter until=86400s"
+        );
+        assert_eq!(
+            prune_cmd(PrunePolicy::Dangling, &PrivilegeEscalation::Doas)
+                .unwrap()
+                .as_str(),
+            "doas -n docker image prune -f"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prune_after_successful_update() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![
+            ("config --services", vec![output(0, "web\n", "")]),
+            (
+                "image prune",
+                vec![output(
+                    0,
+                    "Deleted Images:\ndeleted: sha256:aaa\n\nTotal reclaimed space: 250MB\n",
+                    "",
+                )],
+            ),
+        ]));
+        let manager = DockerComposeManager::new(executor.clone(), vec![PathBuf::from("/opt/app")])
+            .unwrap()
+            .with_prune_policy(PrunePolicy::Dangling);
+
+        let result = manager.upgrade_all().await.unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.reclaimed_bytes, Some(250_000_000));
+        let commands = executor.commands.lock().unwrap();
+        assert_eq!(commands.last().unwrap(), "docker image prune -f");
+    }
+
+    #[tokio::test]
+    async fn test_prune_skipped_when_a_service_fails() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![
+            ("config --services", vec![output(0, "web\n", "")]),
+            (
+                "up -d",
+                vec![output(1, "", "Error: container web is unhealthy\n")],
+            ),
+        ]));
+        let manager = DockerComposeManager::new(
+            executor.clone(),
+            vec![PathBuf::from("/opt/app"), PathBuf::from("/opt/db")],
+        )
+        .unwrap()
+        .with_prune_policy(PrunePolicy::UnusedOlderThan(Duration::from_secs(3600)));
+
+        let result = manager.upgrade_all().await.unwrap();
+
+        assert!(!result.success);
+        assert_eq!(result.reclaimed_bytes, None);
+        let commands = executor.commands.lock().unwrap();
+        assert!(!commands.iter().any(|c| c.contains("image prune")));
+    }
+
     #[test]
     fn test_compose_cmd_v2() {
         let manager = DockerComposeManager::new(
@@ -319,8 +859,187 @@ mod tests {
         )
         .unwrap();
 
-        let cmd = manager.compose_cmd(&PathBuf::from("/opt/stacks/monitoring"), "up -d");
-        assert!(cmd.contains("docker compose"));
-        assert!(cmd.contains("/opt/stacks/monitoring/docker-compose.yml"));
+        let cmd = manager.compose_cmd(&PathBuf::from("/opt/stacks/monitoring"), &["up", "-d"]);
+        assert!(cmd.as_str().starts_with("docker compose"));
+        assert!(
+            cmd.as_str()
+                .contains("/opt/stacks/monitoring/docker-compose.yml")
+        );
+
+        let manager = manager.with_escalation(PrivilegeEscalation::Sudo);
+        let cmd = manager.compose_cmd(&PathBuf::from("/opt/stacks/monitoring"), &["pull"]);
+        assert_eq!(
+            cmd.as_str(),
+            "sudo -n docker compose -f /opt/stacks/monitoring/docker-compose.yml \
+             --project-directory /opt/stacks/monitoring pull"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_every_command_names_the_project_directory() {
+        let dir = PathBuf::from("/opt/stacks/app");
+        let script = || {
+            Arc::new(ScriptedExecutor::new(vec![
+                ("config --services", vec![output(0, "web\n", "")]),
+                ("docker inspect", vec![output(0, "web sha256:aaa\n", "")]),
+            ]))
+        };
+
+        let executor = script();
+        DockerComposeManager::new(executor.clone(), vec![dir.clone()])
+            .unwrap()
+            .with_env_file(&dir, ".env.prod")
+            .upgrade_all()
+            .await
+            .unwrap();
+        let compose = "docker compose -f /opt/stacks/app/docker-compose.yml \
+                       --project-directory /opt/stacks/app \
+                       --env-file /opt/stacks/app/.env.prod";
+        let inspect = "| xargs -r docker inspect --format \
+                       '{{index .Config.Labels \"com.docker.compose.service\"}} {{.Image}}'";
+        assert_eq!(
+            *executor.commands.lock().unwrap(),
+            [
+                "test -f /opt/stacks/app/docker-compose.yml".to_string(),
+                format!("{compose} config --services"),
+                format!("{compose} ps -q {inspect}"),
+                format!("{compose} pull web"),
+                format!("{compose} up -d --force-recreate"),
+                format!("{compose} ps -q {inspect}"),
+            ]
+        );
+
+        // Changing directory instead, with an absolute env file kept as is
+        let executor = script();
+        DockerComposeManager::new(executor.clone(), vec![dir.clone()])
+            .unwrap()
+            .with_env_file(&dir, "/etc/app.env")
+            .with_escalation(PrivilegeEscalation::Sudo)
+            .with_project_directory(ProjectDirectory::ChangeDir)
+            .upgrade_all()
+            .await
+            .unwrap();
+        let compose = "cd /opt/stacks/app && sudo -n docker compose \
+                       -f /opt/stacks/app/docker-compose.yml --env-file /etc/app.env";
+        let inspect = inspect.replace("xargs -r", "xargs -r sudo -n");
+        assert_eq!(
+            executor.commands.lock().unwrap()[1..],
+            [
+                format!("{compose} config --services"),
+                format!("{compose} ps -q {inspect}"),
+                format!("{compose} pull web"),
+                format!("{compose} up -d --force-recreate"),
+                format!("{compose} ps -q {inspect}"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_compose_config_is_reported() {
+        let invalid = output(
+            15,
+            "",
+            "required variable DB_PASSWORD is missing a value: set it in .env\n",
+        );
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "config --services",
+            vec![invalid.clone(), invalid.clone(), invalid],
+        )]));
+        let manager =
+            DockerComposeManager::new(executor.clone(), vec![PathBuf::from("/opt/app")]).unwrap();
+
+        let err = manager.list_upgradable().await.unwrap_err();
+        let PackageError::ComposeConfigInvalid { dir, stderr } = &err else {
+            panic!("expected an invalid config, got {err:?}");
+        };
+        assert_eq!(dir, "/opt/app");
+        assert_eq!(
+            stderr,
+            "required variable DB_PASSWORD is missing a value: set it in .env"
+        );
+        assert_eq!(err.output(), Some(stderr.as_str()));
+
+        // Nothing is pulled or recreated for a project that doesn't resolve
+        assert!(matches!(
+            manager.upgrade_all().await,
+            Err(PackageError::ComposeConfigInvalid { .. })
+        ));
+        assert!(matches!(
+            manager.upgrade_dry_run().await,
+            Err(PackageError::ComposeConfigInvalid { .. })
+        ));
+        let commands = executor.commands.lock().unwrap();
+        assert!(
+            !commands
+                .iter()
+                .any(|c| c.contains(" pull") || c.contains(" up "))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compose_dir_with_shell_metacharacters_stays_one_argument() {
+        let dir = PathBuf::from("/tmp/a b;echo pwned");
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "config --services",
+            vec![output(0, "web;id\n", "")],
+        )]));
+        let manager = DockerComposeManager::new(executor.clone(), vec![dir.clone()]).unwrap();
+
+        manager.upgrade_all().await.unwrap();
+
+        {
+            let commands = executor.commands.lock().unwrap();
+            assert_eq!(
+                commands[0],
+                "test -f '/tmp/a b;echo pwned/docker-compose.yml'"
+            );
+            assert!(
+                commands
+                    .iter()
+                    .any(|c| c.ends_with("--project-directory '/tmp/a b;echo pwned' pull 'web;id'"))
+            );
+        }
+
+        // The quoted paths reach the program as single arguments
+        let cmd = manager.compose_cmd(&dir, &[]);
+        let args = cmd
+            .as_str()
+            .strip_prefix("docker compose ")
+            .unwrap()
+            .to_string();
+        let echo = ShellCommand::new("printf").arg("%s\n").raw(&args);
+        let result = LocalExecutor::new().run(echo.as_str()).await.unwrap();
+        assert_eq!(
+            result.stdout,
+            "-f\n/tmp/a b;echo pwned/docker-compose.yml\n--project-directory\n/tmp/a b;echo pwned\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_stacks_and_unknown_stack() {
+        let manager = DockerComposeManager::new(
+            Arc::new(LocalExecutor::new()),
+            vec![
+                PathBuf::from("/opt/stacks/monitoring"),
+                PathBuf::from("/opt/stacks/media/"),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            manager.list_stacks().await.unwrap(),
+            vec!["monitoring", "media"]
+        );
+        assert_eq!(
+            manager.stack_dir("media").unwrap(),
+            Path::new("/opt/stacks/media/")
+        );
+
+        let err = manager.upgrade_stack("nextcloud").await.unwrap_err();
+        assert!(matches!(err, PackageError::UnknownStack { .. }));
+        assert_eq!(
+            err.to_string(),
+            "unknown stack 'nextcloud' (valid stacks: monitoring, media)"
+        );
     }
 }
@@ -1,7 +1,16 @@
 //! Error types for tendhost-pkg
 
+use std::time::Duration;
+
+use tendhost_exec::error::ExecError;
+use tendhost_exec::result::CommandResult;
 use thiserror::Error;
 
+use crate::disk::format_bytes;
+
+/// Most output kept from a failed command, in bytes
+pub const MAX_FAILURE_OUTPUT_BYTES: usize = 64 * 1024;
+
 /// Errors that can occur during package operations
 #[derive(Error, Debug, Clone)]
 pub enum PackageError {
@@ -25,6 +34,10 @@ pub enum PackageError {
     #[error("insufficient permissions: {0}")]
     PermissionDenied(String),
 
+    /// The host refused the SSH login or key
+    #[error("authentication failed: {0}")]
+    AuthenticationFailed(String),
+
     /// Command execution failed
     #[error("command failed: {status} - {message}")]
     CommandFailed {
@@ -32,6 +45,8 @@ pub enum PackageError {
         status: i32,
         /// Error message
         message: String,
+        /// End of the command's stdout and stderr, see [`output_tail`]
+        output: String,
     },
 
     /// Failed to parse command output
@@ -50,12 +65,134 @@ pub enum PackageError {
     #[error("compose file not found: {0}")]
     ComposeFileNotFound(String),
 
+    /// `docker compose config` rejected a project, e.g. for a bad
+    /// interpolation or a missing env file
+    #[error("invalid compose project {dir}: {stderr}")]
+    ComposeConfigInvalid {
+        /// Compose directory
+        dir: String,
+        /// What compose printed about the problem
+        stderr: String,
+    },
+
     /// Invalid configuration
     #[error("invalid configuration: {0}")]
     ConfigError(String),
+
+    /// Operation not supported by this package manager
+    #[error("unsupported operation: {0}")]
+    Unsupported(String),
+
+    /// Requested stack is not managed by this host
+    #[error("unknown stack '{stack}' (valid stacks: {})", valid.join(", "))]
+    UnknownStack {
+        /// Requested stack name
+        stack: String,
+        /// Stacks that are configured
+        valid: Vec<String>,
+    },
+
+    /// Too little free space to start an update safely
+    #[error(
+        "insufficient disk space on {mount}: {} available, {} required",
+        format_bytes(*available),
+        format_bytes(*required)
+    )]
+    InsufficientDiskSpace {
+        /// Checked mount point
+        mount: String,
+        /// Bytes available
+        available: u64,
+        /// Bytes the host policy requires
+        required: u64,
+    },
+
+    /// Command did not finish within its configured timeout
+    #[error("{operation} timed out after {}", format_duration(*timeout))]
+    Timeout {
+        /// Operation that timed out (e.g. "upgrade")
+        operation: String,
+        /// Timeout that was exceeded
+        timeout: Duration,
+    },
+}
+
+/// Combined stdout and stderr of a command, cut to its last `max_bytes`
+///
+/// Stdout comes first since package managers print most errors there.
+/// When output has to be cut, it starts at the first whole line that fits.
+#[must_use]
+pub fn output_tail(stdout: &str, stderr: &str, max_bytes: usize) -> String {
+    let combined = match (stdout.trim_end(), stderr.trim_end()) {
+        (out, "") => out.to_string(),
+        ("", err) => err.to_string(),
+        (out, err) => format!("{out}\n{err}"),
+    };
+    if combined.len() <= max_bytes {
+        return combined;
+    }
+
+    let mut start = combined.len() - max_bytes;
+    while !combined.is_char_boundary(start) {
+        start += 1;
+    }
+    // Drop a partial first line unless the tail is a single line
+    if let Some(newline) = combined[start..].find('\n')
+        && start > 0
+        && combined.as_bytes()[start - 1] != b'\n'
+    {
+        start += newline + 1;
+    }
+    combined[start..].to_string()
+}
+
+/// Format a duration compactly, e.g. `30m`, `90s`, `2h`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 && secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs >= 60 && secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{secs}s")
+    }
 }
 
 impl PackageError {
+    /// Error for a command that exited unsuccessfully, keeping its output tail
+    #[must_use]
+    pub fn command_failed(result: &CommandResult) -> Self {
+        PackageError::CommandFailed {
+            status: result.status,
+            message: result.stderr.clone(),
+            output: output_tail(&result.stdout, &result.stderr, MAX_FAILURE_OUTPUT_BYTES),
+        }
+    }
+
+    /// Output of the failed command, when the error carries any
+    #[must_use]
+    pub fn output(&self) -> Option<&str> {
+        match self {
+            PackageError::CommandFailed { output, .. } if !output.is_empty() => Some(output),
+            PackageError::ComposeConfigInvalid { stderr, .. } if !stderr.is_empty() => Some(stderr),
+            _ => None,
+        }
+    }
+
+    /// Convert an executor error, keeping timeouts and refused logins distinct
+    pub(crate) fn from_exec(operation: &str, err: ExecError) -> Self {
+        match err {
+            ExecError::Timeout { timeout } => PackageError::Timeout {
+                operation: operation.to_string(),
+                timeout,
+            },
+            ExecError::AuthenticationFailed(_) | ExecError::SshKeyError(_) => {
+                PackageError::AuthenticationFailed(err.to_string())
+            }
+            other => PackageError::ExecutionError(other.to_string()),
+        }
+    }
+
     /// Check if error is retryable
     #[must_use]
     pub fn is_retryable(&self) -> bool {
@@ -71,3 +208,79 @@ impl PackageError {
         matches!(self, PackageError::PermissionDenied(_))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_message() {
+        let err = PackageError::from_exec(
+            "upgrade",
+            ExecError::Timeout {
+                timeout: Duration::from_secs(30 * 60),
+            },
+        );
+        assert_eq!(err.to_string(), "upgrade timed out after 30m");
+
+        let err = PackageError::from_exec("query", ExecError::NotConnected);
+        assert!(matches!(err, PackageError::ExecutionError(_)));
+
+        let err = PackageError::from_exec(
+            "query",
+            ExecError::AuthenticationFailed("publickey".to_string()),
+        );
+        assert!(matches!(err, PackageError::AuthenticationFailed(_)));
+    }
+
+    #[test]
+    fn test_command_failed_keeps_stdout_and_stderr() {
+        let result = CommandResult {
+            status: 100,
+            stdout: "Reading package lists...\nE: Unmet dependencies\n".to_string(),
+            stderr: "E: Sub-process returned an error code\n".to_string(),
+            duration: Duration::from_secs(1),
+        };
+        let err = PackageError::command_failed(&result);
+        assert_eq!(
+            err.output(),
+            Some(
+                "Reading package lists...\nE: Unmet dependencies\nE: Sub-process returned an error code"
+            )
+        );
+        assert_eq!(
+            err.to_string(),
+            "command failed: 100 - E: Sub-process returned an error code\n"
+        );
+
+        let quiet = CommandResult {
+            stdout: String::new(),
+            stderr: String::new(),
+            ..result
+        };
+        assert_eq!(PackageError::command_failed(&quiet).output(), None);
+    }
+
+    #[test]
+    fn test_output_tail_caps_size_at_line_boundary() {
+        let stdout: String = (1..=1000).map(|i| format!("line {i}\n")).collect();
+        let tail = output_tail(&stdout, "", 100);
+
+        assert!(tail.len() <= 100);
+        assert!(tail.starts_with("line "), "partial first line: {tail:?}");
+        assert!(tail.ends_with("line 1000"));
+
+        // Short output is kept whole
+        assert_eq!(output_tail("a\nb\n", "c", 100), "a\nb\nc");
+    }
+
+    #[test]
+    fn test_output_tail_cuts_long_lines_on_char_boundary() {
+        let line = "é".repeat(100);
+        let tail = output_tail(&line, "", 51);
+
+        assert!(tail.len() <= 51);
+        assert!(tail.chars().all(|c| c == 'é'));
+        assert_eq!(tail.chars().count(), 25);
+    }
+}
@@ -0,0 +1,331 @@
+//! Reboot detection by comparing the running kernel with installed ones
+//!
+//! The distribution tools (`needs-restarting`, `/var/run/reboot-required`)
+//! are not always there or not always told: minimal images lack dnf-utils,
+//! and a kernel installed with plain `dpkg -i` never touches the apt flag
+//! file. As a fallback the running kernel (`uname -r`) is compared with the
+//! newest installed kernel package; if they differ, the host runs an old
+//! kernel and needs a reboot.
+
+use std::cmp::Ordering;
+use std::time::Duration;
+
+use tendhost_exec::traits::RemoteExecutor;
+use tracing::debug;
+
+use crate::error::PackageError;
+
+/// Package database that lists the installed kernels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KernelSource {
+    /// `kernel-core` / `kernel` RPMs (Fedora, RHEL and derivatives)
+    Rpm,
+    /// `linux-image-<version>` packages (Debian, Ubuntu)
+    Dpkg,
+}
+
+impl KernelSource {
+    /// Command printing the installed kernels, one per line
+    fn list_command(self) -> &'static str {
+        match self {
+            Self::Rpm => "rpm -q kernel-core kernel --qf '%{VERSION}-%{RELEASE}.%{ARCH}\\n'",
+            Self::Dpkg => {
+                "dpkg-query -W -f '${db:Status-Abbrev} ${Package}\\n' 'linux-image-[0-9]*'"
+            }
+        }
+    }
+
+    /// Installed kernel versions in `uname -r` form, from the list command
+    #[must_use]
+    pub fn parse_installed(self, output: &str) -> Vec<String> {
+        match self {
+            // `rpm -q` reports "package kernel is not installed" for a
+            // missing name; versions never contain spaces
+            Self::Rpm => output
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.contains(char::is_whitespace))
+                .map(ToString::to_string)
+                .collect(),
+            // Only fully installed packages; removed ones linger as `rc`
+            Self::Dpkg => output
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix("ii "))
+                .filter_map(|package| package.trim().strip_prefix("linux-image-"))
+                .filter(|version| !version.ends_with("-dbg"))
+                .map(ToString::to_string)
+                .collect(),
+        }
+    }
+
+    /// Package name that provides kernel `version`
+    #[must_use]
+    pub fn package_name(self, version: &str) -> String {
+        match self {
+            Self::Rpm => format!("kernel-core-{version}"),
+            Self::Dpkg => format!("linux-image-{version}"),
+        }
+    }
+}
+
+/// Running and newest installed kernel of a host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KernelStatus {
+    /// Output of `uname -r`
+    pub running: String,
+    /// Highest installed kernel version
+    pub newest: String,
+}
+
+impl KernelStatus {
+    /// Compare `running` with the `installed` kernels
+    ///
+    /// Returns `None` when no kernel package is installed, as in containers
+    /// that run the host's kernel; nothing can be concluded then.
+    #[must_use]
+    pub fn new(running: &str, installed: &[String]) -> Option<Self> {
+        let running = running.trim();
+        if running.is_empty() {
+            return None;
+        }
+        let newest = installed
+            .iter()
+            .max_by(|a, b| compare_versions(a, b))?
+            .clone();
+        Some(Self {
+            running: running.to_string(),
+            newest,
+        })
+    }
+
+    /// Whether a newer kernel than the running one is installed
+    #[must_use]
+    pub fn reboot_needed(&self) -> bool {
+        self.running != self.newest
+    }
+}
+
+/// Whether upgrading package `name` usually leaves the host needing a reboot
+///
+/// Kernels, CPU microcode and the core system libraries and daemons only
+/// take effect after one. An architecture suffix such as `:amd64` is
+/// ignored.
+#[must_use]
+pub fn is_reboot_package(name: &str) -> bool {
+    let name = name.split(':').next().unwrap_or(name);
+    name.starts_with("linux-image")
+        || name == "kernel"
+        || name.starts_with("kernel-core")
+        || name.ends_with("-microcode")
+        || name == "microcode_ctl"
+        || matches!(name, "libc6" | "glibc" | "systemd" | "dbus" | "dbus-broker")
+}
+
+/// Order version strings segment by segment, like `rpmvercmp`
+///
+/// Runs of digits compare numerically and runs of letters alphabetically;
+/// a numeric segment is newer than an alphabetic one, and a version with
+/// segments left over is newer than its prefix. Separators such as `.`,
+/// `-` and `_` only delimit segments.
+#[must_use]
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (segments(a), segments(b));
+    loop {
+        match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (Some(_), None) => return Ordering::Greater,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(x), Some(y)) => {
+                let x_numeric = x.starts_with(|c: char| c.is_ascii_digit());
+                let y_numeric = y.starts_with(|c: char| c.is_ascii_digit());
+                let ordering = match (x_numeric, y_numeric) {
+                    (true, true) => {
+                        let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                        x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+                    }
+                    (false, false) => x.cmp(y),
+                    (true, false) => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                };
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+        }
+    }
+}
+
+/// Maximal runs of ASCII digits or of letters in `version`
+fn segments(version: &str) -> impl Iterator<Item = &str> {
+    let mut rest = version;
+    std::iter::from_fn(move || {
+        rest = rest.trim_start_matches(|c: char| !c.is_ascii_alphanumeric());
+        let first = rest.chars().next()?;
+        let end = if first.is_ascii_digit() {
+            rest.find(|c: char| !c.is_ascii_digit())
+        } else {
+            rest.find(|c: char| !c.is_ascii_alphabetic())
+        }
+        .unwrap_or(rest.len());
+        let (segment, tail) = rest.split_at(end);
+        rest = tail;
+        Some(segment)
+    })
+}
+
+/// Read the running and newest installed kernel of the host
+///
+/// Returns `None` if either command fails or no kernel package is
+/// installed, so callers can fall back to "no reboot known to be needed".
+///
+/// # Errors
+/// Returns `PackageError::Timeout` or `PackageError::ExecutionError` if a
+/// command could not be run at all.
+pub async fn kernel_status(
+    executor: &dyn RemoteExecutor,
+    source: KernelSource,
+    timeout: Duration,
+) -> Result<Option<KernelStatus>, PackageError> {
+    let run = |cmd: &'static str| async move {
+        executor
+            .run_with_timeout(cmd, timeout)
+            .await
+            .map_err(|e| PackageError::from_exec("kernel check", e))
+    };
+
+    let running = run("uname -r").await?;
+    if !running.success() {
+        debug!(status = running.status, "uname -r failed");
+        return Ok(None);
+    }
+    let installed = run(source.list_command()).await?;
+    // rpm exits non-zero when one of the names is not installed, but still
+    // prints the others
+    let versions = source.parse_installed(&installed.stdout);
+    let status = KernelStatus::new(&running.stdout, &versions);
+    if status.is_none() {
+        debug!(?source, "no installed kernel package found");
+    }
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::testing::{ScriptedExecutor, output};
+
+    #[test]
+    fn test_is_reboot_package() {
+        for name in [
+            "linux-image-amd64",
+            "linux-image-6.1.0-21-amd64",
+            "kernel-core",
+            "libc6:amd64",
+            "intel-microcode",
+            "systemd",
+        ] {
+            assert!(is_reboot_package(name), "{name}");
+        }
+        for name in [
+            "curl",
+            "kernel-headers",
+            "linux-libc-dev",
+            "systemd-timesyncd",
+        ] {
+            assert!(!is_reboot_package(name), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(
+            compare_versions("6.8.10-300.fc40.x86_64", "6.8.9-300.fc40.x86_64"),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare_versions("6.1.0-9-amd64", "6.1.0-18-amd64"),
+            Ordering::Less
+        );
+        assert_eq!(
+            compare_versions("5.14.0-362.el9", "5.14.0-362.el9"),
+            Ordering::Equal
+        );
+        // Leading zeros don't count; extra segments are newer
+        assert_eq!(compare_versions("1.010", "1.10"), Ordering::Equal);
+        assert_eq!(compare_versions("6.1.0", "6.1"), Ordering::Greater);
+        // Numbers beat letters
+        assert_eq!(compare_versions("1.0.1", "1.0.a"), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_parse_installed_rpm_kernels() {
+        let out =
+            "6.8.9-300.fc40.x86_64\n6.8.10-300.fc40.x86_64\npackage kernel is not installed\n";
+        assert_eq!(
+            KernelSource::Rpm.parse_installed(out),
+            ["6.8.9-300.fc40.x86_64", "6.8.10-300.fc40.x86_64"]
+        );
+    }
+
+    #[test]
+    fn test_parse_installed_dpkg_kernels() {
+        let out = "ii  linux-image-6.1.0-18-amd64\n\
+                   rc  linux-image-6.1.0-9-amd64\n\
+                   ii  linux-image-6.1.0-21-amd64\n\
+                   ii  linux-image-6.1.0-21-amd64-dbg\n";
+        assert_eq!(
+            KernelSource::Dpkg.parse_installed(out),
+            ["6.1.0-18-amd64", "6.1.0-21-amd64"]
+        );
+    }
+
+    #[test]
+    fn test_kernel_status_branches() {
+        let installed = vec!["6.1.0-18-amd64".to_string(), "6.1.0-21-amd64".to_string()];
+
+        // Running an older kernel than the newest installed
+        let old = KernelStatus::new("6.1.0-18-amd64\n", &installed).unwrap();
+        assert_eq!(old.newest, "6.1.0-21-amd64");
+        assert!(old.reboot_needed());
+
+        // Already on the newest
+        let current = KernelStatus::new("6.1.0-21-amd64", &installed).unwrap();
+        assert!(!current.reboot_needed());
+
+        // No kernel packages (containers) or no running kernel: unknown
+        assert_eq!(KernelStatus::new("6.8.12-4-pve", &[]), None);
+        assert_eq!(KernelStatus::new("", &installed), None);
+    }
+
+    #[tokio::test]
+    async fn test_kernel_status_from_host() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![
+            ("uname -r", vec![output(0, "6.8.9-300.fc40.x86_64\n", "")]),
+            (
+                "rpm -q",
+                vec![output(
+                    1,
+                    "package kernel-core is not installed\n6.8.9-300.fc40.x86_64\n6.8.11-300.fc40.x86_64\n",
+                    "",
+                )],
+            ),
+        ]));
+        let status = kernel_status(executor.as_ref(), KernelSource::Rpm, Duration::from_secs(5))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(status.reboot_needed());
+        assert_eq!(
+            KernelSource::Rpm.package_name(&status.newest),
+            "kernel-core-6.8.11-300.fc40.x86_64"
+        );
+
+        let failing = ScriptedExecutor::new(vec![("uname -r", vec![output(127, "", "")])]);
+        let status = kernel_status(&failing, KernelSource::Dpkg, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(status, None);
+    }
+}
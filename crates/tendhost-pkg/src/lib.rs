@@ -7,27 +7,47 @@
 //! ```rust,no_run
 //! use std::sync::Arc;
 //! use tendhost_exec::LocalExecutor;
-//! use tendhost_pkg::{AptManager, PackageManager};
+//! use tendhost_pkg::{AptManager, PackageManager, PrivilegeEscalation};
 //!
 //! # #[tokio::main]
 //! # async fn main() -> Result<(), tendhost_pkg::PackageError> {
 //! let executor = Arc::new(LocalExecutor::new());
-//! let manager = AptManager::new(executor, true);
+//! let manager = AptManager::new(executor, PrivilegeEscalation::Sudo);
 //! let packages = manager.list_upgradable().await?;
 //! # Ok(())
 //! # }
 //! ```
 
 pub mod apt;
+pub mod deferred;
+pub mod detect;
+pub mod disk;
 pub mod dnf;
 pub mod docker;
 pub mod error;
+pub mod kernel;
+mod lists;
+pub mod lock;
+mod osquery;
+
+mod locale;
 pub mod traits;
 pub mod types;
 
+#[cfg(test)]
+mod testing;
+
 pub use apt::AptManager;
+pub use deferred::DeferredPackageManager;
+pub use detect::{detect_distro, distro_from_os_release};
+pub use disk::{DiskCheck, check_disk_space};
 pub use dnf::DnfManager;
-pub use docker::DockerComposeManager;
+pub use docker::{DockerComposeManager, ProjectDirectory};
 pub use error::PackageError;
+pub use lock::{LockHolder, LockWait, retry_while_locked};
+pub use tendhost_exec::escalation::{self, PrivilegeEscalation};
 pub use traits::{PackageManager, PackageManagerExt};
-pub use types::{DistroInfo, PackageManagerType, UpdateResult, UpgradablePackage};
+pub use types::{
+    DEFAULT_METADATA_MAX_AGE, DistroInfo, OperationTimeouts, PackageManagerType, ParseConfidence,
+    PrunePolicy, RestartRequirement, UpdateResult, UpgradablePackage,
+};
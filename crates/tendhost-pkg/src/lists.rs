@@ -0,0 +1,70 @@
+//! Tracking when a manager last refreshed its package lists
+//!
+//! Refreshing (`apt update`, `dnf makecache`) downloads repository metadata
+//! and is by far the slowest part of checking for updates. Lists refreshed
+//! less than the manager's max age ago are reused; an explicit refresh
+//! always runs.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Last successful package list refresh of one manager
+#[derive(Debug)]
+pub(crate) struct ListsRefresh {
+    max_age: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl ListsRefresh {
+    pub(crate) fn new(max_age: Duration) -> Self {
+        Self {
+            max_age,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Whether the lists were refreshed within the max age
+    pub(crate) fn is_fresh(&self) -> bool {
+        self.lock()
+            .is_some_and(|last| last.elapsed() < self.max_age)
+    }
+
+    /// Record a successful refresh
+    pub(crate) fn refreshed(&self) {
+        *self.lock() = Some(Instant::now());
+    }
+
+    /// Forget the last refresh, so the next query refreshes
+    pub(crate) fn invalidate(&self) {
+        *self.lock() = None;
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        // Plain data, still consistent after a panic elsewhere
+        self.last.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lists_expire_after_max_age() {
+        let lists = ListsRefresh::new(Duration::from_secs(60));
+        assert!(!lists.is_fresh());
+        lists.refreshed();
+        assert!(lists.is_fresh());
+        lists.invalidate();
+        assert!(!lists.is_fresh());
+
+        // A zero max age never reuses lists
+        let always = ListsRefresh::new(Duration::ZERO);
+        always.refreshed();
+        assert!(!always.is_fresh());
+    }
+}
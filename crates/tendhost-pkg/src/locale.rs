@@ -0,0 +1,35 @@
+//! Running package managers under the C locale
+//!
+//! Summary lines and error messages are matched as English text, so every
+//! apt and dnf invocation sets `LC_ALL=C` and `LANG=C` instead of
+//! inheriting whatever locale the remote login shell uses.
+
+use tendhost_exec::ShellCommand;
+
+use crate::escalation::PrivilegeEscalation;
+
+/// Variables that force untranslated output
+const C_LOCALE: [(&str, &str); 2] = [("LC_ALL", "C"), ("LANG", "C")];
+
+/// Start a command running `program` under the C locale
+///
+/// `extra` variables are set alongside the locale. Run through
+/// `escalation` they are passed through `env`, because sudo and doas reset
+/// the environment; so are the executor's variables named in `keep`.
+pub(crate) fn command(
+    program: &str,
+    escalation: &PrivilegeEscalation,
+    extra: &[(&str, &str)],
+    keep: &[String],
+) -> ShellCommand {
+    let vars = C_LOCALE.iter().chain(extra).copied();
+    if escalation.is_none() {
+        ShellCommand::with_env(vars, program)
+    } else {
+        escalation
+            .command("env")
+            .args(vars.map(|(name, value)| format!("{name}={value}")))
+            .pass_env(keep)
+            .arg(program)
+    }
+}
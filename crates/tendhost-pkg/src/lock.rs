@@ -0,0 +1,298 @@
+//! Waiting for another package manager to finish
+//!
+//! apt and dnf refuse to start while another process holds their lock,
+//! most often `unattended-upgrades`, `dnf-automatic` or PackageKit running
+//! on a timer. Those finish within minutes, so an update can wait for the
+//! lock instead of failing. Modern apt waits by itself when given
+//! `DPkg::Lock::Timeout` (see [`AptManager::with_lock_timeout`]); for older
+//! apt and for dnf, [`retry_while_locked`] runs the operation again with a
+//! backoff until the wait budget is spent.
+//!
+//! [`AptManager::with_lock_timeout`]: crate::apt::AptManager::with_lock_timeout
+
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use tracing::debug;
+
+use crate::error::PackageError;
+
+/// Default first delay before trying a locked operation again
+pub const DEFAULT_LOCK_INITIAL_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Default longest delay between attempts at a locked operation
+pub const DEFAULT_LOCK_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// The process holding a package manager lock, as far as its message says
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LockHolder {
+    /// Process id
+    pub pid: Option<u32>,
+    /// Process name, e.g. `unattended-upgr` (apt truncates it)
+    pub process: Option<String>,
+}
+
+impl LockHolder {
+    /// Find the holder in a lock error message
+    ///
+    /// Understands apt's "It is held by process 1234 (unattended-upgr)",
+    /// dnf's "Waiting for process with pid 1234 to finish" and yum's
+    /// "The other application is: PackageKit".
+    #[must_use]
+    pub fn parse(message: &str) -> Option<Self> {
+        let mut holder = Self::default();
+        if let Some(rest) = after(message, "held by process ") {
+            holder.pid = leading_number(rest);
+            holder.process = rest
+                .split_once('(')
+                .and_then(|(_, name)| name.split_once(')'))
+                .map(|(name, _)| name.trim().to_string())
+                .filter(|name| !name.is_empty());
+        } else if let Some(rest) = after(message, "process with pid ") {
+            holder.pid = leading_number(rest);
+        } else if let Some(rest) = after(message, "as pid ") {
+            holder.pid = leading_number(rest);
+        }
+        if holder.process.is_none()
+            && let Some(rest) = after(message, "The other application is: ")
+        {
+            holder.process = rest
+                .lines()
+                .next()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty());
+        }
+        (holder.pid.is_some() || holder.process.is_some()).then_some(holder)
+    }
+}
+
+impl fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.pid, &self.process) {
+            (Some(pid), Some(process)) => write!(f, "process {pid} ({process})"),
+            (Some(pid), None) => write!(f, "process {pid}"),
+            (None, Some(process)) => f.write_str(process),
+            (None, None) => f.write_str("another process"),
+        }
+    }
+}
+
+/// The text after the last occurrence of `marker`
+///
+/// The last one is the most recent: apt repeats the message while waiting.
+fn after<'a>(message: &'a str, marker: &str) -> Option<&'a str> {
+    message
+        .rfind(marker)
+        .map(|start| &message[start + marker.len()..])
+}
+
+fn leading_number(text: &str) -> Option<u32> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    text[..end].parse().ok()
+}
+
+/// How long an operation waits for a held lock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockWait {
+    /// Total time spent waiting before giving up; zero fails at once
+    pub budget: Duration,
+    /// Delay before the first retry, doubled after each one
+    pub initial_backoff: Duration,
+    /// Longest delay between retries
+    pub max_backoff: Duration,
+}
+
+impl LockWait {
+    /// Fail as soon as the lock is found held
+    #[must_use]
+    pub fn fail_fast() -> Self {
+        Self::up_to(Duration::ZERO)
+    }
+
+    /// Wait up to `budget`, with the default backoff
+    #[must_use]
+    pub fn up_to(budget: Duration) -> Self {
+        Self {
+            budget,
+            initial_backoff: DEFAULT_LOCK_INITIAL_BACKOFF,
+            max_backoff: DEFAULT_LOCK_MAX_BACKOFF,
+        }
+    }
+
+    /// Set the delays between retries
+    #[must_use]
+    pub fn with_backoff(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_backoff = initial;
+        self.max_backoff = max;
+        self
+    }
+}
+
+impl Default for LockWait {
+    fn default() -> Self {
+        Self::fail_fast()
+    }
+}
+
+/// Run `operation`, trying again while it fails with
+/// `PackageError::LockConflict` and `wait` allows
+///
+/// `on_wait` is called with the holder, if the message names one, and the
+/// time waited so far before each retry.
+///
+/// # Errors
+/// Returns the operation's error. A lock still held when the budget is
+/// spent becomes a `PackageError::LockConflict` naming the holder and the
+/// time waited; with no budget the original error is returned unchanged.
+pub async fn retry_while_locked<T, F, Fut>(
+    wait: &LockWait,
+    mut on_wait: impl FnMut(Option<&LockHolder>, Duration),
+    mut operation: F,
+) -> Result<T, PackageError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, PackageError>>,
+{
+    let started = Instant::now();
+    let mut backoff = wait.initial_backoff;
+    loop {
+        let message = match operation().await {
+            Err(PackageError::LockConflict(message)) => message,
+            other => return other,
+        };
+        if wait.budget.is_zero() {
+            return Err(PackageError::LockConflict(message));
+        }
+
+        let holder = LockHolder::parse(&message);
+        let waited = started.elapsed();
+        if waited >= wait.budget {
+            let holder = holder.map_or_else(|| "another process".to_string(), |h| h.to_string());
+            return Err(PackageError::LockConflict(format!(
+                "still held by {holder} after waiting {}s",
+                waited.as_secs()
+            )));
+        }
+
+        on_wait(holder.as_ref(), waited);
+        let delay = backoff.min(wait.budget - waited);
+        debug!(?delay, ?holder, "package manager lock held, waiting");
+        tokio::time::sleep(delay).await;
+        backoff = (backoff * 2).min(wait.max_backoff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::apt::AptManager;
+    use crate::escalation::PrivilegeEscalation;
+    use crate::testing::{ScriptedExecutor, output};
+    use crate::traits::PackageManager;
+
+    const APT_LOCKED: &str = "E: Could not get lock /var/lib/dpkg/lock-frontend. \
+                              It is held by process 4211 (unattended-upgr)\n\
+                              E: Unable to acquire the dpkg frontend lock";
+
+    fn quick(budget: Duration) -> LockWait {
+        LockWait::up_to(budget).with_backoff(Duration::from_millis(5), Duration::from_millis(20))
+    }
+
+    #[test]
+    fn test_parse_lock_holder() {
+        assert_eq!(
+            LockHolder::parse(APT_LOCKED),
+            Some(LockHolder {
+                pid: Some(4211),
+                process: Some("unattended-upgr".to_string()),
+            })
+        );
+        let dnf = LockHolder::parse("Waiting for process with pid 977 to finish.").unwrap();
+        assert_eq!(dnf.to_string(), "process 977");
+        let yum = LockHolder::parse(
+            "Another app is currently holding the yum lock; waiting for it to exit...\n  \
+             The other application is: PackageKit\n    Memory : 153 M RSS",
+        )
+        .unwrap();
+        assert_eq!(yum.to_string(), "PackageKit");
+
+        // Old apt doesn't say who holds it
+        assert_eq!(
+            LockHolder::parse(
+                "E: Could not get lock /var/lib/dpkg/lock - open (11: Resource temporarily unavailable)"
+            ),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_lock_is_released() {
+        let executor = Arc::new(ScriptedExecutor::new(vec![(
+            "upgrade",
+            vec![
+                output(100, "", APT_LOCKED),
+                output(100, "", APT_LOCKED),
+                output(0, "1 upgraded, 0 newly installed, 0 to remove", ""),
+            ],
+        )]));
+        let manager = AptManager::new(executor.clone(), PrivilegeEscalation::None);
+
+        let mut waits = Vec::new();
+        let result = retry_while_locked(
+            &quick(Duration::from_secs(5)),
+            |holder, _| waits.push(holder.map(ToString::to_string)),
+            || manager.upgrade_all(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(result.upgraded_count, 1);
+        assert_eq!(
+            waits,
+            [
+                Some("process 4211 (unattended-upgr)".to_string()),
+                Some("process 4211 (unattended-upgr)".to_string()),
+            ]
+        );
+        let upgrades = executor
+            .commands
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|cmd| cmd.contains("upgrade"))
+            .count();
+        assert_eq!(upgrades, 3);
+    }
+
+    #[tokio::test]
+    async fn test_spent_budget_names_the_holder() {
+        let locked = || async { Err::<(), _>(PackageError::LockConflict(APT_LOCKED.to_string())) };
+
+        let mut waits = 0;
+        let err = retry_while_locked(&quick(Duration::from_millis(30)), |_, _| waits += 1, locked)
+            .await
+            .unwrap_err();
+        let PackageError::LockConflict(message) = err else {
+            panic!("expected a lock conflict, got {err:?}");
+        };
+        assert!(
+            message.starts_with("still held by process 4211 (unattended-upgr) after waiting"),
+            "{message}"
+        );
+        assert!(waits > 0);
+
+        // Failing fast keeps apt's own message
+        let err = retry_while_locked(&LockWait::fail_fast(), |_, _| panic!("waited"), locked)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("Unable to acquire the dpkg frontend lock")
+        );
+    }
+}
@@ -0,0 +1,25 @@
+//! Installing osquery from its upstream package repositories
+//!
+//! osquery is not packaged by the distributions, so the install adds the
+//! osquery.io repository and signing key first, then installs through the
+//! host's package manager. Each script runs as one root shell that stops
+//! at the first failing line.
+
+/// Adds the osquery apt repository and installs the package
+pub(crate) const APT_INSTALL: &[&str] = &[
+    "mkdir -p /etc/apt/keyrings",
+    "curl -fsSL https://pkg.osquery.io/deb/pubkey.gpg -o /etc/apt/keyrings/osquery.asc",
+    "echo \"deb [arch=$(dpkg --print-architecture) signed-by=/etc/apt/keyrings/osquery.asc] \
+     https://pkg.osquery.io/deb deb main\" > /etc/apt/sources.list.d/osquery.list",
+    "apt-get update",
+    "DEBIAN_FRONTEND=noninteractive apt-get install -y osquery",
+];
+
+/// Adds the osquery rpm repository; the package is installed with dnf or yum
+pub(crate) const RPM_REPO_SETUP: &[&str] = &[
+    "curl -fsSL https://pkg.osquery.io/rpm/GPG -o /etc/pki/rpm-gpg/RPM-GPG-KEY-osquery",
+    "printf '%s\\n' '[osquery-s3-rpm-repo]' 'name=osquery RPM repository - $basearch' \
+     'baseurl=https://s3.amazonaws.com/osquery-packages/rpm/$basearch/' 'enabled=1' \
+     'gpgcheck=1' 'gpgkey=file:///etc/pki/rpm-gpg/RPM-GPG-KEY-osquery' \
+     > /etc/yum.repos.d/osquery-s3-rpm.repo",
+];
@@ -0,0 +1,85 @@
+//! Test doubles shared by the package manager tests
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tendhost_exec::error::ExecError;
+use tendhost_exec::result::CommandResult;
+use tendhost_exec::traits::RemoteExecutor;
+
+/// Executor answering commands from a script
+///
+/// Each entry pairs a command substring with the results to return for
+/// successive matching commands; the last result repeats. Unmatched
+/// commands succeed with no output.
+pub(crate) struct ScriptedExecutor {
+    script: Mutex<Vec<(&'static str, VecDeque<CommandResult>)>>,
+    pub(crate) commands: Mutex<Vec<String>>,
+    env_names: Vec<String>,
+}
+
+impl ScriptedExecutor {
+    pub(crate) fn new(script: Vec<(&'static str, Vec<CommandResult>)>) -> Self {
+        Self {
+            script: Mutex::new(
+                script
+                    .into_iter()
+                    .map(|(pattern, results)| (pattern, results.into()))
+                    .collect(),
+            ),
+            commands: Mutex::new(Vec::new()),
+            env_names: Vec::new(),
+        }
+    }
+
+    /// Report `names` as the variables set for every command
+    pub(crate) fn with_env_names(mut self, names: &[&str]) -> Self {
+        self.env_names = names.iter().map(ToString::to_string).collect();
+        self
+    }
+}
+
+pub(crate) fn output(status: i32, stdout: &str, stderr: &str) -> CommandResult {
+    CommandResult {
+        status,
+        stdout: stdout.to_string(),
+        stderr: stderr.to_string(),
+        duration: Duration::from_millis(1),
+    }
+}
+
+#[async_trait]
+impl RemoteExecutor for ScriptedExecutor {
+    async fn run(&self, cmd: &str) -> Result<CommandResult, ExecError> {
+        self.commands.lock().unwrap().push(cmd.to_string());
+        let mut script = self.script.lock().unwrap();
+        let Some((_, results)) = script.iter_mut().find(|(pattern, _)| cmd.contains(pattern))
+        else {
+            return Ok(output(0, "", ""));
+        };
+        let result = if results.len() > 1 {
+            results.pop_front()
+        } else {
+            results.front().cloned()
+        };
+        Ok(result.unwrap_or_else(|| output(0, "", "")))
+    }
+
+    async fn run_with_timeout(
+        &self,
+        cmd: &str,
+        _timeout: Duration,
+    ) -> Result<CommandResult, ExecError> {
+        self.run(cmd).await
+    }
+
+    fn env_names(&self) -> Vec<String> {
+        self.env_names.clone()
+    }
+
+    fn executor_type(&self) -> &'static str {
+        "scripted"
+    }
+}
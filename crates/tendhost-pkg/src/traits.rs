@@ -3,7 +3,7 @@
 use async_trait::async_trait;
 
 use crate::error::PackageError;
-use crate::types::{UpdateResult, UpgradablePackage};
+use crate::types::{RestartRequirement, UpdateResult, UpgradablePackage};
 
 /// Trait for package management operations
 ///
@@ -12,11 +12,27 @@ use crate::types::{UpdateResult, UpgradablePackage};
 pub trait PackageManager: Send + Sync {
     /// List packages with available upgrades
     ///
+    /// Managers with package lists refresh them first unless they were
+    /// refreshed within the manager's metadata max age.
+    ///
     /// # Returns
     /// * `Ok(Vec<UpgradablePackage>)` - List of upgradable packages
     /// * `Err(PackageError)` - Failed to query packages
     async fn list_upgradable(&self) -> Result<Vec<UpgradablePackage>, PackageError>;
 
+    /// Download fresh package lists (`apt update`, `dnf makecache`)
+    ///
+    /// Runs even if the lists are recent; the next
+    /// [`list_upgradable`](Self::list_upgradable) then reuses them. Managers
+    /// without package lists do nothing.
+    ///
+    /// # Returns
+    /// * `Ok(())` - Lists refreshed
+    /// * `Err(PackageError)` - A repository could not be reached
+    async fn update_package_lists(&self) -> Result<(), PackageError> {
+        Ok(())
+    }
+
     /// Upgrade all packages
     ///
     /// # Returns
@@ -33,6 +49,81 @@ pub trait PackageManager: Send + Sync {
     /// * `Err(PackageError)` - Failed to simulate
     async fn upgrade_dry_run(&self) -> Result<UpdateResult, PackageError>;
 
+    /// Upgrade only packages with security fixes
+    ///
+    /// # Returns
+    /// * `Ok(UpdateResult)` - Update completed successfully
+    /// * `Err(PackageError)` - Update failed or security classification is unsupported
+    async fn upgrade_security(&self) -> Result<UpdateResult, PackageError> {
+        Err(PackageError::Unsupported(format!(
+            "{} cannot restrict upgrades to security fixes",
+            self.manager_type()
+        )))
+    }
+
+    /// Simulate a security-only upgrade (dry run)
+    ///
+    /// # Returns
+    /// * `Ok(UpdateResult)` - Simulated update result
+    /// * `Err(PackageError)` - Failed to simulate or security classification is unsupported
+    async fn upgrade_security_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        Err(PackageError::Unsupported(format!(
+            "{} cannot restrict upgrades to security fixes",
+            self.manager_type()
+        )))
+    }
+
+    /// Upgrade only the named packages
+    ///
+    /// Named packages that are not installed stay uninstalled.
+    ///
+    /// # Returns
+    /// * `Ok(UpdateResult)` - Update completed successfully
+    /// * `Err(PackageError)` - Update failed or selecting packages is unsupported
+    async fn upgrade_packages(&self, packages: &[String]) -> Result<UpdateResult, PackageError> {
+        Err(PackageError::Unsupported(format!(
+            "{} cannot upgrade selected packages ({} requested)",
+            self.manager_type(),
+            packages.len()
+        )))
+    }
+
+    /// Simulate upgrading only the named packages (dry run)
+    ///
+    /// # Returns
+    /// * `Ok(UpdateResult)` - Simulated update result
+    /// * `Err(PackageError)` - Failed to simulate or selecting packages is unsupported
+    async fn upgrade_packages_dry_run(
+        &self,
+        packages: &[String],
+    ) -> Result<UpdateResult, PackageError> {
+        Err(PackageError::Unsupported(format!(
+            "{} cannot upgrade selected packages ({} requested)",
+            self.manager_type(),
+            packages.len()
+        )))
+    }
+
+    /// List independently updatable stacks (e.g. compose projects)
+    ///
+    /// Managers without stacks return an empty list.
+    async fn list_stacks(&self) -> Result<Vec<String>, PackageError> {
+        Ok(Vec::new())
+    }
+
+    /// Upgrade a single stack returned by [`list_stacks`](Self::list_stacks)
+    ///
+    /// # Returns
+    /// * `Ok(UpdateResult)` - Update completed
+    /// * `Err(PackageError::UnknownStack)` - No stack with this name
+    /// * `Err(PackageError)` - Update failed or stacks are unsupported
+    async fn upgrade_stack(&self, stack: &str) -> Result<UpdateResult, PackageError> {
+        Err(PackageError::Unsupported(format!(
+            "{} does not manage stacks (requested '{stack}')",
+            self.manager_type()
+        )))
+    }
+
     /// Check if reboot is required after updates
     ///
     /// # Returns
@@ -41,9 +132,100 @@ pub trait PackageManager: Send + Sync {
     /// * `Err(PackageError)` - Failed to check
     async fn reboot_required(&self) -> Result<bool, PackageError>;
 
+    /// Check what has to be restarted after updates
+    ///
+    /// Distinguishes a kernel-level reboot from services that only need a
+    /// restart. The default only knows about reboots, via
+    /// [`reboot_required`](Self::reboot_required).
+    ///
+    /// # Returns
+    /// * `Ok(RestartRequirement)` - Reboot flag, services and triggering packages
+    /// * `Err(PackageError)` - Failed to check
+    async fn restart_requirement(&self) -> Result<RestartRequirement, PackageError> {
+        Ok(RestartRequirement {
+            reboot_needed: self.reboot_required().await?,
+            ..RestartRequirement::default()
+        })
+    }
+
+    /// Terminate a running upgrade on the target system
+    ///
+    /// Best-effort cleanup after the local upgrade task has been aborted.
+    /// Finding no matching process is not an error.
+    ///
+    /// # Returns
+    /// * `Ok(())` - No upgrade process is left running
+    /// * `Err(PackageError)` - Failed to signal the upgrade process
+    async fn cancel_upgrade(&self) -> Result<(), PackageError> {
+        Ok(())
+    }
+
+    /// Remove packages installed as dependencies that nothing needs
+    /// anymore, such as old kernels
+    ///
+    /// Managers without such a command remove nothing.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<String>)` - Names of the removed packages
+    /// * `Err(PackageError)` - The removal failed
+    async fn autoremove(&self) -> Result<Vec<String>, PackageError> {
+        Ok(Vec::new())
+    }
+
+    /// Delete downloaded package files from the package cache
+    ///
+    /// Managers without a cache do nothing.
+    ///
+    /// # Returns
+    /// * `Ok(())` - The cache is empty
+    /// * `Err(PackageError)` - Cleaning failed
+    async fn clean_cache(&self) -> Result<(), PackageError> {
+        Ok(())
+    }
+
+    /// Install osquery from its upstream package repository
+    ///
+    /// # Returns
+    /// * `Ok(String)` - Output of the installation
+    /// * `Err(PackageError::Unsupported)` - This manager can't install it
+    /// * `Err(PackageError)` - Installation failed
+    async fn install_osquery(&self) -> Result<String, PackageError> {
+        Err(PackageError::Unsupported(format!(
+            "{} cannot install osquery",
+            self.manager_type()
+        )))
+    }
+
+    /// Finish setting the manager up, e.g. detecting the distribution
+    ///
+    /// Most managers are ready when constructed and do nothing; a
+    /// [`DeferredPackageManager`](crate::deferred::DeferredPackageManager)
+    /// detects the real manager here. Call it before relying on
+    /// [`escalation`](Self::escalation) or [`distro`](Self::distro).
+    ///
+    /// # Returns
+    /// * `Ok(())` - The manager is ready
+    /// * `Err(PackageError)` - Setup failed; the next call tries again
+    async fn prepare(&self) -> Result<(), PackageError> {
+        Ok(())
+    }
+
+    /// How commands that need root are run
+    ///
+    /// Hosts check that the escalation command works without a password
+    /// before updating through a manager that uses one.
+    fn escalation(&self) -> crate::escalation::PrivilegeEscalation {
+        crate::escalation::PrivilegeEscalation::None
+    }
+
     /// Get package manager type
     fn manager_type(&self) -> crate::types::PackageManagerType;
 
+    /// Distribution this manager was selected for, if it was detected
+    fn distro(&self) -> Option<&crate::types::DistroInfo> {
+        None
+    }
+
     /// Check if package manager is available on the system
     async fn is_available(&self) -> bool;
 }
@@ -62,15 +244,6 @@ pub trait PackageManagerExt: PackageManager {
         let packages = self.list_upgradable().await?;
         u32::try_from(packages.len()).map_err(|e| PackageError::ParseError(e.to_string()))
     }
-
-    /// Update package lists (apt update, dnf makecache)
-    async fn update_package_lists(&self) -> Result<(), PackageError>;
 }
 
-#[async_trait]
-impl<T: PackageManager> PackageManagerExt for T {
-    async fn update_package_lists(&self) -> Result<(), PackageError> {
-        // Default: no-op (override in implementations)
-        Ok(())
-    }
-}
+impl<T: PackageManager> PackageManagerExt for T {}
@@ -1,7 +1,47 @@
 //! Type definitions for package management
 
+use std::time::Duration;
+
 use serde::{Deserialize, Serialize};
 
+/// Timeouts applied to package manager commands
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OperationTimeouts {
+    /// Refreshing package lists (`apt update`, `dnf check-update`)
+    pub update_lists: Duration,
+    /// Applying upgrades and pulling images
+    pub upgrade: Duration,
+    /// Read-only queries and dry runs
+    pub query: Duration,
+}
+
+impl Default for OperationTimeouts {
+    fn default() -> Self {
+        Self {
+            update_lists: Duration::from_secs(5 * 60),
+            upgrade: Duration::from_secs(30 * 60),
+            query: Duration::from_secs(2 * 60),
+        }
+    }
+}
+
+/// How long refreshed package lists are reused before checking for updates
+/// downloads them again
+pub const DEFAULT_METADATA_MAX_AGE: Duration = Duration::from_secs(15 * 60);
+
+/// Docker image cleanup after a successful compose update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrunePolicy {
+    /// Keep all images
+    #[default]
+    Off,
+    /// Remove untagged images left behind by pulls (`docker image prune`)
+    Dangling,
+    /// Remove every image no container uses that was created at least this
+    /// long ago; zero removes all unused images
+    UnusedOlderThan(Duration),
+}
+
 /// A package with available updates
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpgradablePackage {
@@ -15,6 +55,9 @@ pub struct UpgradablePackage {
     pub arch: Option<String>,
     /// Package repository
     pub repository: Option<String>,
+    /// Whether the upgrade comes from a security repository or advisory
+    #[serde(default)]
+    pub security: bool,
 }
 
 impl UpgradablePackage {
@@ -30,6 +73,7 @@ impl UpgradablePackage {
             new_version: new.into(),
             arch: None,
             repository: None,
+            security: false,
         }
     }
 
@@ -46,6 +90,43 @@ impl UpgradablePackage {
         self.repository = Some(repo.into());
         self
     }
+
+    /// Mark as security update
+    #[must_use]
+    pub fn with_security(mut self, security: bool) -> Self {
+        self.security = security;
+        self
+    }
+}
+
+/// What has to be restarted for updates to take effect
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RestartRequirement {
+    /// Kernel or core libraries were updated; only a reboot applies them
+    pub reboot_needed: bool,
+    /// Services still running outdated binaries or libraries
+    #[serde(default)]
+    pub services_needing_restart: Vec<String>,
+    /// Packages that caused the requirement, if the system reports them
+    #[serde(default)]
+    pub triggered_by: Vec<String>,
+}
+
+impl RestartRequirement {
+    /// Requirement for a reboot without further details
+    #[must_use]
+    pub fn reboot() -> Self {
+        Self {
+            reboot_needed: true,
+            ..Self::default()
+        }
+    }
+
+    /// Whether nothing needs restarting
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        !self.reboot_needed && self.services_needing_restart.is_empty()
+    }
 }
 
 /// Result of an update operation
@@ -59,12 +140,41 @@ pub struct UpdateResult {
     pub new_count: u32,
     /// Number of packages removed
     pub removed_count: u32,
-    /// Whether a reboot is required
+    /// Whether a reboot is required (same as `restart.reboot_needed`)
     pub reboot_required: bool,
+    /// Reboot and service restarts needed after the update
+    #[serde(default)]
+    pub restart: RestartRequirement,
     /// List of upgraded packages
     pub upgraded_packages: Vec<String>,
     /// Error message if failed
     pub error: Option<String>,
+    /// Disk space freed by pruning images after the update, if it ran
+    #[serde(default)]
+    pub reclaimed_bytes: Option<u64>,
+    /// Size of the archives still to download, if the package manager said
+    #[serde(default)]
+    pub download_bytes: Option<u64>,
+    /// Packages removed by the autoremove after the update; also counted
+    /// in `removed_count`
+    #[serde(default)]
+    pub autoremoved_packages: Vec<String>,
+    /// How the counts were read from the package manager's output
+    #[serde(default)]
+    pub parse_confidence: ParseConfidence,
+}
+
+/// How the counts in an [`UpdateResult`] were obtained
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParseConfidence {
+    /// Read from the package manager's own summary
+    #[default]
+    Summary,
+    /// Counted from per-package progress lines because the summary was missing
+    Estimated,
+    /// Nothing in the output was recognised, so the counts are zero
+    Unknown,
 }
 
 impl UpdateResult {
@@ -77,8 +187,13 @@ impl UpdateResult {
             new_count: 0,
             removed_count: 0,
             reboot_required: false,
+            restart: RestartRequirement::default(),
             upgraded_packages: Vec::new(),
             error: None,
+            reclaimed_bytes: None,
+            download_bytes: None,
+            autoremoved_packages: Vec::new(),
+            parse_confidence: ParseConfidence::Summary,
         }
     }
 
@@ -90,8 +205,13 @@ impl UpdateResult {
             new_count: 0,
             removed_count: 0,
             reboot_required: false,
+            restart: RestartRequirement::default(),
             upgraded_packages: Vec::new(),
             error: Some(error.into()),
+            reclaimed_bytes: None,
+            download_bytes: None,
+            autoremoved_packages: Vec::new(),
+            parse_confidence: ParseConfidence::Summary,
         }
     }
 
@@ -106,6 +226,15 @@ impl UpdateResult {
     #[must_use]
     pub fn with_reboot(mut self) -> Self {
         self.reboot_required = true;
+        self.restart.reboot_needed = true;
+        self
+    }
+
+    /// Set the restart requirement, keeping `reboot_required` in sync
+    #[must_use]
+    pub fn with_restart(mut self, restart: RestartRequirement) -> Self {
+        self.reboot_required = restart.reboot_needed;
+        self.restart = restart;
         self
     }
 }
@@ -120,6 +249,9 @@ pub enum PackageManagerType {
     Dnf,
     /// Docker Compose
     DockerCompose,
+    /// Not detected yet, see
+    /// [`DeferredPackageManager`](crate::deferred::DeferredPackageManager)
+    Unknown,
 }
 
 impl std::fmt::Display for PackageManagerType {
@@ -128,6 +260,7 @@ impl std::fmt::Display for PackageManagerType {
             PackageManagerType::Apt => write!(f, "apt"),
             PackageManagerType::Dnf => write!(f, "dnf"),
             PackageManagerType::DockerCompose => write!(f, "docker-compose"),
+            PackageManagerType::Unknown => write!(f, "unknown"),
         }
     }
 }
@@ -144,3 +277,18 @@ pub struct DistroInfo {
     /// Package manager type
     pub package_manager: PackageManagerType,
 }
+
+impl std::fmt::Display for DistroInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = if self.name.is_empty() {
+            &self.id
+        } else {
+            &self.name
+        };
+        if self.version_id.is_empty() {
+            write!(f, "{name}")
+        } else {
+            write!(f, "{name} {}", self.version_id)
+        }
+    }
+}
@@ -1,5 +1,7 @@
 //! User actions for the TUI application
 
+use crate::ui::input::InputEdit;
+
 /// Actions that can be performed in the application
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
@@ -26,24 +28,54 @@ pub enum Action {
     Help,
     /// Trigger update on selected host
     TriggerUpdate,
+    /// Preview an update of the selected host without changing it
+    PreviewUpdate,
     /// Trigger fleet update
     TriggerFleetUpdate,
     /// Trigger reboot on selected host
     TriggerReboot,
+    /// Cancel running update on selected host (asks for confirmation)
+    CancelUpdate,
+    /// Confirm the pending confirmation prompt
+    Confirm,
     /// Retry failed host
     RetryHost,
     /// Acknowledge failure
     AcknowledgeFailure,
-    /// Refresh host inventory
+    /// Pause the selected host, or resume it if paused
+    TogglePause,
+    /// Edit the tags of the selected host
+    EditTags,
+    /// Open the package picker for the selected host
+    PickPackages,
+    /// Check or uncheck the package under the cursor
+    ToggleItem,
+    /// Check every package in the picker
+    SelectAll,
+    /// Uncheck every package in the picker
+    SelectNone,
+    /// Submit the open tag editor
+    Submit,
+    /// Open (or refresh) the inventory view for the selected host
     RefreshInventory,
+    /// Show the next inventory section
+    NextSection,
+    /// Show the previous inventory section
+    PrevSection,
+    /// Sort the host list by the next column
+    CycleSort,
+    /// Reverse the host list order
+    ReverseSort,
+    /// Mark or unmark the selected host for a bulk action
+    ToggleMark,
+    /// Start range selection at the selected host, or end it
+    RangeSelect,
     /// Toggle focus between panels
     ToggleFocus,
     /// Start search mode
     StartSearch,
-    /// Update search query
-    SearchInput(char),
-    /// Backspace in search
-    SearchBackspace,
+    /// Edit the focused text input (search, filter, or tag editor)
+    Input(InputEdit),
     /// Clear search
     ClearSearch,
     /// WebSocket event received
@@ -58,6 +90,15 @@ pub enum Action {
     HostsLoaded(Vec<serde_json::Value>),
     /// Host details loaded
     HostDetailsLoaded(String, serde_json::Value),
+    /// Inventory fetch for a host finished
+    InventoryLoaded(
+        String,
+        Result<tendhost_api::responses::HostInventoryResponse, String>,
+    ),
+    /// Details refetched for the host in the package picker
+    PickerDetailsLoaded(Box<tendhost_api::responses::HostDetail>),
+    /// Fleet summary for the status bar loaded
+    FleetSummaryLoaded(tendhost_api::responses::FleetSummary),
     /// No operation
     None,
 }
@@ -1,13 +1,29 @@
 //! Application state and logic
 
-use std::collections::VecDeque;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Local, Utc};
 use color_eyre::Result;
-use tendhost_api::events::WsEvent;
-use tendhost_client::{HttpClient, WsClient};
+use tendhost_api::events::{EventEnvelope, WsEvent};
+use tendhost_api::responses::{
+    FleetSummary, HostDetail, HostInventoryResponse, HostSummary, UpdateHistoryEntry,
+    UpdatePreview, UpgradablePackageInfo,
+};
+use tendhost_client::{
+    ClientError, HostListQuery, HttpClient, TendhostApi, VersionCheck, WsClient,
+};
+use tokio::sync::mpsc;
 
 use crate::action::Action;
+use crate::event::InputMode;
+use crate::keymap::{Command, KeyMap};
+use crate::ui::checklist::Checklist;
+use crate::ui::format_bytes;
+use crate::ui::input::TextInput;
+use crate::ui::toast::Toasts;
 
 /// UI focus state
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
@@ -44,6 +60,391 @@ pub enum EventLevel {
     Error,
 }
 
+/// Destructive action waiting for the user to confirm
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingConfirm {
+    /// Cancel the running update on a host
+    CancelUpdate { host: String },
+    /// Reboot every marked host
+    RebootHosts { hosts: Vec<String> },
+}
+
+impl PendingConfirm {
+    /// Question shown in the confirmation popup
+    pub fn prompt(&self) -> String {
+        match self {
+            Self::CancelUpdate { host } => format!("Cancel the running update on {host}?"),
+            Self::RebootHosts { hosts } => {
+                format!("Reboot {} hosts: {}?", hosts.len(), hosts.join(", "))
+            }
+        }
+    }
+}
+
+/// Operation a bulk run applies to each marked host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkAction {
+    Update,
+    Reboot,
+    Retry,
+    Acknowledge,
+}
+
+impl BulkAction {
+    /// Name of the operation, e.g. `update`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Update => "update",
+            Self::Reboot => "reboot",
+            Self::Retry => "retry",
+            Self::Acknowledge => "acknowledge",
+        }
+    }
+
+    /// What happened to a host it succeeded on, e.g. `updated`
+    fn done(self) -> &'static str {
+        match self {
+            Self::Update => "updated",
+            Self::Reboot => "rebooted",
+            Self::Retry => "retried",
+            Self::Acknowledge => "acknowledged",
+        }
+    }
+}
+
+/// Bulk action working through the marked hosts one request at a time
+///
+/// The main loop advances it between key presses, so it can be cancelled
+/// with the hosts not contacted yet left alone.
+#[derive(Debug, Clone)]
+pub struct BulkRun {
+    pub action: BulkAction,
+    /// Hosts not contacted yet, in order
+    pending: VecDeque<String>,
+    pub total: usize,
+    succeeded: usize,
+    /// Hosts the request failed on, with a short reason each
+    failed: Vec<(String, String)>,
+}
+
+impl BulkRun {
+    fn new(action: BulkAction, hosts: Vec<String>) -> Self {
+        Self {
+            action,
+            total: hosts.len(),
+            pending: hosts.into(),
+            succeeded: 0,
+            failed: Vec::new(),
+        }
+    }
+
+    /// Hosts contacted so far
+    pub fn done(&self) -> usize {
+        self.total - self.pending.len()
+    }
+
+    /// Outcome such as `updated 4, failed 1: web-3 busy`
+    fn summary(&self) -> String {
+        let mut summary = format!("{} {}", self.action.done(), self.succeeded);
+        if !self.failed.is_empty() {
+            let reasons: Vec<String> = self
+                .failed
+                .iter()
+                .map(|(host, reason)| format!("{host} {reason}"))
+                .collect();
+            summary.push_str(&format!(
+                ", failed {}: {}",
+                self.failed.len(),
+                reasons.join(", ")
+            ));
+        }
+        if !self.pending.is_empty() {
+            summary.push_str(&format!(" (cancelled, {} not run)", self.pending.len()));
+        }
+        summary
+    }
+
+    /// Level of the summary toast
+    fn level(&self) -> EventLevel {
+        if self.failed.is_empty() && self.pending.is_empty() {
+            EventLevel::Success
+        } else if self.succeeded == 0 && !self.failed.is_empty() {
+            EventLevel::Error
+        } else {
+            EventLevel::Warning
+        }
+    }
+}
+
+/// Range selection started with the range key
+#[derive(Debug, Clone)]
+struct RangeSelect {
+    /// Host the range started on
+    anchor: String,
+    /// Marks from before the range started
+    base: BTreeSet<String>,
+}
+
+/// Tag editor popup for one host
+#[derive(Debug, Clone)]
+pub struct TagEditor {
+    pub host: String,
+    /// Comma-separated tags
+    pub input: TextInput,
+}
+
+/// Split tags at commas and whitespace, which tags never contain,
+/// dropping empty and duplicate entries while keeping the original order
+fn parse_tags(input: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in input.split(|c: char| c == ',' || c.is_whitespace()) {
+        if !tag.is_empty() && !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// Package picker popup for one host
+///
+/// Lists the host's upgradable packages for a selective update. The
+/// selection is kept while the pending set stays the same.
+#[derive(Debug, Clone)]
+pub struct PackagePicker {
+    pub host: String,
+    packages: Vec<UpgradablePackageInfo>,
+    pub list: Checklist,
+}
+
+impl PackagePicker {
+    fn new(host: String, packages: Vec<UpgradablePackageInfo>) -> Self {
+        let labels = packages
+            .iter()
+            .map(|pkg| {
+                let security = if pkg.security { " [security]" } else { "" };
+                format!(
+                    "{} ({} → {}){security}",
+                    pkg.name, pkg.current_version, pkg.new_version
+                )
+            })
+            .collect();
+        Self {
+            host,
+            packages,
+            list: Checklist::new(labels),
+        }
+    }
+
+    /// Show `packages`, starting over with nothing checked if they differ
+    /// from the listed ones
+    fn sync(&mut self, packages: &[UpgradablePackageInfo]) {
+        if self.packages != packages {
+            *self = Self::new(std::mem::take(&mut self.host), packages.to_vec());
+        }
+    }
+
+    /// Names of the checked packages
+    pub fn selected(&self) -> Vec<String> {
+        self.list
+            .checked()
+            .map(|i| self.packages[i].name.clone())
+            .collect()
+    }
+
+    /// Apply a navigation or selection action; returns whether it was consumed
+    fn handle(&mut self, action: &Action) -> bool {
+        match action {
+            Action::Up => self.list.up(),
+            Action::Down => self.list.down(),
+            Action::First => self.list.first(),
+            Action::Last => self.list.last(),
+            Action::ToggleItem => self.list.toggle(),
+            Action::SelectAll => self.list.set_all(true),
+            Action::SelectNone => self.list.set_all(false),
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Section of the inventory view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InventorySection {
+    #[default]
+    System,
+    Disks,
+    Network,
+    Containers,
+    Packages,
+}
+
+impl InventorySection {
+    /// All sections in tab order
+    pub const ALL: [Self; 5] = [
+        Self::System,
+        Self::Disks,
+        Self::Network,
+        Self::Containers,
+        Self::Packages,
+    ];
+
+    /// Tab title
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::System => "System",
+            Self::Disks => "Disks",
+            Self::Network => "Network",
+            Self::Containers => "Containers",
+            Self::Packages => "Packages",
+        }
+    }
+
+    /// Position in [`InventorySection::ALL`]
+    pub fn index(self) -> usize {
+        Self::ALL.iter().position(|s| *s == self).unwrap_or(0)
+    }
+
+    fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    fn prev(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// Inventory fetch state
+#[derive(Debug, Clone)]
+pub enum InventoryData {
+    /// Request in flight
+    Loading,
+    /// Inventory response
+    Loaded(HostInventoryResponse),
+    /// Fetch failed with this message
+    Failed(String),
+}
+
+/// Inventory screen for one host
+///
+/// Kept in [`App`] so section, scroll position, and filter survive re-renders
+/// and refreshes.
+#[derive(Debug, Clone)]
+pub struct InventoryView {
+    pub host: String,
+    pub data: InventoryData,
+    pub section: InventorySection,
+    /// Index of the first visible row in the current section
+    pub scroll: usize,
+    /// Package name filter
+    pub filter: TextInput,
+    /// Filter input has focus
+    pub filter_active: bool,
+}
+
+impl InventoryView {
+    fn new(host: String) -> Self {
+        Self {
+            host,
+            data: InventoryData::Loading,
+            section: InventorySection::default(),
+            scroll: 0,
+            filter: TextInput::default(),
+            filter_active: false,
+        }
+    }
+
+    /// The `inventory` object of a loaded response
+    pub fn inventory(&self) -> Option<&serde_json::Value> {
+        match &self.data {
+            InventoryData::Loaded(response) => Some(&response.inventory),
+            _ => None,
+        }
+    }
+
+    fn array_at(&self, path: &[&str]) -> &[serde_json::Value] {
+        path.iter()
+            .try_fold(self.inventory(), |value, key| value.map(|v| v.get(key)))
+            .flatten()
+            .and_then(serde_json::Value::as_array)
+            .map_or(&[], Vec::as_slice)
+    }
+
+    pub fn disks(&self) -> &[serde_json::Value] {
+        self.array_at(&["hardware", "disks"])
+    }
+
+    pub fn interfaces(&self) -> &[serde_json::Value] {
+        self.array_at(&["hardware", "network_interfaces"])
+    }
+
+    pub fn containers(&self) -> &[serde_json::Value] {
+        self.array_at(&["docker_containers"])
+    }
+
+    pub fn packages(&self) -> &[serde_json::Value] {
+        self.array_at(&["packages"])
+    }
+
+    /// Packages whose name contains the filter (case-insensitive)
+    pub fn filtered_packages(&self) -> Vec<&serde_json::Value> {
+        let filter = self.filter.value().to_lowercase();
+        self.packages()
+            .iter()
+            .filter(|p| {
+                filter.is_empty()
+                    || p.get("name")
+                        .and_then(|v| v.as_str())
+                        .is_some_and(|name| name.to_lowercase().contains(&filter))
+            })
+            .collect()
+    }
+
+    /// Number of scrollable rows in the current section
+    fn row_count(&self) -> usize {
+        match self.section {
+            // Fits on screen, never scrolls
+            InventorySection::System => 0,
+            InventorySection::Disks => self.disks().len(),
+            InventorySection::Network => self.interfaces().len(),
+            InventorySection::Containers => self.containers().len(),
+            InventorySection::Packages => self.filtered_packages().len(),
+        }
+    }
+
+    fn set_section(&mut self, section: InventorySection) {
+        self.section = section;
+        self.scroll = 0;
+    }
+
+    /// Apply a navigation or filter action; returns whether it was consumed
+    fn handle(&mut self, action: &Action) -> bool {
+        match action {
+            Action::Up => self.scroll = self.scroll.saturating_sub(1),
+            Action::Down => {
+                if self.scroll + 1 < self.row_count() {
+                    self.scroll += 1;
+                }
+            }
+            Action::First => self.scroll = 0,
+            Action::Last => self.scroll = self.row_count().saturating_sub(1),
+            Action::NextSection => self.set_section(self.section.next()),
+            Action::PrevSection => self.set_section(self.section.prev()),
+            Action::StartSearch => {
+                self.set_section(InventorySection::Packages);
+                self.filter_active = true;
+            }
+            Action::Input(edit) if self.filter_active => {
+                if self.filter.apply(*edit) {
+                    self.scroll = 0;
+                }
+            }
+            Action::Back if self.filter_active => self.filter_active = false,
+            _ => return false,
+        }
+        true
+    }
+}
+
 /// Host display data
 #[derive(Debug, Clone, Default)]
 #[allow(dead_code)]
@@ -53,15 +454,191 @@ pub struct HostDisplay {
     pub os: String,
     pub packages: Option<u32>,
     pub last_updated: Option<DateTime<Utc>>,
+    pub unreachable: bool,
+    /// Updates need sudo, but it asks the SSH user for a password
+    pub no_sudo: bool,
+    /// Failure has been acknowledged by an operator
+    pub acknowledged: bool,
+    /// Automation leaves the host alone
+    pub paused: bool,
+    pub tags: Vec<String>,
+}
+
+impl From<HostSummary> for HostDisplay {
+    fn from(h: HostSummary) -> Self {
+        Self {
+            name: h.name,
+            state: h.state,
+            os: h.os.unwrap_or_default(),
+            packages: h.pending_updates,
+            last_updated: h.last_updated,
+            unreachable: !h.reachable,
+            no_sudo: h.sudo_available == Some(false),
+            acknowledged: h.acknowledged.unwrap_or(false),
+            paused: h.paused,
+            tags: h.tags,
+        }
+    }
+}
+
+impl HostDisplay {
+    /// Whether the host is in the failed state
+    pub fn is_failed(&self) -> bool {
+        self.state.eq_ignore_ascii_case("failed")
+    }
+}
+
+/// States in the order they need attention, with their summary labels
+///
+/// The REST API spells states like `PendingUpdates` and events like
+/// `pending_updates`; both are matched lowercased without underscores.
+const STATE_ORDER: [(&str, &str); 9] = [
+    ("failed", "failed"),
+    ("offline", "offline"),
+    ("waitingreboot", "waiting reboot"),
+    ("pendingupdates", "pending"),
+    ("updating", "updating"),
+    ("rebooting", "rebooting"),
+    ("verifying", "verifying"),
+    ("querying", "querying"),
+    ("idle", "idle"),
+];
+
+/// Position of `state` in [`STATE_ORDER`]; unknown states come last
+fn state_rank(state: &str) -> usize {
+    let key = state.to_lowercase().replace('_', "");
+    STATE_ORDER
+        .iter()
+        .position(|(k, _)| *k == key)
+        .unwrap_or(STATE_ORDER.len())
+}
+
+/// Host list column the table is sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortColumn {
+    #[default]
+    Name,
+    /// Hosts needing attention first
+    State,
+    /// Fewest pending packages first; unknown counts before zero
+    Packages,
+    /// Least recently updated first; never-updated hosts before any
+    LastUpdate,
+}
+
+impl SortColumn {
+    fn next(self) -> Self {
+        match self {
+            Self::Name => Self::State,
+            Self::State => Self::Packages,
+            Self::Packages => Self::LastUpdate,
+            Self::LastUpdate => Self::Name,
+        }
+    }
+}
+
+/// How the host list is ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HostSort {
+    pub column: SortColumn,
+    pub descending: bool,
+}
+
+impl HostSort {
+    /// Order two hosts; ties are broken by name so the order is stable
+    pub fn compare(self, a: &HostDisplay, b: &HostDisplay) -> Ordering {
+        let by_column = match self.column {
+            SortColumn::Name => Ordering::Equal,
+            SortColumn::State => state_rank(&a.state).cmp(&state_rank(&b.state)),
+            SortColumn::Packages => a.packages.cmp(&b.packages),
+            SortColumn::LastUpdate => a.last_updated.cmp(&b.last_updated),
+        };
+        let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase());
+        let ordering = by_column.then_with(by_name);
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    /// Arrow shown next to the sorted column
+    pub fn arrow(self) -> &'static str {
+        if self.descending { "▼" } else { "▲" }
+    }
+}
+
+/// Per-state host counts in attention order, e.g. `1 failed / 22 idle`
+pub fn state_summary(hosts: &[&HostDisplay]) -> String {
+    let mut counts: Vec<(usize, String, usize)> = Vec::new();
+    for host in hosts {
+        let rank = state_rank(&host.state);
+        let label = STATE_ORDER
+            .get(rank)
+            .map_or_else(|| host.state.to_lowercase(), |(_, l)| (*l).to_string());
+        match counts.iter_mut().find(|(_, l, _)| *l == label) {
+            Some((_, _, n)) => *n += 1,
+            None => counts.push((rank, label, 1)),
+        }
+    }
+    counts.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.cmp(&b.1)));
+    counts
+        .iter()
+        .map(|(_, label, n)| format!("{n} {label}"))
+        .collect::<Vec<_>>()
+        .join(" / ")
+}
+
+/// Why a request failed, in words for the status bar
+///
+/// Daemon errors carry a JSON body; only its message is shown.
+fn error_reason(error: &ClientError) -> String {
+    match error {
+        ClientError::Api { status, message } => {
+            let reason = serde_json::from_str::<serde_json::Value>(message)
+                .ok()
+                .and_then(|body| body["message"].as_str().map(str::to_string))
+                .unwrap_or_else(|| message.clone());
+            format!("{reason} ({status})")
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Error code of a daemon error body, e.g. `HOST_BUSY`
+fn error_code(body: &str) -> Option<String> {
+    let body: serde_json::Value = serde_json::from_str(body).ok()?;
+    body["code"].as_str().map(str::to_string)
+}
+
+/// Why a request failed in a word or two, e.g. `busy` for `HOST_BUSY`
+fn short_reason(error: &ClientError) -> String {
+    if let ClientError::Api { message, .. } = error
+        && let Some(code) = error_code(message)
+    {
+        let code = code.strip_prefix("HOST_").unwrap_or(&code);
+        return code.to_lowercase().replace('_', " ");
+    }
+    error_reason(error)
 }
 
+/// Number of past updates shown in the details panel
+const UPDATE_HISTORY_SHOWN: usize = 5;
+
+/// Events of each host replayed after connecting
+const REPLAYED_PER_HOST: usize = 10;
+
 /// Application state
 #[allow(dead_code)]
 pub struct App {
     /// Server URL
     server_url: String,
-    /// HTTP client
-    http_client: Option<HttpClient>,
+    /// Daemon API, an [`HttpClient`] once connected
+    api: Option<Arc<dyn TendhostApi>>,
+    /// Whether the daemon's protocol version is checked
+    pub version_check: VersionCheck,
+    /// Interval the daemon batches events over, if any
+    pub event_batch: Option<Duration>,
     /// WebSocket client
     ws_client: Option<WsClient>,
     /// Should quit
@@ -72,44 +649,104 @@ pub struct App {
     pub connection_state: ConnectionState,
     /// Host list
     pub hosts: Vec<HostDisplay>,
-    /// Selected host index
+    /// Index of the selected host in `visible_hosts()`
     pub selected_host: usize,
+    /// Order of the host list
+    pub sort: HostSort,
+    /// Names of the marked hosts, which actions apply to instead of the
+    /// selected host; kept by name so sorting and searching keep them
+    pub marked: BTreeSet<String>,
+    /// Range selection in progress, if any
+    range: Option<RangeSelect>,
+    /// Bulk action in progress on the marked hosts
+    pub bulk: Option<BulkRun>,
     /// Selected host details (JSON)
-    pub host_details: Option<serde_json::Value>,
+    pub host_details: Option<HostDetail>,
+    /// Most recent updates of the host in `host_details`, newest first
+    pub update_history: Vec<UpdateHistoryEntry>,
+    /// Lines the failure output in the details panel is scrolled up from
+    /// its end
+    pub failure_output_scroll: usize,
+    /// Fleet-wide counts shown in the status bar
+    pub fleet_summary: Option<FleetSummary>,
     /// Event log
     pub event_log: VecDeque<EventLogEntry>,
+    /// Sequence number of the last replayed event; stream events up to it
+    /// were already handled
+    replayed_until: Option<u64>,
+    /// Publish time of the replayed event being handled
+    replaying: Option<DateTime<Utc>>,
     /// Show help popup
     pub show_help: bool,
+    /// Key bindings for the host list and inventory view
+    pub keymap: KeyMap,
+    /// Confirmation prompt awaiting y/n
+    pub confirm: Option<PendingConfirm>,
     /// Search mode active
     pub search_active: bool,
     /// Search query
-    pub search_query: String,
-    /// Error message (for toast)
-    pub error_message: Option<String>,
+    pub search: TextInput,
+    /// Tag editor popup, if open
+    pub tag_editor: Option<TagEditor>,
+    /// Package picker popup, if open
+    pub package_picker: Option<PackagePicker>,
+    /// Notifications shown in the status bar
+    pub toasts: Toasts,
+    /// Inventory view replacing the normal layout while open
+    pub inventory: Option<InventoryView>,
+    /// Results of background requests, fed back into `handle_action`
+    background_tx: mpsc::UnboundedSender<Action>,
+    background_rx: mpsc::UnboundedReceiver<Action>,
     /// Tick counter for animations
     pub tick: u64,
 }
 
 impl App {
     /// Create a new application
-    pub fn new(server_url: &str) -> Self {
-        Self {
+    pub fn new(server_url: &str, keymap: KeyMap, keymap_warnings: &[String]) -> Self {
+        let (background_tx, background_rx) = mpsc::unbounded_channel();
+        let mut app = Self {
             server_url: server_url.to_string(),
-            http_client: None,
+            api: None,
+            version_check: VersionCheck::default(),
+            event_batch: None,
             ws_client: None,
             should_quit: false,
             focus: Focus::HostList,
             connection_state: ConnectionState::Disconnected,
             hosts: Vec::new(),
             selected_host: 0,
+            sort: HostSort::default(),
+            marked: BTreeSet::new(),
+            range: None,
+            bulk: None,
             host_details: None,
+            update_history: Vec::new(),
+            failure_output_scroll: 0,
+            fleet_summary: None,
             event_log: VecDeque::with_capacity(100),
+            replayed_until: None,
+            replaying: None,
             show_help: false,
+            keymap,
+            confirm: None,
             search_active: false,
-            search_query: String::new(),
-            error_message: None,
+            search: TextInput::default(),
+            tag_editor: None,
+            package_picker: None,
+            toasts: Toasts::default(),
+            inventory: None,
+            background_tx,
+            background_rx,
             tick: 0,
+        };
+        for warning in keymap_warnings {
+            app.log_event(
+                &format!("Ignored key binding {warning}"),
+                EventLevel::Warning,
+            );
         }
+        app
     }
 
     /// Check if app should quit
@@ -118,23 +755,41 @@ impl App {
     }
 
     /// Connect to the daemon
+    ///
+    /// # Errors
+    /// Fails if the daemon speaks a protocol version this TUI doesn't
+    /// support, rather than showing misread data.
     pub async fn connect(&mut self) -> Result<()> {
         self.connection_state = ConnectionState::Connecting;
 
         // Create HTTP client
-        let http_client = HttpClient::new(&self.server_url)?;
-        self.http_client = Some(http_client);
+        let mut builder = HttpClient::builder(&self.server_url);
+        if self.version_check == VersionCheck::Ignore {
+            builder = builder.ignore_api_version();
+        }
+        let client = Arc::new(builder.build()?);
+        // An unreachable daemon is retried later; a mismatched one never fits
+        if let Err(e @ ClientError::IncompatibleServer { .. }) = client.health().await {
+            return Err(e.into());
+        }
+        self.api = Some(client);
 
         // Load initial host list
         self.load_hosts().await?;
 
         // Connect WebSocket for event receiving
         let ws_url = self.server_url.replace("http", "ws") + "/ws/events";
-        match WsClient::connect(&ws_url).await {
+        let mut ws = WsClient::builder(&ws_url).version_check(self.version_check);
+        if let Some(interval) = self.event_batch {
+            ws = ws.batch(interval);
+        }
+        match ws.connect().await {
             Ok(ws_client) => {
                 self.ws_client = Some(ws_client);
                 self.connection_state = ConnectionState::Connected;
                 self.log_event("Connected to daemon", EventLevel::Success);
+                // Catch up on what happened while nobody was listening
+                self.replay_recent_events().await;
             }
             Err(e) => {
                 self.log_event(&format!("WebSocket failed: {e}"), EventLevel::Warning);
@@ -148,76 +803,172 @@ impl App {
 
     /// Load hosts from HTTP API
     async fn load_hosts(&mut self) -> Result<()> {
-        if let Some(client) = &self.http_client {
-            match client.list_hosts().send().await {
+        if let Some(client) = &self.api {
+            match client.list_hosts(&HostListQuery::default()).await {
                 Ok(response) => {
-                    self.hosts = response
-                        .data
-                        .iter()
-                        .map(|h| HostDisplay {
-                            name: h
-                                .get("name")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("unknown")
-                                .to_string(),
-                            state: h
-                                .get("state")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("unknown")
-                                .to_string(),
-                            os: h
-                                .get("os")
-                                .and_then(|v| v.as_str())
-                                .unwrap_or("")
-                                .to_string(),
-                            packages: h
-                                .get("upgradable_packages")
-                                .and_then(serde_json::Value::as_u64)
-                                .and_then(|v| u32::try_from(v).ok()),
-                            last_updated: None,
-                        })
-                        .collect();
-                }
-                Err(e) => {
-                    self.log_event(&format!("Failed to load hosts: {e}"), EventLevel::Error);
+                    let pinned = self.selected_host_name().map(str::to_string);
+                    self.hosts = response.hosts.into_iter().map(HostDisplay::from).collect();
+                    let hosts = &self.hosts;
+                    self.marked
+                        .retain(|name| hosts.iter().any(|h| h.name == *name));
+                    self.reselect(pinned.as_deref());
+                    self.refresh_fleet_summary();
                 }
+                Err(e) => self.report_failure("Failed to load hosts", None, &e),
             }
         }
         Ok(())
     }
 
+    /// Handle the last events of each host, marked as replayed in the log
+    ///
+    /// Called right after the WebSocket connects, so the stream may
+    /// deliver some of them again; see [`Self::already_replayed`].
+    async fn replay_recent_events(&mut self) {
+        let Some(client) = self.api.clone() else {
+            return;
+        };
+        match client.recent_events(REPLAYED_PER_HOST).await {
+            Ok(envelopes) => {
+                let pinned = self.selected_host_name().map(str::to_string);
+                for envelope in &envelopes {
+                    self.replaying = Some(envelope.ts);
+                    self.handle_ws_event(&envelope.event);
+                }
+                self.replaying = None;
+                self.replayed_until = envelopes.last().map(|e| e.seq);
+                self.reselect(pinned.as_deref());
+            }
+            Err(e) => {
+                self.log_event(
+                    &format!("Failed to replay events: {e}"),
+                    EventLevel::Warning,
+                );
+            }
+        }
+    }
+
+    /// Whether a stream event was already handled as replayed
+    ///
+    /// Sequence numbers only grow, so the first newer event ends the check.
+    fn already_replayed(&mut self, seq: u64) -> bool {
+        match self.replayed_until {
+            Some(until) if seq <= until => true,
+            Some(_) => {
+                self.replayed_until = None;
+                false
+            }
+            None => false,
+        }
+    }
+
     /// Process WebSocket events
     pub async fn process_ws_events(&mut self) -> Result<()> {
         // Collect events first to avoid borrow issues
-        let mut events = Vec::new();
+        let mut envelopes = Vec::new();
         if let Some(ws_client) = &mut self.ws_client {
             // Non-blocking check for events
-            while let Ok(event) = tokio::time::timeout(std::time::Duration::from_millis(1), async {
-                ws_client.recv().await
+            while let Ok(envelope) = tokio::time::timeout(Duration::from_millis(1), async {
+                ws_client.recv_envelope().await
             })
             .await
             {
-                if let Some(event) = event {
-                    events.push(event);
+                if let Some(envelope) = envelope {
+                    envelopes.push(envelope);
                 }
             }
         }
+        self.handle_ws_envelopes(envelopes);
+        Ok(())
+    }
+
+    /// Handle events received from the stream, skipping replayed ones
+    fn handle_ws_envelopes(&mut self, envelopes: Vec<EventEnvelope>) {
+        let events: Vec<WsEvent> = envelopes
+            .into_iter()
+            .filter(|envelope| !self.already_replayed(envelope.seq))
+            .map(|envelope| envelope.event)
+            .collect();
 
-        // Handle collected events
+        // State changes can reorder the list
+        let pinned = self.selected_host_name().map(str::to_string);
         for event in &events {
             self.handle_ws_event(event);
         }
+        self.reselect(pinned.as_deref());
+        if events.iter().any(|e| {
+            matches!(
+                e,
+                WsEvent::HostStateChanged { .. } | WsEvent::EventsDropped { .. }
+            )
+        }) {
+            self.refresh_fleet_summary();
+        }
+    }
+
+    /// Fetch the fleet summary in the background, keeping the last one
+    /// shown if the request fails
+    fn refresh_fleet_summary(&self) {
+        let Some(client) = self.api.clone() else {
+            return;
+        };
+        let tx = self.background_tx.clone();
+        tokio::spawn(async move {
+            if let Ok(summary) = client.fleet_status().await {
+                let _ = tx.send(Action::FleetSummaryLoaded(summary));
+            }
+        });
+    }
+
+    /// Handle results of finished background requests
+    pub async fn process_background_actions(&mut self) -> Result<()> {
+        while let Ok(action) = self.background_rx.try_recv() {
+            self.handle_action(action).await?;
+        }
         Ok(())
     }
 
+    /// Which key map applies to the next key press
+    pub fn input_mode(&self) -> InputMode {
+        if self.confirm.is_some() {
+            InputMode::Confirm
+        } else if self.tag_editor.is_some() {
+            InputMode::EditTags
+        } else if self.package_picker.is_some() {
+            InputMode::Packages
+        } else if let Some(view) = &self.inventory {
+            if view.filter_active {
+                InputMode::Search
+            } else {
+                InputMode::Inventory
+            }
+        } else if self.search_active {
+            InputMode::Search
+        } else {
+            InputMode::Normal
+        }
+    }
+
     /// Handle a WebSocket event
     fn handle_ws_event(&mut self, event: &WsEvent) {
+        // Queries and updates change what the open package picker lists
+        if matches!(
+            event,
+            WsEvent::InventoryQueried { .. } | WsEvent::UpdateCompleted { .. }
+        ) && let Some(host) = event.host()
+            && self.package_picker.as_ref().is_some_and(|p| p.host == host)
+        {
+            self.reload_picker_details(host.to_string());
+        }
+
         match event {
-            WsEvent::HostStateChanged { host, from, to } => {
+            WsEvent::HostStateChanged { host, from, to, .. } => {
                 self.log_event(&format!("{host}: {from} -> {to}"), EventLevel::Info);
                 // Update host state in list
                 if let Some(h) = self.hosts.iter_mut().find(|h| h.name == *host) {
                     h.state.clone_from(to);
+                    // A new state means a new (or no) failure to acknowledge
+                    h.acknowledged = false;
                 }
             }
             WsEvent::UpdateProgress {
@@ -230,73 +981,384 @@ impl App {
                     EventLevel::Info,
                 );
             }
-            WsEvent::UpdateCompleted { host, result } => {
+            WsEvent::UpdateCompleted {
+                host,
+                result,
+                dry_run: true,
+            } => {
+                // Nothing was installed, so the host was not updated
+                self.log_event(
+                    &format!("{host}: Dry run completed - {result}"),
+                    EventLevel::Info,
+                );
+            }
+            WsEvent::UpdateCompleted { host, result, .. } => {
                 self.log_event(
                     &format!("{host}: Update completed - {result}"),
                     EventLevel::Success,
                 );
+                let at = self.replaying.unwrap_or_else(Utc::now);
+                if let Some(h) = self.hosts.iter_mut().find(|h| h.name == *host) {
+                    h.last_updated = Some(at);
+                }
             }
             WsEvent::HostConnected { host } => {
                 self.log_event(&format!("{host}: Connected"), EventLevel::Info);
+                if let Some(h) = self.hosts.iter_mut().find(|h| h.name == *host) {
+                    h.unreachable = false;
+                }
             }
             WsEvent::HostDisconnected { host, reason } => {
+                if let Some(h) = self.hosts.iter_mut().find(|h| h.name == *host) {
+                    h.unreachable = true;
+                }
                 self.log_event(
                     &format!("{host}: Disconnected - {reason}"),
                     EventLevel::Warning,
                 );
             }
-        }
-    }
-
-    /// Log an event
-    fn log_event(&mut self, message: &str, level: EventLevel) {
-        let entry = EventLogEntry {
-            timestamp: Utc::now(),
-            message: message.to_string(),
-            level,
-        };
-        self.event_log.push_front(entry);
-        if self.event_log.len() > 100 {
-            self.event_log.pop_back();
-        }
-    }
-
-    /// Handle an action
-    pub async fn handle_action(&mut self, action: Action) -> Result<()> {
-        match action {
-            Action::Quit => {
-                self.should_quit = true;
-            }
-            Action::Tick => {
-                self.tick = self.tick.wrapping_add(1);
-            }
-            Action::Up => {
-                if self.selected_host > 0 {
-                    self.selected_host -= 1;
-                }
-            }
-            Action::Down => {
-                if self.selected_host < self.hosts.len().saturating_sub(1) {
-                    self.selected_host += 1;
+            WsEvent::HookExecuted {
+                host,
+                hook,
+                success,
+            } => {
+                if *success {
+                    self.log_event(&format!("{host}: Hook ok: {hook}"), EventLevel::Info);
+                } else {
+                    self.log_event(&format!("{host}: Hook failed: {hook}"), EventLevel::Error);
                 }
             }
-            Action::First => {
-                self.selected_host = 0;
+            WsEvent::RetryScheduled {
+                host,
+                attempt,
+                max_retries,
+                delay_secs,
+            } => {
+                self.log_event(
+                    &format!("{host}: Retry {attempt}/{max_retries} in {delay_secs}s"),
+                    EventLevel::Warning,
+                );
             }
-            Action::Last => {
-                self.selected_host = self.hosts.len().saturating_sub(1);
+            WsEvent::WaitingForLock {
+                host,
+                holder,
+                waited_secs,
+            } => {
+                let holder = holder.as_deref().unwrap_or("another process");
+                self.log_event(
+                    &format!("{host}: Waiting for package lock held by {holder} ({waited_secs}s)"),
+                    EventLevel::Warning,
+                );
             }
-            Action::Select => {
+            WsEvent::RetryStarted { host, attempt } => {
+                self.log_event(
+                    &format!("{host}: Retry {attempt} started"),
+                    EventLevel::Info,
+                );
+            }
+            WsEvent::RetriesExhausted { host, attempts } => {
+                self.log_event(
+                    &format!("{host}: Gave up after {attempts} retries"),
+                    EventLevel::Error,
+                );
+            }
+            WsEvent::HostAcknowledged { host } => {
+                self.log_event(&format!("{host}: Failure acknowledged"), EventLevel::Info);
+                self.mark_acknowledged(host);
+            }
+            WsEvent::HostPauseChanged { host, paused } => {
+                let change = if *paused { "Paused" } else { "Resumed" };
+                self.log_event(&format!("{host}: {change}"), EventLevel::Info);
+                self.mark_paused(host, *paused);
+            }
+            WsEvent::CircuitOpened {
+                host,
+                failures,
+                retry_at,
+            } => {
+                if let Some(h) = self.hosts.iter_mut().find(|h| h.name == *host) {
+                    h.unreachable = true;
+                }
+                self.log_event(
+                    &format!(
+                        "{host}: Unreachable after {failures} failures, retrying at {}",
+                        retry_at.with_timezone(&Local).format("%H:%M")
+                    ),
+                    EventLevel::Warning,
+                );
+            }
+            WsEvent::CircuitClosed { host } => {
+                self.log_event(&format!("{host}: Reachable again"), EventLevel::Info);
+            }
+            WsEvent::InventoryChanged { host, summary } => {
+                self.log_event(
+                    &format!("{host}: Inventory changed: {summary}"),
+                    EventLevel::Info,
+                );
+            }
+            WsEvent::InventoryQueried {
+                host,
+                summary,
+                partial: true,
+            } => {
+                self.log_event(&format!("{host}: {summary}"), EventLevel::Warning);
+            }
+            WsEvent::FleetHostFinished {
+                host,
+                phase,
+                skipped: true,
+                error,
+                ..
+            } => {
+                let error = error.as_deref().unwrap_or("host unreachable");
+                self.log_event(
+                    &format!("{host}: Fleet update ({phase}) skipped: {error}"),
+                    EventLevel::Warning,
+                );
+            }
+            WsEvent::FleetHostFinished {
+                host,
+                phase,
+                success,
+                error,
+                ..
+            } => {
+                if *success {
+                    self.log_event(
+                        &format!("{host}: Fleet update ({phase}) done"),
+                        EventLevel::Success,
+                    );
+                } else {
+                    let error = error.as_deref().unwrap_or("unknown error");
+                    self.log_event(
+                        &format!("{host}: Fleet update ({phase}) failed: {error}"),
+                        EventLevel::Error,
+                    );
+                }
+            }
+            WsEvent::FleetUpdateHalted {
+                failed_canaries,
+                skipped_hosts,
+            } => {
+                self.log_event(
+                    &format!(
+                        "Fleet update halted: canary {} failed, {skipped_hosts} hosts skipped",
+                        failed_canaries.join(", ")
+                    ),
+                    EventLevel::Error,
+                );
+            }
+            WsEvent::FleetUpdateFinished {
+                total_hosts,
+                completed,
+                failed,
+                skipped,
+                dry_run,
+            } => {
+                let kind = if *dry_run { "dry run" } else { "update" };
+                let level = if *failed > 0 {
+                    EventLevel::Error
+                } else {
+                    EventLevel::Success
+                };
+                self.log_event(
+                    &format!(
+                        "Fleet {kind} finished: {completed}/{total_hosts} updated, {failed} failed, {skipped} skipped"
+                    ),
+                    level,
+                );
+            }
+            WsEvent::EventsDropped { count } => {
+                self.log_event(
+                    &format!("Missed {count} events; refresh for current state"),
+                    EventLevel::Warning,
+                );
+            }
+            // Event types added in a newer daemon
+            _ => {}
+        }
+    }
+
+    /// Output of the failed command of the host in `host_details`
+    pub fn failure_output(&self) -> Option<&str> {
+        self.host_details
+            .as_ref()?
+            .failure_output
+            .as_deref()
+            .filter(|o| !o.trim().is_empty())
+    }
+
+    /// Whether navigation keys scroll the failure output instead of the
+    /// host list
+    fn scrolls_failure_output(&self) -> bool {
+        self.focus == Focus::Details && self.failure_output().is_some()
+    }
+
+    /// Show a notification in the status bar
+    fn notify(&mut self, level: EventLevel, message: impl Into<String>) {
+        self.toasts.push(self.tick, level, message, None);
+    }
+
+    /// Log a failed request and show it in the status bar
+    ///
+    /// `action` says what failed, e.g. `Update failed on web`; `host` is
+    /// the host the request was about, if any, for the hint.
+    fn report_failure(&mut self, action: &str, host: Option<&str>, error: &ClientError) {
+        let message = format!("{action}: {}", error_reason(error));
+        self.log_event(&message, EventLevel::Error);
+        let hint = self.failure_hint(host, error);
+        self.toasts
+            .push(self.tick, EventLevel::Error, message, hint);
+    }
+
+    /// How to get past a failed request, if there is a way
+    fn failure_hint(&self, host: Option<&str>, error: &ClientError) -> Option<String> {
+        let key = |command| self.keymap.primary(command);
+        let failed = host
+            .and_then(|name| self.hosts.iter().find(|h| h.name == name))
+            .is_some_and(HostDisplay::is_failed);
+        match error {
+            ClientError::Api { status: 409, .. } if failed => Some(format!(
+                "press {} to retry or {} to acknowledge",
+                key(Command::Retry),
+                key(Command::Acknowledge)
+            )),
+            ClientError::Api {
+                status: 409,
+                message,
+            } => match error_code(message).as_deref() {
+                Some("HOST_PAUSED") => Some(format!("press {} to resume it", key(Command::Pause))),
+                Some("HOST_BUSY") => Some("wait for the running operation to finish".to_string()),
+                _ => None,
+            },
+            ClientError::Api { status: 404, .. } if host.is_some() => {
+                Some("the host was removed from the daemon".to_string())
+            }
+            ClientError::Api {
+                status: 401 | 403, ..
+            } => Some("check the API token".to_string()),
+            ClientError::Http(_) | ClientError::Timeout | ClientError::RetriesExhausted { .. } => {
+                Some(format!("is the daemon running at {}?", self.server_url))
+            }
+            _ => None,
+        }
+    }
+
+    /// Log an event, at its publish time and marked if replayed
+    fn log_event(&mut self, message: &str, level: EventLevel) {
+        let entry = match self.replaying {
+            Some(at) => EventLogEntry {
+                timestamp: at,
+                message: format!("{message} (replayed)"),
+                level,
+            },
+            None => EventLogEntry {
+                timestamp: Utc::now(),
+                message: message.to_string(),
+                level,
+            },
+        };
+        self.event_log.push_front(entry);
+        if self.event_log.len() > 100 {
+            self.event_log.pop_back();
+        }
+    }
+
+    /// Handle an action
+    pub async fn handle_action(&mut self, action: Action) -> Result<()> {
+        if self.confirm.is_none() && !self.show_help {
+            if let Some(picker) = &mut self.package_picker
+                && picker.handle(&action)
+            {
+                return Ok(());
+            }
+            if self.tag_editor.is_none()
+                && let Some(view) = &mut self.inventory
+                && view.handle(&action)
+            {
+                return Ok(());
+            }
+        }
+
+        let navigates = matches!(
+            action,
+            Action::Up | Action::Down | Action::First | Action::Last
+        );
+        match action {
+            Action::Quit => {
+                self.should_quit = true;
+            }
+            Action::Tick => {
+                self.tick = self.tick.wrapping_add(1);
+                self.toasts.expire(self.tick);
+            }
+            Action::Up if self.scrolls_failure_output() => {
+                let top = self.failure_output().map_or(0, |o| o.lines().count() - 1);
+                self.failure_output_scroll = (self.failure_output_scroll + 1).min(top);
+            }
+            Action::Down if self.scrolls_failure_output() => {
+                self.failure_output_scroll = self.failure_output_scroll.saturating_sub(1);
+            }
+            Action::First if self.scrolls_failure_output() => {
+                self.failure_output_scroll =
+                    self.failure_output().map_or(0, |o| o.lines().count() - 1);
+            }
+            Action::Last if self.scrolls_failure_output() => {
+                self.failure_output_scroll = 0;
+            }
+            Action::Up if self.selected_host > 0 => {
+                self.selected_host -= 1;
+            }
+            Action::Down if self.selected_host + 1 < self.visible_hosts().len() => {
+                self.selected_host += 1;
+            }
+            Action::First => {
+                self.selected_host = 0;
+            }
+            Action::Last => {
+                self.selected_host = self.visible_hosts().len().saturating_sub(1);
+            }
+            Action::CycleSort => {
+                self.set_sort(HostSort {
+                    column: self.sort.column.next(),
+                    ..self.sort
+                });
+            }
+            Action::ReverseSort => {
+                self.set_sort(HostSort {
+                    descending: !self.sort.descending,
+                    ..self.sort
+                });
+            }
+            Action::Select => {
                 self.load_selected_host_details().await?;
             }
             Action::Back => {
-                if self.show_help {
+                if self.confirm.is_some() {
+                    self.confirm = None;
+                } else if self.tag_editor.is_some() {
+                    self.tag_editor = None;
+                } else if self.show_help {
                     self.show_help = false;
+                } else if self.package_picker.is_some() {
+                    self.package_picker = None;
+                } else if self.inventory.is_some() {
+                    self.inventory = None;
+                } else if self.bulk.is_some() {
+                    self.finish_bulk();
                 } else if self.search_active {
                     self.search_active = false;
-                    self.search_query.clear();
+                    self.keep_selection(|app| app.search.clear());
+                } else {
+                    self.clear_marks();
                 }
             }
+            Action::ToggleMark => {
+                self.toggle_mark();
+            }
+            Action::RangeSelect => {
+                self.toggle_range();
+            }
             Action::Help => {
                 self.show_help = !self.show_help;
             }
@@ -307,44 +1369,335 @@ impl App {
                     Focus::Events => Focus::HostList,
                 };
             }
+            Action::TriggerUpdate if self.package_picker.is_some() => {
+                self.submit_package_selection().await?;
+            }
+            Action::TriggerUpdate if !self.marked.is_empty() => {
+                self.start_bulk(BulkAction::Update, self.marked_hosts());
+            }
             Action::TriggerUpdate => {
                 self.trigger_update_on_selected().await?;
             }
+            Action::PreviewUpdate => {
+                self.preview_update_on_selected().await?;
+            }
+            Action::TriggerReboot if !self.marked.is_empty() => {
+                self.confirm = Some(PendingConfirm::RebootHosts {
+                    hosts: self.marked_hosts(),
+                });
+            }
             Action::TriggerReboot => {
                 self.trigger_reboot_on_selected().await?;
             }
+            Action::CancelUpdate => {
+                if let Some(name) = self.selected_host_name() {
+                    self.confirm = Some(PendingConfirm::CancelUpdate {
+                        host: name.to_string(),
+                    });
+                }
+            }
+            Action::Confirm => {
+                if let Some(pending) = self.confirm.take() {
+                    self.run_confirmed(pending).await?;
+                }
+            }
+            Action::RetryHost if !self.marked.is_empty() => {
+                self.start_bulk(BulkAction::Retry, self.marked_hosts());
+            }
             Action::RetryHost => {
                 self.retry_selected_host().await?;
             }
-            Action::StartSearch => {
-                self.search_active = true;
+            Action::AcknowledgeFailure if !self.marked.is_empty() => {
+                self.start_bulk(BulkAction::Acknowledge, self.marked_hosts());
+            }
+            Action::AcknowledgeFailure => {
+                self.acknowledge_selected_host().await?;
+            }
+            Action::TogglePause => {
+                self.toggle_pause_on_selected().await?;
             }
-            Action::SearchInput(c) => {
-                if self.search_active {
-                    self.search_query.push(c);
+            Action::EditTags => {
+                if let Some(host) = self.selected() {
+                    self.tag_editor = Some(TagEditor {
+                        host: host.name.clone(),
+                        input: TextInput::with_value(host.tags.join(", ")),
+                    });
                 }
             }
-            Action::SearchBackspace => {
-                if self.search_active {
-                    self.search_query.pop();
+            Action::Submit => {
+                self.submit_tags().await?;
+            }
+            Action::PickPackages => {
+                self.open_package_picker().await?;
+            }
+            Action::PickerDetailsLoaded(details) => {
+                if self
+                    .host_details
+                    .as_ref()
+                    .is_some_and(|d| d.name == details.name)
+                {
+                    self.host_details = Some(*details);
+                    self.sync_package_picker();
+                } else if let Some(picker) = self
+                    .package_picker
+                    .as_mut()
+                    .filter(|p| p.host == details.name)
+                {
+                    picker.sync(details.upgradable_packages.as_deref().unwrap_or_default());
+                }
+            }
+            Action::RefreshInventory => {
+                self.load_inventory();
+            }
+            Action::InventoryLoaded(host, result) => {
+                self.apply_inventory(&host, result);
+            }
+            Action::FleetSummaryLoaded(summary) => {
+                self.fleet_summary = Some(summary);
+            }
+            Action::StartSearch => {
+                self.search_active = true;
+            }
+            Action::Input(edit) => {
+                if let Some(editor) = &mut self.tag_editor {
+                    editor.input.apply(edit);
+                } else if self.search_active {
+                    self.keep_selection(|app| {
+                        app.search.apply(edit);
+                    });
                 }
             }
             Action::ClearSearch => {
-                self.search_query.clear();
+                self.keep_selection(|app| app.search.clear());
             }
             _ => {}
         }
+        if navigates {
+            self.extend_range();
+        }
+        Ok(())
+    }
+
+    /// Mark the selected host, or unmark it if marked
+    ///
+    /// Ends a range selection, keeping its marks.
+    fn toggle_mark(&mut self) {
+        self.range = None;
+        if let Some(name) = self.selected_host_name().map(str::to_string)
+            && !self.marked.remove(&name)
+        {
+            self.marked.insert(name);
+        }
+    }
+
+    /// Start a range selection at the selected host, or end the running one
+    fn toggle_range(&mut self) {
+        if self.range.take().is_some() {
+            return;
+        }
+        if let Some(name) = self.selected_host_name() {
+            self.range = Some(RangeSelect {
+                anchor: name.to_string(),
+                base: self.marked.clone(),
+            });
+            self.extend_range();
+        }
+    }
+
+    /// Whether moving the selection marks a range of hosts
+    pub fn range_active(&self) -> bool {
+        self.range.is_some()
+    }
+
+    /// Mark the listed hosts from the range's start to the selected host,
+    /// on top of the marks from before the range
+    ///
+    /// The range ends once a search hides the host it started on.
+    fn extend_range(&mut self) {
+        let Some(range) = &self.range else {
+            return;
+        };
+        let visible = self.visible_hosts();
+        let marked = visible
+            .iter()
+            .position(|h| h.name == range.anchor)
+            .map(|anchor| {
+                let from = anchor.min(self.selected_host);
+                let to = anchor.max(self.selected_host);
+                let mut marked = range.base.clone();
+                marked.extend(
+                    visible
+                        .iter()
+                        .skip(from)
+                        .take(to - from + 1)
+                        .map(|h| h.name.clone()),
+                );
+                marked
+            });
+        match marked {
+            Some(marked) => self.marked = marked,
+            None => self.range = None,
+        }
+    }
+
+    /// Unmark every host and end a range selection
+    fn clear_marks(&mut self) {
+        self.marked.clear();
+        self.range = None;
+    }
+
+    /// Names of the marked hosts in list order, including ones the
+    /// search hides
+    pub fn marked_hosts(&self) -> Vec<String> {
+        let mut hosts: Vec<&HostDisplay> = self
+            .hosts
+            .iter()
+            .filter(|h| self.marked.contains(&h.name))
+            .collect();
+        hosts.sort_by(|a, b| self.sort.compare(a, b));
+        hosts.into_iter().map(|h| h.name.clone()).collect()
+    }
+
+    /// Start applying `action` to `hosts`, unless a bulk action is running
+    fn start_bulk(&mut self, action: BulkAction, hosts: Vec<String>) {
+        if let Some(run) = &self.bulk {
+            let message = format!(
+                "Bulk {} is still running; press {} to cancel it",
+                run.action.name(),
+                self.keymap.primary(Command::Back)
+            );
+            self.notify(EventLevel::Warning, message);
+            return;
+        }
+        if self.api.is_none() || hosts.is_empty() {
+            return;
+        }
+        self.log_event(
+            &format!("Starting bulk {} on {} hosts", action.name(), hosts.len()),
+            EventLevel::Info,
+        );
+        self.bulk = Some(BulkRun::new(action, hosts));
+    }
+
+    /// Whether a bulk action has hosts left to contact
+    pub fn bulk_running(&self) -> bool {
+        self.bulk.is_some()
+    }
+
+    /// Send the request of the running bulk action to its next host
+    ///
+    /// The main loop calls this once per turn, so key presses are handled
+    /// between hosts and Esc can cancel the rest.
+    pub async fn process_bulk(&mut self) -> Result<()> {
+        let Some(client) = self.api.clone() else {
+            return Ok(());
+        };
+        let Some((action, name)) = self
+            .bulk
+            .as_mut()
+            .and_then(|run| Some((run.action, run.pending.pop_front()?)))
+        else {
+            return Ok(());
+        };
+
+        // Only failed hosts can be acknowledged; others aren't sent
+        let failed = self.hosts.iter().any(|h| h.name == name && h.is_failed());
+        let result = match action {
+            BulkAction::Update => Some(client.update_host_packages(&name, false).await.map(drop)),
+            BulkAction::Reboot => Some(client.reboot_host(&name).await.map(drop)),
+            BulkAction::Retry => Some(client.retry_host(&name).await.map(drop)),
+            BulkAction::Acknowledge if !failed => None,
+            BulkAction::Acknowledge => Some(client.acknowledge_host(&name).await.map(drop)),
+        };
+
+        match result {
+            None => self.bulk_failed(action, &name, "not failed", "not failed".to_string()),
+            Some(Ok(())) => {
+                let message = match action {
+                    BulkAction::Update => format!("Update started on {name}"),
+                    BulkAction::Reboot => format!("Reboot started on {name}"),
+                    BulkAction::Retry => format!("Retry started on {name}"),
+                    BulkAction::Acknowledge => {
+                        self.mark_acknowledged(&name);
+                        format!("Acknowledged failure on {name}")
+                    }
+                };
+                self.log_event(&message, EventLevel::Success);
+                if let Some(run) = &mut self.bulk {
+                    run.succeeded += 1;
+                }
+            }
+            Some(Err(e)) => self.bulk_failed(action, &name, &error_reason(&e), short_reason(&e)),
+        }
+        if self.bulk.as_ref().is_some_and(|run| run.pending.is_empty()) {
+            self.finish_bulk();
+        }
         Ok(())
     }
 
+    /// Log a host a bulk action failed on and count it for the summary
+    fn bulk_failed(&mut self, action: BulkAction, name: &str, reason: &str, short: String) {
+        self.log_event(
+            &format!("Bulk {} failed on {name}: {reason}", action.name()),
+            EventLevel::Error,
+        );
+        if let Some(run) = &mut self.bulk {
+            run.failed.push((name.to_string(), short));
+        }
+    }
+
+    /// End the bulk action, cancelling the hosts not contacted yet, and
+    /// show its summary
+    fn finish_bulk(&mut self) {
+        let Some(run) = self.bulk.take() else {
+            return;
+        };
+        let outcome = if run.pending.is_empty() {
+            "finished"
+        } else {
+            "cancelled"
+        };
+        let summary = run.summary();
+        self.log_event(
+            &format!("Bulk {} {outcome}: {summary}", run.action.name()),
+            run.level(),
+        );
+        self.notify(run.level(), summary);
+    }
+
+    /// The selected host, as shown in the list
+    pub fn selected(&self) -> Option<&HostDisplay> {
+        self.visible_hosts().get(self.selected_host).copied()
+    }
+
     /// Get the currently selected host name
     pub fn selected_host_name(&self) -> Option<&str> {
-        self.hosts.get(self.selected_host).map(|h| h.name.as_str())
+        self.selected().map(|h| h.name.as_str())
+    }
+
+    /// Change the order of the list, keeping the same host selected
+    pub fn set_sort(&mut self, sort: HostSort) {
+        self.keep_selection(|app| app.sort = sort);
+    }
+
+    /// Run `change` and select the host that was selected before it, or
+    /// the nearest row if that host is no longer listed
+    fn keep_selection(&mut self, change: impl FnOnce(&mut Self)) {
+        let pinned = self.selected_host_name().map(str::to_string);
+        change(self);
+        self.reselect(pinned.as_deref());
+    }
+
+    fn reselect(&mut self, name: Option<&str>) {
+        let visible = self.visible_hosts();
+        let position = name.and_then(|name| visible.iter().position(|h| h.name == name));
+        self.selected_host =
+            position.unwrap_or_else(|| self.selected_host.min(visible.len().saturating_sub(1)));
     }
 
     /// Load details for the selected host
     async fn load_selected_host_details(&mut self) -> Result<()> {
-        let client = self.http_client.clone();
+        let client = self.api.clone();
         let name = self
             .selected_host_name()
             .map(std::string::ToString::to_string);
@@ -353,18 +1706,70 @@ impl App {
             match client.get_host(&name).await {
                 Ok(details) => {
                     self.host_details = Some(details);
+                    self.sync_package_picker();
+                    self.failure_output_scroll = 0;
+                    // History is secondary; show the details even without it
+                    self.update_history = client
+                        .get_update_history(&name, Some(UPDATE_HISTORY_SHOWN))
+                        .await
+                        .unwrap_or_default();
                 }
-                Err(e) => {
-                    self.log_event(&format!("Failed to load details: {e}"), EventLevel::Error);
-                }
+                Err(e) => self.report_failure("Failed to load details", Some(&name), &e),
             }
         }
         Ok(())
     }
 
+    /// Open the inventory view and fetch inventory in the background
+    ///
+    /// While the view is open this refreshes the shown host, keeping the
+    /// current section, scroll position, and filter.
+    fn load_inventory(&mut self) {
+        let Some(client) = self.api.clone() else {
+            return;
+        };
+        let view = match self.inventory.take() {
+            Some(view) => view,
+            None => match self.selected_host_name() {
+                Some(name) => InventoryView::new(name.to_string()),
+                None => return,
+            },
+        };
+        let host = view.host.clone();
+        self.inventory = Some(InventoryView {
+            data: InventoryData::Loading,
+            ..view
+        });
+
+        let tx = self.background_tx.clone();
+        tokio::spawn(async move {
+            let result = client
+                .get_host_inventory(&host)
+                .await
+                .map_err(|e| e.to_string());
+            let _ = tx.send(Action::InventoryLoaded(host, result));
+        });
+    }
+
+    /// Store a finished inventory fetch if its view is still open
+    fn apply_inventory(&mut self, host: &str, result: Result<HostInventoryResponse, String>) {
+        if let Err(e) = &result {
+            let message = format!("{host}: Failed to load inventory: {e}");
+            self.log_event(&message, EventLevel::Error);
+            self.notify(EventLevel::Error, message);
+        }
+        if let Some(view) = self.inventory.as_mut().filter(|view| view.host == host) {
+            view.data = match result {
+                Ok(response) => InventoryData::Loaded(response),
+                Err(e) => InventoryData::Failed(e),
+            };
+            view.scroll = view.scroll.min(view.row_count().saturating_sub(1));
+        }
+    }
+
     /// Trigger update on selected host
     async fn trigger_update_on_selected(&mut self) -> Result<()> {
-        let client = self.http_client.clone();
+        let client = self.api.clone();
         let name = self
             .selected_host_name()
             .map(std::string::ToString::to_string);
@@ -376,16 +1781,148 @@ impl App {
                     self.log_event(&format!("Update started on {name}"), EventLevel::Success);
                 }
                 Err(e) => {
-                    self.log_event(&format!("Update failed: {e}"), EventLevel::Error);
+                    self.report_failure(&format!("Update failed on {name}"), Some(&name), &e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Preview an update of the selected host; the host is left untouched
+    async fn preview_update_on_selected(&mut self) -> Result<()> {
+        let client = self.api.clone();
+        let name = self.selected_host_name().map(str::to_string);
+
+        if let (Some(client), Some(name)) = (client, name) {
+            match client.preview_update(&name, None).await {
+                Ok(preview) => self.show_preview(&preview),
+                Err(e) => {
+                    self.report_failure(&format!("Preview failed on {name}"), Some(&name), &e);
                 }
             }
         }
         Ok(())
     }
 
+    /// Log an update preview and show its summary in the status bar
+    fn show_preview(&mut self, preview: &UpdatePreview) {
+        let mut summary = format!(
+            "{}: Update would upgrade {} packages",
+            preview.host, preview.upgraded_count
+        );
+        if preview.new_count > 0 {
+            summary.push_str(&format!(", install {}", preview.new_count));
+        }
+        if preview.removed_count > 0 {
+            summary.push_str(&format!(", remove {}", preview.removed_count));
+        }
+        if let Some(bytes) = preview.download_bytes {
+            summary.push_str(&format!(", download {}", format_bytes(bytes)));
+        }
+        if preview.reboot_likely {
+            summary.push_str(", likely needs a reboot");
+        }
+        for warning in &preview.warnings {
+            self.log_event(&format!("{}: {warning}", preview.host), EventLevel::Warning);
+        }
+
+        let level = if preview.is_blocked() {
+            summary.push_str(&format!("; blocked: {}", preview.blockers.join("; ")));
+            EventLevel::Warning
+        } else {
+            EventLevel::Info
+        };
+        self.log_event(&summary, level);
+        self.notify(level, summary);
+    }
+
+    /// Open the package picker with the selected host's upgradable packages
+    ///
+    /// Loads the host's details first unless they are already shown.
+    async fn open_package_picker(&mut self) -> Result<()> {
+        let Some(name) = self.selected_host_name().map(str::to_string) else {
+            return Ok(());
+        };
+        if self.host_details.as_ref().is_none_or(|d| d.name != name) {
+            self.load_selected_host_details().await?;
+        }
+        let packages = self
+            .host_details
+            .as_ref()
+            .filter(|d| d.name == name)
+            .and_then(|d| d.upgradable_packages.clone())
+            .unwrap_or_default();
+        if packages.is_empty() {
+            self.notify(
+                EventLevel::Warning,
+                format!("{name} has no pending updates to pick from"),
+            );
+            return Ok(());
+        }
+        self.package_picker = Some(PackagePicker::new(name, packages));
+        Ok(())
+    }
+
+    /// Show the pending set of the host in `host_details` in the open
+    /// package picker for the same host
+    fn sync_package_picker(&mut self) {
+        if let (Some(picker), Some(details)) = (&mut self.package_picker, &self.host_details)
+            && picker.host == details.name
+        {
+            picker.sync(details.upgradable_packages.as_deref().unwrap_or_default());
+        }
+    }
+
+    /// Fetch the details of the host in the package picker in the background
+    fn reload_picker_details(&self, host: String) {
+        let Some(client) = self.api.clone() else {
+            return;
+        };
+        let tx = self.background_tx.clone();
+        tokio::spawn(async move {
+            if let Ok(details) = client.get_host(&host).await {
+                let _ = tx.send(Action::PickerDetailsLoaded(Box::new(details)));
+            }
+        });
+    }
+
+    /// Update only the packages checked in the package picker
+    ///
+    /// On success the picker closes; on failure it stays open with the
+    /// selection intact.
+    async fn submit_package_selection(&mut self) -> Result<()> {
+        let Some(picker) = &self.package_picker else {
+            return Ok(());
+        };
+        let host = picker.host.clone();
+        let packages = picker.selected();
+        if packages.is_empty() {
+            self.notify(EventLevel::Warning, "No packages checked");
+            return Ok(());
+        }
+        let Some(client) = self.api.clone() else {
+            return Ok(());
+        };
+
+        let count = packages.len();
+        match client.update_selected_packages(&host, &packages).await {
+            Ok(_) => {
+                self.package_picker = None;
+                self.log_event(
+                    &format!("Update of {count} packages started on {host}"),
+                    EventLevel::Success,
+                );
+            }
+            Err(e) => {
+                self.report_failure(&format!("Update failed on {host}"), Some(&host), &e);
+            }
+        }
+        Ok(())
+    }
+
     /// Trigger reboot on selected host
     async fn trigger_reboot_on_selected(&mut self) -> Result<()> {
-        let client = self.http_client.clone();
+        let client = self.api.clone();
         let name = self
             .selected_host_name()
             .map(std::string::ToString::to_string);
@@ -397,7 +1934,34 @@ impl App {
                     self.log_event(&format!("Reboot started on {name}"), EventLevel::Success);
                 }
                 Err(e) => {
-                    self.log_event(&format!("Reboot failed: {e}"), EventLevel::Error);
+                    self.report_failure(&format!("Reboot failed on {name}"), Some(&name), &e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Execute an action the user has confirmed
+    async fn run_confirmed(&mut self, pending: PendingConfirm) -> Result<()> {
+        match pending {
+            PendingConfirm::CancelUpdate { host } => self.cancel_update(&host).await,
+            PendingConfirm::RebootHosts { hosts } => {
+                self.start_bulk(BulkAction::Reboot, hosts);
+                Ok(())
+            }
+        }
+    }
+
+    /// Cancel the running update on a host
+    async fn cancel_update(&mut self, name: &str) -> Result<()> {
+        if let Some(client) = self.api.clone() {
+            self.log_event(&format!("Cancelling update on {name}"), EventLevel::Info);
+            match client.cancel_host_update(name).await {
+                Ok(_) => {
+                    self.log_event(&format!("Update cancelled on {name}"), EventLevel::Warning);
+                }
+                Err(e) => {
+                    self.report_failure(&format!("Cancel failed on {name}"), Some(name), &e);
                 }
             }
         }
@@ -406,7 +1970,7 @@ impl App {
 
     /// Retry a failed host
     async fn retry_selected_host(&mut self) -> Result<()> {
-        let client = self.http_client.clone();
+        let client = self.api.clone();
         let name = self
             .selected_host_name()
             .map(std::string::ToString::to_string);
@@ -418,19 +1982,137 @@ impl App {
                     self.log_event(&format!("Retry started on {name}"), EventLevel::Success);
                 }
                 Err(e) => {
-                    self.log_event(&format!("Retry failed: {e}"), EventLevel::Error);
+                    self.report_failure(&format!("Retry failed on {name}"), Some(&name), &e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Acknowledge the failure on the selected host
+    ///
+    /// Only failed hosts can be acknowledged; anything else shows a toast
+    /// without contacting the daemon.
+    async fn acknowledge_selected_host(&mut self) -> Result<()> {
+        let Some(host) = self.selected() else {
+            return Ok(());
+        };
+        let name = host.name.clone();
+        if !host.is_failed() {
+            self.notify(
+                EventLevel::Warning,
+                format!("{name} is not failed; nothing to acknowledge"),
+            );
+            return Ok(());
+        }
+        let Some(client) = self.api.clone() else {
+            return Ok(());
+        };
+
+        match client.acknowledge_host(&name).await {
+            Ok(_) => {
+                self.log_event(
+                    &format!("Acknowledged failure on {name}"),
+                    EventLevel::Success,
+                );
+                self.mark_acknowledged(&name);
+            }
+            Err(e) => {
+                self.report_failure(&format!("Acknowledge failed on {name}"), Some(&name), &e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Show the failure on `name` as acknowledged in the list and details
+    fn mark_acknowledged(&mut self, name: &str) {
+        if let Some(h) = self.hosts.iter_mut().find(|h| h.name == name) {
+            h.acknowledged = true;
+        }
+        if let Some(details) = self.host_details.as_mut().filter(|d| d.name == name) {
+            details.acknowledged = Some(true);
+        }
+    }
+
+    /// Pause the selected host, or resume it if it is paused
+    async fn toggle_pause_on_selected(&mut self) -> Result<()> {
+        let (Some(host), Some(client)) = (self.selected(), self.api.clone()) else {
+            return Ok(());
+        };
+        let name = host.name.clone();
+        let pause = !host.paused;
+
+        let result = if pause {
+            client.pause_host(&name).await
+        } else {
+            client.resume_host(&name).await
+        };
+        match result {
+            Ok(_) => {
+                let message = if pause {
+                    format!("Paused {name}; automation will leave it alone")
+                } else {
+                    format!("Resumed {name}")
+                };
+                self.log_event(&message, EventLevel::Success);
+                self.mark_paused(&name, pause);
+            }
+            Err(e) => {
+                let verb = if pause { "Pause" } else { "Resume" };
+                self.report_failure(&format!("{verb} failed on {name}"), Some(&name), &e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Show `name` as paused or resumed in the list and details
+    fn mark_paused(&mut self, name: &str, paused: bool) {
+        if let Some(h) = self.hosts.iter_mut().find(|h| h.name == name) {
+            h.paused = paused;
+        }
+        if let Some(details) = self.host_details.as_mut().filter(|d| d.name == name) {
+            details.paused = paused;
+        }
+    }
+
+    /// Save the tags from the open tag editor
+    ///
+    /// On success the editor closes and the host list is reloaded; on
+    /// failure the editor stays open so the input can be corrected.
+    async fn submit_tags(&mut self) -> Result<()> {
+        let (Some(editor), Some(client)) = (&self.tag_editor, self.api.clone()) else {
+            return Ok(());
+        };
+        let host = editor.host.clone();
+        let tags = parse_tags(editor.input.value());
+
+        match client
+            .update_host(&host, serde_json::json!({ "tags": tags }))
+            .await
+        {
+            Ok(_) => {
+                self.tag_editor = None;
+                self.log_event(&format!("Updated tags on {host}"), EventLevel::Success);
+                self.load_hosts().await?;
+                let showing_host = self.host_details.as_ref().is_some_and(|d| d.name == host);
+                if showing_host && let Ok(details) = client.get_host(&host).await {
+                    self.host_details = Some(details);
+                    self.sync_package_picker();
                 }
             }
+            Err(e) => {
+                self.report_failure(&format!("Failed to update tags on {host}"), Some(&host), &e);
+            }
         }
         Ok(())
     }
 
     /// Get filtered hosts based on search query
     pub fn filtered_hosts(&self) -> Vec<&HostDisplay> {
-        if self.search_query.is_empty() {
+        if self.search.is_empty() {
             self.hosts.iter().collect()
         } else {
-            let query = self.search_query.to_lowercase();
+            let query = self.search.value().to_lowercase();
             self.hosts
                 .iter()
                 .filter(|h| h.name.to_lowercase().contains(&query))
@@ -438,9 +2120,659 @@ impl App {
         }
     }
 
+    /// Hosts matching the search, in the chosen sort order
+    pub fn visible_hosts(&self) -> Vec<&HostDisplay> {
+        let mut hosts = self.filtered_hosts();
+        hosts.sort_by(|a, b| self.sort.compare(a, b));
+        hosts
+    }
+
     /// Check connection health and reconnect if needed
     #[allow(clippy::unused_self)]
     pub fn check_connection(&mut self) {
         // For now, just a placeholder. Could add reconnection logic later.
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::input::InputEdit;
+    use tendhost_api::requests::UpdateScope;
+    use tendhost_client::{ApiCall, MockTendhostApi};
+
+    fn host(
+        name: &str,
+        state: &str,
+        packages: Option<u32>,
+        updated_day: Option<u32>,
+    ) -> HostDisplay {
+        HostDisplay {
+            name: name.to_string(),
+            state: state.to_string(),
+            packages,
+            last_updated: updated_day
+                .map(|day| format!("2026-01-{day:02}T00:00:00Z").parse().unwrap()),
+            ..HostDisplay::default()
+        }
+    }
+
+    fn app() -> App {
+        let mut app = App::new("http://localhost:8080", KeyMap::default(), &[]);
+        app.hosts = vec![
+            host("web", "Idle", Some(3), Some(5)),
+            host("db", "Failed", Some(0), None),
+            host("cache", "PendingUpdates", Some(12), Some(2)),
+            host("proxy", "pending_updates", None, Some(9)),
+        ];
+        app
+    }
+
+    #[test]
+    fn test_parse_tags_splits_at_commas_and_whitespace() {
+        assert_eq!(parse_tags("prod,web"), ["prod", "web"]);
+        assert_eq!(parse_tags(" prod , web "), ["prod", "web"]);
+        assert_eq!(parse_tags("prod web\tdebian"), ["prod", "web", "debian"]);
+    }
+
+    #[test]
+    fn test_parse_tags_drops_empty_and_duplicate_tags() {
+        assert!(parse_tags("").is_empty());
+        assert!(parse_tags(" , ,, ").is_empty());
+        assert_eq!(parse_tags("web,,prod, web"), ["web", "prod"]);
+    }
+
+    fn names(app: &App) -> Vec<&str> {
+        app.visible_hosts()
+            .iter()
+            .map(|h| h.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_sort_columns_and_direction() {
+        let mut app = app();
+        assert_eq!(names(&app), ["cache", "db", "proxy", "web"]);
+
+        app.set_sort(HostSort {
+            column: SortColumn::State,
+            descending: false,
+        });
+        // REST and event spellings of a state sort together
+        assert_eq!(names(&app), ["db", "cache", "proxy", "web"]);
+
+        app.set_sort(HostSort {
+            column: SortColumn::Packages,
+            descending: true,
+        });
+        assert_eq!(names(&app), ["cache", "web", "db", "proxy"]);
+
+        app.set_sort(HostSort {
+            column: SortColumn::LastUpdate,
+            descending: false,
+        });
+        assert_eq!(names(&app), ["db", "cache", "web", "proxy"]);
+    }
+
+    #[tokio::test]
+    async fn test_sort_keys_cycle_and_keep_selection() {
+        let mut app = app();
+        app.selected_host = 3;
+        assert_eq!(app.selected_host_name(), Some("web"));
+
+        app.handle_action(Action::CycleSort).await.unwrap();
+        assert_eq!(app.sort.column, SortColumn::State);
+        assert_eq!(app.selected_host_name(), Some("web"));
+        assert_eq!(app.selected_host, 3);
+
+        app.handle_action(Action::ReverseSort).await.unwrap();
+        assert!(app.sort.descending);
+        assert_eq!(app.selected_host_name(), Some("web"));
+        assert_eq!(app.selected_host, 0);
+
+        for _ in 0..3 {
+            app.handle_action(Action::CycleSort).await.unwrap();
+        }
+        assert_eq!(app.sort.column, SortColumn::Name);
+    }
+
+    #[tokio::test]
+    async fn test_sort_composes_with_search() {
+        let mut app = app();
+        app.search = TextInput::with_value("e");
+        app.set_sort(HostSort {
+            column: SortColumn::Packages,
+            descending: true,
+        });
+        assert_eq!(names(&app), ["cache", "web"]);
+        app.handle_action(Action::Down).await.unwrap();
+        assert_eq!(app.selected_host_name(), Some("web"));
+        // Already on the last visible host
+        app.handle_action(Action::Down).await.unwrap();
+        assert_eq!(app.selected_host_name(), Some("web"));
+    }
+
+    #[test]
+    fn test_state_changes_keep_selection() {
+        let mut app = app();
+        app.set_sort(HostSort {
+            column: SortColumn::State,
+            descending: false,
+        });
+        app.selected_host = 3;
+        let pinned = app.selected_host_name().map(str::to_string);
+        app.handle_ws_event(&WsEvent::HostStateChanged {
+            host: "web".to_string(),
+            from: "idle".to_string(),
+            to: "failed".to_string(),
+            failure_kind: None,
+        });
+        app.reselect(pinned.as_deref());
+        assert_eq!(app.selected_host_name(), Some("web"));
+        assert_eq!(app.selected_host, 1);
+    }
+
+    fn details(name: &str, packages: &[(&str, &str)]) -> HostDetail {
+        let packages: Vec<_> = packages
+            .iter()
+            .map(|(name, version)| {
+                serde_json::json!({
+                    "name": name,
+                    "current_version": "1.0",
+                    "new_version": version,
+                })
+            })
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "state": "PendingUpdates",
+            "reachable": true,
+            "upgradable_packages": packages,
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_package_picker_selection() {
+        let mut app = app();
+        app.selected_host = 3;
+        app.host_details = Some(details(
+            "web",
+            &[("curl", "1.1"), ("openssl", "1.1"), ("vim", "1.1")],
+        ));
+
+        app.handle_action(Action::PickPackages).await.unwrap();
+        assert_eq!(app.input_mode(), InputMode::Packages);
+        for action in [Action::ToggleItem, Action::Last, Action::ToggleItem] {
+            app.handle_action(action).await.unwrap();
+        }
+        let picker = app.package_picker.as_ref().unwrap();
+        assert_eq!(picker.selected(), ["curl", "vim"]);
+
+        app.handle_action(Action::SelectAll).await.unwrap();
+        assert_eq!(app.package_picker.as_ref().unwrap().selected().len(), 3);
+
+        // Nothing checked is refused without closing the picker
+        app.handle_action(Action::SelectNone).await.unwrap();
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        let toast = app.toasts.current().unwrap();
+        assert_eq!(toast.message, "No packages checked");
+        assert_eq!(toast.level, EventLevel::Warning);
+        assert!(app.package_picker.is_some());
+
+        app.handle_action(Action::Back).await.unwrap();
+        assert!(app.package_picker.is_none());
+        assert_eq!(app.input_mode(), InputMode::Normal);
+    }
+
+    #[tokio::test]
+    async fn test_package_picker_resets_when_pending_set_changes() {
+        let mut app = app();
+        app.selected_host = 3;
+        app.host_details = Some(details("web", &[("curl", "1.1"), ("vim", "1.1")]));
+        app.handle_action(Action::PickPackages).await.unwrap();
+        app.handle_action(Action::ToggleItem).await.unwrap();
+
+        // The same pending set keeps the selection
+        let same = details("web", &[("curl", "1.1"), ("vim", "1.1")]);
+        app.handle_action(Action::PickerDetailsLoaded(Box::new(same)))
+            .await
+            .unwrap();
+        assert_eq!(app.package_picker.as_ref().unwrap().selected(), ["curl"]);
+
+        // A newer version of a package starts over
+        let changed = details("web", &[("curl", "1.2"), ("vim", "1.1")]);
+        app.handle_action(Action::PickerDetailsLoaded(Box::new(changed)))
+            .await
+            .unwrap();
+        let picker = app.package_picker.as_ref().unwrap();
+        assert!(picker.selected().is_empty());
+        assert_eq!(picker.host, "web");
+        assert_eq!(
+            app.host_details
+                .as_ref()
+                .unwrap()
+                .upgradable_packages
+                .as_ref()
+                .unwrap()[0]
+                .new_version,
+            "1.2"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_package_picker_needs_pending_updates() {
+        let mut app = app();
+        app.selected_host = 3;
+        app.host_details = Some(details("web", &[]));
+        app.handle_action(Action::PickPackages).await.unwrap();
+        assert!(app.package_picker.is_none());
+        assert!(app.toasts.current().is_some());
+    }
+
+    #[test]
+    fn test_state_summary() {
+        let app = app();
+        assert_eq!(
+            state_summary(&app.visible_hosts()),
+            "1 failed / 2 pending / 1 idle"
+        );
+        assert_eq!(state_summary(&[]), "");
+        let unknown = host("nas", "Mystery", None, None);
+        assert_eq!(state_summary(&[&unknown]), "1 mystery");
+    }
+
+    /// `app()` talking to a mock knowing its hosts
+    fn app_with_api(api: MockTendhostApi) -> (App, Arc<MockTendhostApi>) {
+        let mut app = app();
+        let api = Arc::new(
+            app.hosts
+                .iter()
+                .fold(api, |api, h| api.with_host_named(&h.name, &h.state)),
+        );
+        app.api = Some(api.clone());
+        (app, api)
+    }
+
+    #[tokio::test]
+    async fn test_replayed_events_are_logged_once_in_order() {
+        let state_changed = |from: &str, to: &str| WsEvent::HostStateChanged {
+            host: "web".to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            failure_kind: None,
+        };
+        let connected = |host: &str| WsEvent::HostConnected {
+            host: host.to_string(),
+        };
+        let missed = vec![
+            EventEnvelope::new(1, state_changed("idle", "querying")),
+            EventEnvelope::new(2, connected("db")),
+            EventEnvelope::new(3, state_changed("querying", "pending_updates")),
+        ];
+        let (mut app, api) = app_with_api(MockTendhostApi::new().with_events(missed.clone()));
+
+        app.replay_recent_events().await;
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::RecentEvents(REPLAYED_PER_HOST)]
+        ));
+        let web = app.hosts.iter().find(|h| h.name == "web").unwrap();
+        assert_eq!(web.state, "pending_updates");
+
+        // The stream delivers the last replayed event again
+        app.handle_ws_envelopes(vec![
+            missed[2].clone(),
+            EventEnvelope::new(4, connected("cache")),
+        ]);
+        let log: Vec<&str> = app.event_log.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            log,
+            [
+                "cache: Connected",
+                "web: querying -> pending_updates (replayed)",
+                "db: Connected (replayed)",
+                "web: idle -> querying (replayed)",
+            ]
+        );
+        assert_eq!(app.event_log[1].timestamp, missed[2].ts);
+    }
+
+    #[tokio::test]
+    async fn test_trigger_update_updates_selected_host() {
+        let (mut app, api) = app_with_api(MockTendhostApi::new());
+        app.selected_host = 3;
+
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        let calls = api.calls();
+        assert_eq!(calls.len(), 1);
+        assert!(matches!(
+            &calls[0],
+            ApiCall::UpdateHostPackages { name, dry_run: false } if name == "web"
+        ));
+        assert_eq!(app.event_log[0].level, EventLevel::Success);
+    }
+
+    #[tokio::test]
+    async fn test_preview_reports_blockers_without_updating() {
+        let preview = UpdatePreview {
+            host: "web".to_string(),
+            scope: UpdateScope::All,
+            packages: vec!["curl".to_string(), "linux-image-amd64".to_string()],
+            upgraded_count: 2,
+            new_count: 1,
+            removed_count: 0,
+            download_bytes: Some(3 * 1024 * 1024),
+            reboot_likely: true,
+            blockers: vec!["host is paused: web".to_string()],
+            warnings: Vec::new(),
+            previewed_at: Utc::now(),
+        };
+        let (mut app, api) = app_with_api(MockTendhostApi::new().with_preview(preview));
+        app.selected_host = 3;
+
+        app.handle_action(Action::PreviewUpdate).await.unwrap();
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::PreviewUpdate { name, scope: None }] if name == "web"
+        ));
+        let toast = app.toasts.current().unwrap();
+        assert_eq!(
+            toast.message,
+            "web: Update would upgrade 2 packages, install 1, download 3.0 MiB, \
+             likely needs a reboot; blocked: host is paused: web"
+        );
+        assert_eq!(toast.level, EventLevel::Warning);
+    }
+
+    #[tokio::test]
+    async fn test_package_picker_submits_checked_packages() {
+        let (mut app, api) = app_with_api(MockTendhostApi::new().failing(
+            "update_selected_packages",
+            409,
+            "host is busy",
+        ));
+        app.selected_host = 3;
+        app.host_details = Some(details("web", &[("curl", "1.1"), ("vim", "1.1")]));
+        app.handle_action(Action::PickPackages).await.unwrap();
+        app.handle_action(Action::ToggleItem).await.unwrap();
+
+        // A refused update keeps the picker open with the selection
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::UpdateSelectedPackages { name, packages }]
+                if name == "web" && packages == &["curl"]
+        ));
+        assert_eq!(
+            app.toasts.current().unwrap().message,
+            "Update failed on web: host is busy (409)"
+        );
+        assert_eq!(app.package_picker.as_ref().unwrap().selected(), ["curl"]);
+    }
+
+    #[tokio::test]
+    async fn test_acknowledge_only_contacts_daemon_for_failed_hosts() {
+        let (mut app, api) = app_with_api(MockTendhostApi::new());
+        app.selected_host = 3;
+        app.handle_action(Action::AcknowledgeFailure).await.unwrap();
+        assert!(api.calls().is_empty());
+
+        app.selected_host = 1;
+        app.handle_action(Action::AcknowledgeFailure).await.unwrap();
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::AcknowledgeHost(name)] if name == "db"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_toggle_pause() {
+        let (mut app, api) = app_with_api(MockTendhostApi::new());
+        app.selected_host = 3;
+        app.handle_action(Action::TogglePause).await.unwrap();
+        assert!(app.hosts.iter().find(|h| h.name == "web").unwrap().paused);
+        app.handle_action(Action::TogglePause).await.unwrap();
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::PauseHost(a), ApiCall::ResumeHost(b)] if a == "web" && b == "web"
+        ));
+
+        // Pauses by others show up through events
+        app.handle_ws_event(&WsEvent::HostPauseChanged {
+            host: "db".to_string(),
+            paused: true,
+        });
+        assert!(app.hosts.iter().find(|h| h.name == "db").unwrap().paused);
+    }
+
+    #[tokio::test]
+    async fn test_refused_update_hints_at_recovery() {
+        let busy =
+            r#"{"code":"HOST_BUSY","message":"invalid state transition from Failed to Updating"}"#;
+        let (mut app, _) =
+            app_with_api(MockTendhostApi::new().failing("update_host_packages", 409, busy));
+
+        // A failed host is recovered by retrying or acknowledging it
+        app.selected_host = 1;
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        let toast = app.toasts.current().unwrap();
+        assert_eq!(
+            toast.message,
+            "Update failed on db: invalid state transition from Failed to Updating (409)"
+        );
+        assert_eq!(
+            toast.hint.as_deref(),
+            Some("press R to retry or a to acknowledge")
+        );
+        assert_eq!(app.event_log[0].level, EventLevel::Error);
+
+        // Pressing it again counts the message instead of stacking it
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        assert_eq!(app.toasts.current().unwrap().count, 2);
+        assert_eq!(app.toasts.waiting(), 0);
+
+        for _ in 0..crate::ui::toast::TOAST_TICKS {
+            app.handle_action(Action::Tick).await.unwrap();
+        }
+        assert!(app.toasts.current().is_none());
+
+        // A busy host that isn't failed just has to be waited for
+        app.selected_host = 3;
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        assert_eq!(
+            app.toasts.current().unwrap().hint.as_deref(),
+            Some("wait for the running operation to finish")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_paused_host_hints_at_resuming() {
+        let paused = r#"{"code":"HOST_PAUSED","message":"host web is paused"}"#;
+        let (mut app, _) = app_with_api(MockTendhostApi::new().failing("reboot_host", 409, paused));
+        app.selected_host = 3;
+        app.handle_action(Action::TriggerReboot).await.unwrap();
+        let toast = app.toasts.current().unwrap();
+        assert_eq!(
+            toast.message,
+            "Reboot failed on web: host web is paused (409)"
+        );
+        assert_eq!(toast.hint.as_deref(), Some("press P to resume it"));
+    }
+
+    /// Mark the hosts at `positions` of the visible list
+    async fn mark(app: &mut App, positions: &[usize]) {
+        for &position in positions {
+            app.selected_host = position;
+            app.handle_action(Action::ToggleMark).await.unwrap();
+        }
+    }
+
+    /// Run the bulk action in progress to its end, as the main loop would
+    async fn run_bulk(app: &mut App) {
+        while app.bulk_running() {
+            app.process_bulk().await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_marks_survive_sorting_and_search() {
+        let mut app = app();
+        mark(&mut app, &[1, 3, 3, 3]).await;
+        assert_eq!(app.marked_hosts(), ["db", "web"]);
+
+        app.set_sort(HostSort {
+            column: SortColumn::Packages,
+            descending: true,
+        });
+        assert_eq!(app.marked_hosts(), ["web", "db"]);
+
+        // Hosts hidden by the search stay marked
+        app.handle_action(Action::StartSearch).await.unwrap();
+        app.handle_action(Action::Input(InputEdit::Insert('w')))
+            .await
+            .unwrap();
+        assert_eq!(names(&app), ["web"]);
+        assert_eq!(app.marked_hosts(), ["web", "db"]);
+
+        // Esc closes the search first, then clears the marks
+        app.handle_action(Action::Back).await.unwrap();
+        assert_eq!(app.marked_hosts(), ["web", "db"]);
+        app.handle_action(Action::Back).await.unwrap();
+        assert!(app.marked.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_range_select_marks_between_start_and_cursor() {
+        let mut app = app();
+        mark(&mut app, &[3]).await;
+        app.selected_host = 2;
+        app.handle_action(Action::RangeSelect).await.unwrap();
+        assert!(app.range_active());
+        app.handle_action(Action::Up).await.unwrap();
+        app.handle_action(Action::Up).await.unwrap();
+        assert_eq!(app.marked_hosts(), ["cache", "db", "proxy", "web"]);
+
+        // Moving back shrinks the range but keeps the earlier marks
+        app.handle_action(Action::Last).await.unwrap();
+        assert_eq!(app.marked_hosts(), ["proxy", "web"]);
+
+        // Ending the range keeps its marks
+        app.handle_action(Action::RangeSelect).await.unwrap();
+        app.handle_action(Action::First).await.unwrap();
+        assert!(!app.range_active());
+        assert_eq!(app.marked_hosts(), ["proxy", "web"]);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_update_reports_every_host() {
+        let busy = r#"{"code":"HOST_BUSY","message":"host proxy is busy"}"#;
+        let (mut app, api) = app_with_api(MockTendhostApi::new().failing_for(
+            "update_host_packages",
+            "proxy",
+            409,
+            busy,
+        ));
+        mark(&mut app, &[0, 2, 3]).await;
+
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        assert!(api.calls().is_empty());
+        run_bulk(&mut app).await;
+
+        let updated: Vec<_> = api
+            .calls()
+            .iter()
+            .filter_map(|call| call.host().map(str::to_string))
+            .collect();
+        assert_eq!(updated, ["cache", "proxy", "web"]);
+        let toast = app.toasts.current().unwrap();
+        assert_eq!(toast.message, "updated 2, failed 1: proxy busy");
+        assert_eq!(toast.level, EventLevel::Warning);
+        let log: Vec<&str> = app.event_log.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            log,
+            [
+                "Bulk update finished: updated 2, failed 1: proxy busy",
+                "Update started on web",
+                "Bulk update failed on proxy: host proxy is busy (409)",
+                "Update started on cache",
+                "Starting bulk update on 3 hosts",
+            ]
+        );
+        // Marks stay for another action
+        assert_eq!(app.marked.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_action_can_be_cancelled() {
+        let (mut app, api) = app_with_api(MockTendhostApi::new());
+        mark(&mut app, &[0, 1, 2]).await;
+        app.handle_action(Action::RetryHost).await.unwrap();
+        app.process_bulk().await.unwrap();
+
+        // Another bulk action has to wait
+        app.handle_action(Action::TriggerUpdate).await.unwrap();
+        assert_eq!(
+            app.toasts.current().unwrap().message,
+            "Bulk retry is still running; press Esc to cancel it"
+        );
+
+        app.handle_action(Action::Back).await.unwrap();
+        assert!(!app.bulk_running());
+        app.process_bulk().await.unwrap();
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::RetryHost(name)] if name == "cache"
+        ));
+        assert_eq!(
+            app.event_log[0].message,
+            "Bulk retry cancelled: retried 1 (cancelled, 2 not run)"
+        );
+        assert_eq!(app.marked.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_reboot_confirms_the_full_list() {
+        let (mut app, api) = app_with_api(MockTendhostApi::new());
+        mark(&mut app, &[3, 1]).await;
+
+        app.handle_action(Action::TriggerReboot).await.unwrap();
+        let pending = app.confirm.clone().unwrap();
+        assert_eq!(pending.prompt(), "Reboot 2 hosts: db, web?");
+        assert!(api.calls().is_empty());
+
+        app.handle_action(Action::Confirm).await.unwrap();
+        run_bulk(&mut app).await;
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::RebootHost(a), ApiCall::RebootHost(b)] if a == "db" && b == "web"
+        ));
+        let toast = app.toasts.current().unwrap();
+        assert_eq!(toast.message, "rebooted 2");
+        assert_eq!(toast.level, EventLevel::Success);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_acknowledge_skips_hosts_that_are_not_failed() {
+        let (mut app, api) = app_with_api(MockTendhostApi::new());
+        mark(&mut app, &[1, 3]).await;
+        app.handle_action(Action::AcknowledgeFailure).await.unwrap();
+        run_bulk(&mut app).await;
+
+        assert!(matches!(
+            &api.calls()[..],
+            [ApiCall::AcknowledgeHost(name)] if name == "db"
+        ));
+        assert!(
+            app.hosts
+                .iter()
+                .find(|h| h.name == "db")
+                .unwrap()
+                .acknowledged
+        );
+        assert_eq!(
+            app.toasts.current().unwrap().message,
+            "acknowledged 1, failed 1: web not failed"
+        );
+    }
+}
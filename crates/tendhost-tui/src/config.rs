@@ -54,6 +54,21 @@ pub fn selected_style() -> Style {
         .add_modifier(Modifier::BOLD)
 }
 
+/// Style for hosts that fail reachability probes
+pub fn unreachable_style() -> Style {
+    Style::default().fg(Color::Red)
+}
+
+/// Style for hosts whose SSH user lacks passwordless sudo
+pub fn no_sudo_style() -> Style {
+    Style::default().fg(Color::Yellow)
+}
+
+/// Style for failed hosts whose failure has been acknowledged
+pub fn acknowledged_failure_style() -> Style {
+    Style::default().fg(Color::Red).add_modifier(Modifier::DIM)
+}
+
 /// Normal row style
 pub fn normal_style() -> Style {
     Style::default()
@@ -6,6 +6,25 @@ use crossterm::event::{self, Event as CrosstermEvent, KeyCode, KeyEvent, KeyModi
 use tokio::sync::mpsc;
 
 use crate::action::Action;
+use crate::keymap::KeyMap;
+use crate::ui::input::InputEdit;
+
+/// Which key map applies, depending on what has focus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputMode {
+    /// Host list and details
+    Normal,
+    /// Confirmation prompt awaiting y/n
+    Confirm,
+    /// Host search or inventory package filter is taking text
+    Search,
+    /// Tag editor popup is open
+    EditTags,
+    /// Inventory view is open
+    Inventory,
+    /// Package picker popup is open
+    Packages,
+}
 
 /// Terminal event types
 #[derive(Debug, Clone)]
@@ -56,18 +75,15 @@ impl EventHandler {
 
                 // Poll for events
                 if event::poll(timeout).unwrap_or(false) {
-                    match event::read() {
-                        Ok(CrosstermEvent::Key(key)) => {
-                            if sender.send(Event::Key(key)).is_err() {
-                                break;
-                            }
-                        }
-                        Ok(CrosstermEvent::Resize(w, h)) => {
-                            if sender.send(Event::Resize(w, h)).is_err() {
-                                break;
-                            }
-                        }
-                        _ => {}
+                    let event = match event::read() {
+                        Ok(CrosstermEvent::Key(key)) => Some(Event::Key(key)),
+                        Ok(CrosstermEvent::Resize(w, h)) => Some(Event::Resize(w, h)),
+                        _ => None,
+                    };
+                    if let Some(event) = event
+                        && sender.send(event).is_err()
+                    {
+                        break;
                     }
                 }
 
@@ -89,42 +105,46 @@ impl EventHandler {
 }
 
 /// Convert a key event to an action
-pub fn key_to_action(key: KeyEvent, search_active: bool) -> Action {
-    if search_active {
-        match key.code {
-            KeyCode::Esc | KeyCode::Enter => Action::Back,
-            KeyCode::Backspace => Action::SearchBackspace,
-            KeyCode::Char(c) => Action::SearchInput(c),
+///
+/// Prompts and text inputs use fixed keys; the host list, inventory view
+/// and package picker use `keymap`.
+pub fn key_to_action(key: KeyEvent, mode: InputMode, keymap: &KeyMap) -> Action {
+    match mode {
+        InputMode::Confirm => match key.code {
+            KeyCode::Char('y' | 'Y') => Action::Confirm,
+            KeyCode::Char('n' | 'N') | KeyCode::Esc => Action::Back,
             _ => Action::None,
-        }
-    } else {
-        match key.code {
-            // Quit
-            KeyCode::Char('q') => Action::Quit,
-            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Action::Quit,
-
-            // Navigation
-            KeyCode::Up | KeyCode::Char('k') => Action::Up,
-            KeyCode::Down | KeyCode::Char('j') => Action::Down,
-            KeyCode::Char('g') => Action::First,
-            KeyCode::Char('G') => Action::Last,
-            KeyCode::Enter => Action::Select,
+        },
+        InputMode::Search => match key.code {
+            KeyCode::Esc | KeyCode::Enter => Action::Back,
+            _ => text_edit(key).map_or(Action::None, Action::Input),
+        },
+        InputMode::EditTags => match key.code {
             KeyCode::Esc => Action::Back,
-            KeyCode::Tab => Action::ToggleFocus,
-
-            // Actions
-            KeyCode::Char('u') => Action::TriggerUpdate,
-            KeyCode::Char('U') => Action::TriggerFleetUpdate,
-            KeyCode::Char('r') => Action::TriggerReboot,
-            KeyCode::Char('R') => Action::RetryHost,
-            KeyCode::Char('a') => Action::AcknowledgeFailure,
-            KeyCode::Char('i') => Action::RefreshInventory,
-
-            // Help and search
-            KeyCode::Char('?') => Action::Help,
-            KeyCode::Char('/') => Action::StartSearch,
-
-            _ => Action::None,
+            KeyCode::Enter => Action::Submit,
+            _ => text_edit(key).map_or(Action::None, Action::Input),
+        },
+        InputMode::Normal | InputMode::Inventory | InputMode::Packages => {
+            // Always available, whatever the keymap says
+            if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+                return Action::Quit;
+            }
+            keymap.action(&key, mode).unwrap_or(Action::None)
         }
     }
 }
+
+/// Map a key to a text input edit, shared by all text inputs
+fn text_edit(key: KeyEvent) -> Option<InputEdit> {
+    let edit = match key.code {
+        KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => InputEdit::Insert(c),
+        KeyCode::Backspace => InputEdit::Backspace,
+        KeyCode::Delete => InputEdit::Delete,
+        KeyCode::Left => InputEdit::Left,
+        KeyCode::Right => InputEdit::Right,
+        KeyCode::Home => InputEdit::Home,
+        KeyCode::End => InputEdit::End,
+        _ => return None,
+    };
+    Some(edit)
+}
@@ -0,0 +1,773 @@
+//! Configurable key bindings
+//!
+//! Bindings are read from the `[keys]` table of `tui.toml`, mapping action
+//! names to one key descriptor or a list of them:
+//!
+//! ```toml
+//! [keys]
+//! update = "x"
+//! reboot = ["ctrl+r", "f5"]
+//! ```
+//!
+//! Descriptors are a key, optionally prefixed by `ctrl+`, `alt+` and
+//! `shift+`. Keys are single characters or names such as `enter`, `esc`,
+//! `tab`, `space`, `up`, `pagedown` and `f1`–`f12`. Actions left out keep
+//! their default keys. `ctrl+c` always quits.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::action::Action;
+use crate::event::InputMode;
+
+/// A key with modifiers, as written in the keymap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl KeyBinding {
+    /// Parse a descriptor such as `"q"`, `"shift+u"`, `"ctrl+c"` or `"f5"`
+    ///
+    /// Shift on a character selects its uppercase form, so `"shift+g"` and
+    /// `"G"` are the same binding.
+    pub fn parse(descriptor: &str) -> Result<Self, String> {
+        // A lone space is a key, not padding
+        let mut rest = match descriptor.trim() {
+            "" => descriptor,
+            trimmed => trimmed,
+        };
+        let mut modifiers = KeyModifiers::NONE;
+        loop {
+            let lower = rest.to_ascii_lowercase();
+            let (modifier, len) = if lower.starts_with("ctrl+") {
+                (KeyModifiers::CONTROL, 5)
+            } else if lower.starts_with("alt+") {
+                (KeyModifiers::ALT, 4)
+            } else if lower.starts_with("shift+") {
+                (KeyModifiers::SHIFT, 6)
+            } else {
+                break;
+            };
+            modifiers |= modifier;
+            rest = &rest[len..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (None, _) => return Err(format!("'{descriptor}': missing key")),
+            (Some(c), None) => KeyCode::Char(c),
+            _ => named_key(&rest.to_ascii_lowercase())
+                .ok_or_else(|| format!("'{descriptor}': unknown key '{rest}'"))?,
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+
+    /// Normalize shifted characters and shift+tab
+    fn new(code: KeyCode, mut modifiers: KeyModifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+        Self { code, modifiers }
+    }
+
+    /// Whether a terminal key event is this binding
+    ///
+    /// Terminals disagree on reporting shift with characters, so only the
+    /// character itself and ctrl/alt are compared.
+    pub fn matches(&self, key: &KeyEvent) -> bool {
+        let pressed = Self::new(
+            key.code,
+            key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT),
+        );
+        let pressed = match pressed.code {
+            KeyCode::Char(_) => Self {
+                modifiers: pressed.modifiers - KeyModifiers::SHIFT,
+                ..pressed
+            },
+            _ => pressed,
+        };
+        pressed == *self
+    }
+}
+
+fn named_key(name: &str) -> Option<KeyCode> {
+    let code = match name {
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "space" => KeyCode::Char(' '),
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        _ => {
+            let n: u8 = name.strip_prefix('f')?.parse().ok()?;
+            if !(1..=12).contains(&n) {
+                return None;
+            }
+            KeyCode::F(n)
+        }
+    };
+    Some(code)
+}
+
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            f.write_str("Shift+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Up => f.write_str("↑"),
+            KeyCode::Down => f.write_str("↓"),
+            KeyCode::Left => f.write_str("←"),
+            KeyCode::Right => f.write_str("→"),
+            KeyCode::BackTab => f.write_str("Shift+Tab"),
+            KeyCode::PageUp => f.write_str("PgUp"),
+            KeyCode::PageDown => f.write_str("PgDn"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => write!(f, "{code}"),
+        }
+    }
+}
+
+/// Help popup section a command is listed under
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Navigation,
+    Actions,
+    Inventory,
+    Packages,
+    General,
+}
+
+impl Section {
+    pub const ALL: [Self; 5] = [
+        Self::Navigation,
+        Self::Actions,
+        Self::Inventory,
+        Self::Packages,
+        Self::General,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Self::Navigation => "Navigation",
+            Self::Actions => "Actions",
+            Self::Inventory => "Inventory",
+            Self::Packages => "Package picker",
+            Self::General => "General",
+        }
+    }
+}
+
+/// A rebindable command, named as in the `[keys]` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Quit,
+    Up,
+    Down,
+    First,
+    Last,
+    Select,
+    Back,
+    FocusNext,
+    Sort,
+    ReverseSort,
+    Mark,
+    RangeSelect,
+    Update,
+    Preview,
+    FleetUpdate,
+    Cancel,
+    Reboot,
+    Retry,
+    Acknowledge,
+    Pause,
+    Inventory,
+    EditTags,
+    Packages,
+    NextSection,
+    PrevSection,
+    Toggle,
+    SelectAll,
+    SelectNone,
+    Search,
+    Help,
+}
+
+impl Command {
+    pub const ALL: [Self; 30] = [
+        Self::Quit,
+        Self::Up,
+        Self::Down,
+        Self::First,
+        Self::Last,
+        Self::Select,
+        Self::Back,
+        Self::FocusNext,
+        Self::Sort,
+        Self::ReverseSort,
+        Self::Mark,
+        Self::RangeSelect,
+        Self::Update,
+        Self::Preview,
+        Self::FleetUpdate,
+        Self::Cancel,
+        Self::Reboot,
+        Self::Retry,
+        Self::Acknowledge,
+        Self::Pause,
+        Self::Inventory,
+        Self::EditTags,
+        Self::Packages,
+        Self::NextSection,
+        Self::PrevSection,
+        Self::Toggle,
+        Self::SelectAll,
+        Self::SelectNone,
+        Self::Search,
+        Self::Help,
+    ];
+
+    /// Name used in the `[keys]` table
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Quit => "quit",
+            Self::Up => "up",
+            Self::Down => "down",
+            Self::First => "first",
+            Self::Last => "last",
+            Self::Select => "select",
+            Self::Back => "back",
+            Self::FocusNext => "focus_next",
+            Self::Sort => "sort",
+            Self::ReverseSort => "reverse_sort",
+            Self::Mark => "mark",
+            Self::RangeSelect => "range_select",
+            Self::Update => "update",
+            Self::Preview => "preview",
+            Self::FleetUpdate => "fleet_update",
+            Self::Cancel => "cancel",
+            Self::Reboot => "reboot",
+            Self::Retry => "retry",
+            Self::Acknowledge => "acknowledge",
+            Self::Pause => "pause",
+            Self::Inventory => "inventory",
+            Self::EditTags => "edit_tags",
+            Self::Packages => "packages",
+            Self::NextSection => "next_section",
+            Self::PrevSection => "prev_section",
+            Self::Toggle => "toggle",
+            Self::SelectAll => "select_all",
+            Self::SelectNone => "select_none",
+            Self::Search => "search",
+            Self::Help => "help",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    /// Description shown in the help popup
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Quit => "Quit",
+            Self::Up => "Move up",
+            Self::Down => "Move down",
+            Self::First => "Jump to first",
+            Self::Last => "Jump to last",
+            Self::Select => "Show host details",
+            Self::Back => "Close popup/clear search/marks",
+            Self::FocusNext => "Switch panel focus",
+            Self::Sort => "Sort hosts by next column",
+            Self::ReverseSort => "Reverse sort order",
+            Self::Mark => "Mark/unmark host",
+            Self::RangeSelect => "Mark a range of hosts",
+            Self::Update => "Trigger update",
+            Self::Preview => "Preview update (dry run)",
+            Self::FleetUpdate => "Fleet update",
+            Self::Cancel => "Cancel running update",
+            Self::Reboot => "Reboot host",
+            Self::Retry => "Retry failed host",
+            Self::Acknowledge => "Acknowledge failure",
+            Self::Pause => "Pause/resume automation",
+            Self::Inventory => "Show/refresh inventory",
+            Self::EditTags => "Edit tags",
+            Self::Packages => "Pick packages to update",
+            Self::NextSection => "Next inventory section",
+            Self::PrevSection => "Previous inventory section",
+            Self::Toggle => "Check/uncheck package",
+            Self::SelectAll => "Check all packages",
+            Self::SelectNone => "Uncheck all packages",
+            Self::Search => "Search hosts/filter packages",
+            Self::Help => "Toggle help",
+        }
+    }
+
+    pub fn section(self) -> Section {
+        match self {
+            Self::Up
+            | Self::Down
+            | Self::First
+            | Self::Last
+            | Self::Select
+            | Self::Back
+            | Self::FocusNext
+            | Self::Sort
+            | Self::ReverseSort
+            | Self::Mark
+            | Self::RangeSelect => Section::Navigation,
+            Self::Update
+            | Self::Preview
+            | Self::FleetUpdate
+            | Self::Cancel
+            | Self::Reboot
+            | Self::Retry
+            | Self::Acknowledge
+            | Self::Pause
+            | Self::Inventory
+            | Self::EditTags
+            | Self::Packages => Section::Actions,
+            Self::NextSection | Self::PrevSection => Section::Inventory,
+            Self::Toggle | Self::SelectAll | Self::SelectNone => Section::Packages,
+            Self::Search | Self::Help | Self::Quit => Section::General,
+        }
+    }
+
+    fn default_keys(self) -> &'static [&'static str] {
+        match self {
+            Self::Quit => &["q"],
+            Self::Up => &["k", "up"],
+            Self::Down => &["j", "down"],
+            Self::First => &["g"],
+            Self::Last => &["G"],
+            Self::Select => &["enter"],
+            Self::Back => &["esc"],
+            Self::FocusNext => &["tab"],
+            Self::Sort => &["s"],
+            Self::ReverseSort => &["S"],
+            Self::Mark => &["space"],
+            Self::RangeSelect => &["v"],
+            Self::Update => &["u"],
+            Self::Preview => &["d"],
+            Self::FleetUpdate => &["U"],
+            Self::Cancel => &["c"],
+            Self::Reboot => &["r"],
+            Self::Retry => &["R"],
+            Self::Acknowledge => &["a"],
+            Self::Pause => &["P"],
+            Self::Inventory => &["i"],
+            Self::EditTags => &["t"],
+            Self::Packages => &["p"],
+            Self::NextSection => &["tab", "l", "right"],
+            Self::PrevSection => &["backtab", "h", "left"],
+            Self::Toggle => &["space"],
+            Self::SelectAll => &["a"],
+            Self::SelectNone => &["n"],
+            Self::Search => &["/"],
+            Self::Help => &["?"],
+        }
+    }
+
+    /// Whether the command is available in `mode`
+    fn applies_in(self, mode: InputMode) -> bool {
+        match mode {
+            InputMode::Normal => !matches!(
+                self,
+                Self::NextSection
+                    | Self::PrevSection
+                    | Self::Toggle
+                    | Self::SelectAll
+                    | Self::SelectNone
+            ),
+            InputMode::Inventory => matches!(
+                self,
+                Self::Quit
+                    | Self::Up
+                    | Self::Down
+                    | Self::First
+                    | Self::Last
+                    | Self::Back
+                    | Self::NextSection
+                    | Self::PrevSection
+                    | Self::Inventory
+                    | Self::Search
+                    | Self::Help
+            ),
+            // The update key submits the checked packages
+            InputMode::Packages => matches!(
+                self,
+                Self::Quit
+                    | Self::Up
+                    | Self::Down
+                    | Self::First
+                    | Self::Last
+                    | Self::Back
+                    | Self::Update
+                    | Self::Toggle
+                    | Self::SelectAll
+                    | Self::SelectNone
+                    | Self::Help
+            ),
+            InputMode::Confirm | InputMode::Search | InputMode::EditTags => false,
+        }
+    }
+
+    fn action(self) -> Action {
+        match self {
+            Self::Quit => Action::Quit,
+            Self::Up => Action::Up,
+            Self::Down => Action::Down,
+            Self::First => Action::First,
+            Self::Last => Action::Last,
+            Self::Select => Action::Select,
+            Self::Back => Action::Back,
+            Self::FocusNext => Action::ToggleFocus,
+            Self::Sort => Action::CycleSort,
+            Self::ReverseSort => Action::ReverseSort,
+            Self::Mark => Action::ToggleMark,
+            Self::RangeSelect => Action::RangeSelect,
+            Self::Update => Action::TriggerUpdate,
+            Self::Preview => Action::PreviewUpdate,
+            Self::FleetUpdate => Action::TriggerFleetUpdate,
+            Self::Cancel => Action::CancelUpdate,
+            Self::Reboot => Action::TriggerReboot,
+            Self::Retry => Action::RetryHost,
+            Self::Acknowledge => Action::AcknowledgeFailure,
+            Self::Pause => Action::TogglePause,
+            Self::Inventory => Action::RefreshInventory,
+            Self::EditTags => Action::EditTags,
+            Self::Packages => Action::PickPackages,
+            Self::NextSection => Action::NextSection,
+            Self::PrevSection => Action::PrevSection,
+            Self::Toggle => Action::ToggleItem,
+            Self::SelectAll => Action::SelectAll,
+            Self::SelectNone => Action::SelectNone,
+            Self::Search => Action::StartSearch,
+            Self::Help => Action::Help,
+        }
+    }
+}
+
+/// Active key bindings for every command
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(Command, Vec<KeyBinding>)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = Command::ALL
+            .into_iter()
+            .map(|command| {
+                let keys = command
+                    .default_keys()
+                    .iter()
+                    .map(|d| KeyBinding::parse(d).expect("default key descriptors are valid"))
+                    .collect();
+                (command, keys)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl KeyMap {
+    /// `$XDG_CONFIG_HOME/tendhost/tui.toml`, falling back to `~/.config`
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(config_dir.join("tendhost").join("tui.toml"))
+    }
+
+    /// Load the keymap from `path`, returning warnings about ignored entries
+    ///
+    /// A missing file gives the defaults without warnings.
+    pub fn load(path: &Path) -> (Self, Vec<String>) {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (Self::default(), Vec::new()),
+            Err(e) => (
+                Self::default(),
+                vec![format!("cannot read {}: {e}", path.display())],
+            ),
+        }
+    }
+
+    /// Parse the `[keys]` table of a `tui.toml`
+    pub fn parse(content: &str) -> (Self, Vec<String>) {
+        let mut keymap = Self::default();
+        let mut warnings = Vec::new();
+
+        let table = match content.parse::<toml::Table>() {
+            Ok(table) => table,
+            Err(e) => return (keymap, vec![format!("invalid keymap file: {e}")]),
+        };
+        let Some(keys) = table.get("keys") else {
+            return (keymap, warnings);
+        };
+        let Some(keys) = keys.as_table() else {
+            return (keymap, vec!["[keys] must be a table".to_string()]);
+        };
+
+        for (name, value) in keys {
+            let Some(command) = Command::from_name(name) else {
+                warnings.push(format!("unknown action '{name}'"));
+                continue;
+            };
+            let descriptors: Vec<&str> = match value {
+                toml::Value::String(s) => vec![s.as_str()],
+                toml::Value::Array(values) => values.iter().filter_map(|v| v.as_str()).collect(),
+                _ => {
+                    warnings.push(format!("{name}: expected a key or a list of keys"));
+                    continue;
+                }
+            };
+
+            let mut bindings = Vec::new();
+            for descriptor in descriptors {
+                match KeyBinding::parse(descriptor) {
+                    Ok(binding) => bindings.push(binding),
+                    Err(e) => warnings.push(format!("{name}: {e}")),
+                }
+            }
+            // Keep the defaults rather than leaving an action unreachable
+            if !bindings.is_empty() {
+                keymap.set(command, bindings);
+            }
+        }
+
+        (keymap, warnings)
+    }
+
+    fn set(&mut self, command: Command, keys: Vec<KeyBinding>) {
+        if let Some((_, bindings)) = self.bindings.iter_mut().find(|(c, _)| *c == command) {
+            *bindings = keys;
+        }
+    }
+
+    /// Keys bound to a command
+    pub fn keys(&self, command: Command) -> &[KeyBinding] {
+        self.bindings
+            .iter()
+            .find(|(c, _)| *c == command)
+            .map_or(&[], |(_, keys)| keys.as_slice())
+    }
+
+    /// Keys bound to a command joined for display, e.g. `k/↑`
+    pub fn label(&self, command: Command) -> String {
+        self.keys(command)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// First key bound to a command, for compact hints
+    pub fn primary(&self, command: Command) -> String {
+        self.keys(command)
+            .first()
+            .map(ToString::to_string)
+            .unwrap_or_default()
+    }
+
+    /// Action bound to a key in `mode`
+    pub fn action(&self, key: &KeyEvent, mode: InputMode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .filter(|(command, _)| command.applies_in(mode))
+            .find(|(_, keys)| keys.iter().any(|k| k.matches(key)))
+            .map(|(command, _)| command.action())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_descriptors() {
+        let parse = |d| KeyBinding::parse(d).unwrap();
+
+        assert_eq!(
+            parse("q"),
+            KeyBinding::new(KeyCode::Char('q'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("ctrl+c"),
+            KeyBinding::new(KeyCode::Char('c'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(parse("shift+u"), parse("U"));
+        assert_eq!(parse("Shift+Tab"), parse("backtab"));
+        assert_eq!(
+            parse("F5"),
+            KeyBinding::new(KeyCode::F(5), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("ctrl+alt+delete"),
+            KeyBinding::new(KeyCode::Delete, KeyModifiers::CONTROL | KeyModifiers::ALT)
+        );
+        assert_eq!(
+            parse("+"),
+            KeyBinding::new(KeyCode::Char('+'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            parse("ctrl++"),
+            KeyBinding::new(KeyCode::Char('+'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(parse("space"), parse(" "));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_descriptors() {
+        assert!(KeyBinding::parse("").is_err());
+        assert!(KeyBinding::parse("ctrl+").is_err());
+        assert!(KeyBinding::parse("f13").is_err());
+        assert!(KeyBinding::parse("hyper+x").is_err());
+        assert!(KeyBinding::parse("enterr").is_err());
+    }
+
+    #[test]
+    fn test_matches_ignores_reported_shift_on_chars() {
+        let binding = KeyBinding::parse("shift+g").unwrap();
+        assert!(binding.matches(&key(KeyCode::Char('G'), KeyModifiers::SHIFT)));
+        assert!(binding.matches(&key(KeyCode::Char('G'), KeyModifiers::NONE)));
+        assert!(!binding.matches(&key(KeyCode::Char('g'), KeyModifiers::NONE)));
+
+        let ctrl_r = KeyBinding::parse("ctrl+r").unwrap();
+        assert!(ctrl_r.matches(&key(KeyCode::Char('r'), KeyModifiers::CONTROL)));
+        assert!(!ctrl_r.matches(&key(KeyCode::Char('r'), KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn test_keymap_overrides_and_defaults() {
+        let (keymap, warnings) = KeyMap::parse(
+            r#"
+            [keys]
+            update = "x"
+            reboot = ["ctrl+r", "f5"]
+            retry = "hyper+r"
+            launch = "l"
+            "#,
+        );
+
+        assert_eq!(
+            warnings,
+            [
+                "unknown action 'launch'",
+                "retry: 'hyper+r': unknown key 'hyper+r'"
+            ]
+        );
+        let normal = InputMode::Normal;
+        assert!(matches!(
+            keymap.action(&key(KeyCode::Char('x'), KeyModifiers::NONE), normal),
+            Some(Action::TriggerUpdate)
+        ));
+        assert!(
+            keymap
+                .action(&key(KeyCode::Char('u'), KeyModifiers::NONE), normal)
+                .is_none()
+        );
+        assert!(
+            keymap
+                .action(&key(KeyCode::Char('r'), KeyModifiers::NONE), normal)
+                .is_none()
+        );
+        assert!(matches!(
+            keymap.action(&key(KeyCode::F(5), KeyModifiers::NONE), normal),
+            Some(Action::TriggerReboot)
+        ));
+        // Invalid entries keep the defaults
+        assert!(matches!(
+            keymap.action(&key(KeyCode::Char('R'), KeyModifiers::SHIFT), normal),
+            Some(Action::RetryHost)
+        ));
+        assert_eq!(keymap.label(Command::Reboot), "Ctrl+r/F5");
+    }
+
+    #[test]
+    fn test_bindings_depend_on_mode() {
+        let keymap = KeyMap::default();
+        let tab = key(KeyCode::Tab, KeyModifiers::NONE);
+
+        assert!(matches!(
+            keymap.action(&tab, InputMode::Normal),
+            Some(Action::ToggleFocus)
+        ));
+        assert!(matches!(
+            keymap.action(&tab, InputMode::Inventory),
+            Some(Action::NextSection)
+        ));
+        assert!(
+            keymap
+                .action(
+                    &key(KeyCode::Char('u'), KeyModifiers::NONE),
+                    InputMode::Inventory
+                )
+                .is_none()
+        );
+
+        let a = key(KeyCode::Char('a'), KeyModifiers::NONE);
+        assert!(matches!(
+            keymap.action(&a, InputMode::Normal),
+            Some(Action::AcknowledgeFailure)
+        ));
+        assert!(matches!(
+            keymap.action(&a, InputMode::Packages),
+            Some(Action::SelectAll)
+        ));
+        assert!(matches!(
+            keymap.action(
+                &key(KeyCode::Char(' '), KeyModifiers::NONE),
+                InputMode::Packages
+            ),
+            Some(Action::ToggleItem)
+        ));
+        assert!(matches!(
+            keymap.action(
+                &key(KeyCode::Char(' '), KeyModifiers::NONE),
+                InputMode::Normal
+            ),
+            Some(Action::ToggleMark)
+        ));
+    }
+}
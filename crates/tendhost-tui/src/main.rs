@@ -3,6 +3,7 @@
 //! Terminal user interface for monitoring and controlling tendhost daemon
 
 use std::io;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use clap::Parser;
@@ -18,10 +19,13 @@ mod action;
 mod app;
 mod config;
 mod event;
+mod keymap;
 mod ui;
 
 use app::App;
 use event::EventHandler;
+use keymap::KeyMap;
+use tendhost_client::VersionCheck;
 
 /// tendhost Terminal UI
 #[derive(Parser, Debug)]
@@ -38,6 +42,24 @@ struct Args {
     /// Enable debug logging to file
     #[arg(long)]
     debug: bool,
+
+    /// Key binding config (defaults to ~/.config/tendhost/tui.toml)
+    #[arg(long)]
+    keymap: Option<PathBuf>,
+
+    /// Talk to a daemon whose API version this TUI doesn't support
+    ///
+    /// Its replies may be misread; only for when the differences are known
+    /// not to matter.
+    #[arg(long)]
+    ignore_api_version: bool,
+
+    /// Have the daemon send events in batches every this many milliseconds
+    ///
+    /// Saves bandwidth and redraws on slow links; host failures still
+    /// arrive right away. At most 5000.
+    #[arg(long)]
+    event_batch_ms: Option<u64>,
 }
 
 #[tokio::main]
@@ -65,7 +87,15 @@ async fn main() -> Result<()> {
 
     // Create app and run
     let tick_rate = Duration::from_millis(args.tick_rate);
-    let mut app = App::new(&args.server);
+    let (keymap, warnings) = match args.keymap.or_else(KeyMap::default_path) {
+        Some(path) => KeyMap::load(&path),
+        None => (KeyMap::default(), Vec::new()),
+    };
+    let mut app = App::new(&args.server, keymap, &warnings);
+    if args.ignore_api_version {
+        app.version_check = VersionCheck::Ignore;
+    }
+    app.event_batch = args.event_batch_ms.map(Duration::from_millis);
     let result = run_app(&mut terminal, &mut app, tick_rate).await;
 
     // Restore terminal
@@ -111,7 +141,9 @@ where
             event = events.next() => {
                 if let Some(event) = event {
                     let action = match event {
-                        event::Event::Key(key) => event::key_to_action(key, app.search_active),
+                        event::Event::Key(key) => {
+                            event::key_to_action(key, app.input_mode(), &app.keymap)
+                        }
                         event::Event::Resize(_, _) => action::Action::Render,
                         event::Event::Tick => action::Action::Tick,
                     };
@@ -122,11 +154,19 @@ where
             _ = check_connection_interval.tick() => {
                 app.check_connection();
             }
+            // Keep a bulk action going without waiting for input
+            () = std::future::ready(()), if app.bulk_running() => {}
         }
 
+        // Send the next request of a bulk action
+        app.process_bulk().await?;
+
         // Process WebSocket events
         app.process_ws_events().await?;
 
+        // Process finished background requests
+        app.process_background_actions().await?;
+
         if app.should_quit() {
             break;
         }
@@ -0,0 +1,97 @@
+//! Scrollable checklist widget
+//!
+//! Used by the package picker. The list keeps the cursor and check marks;
+//! rendering scrolls just far enough to keep the cursor visible, so lists
+//! with hundreds of rows work in any popup height.
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, List, ListItem, ListState};
+
+use crate::config;
+
+/// Rows with a check mark each and a cursor
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Checklist {
+    labels: Vec<String>,
+    checked: Vec<bool>,
+    cursor: usize,
+}
+
+impl Checklist {
+    /// Unchecked rows with the cursor on the first
+    pub fn new(labels: Vec<String>) -> Self {
+        let checked = vec![false; labels.len()];
+        Self {
+            labels,
+            checked,
+            cursor: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn up(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub fn down(&mut self) {
+        if self.cursor + 1 < self.len() {
+            self.cursor += 1;
+        }
+    }
+
+    pub fn first(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub fn last(&mut self) {
+        self.cursor = self.len().saturating_sub(1);
+    }
+
+    /// Check or uncheck the row under the cursor
+    pub fn toggle(&mut self) {
+        if let Some(checked) = self.checked.get_mut(self.cursor) {
+            *checked = !*checked;
+        }
+    }
+
+    /// Check or uncheck every row
+    pub fn set_all(&mut self, checked: bool) {
+        self.checked.fill(checked);
+    }
+
+    /// Indices of the checked rows, in list order
+    pub fn checked(&self) -> impl Iterator<Item = usize> + '_ {
+        self.checked
+            .iter()
+            .enumerate()
+            .filter_map(|(i, checked)| checked.then_some(i))
+    }
+
+    /// Render the rows inside `block`
+    pub fn render(&self, frame: &mut Frame, area: Rect, block: Block) {
+        let items: Vec<ListItem> = self
+            .labels
+            .iter()
+            .zip(&self.checked)
+            .map(|(label, checked)| {
+                let mark = if *checked { "[x]" } else { "[ ]" };
+                ListItem::new(format!("{mark} {label}"))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(block)
+            .highlight_style(config::selected_style())
+            .highlight_symbol("▸ ");
+
+        let mut state = ListState::default();
+        state.select(Some(self.cursor).filter(|_| !self.is_empty()));
+        frame.render_stateful_widget(list, area, &mut state);
+    }
+}
@@ -0,0 +1,50 @@
+//! Confirmation popup widget
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+
+use crate::app::PendingConfirm;
+
+/// Render the confirmation popup
+pub fn render(frame: &mut Frame, pending: &PendingConfirm) {
+    let prompt = pending.prompt();
+    let text = vec![
+        Line::from(""),
+        Line::from(format!("  {prompt}")),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("  "),
+            Span::styled("[y]", Style::default().fg(Color::Green)),
+            Span::raw(" Yes   "),
+            Span::styled("[n]", Style::default().fg(Color::Red)),
+            Span::raw(" No"),
+        ]),
+    ];
+
+    // Calculate popup area (centered, 50 wide), tall enough for a prompt
+    // listing many hosts
+    let area = frame.area();
+    let popup_width = 50.min(area.width.saturating_sub(4));
+    let prompt_width = usize::from(popup_width.saturating_sub(4)).max(1);
+    let prompt_lines = (prompt.chars().count() + 2).div_ceil(prompt_width);
+    let popup_height = u16::try_from(prompt_lines + 6)
+        .unwrap_or(u16::MAX)
+        .min(area.height.saturating_sub(4));
+    let x = (area.width.saturating_sub(popup_width)) / 2;
+    let y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(x, y, popup_width, popup_height);
+
+    // Clear the area behind the popup
+    frame.render_widget(Clear, popup_area);
+
+    let paragraph = Paragraph::new(text)
+        .block(
+            Block::default()
+                .title(" Confirm ")
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(Color::Yellow)),
+        )
+        .wrap(Wrap { trim: false });
+
+    frame.render_widget(paragraph, popup_area);
+}
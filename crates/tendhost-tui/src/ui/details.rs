@@ -3,6 +3,8 @@
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph, Wrap};
 
+use tendhost_api::responses::{ConnectionStatsInfo, HostDetail, UpdateHistoryEntry};
+
 use crate::app::{App, Focus};
 use crate::config;
 
@@ -20,8 +22,13 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     };
 
     let content = if let Some(details) = &app.host_details {
-        format_details(details)
-    } else if let Some(host) = app.hosts.get(app.selected_host) {
+        let mut content = format_details(details);
+        if !app.update_history.is_empty() {
+            content.push_str("\n\n");
+            content.push_str(&format_update_history(&app.update_history));
+        }
+        content
+    } else if let Some(host) = app.selected() {
         format!(
             "Host: {}\nState: {}\nOS: {}\n\nPress Enter to load details",
             host.name, host.state, host.os
@@ -39,76 +46,176 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
         )
         .wrap(Wrap { trim: true });
 
-    frame.render_widget(paragraph, area);
-}
+    let Some(output) = app.failure_output() else {
+        frame.render_widget(paragraph, area);
+        return;
+    };
+
+    let output_lines = output.lines().count();
+    let output_height = u16::try_from(output_lines + 2)
+        .unwrap_or(u16::MAX)
+        .min(area.height / 2);
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(output_height)])
+        .split(area);
+    frame.render_widget(paragraph, chunks[0]);
 
-/// Format host details JSON into readable text
-fn format_details(details: &serde_json::Value) -> String {
-    let mut lines = Vec::new();
+    // Unwrapped so one row is one line; scrolling counts up from the end
+    let visible = usize::from(output_height.saturating_sub(2));
+    let offset = output_lines
+        .saturating_sub(visible)
+        .saturating_sub(app.failure_output_scroll);
+    let output_title = if app.focus == Focus::Details {
+        format!(" Failure output ({output_lines} lines, ↑/↓ to scroll) ")
+    } else {
+        format!(" Failure output ({output_lines} lines) ")
+    };
+    let output_panel = Paragraph::new(output)
+        .block(
+            Block::default()
+                .title(output_title)
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        )
+        .scroll((u16::try_from(offset).unwrap_or(u16::MAX), 0));
+    frame.render_widget(output_panel, chunks[1]);
+}
 
-    // Extract common fields
-    if let Some(name) = details.get("name").and_then(|v| v.as_str()) {
-        lines.push(format!("Name: {name}"));
+/// Format host details into readable text
+fn format_details(details: &HostDetail) -> String {
+    let mut lines = vec![
+        format!("Name: {}", details.name),
+        format!("State: {}", details.state),
+    ];
+    if let Some(config) = &details.config {
+        lines.push(format!("Address: {}@{}", config.user, config.addr));
+    }
+    if let Some(os) = &details.os {
+        lines.push(format!("OS: {os}"));
+    }
+    if !details.reachable {
+        let last_seen = details
+            .last_seen
+            .map_or_else(|| "never".to_string(), |at| at.to_rfc3339());
+        match details.circuit_open_until {
+            Some(retry_at) => lines.push(format!(
+                "Unreachable (last seen: {last_seen}), retrying at {}",
+                retry_at.with_timezone(&chrono::Local).format("%H:%M")
+            )),
+            None => lines.push(format!("Unreachable (last seen: {last_seen})")),
+        }
+    }
+    if let Some(connection) = &details.connection {
+        lines.push(format_connection(connection));
+    }
+    if details.paused {
+        lines.push("Paused: skipped by scheduled and fleet updates".to_string());
+    }
+    if let (Some(initiator), Some(since)) = (&details.initiator, details.initiated_at) {
+        lines.push(format!(
+            "Busy: {initiator} since {}",
+            since.with_timezone(&chrono::Local).format("%H:%M")
+        ));
+    }
+    if let Some(error) = &details.error {
+        lines.push(String::new());
+        let acknowledged = details.acknowledged.unwrap_or(false);
+        if acknowledged {
+            lines.push("Failure (acknowledged):".to_string());
+        } else {
+            lines.push("Failure:".to_string());
+        }
+        lines.push(format!("  Error: {error}"));
+        if let Some(previous) = &details.previous_state {
+            lines.push(format!("  Previous state: {previous}"));
+        }
+        if let Some(failed_at) = details.failed_at {
+            lines.push(format!("  Failed at: {}", failed_at.to_rfc3339()));
+        }
+        if let Some(retries) = details.retry_count {
+            lines.push(format!("  Retries: {retries}"));
+        }
+        for attempt in &details.retry_attempts {
+            lines.push(format!(
+                "    {}: {}",
+                attempt.attempted_at.to_rfc3339(),
+                attempt.error
+            ));
+        }
+        if let Some(next) = details.next_retry_at {
+            lines.push(format!("  Next retry: {}", next.to_rfc3339()));
+        }
+        if !details.recent_transitions.is_empty() {
+            lines.push("  Transitions:".to_string());
+        }
+        for t in &details.recent_transitions {
+            let by = t
+                .initiator
+                .as_ref()
+                .map_or_else(String::new, |initiator| format!(", {initiator}"));
+            lines.push(format!(
+                "    {} {} → {} ({}{by})",
+                t.at.with_timezone(&chrono::Local).format("%H:%M"),
+                t.from,
+                t.to,
+                t.trigger
+            ));
+        }
+        if !acknowledged {
+            lines.push("  Press a to acknowledge, R to retry".to_string());
+        }
     }
-    if let Some(state) = details.get("state").and_then(|v| v.as_str()) {
-        lines.push(format!("State: {state}"));
+    for warning in &details.warnings {
+        lines.push(format!("Warning: {warning}"));
     }
-    if let Some(addr) = details.get("addr").and_then(|v| v.as_str()) {
-        lines.push(format!("Address: {addr}"));
+    if let Some(pending) = details.pending_updates {
+        match details.security_updates {
+            Some(security) if security > 0 => {
+                lines.push(format!("Updates: {pending} ({security} security)"));
+            }
+            _ => lines.push(format!("Updates: {pending}")),
+        }
     }
 
     lines.push(String::new());
 
-    // System info
-    if let Some(system) = details.get("system") {
-        if let Some(os) = system.get("os_name").and_then(|v| v.as_str()) {
-            let version = system
-                .get("os_version")
-                .and_then(|v| v.as_str())
-                .unwrap_or("");
-            lines.push(format!("OS: {os} {version}"));
-        }
-        if let Some(hostname) = system.get("hostname").and_then(|v| v.as_str()) {
-            lines.push(format!("Hostname: {hostname}"));
-        }
-        if let Some(uptime) = system
-            .get("uptime_seconds")
-            .and_then(serde_json::Value::as_u64)
-        {
-            let uptime_str = format_uptime(uptime);
-            lines.push(format!("Uptime: {uptime_str}"));
+    // System info from the last inventory
+    if let Some(inventory) = &details.inventory {
+        lines.push(format!("OS: {}", inventory.os));
+        lines.push(format!("Kernel: {}", inventory.kernel));
+        lines.push(format!(
+            "Uptime: {}",
+            format_uptime(inventory.uptime_seconds)
+        ));
+        for disk in &inventory.disks {
+            lines.push(format!(
+                "Disk {}: {:.0}% used",
+                disk.mount_point, disk.used_percent
+            ));
         }
     }
 
     lines.push(String::new());
 
     // Upgradable packages
-    if let Some(packages) = details
-        .get("upgradable_packages")
-        .and_then(|v| v.as_array())
-    {
-        let pkg_count = packages.len();
-        lines.push(format!("Upgradable Packages: {pkg_count}"));
-        for (i, pkg) in packages.iter().take(10).enumerate() {
-            if let Some(name) = pkg.get("name").and_then(|v| v.as_str()) {
-                let from = pkg
-                    .get("current_version")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("?");
-                let to = pkg
-                    .get("new_version")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("?");
-                let prefix = if i == packages.len().min(10) - 1 {
-                    "└──"
-                } else {
-                    "├──"
-                };
-                lines.push(format!("  {prefix} {name} ({from} → {to})"));
-            }
+    if let Some(packages) = &details.upgradable_packages {
+        lines.push(format!("Upgradable Packages: {}", packages.len()));
+        let shown = packages.len().min(10);
+        for (i, pkg) in packages.iter().take(shown).enumerate() {
+            let prefix = if i == shown - 1 {
+                "└──"
+            } else {
+                "├──"
+            };
+            let security = if pkg.security { " [security]" } else { "" };
+            lines.push(format!(
+                "  {prefix} {} ({} → {}){security}",
+                pkg.name, pkg.current_version, pkg.new_version
+            ));
         }
-        if packages.len() > 10 {
-            let more_count = packages.len() - 10;
+        if packages.len() > shown {
+            let more_count = packages.len() - shown;
             lines.push(format!("  ... ({more_count} more)"));
         }
     }
@@ -116,8 +223,50 @@ fn format_details(details: &serde_json::Value) -> String {
     lines.join("\n")
 }
 
+/// One line on how the connection has been doing, e.g.
+/// `Connection: 42 commands, 1 failed, p50 120ms, p95 900ms, connect 80ms`
+fn format_connection(connection: &ConnectionStatsInfo) -> String {
+    let mut parts = vec![format!("{} commands", connection.commands)];
+    if connection.failures > 0 {
+        parts.push(format!("{} failed", connection.failures));
+    }
+    if let (Some(p50), Some(p95)) = (connection.p50_ms, connection.p95_ms) {
+        parts.push(format!("p50 {p50}ms, p95 {p95}ms"));
+    }
+    if let Some(connect) = connection.last_connect_ms {
+        parts.push(format!("connect {connect}ms"));
+    }
+    if connection.consecutive_connection_failures > 0 {
+        parts.push(format!(
+            "{} connection failures in a row",
+            connection.consecutive_connection_failures
+        ));
+    }
+    format!("Connection: {}", parts.join(", "))
+}
+
+/// Format recent update runs, newest first
+fn format_update_history(history: &[UpdateHistoryEntry]) -> String {
+    let mut lines = vec!["Recent Updates:".to_string()];
+    for run in history {
+        let when = run.timestamp.format("%Y-%m-%d %H:%M");
+        let kind = if run.dry_run { " (dry run)" } else { "" };
+        let outcome = if run.success {
+            let reboot = if run.reboot_required { ", reboot" } else { "" };
+            format!("{} packages{reboot}", run.upgraded_count)
+        } else {
+            format!(
+                "failed: {}",
+                run.error.as_deref().unwrap_or("unknown error")
+            )
+        };
+        lines.push(format!("  {when}{kind}  {outcome}"));
+    }
+    lines.join("\n")
+}
+
 /// Format uptime seconds to human-readable string
-fn format_uptime(seconds: u64) -> String {
+pub(super) fn format_uptime(seconds: u64) -> String {
     let days = seconds / 86400;
     let hours = (seconds % 86400) / 3600;
     let minutes = (seconds % 3600) / 60;
@@ -3,39 +3,42 @@
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
 
+use crate::keymap::{Command, KeyMap, Section};
+
+/// Build the help text from the configured bindings
+fn help_lines(keymap: &KeyMap) -> Vec<Line<'static>> {
+    let mut lines = Vec::new();
+    for section in Section::ALL {
+        let title = section.title();
+        lines.push(Line::raw(""));
+        lines.push(Line::raw(format!("  {title}")));
+        lines.push(Line::raw(format!(
+            "  {}",
+            "─".repeat(title.chars().count())
+        )));
+        for command in Command::ALL
+            .into_iter()
+            .filter(|command| command.section() == section)
+        {
+            lines.push(Line::raw(format!(
+                "  {:<10}{}",
+                keymap.label(command),
+                command.description()
+            )));
+        }
+    }
+    lines
+}
+
 /// Render the help popup
-pub fn render(frame: &mut Frame) {
-    let help_text = r"
-  Navigation
-  ──────────
-  j/↓       Move down
-  k/↑       Move up
-  g         Jump to first
-  G         Jump to last
-  Tab       Switch panel focus
-  Enter     Show host details
-  Esc       Close popup/clear search
-
-  Actions
-  ───────
-  u         Trigger update
-  U         Fleet update
-  r         Reboot host
-  R         Retry failed host
-  a         Acknowledge failure
-  i         Refresh inventory
-
-  General
-  ───────
-  /         Search hosts
-  ?         Toggle help
-  q         Quit
-";
-
-    // Calculate popup area (centered, 50x24)
+pub fn render(frame: &mut Frame, keymap: &KeyMap) {
+    let lines = help_lines(keymap);
+
+    // Calculate popup area (centered, 50 wide and tall enough for the text)
     let area = frame.area();
+    let content_height = u16::try_from(lines.len() + 3).unwrap_or(u16::MAX);
     let popup_width = 50.min(area.width.saturating_sub(4));
-    let popup_height = 24.min(area.height.saturating_sub(4));
+    let popup_height = content_height.min(area.height.saturating_sub(4));
     let x = (area.width.saturating_sub(popup_width)) / 2;
     let y = (area.height.saturating_sub(popup_height)) / 2;
     let popup_area = Rect::new(x, y, popup_width, popup_height);
@@ -43,7 +46,7 @@ pub fn render(frame: &mut Frame) {
     // Clear the area behind the popup
     frame.render_widget(Clear, popup_area);
 
-    let paragraph = Paragraph::new(help_text)
+    let paragraph = Paragraph::new(lines)
         .block(
             Block::default()
                 .title(" Help ")
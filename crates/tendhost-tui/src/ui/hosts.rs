@@ -3,19 +3,29 @@
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Cell, Row, Table, TableState};
 
-use crate::app::{App, Focus};
+use crate::app::{App, Focus, SortColumn, state_summary};
 use crate::config;
+use crate::keymap::Command;
 
 /// Render the host list table
 pub fn render(frame: &mut Frame, app: &App, area: Rect) {
-    let hosts = app.filtered_hosts();
+    let hosts = app.visible_hosts();
 
-    // Create header
+    // Create header, marking the sorted column
+    let heading = |title: &str, column: SortColumn| {
+        if app.sort.column == column {
+            Cell::from(format!("{title} {}", app.sort.arrow()))
+        } else {
+            Cell::from(title.to_string())
+        }
+    };
     let header = Row::new(vec![
-        Cell::from("Host"),
-        Cell::from("State"),
+        Cell::from(""),
+        heading("Host", SortColumn::Name),
+        heading("State", SortColumn::State),
         Cell::from("OS"),
-        Cell::from("Pkgs"),
+        heading("Pkgs", SortColumn::Packages),
+        heading("Updated", SortColumn::LastUpdate),
     ])
     .style(config::header_style())
     .height(1);
@@ -29,15 +39,44 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
             let state_color = config::state_color(&host.state);
 
             let state = &host.state;
+            let state_style = if host.is_failed() && host.acknowledged {
+                config::acknowledged_failure_style()
+            } else {
+                Style::default().fg(state_color)
+            };
+            let name_style = if host.unreachable {
+                config::unreachable_style()
+            } else if host.no_sudo {
+                config::no_sudo_style()
+            } else {
+                Style::default()
+            };
+            let mut name = if host.paused {
+                format!("⏸ {}", host.name)
+            } else {
+                host.name.clone()
+            };
+            if host.no_sudo {
+                name.push_str(" (no sudo)");
+            }
+            let mark = if app.marked.contains(&host.name) {
+                "[x]"
+            } else {
+                "[ ]"
+            };
             let cells = vec![
-                Cell::from(host.name.clone()),
-                Cell::from(format!("{state_symbol} {state}"))
-                    .style(Style::default().fg(state_color)),
+                Cell::from(mark),
+                Cell::from(name).style(name_style),
+                Cell::from(format!("{state_symbol} {state}")).style(state_style),
                 Cell::from(host.os.clone()),
                 Cell::from(
                     host.packages
                         .map_or_else(|| "--".to_string(), |p| p.to_string()),
                 ),
+                Cell::from(
+                    host.last_updated
+                        .map_or_else(|| "--".to_string(), |t| t.format("%Y-%m-%d").to_string()),
+                ),
             ];
 
             let style = if i == app.selected_host {
@@ -52,10 +91,12 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
 
     // Create table
     let widths = [
-        Constraint::Percentage(30),
-        Constraint::Percentage(25),
-        Constraint::Percentage(30),
-        Constraint::Percentage(15),
+        Constraint::Length(3),
+        Constraint::Percentage(26),
+        Constraint::Percentage(22),
+        Constraint::Percentage(22),
+        Constraint::Percentage(12),
+        Constraint::Percentage(18),
     ];
 
     let border_style = if app.focus == Focus::HostList {
@@ -65,11 +106,31 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
     };
 
     let title = if app.search_active {
-        let query = &app.search_query;
-        format!(" Hosts (/{query}) ")
+        let mut spans = vec![Span::raw(" Hosts (/")];
+        spans.extend(app.search.spans(Style::default(), true));
+        spans.push(Span::raw(") "));
+        Line::from(spans)
     } else {
-        let count = hosts.len();
-        format!(" Hosts ({count}) ")
+        let mut parts = vec![format!("Hosts ({})", hosts.len())];
+        if app.range_active() {
+            parts.push(format!("{} marked (range)", app.marked.len()));
+        } else if !app.marked.is_empty() {
+            parts.push(format!("{} marked", app.marked.len()));
+        }
+        if let Some(run) = &app.bulk {
+            parts.push(format!(
+                "{} {}/{} ({} cancels)",
+                run.action.name(),
+                run.done(),
+                run.total,
+                app.keymap.primary(Command::Back)
+            ));
+        }
+        let summary = state_summary(&hosts);
+        if !summary.is_empty() {
+            parts.push(summary);
+        }
+        Line::from(format!(" {} ", parts.join(" · ")))
     };
 
     let table = Table::new(rows, widths)
@@ -0,0 +1,184 @@
+//! Single-line text input widget
+//!
+//! Used by the host search, the inventory package filter and the tag
+//! editor. Keys are translated to [`InputEdit`]s in `event.rs` so the
+//! widget itself stays independent of the terminal backend.
+
+use ratatui::prelude::*;
+
+/// Editing operation on a [`TextInput`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEdit {
+    /// Insert a character at the cursor
+    Insert(char),
+    /// Delete the character before the cursor
+    Backspace,
+    /// Delete the character under the cursor
+    Delete,
+    /// Move the cursor one character left
+    Left,
+    /// Move the cursor one character right
+    Right,
+    /// Move the cursor to the start
+    Home,
+    /// Move the cursor to the end
+    End,
+}
+
+/// Text value with a cursor position
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextInput {
+    value: String,
+    /// Cursor position in characters, `0..=value.chars().count()`
+    cursor: usize,
+}
+
+impl TextInput {
+    /// Input pre-filled with `value`, cursor at the end
+    pub fn with_value(value: impl Into<String>) -> Self {
+        let value = value.into();
+        let cursor = value.chars().count();
+        Self { value, cursor }
+    }
+
+    /// Current text
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.value.is_empty()
+    }
+
+    /// Remove all text
+    pub fn clear(&mut self) {
+        self.value.clear();
+        self.cursor = 0;
+    }
+
+    /// Apply an edit; returns whether the text changed
+    pub fn apply(&mut self, edit: InputEdit) -> bool {
+        let len = self.value.chars().count();
+        match edit {
+            InputEdit::Insert(c) => {
+                let at = self.byte_index(self.cursor);
+                self.value.insert(at, c);
+                self.cursor += 1;
+                return true;
+            }
+            InputEdit::Backspace if self.cursor > 0 => {
+                self.cursor -= 1;
+                let at = self.byte_index(self.cursor);
+                self.value.remove(at);
+                return true;
+            }
+            InputEdit::Delete if self.cursor < len => {
+                let at = self.byte_index(self.cursor);
+                self.value.remove(at);
+                return true;
+            }
+            InputEdit::Left => self.cursor = self.cursor.saturating_sub(1),
+            InputEdit::Right => self.cursor = (self.cursor + 1).min(len),
+            InputEdit::Home => self.cursor = 0,
+            InputEdit::End => self.cursor = len,
+            InputEdit::Backspace | InputEdit::Delete => {}
+        }
+        false
+    }
+
+    fn byte_index(&self, chars: usize) -> usize {
+        self.value
+            .char_indices()
+            .nth(chars)
+            .map_or(self.value.len(), |(i, _)| i)
+    }
+
+    /// Text as spans, with a block cursor when `focused`
+    pub fn spans(&self, style: Style, focused: bool) -> Vec<Span<'static>> {
+        if !focused {
+            return vec![Span::styled(self.value.clone(), style)];
+        }
+
+        let at = self.byte_index(self.cursor);
+        let (before, rest) = self.value.split_at(at);
+        let mut chars = rest.chars();
+        let cursor_style = style.add_modifier(Modifier::REVERSED);
+        let cursor = match chars.next() {
+            Some(c) => Span::styled(c.to_string(), cursor_style),
+            None => Span::styled("█", style),
+        };
+        vec![
+            Span::styled(before.to_string(), style),
+            cursor,
+            Span::styled(chars.as_str().to_string(), style),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(text: &str) -> TextInput {
+        let mut input = TextInput::default();
+        for c in text.chars() {
+            input.apply(InputEdit::Insert(c));
+        }
+        input
+    }
+
+    #[test]
+    fn test_insert_at_the_cursor() {
+        let mut input = typed("wb");
+        assert!(!input.apply(InputEdit::Left));
+        assert!(input.apply(InputEdit::Insert('e')));
+        assert_eq!(input.value(), "web");
+
+        input.apply(InputEdit::Home);
+        input.apply(InputEdit::Insert('>'));
+        input.apply(InputEdit::End);
+        input.apply(InputEdit::Insert('<'));
+        assert_eq!(input.value(), ">web<");
+    }
+
+    #[test]
+    fn test_deleting_at_the_edges_changes_nothing() {
+        let mut input = typed("db");
+        assert!(!input.apply(InputEdit::Delete));
+        input.apply(InputEdit::Home);
+        assert!(!input.apply(InputEdit::Backspace));
+        assert_eq!(input.value(), "db");
+
+        assert!(input.apply(InputEdit::Delete));
+        assert_eq!(input.value(), "b");
+        input.apply(InputEdit::End);
+        assert!(input.apply(InputEdit::Backspace));
+        assert!(input.is_empty());
+        assert!(!input.apply(InputEdit::Backspace));
+        assert!(!input.apply(InputEdit::Delete));
+    }
+
+    #[test]
+    fn test_cursor_stays_within_the_text() {
+        let mut input = TextInput::with_value("ab");
+        input.apply(InputEdit::Right);
+        input.apply(InputEdit::Insert('c'));
+        assert_eq!(input.value(), "abc");
+
+        input.apply(InputEdit::Home);
+        input.apply(InputEdit::Left);
+        input.apply(InputEdit::Insert('_'));
+        assert_eq!(input.value(), "_abc");
+    }
+
+    #[test]
+    fn test_edits_count_characters_not_bytes() {
+        let mut input = TextInput::with_value("grüße");
+        input.apply(InputEdit::Left);
+        input.apply(InputEdit::Left);
+        assert!(input.apply(InputEdit::Backspace));
+        assert_eq!(input.value(), "grße");
+        assert!(input.apply(InputEdit::Delete));
+        assert_eq!(input.value(), "gre");
+    }
+}
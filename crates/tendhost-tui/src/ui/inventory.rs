@@ -0,0 +1,293 @@
+//! Host inventory panel widget
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Paragraph, Tabs, Wrap};
+use serde_json::Value;
+
+use crate::app::{App, InventoryData, InventorySection, InventoryView};
+use crate::config;
+
+/// Width of disk usage bars in cells
+const USAGE_BAR_WIDTH: usize = 20;
+
+/// Render the inventory view for one host
+pub fn render(frame: &mut Frame, app: &App, view: &InventoryView, area: Rect) {
+    let block = Block::default()
+        .title(format!(" Inventory: {} ", view.host))
+        .borders(Borders::ALL)
+        .border_style(config::focused_border_style());
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2), // Tabs
+            Constraint::Min(1),    // Section content
+            Constraint::Length(1), // Footer
+        ])
+        .split(inner);
+
+    let tabs = Tabs::new(InventorySection::ALL.map(InventorySection::title))
+        .select(view.section.index())
+        .style(Style::default().fg(Color::DarkGray))
+        .highlight_style(config::header_style())
+        .divider("│");
+    frame.render_widget(tabs, chunks[0]);
+
+    match &view.data {
+        InventoryData::Loading => {
+            let symbol = config::state_symbol("querying", app.tick);
+            let loading = Paragraph::new(format!("{symbol} Loading inventory..."))
+                .style(Style::default().fg(Color::Yellow));
+            frame.render_widget(loading, chunks[1]);
+        }
+        InventoryData::Failed(message) => {
+            let error = Paragraph::new(vec![
+                Line::styled(
+                    "Failed to load inventory",
+                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                ),
+                Line::styled(message.clone(), Style::default().fg(Color::Red)),
+                Line::raw(""),
+                Line::styled(
+                    "Press i to retry or Esc to go back",
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ])
+            .wrap(Wrap { trim: true });
+            frame.render_widget(error, chunks[1]);
+        }
+        InventoryData::Loaded(_) => {
+            let lines = section_lines(view);
+            let scroll = u16::try_from(view.scroll).unwrap_or(u16::MAX);
+            frame.render_widget(Paragraph::new(lines).scroll((scroll, 0)), chunks[1]);
+        }
+    }
+
+    frame.render_widget(footer(view), chunks[2]);
+}
+
+/// Content lines for the current section
+fn section_lines(view: &InventoryView) -> Vec<Line<'static>> {
+    let empty = |what: &str| {
+        vec![Line::styled(
+            format!("No {what}"),
+            Style::default().fg(Color::DarkGray),
+        )]
+    };
+
+    match view.section {
+        InventorySection::System => system_lines(view.inventory()),
+        InventorySection::Disks if view.disks().is_empty() => empty("disks"),
+        InventorySection::Disks => view.disks().iter().map(disk_line).collect(),
+        InventorySection::Network if view.interfaces().is_empty() => empty("network interfaces"),
+        InventorySection::Network => view.interfaces().iter().map(interface_line).collect(),
+        InventorySection::Containers if view.containers().is_empty() => empty("containers"),
+        InventorySection::Containers => view.containers().iter().map(container_line).collect(),
+        InventorySection::Packages => {
+            let packages = view.filtered_packages();
+            if packages.is_empty() {
+                empty("matching packages")
+            } else {
+                packages.into_iter().map(package_line).collect()
+            }
+        }
+    }
+}
+
+fn str_at<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+fn u64_at(value: &Value, key: &str) -> u64 {
+    value.get(key).and_then(Value::as_u64).unwrap_or(0)
+}
+
+fn system_lines(inventory: Option<&Value>) -> Vec<Line<'static>> {
+    let Some(inventory) = inventory else {
+        return Vec::new();
+    };
+    let null = Value::Null;
+    let system = inventory.get("system").unwrap_or(&null);
+    let hardware = inventory.get("hardware").unwrap_or(&null);
+    let cpu = hardware.get("cpu").unwrap_or(&null);
+    let memory = hardware.get("memory").unwrap_or(&null);
+
+    let row = |label: &str, value: String| {
+        Line::from(vec![
+            Span::styled(format!("{label:<12}"), config::header_style()),
+            Span::raw(value),
+        ])
+    };
+
+    vec![
+        row("Hostname", str_at(system, "hostname").to_string()),
+        row(
+            "OS",
+            format!(
+                "{} {}",
+                str_at(system, "os_name"),
+                str_at(system, "os_version")
+            ),
+        ),
+        row("Kernel", str_at(system, "kernel_version").to_string()),
+        row("Arch", str_at(system, "arch").to_string()),
+        row(
+            "Uptime",
+            super::details::format_uptime(u64_at(system, "uptime_seconds")),
+        ),
+        row(
+            "CPU",
+            format!(
+                "{} ({} cores, {} threads)",
+                str_at(cpu, "model"),
+                u64_at(cpu, "physical_cores"),
+                u64_at(cpu, "logical_cores")
+            ),
+        ),
+        row(
+            "Memory",
+            format!(
+                "{} / {}",
+                format_bytes(u64_at(memory, "used_bytes")),
+                format_bytes(u64_at(memory, "total_bytes"))
+            ),
+        ),
+        row("Collected", str_at(inventory, "collected_at").to_string()),
+    ]
+}
+
+fn disk_line(disk: &Value) -> Line<'static> {
+    let used = u64_at(disk, "used_bytes");
+    let total = u64_at(disk, "total_bytes");
+    let (bar, color) = usage_bar(used, total);
+
+    Line::from(vec![
+        Span::raw(format!(
+            "{:<20} {:<8} ",
+            str_at(disk, "mount_point"),
+            str_at(disk, "filesystem")
+        )),
+        Span::styled(bar, Style::default().fg(color)),
+        Span::raw(format!(" {} / {}", format_bytes(used), format_bytes(total))),
+    ])
+}
+
+fn interface_line(interface: &Value) -> Line<'static> {
+    let addresses: Vec<&str> = ["ipv4", "ipv6"]
+        .iter()
+        .filter_map(|key| interface.get(*key).and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+
+    Line::raw(format!(
+        "{:<12} {:<18} {}",
+        str_at(interface, "name"),
+        str_at(interface, "mac"),
+        addresses.join(", ")
+    ))
+}
+
+fn container_line(container: &Value) -> Line<'static> {
+    let state = str_at(container, "state");
+    let state_color = if state == "running" {
+        Color::Green
+    } else {
+        Color::DarkGray
+    };
+
+    Line::from(vec![
+        Span::raw(format!("{:<24} ", str_at(container, "name"))),
+        Span::styled(format!("{state:<10} "), Style::default().fg(state_color)),
+        Span::raw(str_at(container, "image").to_string()),
+    ])
+}
+
+fn package_line(package: &Value) -> Line<'static> {
+    Line::raw(format!(
+        "{:<32} {:<28} {:<8} {}",
+        str_at(package, "name"),
+        str_at(package, "version"),
+        str_at(package, "arch"),
+        str_at(package, "source")
+    ))
+}
+
+/// Footer with the package filter or key hints
+fn footer(view: &InventoryView) -> Paragraph<'static> {
+    if view.section == InventorySection::Packages {
+        let shown = view.filtered_packages().len();
+        let total = view.packages().len();
+        let mut spans = vec![Span::styled("/", config::header_style())];
+        spans.extend(
+            view.filter
+                .spans(config::header_style(), view.filter_active),
+        );
+        spans.push(Span::styled(
+            format!("  ({shown} of {total})"),
+            Style::default().fg(Color::DarkGray),
+        ));
+        Paragraph::new(Line::from(spans))
+    } else {
+        Paragraph::new(Span::styled(
+            "[Tab] Next section  [/] Filter packages  [i] Refresh  [Esc] Back",
+            Style::default().fg(Color::DarkGray),
+        ))
+    }
+}
+
+/// Text usage bar and its color for `used` out of `total`
+fn usage_bar(used: u64, total: u64) -> (String, Color) {
+    let ratio = if total == 0 {
+        0.0
+    } else {
+        #[allow(clippy::cast_precision_loss)]
+        let ratio = used as f64 / total as f64;
+        ratio.clamp(0.0, 1.0)
+    };
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    let filled = (ratio * USAGE_BAR_WIDTH as f64).round() as usize;
+
+    let color = if ratio >= 0.9 {
+        Color::Red
+    } else if ratio >= 0.7 {
+        Color::Yellow
+    } else {
+        Color::Green
+    };
+
+    (
+        format!(
+            "[{}{}] {:>3.0}%",
+            "█".repeat(filled),
+            "░".repeat(USAGE_BAR_WIDTH - filled),
+            ratio * 100.0
+        ),
+        color,
+    )
+}
+
+/// Format a byte count with a binary unit
+pub(crate) fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    #[allow(clippy::cast_precision_loss)]
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
@@ -4,6 +4,8 @@ use ratatui::prelude::*;
 
 /// Layout areas for the UI
 pub struct LayoutAreas {
+    /// Everything above the status bar
+    pub content: Rect,
     pub hosts: Rect,
     pub details: Rect,
     pub events: Rect,
@@ -48,6 +50,7 @@ pub fn calculate_layout(area: Rect) -> LayoutAreas {
     let events = right_panel[1];
 
     LayoutAreas {
+        content: content_area,
         hosts,
         details,
         events,
@@ -1,28 +1,54 @@
 //! UI rendering modules
 
+pub mod checklist;
+mod confirm;
 mod details;
 mod events;
 mod help;
 mod hosts;
+pub mod input;
+mod inventory;
 mod layout;
+mod packages;
 mod statusbar;
+mod tags;
+pub mod toast;
 
 use ratatui::prelude::*;
 
 use crate::app::App;
 
+pub(crate) use inventory::format_bytes;
+
 /// Render the entire UI
 pub fn render(frame: &mut Frame, app: &App) {
     let areas = layout::calculate_layout(frame.area());
 
-    // Render main components
-    hosts::render(frame, app, areas.hosts);
-    details::render(frame, app, areas.details);
-    events::render(frame, app, areas.events);
+    // Render main components; the inventory view takes over the content area
+    if let Some(view) = &app.inventory {
+        inventory::render(frame, app, view, areas.content);
+    } else {
+        hosts::render(frame, app, areas.hosts);
+        details::render(frame, app, areas.details);
+        events::render(frame, app, areas.events);
+    }
     statusbar::render(frame, app, areas.statusbar);
 
     // Render help popup if active
     if app.show_help {
-        help::render(frame);
+        help::render(frame, &app.keymap);
+    }
+
+    if let Some(editor) = &app.tag_editor {
+        tags::render(frame, editor);
+    }
+
+    if let Some(picker) = &app.package_picker {
+        packages::render(frame, picker, &app.keymap);
+    }
+
+    // Confirmation prompt goes on top of everything else
+    if let Some(pending) = &app.confirm {
+        confirm::render(frame, pending);
     }
 }
@@ -0,0 +1,56 @@
+//! Package picker popup widget
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::app::PackagePicker;
+use crate::keymap::{Command, KeyMap};
+
+/// Render the package picker popup
+pub fn render(frame: &mut Frame, picker: &PackagePicker, keymap: &KeyMap) {
+    // Calculate popup area (centered, 70 wide, most of the height)
+    let area = frame.area();
+    let popup_width = 70.min(area.width.saturating_sub(4));
+    let popup_height = area.height.saturating_sub(6).max(area.height.min(8));
+    let x = (area.width.saturating_sub(popup_width)) / 2;
+    let y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(x, y, popup_width, popup_height);
+
+    // Clear the area behind the popup
+    frame.render_widget(Clear, popup_area);
+
+    let checked = picker.list.checked().count();
+    let block = Block::default()
+        .title(format!(
+            " Packages: {} ({checked}/{} checked) ",
+            picker.host,
+            picker.list.len()
+        ))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    let inner = block.inner(popup_area);
+    frame.render_widget(block, popup_area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(0), Constraint::Length(1)])
+        .split(inner);
+    picker.list.render(frame, chunks[0], Block::default());
+
+    let hints = [
+        (Command::Toggle, "Check"),
+        (Command::SelectAll, "All"),
+        (Command::SelectNone, "None"),
+        (Command::Update, "Update checked"),
+        (Command::Back, "Close"),
+    ];
+    let hint = hints
+        .iter()
+        .map(|(command, label)| format!("[{}] {label}", keymap.primary(*command)))
+        .collect::<Vec<_>>()
+        .join("  ");
+    frame.render_widget(
+        Paragraph::new(hint).style(Style::default().fg(Color::DarkGray)),
+        chunks[1],
+    );
+}
@@ -3,7 +3,35 @@
 use ratatui::prelude::*;
 use ratatui::widgets::Paragraph;
 
+use tendhost_api::responses::FleetSummary;
+
 use crate::app::{App, ConnectionState};
+use crate::keymap::Command;
+
+/// States in the order the fleet summary lists them
+const SUMMARY_STATES: [&str; 8] = [
+    "idle",
+    "pending_updates",
+    "querying",
+    "updating",
+    "waiting_reboot",
+    "rebooting",
+    "verifying",
+    "failed",
+];
+
+/// Fleet counts such as `24 idle · 2 updating · 1 failed · 137 updates pending`
+fn fleet_summary_text(summary: &FleetSummary) -> String {
+    let mut parts: Vec<String> = SUMMARY_STATES
+        .iter()
+        .filter_map(|state| {
+            let count = summary.count(state);
+            (count > 0).then(|| format!("{count} {}", state.replace('_', " ")))
+        })
+        .collect();
+    parts.push(format!("{} updates pending", summary.pending_updates));
+    parts.join(" · ")
+}
 
 /// Render the status bar
 pub fn render(frame: &mut Frame, app: &App, area: Rect) {
@@ -17,17 +45,92 @@ pub fn render(frame: &mut Frame, app: &App, area: Rect) {
         }
     };
 
-    let keybindings = "[j/k] Navigate  [Enter] Details  [u] Update  [r] Reboot  [?] Help  [q] Quit";
+    let hints: &[(Command, &str)] = if app.inventory.is_some() {
+        &[
+            (Command::Down, "Scroll"),
+            (Command::NextSection, "Section"),
+            (Command::Search, "Filter packages"),
+            (Command::Inventory, "Refresh"),
+            (Command::Back, "Back"),
+            (Command::Quit, "Quit"),
+        ]
+    } else {
+        &[
+            (Command::Down, "Navigate"),
+            (Command::Select, "Details"),
+            (Command::Update, "Update"),
+            (Command::Cancel, "Cancel"),
+            (Command::Inventory, "Inventory"),
+            (Command::Packages, "Packages"),
+            (Command::EditTags, "Tags"),
+            (Command::Help, "Help"),
+            (Command::Quit, "Quit"),
+        ]
+    };
+    let keybindings = hints
+        .iter()
+        .map(|(command, label)| format!("[{}] {label}", app.keymap.primary(*command)))
+        .collect::<Vec<_>>()
+        .join("  ");
+
+    // A notification temporarily replaces the key hints
+    let hint = match app.toasts.current() {
+        Some(toast) => toast.spans(app.toasts.waiting()),
+        None => vec![Span::styled(
+            keybindings,
+            Style::default().fg(Color::DarkGray),
+        )],
+    };
 
-    let status_line = Line::from(vec![
-        Span::styled(
-            connection_status.0,
-            Style::default().fg(connection_status.1),
-        ),
-        Span::raw("  │  "),
-        Span::styled(keybindings, Style::default().fg(Color::DarkGray)),
-    ]);
+    let mut spans = vec![Span::styled(
+        connection_status.0,
+        Style::default().fg(connection_status.1),
+    )];
+    if let Some(summary) = &app.fleet_summary {
+        let color = if summary.failed_hosts.is_empty() {
+            Color::Reset
+        } else {
+            Color::Red
+        };
+        spans.push(Span::raw("  │  "));
+        spans.push(Span::styled(
+            fleet_summary_text(summary),
+            Style::default().fg(color),
+        ));
+    }
+    spans.push(Span::raw("  │  "));
+    spans.extend(hint);
+    let status_line = Line::from(spans);
 
     let paragraph = Paragraph::new(status_line);
     frame.render_widget(paragraph, area);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fleet_summary_text_lists_occupied_states() {
+        let mut summary = FleetSummary {
+            total_hosts: 27,
+            pending_updates: 137,
+            ..FleetSummary::default()
+        };
+        summary.states.insert("failed".to_string(), 1);
+        summary.states.insert("idle".to_string(), 24);
+        summary.states.insert("updating".to_string(), 2);
+
+        assert_eq!(
+            fleet_summary_text(&summary),
+            "24 idle · 2 updating · 1 failed · 137 updates pending"
+        );
+
+        summary.states = [("waiting_reboot".to_string(), 3)].into();
+        summary.pending_updates = 0;
+        assert_eq!(
+            fleet_summary_text(&summary),
+            "3 waiting reboot · 0 updates pending"
+        );
+    }
+}
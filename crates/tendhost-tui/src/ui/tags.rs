@@ -0,0 +1,49 @@
+//! Tag editor popup widget
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+use crate::app::TagEditor;
+
+/// Render the tag editor popup
+pub fn render(frame: &mut Frame, editor: &TagEditor) {
+    let mut input = vec![Span::raw("  ")];
+    input.extend(editor.input.spans(Style::default(), true));
+
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::styled(
+            "  Tags separated by commas or spaces",
+            Style::default().fg(Color::DarkGray),
+        )),
+        Line::from(input),
+        Line::from(""),
+        Line::from(vec![
+            Span::raw("  "),
+            Span::styled("[Enter]", Style::default().fg(Color::Green)),
+            Span::raw(" Save   "),
+            Span::styled("[Esc]", Style::default().fg(Color::Red)),
+            Span::raw(" Cancel"),
+        ]),
+    ];
+
+    // Calculate popup area (centered, 60x8)
+    let area = frame.area();
+    let popup_width = 60.min(area.width.saturating_sub(4));
+    let popup_height = 8.min(area.height.saturating_sub(4));
+    let x = (area.width.saturating_sub(popup_width)) / 2;
+    let y = (area.height.saturating_sub(popup_height)) / 2;
+    let popup_area = Rect::new(x, y, popup_width, popup_height);
+
+    // Clear the area behind the popup
+    frame.render_widget(Clear, popup_area);
+
+    let paragraph = Paragraph::new(text).block(
+        Block::default()
+            .title(format!(" Tags: {} ", editor.host))
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Yellow)),
+    );
+
+    frame.render_widget(paragraph, popup_area);
+}
@@ -0,0 +1,182 @@
+//! Status bar notifications
+//!
+//! Failed actions and refused keys show a message in place of the key
+//! hints until it expires a few seconds later. Pressing a failing key again
+//! while its message is shown counts it instead of stacking copies, and a
+//! hint says how to get past the problem where there is a way, e.g. which
+//! key retries a failed host.
+
+use std::collections::VecDeque;
+
+use ratatui::prelude::*;
+
+use crate::app::EventLevel;
+
+/// Ticks a notification stays visible
+pub const TOAST_TICKS: u64 = 12;
+
+/// Notifications kept at once; the oldest goes first beyond this
+const MAX_TOASTS: usize = 5;
+
+/// A message shown in the status bar
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    pub level: EventLevel,
+    pub message: String,
+    /// How to get past the problem
+    pub hint: Option<String>,
+    /// Times the message was raised while shown
+    pub count: u32,
+    /// Tick at which the message is hidden
+    until: u64,
+}
+
+/// Visible notifications, the newest shown
+#[derive(Debug, Clone, Default)]
+pub struct Toasts {
+    queue: VecDeque<Toast>,
+}
+
+impl Toasts {
+    /// Show a message from tick `now` on
+    ///
+    /// A message of the same level as one still visible replaces it: it is
+    /// counted again, shown anew, and stays for another full period.
+    pub fn push(
+        &mut self,
+        now: u64,
+        level: EventLevel,
+        message: impl Into<String>,
+        hint: Option<String>,
+    ) {
+        let message = message.into();
+        let count = match self
+            .queue
+            .iter()
+            .position(|t| t.level == level && t.message == message)
+        {
+            Some(index) => self.queue.remove(index).map_or(1, |t| t.count + 1),
+            None => 1,
+        };
+        self.queue.push_back(Toast {
+            level,
+            message,
+            hint,
+            count,
+            until: now.saturating_add(TOAST_TICKS),
+        });
+        if self.queue.len() > MAX_TOASTS {
+            self.queue.pop_front();
+        }
+    }
+
+    /// Hide the messages whose time is up at tick `now`
+    pub fn expire(&mut self, now: u64) {
+        self.queue.retain(|t| now < t.until);
+    }
+
+    /// The message to show
+    pub fn current(&self) -> Option<&Toast> {
+        self.queue.back()
+    }
+
+    /// Visible messages other than the shown one
+    pub fn waiting(&self) -> usize {
+        self.queue.len().saturating_sub(1)
+    }
+}
+
+impl Toast {
+    /// The message as status bar spans, colored by level
+    pub fn spans(&self, waiting: usize) -> Vec<Span<'static>> {
+        let (icon, color) = match self.level {
+            EventLevel::Info => ("•", Color::White),
+            EventLevel::Success => ("✓", Color::Green),
+            EventLevel::Warning => ("⚠", Color::Yellow),
+            EventLevel::Error => ("✗", Color::Red),
+        };
+        let mut text = format!("{icon} {}", self.message);
+        if self.count > 1 {
+            text.push_str(&format!(" (×{})", self.count));
+        }
+        let mut spans = vec![Span::styled(
+            text,
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        )];
+        if let Some(hint) = &self.hint {
+            spans.push(Span::styled(
+                format!(" — {hint}"),
+                Style::default().fg(color),
+            ));
+        }
+        if waiting > 0 {
+            spans.push(Span::styled(
+                format!("  +{waiting} more"),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(toasts: &Toasts) -> String {
+        let toast = toasts.current().unwrap();
+        toast
+            .spans(toasts.waiting())
+            .iter()
+            .map(|span| span.content.as_ref())
+            .collect()
+    }
+
+    #[test]
+    fn test_messages_expire_after_their_period() {
+        let mut toasts = Toasts::default();
+        toasts.push(0, EventLevel::Error, "Reboot failed on web", None);
+        toasts.push(5, EventLevel::Warning, "No packages checked", None);
+        assert_eq!(text(&toasts), "⚠ No packages checked  +1 more");
+
+        toasts.expire(TOAST_TICKS - 1);
+        assert_eq!(toasts.waiting(), 1);
+        toasts.expire(TOAST_TICKS);
+        assert_eq!(text(&toasts), "⚠ No packages checked");
+        toasts.expire(TOAST_TICKS + 5);
+        assert!(toasts.current().is_none());
+    }
+
+    #[test]
+    fn test_repeated_messages_collapse_into_a_count() {
+        let mut toasts = Toasts::default();
+        let hint = || Some("press R to retry or a to acknowledge".to_string());
+        toasts.push(0, EventLevel::Error, "Update failed on db", hint());
+        toasts.push(2, EventLevel::Warning, "No packages checked", None);
+        toasts.push(10, EventLevel::Error, "Update failed on db", hint());
+        assert_eq!(
+            text(&toasts),
+            "✗ Update failed on db (×2) — press R to retry or a to acknowledge  +1 more"
+        );
+
+        // The repeat restarted the period of the message
+        toasts.expire(TOAST_TICKS + 5);
+        assert_eq!(toasts.waiting(), 0);
+        assert_eq!(toasts.current().unwrap().count, 2);
+
+        // The same text at another level is another message
+        toasts.push(12, EventLevel::Warning, "Update failed on db", None);
+        assert_eq!(toasts.current().unwrap().count, 1);
+        assert_eq!(toasts.waiting(), 1);
+    }
+
+    #[test]
+    fn test_oldest_message_goes_beyond_the_limit() {
+        let mut toasts = Toasts::default();
+        for n in 0..=MAX_TOASTS {
+            toasts.push(0, EventLevel::Error, format!("failure {n}"), None);
+        }
+        assert_eq!(toasts.waiting(), MAX_TOASTS - 1);
+        assert!(toasts.queue.iter().all(|t| t.message != "failure 0"));
+    }
+}
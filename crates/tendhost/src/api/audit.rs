@@ -0,0 +1,236 @@
+//! Audit log API endpoints and recording middleware
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use axum::{
+    Json, RequestExt,
+    body::{Body, to_bytes},
+    extract::{ConnectInfo, MatchedPath, Query, RawPathParams, Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tendhost_api::responses::AuditEntry;
+use tendhost_core::{AuditQuery, HostName};
+use tracing::warn;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::error::{ApiError, AppError};
+use crate::state::AppState;
+
+/// Largest request body stored whole in the audit record
+///
+/// Larger bodies, like a bulk registration of a big fleet, still reach the
+/// handler; the audit entry only keeps an excerpt of them.
+const MAX_AUDITED_BODY: usize = 64 * 1024;
+
+/// Query parameters for reading the audit log
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct AuditListQuery {
+    /// Only entries touching this host
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Only entries at or after this time (RFC 3339)
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Maximum number of entries to return
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+/// List recent audit entries, newest first
+///
+/// # Errors
+/// Returns `AppError` if the audit log cannot be read
+#[utoipa::path(
+    get,
+    path = "/audit",
+    tag = "audit",
+    params(AuditListQuery),
+    responses(
+        (status = 200, description = "Audit entries, newest first", body = Vec<AuditEntry>),
+        (status = 500, description = "Audit log unreadable", body = ApiError),
+    )
+)]
+pub async fn list_audit(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditListQuery>,
+) -> Result<impl IntoResponse, AppError> {
+    let entries = state
+        .audit
+        .query(&AuditQuery {
+            host: query.host,
+            since: query.since,
+            limit: query.limit,
+        })
+        .await?;
+
+    Ok(Json(entries))
+}
+
+/// Map a matched route and method to an audited operation name
+fn audited_operation(method: &Method, route: &str) -> Option<&'static str> {
+    let op = match (method.as_str(), route) {
+        ("POST", "/hosts") => "register_host",
+        ("POST", "/hosts/import") => "import_hosts",
+        ("PATCH", "/hosts/{hostname}") => "update_host_config",
+        ("DELETE", "/hosts/{hostname}") => "unregister_host",
+        ("POST", "/hosts/{hostname}/update") => "update",
+        ("POST", "/hosts/{hostname}/cancel") => "cancel",
+        ("POST", "/hosts/{hostname}/reboot") => "reboot",
+        ("POST", "/hosts/{hostname}/retry") => "retry",
+        ("POST", "/hosts/{hostname}/acknowledge") => "acknowledge",
+        ("POST", "/hosts/{hostname}/pause") => "pause",
+        ("POST", "/hosts/{hostname}/resume") => "resume",
+        ("POST", "/fleet/update") => "fleet_update",
+        ("POST", "/schedules/{id}/run-now") => "schedule_run_now",
+        _ => return None,
+    };
+    Some(op)
+}
+
+/// The host named by the `{hostname}` route segment, percent-decoded
+fn hostname_from_params(params: &RawPathParams) -> Option<String> {
+    params
+        .iter()
+        .find(|(key, _)| *key == "hostname")
+        .map(|(_, name)| name)
+        .filter(|name| !name.is_empty())
+        .map(|name| HostName::new(name).to_string())
+}
+
+/// The parameters stored for a request body
+///
+/// Bodies over [`MAX_AUDITED_BODY`] are stored as a marked excerpt rather
+/// than parsed, so one large import cannot bloat the audit log.
+fn audited_params(bytes: &[u8]) -> Value {
+    if bytes.is_empty() {
+        return Value::Null;
+    }
+    if bytes.len() > MAX_AUDITED_BODY {
+        return json!({
+            "truncated": true,
+            "size": bytes.len(),
+            "excerpt": String::from_utf8_lossy(&bytes[..MAX_AUDITED_BODY]),
+        });
+    }
+    serde_json::from_slice(bytes).unwrap_or(Value::Null)
+}
+
+/// Record every mutating request in the audit log
+///
+/// The request body is buffered so its parameters can be stored alongside
+/// the operation, up to the same `max_body_bytes` limit that
+/// [`limit_body`](crate::limits::limit_body) enforces. The entry is written
+/// after the handler responds so the resulting status code is included.
+pub async fn record_mutations(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string());
+    let Some(operation) = route
+        .as_deref()
+        .and_then(|route| audited_operation(request.method(), route))
+    else {
+        return next.run(request).await;
+    };
+
+    let remote_addr = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.to_string());
+    let path_host = request
+        .extract_parts::<RawPathParams>()
+        .await
+        .ok()
+        .and_then(|params| hostname_from_params(&params));
+
+    let max_body_bytes = state.config().daemon.limits.max_body_bytes;
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, max_body_bytes).await {
+        Ok(bytes) => bytes,
+        Err(_) => return AppError::payload_too_large(max_body_bytes).into_response(),
+    };
+    let params = audited_params(&bytes);
+    let request = Request::from_parts(parts, Body::from(bytes));
+
+    let host = path_host.or_else(|| {
+        (operation == "register_host")
+            .then(|| params.get("name").and_then(Value::as_str))
+            .flatten()
+            .map(|name| HostName::new(name).to_string())
+    });
+
+    let response = next.run(request).await;
+
+    let entry = AuditEntry {
+        timestamp: Utc::now(),
+        operation: operation.to_string(),
+        hosts: host.into_iter().collect(),
+        params,
+        source: "api".to_string(),
+        remote_addr,
+        identity: None,
+        status: Some(response.status().as_u16()),
+    };
+    if let Err(e) = state.audit.record(&entry).await {
+        warn!(operation, error = %e, "failed to write audit entry");
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audited_operation() {
+        assert_eq!(
+            audited_operation(&Method::POST, "/hosts/{hostname}/update"),
+            Some("update")
+        );
+        assert_eq!(
+            audited_operation(&Method::DELETE, "/hosts/{hostname}"),
+            Some("unregister_host")
+        );
+        assert_eq!(audited_operation(&Method::GET, "/hosts/{hostname}"), None);
+        assert_eq!(audited_operation(&Method::GET, "/audit"), None);
+    }
+
+    #[test]
+    fn test_audited_params_keeps_small_bodies_whole() {
+        assert_eq!(audited_params(b""), Value::Null);
+        assert_eq!(
+            audited_params(br#"{"name": "web-1"}"#),
+            json!({"name": "web-1"})
+        );
+    }
+
+    #[test]
+    fn test_audited_params_truncates_large_bodies() {
+        let body = vec![b'x'; MAX_AUDITED_BODY * 2];
+
+        let params = audited_params(&body);
+
+        assert_eq!(params["truncated"], true);
+        assert_eq!(params["size"], MAX_AUDITED_BODY * 2);
+        assert_eq!(
+            params["excerpt"].as_str().map(str::len),
+            Some(MAX_AUDITED_BODY)
+        );
+    }
+}
@@ -4,20 +4,49 @@
 
 #![allow(dead_code)]
 
+use std::time::Duration;
+
 use axum::{
     Json,
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use kameo::error::SendError;
 use serde::{Deserialize, Serialize};
+use tendhost_api::pagination::PageParams;
+use tendhost_core::{CoreError, FieldError};
+use utoipa::ToSchema;
+
+use crate::scheduler::ScheduleError;
 
 /// API error response
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct ApiError {
     /// Error code
     pub code: String,
     /// Error message
     pub message: String,
+    /// Invalid request fields, for `VALIDATION_FAILED` errors
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub details: Vec<FieldViolation>,
+}
+
+/// A single invalid field in a request
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FieldViolation {
+    /// Field name, with an index for list entries (e.g. `tags[2]`)
+    pub field: String,
+    /// What is wrong with the value
+    pub message: String,
+}
+
+impl From<FieldError> for FieldViolation {
+    fn from(err: FieldError) -> Self {
+        Self {
+            field: err.field,
+            message: err.message,
+        }
+    }
 }
 
 impl ApiError {
@@ -26,10 +55,22 @@ impl ApiError {
         Self {
             code: "INTERNAL_ERROR".to_string(),
             message: message.into(),
+            details: Vec::new(),
         }
     }
 }
 
+/// Field errors for out-of-range page parameters
+pub fn page_param_errors(params: &PageParams) -> Vec<FieldError> {
+    params
+        .validate()
+        .err()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|e| FieldError::new(e.field, e.message))
+        .collect()
+}
+
 /// Wrapper for API errors with status codes
 pub struct AppError {
     pub status: StatusCode,
@@ -37,6 +78,73 @@ pub struct AppError {
 }
 
 impl AppError {
+    /// The daemon is shutting down and not accepting new work
+    pub fn shutting_down() -> Self {
+        Self {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            error: ApiError {
+                code: "SHUTTING_DOWN".to_string(),
+                message: "daemon is shutting down".to_string(),
+                details: Vec::new(),
+            },
+        }
+    }
+
+    /// The request failed validation; lists every invalid field
+    pub fn validation(errors: Vec<FieldError>) -> Self {
+        Self {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            error: ApiError {
+                code: "VALIDATION_FAILED".to_string(),
+                message: format!("{} invalid field(s)", errors.len()),
+                details: errors.into_iter().map(FieldViolation::from).collect(),
+            },
+        }
+    }
+
+    /// The request body is larger than `limit` bytes
+    pub fn payload_too_large(limit: usize) -> Self {
+        Self::with_code(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            "PAYLOAD_TOO_LARGE",
+            format!("request body exceeds {limit} bytes"),
+        )
+    }
+
+    /// The request was not finished within `timeout`
+    pub fn request_timeout(timeout: Duration) -> Self {
+        Self::with_code(
+            StatusCode::REQUEST_TIMEOUT,
+            "REQUEST_TIMEOUT",
+            format!("request not completed within {}s", timeout.as_secs()),
+        )
+    }
+
+    /// `allow_remote_install` is off, so nothing is installed on hosts
+    pub fn remote_install_disabled() -> Self {
+        Self::with_code(
+            StatusCode::FORBIDDEN,
+            "REMOTE_INSTALL_DISABLED",
+            "remote installs are disabled; set allow_remote_install = true under [daemon]",
+        )
+    }
+
+    /// The request body could not be read
+    pub fn invalid_body(message: impl Into<String>) -> Self {
+        Self::with_code(StatusCode::BAD_REQUEST, "INVALID_BODY", message)
+    }
+
+    fn with_code(status: StatusCode, code: &str, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            error: ApiError {
+                code: code.to_string(),
+                message: message.into(),
+                details: Vec::new(),
+            },
+        }
+    }
+
     pub fn internal(message: impl Into<String>) -> Self {
         Self {
             status: StatusCode::INTERNAL_SERVER_ERROR,
@@ -45,6 +153,73 @@ impl AppError {
     }
 }
 
+impl From<CoreError> for AppError {
+    fn from(err: CoreError) -> Self {
+        if let CoreError::InvalidHostConfig(errors) = err {
+            return Self::validation(errors);
+        }
+
+        let (status, code) = match &err {
+            CoreError::HostNotFound(_) => (StatusCode::NOT_FOUND, "HOST_NOT_FOUND"),
+            CoreError::HostAlreadyExists(_) => (StatusCode::CONFLICT, "HOST_ALREADY_EXISTS"),
+            CoreError::HostBusy(_)
+            | CoreError::HostOwned(_)
+            | CoreError::InvalidTransition { .. } => (StatusCode::CONFLICT, "HOST_BUSY"),
+            CoreError::NotUpdating(_) => (StatusCode::CONFLICT, "HOST_NOT_UPDATING"),
+            CoreError::NotFailed(_) => (StatusCode::CONFLICT, "HOST_NOT_FAILED"),
+            CoreError::HostPaused(_) => (StatusCode::CONFLICT, "HOST_PAUSED"),
+            CoreError::Cancelled(_) => (StatusCode::CONFLICT, "OPERATION_CANCELLED"),
+            CoreError::HostUnreachable { .. } => {
+                (StatusCode::SERVICE_UNAVAILABLE, "HOST_UNREACHABLE")
+            }
+            CoreError::HostCrashed { .. } => {
+                (StatusCode::SERVICE_UNAVAILABLE, "HOST_ACTOR_CRASHED")
+            }
+            CoreError::ConfigError(_) => (StatusCode::BAD_REQUEST, "INVALID_CONFIG"),
+            CoreError::OsqueryNotInstalled { .. } => {
+                (StatusCode::FAILED_DEPENDENCY, "OSQUERY_NOT_INSTALLED")
+            }
+            _ => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR"),
+        };
+
+        Self {
+            status,
+            error: ApiError {
+                code: code.to_string(),
+                message: err.to_string(),
+                details: Vec::new(),
+            },
+        }
+    }
+}
+
+impl<M> From<SendError<M, CoreError>> for AppError {
+    fn from(err: SendError<M, CoreError>) -> Self {
+        match err {
+            SendError::HandlerError(e) => e.into(),
+            other => Self::internal(format!("orchestrator unavailable: {}", other)),
+        }
+    }
+}
+
+impl From<ScheduleError> for AppError {
+    fn from(err: ScheduleError) -> Self {
+        let (status, code) = match &err {
+            ScheduleError::NotFound(_) => (StatusCode::NOT_FOUND, "SCHEDULE_NOT_FOUND"),
+            ScheduleError::AlreadyRunning(_) => (StatusCode::CONFLICT, "SCHEDULE_RUNNING"),
+        };
+
+        Self {
+            status,
+            error: ApiError {
+                code: code.to_string(),
+                message: err.to_string(),
+                details: Vec::new(),
+            },
+        }
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         (self.status, Json(self.error)).into_response()
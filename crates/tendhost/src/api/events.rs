@@ -0,0 +1,110 @@
+//! Event history endpoints
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::Deserialize;
+use tendhost_api::events::EventEnvelope;
+use tendhost_api::pagination::{PageParams, Paginated, paginate_by_cursor};
+use tendhost_core::{FieldError, GetRecentEvents};
+use utoipa::IntoParams;
+
+use crate::api::error::{ApiError, AppError, page_param_errors};
+use crate::state::AppState;
+
+/// Events per host returned by `GET /events/recent` by default
+const DEFAULT_RECENT_PER_HOST: usize = 10;
+
+/// Most events per host `GET /events/recent` returns
+const MAX_RECENT_PER_HOST: usize = 100;
+
+/// Query parameters of `GET /events/recent`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RecentEventsQuery {
+    /// Events returned per host, 1 to 100 (default 10)
+    #[serde(default = "default_per_host")]
+    pub per_host: usize,
+}
+
+fn default_per_host() -> usize {
+    DEFAULT_RECENT_PER_HOST
+}
+
+/// Page through recent events, oldest first
+///
+/// Pass the returned `next_cursor` as `cursor` to get the events delivered
+/// since the previous page. Only the most recent events are kept, so a gap
+/// between `cursor` and the first returned `seq` means older events were
+/// trimmed.
+///
+/// # Errors
+/// Returns `AppError` if the page size is out of range
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "events",
+    params(PageParams),
+    responses(
+        (status = 200, description = "Events after the cursor", body = Paginated<EventEnvelope>),
+        (status = 422, description = "Invalid page size", body = ApiError),
+    )
+)]
+pub async fn list_events(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<Paginated<EventEnvelope>>, AppError> {
+    let errors = page_param_errors(&params);
+    if !errors.is_empty() {
+        return Err(AppError::validation(errors));
+    }
+
+    Ok(Json(paginate_by_cursor(
+        state.events.history(),
+        &params,
+        |envelope| envelope.seq,
+    )))
+}
+
+/// The last events of each host, oldest first
+///
+/// For clients that connect to `/ws/events` and want to catch up on what
+/// happened while they weren't listening: envelopes carry the same `seq`
+/// as on the stream, so events seen both ways can be told apart. Events
+/// not about a single host, such as fleet results, are not included.
+///
+/// # Errors
+/// Returns `AppError` if `per_host` is out of range
+#[utoipa::path(
+    get,
+    path = "/events/recent",
+    tag = "events",
+    params(RecentEventsQuery),
+    responses(
+        (status = 200, description = "Recent events of each host", body = Vec<EventEnvelope>),
+        (status = 422, description = "Invalid per_host", body = ApiError),
+    )
+)]
+pub async fn recent_events(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RecentEventsQuery>,
+) -> Result<Json<Vec<EventEnvelope>>, AppError> {
+    if !(1..=MAX_RECENT_PER_HOST).contains(&query.per_host) {
+        return Err(AppError::validation(vec![FieldError::new(
+            "per_host",
+            format!("must be between 1 and {MAX_RECENT_PER_HOST}"),
+        )]));
+    }
+
+    let events = state
+        .orchestrator
+        .ask(GetRecentEvents {
+            per_host: query.per_host,
+        })
+        .await
+        .map_err(|e| AppError::internal(format!("failed to get recent events: {e}")))?;
+    Ok(Json(events))
+}
@@ -1,3 +1,156 @@
 //! Fleet API routes
 
-// TODO: Implement /fleet/update endpoints
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use tendhost_api::requests::FleetUpdateRequest;
+use tendhost_api::responses::{FleetDryRunReport, FleetSummary};
+use tendhost_core::{
+    CoreError, FactFilter, FleetDryRun, FleetFilter, FleetUpdateConfig, GetFleetSummary,
+    GetHostStatus, HostName, Traced, TriggerFleetUpdate,
+};
+use tracing::{info, warn};
+
+use crate::api::error::{ApiError, AppError};
+use crate::state::AppState;
+
+/// Convert an API fleet update request into the orchestrator's config
+fn fleet_config(req: FleetUpdateRequest) -> Result<FleetUpdateConfig, CoreError> {
+    if req.batch_size == 0 {
+        return Err(CoreError::ConfigError(
+            "batch_size must be at least 1".to_string(),
+        ));
+    }
+
+    let filter = req
+        .filter
+        .map(|f| {
+            let fact_filters = f
+                .facts
+                .unwrap_or_default()
+                .iter()
+                .map(|fact| fact.parse::<FactFilter>())
+                .collect::<Result<_, _>>()
+                .map_err(CoreError::ConfigError)?;
+            Ok::<_, CoreError>(FleetFilter {
+                tags: f.tags.unwrap_or_default(),
+                groups: f.groups.unwrap_or_default(),
+                exclude_hosts: f
+                    .exclude_hosts
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(HostName::from)
+                    .collect(),
+                fact_filters,
+            })
+        })
+        .transpose()?;
+
+    Ok(FleetUpdateConfig {
+        batch_size: req.batch_size,
+        delay_between_batches: Duration::from_millis(req.delay_ms),
+        filter,
+        dry_run: req.dry_run,
+        scope: req.scope,
+        canary_hosts: req.canary_hosts.into_iter().map(HostName::from).collect(),
+        halt_on_canary_failure: req.halt_on_canary_failure,
+    })
+}
+
+/// Trigger a fleet-wide update
+///
+/// The update runs in the background; progress is reported over the
+/// WebSocket event stream. Hosts in `canary_hosts` are updated first, in
+/// one batch, and the rest follow with dependents before the hosts they
+/// depend on, in name order otherwise. With `dry_run` set, nothing is
+/// updated: every matching host is checked and the aggregated report,
+/// batches included, is returned.
+///
+/// # Errors
+/// Returns `AppError` if the request is invalid
+#[utoipa::path(
+    post,
+    path = "/fleet/update",
+    tag = "fleet",
+    request_body = FleetUpdateRequest,
+    responses(
+        (status = 202, description = "Fleet update started"),
+        (status = 200, description = "Dry-run report", body = FleetDryRunReport),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 404, description = "Canary host not found", body = ApiError),
+    )
+)]
+pub async fn update_fleet(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<FleetUpdateRequest>,
+) -> Result<Response, AppError> {
+    let config = fleet_config(req)?;
+
+    if config.dry_run {
+        let report = state
+            .orchestrator
+            .ask(Traced::new(FleetDryRun { config }))
+            .await?;
+        return Ok(Json(report).into_response());
+    }
+
+    // The update itself runs detached, so catch mistyped canaries up front
+    for hostname in &config.canary_hosts {
+        state
+            .orchestrator
+            .ask(Traced::new(GetHostStatus {
+                hostname: hostname.clone(),
+            }))
+            .await?;
+    }
+
+    let orchestrator = state.orchestrator.clone();
+    tokio::spawn(async move {
+        match orchestrator
+            .ask(Traced::new(TriggerFleetUpdate {
+                config,
+                scheduled: false,
+            }))
+            .await
+        {
+            Ok(progress) => info!(
+                total = progress.total_hosts,
+                completed = progress.completed,
+                failed = progress.failed,
+                "fleet update finished"
+            ),
+            Err(e) => warn!(error = %e, "fleet update failed"),
+        }
+    });
+
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+/// Summarize the fleet: hosts per state, pending updates and failures
+///
+/// Served from recently fetched host statuses, so it is cheap to poll.
+///
+/// # Errors
+/// Returns `AppError` if the orchestrator is unavailable
+#[utoipa::path(
+    get,
+    path = "/fleet/status",
+    tag = "fleet",
+    responses(
+        (status = 200, description = "Fleet summary", body = FleetSummary),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
+pub async fn fleet_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<FleetSummary>, AppError> {
+    Ok(Json(
+        state.orchestrator.ask(Traced::new(GetFleetSummary)).await?,
+    ))
+}
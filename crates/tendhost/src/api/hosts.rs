@@ -1,268 +1,772 @@
 //! Host management API endpoints
 
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Arc;
+use std::time::Duration;
 
 use axum::{
     Json,
     extract::{Path, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
-use tendhost_api::requests::UpdateRequest;
+use serde_json::Value;
+use tendhost_api::pagination::{PageParams, Pagination, paginate_vec};
+use tendhost_api::requests::{
+    HostSort, ImportParams, PreviewParams, RegisterHostRequest, SortOrder, UpdateParams,
+    UpdateRequest,
+};
+use tendhost_api::responses::{
+    AppliedFilters, BulkRegisterReport, CommandHistoryEntry, HostDetail, HostInventoryResponse,
+    HostListResponse, HostSummary, ImportReport, OsqueryInstallResponse, StateTransitionInfo,
+    UpdateAccepted, UpdateHistoryEntry, UpdatePreview, UpdateResultInfo,
+};
+use tendhost_core::responses::{command_entry, connection_stats};
 use tendhost_core::{
-    AcknowledgeHost, GetHostStatus, ListHosts, QueryHostInventory, RegisterHost, RetryHost,
-    TriggerHostUpdate, UnregisterHost,
+    AcknowledgeHost, CancelHostUpdate, CollectHostInventory, CoreError, FactFilter, FieldError,
+    GetHostCommandHistory, GetHostExecutorStats, GetHostInventoryDiff, GetHostStatus,
+    GetHostTransitionHistory, GetHostUpdateHistory, HostConfigPatch, HostName, HostPolicyPatch,
+    HostState, HostStatus, Initiator, InstallHostOsquery, ListHostConfigs, ListHosts, PauseHost,
+    PreviewHostUpdate, QueryHostInventory, RegisterHost, RegisterHosts, ResumeHost, RetryHost,
+    Traced, TriggerHostUpdate, UnregisterHost, UpdateHostConfig,
 };
-use utoipa::ToSchema;
+use tendhost_inventory::backend::shell::OSQUERY_ONLY_SECTIONS;
+use tendhost_inventory::{HostInventory, InventoryDiff};
+use tracing::{info, warn};
+use utoipa::{IntoParams, ToSchema};
 
-use crate::api::error::AppError;
+use crate::api::error::{ApiError, AppError, page_param_errors};
+use crate::hosts_toml;
 use crate::state::AppState;
 
-/// Query parameters for listing hosts
-#[derive(Debug, Deserialize, ToSchema)]
+/// Filters and sorting for listing hosts
+///
+/// Paging comes from [`PageParams`], read from the same query string.
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ListHostsQuery {
-    /// Page number (1-indexed)
-    #[serde(default = "default_page")]
-    pub page: u64,
-    /// Items per page
-    #[serde(default = "default_per_page")]
-    pub per_page: u64,
-    /// Filter by tags
+    /// Comma-separated tags; hosts must have all of them
     #[serde(default)]
     pub tags: Option<String>,
+    /// Comma-separated facts as `key:value`, e.g. `os_id:debian`; hosts
+    /// must have all of them
+    #[serde(default)]
+    pub fact: Option<String>,
+    /// Only hosts in this state (`idle`, `pending_updates`, ...)
+    #[serde(default)]
+    #[schema(value_type = Option<String>)]
+    #[param(value_type = Option<String>)]
+    pub state: Option<HostState>,
+    /// Only hosts in this group from the `[groups]` config section
+    #[serde(default)]
+    pub group: Option<String>,
+    /// Only hosts whose name starts with this prefix
+    #[serde(default)]
+    pub search: Option<String>,
+    /// Only failed hosts whose failure has (`true`) or hasn't (`false`)
+    /// been acknowledged
+    #[serde(default)]
+    pub acknowledged: Option<bool>,
+    /// Sort field
+    #[serde(default)]
+    pub sort: HostSort,
+    /// Sort direction
+    #[serde(default)]
+    pub order: SortOrder,
 }
 
-fn default_page() -> u64 {
-    1
-}
-
-fn default_per_page() -> u64 {
-    50
-}
-
-/// Host list response
-#[derive(Debug, Serialize, ToSchema)]
-pub struct HostListResponse {
-    /// List of hosts
-    pub hosts: Vec<HostSummary>,
-    /// Pagination info
-    pub pagination: PaginationInfo,
-}
-
-/// Host summary for list view
-#[derive(Debug, Clone, Serialize, ToSchema)]
-pub struct HostSummary {
-    /// Host name
-    pub name: String,
-    /// Current state
-    pub state: String,
-    /// Number of pending updates
-    pub pending_updates: Option<u32>,
-    /// Tags
-    pub tags: Vec<String>,
-    /// Last update timestamp
-    pub last_updated: Option<String>,
-    /// Error message if failed
-    pub error: Option<String>,
-}
-
-/// Pagination metadata
-#[derive(Debug, Serialize, ToSchema)]
-pub struct PaginationInfo {
-    /// Current page (1-indexed)
-    pub page: u64,
-    /// Items per page
-    pub per_page: u64,
-    /// Total number of items
-    pub total_items: u64,
-    /// Total number of pages
-    pub total_pages: u64,
-}
-
-/// Host details response
-#[derive(Debug, Serialize, ToSchema)]
-pub struct HostDetailResponse {
-    /// Host name
-    pub name: String,
-    /// Current state
-    pub state: String,
-    /// Number of pending updates
-    pub pending_updates: Option<u32>,
-    /// Tags
-    pub tags: Vec<String>,
-    /// Last update timestamp
-    pub last_updated: Option<String>,
-    /// Error message if failed
-    pub error: Option<String>,
+/// Detail view of a host with the given status, from the orchestrator's
+/// configuration and the daemon's inventory cache
+async fn load_host_detail(state: &AppState, status: HostStatus) -> Result<HostDetail, AppError> {
+    let configs = state
+        .orchestrator
+        .ask(Traced::new(ListHostConfigs))
+        .await
+        .map_err(|e| AppError::internal(format!("failed to list host configs: {e}")))?;
+    let config = configs.iter().find(|c| c.name == status.name);
+    let connection = state
+        .orchestrator
+        .ask(Traced::new(GetHostExecutorStats {
+            hostname: status.name.clone(),
+        }))
+        .await?;
+    let inventories = state.inventories.read().await;
+    let inventory = inventories.get(&status.name);
+    let mut detail = status.into_detail(config, inventory);
+    detail.connection = connection.map(connection_stats);
+    Ok(detail)
 }
 
-/// Host registration request
+/// Partial host configuration update request
+///
+/// Omitted fields keep their current value. The host name cannot be changed.
 #[derive(Debug, Deserialize, ToSchema)]
-pub struct RegisterHostRequest {
-    /// Host name
-    pub name: String,
-    /// Host address
-    pub addr: String,
+pub struct UpdateHostConfigRequest {
+    /// Host name (must match the path)
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Host address, optionally as `host:port`
+    #[serde(default)]
+    pub addr: Option<String>,
+    /// SSH port
+    #[serde(default)]
+    pub port: Option<u16>,
     /// SSH user
-    #[serde(default = "default_user")]
-    pub user: String,
+    #[serde(default)]
+    pub user: Option<String>,
     /// SSH key path
+    #[serde(default)]
     pub ssh_key: Option<String>,
+    /// Environment variable holding the SSH key passphrase
+    #[serde(default)]
+    pub ssh_key_passphrase_env: Option<String>,
+    /// Seconds establishing the SSH connection may take
+    #[serde(default)]
+    pub connect_timeout_secs: Option<u64>,
+    /// Most commands run over the SSH connection at once
+    #[serde(default)]
+    pub max_ssh_channels: Option<usize>,
+    /// Jump host to connect through
+    #[serde(default)]
+    pub jump_host: Option<String>,
+    /// Docker compose directories
+    #[serde(default)]
+    pub compose_paths: Option<Vec<String>>,
+    /// Env files by compose directory, replacing the configured ones
+    #[serde(default)]
+    pub compose_env_files: Option<BTreeMap<String, String>>,
     /// Tags
     #[serde(default)]
-    pub tags: Vec<String>,
+    pub tags: Option<Vec<String>>,
+    /// Hosts this one depends on, replacing the configured ones
+    #[serde(default)]
+    pub depends_on: Option<Vec<String>>,
+    /// Variables set for every command, replacing the configured ones
+    #[serde(default)]
+    pub env: Option<BTreeMap<String, String>>,
+    /// Policy fields
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub policy: Option<HostPolicyPatch>,
 }
 
-fn default_user() -> String {
-    "root".to_string()
+impl From<UpdateHostConfigRequest> for HostConfigPatch {
+    fn from(req: UpdateHostConfigRequest) -> Self {
+        Self {
+            name: req.name.map(HostName::from),
+            addr: req.addr,
+            port: req.port,
+            user: req.user,
+            ssh_key: req.ssh_key,
+            ssh_key_passphrase_env: req.ssh_key_passphrase_env,
+            connect_timeout: req.connect_timeout_secs.map(Duration::from_secs),
+            max_ssh_channels: req.max_ssh_channels,
+            jump_host: req.jump_host,
+            compose_paths: req.compose_paths,
+            compose_env_files: req.compose_env_files,
+            tags: req.tags,
+            depends_on: req
+                .depends_on
+                .map(|names| names.iter().map(|name| HostName::new(name)).collect()),
+            env: req.env,
+            policy: req.policy,
+        }
+    }
+}
+
+/// Weak ETag of a page of hosts
+///
+/// Covers what a list view shows changing between polls: each host's
+/// name, state, pending updates, last update, error, reachability and
+/// pause, in page order, plus the page's position in the list.
+fn host_list_etag(hosts: &[HostSummary], pagination: &Pagination) -> String {
+    let mut hasher = DefaultHasher::new();
+    for host in hosts {
+        (
+            &host.name,
+            &host.state,
+            host.pending_updates,
+            host.last_updated,
+            &host.error,
+            host.reachable,
+            host.paused,
+        )
+            .hash(&mut hasher);
+    }
+    (pagination.page, pagination.per_page, pagination.total_items).hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+/// Whether the request's `If-None-Match` lists `etag`
+///
+/// Comparison is weak, so `"x"` and `W/"x"` match, and `*` matches
+/// anything.
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|tag| tag.trim() == "*" || opaque(tag) == etag)
 }
 
 /// List all managed hosts
 ///
+/// The response carries a weak `ETag`; a request sending it back in
+/// `If-None-Match` gets an empty `304` while the page is unchanged.
+///
 /// # Errors
 /// Returns `AppError` if orchestrator communication fails
+#[utoipa::path(
+    get,
+    path = "/hosts",
+    tag = "hosts",
+    params(
+        PageParams,
+        ListHostsQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous response"),
+    ),
+    responses(
+        (status = 200, description = "Page of hosts", body = HostListResponse,
+            headers(("ETag" = String, description = "Weak ETag of the page"))),
+        (status = 304, description = "Page unchanged since the ETag in If-None-Match"),
+        (status = 400, description = "Unknown state, sort or order value"),
+        (status = 422, description = "Invalid page, page size, group or fact filter", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
 pub async fn list_hosts(
     State(state): State<Arc<AppState>>,
+    Query(page): Query<PageParams>,
     Query(query): Query<ListHostsQuery>,
-) -> Result<impl IntoResponse, AppError> {
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let mut errors = page_param_errors(&page);
+    if page.cursor.is_some() {
+        errors.push(FieldError::new(
+            "cursor",
+            "hosts are paged by number, not cursor",
+        ));
+    }
+    let config = state.config();
+    let group_members = match &query.group {
+        Some(group) => match config.groups.get(group) {
+            Some(members) => Some(members),
+            None => {
+                errors.push(FieldError::new("group", format!("unknown group '{group}'")));
+                None
+            }
+        },
+        None => None,
+    };
+    let mut fact_filters = Vec::new();
+    for fact in query.fact.as_deref().unwrap_or_default().split(',') {
+        if fact.trim().is_empty() {
+            continue;
+        }
+        match fact.parse::<FactFilter>() {
+            Ok(filter) => fact_filters.push(filter),
+            Err(e) => errors.push(FieldError::new("fact", e)),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(AppError::validation(errors));
+    }
+
     // Get all hosts from orchestrator
-    let hosts = state
+    let mut hosts = state
         .orchestrator
-        .ask(ListHosts)
+        .ask(Traced::new(ListHosts))
         .await
         .map_err(|e| AppError::internal(format!("failed to list hosts: {e}")))?;
 
-    // Apply tag filtering if specified
-    let mut filtered_hosts = hosts;
-    if let Some(tags_str) = &query.tags {
-        let filter_tags: Vec<&str> = tags_str.split(',').collect();
-        filtered_hosts.retain(|h| {
-            filter_tags
-                .iter()
-                .all(|tag| h.tags.iter().any(|t| t == tag))
-        });
-    }
+    let filter_tags: Vec<String> = query
+        .tags
+        .as_deref()
+        .map(|tags| {
+            tags.split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+    hosts.retain(|h| {
+        filter_tags.iter().all(|tag| h.tags.contains(tag))
+            && fact_filters.iter().all(|f| f.matches(&h.facts))
+            && query.state.is_none_or(|s| h.state == s)
+            && group_members.is_none_or(|members| members.iter().any(|m| h.name == *m))
+            && query
+                .search
+                .as_deref()
+                .is_none_or(|prefix| h.name.starts_with(prefix))
+            && query
+                .acknowledged
+                .is_none_or(|a| h.acknowledged() == Some(a))
+    });
 
-    // Calculate pagination
-    let total_items = filtered_hosts.len() as u64;
-    let total_pages = total_items.div_ceil(query.per_page);
-    #[allow(clippy::cast_possible_truncation)]
-    let start = ((query.page - 1) * query.per_page) as usize;
-    #[allow(clippy::cast_possible_truncation)]
-    let end = (start + query.per_page as usize).min(filtered_hosts.len());
+    // Sort before paging so pages are stable between requests
+    hosts.sort_by(|a, b| {
+        let by_field = match query.sort {
+            HostSort::Name => Ordering::Equal,
+            HostSort::State => (a.state as u8).cmp(&(b.state as u8)),
+            HostSort::PendingUpdates => a.pending_updates.cmp(&b.pending_updates),
+            HostSort::LastUpdated => a.last_updated.cmp(&b.last_updated),
+        };
+        let ordering = by_field.then_with(|| a.name.cmp(&b.name));
+        match query.order {
+            SortOrder::Asc => ordering,
+            SortOrder::Desc => ordering.reverse(),
+        }
+    });
 
-    // Get page of results
-    let page_hosts: Vec<HostSummary> = filtered_hosts[start..end]
-        .iter()
-        .map(|h| HostSummary {
-            name: h.name.clone(),
-            state: format!("{:?}", h.state),
-            pending_updates: h.pending_updates,
-            tags: h.tags.clone(),
-            last_updated: h.last_updated.map(|dt| dt.to_rfc3339()),
-            error: h.error.clone(),
-        })
-        .collect();
+    // Pages past the end are empty
+    let page = paginate_vec(hosts, &page).map(HostSummary::from);
+    let etag = host_list_etag(&page.data, &page.pagination);
+    if if_none_match(&headers, &etag) {
+        return Ok((StatusCode::NOT_MODIFIED, [(header::ETAG, etag)]).into_response());
+    }
 
-    Ok(Json(HostListResponse {
-        hosts: page_hosts,
-        pagination: PaginationInfo {
-            page: query.page,
-            per_page: query.per_page,
-            total_items,
-            total_pages,
+    let response = Json(HostListResponse {
+        hosts: page.data,
+        pagination: page.pagination,
+        filters: AppliedFilters {
+            tags: filter_tags,
+            facts: fact_filters.iter().map(ToString::to_string).collect(),
+            state: query.state.map(|s| s.to_string()),
+            group: query.group,
+            search: query.search,
+            acknowledged: query.acknowledged,
+            sort: query.sort,
+            order: query.order,
         },
-    }))
+    });
+    Ok(([(header::ETAG, etag)], response).into_response())
 }
 
 /// Get details for a specific host
 ///
 /// # Errors
 /// Returns `AppError` if host not found or orchestrator communication fails
+#[utoipa::path(
+    get,
+    path = "/hosts/{hostname}",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 200, description = "Host status, configuration, inventory summary and pending packages", body = HostDetail),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
 pub async fn get_host(
     State(state): State<Arc<AppState>>,
-    Path(hostname): Path<String>,
+    Path(hostname): Path<HostName>,
 ) -> Result<impl IntoResponse, AppError> {
     let status = state
         .orchestrator
-        .ask(GetHostStatus { hostname })
-        .await
-        .map_err(|e| AppError::internal(format!("failed to get host status: {e}")))?;
-
-    Ok(Json(HostDetailResponse {
-        name: status.name,
-        state: format!("{:?}", status.state),
-        pending_updates: status.pending_updates,
-        tags: status.tags,
-        last_updated: status.last_updated.map(|dt| dt.to_rfc3339()),
-        error: status.error,
-    }))
+        .ask(Traced::new(GetHostStatus { hostname }))
+        .await?;
+
+    Ok(Json(load_host_detail(&state, status).await?))
 }
 
 /// Register a new host
 ///
 /// # Errors
 /// Returns `AppError` if registration fails
+#[utoipa::path(
+    post,
+    path = "/hosts",
+    tag = "hosts",
+    request_body = RegisterHostRequest,
+    responses(
+        (status = 201, description = "Host registered"),
+        (status = 409, description = "Host already exists", body = ApiError),
+        (status = 422, description = "Invalid host configuration", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
 pub async fn register_host(
     State(state): State<Arc<AppState>>,
     Json(req): Json<RegisterHostRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    use tendhost_core::{HostConfig, HostPolicy};
+    let config = host_config(req);
+    state
+        .orchestrator
+        .ask(Traced::new(RegisterHost { config }))
+        .await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Register several hosts in one request
+///
+/// Hosts are registered independently and concurrently. The response lists
+/// each host's outcome in request order: every host with `created` or
+/// `already_exists` status is registered afterwards, and none with `error`
+/// status are. Existing hosts are left unchanged.
+///
+/// # Errors
+/// Returns `AppError` if the batch names a host more than once, in which
+/// case nothing is registered
+#[utoipa::path(
+    post,
+    path = "/hosts/bulk",
+    tag = "hosts",
+    request_body = Vec<RegisterHostRequest>,
+    responses(
+        (status = 200, description = "Per-host registration results", body = BulkRegisterReport),
+        (status = 400, description = "Duplicate host names in the batch", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
+pub async fn register_hosts(
+    State(state): State<Arc<AppState>>,
+    Json(requests): Json<Vec<RegisterHostRequest>>,
+) -> Result<Json<BulkRegisterReport>, AppError> {
+    let configs = requests.into_iter().map(host_config).collect();
+    let report = state
+        .orchestrator
+        .ask(Traced::new(RegisterHosts { configs }))
+        .await?;
+    info!(
+        created = report.created,
+        already_exists = report.already_exists,
+        failed = report.failed,
+        "bulk host registration"
+    );
+
+    Ok(Json(report))
+}
 
-    let config = HostConfig {
-        name: req.name,
+/// Export every registered host as TOML
+///
+/// The document holds one `[[host]]` table per host, sorted by name, in
+/// the same shape as the daemon config. Only configuration is included,
+/// plus the facts detected on each host in a `facts` table;
+/// `POST /hosts/import` reads it back, ignoring the facts.
+///
+/// # Errors
+/// Returns `AppError` if the orchestrator is unavailable
+#[utoipa::path(
+    get,
+    path = "/hosts/export",
+    tag = "hosts",
+    responses(
+        (status = 200, description = "Registered hosts as `[[host]]` tables", body = String, content_type = "application/toml"),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
+pub async fn export_hosts(State(state): State<Arc<AppState>>) -> Result<Response, AppError> {
+    let configs = state
+        .orchestrator
+        .ask(Traced::new(ListHostConfigs))
+        .await
+        .map_err(|e| AppError::internal(format!("failed to list host configs: {e}")))?;
+    let facts = state
+        .orchestrator
+        .ask(Traced::new(ListHosts))
+        .await
+        .map_err(|e| AppError::internal(format!("failed to list hosts: {e}")))?
+        .into_iter()
+        .map(|status| (status.name, status.facts))
+        .collect();
+    let document = hosts_toml::to_toml(&configs, &facts)?;
+    Ok(([(header::CONTENT_TYPE, "application/toml")], document).into_response())
+}
+
+/// Register the hosts of an exported TOML document
+///
+/// With `mode=merge` (the default) hosts that are already registered are
+/// skipped. With `mode=replace` the registered hosts are made to match the
+/// document: changed hosts are reconfigured and hosts missing from it are
+/// unregistered, unless one of those is busy, in which case nothing is
+/// changed.
+///
+/// # Errors
+/// Returns `AppError` if the document is invalid or names a host twice, or
+/// a replace would unregister a busy host
+#[utoipa::path(
+    post,
+    path = "/hosts/import",
+    tag = "hosts",
+    params(ImportParams),
+    request_body(content = String, description = "`[[host]]` tables as written by `GET /hosts/export`", content_type = "application/toml"),
+    responses(
+        (status = 200, description = "What was imported", body = ImportReport),
+        (status = 400, description = "Invalid document", body = ApiError),
+        (status = 409, description = "A host to unregister is busy", body = ApiError),
+    )
+)]
+pub async fn import_hosts(
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ImportParams>,
+    body: String,
+) -> Result<Json<ImportReport>, AppError> {
+    let hosts = hosts_toml::from_toml(&body)?;
+    let report = hosts_toml::import(&state, hosts, params.mode).await?;
+    Ok(Json(report))
+}
+
+fn host_config(req: RegisterHostRequest) -> tendhost_core::HostConfig {
+    tendhost_core::HostConfig {
+        name: req.name.into(),
         addr: req.addr,
+        port: req.port,
         user: req.user,
         ssh_key: req.ssh_key,
+        ssh_key_passphrase_env: None,
+        connect_timeout: req.connect_timeout_secs.map(Duration::from_secs),
+        max_ssh_channels: None,
+        jump_host: req.jump_host,
         compose_paths: vec![],
+        compose_env_files: BTreeMap::new(),
         tags: req.tags,
-        policy: HostPolicy::default(),
-    };
+        depends_on: vec![],
+        env: BTreeMap::new(),
+        policy: tendhost_core::HostPolicy::default(),
+    }
+}
 
-    state
+/// Update the configuration of a registered host
+///
+/// # Errors
+/// Returns `AppError` if the host is not found, the patch renames the host,
+/// or the host is busy while connection settings change
+#[utoipa::path(
+    patch,
+    path = "/hosts/{hostname}",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    request_body = UpdateHostConfigRequest,
+    responses(
+        (status = 200, description = "Updated host details", body = HostDetail),
+        (status = 400, description = "Invalid configuration", body = ApiError),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host is busy", body = ApiError),
+        (status = 422, description = "Invalid host configuration", body = ApiError),
+    )
+)]
+pub async fn update_host_config(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+    Json(req): Json<UpdateHostConfigRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let status = state
         .orchestrator
-        .ask(RegisterHost { config })
-        .await
-        .map_err(|e| AppError::internal(format!("failed to register host: {e}")))?;
+        .ask(Traced::new(UpdateHostConfig {
+            hostname,
+            patch: req.into(),
+        }))
+        .await?;
 
-    Ok(StatusCode::CREATED)
+    Ok(Json(load_host_detail(&state, status).await?))
 }
 
 /// Unregister a host
 ///
 /// # Errors
 /// Returns `AppError` if host not found or unregistration fails
+#[utoipa::path(
+    delete,
+    path = "/hosts/{hostname}",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 204, description = "Host unregistered"),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
 pub async fn unregister_host(
     State(state): State<Arc<AppState>>,
-    Path(hostname): Path<String>,
+    Path(hostname): Path<HostName>,
 ) -> Result<impl IntoResponse, AppError> {
     state
         .orchestrator
-        .ask(UnregisterHost { hostname })
-        .await
-        .map_err(|e| AppError::internal(format!("failed to unregister host: {e}")))?;
+        .ask(Traced::new(UnregisterHost {
+            hostname: hostname.clone(),
+        }))
+        .await?;
+
+    state.inventories.write().await.remove(&hostname);
 
     Ok(StatusCode::NO_CONTENT)
 }
 
 /// Trigger update for a specific host
 ///
+/// The update runs in the background; progress is reported over the
+/// WebSocket event stream. With `wait` the request instead answers once
+/// the update has finished, with its result; it isn't cut off by the
+/// request timeout then.
+///
+/// A paused host is only updated when the request sets `force`.
+///
 /// # Errors
-/// Returns `AppError` if update trigger fails
+/// Returns `AppError` if the host is not found, paused, or busy with
+/// another operation
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/update",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name"), UpdateParams),
+    request_body = UpdateRequest,
+    responses(
+        (status = 200, description = "Update finished; only with `wait`", body = UpdateResultInfo),
+        (status = 202, description = "Update started", body = UpdateAccepted),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host is busy, or paused and the update not forced", body = ApiError),
+        (status = 503, description = "Host is unreachable; its circuit breaker is open", body = ApiError),
+    )
+)]
 pub async fn update_host(
     State(state): State<Arc<AppState>>,
-    Path(hostname): Path<String>,
+    Path(hostname): Path<HostName>,
+    Query(params): Query<UpdateParams>,
     Json(req): Json<UpdateRequest>,
+) -> Result<Response, AppError> {
+    let status = state
+        .orchestrator
+        .ask(Traced::new(GetHostStatus {
+            hostname: hostname.clone(),
+        }))
+        .await?;
+
+    if let Some(retry_at) = status
+        .circuit_open_until
+        .filter(|at| *at > chrono::Utc::now())
+    {
+        return Err(CoreError::HostUnreachable {
+            host: hostname.to_string(),
+            retry_at,
+        }
+        .into());
+    }
+    if let Some(owner) = status
+        .owner
+        .filter(|owner| owner.initiator != Initiator::ManualApi)
+    {
+        return Err(CoreError::HostOwned(owner).into());
+    }
+    if status.paused && !req.force {
+        return Err(CoreError::HostPaused(format!(
+            "{hostname}; force the update to run it anyway"
+        ))
+        .into());
+    }
+    if !status.state.can_transition_to(HostState::Updating) {
+        return Err(CoreError::HostBusy(format!("{hostname} is {}", status.state)).into());
+    }
+
+    let update = TriggerHostUpdate {
+        hostname: hostname.clone(),
+        dry_run: req.dry_run,
+        scope: req.scope,
+        stack: req.stack,
+        packages: req.packages,
+        force: req.force,
+        skip_cleanup: req.skip_cleanup,
+    };
+    if params.wait {
+        let result = state.orchestrator.ask(Traced::new(update)).await?;
+        return Ok(Json(UpdateResultInfo::from(result)).into_response());
+    }
+
+    let accepted = UpdateAccepted {
+        host: hostname.to_string(),
+        dry_run: req.dry_run,
+        message: if req.dry_run {
+            format!("Dry run started on {hostname}; no packages will be installed")
+        } else {
+            format!("Update started on {hostname}")
+        },
+    };
+
+    let orchestrator = state.orchestrator.clone();
+    tokio::spawn(async move {
+        match orchestrator.ask(Traced::new(update)).await {
+            Ok(result) => info!(
+                host = %hostname,
+                upgraded = result.upgraded_count,
+                dry_run = req.dry_run,
+                "host update finished"
+            ),
+            Err(e) => warn!(host = %hostname, error = %e, "host update failed"),
+        }
+    });
+
+    Ok((StatusCode::ACCEPTED, Json(accepted)).into_response())
+}
+
+/// Preview what an update of a specific host would do
+///
+/// Simulates the upgrade and checks the free disk space, the pause and
+/// reservations of the host without changing it: no state transition,
+/// event or history entry comes of it, so it works while updates are
+/// pending. Busy states other than updating and rebooting are reported as
+/// blockers rather than refused.
+///
+/// # Errors
+/// Returns `AppError` if the host is not found, updating or rebooting, or
+/// the simulation fails
+#[utoipa::path(
+    get,
+    path = "/hosts/{hostname}/update-preview",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name"), PreviewParams),
+    responses(
+        (status = 200, description = "What the update would do and what would stop it", body = UpdatePreview),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host is updating or rebooting", body = ApiError),
+        (status = 503, description = "Host is unreachable; its circuit breaker is open", body = ApiError),
+    )
+)]
+pub async fn preview_host_update(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+    Query(params): Query<PreviewParams>,
+) -> Result<Json<UpdatePreview>, AppError> {
+    let preview = state
+        .orchestrator
+        .ask(Traced::new(PreviewHostUpdate {
+            hostname,
+            scope: params.scope,
+        }))
+        .await?;
+
+    Ok(Json(preview))
+}
+
+/// Cancel the running update of a specific host
+///
+/// # Errors
+/// Returns `AppError` if the host is not found or not updating
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/cancel",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 202, description = "Cancellation requested"),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host is not updating", body = ApiError),
+    )
+)]
+pub async fn cancel_host_update(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
 ) -> Result<impl IntoResponse, AppError> {
     state
         .orchestrator
-        .ask(TriggerHostUpdate {
-            hostname,
-            dry_run: req.dry_run,
-        })
-        .await
-        .map_err(|e| AppError::internal(format!("failed to trigger update: {e}")))?;
+        .ask(Traced::new(CancelHostUpdate { hostname }))
+        .await?;
 
     Ok(StatusCode::ACCEPTED)
 }
@@ -271,9 +775,18 @@ pub async fn update_host(
 ///
 /// # Errors
 /// Returns `AppError` if reboot trigger fails
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/reboot",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 202, description = "Reboot requested"),
+    )
+)]
 pub async fn reboot_host(
     State(_state): State<Arc<AppState>>,
-    Path(_hostname): Path<String>,
+    Path(_hostname): Path<HostName>,
 ) -> Result<impl IntoResponse, AppError> {
     // For now, we just accept the request
     // TODO: Implement actual reboot logic through orchestrator
@@ -284,15 +797,26 @@ pub async fn reboot_host(
 ///
 /// # Errors
 /// Returns `AppError` if retry fails
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/retry",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 202, description = "Retry started"),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host has not failed", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
 pub async fn retry_host(
     State(state): State<Arc<AppState>>,
-    Path(hostname): Path<String>,
+    Path(hostname): Path<HostName>,
 ) -> Result<impl IntoResponse, AppError> {
     state
         .orchestrator
-        .ask(RetryHost { hostname })
-        .await
-        .map_err(|e| AppError::internal(format!("failed to retry host: {e}")))?;
+        .ask(Traced::new(RetryHost { hostname }))
+        .await?;
 
     Ok(StatusCode::ACCEPTED)
 }
@@ -301,36 +825,504 @@ pub async fn retry_host(
 ///
 /// # Errors
 /// Returns `AppError` if acknowledgement fails
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/acknowledge",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 202, description = "Failure acknowledged"),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host has not failed", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
 pub async fn acknowledge_host(
     State(state): State<Arc<AppState>>,
-    Path(hostname): Path<String>,
+    Path(hostname): Path<HostName>,
 ) -> Result<impl IntoResponse, AppError> {
     state
         .orchestrator
-        .ask(AcknowledgeHost { hostname })
-        .await
-        .map_err(|e| AppError::internal(format!("failed to acknowledge host: {e}")))?;
+        .ask(Traced::new(AcknowledgeHost { hostname }))
+        .await?;
 
     Ok(StatusCode::ACCEPTED)
 }
 
+/// Install osquery on a host
+///
+/// Adds the osquery.io package repository and installs osquery through the
+/// host's package manager, unless a supported version is already there.
+/// Disabled unless `allow_remote_install` is set under `[daemon]`.
+///
+/// # Errors
+/// Returns `AppError` if remote installs are disabled, the host is not
+/// found or busy, or the install fails
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/install-osquery",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 200, description = "osquery is installed", body = OsqueryInstallResponse),
+        (status = 403, description = "Remote installs are disabled", body = ApiError),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host is busy", body = ApiError),
+        (status = 500, description = "Install failed", body = ApiError),
+    )
+)]
+pub async fn install_osquery(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+) -> Result<impl IntoResponse, AppError> {
+    if !state.config().daemon.allow_remote_install {
+        return Err(AppError::remote_install_disabled());
+    }
+
+    let install = state
+        .orchestrator
+        .ask(Traced::new(InstallHostOsquery {
+            hostname: hostname.clone(),
+        }))
+        .await?;
+    info!(host = %hostname, version = ?install.version, installed = install.installed, "osquery install finished");
+
+    Ok(Json(OsqueryInstallResponse {
+        name: hostname.to_string(),
+        version: install.version,
+        installed: install.installed,
+    }))
+}
+
+/// Pause a host
+///
+/// Automation leaves a paused host alone: scheduled and fleet updates skip
+/// it, and it isn't probed or retried automatically. Manual updates need
+/// `force`. Pausing a paused host changes nothing.
+///
+/// # Errors
+/// Returns `AppError` if the host is not found
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/pause",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 202, description = "Host paused"),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
+pub async fn pause_host(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .orchestrator
+        .ask(Traced::new(PauseHost { hostname }))
+        .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Resume a paused host
+///
+/// # Errors
+/// Returns `AppError` if the host is not found
+#[utoipa::path(
+    post,
+    path = "/hosts/{hostname}/resume",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 202, description = "Host resumed"),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
+pub async fn resume_host(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+) -> Result<impl IntoResponse, AppError> {
+    state
+        .orchestrator
+        .ask(Traced::new(ResumeHost { hostname }))
+        .await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Query parameters for a host's inventory
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct InventoryQuery {
+    /// Refresh the package lists first, even if they are recent
+    #[serde(default)]
+    pub refresh: bool,
+    /// Comma-separated sections to return (`system`, `hardware`,
+    /// `packages`, `docker_containers`, `docker_images`, `listening_ports`,
+    /// `services`); all of them by default
+    #[serde(default)]
+    pub include: Option<String>,
+    /// How much of each installed package to return
+    #[serde(default)]
+    #[param(value_type = Option<String>)]
+    pub packages: PackageDetail,
+}
+
+/// How much of each installed package an inventory response carries
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PackageDetail {
+    /// Every collected field
+    #[default]
+    Full,
+    /// Only the name and version
+    Summary,
+}
+
+/// Sections of an inventory that can be requested separately
+const INVENTORY_SECTIONS: [&str; 7] = [
+    "system",
+    "hardware",
+    "packages",
+    "docker_containers",
+    "docker_images",
+    "listening_ports",
+    "services",
+];
+
+/// Parse a comma-separated `include` list, rejecting unknown sections
+fn inventory_sections(include: &str) -> Result<Vec<&str>, FieldError> {
+    let sections: Vec<&str> = include
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    match sections
+        .iter()
+        .find(|section| !INVENTORY_SECTIONS.contains(section))
+    {
+        Some(unknown) => Err(FieldError::new(
+            "include",
+            format!(
+                "unknown section '{unknown}', expected one of {}",
+                INVENTORY_SECTIONS.join(", ")
+            ),
+        )),
+        None => Ok(sections),
+    }
+}
+
+/// The parts of `inventory` a client asked for
+///
+/// `collected_at` and `version` are always kept. With `sections` unset
+/// every section is returned.
+fn select_inventory(
+    inventory: &HostInventory,
+    sections: Option<&[&str]>,
+    packages: PackageDetail,
+) -> Value {
+    let mut value = serde_json::to_value(inventory).unwrap_or(Value::Null);
+    let Some(object) = value.as_object_mut() else {
+        return value;
+    };
+    if let Some(sections) = sections {
+        object.retain(|key, _| {
+            !INVENTORY_SECTIONS.contains(&key.as_str()) || sections.contains(&key.as_str())
+        });
+    }
+    if packages == PackageDetail::Summary
+        && let Some(list) = object.get_mut("packages")
+    {
+        *list = inventory
+            .packages
+            .iter()
+            .map(|p| serde_json::json!({"name": p.name, "version": p.version}))
+            .collect();
+    }
+    value
+}
+
 /// Get host inventory
 ///
 /// # Errors
 /// Returns `AppError` if inventory query fails
+#[utoipa::path(
+    get,
+    path = "/hosts/{hostname}/inventory",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name"), InventoryQuery),
+    responses(
+        (status = 200, description = "Pending updates and collected inventory", body = HostInventoryResponse),
+        (status = 400, description = "Unknown package detail"),
+        (status = 404, description = "Host not found", body = ApiError),
+        (status = 409, description = "Host is busy", body = ApiError),
+        (status = 422, description = "Unknown inventory section", body = ApiError),
+        (status = 424, description = "A requested section needs osquery, which the host lacks", body = ApiError),
+    )
+)]
 pub async fn get_host_inventory(
     State(state): State<Arc<AppState>>,
-    Path(hostname): Path<String>,
+    Path(hostname): Path<HostName>,
+    Query(query): Query<InventoryQuery>,
 ) -> Result<impl IntoResponse, AppError> {
-    let _inventory = state
+    let sections = query
+        .include
+        .as_deref()
+        .map(inventory_sections)
+        .transpose()
+        .map_err(|e| AppError::validation(vec![e]))?;
+
+    let pending = state
         .orchestrator
-        .ask(QueryHostInventory { hostname })
+        .ask(Traced::new(QueryHostInventory {
+            hostname: hostname.clone(),
+            refresh: query.refresh,
+        }))
+        .await?;
+
+    let inventory = state
+        .orchestrator
+        .ask(Traced::new(CollectHostInventory {
+            hostname: hostname.clone(),
+            require_osquery: sections
+                .as_deref()
+                .is_some_and(|s| s.iter().any(|s| OSQUERY_ONLY_SECTIONS.contains(s))),
+        }))
+        .await?;
+
+    let selected = select_inventory(&inventory, sections.as_deref(), query.packages);
+    state
+        .inventories
+        .write()
         .await
-        .map_err(|e| AppError::internal(format!("failed to query inventory: {e}")))?;
+        .insert(hostname.clone(), inventory);
+
+    Ok(Json(pending.into_response(&hostname, selected)))
+}
+
+/// Changes between a host's last two inventory collections
+#[derive(Debug, Serialize, ToSchema)]
+pub struct InventoryDiffResponse {
+    /// Host name
+    pub name: String,
+    /// `null` until the inventory has been collected twice
+    #[schema(value_type = Option<Object>)]
+    pub diff: Option<InventoryDiff>,
+    /// One-line description of the changes
+    pub summary: Option<String>,
+}
 
-    // TODO: Return actual inventory when tendhost-core::InventoryResult implements Serialize
-    // For now, return a placeholder
-    Ok(Json(serde_json::json!({
-        "message": "inventory query accepted - response structure pending"
-    })))
+/// Get what changed between a host's last two inventory collections
+///
+/// Inventories are collected by `GET /hosts/{hostname}/inventory`; the
+/// diff compares the latest collection with the one before it.
+///
+/// # Errors
+/// Returns `AppError` if the host does not exist
+#[utoipa::path(
+    get,
+    path = "/hosts/{hostname}/inventory/diff",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 200, description = "Package, container, disk and kernel changes", body = InventoryDiffResponse),
+        (status = 404, description = "Host not found", body = ApiError),
+    )
+)]
+pub async fn get_host_inventory_diff(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+) -> Result<Json<InventoryDiffResponse>, AppError> {
+    let diff = state
+        .orchestrator
+        .ask(Traced::new(GetHostInventoryDiff {
+            hostname: hostname.clone(),
+        }))
+        .await?;
+
+    Ok(Json(InventoryDiffResponse {
+        name: hostname.to_string(),
+        summary: diff.as_ref().map(InventoryDiff::summary),
+        diff,
+    }))
+}
+
+/// Query parameters for a host's update history
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UpdateHistoryQuery {
+    /// Maximum number of records to return
+    #[serde(default = "default_history_limit")]
+    pub limit: usize,
+}
+
+fn default_history_limit() -> usize {
+    20
+}
+
+/// Get the commands recently run on a host, oldest first
+///
+/// # Errors
+/// Returns `AppError` if the host does not exist
+#[utoipa::path(
+    get,
+    path = "/hosts/{hostname}/commands",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 200, description = "Recent commands with redacted secrets", body = [CommandHistoryEntry]),
+        (status = 404, description = "Host not found", body = ApiError),
+    )
+)]
+pub async fn get_host_commands(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+) -> Result<Json<Vec<CommandHistoryEntry>>, AppError> {
+    let records = state
+        .orchestrator
+        .ask(Traced::new(GetHostCommandHistory { hostname }))
+        .await?;
+
+    Ok(Json(records.into_iter().map(command_entry).collect()))
+}
+
+/// Get a host's recorded state transitions, oldest first
+///
+/// The last 100 transitions are kept; the history survives retries but
+/// starts over when the host actor is restarted.
+///
+/// # Errors
+/// Returns `AppError` if the host does not exist
+#[utoipa::path(
+    get,
+    path = "/hosts/{hostname}/transitions",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name")),
+    responses(
+        (status = 200, description = "State transitions", body = [StateTransitionInfo]),
+        (status = 404, description = "Host not found", body = ApiError),
+    )
+)]
+pub async fn get_host_transitions(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+) -> Result<Json<Vec<StateTransitionInfo>>, AppError> {
+    let transitions = state
+        .orchestrator
+        .ask(Traced::new(GetHostTransitionHistory { hostname }))
+        .await?;
+
+    Ok(Json(
+        transitions
+            .into_iter()
+            .map(StateTransitionInfo::from)
+            .collect(),
+    ))
+}
+
+/// Get a host's finished and failed updates, newest first
+///
+/// # Errors
+/// Returns `AppError` if the host does not exist
+#[utoipa::path(
+    get,
+    path = "/hosts/{hostname}/updates",
+    tag = "hosts",
+    params(("hostname" = String, Path, description = "Host name"), UpdateHistoryQuery),
+    responses(
+        (status = 200, description = "Recent updates", body = [UpdateHistoryEntry]),
+        (status = 404, description = "Host not found", body = ApiError),
+    )
+)]
+pub async fn get_host_updates(
+    State(state): State<Arc<AppState>>,
+    Path(hostname): Path<HostName>,
+    Query(query): Query<UpdateHistoryQuery>,
+) -> Result<Json<Vec<UpdateHistoryEntry>>, AppError> {
+    let entries = state
+        .orchestrator
+        .ask(Traced::new(GetHostUpdateHistory {
+            hostname,
+            limit: Some(query.limit),
+        }))
+        .await?;
+
+    Ok(Json(entries))
+}
+
+#[cfg(test)]
+mod tests {
+    use tendhost_inventory::{Package, PackageSource};
+
+    use super::*;
+
+    /// An inventory with `count` installed packages
+    fn inventory_with_packages(count: usize) -> HostInventory {
+        let mut inventory = HostInventory::new();
+        inventory.system.os_name = "Debian GNU/Linux".to_string();
+        inventory.packages = (0..count)
+            .map(|i| Package {
+                name: format!("lib-package-{i}"),
+                version: format!("1.{i}.0-1+deb12u1"),
+                arch: "amd64".to_string(),
+                source: PackageSource::Deb,
+                install_time: Some(chrono::Utc::now()),
+                size_bytes: Some(1_048_576),
+            })
+            .collect();
+        inventory
+    }
+
+    #[test]
+    fn test_if_none_match_compares_weakly() {
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, value.parse().unwrap());
+            headers
+        };
+        let etag = "W/\"00ab\"";
+        assert!(if_none_match(&headers(etag), etag));
+        assert!(if_none_match(&headers("\"00ab\""), etag));
+        assert!(if_none_match(&headers("\"ffff\", W/\"00ab\""), etag));
+        assert!(if_none_match(&headers("*"), etag));
+        assert!(!if_none_match(&headers("W/\"ffff\""), etag));
+        assert!(!if_none_match(&HeaderMap::new(), etag));
+    }
+
+    #[test]
+    fn test_package_summary_is_much_smaller() {
+        let inventory = inventory_with_packages(2000);
+        let full = select_inventory(&inventory, None, PackageDetail::Full);
+        let summary = select_inventory(&inventory, None, PackageDetail::Summary);
+
+        let full_len = serde_json::to_vec(&full).unwrap().len();
+        let summary_len = serde_json::to_vec(&summary).unwrap().len();
+        assert!(
+            summary_len * 2 < full_len,
+            "summary is {summary_len} bytes, full is {full_len}"
+        );
+        assert_eq!(
+            summary["packages"][1999],
+            serde_json::json!({"name": "lib-package-1999", "version": "1.1999.0-1+deb12u1"})
+        );
+        assert_eq!(summary["system"], full["system"]);
+    }
+
+    #[test]
+    fn test_inventory_sections() {
+        let inventory = inventory_with_packages(3);
+        let sections = inventory_sections("system, hardware").unwrap();
+        let selected = select_inventory(&inventory, Some(&sections), PackageDetail::Full);
+
+        let mut keys: Vec<_> = selected.as_object().unwrap().keys().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, ["collected_at", "hardware", "system", "version"]);
+        assert_eq!(selected["system"]["os_name"], "Debian GNU/Linux");
+
+        let err = inventory_sections("system,pakages").unwrap_err();
+        assert_eq!(err.field, "include");
+        assert!(err.message.starts_with("unknown section 'pakages'"));
+    }
 }
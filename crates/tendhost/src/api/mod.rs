@@ -1,12 +1,15 @@
 //! API route handlers
 
+pub mod audit;
 pub mod error;
+pub mod events;
+pub mod fleet;
 pub mod hosts;
+pub mod openapi;
+pub mod reports;
+pub mod schedules;
 pub mod system;
-
-// TODO: Implement these modules
-// pub mod fleet;
-// pub mod ws;
+pub mod ws;
 
 #[allow(unused)]
 pub use error::{ApiError, AppError};
@@ -0,0 +1,176 @@
+//! OpenAPI document for the daemon API
+//!
+//! Served at `/openapi.json` so clients in other languages can be generated
+//! from the same request/response types the Rust client uses.
+
+use tendhost_api::events::{EventEnvelope, WsEvent};
+use tendhost_api::pagination::{PageParams, Pagination};
+use tendhost_api::requests::{
+    FleetUpdateFilter, FleetUpdateRequest, ImportMode, PreviewParams, UpdateParams, UpdateRequest,
+    UpdateScope,
+};
+use tendhost_api::responses::{
+    AuditEntry, BulkRegisterReport, CommandHistoryEntry, ConfigIssue, ConfigValidationReport,
+    FleetDryRunReport, FleetPackage, FleetSummary, HealthResponse, HostDetail, HostDryRun,
+    HostEventStats, HostInventoryResponse, HostRegistration, ImportReport, NotifierStats,
+    OsqueryInstallResponse, RegistrationStatus, ReloadReport, ScheduleInfo, ScheduleNextRun,
+    ScheduleRunInfo, StateTransitionInfo, UpdateAccepted, UpdateHistoryEntry, UpdatePreview,
+    UpdateResultInfo,
+};
+use utoipa::OpenApi;
+
+use crate::api::error::ApiError;
+use crate::api::{audit, events, fleet, hosts, reports, schedules, system, ws};
+
+/// Assembled OpenAPI document
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "tendhost", description = "Homelab update orchestration daemon"),
+    paths(
+        system::health,
+        system::openapi,
+        system::reload_config,
+        system::validate_config,
+        hosts::list_hosts,
+        hosts::register_host,
+        hosts::register_hosts,
+        hosts::export_hosts,
+        hosts::import_hosts,
+        hosts::get_host,
+        hosts::update_host_config,
+        hosts::unregister_host,
+        hosts::update_host,
+        hosts::preview_host_update,
+        hosts::cancel_host_update,
+        hosts::reboot_host,
+        hosts::retry_host,
+        hosts::acknowledge_host,
+        hosts::pause_host,
+        hosts::install_osquery,
+        hosts::resume_host,
+        hosts::get_host_inventory,
+        hosts::get_host_inventory_diff,
+        hosts::get_host_commands,
+        hosts::get_host_updates,
+        hosts::get_host_transitions,
+        fleet::update_fleet,
+        fleet::fleet_status,
+        schedules::list_schedules,
+        schedules::run_schedule_now,
+        reports::packages_report,
+        reports::updates_report,
+        audit::list_audit,
+        events::list_events,
+        events::recent_events,
+        ws::events,
+    ),
+    components(schemas(
+        ApiError,
+        HealthResponse,
+        ReloadReport,
+        ConfigValidationReport,
+        ConfigIssue,
+        ImportMode,
+        ImportReport,
+        UpdateRequest,
+        UpdateParams,
+        PreviewParams,
+        UpdateScope,
+        FleetUpdateRequest,
+        FleetUpdateFilter,
+        FleetDryRunReport,
+        HostDryRun,
+        FleetPackage,
+        FleetSummary,
+        HostDetail,
+        HostInventoryResponse,
+        OsqueryInstallResponse,
+        UpdateAccepted,
+        UpdateResultInfo,
+        UpdatePreview,
+        BulkRegisterReport,
+        HostRegistration,
+        RegistrationStatus,
+        AuditEntry,
+        CommandHistoryEntry,
+        UpdateHistoryEntry,
+        StateTransitionInfo,
+        ScheduleInfo,
+        ScheduleRunInfo,
+        ScheduleNextRun,
+        NotifierStats,
+        HostEventStats,
+        EventEnvelope,
+        WsEvent,
+        PageParams,
+        Pagination,
+    )),
+    tags(
+        (name = "system", description = "Health and API description"),
+        (name = "hosts", description = "Host registration, updates and inventory"),
+        (name = "fleet", description = "Fleet-wide operations"),
+        (name = "schedules", description = "Recurring fleet updates"),
+        (name = "reports", description = "Fleet reports"),
+        (name = "audit", description = "Audit log of mutating requests"),
+        (name = "events", description = "Event history and live stream"),
+    )
+)]
+pub struct ApiDoc;
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn test_document_has_critical_paths() {
+        let json = ApiDoc::openapi().to_json().unwrap();
+        let doc: Value = serde_json::from_str(&json).unwrap();
+        let paths = &doc["paths"];
+
+        assert!(paths["/hosts"]["get"].is_object());
+        assert!(paths["/hosts"]["post"].is_object());
+        assert!(paths["/hosts/bulk"]["post"].is_object());
+        assert!(paths["/hosts/export"]["get"].is_object());
+        assert!(paths["/hosts/import"]["post"].is_object());
+        assert!(paths["/system/config/validate"]["post"].is_object());
+        assert!(paths["/hosts/{hostname}/update"]["post"].is_object());
+        assert!(paths["/hosts/{hostname}/update"]["get"].is_null());
+        assert!(paths["/hosts/{hostname}/update-preview"]["get"].is_object());
+        assert!(paths["/fleet/update"]["post"].is_object());
+        assert!(paths["/fleet/status"]["get"].is_object());
+        assert!(paths["/schedules"]["get"].is_object());
+        assert!(paths["/schedules/{id}/run-now"]["post"].is_object());
+        assert!(paths["/events"]["get"].is_object());
+        assert!(paths["/events/recent"]["get"].is_object());
+        assert!(paths["/system/reload"]["post"].is_object());
+
+        let per_page = paths["/hosts"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|param| param["name"] == "per_page")
+            .unwrap();
+        assert_eq!(per_page["schema"]["maximum"], 200);
+
+        let schemas = &doc["components"]["schemas"];
+        for name in [
+            "UpdateRequest",
+            "FleetUpdateRequest",
+            "HealthResponse",
+            "ReloadReport",
+            "ConfigValidationReport",
+            "HostDetail",
+            "InventorySummary",
+            "UpdateAccepted",
+            "UpdateResultInfo",
+            "UpdatePreview",
+            "HostInventoryResponse",
+            "ApiError",
+            "FieldViolation",
+        ] {
+            assert!(schemas[name].is_object(), "missing schema {name}");
+        }
+    }
+}
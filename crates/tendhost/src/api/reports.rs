@@ -0,0 +1,283 @@
+//! Fleet report endpoints
+//!
+//! Reports are built from the inventory cached by the inventory endpoint and
+//! the orchestrator's host status, and can be rendered as JSON or CSV.
+
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use tendhost_core::{ListHosts, Traced};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::error::{ApiError, AppError};
+use crate::state::AppState;
+
+/// Output format for reports
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    /// JSON array of rows
+    #[default]
+    Json,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+/// Query parameters for report endpoints
+#[derive(Debug, Deserialize, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ReportQuery {
+    /// Output format
+    #[serde(default)]
+    pub format: ReportFormat,
+}
+
+/// One installed package on one host
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PackageReportRow {
+    /// Host name
+    pub host: String,
+    /// Package name
+    pub package: String,
+    /// Installed version
+    pub version: String,
+    /// Package source (deb, rpm, ...)
+    pub source: String,
+    /// Install time (RFC 3339), if known
+    pub install_time: Option<String>,
+}
+
+/// Pending update summary for one host
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UpdateReportRow {
+    /// Host name
+    pub host: String,
+    /// Current state
+    pub state: String,
+    /// Number of pending updates, if queried
+    pub pending_updates: Option<u32>,
+    /// Number of pending security updates, if queried
+    pub security_updates: Option<u32>,
+    /// Last successful update (RFC 3339)
+    pub last_updated: Option<String>,
+    /// Whether the host answers reachability probes
+    pub reachable: bool,
+}
+
+/// A row that can be written as CSV
+trait CsvRow {
+    /// Column names
+    const HEADER: &'static [&'static str];
+
+    /// Field values in `HEADER` order
+    fn fields(&self) -> Vec<Cow<'_, str>>;
+}
+
+impl CsvRow for PackageReportRow {
+    const HEADER: &'static [&'static str] =
+        &["host", "package", "version", "source", "install_time"];
+
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        vec![
+            Cow::Borrowed(&self.host),
+            Cow::Borrowed(&self.package),
+            Cow::Borrowed(&self.version),
+            Cow::Borrowed(&self.source),
+            Cow::Borrowed(self.install_time.as_deref().unwrap_or("")),
+        ]
+    }
+}
+
+impl CsvRow for UpdateReportRow {
+    const HEADER: &'static [&'static str] = &[
+        "host",
+        "state",
+        "pending_updates",
+        "security_updates",
+        "last_updated",
+        "reachable",
+    ];
+
+    fn fields(&self) -> Vec<Cow<'_, str>> {
+        vec![
+            Cow::Borrowed(&self.host),
+            Cow::Borrowed(&self.state),
+            self.pending_updates
+                .map_or(Cow::Borrowed(""), |n| Cow::Owned(n.to_string())),
+            self.security_updates
+                .map_or(Cow::Borrowed(""), |n| Cow::Owned(n.to_string())),
+            Cow::Borrowed(self.last_updated.as_deref().unwrap_or("")),
+            Cow::Owned(self.reachable.to_string()),
+        ]
+    }
+}
+
+/// Quote a CSV field if it contains a delimiter, quote, or line break
+fn escape_csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Join fields into a CRLF-terminated CSV record
+fn csv_record<'a>(fields: impl IntoIterator<Item = Cow<'a, str>>) -> String {
+    let mut line = fields
+        .into_iter()
+        .map(|f| escape_csv_field(&f).into_owned())
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+/// Render rows in the requested format
+///
+/// CSV is streamed one record at a time instead of being assembled into a
+/// single buffer.
+fn render<T>(rows: Vec<T>, format: ReportFormat) -> Response
+where
+    T: CsvRow + Serialize + Send + 'static,
+{
+    match format {
+        ReportFormat::Json => Json(rows).into_response(),
+        ReportFormat::Csv => {
+            let header_line = csv_record(T::HEADER.iter().map(|h| Cow::Borrowed(*h)));
+            let records = rows.into_iter().map(|row| csv_record(row.fields()));
+            let body = stream::iter(
+                std::iter::once(header_line)
+                    .chain(records)
+                    .map(|line| Ok::<_, Infallible>(Bytes::from(line))),
+            );
+
+            (
+                [(header::CONTENT_TYPE, "text/csv; charset=utf-8")],
+                Body::from_stream(body),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Installed packages across all hosts with cached inventory
+///
+/// Only hosts whose inventory has been collected through the inventory
+/// endpoint are included.
+#[utoipa::path(
+    get,
+    path = "/reports/packages",
+    tag = "reports",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Installed packages as JSON or CSV",
+            content((Vec<PackageReportRow> = "application/json"), (String = "text/csv"))
+        ),
+    )
+)]
+pub async fn packages_report(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReportQuery>,
+) -> Response {
+    let rows: Vec<PackageReportRow> = {
+        let inventories = state.inventories.read().await;
+        let mut hosts: Vec<_> = inventories.iter().collect();
+        hosts.sort_by(|a, b| a.0.cmp(b.0));
+
+        hosts
+            .into_iter()
+            .flat_map(|(host, inventory)| {
+                inventory.packages.iter().map(move |p| PackageReportRow {
+                    host: host.to_string(),
+                    package: p.name.clone(),
+                    version: p.version.clone(),
+                    source: p.source.to_string(),
+                    install_time: p.install_time.map(|t| t.to_rfc3339()),
+                })
+            })
+            .collect()
+    };
+
+    render(rows, query.format)
+}
+
+/// Pending updates per host
+///
+/// # Errors
+/// Returns `AppError` if orchestrator communication fails
+#[utoipa::path(
+    get,
+    path = "/reports/updates",
+    tag = "reports",
+    params(ReportQuery),
+    responses(
+        (status = 200, description = "Pending updates as JSON or CSV",
+            content((Vec<UpdateReportRow> = "application/json"), (String = "text/csv"))
+        ),
+        (status = 500, description = "Orchestrator unavailable", body = ApiError),
+    )
+)]
+pub async fn updates_report(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ReportQuery>,
+) -> Result<Response, AppError> {
+    let mut hosts = state
+        .orchestrator
+        .ask(Traced::new(ListHosts))
+        .await
+        .map_err(|e| AppError::internal(format!("failed to list hosts: {e}")))?;
+    hosts.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let rows: Vec<UpdateReportRow> = hosts
+        .into_iter()
+        .map(|h| UpdateReportRow {
+            host: h.name.to_string(),
+            state: h.state.to_string(),
+            pending_updates: h.pending_updates,
+            security_updates: h.security_updates,
+            last_updated: h.last_updated.map(|dt| dt.to_rfc3339()),
+            reachable: h.reachable,
+        })
+        .collect();
+
+    Ok(render(rows, query.format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_csv_field() {
+        assert_eq!(escape_csv_field("openssl"), "openssl");
+        assert_eq!(escape_csv_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_package_row_record() {
+        let row = PackageReportRow {
+            host: "web-1".to_string(),
+            package: "libc6".to_string(),
+            version: "2.36-9+deb12u4".to_string(),
+            source: "deb".to_string(),
+            install_time: None,
+        };
+
+        assert_eq!(
+            csv_record(row.fields()),
+            "web-1,libc6,2.36-9+deb12u4,deb,\r\n"
+        );
+    }
+}
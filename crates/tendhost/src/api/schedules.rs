@@ -0,0 +1,51 @@
+//! Schedule API routes
+
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use tendhost_api::responses::ScheduleInfo;
+
+use crate::api::error::{ApiError, AppError};
+use crate::state::AppState;
+
+/// List configured schedules with their next and last run
+#[utoipa::path(
+    get,
+    path = "/schedules",
+    tag = "schedules",
+    responses((status = 200, description = "Configured schedules", body = Vec<ScheduleInfo>))
+)]
+pub async fn list_schedules(State(state): State<Arc<AppState>>) -> Json<Vec<ScheduleInfo>> {
+    Json(state.scheduler.info())
+}
+
+/// Run a schedule immediately
+///
+/// Works for disabled schedules too. The fleet update runs in the
+/// background; the schedule's `last_run` records the result.
+///
+/// # Errors
+/// Returns `AppError` if the schedule does not exist or is still running
+#[utoipa::path(
+    post,
+    path = "/schedules/{id}/run-now",
+    tag = "schedules",
+    params(("id" = String, Path, description = "Schedule id")),
+    responses(
+        (status = 202, description = "Fleet update started", body = ScheduleInfo),
+        (status = 404, description = "Schedule not found", body = ApiError),
+        (status = 409, description = "Previous run still in progress", body = ApiError),
+    )
+)]
+pub async fn run_schedule_now(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let info = state.scheduler.run_now(&id)?;
+    Ok((StatusCode::ACCEPTED, Json(info)))
+}
@@ -1,21 +1,136 @@
 //! System endpoints (health, docs)
 
-use axum::Json;
-use serde::{Deserialize, Serialize};
-
-/// Health check response
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct HealthResponse {
-    /// Service status
-    pub status: String,
-    /// Service version
-    pub version: String,
-}
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::State,
+    http::HeaderValue,
+    response::{Html, Response},
+};
+use tendhost_api::responses::{ConfigValidationReport, HealthResponse, ReloadReport};
+use tendhost_api::version::{API_VERSION, API_VERSION_HEADER, DAEMON_VERSION_HEADER};
+use tendhost_core::CoreError;
+use utoipa::OpenApi;
+use utoipa_scalar::Scalar;
 
-/// Health check endpoint
-pub async fn health() -> Json<HealthResponse> {
+use crate::api::error::{ApiError, AppError};
+use crate::api::openapi::ApiDoc;
+use crate::config::Config;
+use crate::state::AppState;
+
+/// Report that the daemon is running, with its release and activity
+///
+/// Besides the release and API protocol version, which every reply also
+/// carries in headers, lists when each enabled schedule runs next, what
+/// each webhook has sent, failed and dropped, and how many events each
+/// host emitted while nobody was subscribed.
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses((status = 200, description = "Daemon is running", body = HealthResponse))
+)]
+pub async fn health(State(state): State<Arc<AppState>>) -> Json<HealthResponse> {
     Json(HealthResponse {
         status: "healthy".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
+        api_version: API_VERSION,
+        schedules: state.scheduler.next_runs(),
+        notifications: state.notifier.stats(),
+        events: state.events.host_stats(),
     })
 }
+
+/// Tag a reply with the API protocol version and daemon release
+///
+/// Clients check the version on every reply, errors and WebSocket
+/// upgrades included, so this wraps the whole router.
+pub async fn version_headers(mut response: Response) -> Response {
+    let headers = response.headers_mut();
+    headers.insert(API_VERSION_HEADER, HeaderValue::from(API_VERSION));
+    headers.insert(
+        DAEMON_VERSION_HEADER,
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    );
+    response
+}
+
+/// Re-read the config file and apply its host changes
+///
+/// Same as sending the daemon SIGHUP. Hosts that are busy or whose new
+/// configuration is invalid are skipped and reported; changed settings
+/// that need a restart are listed in `requires_restart`.
+///
+/// # Errors
+/// Returns `AppError` if there is no config file or it can't be parsed
+#[utoipa::path(
+    post,
+    path = "/system/reload",
+    tag = "system",
+    responses(
+        (status = 200, description = "Config reloaded", body = ReloadReport),
+        (status = 400, description = "Config file missing or invalid", body = ApiError),
+    )
+)]
+pub async fn reload_config(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ReloadReport>, AppError> {
+    Ok(Json(crate::reload::reload(&state).await?))
+}
+
+/// Check a config file without applying it
+///
+/// Validates the posted file, or the daemon's own config file when the
+/// body is empty, and lists every problem with its key path and position.
+///
+/// # Errors
+/// Returns `AppError` if the body is empty and the daemon has no config
+/// file, or it can't be read
+#[utoipa::path(
+    post,
+    path = "/system/config/validate",
+    tag = "system",
+    request_body(content = String, description = "Config file to check; empty for the daemon's own", content_type = "application/toml"),
+    responses(
+        (status = 200, description = "Problems found, if any", body = ConfigValidationReport),
+        (status = 400, description = "No config file to check", body = ApiError),
+    )
+)]
+pub async fn validate_config(
+    State(state): State<Arc<AppState>>,
+    body: String,
+) -> Result<Json<ConfigValidationReport>, AppError> {
+    let content = if body.trim().is_empty() {
+        let path = state.config_path.as_ref().ok_or_else(|| {
+            CoreError::ConfigError("daemon was started without a config file".to_string())
+        })?;
+        tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| CoreError::ConfigError(format!("{}: {e}", path.display())))?
+    } else {
+        body
+    };
+
+    let issues = Config::check(&content);
+    Ok(Json(ConfigValidationReport {
+        valid: issues.is_empty(),
+        issues,
+    }))
+}
+
+/// OpenAPI document describing this API
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "system",
+    responses((status = 200, description = "OpenAPI 3.1 document", content_type = "application/json"))
+)]
+pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Interactive API documentation, enabled with `daemon.docs_ui`
+pub async fn docs() -> Html<String> {
+    Html(Scalar::new(ApiDoc::openapi()).to_html())
+}
@@ -1,3 +1,285 @@
 //! WebSocket handler
+//!
+//! Streams [`EventEnvelope`]s from the event hub to connected clients as JSON
+//! text frames. Clients from before the versioned envelope can ask for the
+//! old bare format with `?format=legacy` for one more release.
+//!
+//! With `?batch_ms=` the events of each interval go out as one JSON array
+//! frame instead, so slow links and clients see a frame per interval rather
+//! than one per event. A host failing is sent right away with whatever was
+//! waiting.
 
-// TODO: Implement /ws/events endpoint
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    extract::{
+        Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tendhost_api::events::{EventEnvelope, MAX_BATCH_MS, WsEvent};
+use tendhost_core::FieldError;
+use tokio::sync::mpsc;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{debug, warn};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::api::error::AppError;
+use crate::state::AppState;
+
+/// Query parameters of the event stream
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventStreamQuery {
+    /// Frame format; `legacy` is deprecated and will be removed
+    #[serde(default)]
+    #[param(inline)]
+    pub format: EventFormat,
+    /// Send the events of each interval of this many milliseconds as one
+    /// JSON array frame; 0 sends every event on its own
+    #[serde(default)]
+    #[param(maximum = 5000)]
+    pub batch_ms: u64,
+}
+
+/// Shape of the event stream's frames
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EventFormat {
+    /// Versioned [`EventEnvelope`]
+    #[default]
+    Envelope,
+    /// The event's fields next to `seq`, with a `PascalCase` `type`
+    Legacy,
+}
+
+/// Upgrade to a WebSocket streaming live events
+#[utoipa::path(
+    get,
+    path = "/ws/events",
+    tag = "events",
+    params(EventStreamQuery),
+    responses(
+        (status = 101, description = "WebSocket of JSON `EventEnvelope` text frames, or arrays of them when batched", body = EventEnvelope),
+        (status = 422, description = "`batch_ms` out of range"),
+    )
+)]
+pub async fn events(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EventStreamQuery>,
+) -> Result<Response, AppError> {
+    if query.batch_ms > MAX_BATCH_MS {
+        return Err(AppError::validation(vec![FieldError::new(
+            "batch_ms",
+            format!("must be at most {MAX_BATCH_MS}"),
+        )]));
+    }
+    let events = state.events.subscribe();
+    if query.format == EventFormat::Legacy {
+        debug!("event subscriber asked for the deprecated legacy format");
+    }
+    let batch_every = (query.batch_ms > 0).then(|| Duration::from_millis(query.batch_ms));
+    Ok(ws.on_upgrade(move |socket| stream_events(socket, events, query.format, batch_every)))
+}
+
+/// Frames of a batched stream waiting for the next flush
+#[derive(Debug, Default)]
+struct Batch {
+    frames: Vec<String>,
+}
+
+impl Batch {
+    /// Queue `frame`, flushing right away if it is urgent
+    fn push(&mut self, frame: String, urgent: bool) -> Option<String> {
+        self.frames.push(frame);
+        if urgent { self.flush() } else { None }
+    }
+
+    /// The queued frames as one JSON array; `None` if there are none
+    fn flush(&mut self) -> Option<String> {
+        if self.frames.is_empty() {
+            return None;
+        }
+        let frame = format!("[{}]", self.frames.join(","));
+        self.frames.clear();
+        Some(frame)
+    }
+}
+
+/// Whether `event` should reach batched clients without waiting
+fn is_urgent(event: &WsEvent) -> bool {
+    matches!(event, WsEvent::HostStateChanged { to, .. } if to == "failed")
+}
+
+/// `envelope` as one frame in `format`
+fn encode(envelope: &EventEnvelope, format: EventFormat) -> Option<String> {
+    let text = match format {
+        EventFormat::Envelope => serde_json::to_string(envelope),
+        EventFormat::Legacy => legacy_frame(envelope).map(|frame| frame.to_string()),
+    };
+    text.map_err(|e| warn!(error = %e, "failed to serialize event"))
+        .ok()
+}
+
+/// `envelope` as sent before the versioned envelope existed:
+/// `{"seq": 1, "type": "HostConnected", "host": "web"}`
+fn legacy_frame(envelope: &EventEnvelope) -> serde_json::Result<Value> {
+    let mut value = serde_json::to_value(&envelope.event)?;
+    if let Value::Object(fields) = &mut value {
+        if let Some(Value::String(kind)) = fields.get_mut("type") {
+            *kind = pascal_case(kind);
+        }
+        fields.insert("seq".to_string(), envelope.seq.into());
+    }
+    Ok(value)
+}
+
+/// `host_state_changed` -> `HostStateChanged`
+fn pascal_case(snake: &str) -> String {
+    snake
+        .split('_')
+        .flat_map(|word| {
+            let mut chars = word.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+        })
+        .collect()
+}
+
+/// Forward events until either side goes away
+///
+/// With `batch_every` set, events are queued and sent as one array frame
+/// per interval; intervals without events send nothing.
+async fn stream_events(
+    mut socket: WebSocket,
+    mut events: mpsc::Receiver<EventEnvelope>,
+    format: EventFormat,
+    batch_every: Option<Duration>,
+) {
+    debug!(batch_every = ?batch_every, "event subscriber connected");
+
+    let mut batch = Batch::default();
+    // Only ticks when batching; the period is a placeholder otherwise
+    let period = batch_every.unwrap_or(Duration::from_secs(1));
+    let mut flush = tokio::time::interval_at(Instant::now() + period, period);
+    flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            envelope = events.recv() => {
+                let Some(envelope) = envelope else {
+                    if let Some(frame) = batch.flush() {
+                        let _ = socket.send(Message::Text(frame.into())).await;
+                    }
+                    break;
+                };
+                let Some(text) = encode(&envelope, format) else { continue };
+                let frame = if batch_every.is_some() {
+                    batch.push(text, is_urgent(&envelope.event))
+                } else {
+                    Some(text)
+                };
+                if let Some(frame) = frame
+                    && socket.send(Message::Text(frame.into())).await.is_err()
+                {
+                    break;
+                }
+            }
+            _ = flush.tick(), if batch_every.is_some() => {
+                if let Some(frame) = batch.flush()
+                    && socket.send(Message::Text(frame.into())).await.is_err()
+                {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                // Clients don't send anything meaningful; pings are answered by axum
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    debug!("event subscriber disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tendhost_api::events::WsEvent;
+
+    use super::*;
+
+    #[test]
+    fn test_legacy_frame_is_the_bare_event() {
+        let envelope = EventEnvelope::new(
+            42,
+            WsEvent::HostStateChanged {
+                host: "web".to_string(),
+                from: "idle".to_string(),
+                to: "querying".to_string(),
+                failure_kind: None,
+            },
+        );
+        assert_eq!(
+            legacy_frame(&envelope).unwrap(),
+            json!({"seq": 42, "type": "HostStateChanged", "host": "web", "from": "idle", "to": "querying"})
+        );
+        assert_eq!(pascal_case("fleet_update_finished"), "FleetUpdateFinished");
+    }
+
+    fn state_changed(to: &str) -> WsEvent {
+        WsEvent::HostStateChanged {
+            host: "web".to_string(),
+            from: "updating".to_string(),
+            to: to.to_string(),
+            failure_kind: None,
+        }
+    }
+
+    #[test]
+    fn test_unbatched_frames_are_the_envelope() {
+        let envelope = EventEnvelope::new(7, state_changed("idle"));
+        assert_eq!(
+            encode(&envelope, EventFormat::Envelope).unwrap(),
+            serde_json::to_string(&envelope).unwrap()
+        );
+        let query: EventStreamQuery = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(query.batch_ms, 0);
+    }
+
+    #[test]
+    fn test_batch_flushes_queued_frames_as_an_array() {
+        let mut batch = Batch::default();
+        assert_eq!(batch.flush(), None);
+
+        for seq in [1, 2] {
+            let envelope = EventEnvelope::new(seq, state_changed("idle"));
+            let frame = encode(&envelope, EventFormat::Envelope).unwrap();
+            assert_eq!(batch.push(frame, is_urgent(&envelope.event)), None);
+        }
+        let frame = batch.flush().unwrap();
+        let envelopes: Vec<EventEnvelope> = serde_json::from_str(&frame).unwrap();
+        assert_eq!(envelopes.iter().map(|e| e.seq).collect::<Vec<_>>(), [1, 2]);
+        // An interval without events sends nothing
+        assert_eq!(batch.flush(), None);
+    }
+
+    #[test]
+    fn test_batch_flushes_failures_at_once() {
+        let mut batch = Batch::default();
+        assert_eq!(batch.push("1".to_string(), false), None);
+        assert!(is_urgent(&state_changed("failed")));
+        assert!(!is_urgent(&state_changed("pending_updates")));
+        assert_eq!(batch.push("2".to_string(), true).as_deref(), Some("[1,2]"));
+        assert_eq!(batch.flush(), None);
+    }
+}
@@ -2,13 +2,21 @@
 //!
 //! Minimal skeleton - full implementation pending
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
-use tendhost_core::HostConfig;
+use tendhost_api::requests::UpdateScope;
+use tendhost_api::responses::ConfigIssue;
+use tendhost_core::{DEFAULT_REGISTRATION_CONCURRENCY, HostConfig, HostName, check_key_file};
+use toml::de::{DeTable, DeValue};
+
+use crate::scheduler::schedule_errors;
 
 /// Top-level configuration for tendhost daemon
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     /// Daemon server settings
     #[serde(default)]
@@ -16,10 +24,102 @@ pub struct Config {
     /// Individual host configurations
     #[serde(default)]
     pub host: Vec<HostConfig>,
+    /// Named groups of host names, usable as a list filter
+    #[serde(default)]
+    pub groups: BTreeMap<String, Vec<String>>,
+    /// Recurring fleet updates
+    #[serde(default)]
+    pub schedule: Vec<ScheduleConfig>,
+    /// Webhooks notified about host events
+    #[serde(default)]
+    pub notify: Vec<NotifyConfig>,
+}
+
+/// Webhook that host events are POSTed to (`[[notify]]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotifyConfig {
+    /// Unique name, used in logs and `/health`
+    pub name: String,
+    /// `http://` or `https://` URL to POST to; for ntfy, the topic URL
+    pub url: String,
+    /// Payload format
+    #[serde(default)]
+    pub format: NotifyFormat,
+    /// Event types to send, as in the WebSocket `type` field
+    #[serde(default = "default_notify_events")]
+    pub events: Vec<String>,
+    /// Only send `host_state_changed` events into one of these states (all if empty)
+    #[serde(default = "default_notify_states")]
+    pub states: Vec<String>,
+    /// Only send failures of these kinds, e.g. `connection` (all if empty)
+    #[serde(default)]
+    pub failure_kinds: Vec<String>,
+    /// Only send events about these hosts (all hosts if empty)
+    #[serde(default)]
+    pub hosts: Vec<String>,
+    /// Only send events about hosts with one of these tags (all hosts if empty)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Most notifications sent per minute; the rest are dropped
+    #[serde(default = "default_notify_max_per_minute")]
+    pub max_per_minute: u32,
+    /// Delivery attempts after a failed first one
+    #[serde(default = "default_notify_retries")]
+    pub retries: u32,
+    /// Seconds to wait for the webhook to answer
+    #[serde(default = "default_notify_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+/// Body a webhook receives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyFormat {
+    /// JSON object with a title, a message and the event itself
+    #[default]
+    Json,
+    /// `{"text": ...}`, understood by Slack, Mattermost and Matrix hookshot
+    Slack,
+    /// Plain text message with ntfy's `Title`, `Priority` and `Tags` headers
+    Ntfy,
+}
+
+/// Recurring fleet update (`[[schedule]]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScheduleConfig {
+    /// Unique id, used in `/schedules/{id}/run-now`
+    pub id: String,
+    /// Disabled schedules only run when triggered manually
+    #[serde(default = "default_schedule_enabled")]
+    pub enabled: bool,
+    /// Days of week to run on (`Mon`, `tuesday`, ...); empty means every day
+    #[serde(default)]
+    pub days: Vec<String>,
+    /// Time of day in `HH:MM` format
+    pub at: String,
+    /// Time zone for `at`: `local` (the daemon's), `UTC`, or an offset like `+02:00`
+    #[serde(default = "default_schedule_timezone")]
+    pub timezone: String,
+    /// Only update hosts with one of these tags (all hosts if empty)
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Number of hosts to update in parallel
+    #[serde(default = "default_schedule_batch_size")]
+    pub batch_size: usize,
+    /// Delay between batches in milliseconds
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// Simulate the update without installing anything
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Overrides each host's default scope when set
+    #[serde(default)]
+    pub scope: Option<UpdateScope>,
 }
 
 /// Daemon server settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct DaemonConfig {
     /// Address and port to bind to
     #[serde(default = "default_bind")]
@@ -27,6 +127,157 @@ pub struct DaemonConfig {
     /// Log level (trace, debug, info, warn, error)
     #[serde(default = "default_log_level")]
     pub log_level: String,
+    /// Log line format
+    #[serde(default)]
+    pub log_format: LogFormat,
+    /// Audit log settings
+    #[serde(default)]
+    pub audit: AuditConfig,
+    /// Host state kept across a graceful restart
+    #[serde(default)]
+    pub snapshot: SnapshotConfig,
+    /// Event streaming settings
+    #[serde(default)]
+    pub events: EventsConfig,
+    /// Seconds to wait for running updates to finish on shutdown
+    #[serde(default = "default_shutdown_grace_period_secs")]
+    pub shutdown_grace_period_secs: u64,
+    /// Serve interactive API documentation at `/docs`
+    #[serde(default)]
+    pub docs_ui: bool,
+    /// Reject hosts whose `ssh_key` file does not exist on this machine
+    #[serde(default = "default_check_ssh_keys")]
+    pub check_ssh_keys: bool,
+    /// Hosts prepared at the same time when registering many at once, as
+    /// at startup
+    #[serde(default = "default_registration_concurrency")]
+    pub registration_concurrency: usize,
+    /// Request size, time and concurrency limits
+    #[serde(default)]
+    pub limits: LimitsConfig,
+    /// Allow `POST /hosts/{hostname}/install-osquery` to install software
+    /// on hosts
+    #[serde(default)]
+    pub allow_remote_install: bool,
+}
+
+/// Request size, time and concurrency limits (`[daemon.limits]`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LimitsConfig {
+    /// Largest accepted request body in bytes
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Seconds a request may take, body upload included (0 disables)
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+    /// Requests handled at once; further ones wait for a free slot
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: default_max_body_bytes(),
+            request_timeout_secs: default_request_timeout_secs(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+        }
+    }
+}
+
+impl LimitsConfig {
+    /// Request timeout as a duration, `None` when disabled
+    #[must_use]
+    pub fn request_timeout(&self) -> Option<Duration> {
+        (self.request_timeout_secs > 0).then(|| Duration::from_secs(self.request_timeout_secs))
+    }
+}
+
+/// How log lines are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Pretty,
+    /// One JSON object per line, for log aggregators such as Loki
+    Json,
+}
+
+/// Event streaming settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventsConfig {
+    /// Coalesce progress events for the same package within this window (0 disables)
+    #[serde(default = "default_coalesce_window_ms")]
+    pub coalesce_window_ms: u64,
+    /// Events buffered per subscriber before it starts missing events
+    #[serde(default = "default_subscriber_queue_size")]
+    pub subscriber_queue_size: usize,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            coalesce_window_ms: default_coalesce_window_ms(),
+            subscriber_queue_size: default_subscriber_queue_size(),
+        }
+    }
+}
+
+impl EventsConfig {
+    /// Coalescing window as a duration
+    #[must_use]
+    pub fn coalesce_window(&self) -> Duration {
+        Duration::from_millis(self.coalesce_window_ms)
+    }
+}
+
+/// Audit log settings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// Path of the JSON lines audit file
+    #[serde(default = "default_audit_path")]
+    pub path: PathBuf,
+    /// Rotate the file once it exceeds this many bytes (0 disables rotation)
+    #[serde(default = "default_audit_max_size")]
+    pub max_size_bytes: u64,
+    /// Number of rotated files to keep
+    #[serde(default = "default_audit_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            path: default_audit_path(),
+            max_size_bytes: default_audit_max_size(),
+            max_files: default_audit_max_files(),
+        }
+    }
+}
+
+/// Host snapshot settings (`[daemon.snapshot]`)
+///
+/// On a graceful shutdown every host's pending updates, failure and update
+/// history are written to `path` and handed back to the hosts once they are
+/// registered again, so a daemon upgrade doesn't blank the fleet view.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotConfig {
+    /// Write and restore host snapshots
+    #[serde(default = "default_snapshot_enabled")]
+    pub enabled: bool,
+    /// Path of the JSON snapshot file
+    #[serde(default = "default_snapshot_path")]
+    pub path: PathBuf,
+}
+
+impl Default for SnapshotConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_snapshot_enabled(),
+            path: default_snapshot_path(),
+        }
+    }
 }
 
 impl Default for DaemonConfig {
@@ -34,6 +285,16 @@ impl Default for DaemonConfig {
         Self {
             bind: default_bind(),
             log_level: default_log_level(),
+            log_format: LogFormat::default(),
+            audit: AuditConfig::default(),
+            snapshot: SnapshotConfig::default(),
+            events: EventsConfig::default(),
+            shutdown_grace_period_secs: default_shutdown_grace_period_secs(),
+            docs_ui: false,
+            check_ssh_keys: default_check_ssh_keys(),
+            registration_concurrency: default_registration_concurrency(),
+            limits: LimitsConfig::default(),
+            allow_remote_install: false,
         }
     }
 }
@@ -46,43 +307,389 @@ fn default_log_level() -> String {
     "info".to_string()
 }
 
+fn default_audit_path() -> PathBuf {
+    dirs::data_dir().map_or_else(
+        || PathBuf::from("tendhost-audit.jsonl"),
+        |p| p.join("tendhost/audit.jsonl"),
+    )
+}
+
+fn default_snapshot_enabled() -> bool {
+    true
+}
+
+fn default_snapshot_path() -> PathBuf {
+    dirs::data_dir().map_or_else(
+        || PathBuf::from("tendhost-snapshot.json"),
+        |p| p.join("tendhost/snapshot.json"),
+    )
+}
+
+fn default_audit_max_size() -> u64 {
+    tendhost_core::audit::DEFAULT_MAX_SIZE
+}
+
+fn default_audit_max_files() -> usize {
+    tendhost_core::audit::DEFAULT_MAX_FILES
+}
+
+fn default_check_ssh_keys() -> bool {
+    true
+}
+
+fn default_registration_concurrency() -> usize {
+    DEFAULT_REGISTRATION_CONCURRENCY
+}
+
+fn default_max_body_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_request_timeout_secs() -> u64 {
+    30
+}
+
+fn default_max_concurrent_requests() -> usize {
+    64
+}
+
+fn default_schedule_enabled() -> bool {
+    true
+}
+
+fn default_schedule_timezone() -> String {
+    "local".to_string()
+}
+
+fn default_schedule_batch_size() -> usize {
+    tendhost_core::FleetUpdateConfig::default().batch_size
+}
+
+fn default_notify_events() -> Vec<String> {
+    [
+        "host_state_changed",
+        "fleet_update_halted",
+        "fleet_update_finished",
+    ]
+    .map(ToString::to_string)
+    .to_vec()
+}
+
+fn default_notify_states() -> Vec<String> {
+    vec!["failed".to_string()]
+}
+
+fn default_notify_max_per_minute() -> u32 {
+    10
+}
+
+fn default_notify_retries() -> u32 {
+    3
+}
+
+fn default_notify_timeout_secs() -> u64 {
+    10
+}
+
+fn default_shutdown_grace_period_secs() -> u64 {
+    10 * 60
+}
+
+fn default_coalesce_window_ms() -> u64 {
+    u64::try_from(tendhost_core::events::DEFAULT_COALESCE_WINDOW.as_millis()).unwrap_or(250)
+}
+
+fn default_subscriber_queue_size() -> usize {
+    tendhost_core::events::DEFAULT_SUBSCRIBER_QUEUE_SIZE
+}
+
+impl DaemonConfig {
+    /// How long shutdown waits for in-flight updates
+    #[must_use]
+    pub fn shutdown_grace_period(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_period_secs)
+    }
+}
+
 impl Config {
     /// Load configuration from file
     ///
+    /// Values the daemon can't work with don't stop it from loading; they
+    /// are counted in a warning that points at `--check-config`.
+    ///
     /// # Errors
-    /// Returns error if file cannot be read or parsed
+    /// Returns error if file cannot be read or parsed, naming the line and
+    /// key that didn't fit
     pub fn load(path: &PathBuf) -> eyre::Result<Self> {
         let content = std::fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let config = Self::parse(&content)
+            .map_err(|issue| eyre::eyre!("invalid config {}: {issue}", path.display()))?;
+        let issues = config.validate(&content).len();
+        if issues > 0 {
+            tracing::warn!(
+                path = %path.display(),
+                issues,
+                "config has problems; run `tendhost --check-config` to list them"
+            );
+        }
+        for name in unnormalized_host_names(&content) {
+            tracing::warn!(
+                host = %name,
+                normalized = %HostName::new(&name),
+                "host names are case-insensitive; using the lowercase name"
+            );
+        }
         Ok(config)
     }
 
+    /// Parse the content of a config file
+    ///
+    /// Unknown keys are refused, so a typo doesn't silently fall back to
+    /// the default.
+    ///
+    /// # Errors
+    /// Returns the first key that is unknown or has a value of the wrong
+    /// type, with its path and position, or the first syntax error
+    pub fn parse(content: &str) -> Result<Self, ConfigIssue> {
+        let deserializer = toml::Deserializer::parse(content)
+            .map_err(|e| parse_issue(content, String::new(), &e))?;
+        serde_path_to_error::deserialize(deserializer).map_err(|e| {
+            let path = e.path().to_string();
+            let path = if path == "." { String::new() } else { path };
+            parse_issue(content, path, e.inner())
+        })
+    }
+
+    /// Every value in the configuration the daemon can't work with
+    ///
+    /// Hosts and schedules with problems would be skipped at startup; this
+    /// finds all of them at once, plus host names used twice. `content` is
+    /// the file the configuration was parsed from, to locate the problems
+    /// in. SSH key files are checked when `daemon.check_ssh_keys` is set.
+    #[must_use]
+    pub fn validate(&self, content: &str) -> Vec<ConfigIssue> {
+        let document = DeTable::parse(content).ok();
+        let mut problems: Vec<(String, String)> = Vec::new();
+
+        for (i, host) in self.host.iter().enumerate() {
+            let errors = host.validate().err().unwrap_or_default();
+            let jump_checked = errors.iter().any(|e| e.field == "jump_host");
+            for error in errors {
+                problems.push((format!("host[{i}].{}", error.field), error.message));
+            }
+            if !jump_checked && let Err(error) = host.resolve_jump(&self.host) {
+                problems.push((format!("host[{i}].{}", error.field), error.message));
+            }
+            if self.daemon.check_ssh_keys
+                && let Some(Err(error)) = host.ssh_key.as_deref().map(check_key_file)
+            {
+                problems.push((format!("host[{i}].{}", error.field), error.message));
+            }
+            if let Some(first) = self.host[..i].iter().position(|h| h.name == host.name) {
+                problems.push((
+                    format!("host[{i}].name"),
+                    format!(
+                        "duplicate host name '{}', also used by host[{first}]",
+                        host.name
+                    ),
+                ));
+            }
+        }
+
+        for (i, schedule) in self.schedule.iter().enumerate() {
+            for error in schedule_errors(schedule) {
+                problems.push((format!("schedule[{i}].{}", error.field), error.message));
+            }
+            if let Some(first) = self.schedule[..i].iter().position(|s| s.id == schedule.id) {
+                problems.push((
+                    format!("schedule[{i}].id"),
+                    format!(
+                        "duplicate schedule id '{}', also used by schedule[{first}]",
+                        schedule.id
+                    ),
+                ));
+            }
+        }
+
+        problems
+            .into_iter()
+            .map(|(path, message)| {
+                let position = document
+                    .as_ref()
+                    .and_then(|document| locate(content, document.get_ref(), &path));
+                ConfigIssue {
+                    line: position.map(|(line, _)| line),
+                    column: position.map(|(_, column)| column),
+                    path,
+                    message,
+                }
+            })
+            .collect()
+    }
+
+    /// Parse and validate the content of a config file, returning every
+    /// problem found
+    #[must_use]
+    pub fn check(content: &str) -> Vec<ConfigIssue> {
+        match Self::parse(content) {
+            Ok(config) => config.validate(content),
+            Err(issue) => vec![issue],
+        }
+    }
+
     /// Load from default paths or use defaults
     pub fn load_default() -> eyre::Result<Self> {
+        match Self::find_path() {
+            Some(path) => Self::load(&path),
+            None => {
+                tracing::warn!("no config file found, using defaults");
+                Ok(Config::default())
+            }
+        }
+    }
+
+    /// The config file `load_default` reads, if there is one
+    ///
+    /// `TENDHOST_CONFIG` wins even when the file it names is missing, so a
+    /// typo fails loudly instead of falling back to another file.
+    pub fn find_path() -> Option<PathBuf> {
         // Check environment variable
         if let Ok(path) = std::env::var("TENDHOST_CONFIG") {
-            return Self::load(&PathBuf::from(path));
+            return Some(PathBuf::from(path));
         }
 
         // Try common paths
-        let paths = [
+        [
             PathBuf::from("tendhost.toml"),
             PathBuf::from("/etc/tendhost/tendhost.toml"),
             dirs::config_dir()
                 .map(|p| p.join("tendhost/tendhost.toml"))
                 .unwrap_or_default(),
-        ];
+        ]
+        .into_iter()
+        .find(|path| path.exists())
+    }
+}
+
+/// Host names in a config file that are not already lowercase
+///
+/// Files written before names were normalized may spell them in mixed
+/// case; they still load, so this only feeds a warning.
+fn unnormalized_host_names(content: &str) -> Vec<String> {
+    #[derive(Deserialize)]
+    struct RawHost {
+        #[serde(default)]
+        name: String,
+    }
+    #[derive(Deserialize)]
+    struct RawHosts {
+        #[serde(default)]
+        host: Vec<RawHost>,
+    }
+
+    toml::from_str::<RawHosts>(content)
+        .map(|raw| {
+            raw.host
+                .into_iter()
+                .map(|h| h.name)
+                .filter(|name| !HostName::is_normalized(name))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// An issue for a TOML error at `path`
+///
+/// toml's message repeats the position and quotes the line; only the
+/// last line, the actual complaint, is kept.
+fn parse_issue(content: &str, path: String, error: &toml::de::Error) -> ConfigIssue {
+    let position = error.span().map(|span| position(content, span.start));
+    ConfigIssue {
+        path,
+        line: position.map(|(line, _)| line),
+        column: position.map(|(_, column)| column),
+        message: error.message().trim().to_string(),
+    }
+}
+
+/// Line and column, counting from 1, of byte `offset` in `content`
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (line, before[line_start..].chars().count() + 1)
+}
 
-        for path in paths {
-            if path.exists() {
-                return Self::load(&path);
+/// Where the key at `path`, e.g. `host[1].policy.maintenance_window.start`,
+/// is written in `content`
+///
+/// Keys missing from the document, such as defaulted fields, are located
+/// at their nearest parent that is written.
+fn locate(content: &str, document: &DeTable<'_>, path: &str) -> Option<(usize, usize)> {
+    let mut span = None;
+    let mut table = Some(document);
+    let mut array = None;
+    for segment in path_segments(path) {
+        let value = match (table, array) {
+            (Some(table), _) => {
+                let (key, value) = table.iter().find(|(key, _)| key.get_ref() == segment)?;
+                span = Some(key.span());
+                value
             }
-        }
+            (None, Some(items)) => {
+                let items: &toml::de::DeArray<'_> = items;
+                let value = items.get(segment.parse::<usize>().ok()?)?;
+                span = Some(value.span());
+                value
+            }
+            (None, None) => break,
+        };
+        (table, array) = match value.get_ref() {
+            DeValue::Table(inner) => (Some(inner), None),
+            DeValue::Array(inner) => (None, Some(inner)),
+            _ => (None, None),
+        };
+    }
+    span.map(|span| position(content, span.start))
+}
 
-        // Return default config if no file found
-        tracing::warn!("no config file found, using defaults");
-        Ok(Config::default())
+/// Keys and indexes of a path like `host[1].env[HTTP_PROXY]`, in order
+fn path_segments(path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let mut rest = path;
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix('[') {
+            let end = inner.find(']').unwrap_or(inner.len());
+            segments.push(&inner[..end]);
+            rest = inner.get(end + 1..).unwrap_or_default();
+        } else {
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            segments.push(&rest[..end]);
+            rest = &rest[end..];
+        }
+        rest = rest.strip_prefix('.').unwrap_or(rest);
     }
+    segments
 }
 
-// TODO: Implement configuration parsing from tendhost.toml
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_case_host_names_are_normalized() {
+        let content = r#"
+            [[host]]
+            name = "Web-1"
+            addr = "10.0.0.1"
+
+            [[host]]
+            name = "db-1"
+            addr = "10.0.0.2"
+        "#;
+        let config: Config = toml::from_str(content).unwrap();
+        assert_eq!(config.host[0].name.as_str(), "web-1");
+        assert_eq!(config.host[1].name.as_str(), "db-1");
+        assert_eq!(unnormalized_host_names(content), vec!["Web-1".to_string()]);
+    }
+}
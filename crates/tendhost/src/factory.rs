@@ -5,77 +5,184 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use eyre::Result;
-use tendhost_core::{HostActorFactory, HostConfig};
-use tendhost_exec::{ConnectionInfo, KeySource, LocalExecutor, RemoteExecutor, SshExecutor};
-use tendhost_pkg::{AptManager, DnfManager, DockerComposeManager, PackageManager};
+use tendhost_core::{
+    CoreError, FieldError, HostActorFactory, HostConfig, HostPolicy, JumpHost, check_key_file,
+    check_passphrase_env,
+};
+use tendhost_exec::{
+    ConnectionInfo, KeySource, LocalExecutor, PassphraseSource, RemoteExecutor, RemoteExecutorExt,
+    SshExecutor,
+};
+use tendhost_pkg::{
+    AptManager, DeferredPackageManager, DnfManager, DockerComposeManager, PackageError,
+    PackageManager, PackageManagerType, PrivilegeEscalation, detect_distro,
+    lock::DEFAULT_LOCK_MAX_BACKOFF,
+};
 
 /// Default implementation of `HostActorFactory`
-pub struct DefaultHostFactory;
+pub struct DefaultHostFactory {
+    /// Reject SSH hosts whose key file is missing
+    check_ssh_keys: bool,
+    /// Hosts a `jump_host` name can refer to
+    jump_hosts: Vec<HostConfig>,
+}
 
 impl DefaultHostFactory {
     /// Create a new factory instance
     pub fn new() -> Self {
-        Self
+        Self {
+            check_ssh_keys: true,
+            jump_hosts: Vec::new(),
+        }
+    }
+
+    /// Enable or disable the SSH key existence check
+    #[must_use]
+    pub fn with_ssh_key_check(mut self, enabled: bool) -> Self {
+        self.check_ssh_keys = enabled;
+        self
+    }
+
+    /// Set the hosts `jump_host` names are looked up in
+    ///
+    /// These are the hosts from the config file; hosts registered at
+    /// runtime can only be reached through an inline jump host spec.
+    #[must_use]
+    pub fn with_jump_hosts(mut self, hosts: Vec<HostConfig>) -> Self {
+        self.jump_hosts = hosts;
+        self
+    }
+
+    /// Whether the host is reached through `LocalExecutor`
+    fn is_local(config: &HostConfig) -> bool {
+        config.addr == "localhost" || config.addr == "127.0.0.1"
+    }
+
+    /// Key used to log in to a host
+    fn key_source(config: &HostConfig) -> KeySource {
+        match (&config.ssh_key, &config.ssh_key_passphrase_env) {
+            (Some(key_path), Some(var)) => KeySource::PathWithPassphrase {
+                path: key_path.clone().into(),
+                passphrase: PassphraseSource::Env(var.clone()),
+            },
+            (Some(key_path), None) => KeySource::Path(key_path.clone().into()),
+            (None, _) => KeySource::Agent,
+        }
+    }
+
+    /// Error for a passphrase variable that is not set, if the key has one
+    fn check_passphrase(config: &HostConfig) -> Option<FieldError> {
+        config.ssh_key.as_ref()?;
+        check_passphrase_env(config.ssh_key_passphrase_env.as_deref()?).err()
     }
 
     /// Create a remote executor for a host
-    fn create_executor_sync(config: &HostConfig) -> Result<Arc<dyn RemoteExecutor>> {
+    fn create_executor_sync(&self, config: &HostConfig) -> Result<Arc<dyn RemoteExecutor>> {
         // For localhost connections, use LocalExecutor
-        if config.addr == "localhost" || config.addr == "127.0.0.1" {
-            return Ok(Arc::new(LocalExecutor::new()));
+        if Self::is_local(config) {
+            return Ok(Arc::new(LocalExecutor::new().with_env(config.env.clone())));
         }
 
         // Otherwise create SSH executor
-        let key_source = if let Some(key_path) = &config.ssh_key {
-            KeySource::Path(key_path.clone().into())
-        } else {
-            KeySource::Agent
+        let (host, port) = config.endpoint();
+        let mut conn_info = ConnectionInfo::new(host, &config.user).with_port(port);
+        if let Some(timeout) = config.connect_timeout {
+            conn_info = conn_info.with_connect_timeout(timeout);
+        }
+        if let Some(max_channels) = config.max_ssh_channels {
+            conn_info = conn_info.with_max_channels(max_channels);
+        }
+
+        // The bastion is dialled with the target's timeout; a configured
+        // jump host brings its own user and key, an inline one borrows them
+        let jump = config
+            .resolve_jump(&self.jump_hosts)
+            .map_err(|e| eyre::eyre!("invalid jump host: {e}"))?;
+        let timeout = conn_info.connect_timeout;
+        let jump_key = match jump {
+            Some(JumpHost::Configured(bastion)) => {
+                let (host, port) = bastion.endpoint();
+                conn_info = conn_info.with_jump(
+                    ConnectionInfo::new(host, &bastion.user)
+                        .with_port(port)
+                        .with_connect_timeout(timeout),
+                );
+                Some(Self::key_source(bastion))
+            }
+            Some(JumpHost::Inline(spec)) => {
+                let user = spec.user.as_deref().unwrap_or(&config.user);
+                conn_info = conn_info.with_jump(
+                    ConnectionInfo::new(spec.host, user)
+                        .with_port(spec.port)
+                        .with_connect_timeout(timeout),
+                );
+                None
+            }
+            None => None,
         };
 
-        let conn_info = ConnectionInfo::new(&config.addr, &config.user);
-        let executor = SshExecutor::new(conn_info, &key_source)
-            .map_err(|e| eyre::eyre!("failed to create SSH executor: {e}"))?;
+        let mut executor = SshExecutor::new(conn_info, &Self::key_source(config))
+            .map_err(|e| eyre::eyre!("failed to create SSH executor: {e}"))?
+            .with_env(config.env.clone());
+        if let Some(key_source) = jump_key {
+            executor = executor
+                .with_jump_key(&key_source)
+                .map_err(|e| eyre::eyre!("failed to load jump host key: {e}"))?;
+        }
         Ok(Arc::new(executor))
     }
 
-    /// Detect package manager by probing the host
+    /// Select the package manager from the host's distribution
     async fn detect_package_manager(
         executor: Arc<dyn RemoteExecutor>,
+        policy: &HostPolicy,
     ) -> Result<Arc<dyn PackageManager>> {
-        // Determine if we need sudo (check if we're root)
-        let whoami = executor.run("whoami").await;
-        let use_sudo = whoami
-            .as_ref()
-            .map(|r| !r.stdout.trim().eq("root"))
-            .unwrap_or(true);
-
-        // Try apt first (Debian/Ubuntu)
-        let apt_check = executor.run("which apt-get").await;
-        if apt_check.is_ok() && apt_check.as_ref().unwrap().success() {
-            tracing::info!(use_sudo, "detected apt package manager");
-            return Ok(Arc::new(AptManager::new(executor, use_sudo)));
-        }
+        // Root needs no escalation whatever the policy says
+        let is_root = executor
+            .run_ok("whoami")
+            .await
+            .is_ok_and(|user| user == "root");
+        let escalation = escalation_for(is_root, policy);
+        let timeouts = policy.timeouts.operation_timeouts();
+        let metadata_max_age = policy.metadata_max_age();
+        // apt waits a while by itself each attempt; the host retries until
+        // the whole lock wait is spent
+        let lock_timeout = policy.lock_wait().budget.min(DEFAULT_LOCK_MAX_BACKOFF);
 
-        // Try dnf (Fedora/RHEL 8+)
-        let dnf_check = executor.run("which dnf").await;
-        if dnf_check.is_ok() && dnf_check.as_ref().unwrap().success() {
-            tracing::info!(use_sudo, "detected dnf package manager");
-            return Ok(Arc::new(DnfManager::new(executor, use_sudo)));
-        }
+        let distro = detect_distro(executor.as_ref()).await?;
+        tracing::info!(
+            distro = %distro,
+            id = %distro.id,
+            package_manager = %distro.package_manager,
+            %escalation,
+            "detected distribution"
+        );
 
-        // Try yum (CentOS 7/RHEL 7)
-        let yum_check = executor.run("which yum").await;
-        if yum_check.is_ok() && yum_check.as_ref().unwrap().success() {
-            tracing::info!(use_sudo, "detected yum package manager (using DnfManager)");
-            return Ok(Arc::new(DnfManager::new(executor, use_sudo)));
+        match distro.package_manager {
+            PackageManagerType::Apt => Ok(Arc::new(
+                AptManager::new(executor, escalation)
+                    .with_timeouts(timeouts)
+                    .with_metadata_max_age(metadata_max_age)
+                    .with_lock_timeout(lock_timeout)
+                    .with_distro(distro),
+            )),
+            PackageManagerType::Dnf => Ok(Arc::new(
+                DnfManager::new(executor, escalation)
+                    .with_timeouts(timeouts)
+                    .with_metadata_max_age(metadata_max_age)
+                    .with_distro(distro),
+            )),
+            PackageManagerType::DockerCompose | PackageManagerType::Unknown => {
+                eyre::bail!(
+                    "{} cannot be a host's system package manager",
+                    distro.package_manager
+                )
+            }
         }
-
-        eyre::bail!("no supported package manager found (tried apt, dnf, yum)")
     }
 
     /// Create Docker Compose manager if compose paths are configured
-    #[allow(dead_code)]
-    fn create_compose_manager(
+    fn create_compose_manager_sync(
         config: &HostConfig,
         executor: Arc<dyn RemoteExecutor>,
     ) -> Option<Arc<dyn PackageManager>> {
@@ -86,8 +193,24 @@ impl DefaultHostFactory {
         // Convert String paths to PathBuf
         let compose_dirs: Vec<PathBuf> = config.compose_paths.iter().map(PathBuf::from).collect();
 
+        // Docker usually runs through the docker group, so without an
+        // explicit setting compose commands get no prefix
+        let escalation = config
+            .policy
+            .escalation
+            .clone()
+            .unwrap_or(PrivilegeEscalation::None);
         match DockerComposeManager::new(executor, compose_dirs) {
-            Ok(manager) => Some(Arc::new(manager)),
+            Ok(manager) => {
+                let manager = config.compose_env_files.iter().fold(
+                    manager
+                        .with_timeouts(config.policy.timeouts.operation_timeouts())
+                        .with_prune_policy(config.policy.image_prune.prune_policy())
+                        .with_escalation(escalation),
+                    |manager, (dir, env_file)| manager.with_env_file(dir, env_file),
+                );
+                Some(Arc::new(manager))
+            }
             Err(e) => {
                 tracing::error!(error = %e, "failed to create docker compose manager");
                 None
@@ -96,6 +219,15 @@ impl DefaultHostFactory {
     }
 }
 
+/// Escalation for the package manager of a host whose user is root or not
+fn escalation_for(is_root: bool, policy: &HostPolicy) -> PrivilegeEscalation {
+    if is_root {
+        PrivilegeEscalation::None
+    } else {
+        policy.escalation()
+    }
+}
+
 impl Default for DefaultHostFactory {
     fn default() -> Self {
         Self::new()
@@ -104,41 +236,122 @@ impl Default for DefaultHostFactory {
 
 #[async_trait]
 impl HostActorFactory for DefaultHostFactory {
-    async fn create_executor(&self, config: &HostConfig) -> Arc<dyn RemoteExecutor> {
-        Self::create_executor_sync(config).expect("failed to create executor - configuration error")
+    async fn create_executor(
+        &self,
+        config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        self.create_executor_sync(config)
+            .map_err(|e| CoreError::ConfigError(format!("{}: {e}", config.name)))
     }
 
     async fn create_package_manager(
         &self,
-        _config: &HostConfig,
+        config: &HostConfig,
         executor: Arc<dyn RemoteExecutor>,
     ) -> Arc<dyn PackageManager> {
-        Self::detect_package_manager(executor)
-            .await
-            .expect("failed to detect package manager")
+        // Detection takes several round trips, so it waits until the host
+        // is first used instead of holding up registration
+        let policy = config.policy.clone();
+        Arc::new(DeferredPackageManager::new(move || {
+            let (executor, policy) = (executor.clone(), policy.clone());
+            async move {
+                Self::detect_package_manager(executor, &policy)
+                    .await
+                    .map_err(|e| PackageError::ManagerNotFound(e.to_string()))
+            }
+        }))
+    }
+
+    async fn create_compose_manager(
+        &self,
+        config: &HostConfig,
+        executor: Arc<dyn RemoteExecutor>,
+    ) -> Option<Arc<dyn PackageManager>> {
+        Self::create_compose_manager_sync(config, executor)
+    }
+
+    fn check_config(&self, config: &HostConfig) -> Vec<FieldError> {
+        if Self::is_local(config) {
+            return Vec::new();
+        }
+        let mut errors = Vec::new();
+        let jump = match config.resolve_jump(&self.jump_hosts) {
+            Ok(jump) => jump,
+            Err(e) => {
+                errors.push(e);
+                None
+            }
+        };
+        // Keys are opened when the executor is built, so a passphrase that
+        // can't be read fails the registration instead of the host
+        errors.extend(Self::check_passphrase(config));
+        if let Some(JumpHost::Configured(bastion)) = jump
+            && let Some(e) = Self::check_passphrase(bastion)
+        {
+            errors.push(FieldError::new(
+                "jump_host",
+                format!("key passphrase of '{}': {}", bastion.name, e.message),
+            ));
+        }
+        if !self.check_ssh_keys {
+            return errors;
+        }
+        errors.extend(
+            config
+                .ssh_key
+                .as_deref()
+                .and_then(|key| check_key_file(key).err()),
+        );
+        if let Some(JumpHost::Configured(bastion)) = jump
+            && let Some(Err(e)) = bastion.ssh_key.as_deref().map(check_key_file)
+        {
+            errors.push(FieldError::new(
+                "jump_host",
+                format!("key of '{}': {}", bastion.name, e.message),
+            ));
+        }
+        errors
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use super::*;
 
-    #[test]
-    fn test_localhost_executor_creation() {
+    #[tokio::test]
+    async fn test_localhost_executor_creation() {
         use tendhost_core::HostPolicy;
 
         let config = HostConfig {
-            name: "localhost".to_string(),
+            name: "localhost".into(),
             addr: "127.0.0.1".to_string(),
+            port: None,
             user: "root".to_string(),
             ssh_key: None,
+            ssh_key_passphrase_env: None,
+            connect_timeout: None,
+            max_ssh_channels: None,
+            jump_host: None,
             compose_paths: vec![],
+            compose_env_files: BTreeMap::new(),
             tags: vec![],
+            depends_on: vec![],
+            env: BTreeMap::from([(
+                "TENDHOST_PROXY".to_string(),
+                "http://proxy:3128".to_string(),
+            )]),
             policy: HostPolicy::default(),
         };
 
-        let executor = DefaultHostFactory::create_executor_sync(&config);
-        assert!(executor.is_ok());
+        let executor = DefaultHostFactory::new()
+            .create_executor_sync(&config)
+            .unwrap();
+        if cfg!(unix) {
+            let result = executor.run("echo \"$TENDHOST_PROXY\"").await.unwrap();
+            assert_eq!(result.stdout.trim(), "http://proxy:3128");
+        }
     }
 
     #[test]
@@ -146,17 +359,184 @@ mod tests {
         use tendhost_core::HostPolicy;
 
         let config = HostConfig {
-            name: "docker-host".to_string(),
+            name: "docker-host".into(),
             addr: "localhost".to_string(),
+            port: None,
             user: "root".to_string(),
             ssh_key: None,
+            ssh_key_passphrase_env: None,
+            connect_timeout: None,
+            max_ssh_channels: None,
+            jump_host: None,
             compose_paths: vec!["/opt/stacks".to_string()],
+            compose_env_files: BTreeMap::new(),
             tags: vec![],
+            depends_on: vec![],
+            env: BTreeMap::new(),
             policy: HostPolicy::default(),
         };
 
-        let executor = Arc::new(LocalExecutor::new());
-        let compose = DefaultHostFactory::create_compose_manager(&config, executor);
-        assert!(compose.is_some());
+        let executor: Arc<dyn RemoteExecutor> = Arc::new(LocalExecutor::new());
+        let compose = DefaultHostFactory::create_compose_manager_sync(&config, executor.clone());
+        // Docker group members need no escalation, so it is only used when set
+        assert_eq!(compose.unwrap().escalation(), PrivilegeEscalation::None);
+
+        let mut config = config;
+        config.policy.escalation = Some(PrivilegeEscalation::Doas);
+        let compose = DefaultHostFactory::create_compose_manager_sync(&config, executor);
+        assert_eq!(compose.unwrap().escalation(), PrivilegeEscalation::Doas);
+    }
+
+    #[test]
+    fn test_escalation_for_root_and_other_users() {
+        let mut policy = HostPolicy::default();
+        assert_eq!(escalation_for(false, &policy), PrivilegeEscalation::Sudo);
+        assert_eq!(escalation_for(true, &policy), PrivilegeEscalation::None);
+
+        policy.escalation = Some(PrivilegeEscalation::Doas);
+        assert_eq!(escalation_for(false, &policy), PrivilegeEscalation::Doas);
+        assert_eq!(escalation_for(true, &policy), PrivilegeEscalation::None);
+    }
+
+    #[test]
+    fn test_check_config_rejects_missing_ssh_key() {
+        use tendhost_core::HostPolicy;
+
+        let mut config = HostConfig {
+            name: "remote".into(),
+            addr: "10.0.0.5".to_string(),
+            port: None,
+            user: "root".to_string(),
+            ssh_key: Some("/nonexistent/id_ed25519".to_string()),
+            ssh_key_passphrase_env: None,
+            connect_timeout: None,
+            max_ssh_channels: None,
+            jump_host: None,
+            compose_paths: vec![],
+            compose_env_files: BTreeMap::new(),
+            tags: vec![],
+            depends_on: vec![],
+            env: BTreeMap::new(),
+            policy: HostPolicy::default(),
+        };
+
+        let factory = DefaultHostFactory::new();
+        let errors = factory.check_config(&config);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "ssh_key");
+
+        assert!(
+            DefaultHostFactory::new()
+                .with_ssh_key_check(false)
+                .check_config(&config)
+                .is_empty()
+        );
+
+        // Local hosts never open the key
+        config.addr = "localhost".to_string();
+        assert!(factory.check_config(&config).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unset_passphrase_is_a_config_error() {
+        use tendhost_core::HostPolicy;
+
+        let mut config = HostConfig {
+            name: "remote".into(),
+            addr: "10.0.0.5".to_string(),
+            port: None,
+            user: "root".to_string(),
+            ssh_key: Some("/nonexistent/id_ed25519".to_string()),
+            ssh_key_passphrase_env: Some("TENDHOST_TEST_UNSET_PASSPHRASE".to_string()),
+            connect_timeout: None,
+            max_ssh_channels: None,
+            jump_host: None,
+            compose_paths: vec![],
+            compose_env_files: BTreeMap::new(),
+            tags: vec![],
+            depends_on: vec![],
+            env: BTreeMap::new(),
+            policy: HostPolicy::default(),
+        };
+
+        // Checked even when key files are not
+        let factory = DefaultHostFactory::new().with_ssh_key_check(false);
+        let errors = factory.check_config(&config);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "ssh_key_passphrase_env");
+
+        config.ssh_key_passphrase_env = Some("PATH".to_string());
+        assert!(factory.check_config(&config).is_empty());
+
+        // A key that can't be loaded is an error, not a panic
+        let Err(err) = factory.create_executor(&config).await else {
+            panic!("expected an executor error");
+        };
+        assert!(matches!(err, CoreError::ConfigError(_)), "got {err:?}");
+    }
+
+    #[test]
+    fn test_check_config_resolves_jump_hosts() {
+        use tendhost_core::HostPolicy;
+
+        let host = |name: &str, jump_host: Option<&str>| HostConfig {
+            name: name.into(),
+            addr: format!("{name}.lan"),
+            port: None,
+            user: "root".to_string(),
+            ssh_key: None,
+            ssh_key_passphrase_env: None,
+            connect_timeout: None,
+            max_ssh_channels: None,
+            jump_host: jump_host.map(str::to_string),
+            compose_paths: vec![],
+            compose_env_files: BTreeMap::new(),
+            tags: vec![],
+            depends_on: vec![],
+            env: BTreeMap::new(),
+            policy: HostPolicy::default(),
+        };
+        let mut bastion = host("bastion", None);
+        let factory = DefaultHostFactory::new()
+            .with_jump_hosts(vec![bastion.clone(), host("nested", Some("bastion"))]);
+
+        assert!(
+            factory
+                .check_config(&host("db", Some("bastion")))
+                .is_empty()
+        );
+        assert!(
+            factory
+                .check_config(&host("db", Some("ops@gw:2222")))
+                .is_empty()
+        );
+
+        let errors = factory.check_config(&host("db", Some("nested")));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "jump_host");
+
+        // The bastion's key is checked too
+        bastion.ssh_key = Some("/nonexistent/bastion_ed25519".to_string());
+        let factory = DefaultHostFactory::new().with_jump_hosts(vec![bastion]);
+        let errors = factory.check_config(&host("db", Some("bastion")));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "jump_host");
+        assert!(errors[0].message.starts_with("key of 'bastion':"));
+
+        // So is the variable holding its passphrase
+        let mut bastion = host("bastion", None);
+        bastion.ssh_key = Some("/nonexistent/bastion_ed25519".to_string());
+        bastion.ssh_key_passphrase_env = Some("TENDHOST_TEST_UNSET_PASSPHRASE".to_string());
+        let factory = DefaultHostFactory::new()
+            .with_ssh_key_check(false)
+            .with_jump_hosts(vec![bastion]);
+        let errors = factory.check_config(&host("db", Some("bastion")));
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "jump_host");
+        assert!(
+            errors[0]
+                .message
+                .starts_with("key passphrase of 'bastion':")
+        );
     }
 }
@@ -0,0 +1,379 @@
+//! Host definitions as portable TOML
+//!
+//! `GET /hosts/export` writes the registered hosts as `[[host]]` tables,
+//! the same shape as in tendhost.toml, so they can be kept in git or moved
+//! to another daemon with `POST /hosts/import`. Only configuration is
+//! written, plus each host's detected facts in a `facts` table for
+//! reference, which importing ignores. Hosts are sorted by name:
+//! exporting the same hosts always gives the same document.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use tendhost_api::requests::ImportMode;
+use tendhost_api::responses::ImportReport;
+use tendhost_core::{
+    CoreError, Facts, HostConfig, HostName, ListBusyHosts, ListHostConfigs, RegisterHost,
+    ReplaceHostConfig, Traced, UnregisterHost,
+};
+use tracing::info;
+
+use crate::reload::{diff_hosts, reason};
+use crate::state::AppState;
+
+/// The `[[host]]` tables of a config file
+#[derive(Serialize, Deserialize)]
+struct HostsDocument {
+    #[serde(default)]
+    host: Vec<HostConfig>,
+}
+
+/// Write `hosts` as `[[host]]` tables, sorted by name, with the known
+/// `facts` of each
+///
+/// # Errors
+/// Returns `CoreError::ConfigError` if a configuration can't be written as
+/// TOML.
+pub fn to_toml(
+    hosts: &[HostConfig],
+    facts: &BTreeMap<HostName, Facts>,
+) -> Result<String, CoreError> {
+    let error = |e: toml::ser::Error| CoreError::ConfigError(e.to_string());
+    let mut hosts = hosts.to_vec();
+    hosts.sort_by(|a, b| a.name.cmp(&b.name));
+    let host = hosts
+        .iter()
+        .map(|config| {
+            let mut table = toml::Table::try_from(config).map_err(error)?;
+            if let Some(facts) = facts.get(&config.name).filter(|facts| !facts.is_empty()) {
+                let facts: toml::Table = facts
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.clone().into()))
+                    .collect();
+                table.insert("facts".to_string(), facts.into());
+            }
+            Ok(table)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    toml::to_string(&ExportedHosts { host }).map_err(error)
+}
+
+/// The `[[host]]` tables of an export, facts included
+#[derive(Serialize)]
+struct ExportedHosts {
+    host: Vec<toml::Table>,
+}
+
+/// Read the `[[host]]` tables of a document; anything else in it is ignored
+///
+/// The `facts` tables an export writes are dropped; any other key a host
+/// table doesn't know is refused.
+///
+/// # Errors
+/// Returns `CoreError::ConfigError` if the document isn't valid TOML, a
+/// host table is malformed, or a name appears twice.
+pub fn from_toml(text: &str) -> Result<Vec<HostConfig>, CoreError> {
+    let error = |e: toml::de::Error| CoreError::ConfigError(e.to_string());
+    let mut document: toml::Table = toml::from_str(text).map_err(error)?;
+    if let Some(toml::Value::Array(hosts)) = document.get_mut("host") {
+        for host in hosts.iter_mut().filter_map(toml::Value::as_table_mut) {
+            host.remove("facts");
+        }
+    }
+    let document: HostsDocument = document.try_into().map_err(error)?;
+    let mut names: Vec<&str> = document.host.iter().map(|h| h.name.as_str()).collect();
+    names.sort_unstable();
+    if let Some(pair) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(CoreError::ConfigError(format!(
+            "duplicate host name '{}'",
+            pair[0]
+        )));
+    }
+    Ok(document.host)
+}
+
+/// Register the hosts of an imported document
+///
+/// In merge mode registered names are skipped. In replace mode changed
+/// hosts are reconfigured like on a reload, and registered hosts missing
+/// from the document are unregistered; if any of those is busy nothing is
+/// changed. Hosts the orchestrator refuses are reported as skipped.
+///
+/// # Errors
+/// Returns `CoreError::HostBusy` if a replace would unregister a busy host,
+/// or `CoreError::ActorError` if the orchestrator is unavailable.
+pub async fn import(
+    state: &AppState,
+    hosts: Vec<HostConfig>,
+    mode: ImportMode,
+) -> Result<ImportReport, CoreError> {
+    // A reload at the same time would diff against a stale host list
+    let _guard = state.reload_lock.lock().await;
+
+    let orchestrator = &state.orchestrator;
+    let registered = orchestrator
+        .ask(Traced::new(ListHostConfigs))
+        .await
+        .map_err(|e| CoreError::ActorError(e.to_string()))?;
+    let mut changes = diff_hosts(&registered, &hosts);
+    let mut report = ImportReport::default();
+
+    match mode {
+        ImportMode::Merge => {
+            for config in &hosts {
+                if registered.iter().any(|h| h.name == config.name) {
+                    skip(&mut report, &config.name, "already registered".to_string());
+                }
+            }
+            changes.updated.clear();
+            changes.removed.clear();
+        }
+        ImportMode::Replace => {
+            let busy = orchestrator
+                .ask(Traced::new(ListBusyHosts))
+                .await
+                .map_err(|e| CoreError::ActorError(e.to_string()))?;
+            let blocked: Vec<&str> = changes
+                .removed
+                .iter()
+                .filter(|name| busy.contains(name))
+                .map(|name| name.as_str())
+                .collect();
+            if !blocked.is_empty() {
+                return Err(CoreError::HostBusy(format!(
+                    "cannot unregister {} while busy; nothing was imported",
+                    blocked.join(", ")
+                )));
+            }
+        }
+    }
+
+    for name in changes.removed {
+        match orchestrator
+            .ask(Traced::new(UnregisterHost {
+                hostname: name.clone(),
+            }))
+            .await
+        {
+            Ok(()) => report.removed.push(name.to_string()),
+            Err(e) => skip(&mut report, &name, format!("not removed: {e}")),
+        }
+    }
+
+    for config in changes.added {
+        let name = config.name.clone();
+        match orchestrator.ask(Traced::new(RegisterHost { config })).await {
+            Ok(()) => report.added.push(name.to_string()),
+            Err(e) => skip(&mut report, &name, format!("not added: {}", reason(e))),
+        }
+    }
+
+    for config in changes.updated {
+        let name = config.name.clone();
+        match orchestrator
+            .ask(Traced::new(ReplaceHostConfig { config }))
+            .await
+        {
+            Ok(_) => report.updated.push(name.to_string()),
+            Err(e) => skip(&mut report, &name, format!("not updated: {}", reason(e))),
+        }
+    }
+
+    info!(
+        %mode,
+        added = ?report.added,
+        removed = ?report.removed,
+        updated = ?report.updated,
+        skipped = ?report.skipped,
+        "hosts imported"
+    );
+    Ok(report)
+}
+
+/// Record that `name` was left as it was
+fn skip(report: &mut ImportReport, name: &str, reason: String) {
+    report.skipped.push(name.to_string());
+    report.warnings.push(format!("{name}: {reason}"));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use kameo::actor::Spawn;
+    use tendhost_core::{
+        AuditLog, CoreError, EventHub, HostActorFactory, OrchestratorActor, OrchestratorActorArgs,
+    };
+    use tendhost_exec::{LocalExecutor, RemoteExecutor};
+    use tendhost_pkg::{AptManager, PackageManager, PrivilegeEscalation};
+
+    use tendhost_core::FactKey;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::notify::Notifier;
+    use crate::scheduler::Scheduler;
+
+    const HOSTS: &str = r#"
+        [[host]]
+        name = "web"
+        addr = "10.0.0.1"
+        port = 2222
+        user = "deploy"
+        ssh_key = "~/.ssh/web"
+        connect_timeout_secs = 5
+        jump_host = "bastion"
+        compose_paths = ["/srv/app"]
+        tags = ["prod", "edge"]
+
+        [host.policy]
+        auto_reboot = true
+
+        [[host]]
+        name = "db"
+        addr = "10.0.0.2"
+    "#;
+
+    /// Creates hosts without connecting to them
+    struct OfflineHostFactory;
+
+    #[async_trait]
+    impl HostActorFactory for OfflineHostFactory {
+        async fn create_executor(
+            &self,
+            _config: &HostConfig,
+        ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+            Ok(Arc::new(LocalExecutor::new()))
+        }
+
+        async fn create_package_manager(
+            &self,
+            _config: &HostConfig,
+            executor: Arc<dyn RemoteExecutor>,
+        ) -> Arc<dyn PackageManager> {
+            Arc::new(AptManager::new(executor, PrivilegeEscalation::None))
+        }
+    }
+
+    fn state() -> AppState {
+        let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+            event_channel_capacity: 16,
+            host_factory: Arc::new(OfflineHostFactory),
+            audit_log: None,
+            ..Default::default()
+        });
+        let audit_path =
+            std::env::temp_dir().join(format!("tendhost-import-{}.jsonl", uuid::Uuid::new_v4()));
+        let (_, events) = tokio::sync::broadcast::channel(1);
+        AppState::new(
+            orchestrator.clone(),
+            Config::default(),
+            None,
+            Arc::new(AuditLog::new(audit_path)),
+            EventHub::spawn(events, Duration::ZERO, 16),
+            Arc::new(Scheduler::new(&[], orchestrator.clone(), None)),
+            Arc::new(Notifier::new(&[], orchestrator)),
+        )
+    }
+
+    async fn export(state: &AppState) -> String {
+        let configs = state.orchestrator.ask(ListHostConfigs).await.unwrap();
+        to_toml(&configs, &BTreeMap::new()).unwrap()
+    }
+
+    #[test]
+    fn test_export_includes_facts_import_ignores() {
+        let hosts = from_toml(HOSTS).unwrap();
+        let facts = BTreeMap::from([(
+            HostName::from("web"),
+            Facts::from([
+                (FactKey::OsId, "debian".to_string()),
+                (FactKey::Docker, "true".to_string()),
+            ]),
+        )]);
+        let exported = to_toml(&hosts, &facts).unwrap();
+        let web = exported.find("name = \"web\"").unwrap();
+        let web_facts = exported.find("[host.facts]").unwrap();
+        assert!(web < web_facts, "{exported}");
+        assert!(exported.contains("os_id = \"debian\""), "{exported}");
+        assert_eq!(exported.matches("[host.facts]").count(), 1, "{exported}");
+
+        let mut imported = from_toml(&exported).unwrap();
+        imported.sort_by(|a, b| b.name.cmp(&a.name));
+        assert_eq!(imported, hosts);
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let source = state();
+        let report = import(&source, from_toml(HOSTS).unwrap(), ImportMode::Merge)
+            .await
+            .unwrap();
+        assert_eq!(report.added, ["db", "web"]);
+        let exported = export(&source).await;
+        assert!(exported.contains("jump_host = \"bastion\""), "{exported}");
+        assert!(exported.contains("connect_timeout_secs = 5"), "{exported}");
+
+        let copy = state();
+        let report = import(&copy, from_toml(&exported).unwrap(), ImportMode::Merge)
+            .await
+            .unwrap();
+        assert_eq!(report.added, ["db", "web"]);
+        assert_eq!(export(&copy).await, exported);
+
+        let db = exported.find("name = \"db\"").unwrap();
+        let web = exported.find("name = \"web\"").unwrap();
+        assert!(db < web, "hosts are sorted by name:\n{exported}");
+
+        // Merging again changes nothing
+        let report = import(&copy, from_toml(&exported).unwrap(), ImportMode::Merge)
+            .await
+            .unwrap();
+        assert!(report.added.is_empty());
+        assert_eq!(report.skipped, ["db", "web"]);
+    }
+
+    #[tokio::test]
+    async fn test_replace_import_unregisters_missing_hosts() {
+        let state = state();
+        import(&state, from_toml(HOSTS).unwrap(), ImportMode::Merge)
+            .await
+            .unwrap();
+
+        let document = "[[host]]\nname = \"db\"\naddr = \"10.0.0.20\"\n";
+        let report = import(&state, from_toml(document).unwrap(), ImportMode::Replace)
+            .await
+            .unwrap();
+        assert_eq!(report.removed, ["web"]);
+        assert_eq!(report.updated, ["db"]);
+        assert!(report.skipped.is_empty());
+
+        let configs = state.orchestrator.ask(ListHostConfigs).await.unwrap();
+        assert_eq!(configs.len(), 1);
+        assert_eq!(configs[0].addr, "10.0.0.20");
+    }
+
+    #[test]
+    fn test_from_toml_rejects_duplicates() {
+        let hosts = from_toml(
+            r#"
+            [[host]]
+            name = "web"
+            addr = "10.0.0.1"
+
+            [[host]]
+            name = "Web"
+            addr = "10.0.0.2"
+            "#,
+        );
+        let Err(CoreError::ConfigError(message)) = hosts else {
+            panic!("expected a duplicate name error, got {hosts:?}");
+        };
+        assert_eq!(message, "duplicate host name 'web'");
+
+        assert!(from_toml("").unwrap().is_empty());
+        assert!(from_toml("[[host]]\nname = \"web\"\n").is_err());
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright (C) 2026 Mozart409
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published
+// by the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! tendhost daemon library
+//!
+//! Everything the `tendhost` binary serves, so it can be embedded and
+//! tested end to end. [`AppState::spawn`] builds the orchestrator from a
+//! [`Config`] and any [`HostActorFactory`](tendhost_core::HostActorFactory),
+//! and [`router::create_router`] serves it:
+//!
+//! ```rust,no_run
+//! use std::net::SocketAddr;
+//! use std::sync::Arc;
+//!
+//! use tendhost::{AppState, Config, DefaultHostFactory, router};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! let state = AppState::spawn(Config::default(), None, Arc::new(DefaultHostFactory::new())).await?;
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
+//! let app = router::create_router(Arc::new(state));
+//! axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod api;
+pub mod config;
+pub mod factory;
+pub mod hosts_toml;
+pub mod limits;
+pub mod logging;
+pub mod notify;
+pub mod reload;
+pub mod router;
+pub mod scheduler;
+pub mod shutdown;
+pub mod state;
+
+pub use config::Config;
+pub use factory::DefaultHostFactory;
+pub use state::AppState;
@@ -0,0 +1,115 @@
+//! Request size, time and concurrency limits
+//!
+//! The daemon usually runs on a small box next to the hosts it manages.
+//! Without limits a single misbehaving client could upload gigabytes, keep
+//! a request open forever or pile up enough requests to starve the rest.
+//! Both limits here are read from the configuration on every request, so
+//! a reload applies them immediately.
+
+use std::sync::Arc;
+
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request, State},
+    http::header,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use futures::StreamExt;
+
+use crate::api::error::AppError;
+use crate::state::AppState;
+
+/// Routes the request timeout does not apply to
+///
+/// A fleet dry run plans every host before answering, and WebSocket
+/// connections stay open for as long as the client listens.
+const UNTIMED_ROUTES: &[&str] = &["/fleet/update", "/ws/events"];
+
+/// Whether the request waits for something that may take longer than a
+/// request, like a host update with `?wait=true`
+fn is_untimed(request: &Request) -> bool {
+    let Some(path) = request.extensions().get::<MatchedPath>() else {
+        return false;
+    };
+    if UNTIMED_ROUTES.contains(&path.as_str()) {
+        return true;
+    }
+    path.as_str() == "/hosts/{hostname}/update"
+        && request
+            .uri()
+            .query()
+            .is_some_and(|query| query.split('&').any(|pair| pair == "wait=true"))
+}
+
+/// Answer with 408 once a request takes longer than the configured timeout
+///
+/// The clock starts before the body is read, so a client trickling its
+/// body in counts against the same deadline as a slow handler.
+pub async fn limit_duration(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let untimed = is_untimed(&request);
+    let timeout = state.config().daemon.limits.request_timeout();
+    let Some(timeout) = timeout.filter(|_| !untimed) else {
+        return next.run(request).await;
+    };
+
+    tokio::time::timeout(timeout, next.run(request))
+        .await
+        .unwrap_or_else(|_| AppError::request_timeout(timeout).into_response())
+}
+
+/// Buffer the request body, answering with 413 once it exceeds the limit
+///
+/// A declared `Content-Length` over the limit is refused before anything
+/// is read. Reading is bounded by the request timeout on every route,
+/// including those [`limit_duration`] skips.
+pub async fn limit_body(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limits = state.config().daemon.limits;
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|len| len > limits.max_body_bytes as u64) {
+        return AppError::payload_too_large(limits.max_body_bytes).into_response();
+    }
+
+    let (parts, body) = request.into_parts();
+    let read = read_body(body, limits.max_body_bytes);
+    let bytes = match limits.request_timeout() {
+        Some(timeout) => match tokio::time::timeout(timeout, read).await {
+            Ok(bytes) => bytes,
+            Err(_) => Err(AppError::request_timeout(timeout)),
+        },
+        None => read.await,
+    };
+
+    match bytes {
+        Ok(bytes) => {
+            next.run(Request::from_parts(parts, Body::from(bytes)))
+                .await
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn read_body(body: Body, limit: usize) -> Result<Vec<u8>, AppError> {
+    let mut stream = body.into_data_stream();
+    let mut bytes = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| AppError::invalid_body(e.to_string()))?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(AppError::payload_too_large(limit));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,166 @@
+//! Log output and per-request correlation
+//!
+//! With `daemon.log_format = "json"` every line is one JSON object: the
+//! event's own fields sit at the top level next to `message`, and the
+//! fields of the spans it happened in are under `span` (innermost) and
+//! `spans` (outermost first). Work done for a host runs in a `host` span,
+//! and each HTTP request runs in a `request` span with a `request_id` that
+//! is also returned in the `x-request-id` response header.
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, Method},
+    middleware::Next,
+    response::Response,
+};
+use tracing::{Instrument, Span, Subscriber, info_span};
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
+use uuid::Uuid;
+
+use crate::config::{DaemonConfig, LogFormat};
+
+/// Header carrying the request id, in requests and responses
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Longest client-supplied request id that is kept
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Install the global subscriber for the daemon's log settings
+///
+/// `RUST_LOG` overrides `daemon.log_level` when set.
+pub fn init(daemon: &DaemonConfig) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&daemon.log_level));
+    subscriber(daemon.log_format, filter, std::io::stdout).init();
+}
+
+/// Build a subscriber writing `format` lines to `writer`
+fn subscriber<W>(
+    format: LogFormat,
+    filter: EnvFilter,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(true)
+        .with_writer(writer);
+    match format {
+        LogFormat::Pretty => Box::new(builder.finish()),
+        LogFormat::Json => Box::new(
+            builder
+                .json()
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(true)
+                .finish(),
+        ),
+    }
+}
+
+/// Run each request in a span carrying its request id
+///
+/// A valid `x-request-id` sent by the client is reused so its logs can be
+/// joined with the daemon's; otherwise a new id is generated.
+pub async fn assign_request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(client_request_id)
+        .map_or_else(|| Uuid::new_v4().to_string(), ToString::to_string);
+    let span = request_span(&id, request.method(), request.uri().path());
+
+    let mut response = next.run(request).instrument(span).await;
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    response
+}
+
+fn request_span(id: &str, method: &Method, path: &str) -> Span {
+    info_span!("request", request_id = %id, method = %method, path = %path)
+}
+
+/// The client's request id, if it is short and free of characters that
+/// would garble log lines
+fn client_request_id(id: &str) -> Option<&str> {
+    let valid = !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    valid.then_some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex, PoisonError};
+
+    use serde_json::Value;
+    use tracing::info;
+
+    use super::*;
+
+    /// Collects everything the subscriber writes
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'w> MakeWriter<'w> for Capture {
+        type Writer = Self;
+
+        fn make_writer(&'w self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_json_lines_carry_request_and_host_fields() {
+        let capture = Capture::default();
+        let subscriber = subscriber(LogFormat::Json, EnvFilter::new("info"), capture.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _request = request_span("req-1", &Method::POST, "/hosts/web/update").entered();
+            let _host = info_span!("host", host = "web").entered();
+            info!(package = "vim", "installing package");
+        });
+
+        let output = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let line: Value = serde_json::from_str(output.lines().next().unwrap()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["message"], "installing package");
+        // Event fields are flattened next to the message
+        assert_eq!(line["package"], "vim");
+        assert_eq!(line["span"]["host"], "web");
+        assert_eq!(line["spans"][0]["request_id"], "req-1");
+        assert_eq!(line["spans"][0]["path"], "/hosts/web/update");
+    }
+
+    #[test]
+    fn test_client_request_id_validation() {
+        assert_eq!(client_request_id("3f2a-b9_c.1"), Some("3f2a-b9_c.1"));
+        assert_eq!(client_request_id(""), None);
+        assert_eq!(client_request_id("two words"), None);
+        assert_eq!(client_request_id("line\nbreak"), None);
+        assert_eq!(client_request_id(&"a".repeat(MAX_REQUEST_ID_LEN + 1)), None);
+    }
+}
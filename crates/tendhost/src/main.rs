@@ -27,70 +27,79 @@
 //!
 //! # Run with specific config file
 //! TENDHOST_CONFIG=/path/to/tendhost.toml tendhost
+//!
+//! # Apply edits to the config file without restarting
+//! kill -HUP $(pidof tendhost)
+//!
+//! # List every problem in a config file and exit
+//! tendhost --check-config /path/to/tendhost.toml
 //! ```
 
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
 
 use color_eyre::Result;
 use tokio::signal;
-use tracing::info;
-use tracing_subscriber::EnvFilter;
-
-use kameo::actor::Spawn;
-use tendhost_core::{OrchestratorActor, OrchestratorActorArgs};
+use tokio::sync::oneshot;
+use tracing::{info, warn};
 
-mod api;
-mod config;
-mod factory;
-mod router;
-mod state;
+use tendhost::{AppState, Config, DefaultHostFactory, logging, reload, router, shutdown};
+use tendhost_core::{RegisterHosts, RestoreHosts, SaveSnapshots};
 
-use config::Config;
-use factory::DefaultHostFactory;
-use state::AppState;
+/// Longest wait for queued notifications at shutdown
+const NOTIFY_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize error handling
     color_eyre::install()?;
 
+    let mut args = std::env::args().skip(1);
+    if args.next().as_deref() == Some("--check-config") {
+        std::process::exit(check_config(args.next().map(PathBuf::from)));
+    }
+
     // Load configuration
+    let config_path = Config::find_path();
     let config = Config::load_default()?;
 
     // Initialize tracing
-    let filter = EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| EnvFilter::new(&config.daemon.log_level));
-
-    tracing_subscriber::fmt()
-        .with_env_filter(filter)
-        .with_target(true)
-        .init();
+    logging::init(&config.daemon);
 
     info!("tendhost daemon starting...");
     info!(bind = %config.daemon.bind, "configuration loaded");
 
     // Create host factory
-    let host_factory = Arc::new(DefaultHostFactory::new());
-
-    // Spawn orchestrator actor with factory
-    let orchestrator_args = OrchestratorActorArgs {
-        event_channel_capacity: 1024,
-        host_factory,
-    };
-    let orchestrator = OrchestratorActor::spawn(orchestrator_args);
+    let host_factory = Arc::new(
+        DefaultHostFactory::new()
+            .with_ssh_key_check(config.daemon.check_ssh_keys)
+            .with_jump_hosts(config.host.clone()),
+    );
 
+    // Spawn the orchestrator, audit log and event hub
+    let state = Arc::new(AppState::spawn(config.clone(), config_path, host_factory).await?);
+    let orchestrator = state.orchestrator.clone();
+    info!(path = %state.audit.path().display(), "audit log enabled");
     info!("orchestrator actor started");
 
-    // TODO: Register hosts from config
-    // for host_config in &config.host {
-    //     orchestrator.ask(RegisterHost { config: host_config.clone() }).await?;
-    // }
+    // Start recurring fleet updates
+    let scheduler_task = state.scheduler.spawn();
 
-    // Create application state
-    let state = Arc::new(AppState::new(orchestrator.clone(), config.clone()));
+    // Send matching events to webhooks
+    let notifier_task = state.notifier.spawn(state.events.subscribe());
+
+    // Hosts from the config appear in the API as they become ready
+    let registration_task = spawn_registration(state.clone());
+
+    // Re-read the config file on SIGHUP
+    #[cfg(unix)]
+    let reload_task = spawn_reload_on_hangup(state.clone())?;
 
     // Create router
-    let app = router::create_router(state);
+    let app = router::create_router(state.clone());
 
     // Create listener
     let listener = tokio::net::TcpListener::bind(&config.daemon.bind).await?;
@@ -100,20 +109,152 @@ async fn main() -> Result<()> {
         config.daemon.bind
     );
 
-    // Serve with graceful shutdown
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+    // Serve until the drain below completes
+    // Connect info gives the audit middleware the client address
+    let (stop_server, server_stopped) = oneshot::channel::<()>();
+    let server = tokio::spawn(
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async {
+            let _ = server_stopped.await;
+        })
+        .into_future(),
+    );
+
+    shutdown_signal().await;
+
+    // Drain: refuse new work and let running updates finish
+    state.draining.store(true, Ordering::Relaxed);
+    scheduler_task.abort();
+    registration_task.abort();
+    #[cfg(unix)]
+    reload_task.abort();
+    let grace = state.config().daemon.shutdown_grace_period();
+    info!(grace_secs = grace.as_secs(), "draining before shutdown");
+    tokio::select! {
+        idle = shutdown::wait_for_idle_hosts(&orchestrator, grace) => {
+            if idle {
+                info!("all hosts idle");
+            }
+        }
+        () = shutdown_signal() => warn!("second shutdown signal, forcing shutdown"),
+    }
 
     info!("shutting down...");
 
+    // Hand what the hosts know to the next daemon, e.g. after an upgrade
+    if let Err(e) = orchestrator.ask(SaveSnapshots).await {
+        warn!(error = %e, "failed to save host snapshots");
+    }
+
     // Stop orchestrator (which stops all host actors)
     let _ = orchestrator.stop_gracefully().await;
+    orchestrator.wait_for_shutdown().await;
+
+    // The stopped hosts' executors removed their temporary key files;
+    // remove any a leaked executor still holds
+    let leaked = tendhost_exec::remove_temp_keys();
+    if leaked > 0 {
+        warn!(count = leaked, "removed leftover temporary SSH keys");
+    }
+
+    // Open WebSockets would hold up the server's graceful shutdown
+    let _ = stop_server.send(());
+    state.events.close();
+    server.await??;
+
+    // Closing the event hub ended the notifier's stream; give the
+    // notifications it still has queued a moment to go out
+    if tokio::time::timeout(NOTIFY_FLUSH_TIMEOUT, notifier_task)
+        .await
+        .is_err()
+    {
+        warn!("undelivered notifications dropped at shutdown");
+    }
 
     info!("shutdown complete");
     Ok(())
 }
 
+/// Print every problem in the config file, returning the exit code
+///
+/// Without a path this checks the file the daemon would load.
+fn check_config(path: Option<PathBuf>) -> i32 {
+    let Some(path) = path.or_else(Config::find_path) else {
+        eprintln!("no config file found");
+        return 1;
+    };
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            return 1;
+        }
+    };
+
+    let issues = Config::check(&content);
+    for issue in &issues {
+        eprintln!("{}: {issue}", path.display());
+    }
+    if issues.is_empty() {
+        println!("{}: OK", path.display());
+        0
+    } else {
+        eprintln!("{} problem(s) found", issues.len());
+        1
+    }
+}
+
+/// Register the hosts from the config file, skipping invalid ones instead
+/// of refusing to start, then restore them from the snapshots of the last
+/// graceful shutdown
+///
+/// Holds the reload lock until done, so a SIGHUP meanwhile doesn't diff
+/// against a half-registered host list.
+fn spawn_registration(state: Arc<AppState>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let _guard = state.reload_lock.lock().await;
+        let configs = state.config().host.clone();
+        let total = configs.len();
+        info!(total, "registering hosts from config");
+        match state.orchestrator.ask(RegisterHosts { configs }).await {
+            Ok(report) => {
+                for result in &report.results {
+                    if let Some(error) = &result.error {
+                        warn!(host = %result.name, %error, "skipping host from config");
+                    }
+                }
+                info!(
+                    registered = report.created,
+                    total, "finished registering hosts from config"
+                );
+                if let Err(e) = state.orchestrator.ask(RestoreHosts).await {
+                    warn!(error = %e, "failed to restore host snapshots");
+                }
+            }
+            Err(e) => warn!(error = %e, "failed to register hosts from config"),
+        }
+    })
+}
+
+/// Reload the config file every time the daemon receives SIGHUP
+///
+/// Errors are logged; the daemon keeps running on the previous configuration.
+#[cfg(unix)]
+fn spawn_reload_on_hangup(state: Arc<AppState>) -> Result<tokio::task::JoinHandle<()>> {
+    let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            info!("SIGHUP received, reloading configuration");
+            if let Err(e) = reload::reload(&state).await {
+                warn!(error = %e, "configuration reload failed");
+            }
+        }
+    }))
+}
+
 /// Wait for shutdown signal
 async fn shutdown_signal() {
     let ctrl_c = async {
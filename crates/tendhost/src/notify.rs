@@ -0,0 +1,1045 @@
+//! Webhook notifications from `[[notify]]` config entries
+//!
+//! One task reads the event stream and hands each webhook the events its
+//! filter lets through. Every webhook has its own delivery task and queue,
+//! so an unreachable endpoint only delays its own messages. A webhook gets
+//! at most `max_per_minute` notifications in any minute; a flapping host
+//! beyond that is dropped rather than queued. Failed deliveries are retried
+//! with a doubling delay, and those that still fail are logged and counted
+//! in `/health` like the dropped ones; nothing here stops the daemon.
+//!
+//! Webhook URLs often embed a secret (Slack, Matrix hookshot), so logs name
+//! the webhook instead of showing its URL.
+
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use kameo::actor::ActorRef;
+use reqwest::StatusCode;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use tendhost_api::events::{EventEnvelope, WsEvent};
+use tendhost_api::responses::NotifierStats;
+use tendhost_core::{
+    FailureKind, FieldError, HostState, ListHostConfigs, OrchestratorActor, Traced,
+};
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::config::{NotifyConfig, NotifyFormat};
+
+/// Notifications waiting per webhook before new ones are dropped
+const QUEUE_SIZE: usize = 64;
+
+/// Period `max_per_minute` is counted over
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Wait before the first retry; doubled for each one after it
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How urgent a notification is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Severity {
+    Info,
+    Success,
+    Failure,
+}
+
+impl Severity {
+    /// Emoji short code, as used by Slack and ntfy tags
+    fn emoji(self) -> &'static str {
+        match self {
+            Self::Info => "information_source",
+            Self::Success => "white_check_mark",
+            Self::Failure => "x",
+        }
+    }
+}
+
+/// An event put into words
+#[derive(Debug, Clone)]
+struct Notification {
+    title: String,
+    message: String,
+    severity: Severity,
+    envelope: EventEnvelope,
+}
+
+impl Notification {
+    fn new(envelope: EventEnvelope) -> Self {
+        let (title, message, severity) = describe(&envelope.event);
+        Self {
+            title,
+            message,
+            severity,
+            envelope,
+        }
+    }
+}
+
+/// Title, message and severity of `event`
+fn describe(event: &WsEvent) -> (String, String, Severity) {
+    match event {
+        WsEvent::HostStateChanged {
+            host,
+            from,
+            to,
+            failure_kind,
+        } => {
+            let (title, severity) = if to == "failed" {
+                (format!("{host} failed"), Severity::Failure)
+            } else {
+                (format!("{host} is now {to}"), Severity::Info)
+            };
+            let message = match failure_kind {
+                Some(kind) => format!("{host} went from {from} to {to} ({kind})"),
+                None => format!("{host} went from {from} to {to}"),
+            };
+            (title, message, severity)
+        }
+        WsEvent::UpdateCompleted {
+            host,
+            result,
+            dry_run: true,
+        } => (
+            format!("{host} dry run finished"),
+            result.clone(),
+            Severity::Info,
+        ),
+        WsEvent::UpdateCompleted { host, result, .. } => {
+            (format!("{host} updated"), result.clone(), Severity::Success)
+        }
+        WsEvent::HostDisconnected { host, reason } => (
+            format!("{host} disconnected"),
+            reason.clone(),
+            Severity::Failure,
+        ),
+        WsEvent::RetriesExhausted { host, attempts } => (
+            format!("{host} gave up"),
+            format!("{host} is still failed after {attempts} automatic retries"),
+            Severity::Failure,
+        ),
+        WsEvent::CircuitOpened { host, retry_at, .. } => (
+            format!("{host} unreachable"),
+            format!(
+                "{host} keeps refusing connections; operations fail fast until {}",
+                retry_at.format("%H:%M")
+            ),
+            Severity::Failure,
+        ),
+        WsEvent::FleetHostFinished {
+            host,
+            skipped: true,
+            error,
+            ..
+        } => (
+            format!("{host} skipped"),
+            error
+                .clone()
+                .unwrap_or_else(|| "host unreachable".to_string()),
+            Severity::Info,
+        ),
+        WsEvent::FleetHostFinished {
+            host,
+            phase,
+            success,
+            error,
+            ..
+        } => {
+            if *success {
+                (
+                    format!("{host} updated"),
+                    format!("{host} finished its fleet update ({phase})"),
+                    Severity::Success,
+                )
+            } else {
+                (
+                    format!("{host} failed to update"),
+                    error.clone().unwrap_or_else(|| "unknown error".to_string()),
+                    Severity::Failure,
+                )
+            }
+        }
+        WsEvent::FleetUpdateHalted {
+            failed_canaries,
+            skipped_hosts,
+        } => (
+            "Fleet update halted".to_string(),
+            format!(
+                "Canary {} failed; {skipped_hosts} hosts were skipped",
+                failed_canaries.join(", ")
+            ),
+            Severity::Failure,
+        ),
+        WsEvent::FleetUpdateFinished {
+            total_hosts,
+            completed,
+            failed,
+            skipped,
+            dry_run,
+        } => {
+            let kind = if *dry_run { "dry run" } else { "update" };
+            let severity = if *failed > 0 || *skipped > 0 {
+                Severity::Failure
+            } else {
+                Severity::Success
+            };
+            (
+                format!("Fleet {kind} finished"),
+                format!(
+                    "{completed} of {total_hosts} hosts updated, {failed} failed, {skipped} skipped"
+                ),
+                severity,
+            )
+        }
+        other => {
+            let title = match other.host() {
+                Some(host) => format!("{host}: {}", other.kind()),
+                None => other.kind().to_string(),
+            };
+            let message = serde_json::to_string(other).unwrap_or_default();
+            (title, message, Severity::Info)
+        }
+    }
+}
+
+/// HTTP request body of a notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Payload {
+    content_type: &'static str,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+/// Render `notification` the way a `format` webhook expects it
+fn render(format: NotifyFormat, notification: &Notification) -> Payload {
+    match format {
+        NotifyFormat::Json => Payload {
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: serde_json::json!({
+                "source": "tendhost",
+                "title": notification.title,
+                "message": notification.message,
+                "severity": notification.severity,
+                "host": notification.envelope.event.host(),
+                "event": notification.envelope,
+            })
+            .to_string(),
+        },
+        NotifyFormat::Slack => Payload {
+            content_type: "application/json",
+            headers: Vec::new(),
+            body: serde_json::json!({
+                "text": format!(
+                    ":{}: *{}*\n{}",
+                    notification.severity.emoji(),
+                    notification.title,
+                    notification.message
+                ),
+            })
+            .to_string(),
+        },
+        NotifyFormat::Ntfy => Payload {
+            content_type: "text/plain; charset=utf-8",
+            headers: vec![
+                ("Title", header_text(&notification.title)),
+                (
+                    "Priority",
+                    match notification.severity {
+                        Severity::Failure => "high",
+                        Severity::Info | Severity::Success => "default",
+                    }
+                    .to_string(),
+                ),
+                ("Tags", notification.severity.emoji().to_string()),
+            ],
+            body: notification.message.clone(),
+        },
+    }
+}
+
+/// `text` with everything a header value can't carry replaced by `?`
+fn header_text(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c == ' ' || c.is_ascii_graphic() {
+                c
+            } else {
+                '?'
+            }
+        })
+        .collect()
+}
+
+/// Which events a webhook wants
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Filter {
+    events: Vec<String>,
+    states: Vec<String>,
+    failure_kinds: Vec<String>,
+    hosts: Vec<String>,
+    tags: Vec<String>,
+}
+
+impl Filter {
+    fn new(config: &NotifyConfig) -> Self {
+        Self {
+            events: config.events.clone(),
+            states: config.states.clone(),
+            failure_kinds: config.failure_kinds.clone(),
+            hosts: config.hosts.clone(),
+            tags: config.tags.clone(),
+        }
+    }
+
+    /// Whether `event` passes every filter but the tags
+    ///
+    /// Events about the whole fleet pass the host filters.
+    fn wants(&self, event: &WsEvent) -> bool {
+        if !self.events.iter().any(|kind| kind == event.kind()) {
+            return false;
+        }
+        if let WsEvent::HostStateChanged { to, .. } = event
+            && !self.states.is_empty()
+            && !self.states.contains(to)
+        {
+            return false;
+        }
+        if let WsEvent::HostStateChanged {
+            failure_kind: Some(kind),
+            ..
+        } = event
+            && !self.failure_kinds.is_empty()
+            && !self.failure_kinds.contains(kind)
+        {
+            return false;
+        }
+        event
+            .host()
+            .is_none_or(|host| self.hosts.is_empty() || self.hosts.iter().any(|h| h == host))
+    }
+
+    /// Whether the tags of the event's host have to be looked up
+    fn needs_tags(&self, event: &WsEvent) -> bool {
+        !self.tags.is_empty() && event.host().is_some()
+    }
+
+    /// Whether a host with `tags` passes the tag filter
+    fn wants_tags(&self, tags: &[String]) -> bool {
+        self.tags.is_empty() || self.tags.iter().any(|tag| tags.contains(tag))
+    }
+}
+
+/// Notifications sent within the last [`RATE_WINDOW`]
+#[derive(Debug)]
+struct RateLimit {
+    max: usize,
+    sent: VecDeque<Instant>,
+    /// Dropped since the last notification that got through
+    dropped: u64,
+}
+
+impl RateLimit {
+    fn new(max_per_minute: u32) -> Self {
+        let max = usize::try_from(max_per_minute).unwrap_or(usize::MAX);
+        Self {
+            max,
+            sent: VecDeque::with_capacity(max.min(QUEUE_SIZE)),
+            dropped: 0,
+        }
+    }
+
+    /// Count a notification at `now` if the limit allows one
+    fn allow(&mut self, now: Instant) -> bool {
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= RATE_WINDOW)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() < self.max {
+            self.sent.push_back(now);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+struct Endpoint {
+    config: NotifyConfig,
+    filter: Filter,
+    counters: Counters,
+}
+
+/// Why a delivery attempt failed
+struct DeliveryError {
+    reason: String,
+    /// Whether trying again could succeed
+    retry: bool,
+}
+
+/// Validate a webhook's fields
+fn check_notify(config: &NotifyConfig) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if config.name.is_empty()
+        || !config
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        errors.push(FieldError::new(
+            "name",
+            "must be non-empty and contain only letters, digits, `-`, `_` and `.`",
+        ));
+    }
+    match reqwest::Url::parse(&config.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        _ => errors.push(FieldError::new("url", "must be an http:// or https:// URL")),
+    }
+    if config.events.is_empty() {
+        errors.push(FieldError::new(
+            "events",
+            "must name at least one event type",
+        ));
+    }
+    for (i, kind) in config.events.iter().enumerate() {
+        if !WsEvent::KINDS.contains(&kind.as_str()) {
+            errors.push(FieldError::new(
+                format!("events[{i}]"),
+                format!("unknown event type `{kind}`"),
+            ));
+        }
+    }
+    for (i, state) in config.states.iter().enumerate() {
+        if !HostState::ALL.iter().any(|s| s.to_string() == *state) {
+            errors.push(FieldError::new(
+                format!("states[{i}]"),
+                format!("unknown host state `{state}`"),
+            ));
+        }
+    }
+    for (i, kind) in config.failure_kinds.iter().enumerate() {
+        if !FailureKind::ALL.iter().any(|k| k.to_string() == *kind) {
+            errors.push(FieldError::new(
+                format!("failure_kinds[{i}]"),
+                format!("unknown failure kind `{kind}`"),
+            ));
+        }
+    }
+    if config.max_per_minute == 0 {
+        errors.push(FieldError::new("max_per_minute", "must be at least 1"));
+    }
+    if config.timeout_secs == 0 {
+        errors.push(FieldError::new("timeout_secs", "must be at least 1"));
+    }
+    errors
+}
+
+/// Sends matching events to the configured webhooks
+pub struct Notifier {
+    endpoints: Vec<Arc<Endpoint>>,
+    orchestrator: ActorRef<OrchestratorActor>,
+    client: reqwest::Client,
+    retry_delay: Duration,
+}
+
+impl Notifier {
+    /// Create a notifier for the valid entries of `configs`
+    ///
+    /// Invalid or duplicate webhooks are logged and skipped, like invalid
+    /// schedules.
+    pub fn new(configs: &[NotifyConfig], orchestrator: ActorRef<OrchestratorActor>) -> Self {
+        let mut endpoints: Vec<Arc<Endpoint>> = Vec::new();
+
+        for config in configs {
+            let mut errors = check_notify(config);
+            if endpoints.iter().any(|e| e.config.name == config.name) {
+                errors.push(FieldError::new("name", "duplicate webhook name"));
+            }
+            if !errors.is_empty() {
+                for error in &errors {
+                    warn!(webhook = %config.name, field = %error.field, "{}", error.message);
+                }
+                warn!(webhook = %config.name, "skipping invalid webhook from config");
+                continue;
+            }
+
+            info!(webhook = %config.name, format = ?config.format, events = ?config.events, "loaded webhook");
+            endpoints.push(Arc::new(Endpoint {
+                filter: Filter::new(config),
+                config: config.clone(),
+                counters: Counters::default(),
+            }));
+        }
+
+        Self {
+            endpoints,
+            orchestrator,
+            client: reqwest::Client::new(),
+            retry_delay: RETRY_DELAY,
+        }
+    }
+
+    /// Delivery counters of each webhook
+    pub fn stats(&self) -> Vec<NotifierStats> {
+        self.endpoints
+            .iter()
+            .map(|endpoint| NotifierStats {
+                name: endpoint.config.name.clone(),
+                sent: endpoint.counters.sent.load(Ordering::Relaxed),
+                failed: endpoint.counters.failed.load(Ordering::Relaxed),
+                dropped: endpoint.counters.dropped.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    /// Notify about `events` in the background until the stream ends
+    ///
+    /// The task finishes once every queued notification has been delivered
+    /// or given up on.
+    pub fn spawn(self: &Arc<Self>, mut events: mpsc::Receiver<EventEnvelope>) -> JoinHandle<()> {
+        let notifier = self.clone();
+        tokio::spawn(async move {
+            let mut queues = Vec::with_capacity(notifier.endpoints.len());
+            let mut deliveries = Vec::with_capacity(notifier.endpoints.len());
+            for endpoint in &notifier.endpoints {
+                let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+                queues.push(tx);
+                deliveries.push(tokio::spawn(
+                    notifier.clone().deliver_all(endpoint.clone(), rx),
+                ));
+            }
+            let mut limits: Vec<RateLimit> = notifier
+                .endpoints
+                .iter()
+                .map(|e| RateLimit::new(e.config.max_per_minute))
+                .collect();
+
+            while let Some(envelope) = events.recv().await {
+                notifier.dispatch(&envelope, &queues, &mut limits).await;
+            }
+
+            drop(queues);
+            for delivery in deliveries {
+                let _ = delivery.await;
+            }
+        })
+    }
+
+    /// Queue `envelope` for every webhook that wants it
+    async fn dispatch(
+        &self,
+        envelope: &EventEnvelope,
+        queues: &[mpsc::Sender<Notification>],
+        limits: &mut [RateLimit],
+    ) {
+        let event = &envelope.event;
+        let mut tags = None;
+
+        for ((endpoint, queue), limit) in self.endpoints.iter().zip(queues).zip(limits) {
+            if !endpoint.filter.wants(event) {
+                continue;
+            }
+            if endpoint.filter.needs_tags(event) {
+                if tags.is_none() {
+                    tags = Some(self.host_tags(event.host().unwrap_or_default()).await);
+                }
+                if !endpoint
+                    .filter
+                    .wants_tags(tags.as_deref().unwrap_or_default())
+                {
+                    continue;
+                }
+            }
+
+            let name = &endpoint.config.name;
+            if !limit.allow(Instant::now()) {
+                if limit.dropped == 0 {
+                    warn!(
+                        webhook = %name,
+                        max_per_minute = endpoint.config.max_per_minute,
+                        "notification rate limit reached, dropping notifications"
+                    );
+                }
+                limit.dropped += 1;
+                endpoint.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            if limit.dropped > 0 {
+                info!(webhook = %name, dropped = limit.dropped, "notification rate limit lifted");
+                limit.dropped = 0;
+            }
+
+            match queue.try_send(Notification::new(envelope.clone())) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => {
+                    warn!(webhook = %name, seq = envelope.seq, "notification queue full, dropping notification");
+                    endpoint.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(TrySendError::Closed(_)) => {
+                    endpoint.counters.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Tags of the registered host `name`; none if it is unknown
+    async fn host_tags(&self, name: &str) -> Vec<String> {
+        match self.orchestrator.ask(Traced::new(ListHostConfigs)).await {
+            Ok(hosts) => hosts
+                .into_iter()
+                .find(|host| host.name == name)
+                .map(|host| host.tags)
+                .unwrap_or_default(),
+            Err(e) => {
+                warn!(host = %name, error = %e, "failed to look up host tags for notifications");
+                Vec::new()
+            }
+        }
+    }
+
+    /// Deliver everything queued for `endpoint` until the queue closes
+    async fn deliver_all(
+        self: Arc<Self>,
+        endpoint: Arc<Endpoint>,
+        mut queue: mpsc::Receiver<Notification>,
+    ) {
+        let format = endpoint.config.format;
+        while let Some(notification) = queue.recv().await {
+            let payload = render(format, &notification);
+            let seq = notification.envelope.seq;
+            match self.deliver(&endpoint, &payload).await {
+                Ok(()) => {
+                    debug!(webhook = %endpoint.config.name, seq, "notification sent");
+                    endpoint.counters.sent.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(reason) => {
+                    warn!(webhook = %endpoint.config.name, seq, error = %reason, "notification not delivered");
+                    endpoint.counters.failed.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// POST `payload`, retrying failures that may be temporary
+    async fn deliver(&self, endpoint: &Endpoint, payload: &Payload) -> Result<(), String> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.post(endpoint, payload).await {
+                Ok(()) => return Ok(()),
+                Err(e) if !e.retry || attempt > endpoint.config.retries => return Err(e.reason),
+                Err(e) => {
+                    debug!(
+                        webhook = %endpoint.config.name,
+                        attempt,
+                        error = %e.reason,
+                        retry_in_ms = delay.as_millis(),
+                        "notification attempt failed"
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2);
+                }
+            }
+        }
+    }
+
+    async fn post(&self, endpoint: &Endpoint, payload: &Payload) -> Result<(), DeliveryError> {
+        let mut request = self
+            .client
+            .post(&endpoint.config.url)
+            .timeout(Duration::from_secs(endpoint.config.timeout_secs))
+            .header(CONTENT_TYPE, payload.content_type)
+            .body(payload.body.clone());
+        for (name, value) in &payload.headers {
+            request = request.header(*name, value);
+        }
+
+        let response = request.send().await.map_err(|e| DeliveryError {
+            // Without the URL, which may hold a secret
+            reason: e.without_url().to_string(),
+            retry: true,
+        })?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+        Err(DeliveryError {
+            reason: format!("webhook answered {status}"),
+            retry: status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        })
+    }
+}
+
+#[cfg(test)]
+impl Notifier {
+    fn with_retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use axum::Router;
+    use axum::extract::State;
+    use axum::http::{HeaderMap, StatusCode as HttpStatus};
+    use axum::routing::post;
+    use kameo::actor::Spawn;
+    use serde_json::Value;
+    use tendhost_api::events::FleetPhase;
+    use tendhost_core::{HostConfig, OrchestratorActorArgs, RegisterHost};
+
+    use super::*;
+    use crate::factory::DefaultHostFactory;
+
+    /// A request the test webhook received
+    #[derive(Debug)]
+    struct Received {
+        headers: HeaderMap,
+        body: String,
+    }
+
+    #[derive(Clone)]
+    struct Receiver {
+        requests: mpsc::UnboundedSender<Received>,
+        /// Requests answered with 503 before the webhook starts accepting
+        failures: Arc<AtomicUsize>,
+    }
+
+    async fn hook(
+        State(receiver): State<Receiver>,
+        headers: HeaderMap,
+        body: String,
+    ) -> HttpStatus {
+        let _ = receiver.requests.send(Received { headers, body });
+        let failing = receiver
+            .failures
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .is_ok();
+        if failing {
+            HttpStatus::SERVICE_UNAVAILABLE
+        } else {
+            HttpStatus::NO_CONTENT
+        }
+    }
+
+    /// Start a webhook on a free port that fails its first `failures` requests
+    async fn receiver(failures: usize) -> (String, mpsc::UnboundedReceiver<Received>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let state = Receiver {
+            requests: tx,
+            failures: Arc::new(AtomicUsize::new(failures)),
+        };
+        let app = Router::new().route("/hook", post(hook)).with_state(state);
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}/hook"), rx)
+    }
+
+    fn webhook(url: &str, format: NotifyFormat) -> NotifyConfig {
+        toml::from_str::<NotifyConfig>(&format!("name = \"team\"\nurl = \"{url}\""))
+            .map(|config| NotifyConfig { format, ..config })
+            .unwrap()
+    }
+
+    fn orchestrator() -> ActorRef<OrchestratorActor> {
+        OrchestratorActor::spawn(OrchestratorActorArgs {
+            event_channel_capacity: 16,
+            host_factory: Arc::new(DefaultHostFactory::new()),
+            audit_log: None,
+            ..Default::default()
+        })
+    }
+
+    fn failed(host: &str, seq: u64) -> EventEnvelope {
+        EventEnvelope::new(
+            seq,
+            WsEvent::HostStateChanged {
+                host: host.to_string(),
+                from: "updating".to_string(),
+                to: "failed".to_string(),
+                failure_kind: Some("connection".to_string()),
+            },
+        )
+    }
+
+    fn fleet_finished(seq: u64) -> EventEnvelope {
+        EventEnvelope::new(
+            seq,
+            WsEvent::FleetUpdateFinished {
+                total_hosts: 3,
+                completed: 3,
+                failed: 0,
+                skipped: 0,
+                dry_run: false,
+            },
+        )
+    }
+
+    /// Run `notifier` over `events` until every notification is handled
+    async fn notify(notifier: &Arc<Notifier>, events: Vec<EventEnvelope>) {
+        let (tx, rx) = mpsc::channel(events.len().max(1));
+        let task = notifier.spawn(rx);
+        for envelope in events {
+            tx.send(envelope).await.unwrap();
+        }
+        drop(tx);
+        task.await.unwrap();
+    }
+
+    async fn received(rx: &mut mpsc::UnboundedReceiver<Received>) -> Vec<Received> {
+        let mut requests = Vec::new();
+        while let Ok(request) = rx.try_recv() {
+            requests.push(request);
+        }
+        requests
+    }
+
+    #[tokio::test]
+    async fn test_json_payload() {
+        let (url, mut rx) = receiver(0).await;
+        let notifier = Arc::new(Notifier::new(
+            &[webhook(&url, NotifyFormat::Json)],
+            orchestrator(),
+        ));
+        notify(&notifier, vec![failed("web", 7)]).await;
+
+        let requests = received(&mut rx).await;
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].headers[CONTENT_TYPE], "application/json");
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["source"], "tendhost");
+        assert_eq!(body["title"], "web failed");
+        assert_eq!(
+            body["message"],
+            "web went from updating to failed (connection)"
+        );
+        assert_eq!(body["severity"], "failure");
+        assert_eq!(body["host"], "web");
+        assert_eq!(body["event"]["seq"], 7);
+        assert_eq!(body["event"]["event"]["type"], "host_state_changed");
+        assert_eq!(notifier.stats()[0].sent, 1);
+    }
+
+    #[tokio::test]
+    async fn test_slack_payload() {
+        let (url, mut rx) = receiver(0).await;
+        let notifier = Arc::new(Notifier::new(
+            &[webhook(&url, NotifyFormat::Slack)],
+            orchestrator(),
+        ));
+        notify(&notifier, vec![fleet_finished(1)]).await;
+
+        let requests = received(&mut rx).await;
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "text": ":white_check_mark: *Fleet update finished*\n3 of 3 hosts updated, 0 failed, 0 skipped"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ntfy_payload() {
+        let (url, mut rx) = receiver(0).await;
+        let notifier = Arc::new(Notifier::new(
+            &[webhook(&url, NotifyFormat::Ntfy)],
+            orchestrator(),
+        ));
+        notify(&notifier, vec![failed("web", 1)]).await;
+
+        let requests = received(&mut rx).await;
+        let headers = &requests[0].headers;
+        assert_eq!(headers["title"], "web failed");
+        assert_eq!(headers["priority"], "high");
+        assert_eq!(headers["tags"], "x");
+        assert!(
+            headers[CONTENT_TYPE]
+                .to_str()
+                .unwrap()
+                .starts_with("text/plain")
+        );
+        assert_eq!(
+            requests[0].body,
+            "web went from updating to failed (connection)"
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let mut config = webhook("http://localhost/hook", NotifyFormat::Json);
+        let defaults = Filter::new(&config);
+        assert!(defaults.wants(&failed("web", 1).event));
+        assert!(defaults.wants(&fleet_finished(2).event));
+        // Only state changes into `failed` by default
+        assert!(!defaults.wants(&WsEvent::HostStateChanged {
+            host: "web".to_string(),
+            from: "idle".to_string(),
+            to: "querying".to_string(),
+            failure_kind: None,
+        }));
+        assert!(!defaults.wants(&WsEvent::HostConnected {
+            host: "web".to_string(),
+        }));
+
+        config.failure_kinds = vec!["authentication".to_string()];
+        assert!(!Filter::new(&config).wants(&failed("web", 1).event));
+        config.failure_kinds.push("connection".to_string());
+        assert!(Filter::new(&config).wants(&failed("web", 1).event));
+
+        config.events = vec!["fleet_host_finished".to_string()];
+        config.hosts = vec!["db".to_string()];
+        config.tags = vec!["prod".to_string()];
+        let narrow = Filter::new(&config);
+        let finished = |host: &str| WsEvent::FleetHostFinished {
+            host: host.to_string(),
+            phase: FleetPhase::Main,
+            success: true,
+            skipped: false,
+            error: None,
+        };
+        assert!(narrow.wants(&finished("db")));
+        assert!(!narrow.wants(&finished("web")));
+        assert!(narrow.needs_tags(&finished("db")));
+        assert!(narrow.wants_tags(&["edge".to_string(), "prod".to_string()]));
+        assert!(!narrow.wants_tags(&[]));
+    }
+
+    #[tokio::test]
+    async fn test_tag_filter_looks_up_registered_hosts() {
+        let (url, mut rx) = receiver(0).await;
+        let orchestrator = orchestrator();
+        let config: HostConfig =
+            toml::from_str("name = \"web\"\naddr = \"localhost\"\ntags = [\"prod\"]").unwrap();
+        orchestrator.ask(RegisterHost { config }).await.unwrap();
+
+        let mut config = webhook(&url, NotifyFormat::Json);
+        config.tags = vec!["prod".to_string()];
+        let notifier = Arc::new(Notifier::new(&[config], orchestrator));
+        notify(
+            &notifier,
+            vec![failed("db", 1), failed("web", 2), fleet_finished(3)],
+        )
+        .await;
+
+        let seqs: Vec<Value> = received(&mut rx)
+            .await
+            .iter()
+            .map(|r| serde_json::from_str::<Value>(&r.body).unwrap()["event"]["seq"].clone())
+            .collect();
+        assert_eq!(seqs, [2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_drops_bursts() {
+        let (url, mut rx) = receiver(0).await;
+        let mut config = webhook(&url, NotifyFormat::Json);
+        config.max_per_minute = 2;
+        let notifier = Arc::new(Notifier::new(&[config], orchestrator()));
+        notify(&notifier, (1..=5).map(|seq| failed("web", seq)).collect()).await;
+
+        assert_eq!(received(&mut rx).await.len(), 2);
+        let stats = &notifier.stats()[0];
+        assert_eq!((stats.sent, stats.dropped, stats.failed), (2, 3, 0));
+    }
+
+    #[test]
+    fn test_rate_limit_window_slides() {
+        let mut limit = RateLimit::new(2);
+        let start = Instant::now();
+        assert!(limit.allow(start));
+        assert!(limit.allow(start + Duration::from_secs(30)));
+        assert!(!limit.allow(start + Duration::from_secs(59)));
+        // The first one has left the window
+        assert!(limit.allow(start + Duration::from_secs(60)));
+        assert!(!limit.allow(start + Duration::from_secs(61)));
+    }
+
+    #[tokio::test]
+    async fn test_failed_deliveries_are_retried_and_counted() {
+        // Two 503s, then accepted on the third attempt
+        let (url, mut rx) = receiver(2).await;
+        let notifier = Arc::new(
+            Notifier::new(&[webhook(&url, NotifyFormat::Json)], orchestrator())
+                .with_retry_delay(Duration::from_millis(10)),
+        );
+        notify(&notifier, vec![failed("web", 1)]).await;
+        assert_eq!(received(&mut rx).await.len(), 3);
+        assert_eq!(notifier.stats()[0].sent, 1);
+
+        // Still failing after the first attempt and its only retry
+        let (url, mut rx) = receiver(usize::MAX).await;
+        let mut config = webhook(&url, NotifyFormat::Json);
+        config.retries = 1;
+        let notifier = Arc::new(
+            Notifier::new(&[config], orchestrator()).with_retry_delay(Duration::from_millis(10)),
+        );
+        notify(&notifier, vec![failed("web", 1)]).await;
+        assert_eq!(received(&mut rx).await.len(), 2);
+        let stats = &notifier.stats()[0];
+        assert_eq!((stats.sent, stats.failed), (0, 1));
+
+        // Nobody listening: the connection error is counted too
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+        let mut config = webhook(&url, NotifyFormat::Json);
+        config.retries = 0;
+        let notifier = Arc::new(Notifier::new(&[config], orchestrator()));
+        notify(&notifier, vec![failed("web", 1)]).await;
+        assert_eq!(notifier.stats()[0].failed, 1);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_and_duplicate_webhooks_are_skipped() {
+        let valid = webhook("https://ntfy.sh/homelab", NotifyFormat::Ntfy);
+        let mut bad_url = webhook("ftp://example.com", NotifyFormat::Json);
+        bad_url.name = "ftp".to_string();
+        let mut bad_filter = webhook("https://example.com/hook", NotifyFormat::Json);
+        bad_filter.name = "typo".to_string();
+        bad_filter.events = vec!["host_failed".to_string()];
+        bad_filter.states = vec!["broken".to_string()];
+        bad_filter.failure_kinds = vec!["lock_conflict".to_string()];
+        bad_filter.max_per_minute = 0;
+
+        let fields: Vec<String> = check_notify(&bad_filter)
+            .into_iter()
+            .map(|e| e.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "events[0]",
+                "states[0]",
+                "failure_kinds[0]",
+                "max_per_minute"
+            ]
+        );
+
+        let notifier = Notifier::new(&[valid.clone(), bad_url, bad_filter, valid], orchestrator());
+        let names: Vec<String> = notifier.stats().into_iter().map(|s| s.name).collect();
+        assert_eq!(names, ["team"]);
+    }
+}
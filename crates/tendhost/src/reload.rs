@@ -0,0 +1,318 @@
+//! Re-reading the config file while the daemon runs
+//!
+//! A reload (SIGHUP or `POST /system/reload`) compares the file's hosts
+//! with the registered ones: new hosts are registered, missing ones are
+//! unregistered and changed ones get their new configuration through the
+//! same path as `PATCH /hosts/{hostname}`. Busy hosts are never removed or
+//! restarted; they are skipped with a warning and picked up by the next
+//! reload. Daemon-level settings that are only read at startup are
+//! reported instead of applied.
+
+use std::collections::{BTreeMap, HashSet};
+
+use kameo::error::SendError;
+use tendhost_api::responses::ReloadReport;
+use tendhost_core::{
+    CoreError, HostConfig, HostName, ListBusyHosts, ListHostConfigs, RegisterHost,
+    ReplaceHostConfig, Traced, UnregisterHost,
+};
+use tracing::{info, warn};
+
+use crate::config::Config;
+use crate::state::AppState;
+
+/// How the hosts in the config file differ from the registered ones
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct HostChanges {
+    /// In the file but not registered
+    pub added: Vec<HostConfig>,
+    /// Registered but no longer in the file
+    pub removed: Vec<HostName>,
+    /// In both, with a different configuration
+    pub updated: Vec<HostConfig>,
+}
+
+/// Compare the `registered` hosts with the `desired` ones, by name
+///
+/// Each list comes out sorted by host name.
+pub fn diff_hosts(registered: &[HostConfig], desired: &[HostConfig]) -> HostChanges {
+    let registered: BTreeMap<&str, &HostConfig> =
+        registered.iter().map(|h| (h.name.as_str(), h)).collect();
+    let desired: BTreeMap<&str, &HostConfig> =
+        desired.iter().map(|h| (h.name.as_str(), h)).collect();
+
+    let mut changes = HostChanges::default();
+    for (name, config) in &desired {
+        match registered.get(name) {
+            None => changes.added.push((*config).clone()),
+            Some(current) if current != config => changes.updated.push((*config).clone()),
+            Some(_) => {}
+        }
+    }
+    changes.removed = registered
+        .keys()
+        .filter(|name| !desired.contains_key(*name))
+        .map(|name| HostName::from(*name))
+        .collect();
+    changes
+}
+
+/// Settings that differ between `old` and `new` but are only read at startup
+pub fn restart_required(old: &Config, new: &Config) -> Vec<&'static str> {
+    let (old_daemon, new_daemon) = (&old.daemon, &new.daemon);
+    [
+        ("daemon.bind", old_daemon.bind != new_daemon.bind),
+        (
+            "daemon.log_level",
+            old_daemon.log_level != new_daemon.log_level,
+        ),
+        (
+            "daemon.log_format",
+            old_daemon.log_format != new_daemon.log_format,
+        ),
+        ("daemon.audit", old_daemon.audit != new_daemon.audit),
+        (
+            "daemon.snapshot",
+            old_daemon.snapshot != new_daemon.snapshot,
+        ),
+        ("daemon.events", old_daemon.events != new_daemon.events),
+        ("daemon.docs_ui", old_daemon.docs_ui != new_daemon.docs_ui),
+        (
+            "daemon.check_ssh_keys",
+            old_daemon.check_ssh_keys != new_daemon.check_ssh_keys,
+        ),
+        (
+            "daemon.registration_concurrency",
+            old_daemon.registration_concurrency != new_daemon.registration_concurrency,
+        ),
+        (
+            "daemon.limits.max_concurrent_requests",
+            old_daemon.limits.max_concurrent_requests != new_daemon.limits.max_concurrent_requests,
+        ),
+        ("schedule", old.schedule != new.schedule),
+        ("notify", old.notify != new.notify),
+    ]
+    .into_iter()
+    .filter_map(|(setting, changed)| changed.then_some(setting))
+    .collect()
+}
+
+/// The configuration in effect after reloading `new` over `old`
+///
+/// Hosts and groups come from the new file; settings that need a restart
+/// keep their running values so the daemon reports what it actually uses.
+fn effective_config(old: &Config, mut new: Config) -> Config {
+    let shutdown_grace_period_secs = new.daemon.shutdown_grace_period_secs;
+    new.daemon = old.daemon.clone();
+    new.daemon.shutdown_grace_period_secs = shutdown_grace_period_secs;
+    new.schedule = old.schedule.clone();
+    new.notify = old.notify.clone();
+    new
+}
+
+/// Re-read the config file and apply its host changes
+///
+/// # Errors
+/// Returns `CoreError::ConfigError` if the daemon was started without a
+/// config file, or the file can't be read or names a host twice; nothing is
+/// changed then. Hosts that fail to apply are reported in the result
+/// instead.
+pub async fn reload(state: &AppState) -> Result<ReloadReport, CoreError> {
+    let _guard = state.reload_lock.lock().await;
+
+    let path = state.config_path.as_ref().ok_or_else(|| {
+        CoreError::ConfigError("daemon was started without a config file".to_string())
+    })?;
+    let new = Config::load(path)
+        .map_err(|e| CoreError::ConfigError(format!("{}: {e}", path.display())))?;
+    let mut seen = HashSet::new();
+    if let Some(duplicate) = new.host.iter().find(|h| !seen.insert(h.name.as_str())) {
+        return Err(CoreError::ConfigError(format!(
+            "duplicate host name '{}' in {}",
+            duplicate.name,
+            path.display()
+        )));
+    }
+
+    let orchestrator = &state.orchestrator;
+    let registered = orchestrator
+        .ask(Traced::new(ListHostConfigs))
+        .await
+        .map_err(|e| CoreError::ActorError(e.to_string()))?;
+    let busy = orchestrator
+        .ask(Traced::new(ListBusyHosts))
+        .await
+        .map_err(|e| CoreError::ActorError(e.to_string()))?;
+    let changes = diff_hosts(&registered, &new.host);
+
+    let mut report = ReloadReport::default();
+
+    for name in changes.removed {
+        if busy.contains(&name) {
+            skip(
+                &mut report,
+                &name,
+                "busy; not removed until a reload while it is idle".to_string(),
+            );
+            continue;
+        }
+        match orchestrator
+            .ask(Traced::new(UnregisterHost {
+                hostname: name.clone(),
+            }))
+            .await
+        {
+            Ok(()) => report.removed.push(name.to_string()),
+            Err(e) => skip(&mut report, &name, format!("not removed: {e}")),
+        }
+    }
+
+    for config in changes.added {
+        let name = config.name.clone();
+        match orchestrator.ask(Traced::new(RegisterHost { config })).await {
+            Ok(()) => report.added.push(name.to_string()),
+            Err(e) => skip(&mut report, &name, format!("not added: {}", reason(e))),
+        }
+    }
+
+    for config in changes.updated {
+        let name = config.name.clone();
+        match orchestrator
+            .ask(Traced::new(ReplaceHostConfig { config }))
+            .await
+        {
+            Ok(_) => report.updated.push(name.to_string()),
+            Err(e) => skip(&mut report, &name, format!("not updated: {}", reason(e))),
+        }
+    }
+
+    let old = state.config();
+    for setting in restart_required(&old, &new) {
+        report.requires_restart.push(setting.to_string());
+        report
+            .warnings
+            .push(format!("{setting} changed; restart the daemon to apply it"));
+    }
+    state.set_config(effective_config(&old, new));
+
+    for warning in &report.warnings {
+        warn!(path = %path.display(), "{warning}");
+    }
+    info!(
+        path = %path.display(),
+        added = ?report.added,
+        removed = ?report.removed,
+        updated = ?report.updated,
+        skipped = ?report.skipped,
+        "configuration reloaded"
+    );
+    Ok(report)
+}
+
+/// Record that `name` was left as it was
+fn skip(report: &mut ReloadReport, name: &str, reason: String) {
+    report.skipped.push(name.to_string());
+    report.warnings.push(format!("{name}: {reason}"));
+}
+
+/// Describe why the orchestrator refused a host, naming every invalid field
+pub fn reason<M>(err: SendError<M, CoreError>) -> String {
+    match err {
+        SendError::HandlerError(CoreError::InvalidHostConfig(errors)) => errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>()
+            .join("; "),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    const BEFORE: &str = r#"
+        [daemon]
+        bind = "127.0.0.1:8080"
+
+        [[host]]
+        name = "db"
+        addr = "10.0.0.2"
+
+        [[host]]
+        name = "old"
+        addr = "10.0.0.9"
+
+        [[host]]
+        name = "web"
+        addr = "10.0.0.1"
+        tags = ["prod"]
+    "#;
+
+    const AFTER: &str = r#"
+        [daemon]
+        bind = "0.0.0.0:8080"
+        shutdown_grace_period_secs = 30
+
+        [groups]
+        edge = ["web"]
+
+        [[host]]
+        name = "db"
+        addr = "10.0.0.2"
+
+        [[host]]
+        name = "web"
+        addr = "10.0.0.1"
+        tags = ["prod", "edge"]
+
+        [[host]]
+        name = "cache"
+        addr = "10.0.0.3"
+    "#;
+
+    #[test]
+    fn test_diff_hosts() {
+        let (before, after) = (config(BEFORE), config(AFTER));
+        let changes = diff_hosts(&before.host, &after.host);
+
+        let names = |hosts: &[HostConfig]| -> Vec<String> {
+            hosts.iter().map(|h| h.name.to_string()).collect()
+        };
+        assert_eq!(names(&changes.added), ["cache"]);
+        assert_eq!(changes.removed, ["old"]);
+        assert_eq!(names(&changes.updated), ["web"]);
+        assert_eq!(changes.updated[0].tags, ["prod", "edge"]);
+    }
+
+    #[test]
+    fn test_diff_of_identical_hosts_is_empty() {
+        let before = config(BEFORE);
+        assert_eq!(
+            diff_hosts(&before.host, &before.host),
+            HostChanges::default()
+        );
+    }
+
+    #[test]
+    fn test_restart_required_settings() {
+        let (before, after) = (config(BEFORE), config(AFTER));
+        // Groups and the grace period are read live; the bind address is not
+        assert_eq!(restart_required(&before, &after), ["daemon.bind"]);
+        assert!(restart_required(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_effective_config_keeps_startup_settings() {
+        let (before, after) = (config(BEFORE), config(AFTER));
+        let effective = effective_config(&before, after);
+        assert_eq!(effective.daemon.bind, "127.0.0.1:8080");
+        assert_eq!(effective.daemon.shutdown_grace_period_secs, 30);
+        assert_eq!(effective.groups["edge"], ["web"]);
+        assert_eq!(effective.host.len(), 3);
+    }
+}
@@ -3,32 +3,295 @@
 use std::sync::Arc;
 
 use axum::{
-    Router,
+    Router, middleware,
     routing::{get, post},
 };
+use tower::limit::GlobalConcurrencyLimitLayer;
+use tower_http::compression::CompressionLayer;
 
-use crate::api::{hosts, system};
+use crate::api::{audit, events, fleet, hosts, reports, schedules, system, ws};
 use crate::state::AppState;
+use crate::{limits, logging, shutdown};
 
 /// Create the application router
 pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
+    let max_concurrent = state.config().daemon.limits.max_concurrent_requests.max(1);
+    let mut router = Router::new()
         // System endpoints
         .route("/health", get(system::health))
+        .route("/openapi.json", get(system::openapi))
+        .route("/system/reload", post(system::reload_config))
+        .route("/system/config/validate", post(system::validate_config));
+    if state.config().daemon.docs_ui {
+        router = router.route("/docs", get(system::docs));
+    }
+
+    router
         // Host endpoints
         .route("/hosts", get(hosts::list_hosts).post(hosts::register_host))
+        .route("/hosts/bulk", post(hosts::register_hosts))
+        .route("/hosts/export", get(hosts::export_hosts))
+        .route("/hosts/import", post(hosts::import_hosts))
+        .route(
+            "/hosts/{hostname}",
+            get(hosts::get_host)
+                .patch(hosts::update_host_config)
+                .delete(hosts::unregister_host),
+        )
+        .route("/hosts/{hostname}/update", post(hosts::update_host))
         .route(
-            "/hosts/:hostname",
-            get(hosts::get_host).delete(hosts::unregister_host),
+            "/hosts/{hostname}/update-preview",
+            get(hosts::preview_host_update),
         )
-        .route("/hosts/:hostname/update", post(hosts::update_host))
-        .route("/hosts/:hostname/reboot", post(hosts::reboot_host))
-        .route("/hosts/:hostname/retry", post(hosts::retry_host))
+        .route("/hosts/{hostname}/cancel", post(hosts::cancel_host_update))
+        .route("/hosts/{hostname}/reboot", post(hosts::reboot_host))
+        .route("/hosts/{hostname}/retry", post(hosts::retry_host))
         .route(
-            "/hosts/:hostname/acknowledge",
+            "/hosts/{hostname}/acknowledge",
             post(hosts::acknowledge_host),
         )
-        .route("/hosts/:hostname/inventory", get(hosts::get_host_inventory))
+        .route("/hosts/{hostname}/pause", post(hosts::pause_host))
+        .route("/hosts/{hostname}/resume", post(hosts::resume_host))
+        .route(
+            "/hosts/{hostname}/install-osquery",
+            post(hosts::install_osquery),
+        )
+        .route(
+            "/hosts/{hostname}/inventory",
+            get(hosts::get_host_inventory),
+        )
+        .route(
+            "/hosts/{hostname}/inventory/diff",
+            get(hosts::get_host_inventory_diff),
+        )
+        .route("/hosts/{hostname}/commands", get(hosts::get_host_commands))
+        .route("/hosts/{hostname}/updates", get(hosts::get_host_updates))
+        .route(
+            "/hosts/{hostname}/transitions",
+            get(hosts::get_host_transitions),
+        )
+        // Fleet endpoints
+        .route("/fleet/update", post(fleet::update_fleet))
+        .route("/fleet/status", get(fleet::fleet_status))
+        // Schedule endpoints
+        .route("/schedules", get(schedules::list_schedules))
+        .route("/schedules/{id}/run-now", post(schedules::run_schedule_now))
+        // Report endpoints
+        .route("/reports/packages", get(reports::packages_report))
+        .route("/reports/updates", get(reports::updates_report))
+        // Audit endpoints
+        .route("/audit", get(audit::list_audit))
+        // Events
+        .route("/events", get(events::list_events))
+        .route("/events/recent", get(events::recent_events))
+        .route("/ws/events", get(ws::events))
+        // Record mutating requests
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            audit::record_mutations,
+        ))
+        // Refuse new work while draining for shutdown
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            shutdown::reject_while_draining,
+        ))
+        // Refuse oversized bodies, then bound the whole request in time
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            limits::limit_body,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            limits::limit_duration,
+        ))
+        // Compress responses the client accepts gzip or brotli for
+        .layer(CompressionLayer::new())
+        // Tag every log line of a request with its id
+        .layer(middleware::from_fn(logging::assign_request_id))
+        // Queue requests beyond the concurrency limit
+        .layer(GlobalConcurrencyLimitLayer::new(max_concurrent))
+        // Say which protocol version every reply speaks
+        .layer(middleware::map_response(system::version_headers))
         // State
         .with_state(state)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use std::time::{Duration, Instant};
+
+    use kameo::actor::Spawn;
+    use serde_json::Value;
+    use tendhost_core::{AuditLog, EventHub, OrchestratorActor, OrchestratorActorArgs};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::config::{Config, LimitsConfig};
+    use crate::factory::DefaultHostFactory;
+    use crate::notify::Notifier;
+    use crate::scheduler::Scheduler;
+
+    /// Serve the full router on a free port with `limits`
+    async fn serve(limits: LimitsConfig) -> SocketAddr {
+        let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+            event_channel_capacity: 16,
+            host_factory: Arc::new(DefaultHostFactory::new()),
+            audit_log: None,
+            ..Default::default()
+        });
+        let mut config = Config::default();
+        config.daemon.limits = limits;
+        let audit_path =
+            std::env::temp_dir().join(format!("tendhost-limits-{}.jsonl", uuid::Uuid::new_v4()));
+        let (_, events) = tokio::sync::broadcast::channel(1);
+        let state = Arc::new(AppState::new(
+            orchestrator.clone(),
+            config,
+            None,
+            Arc::new(AuditLog::new(audit_path)),
+            EventHub::spawn(events, Duration::ZERO, 16),
+            Arc::new(Scheduler::new(&[], orchestrator.clone(), None)),
+            Arc::new(Notifier::new(&[], orchestrator)),
+        ));
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = create_router(state).into_make_service_with_connect_info::<SocketAddr>();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        addr
+    }
+
+    fn limits() -> LimitsConfig {
+        LimitsConfig {
+            max_body_bytes: 1024,
+            request_timeout_secs: 1,
+            ..LimitsConfig::default()
+        }
+    }
+
+    /// Write `head` and `body` to a raw connection, then read the response
+    ///
+    /// Returns the status code and the JSON body.
+    async fn exchange(addr: SocketAddr, head: &str, body: &[&[u8]]) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(head.as_bytes()).await.unwrap();
+        for chunk in body {
+            stream.write_all(chunk).await.unwrap();
+        }
+
+        let mut response = Vec::new();
+        let mut buf = [0u8; 4096];
+        let (status, json) = loop {
+            let n = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buf))
+                .await
+                .expect("no response")
+                .unwrap();
+            assert!(n > 0, "connection closed without a response");
+            response.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&response);
+            let Some((headers, body)) = text.split_once("\r\n\r\n") else {
+                continue;
+            };
+            if let Ok(json) = serde_json::from_str::<Value>(body) {
+                let status = headers.split(' ').nth(1).unwrap().parse().unwrap();
+                break (status, json);
+            }
+        };
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn test_declared_oversized_body_is_refused() {
+        let addr = serve(limits()).await;
+        let body = vec![b' '; 4096];
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{addr}/hosts"))
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 413);
+        let error: Value = response.json().await.unwrap();
+        assert_eq!(error["code"], "PAYLOAD_TOO_LARGE");
+    }
+
+    #[tokio::test]
+    async fn test_streamed_oversized_body_is_refused() {
+        let addr = serve(limits()).await;
+        let chunk = format!("400\r\n{}\r\n", " ".repeat(0x400));
+
+        let (status, error) = exchange(
+            addr,
+            "POST /hosts HTTP/1.1\r\nhost: localhost\r\ncontent-type: application/json\r\n\
+             transfer-encoding: chunked\r\n\r\n",
+            &[chunk.as_bytes(), chunk.as_bytes()],
+        )
+        .await;
+
+        assert_eq!(status, 413);
+        assert_eq!(error["code"], "PAYLOAD_TOO_LARGE");
+    }
+
+    #[tokio::test]
+    async fn test_slow_body_times_out() {
+        let addr = serve(limits()).await;
+        let started = Instant::now();
+
+        // Promise 64 bytes, send 10 and stall
+        let (status, error) = exchange(
+            addr,
+            "POST /hosts HTTP/1.1\r\nhost: localhost\r\ncontent-type: application/json\r\n\
+             content-length: 64\r\n\r\n",
+            &[b"{\"name\": \""],
+        )
+        .await;
+
+        assert_eq!(status, 408);
+        assert_eq!(error["code"], "REQUEST_TIMEOUT");
+        assert!(started.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_bodies_under_the_limit_are_not_capped_by_auditing() {
+        let addr = serve(LimitsConfig {
+            max_body_bytes: 1024 * 1024,
+            ..LimitsConfig::default()
+        })
+        .await;
+        // A registration padded well past what the audit log keeps whole
+        let mut body = br#"{"name": "web-1", "addr": "127.0.0.1", "user": "root"}"#.to_vec();
+        body.resize(100 * 1024, b' ');
+
+        let response = reqwest::Client::new()
+            .post(format!("http://{addr}/hosts"))
+            .header("content-type", "application/json")
+            .body(body)
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 201);
+    }
+
+    #[tokio::test]
+    async fn test_small_bodies_pass_through() {
+        let addr = serve(limits()).await;
+
+        let response = reqwest::Client::new()
+            .patch(format!("http://{addr}/hosts/web-1"))
+            .json(&serde_json::json!({"tags": ["prod"]}))
+            .send()
+            .await
+            .unwrap();
+
+        // Reaches the handler, which has no such host
+        assert_eq!(response.status(), 404);
+        let error: Value = response.json().await.unwrap();
+        assert_eq!(error["code"], "HOST_NOT_FOUND");
+    }
+}
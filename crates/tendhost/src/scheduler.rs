@@ -0,0 +1,603 @@
+//! Recurring fleet updates from `[[schedule]]` config entries
+//!
+//! A single background task sleeps until the next schedule is due and then
+//! starts a fleet update through the orchestrator. Runs of one schedule never
+//! overlap: if the previous run is still going when the schedule fires again,
+//! that occurrence is skipped.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Days, FixedOffset, Local, NaiveTime, TimeZone, Utc, Weekday};
+use kameo::actor::ActorRef;
+use tendhost_api::responses::{AuditEntry, ScheduleInfo, ScheduleNextRun, ScheduleRunInfo};
+use tendhost_core::{
+    AuditLog, FieldError, FleetFilter, FleetUpdateConfig, OrchestratorActor, TriggerFleetUpdate,
+};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::config::ScheduleConfig;
+
+/// Longest sleep between checks, so changes to the system clock are noticed
+const MAX_SLEEP: Duration = Duration::from_secs(60);
+
+/// Time zone a schedule's time of day is interpreted in
+///
+/// Named zones (`Europe/Berlin`) are not supported; use `local` to follow
+/// the daemon's zone including DST, or a fixed offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScheduleTimezone {
+    /// The daemon's local time zone
+    Local,
+    Utc,
+    /// Fixed offset from UTC
+    Fixed(FixedOffset),
+}
+
+impl FromStr for ScheduleTimezone {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(Self::Local);
+        }
+        if s.eq_ignore_ascii_case("utc") || s == "Z" {
+            return Ok(Self::Utc);
+        }
+
+        let invalid = || format!("expected `local`, `UTC`, or an offset like `+02:00`, got `{s}`");
+        let (sign, rest) = match s.as_bytes().first() {
+            Some(b'+') => (1, &s[1..]),
+            Some(b'-') => (-1, &s[1..]),
+            _ => return Err(invalid()),
+        };
+        let (hours, minutes) = rest.split_once(':').ok_or_else(invalid)?;
+        let hours: i32 = hours.parse().map_err(|_| invalid())?;
+        let minutes: i32 = minutes.parse().map_err(|_| invalid())?;
+        if hours > 14 || minutes > 59 {
+            return Err(invalid());
+        }
+        FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+            .map(Self::Fixed)
+            .ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for ScheduleTimezone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local => f.write_str("local"),
+            Self::Utc => f.write_str("UTC"),
+            Self::Fixed(offset) => write!(f, "{offset}"),
+        }
+    }
+}
+
+/// When a schedule fires
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScheduleTime {
+    /// Days to run on; empty means every day
+    pub days: Vec<Weekday>,
+    /// Time of day in `timezone`
+    pub at: NaiveTime,
+    pub timezone: ScheduleTimezone,
+}
+
+impl ScheduleTime {
+    /// Parse and validate the timing fields of a schedule
+    ///
+    /// # Errors
+    /// Returns every invalid field
+    pub fn parse(config: &ScheduleConfig) -> Result<Self, Vec<FieldError>> {
+        let mut errors = Vec::new();
+
+        let mut days = Vec::new();
+        for (i, day) in config.days.iter().enumerate() {
+            match day.parse::<Weekday>() {
+                Ok(day) if !days.contains(&day) => days.push(day),
+                Ok(_) => {}
+                Err(_) => errors.push(FieldError::new(
+                    format!("days[{i}]"),
+                    format!("unknown day `{day}`"),
+                )),
+            }
+        }
+        let at = NaiveTime::parse_from_str(&config.at, "%H:%M")
+            .map_err(|_| {
+                errors.push(FieldError::new(
+                    "at",
+                    format!("expected `HH:MM`, got `{}`", config.at),
+                ));
+            })
+            .ok();
+        let timezone = config
+            .timezone
+            .parse::<ScheduleTimezone>()
+            .map_err(|e| errors.push(FieldError::new("timezone", e)))
+            .ok();
+
+        match (at, timezone) {
+            (Some(at), Some(timezone)) if errors.is_empty() => Ok(Self { days, at, timezone }),
+            _ => Err(errors),
+        }
+    }
+
+    /// First run strictly after `now`
+    #[must_use]
+    pub fn next_after(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        match self.timezone {
+            ScheduleTimezone::Local => self.next_in(&Local, now),
+            ScheduleTimezone::Utc => self.next_in(&Utc, now),
+            ScheduleTimezone::Fixed(offset) => self.next_in(&offset, now),
+        }
+    }
+
+    fn next_in<Tz: TimeZone>(&self, tz: &Tz, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let today = now.with_timezone(tz).date_naive();
+        (0..=7)
+            .filter_map(|offset| today.checked_add_days(Days::new(offset)))
+            .filter(|date| self.days.is_empty() || self.days.contains(&date.weekday()))
+            // A time repeated by a DST change runs at its first occurrence;
+            // a time skipped by one does not run that day
+            .filter_map(|date| tz.from_local_datetime(&date.and_time(self.at)).earliest())
+            .map(|time| time.with_timezone(&Utc))
+            .find(|time| *time > now)
+    }
+}
+
+/// Every invalid field of a schedule, timing fields included
+#[must_use]
+pub fn schedule_errors(config: &ScheduleConfig) -> Vec<FieldError> {
+    let mut errors = ScheduleTime::parse(config).err().unwrap_or_default();
+    errors.extend(check_schedule(config));
+    errors
+}
+
+/// Validate a schedule's non-timing fields
+fn check_schedule(config: &ScheduleConfig) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    if config.id.is_empty()
+        || !config
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        errors.push(FieldError::new(
+            "id",
+            "must be non-empty and contain only letters, digits, `-`, `_` and `.`",
+        ));
+    }
+    if config.batch_size == 0 {
+        errors.push(FieldError::new("batch_size", "must be at least 1"));
+    }
+    errors
+}
+
+/// Why a schedule could not be started on request
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// No schedule with this id
+    NotFound(String),
+    /// The previous run of this schedule has not finished
+    AlreadyRunning(String),
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound(id) => write!(f, "schedule not found: {id}"),
+            Self::AlreadyRunning(id) => write!(f, "schedule {id} is still running"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ScheduleState {
+    next_run: Option<DateTime<Utc>>,
+    running: bool,
+    last_run: Option<ScheduleRunInfo>,
+}
+
+struct Schedule {
+    config: ScheduleConfig,
+    time: ScheduleTime,
+    state: Mutex<ScheduleState>,
+}
+
+impl Schedule {
+    fn lock(&self) -> MutexGuard<'_, ScheduleState> {
+        // Plain data, still consistent after a panic elsewhere
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn fleet_config(&self) -> FleetUpdateConfig {
+        let filter = (!self.config.tags.is_empty()).then(|| FleetFilter {
+            tags: self.config.tags.clone(),
+            ..FleetFilter::default()
+        });
+        FleetUpdateConfig {
+            batch_size: self.config.batch_size,
+            delay_between_batches: Duration::from_millis(self.config.delay_ms),
+            filter,
+            dry_run: self.config.dry_run,
+            scope: self.config.scope,
+            ..FleetUpdateConfig::default()
+        }
+    }
+
+    fn info(&self) -> ScheduleInfo {
+        let state = self.lock();
+        ScheduleInfo {
+            id: self.config.id.clone(),
+            enabled: self.config.enabled,
+            days: self.time.days.iter().map(ToString::to_string).collect(),
+            at: self.time.at.format("%H:%M").to_string(),
+            timezone: self.time.timezone.to_string(),
+            tags: self.config.tags.clone(),
+            batch_size: self.config.batch_size,
+            dry_run: self.config.dry_run,
+            next_run: state.next_run,
+            running: state.running,
+            last_run: state.last_run.clone(),
+        }
+    }
+}
+
+/// Runs configured schedules and tracks their last result
+pub struct Scheduler {
+    schedules: Vec<Schedule>,
+    orchestrator: ActorRef<OrchestratorActor>,
+    audit: Option<Arc<AuditLog>>,
+}
+
+impl Scheduler {
+    /// Create a scheduler for the valid entries of `configs`
+    ///
+    /// Invalid or duplicate schedules are logged and skipped, like invalid
+    /// hosts, so one typo does not stop the daemon from starting.
+    pub fn new(
+        configs: &[ScheduleConfig],
+        orchestrator: ActorRef<OrchestratorActor>,
+        audit: Option<Arc<AuditLog>>,
+    ) -> Self {
+        let now = Utc::now();
+        let mut schedules: Vec<Schedule> = Vec::new();
+
+        for config in configs {
+            let mut errors = check_schedule(config);
+            let time = ScheduleTime::parse(config)
+                .map_err(|e| errors.extend(e))
+                .ok();
+            if schedules.iter().any(|s| s.config.id == config.id) {
+                errors.push(FieldError::new("id", "duplicate schedule id"));
+            }
+            let Some(time) = time.filter(|_| errors.is_empty()) else {
+                for error in &errors {
+                    warn!(schedule = %config.id, field = %error.field, "{}", error.message);
+                }
+                warn!(schedule = %config.id, "skipping invalid schedule from config");
+                continue;
+            };
+
+            let next_run = config.enabled.then(|| time.next_after(now)).flatten();
+            info!(schedule = %config.id, next_run = ?next_run, "loaded schedule");
+            schedules.push(Schedule {
+                config: config.clone(),
+                time,
+                state: Mutex::new(ScheduleState {
+                    next_run,
+                    ..ScheduleState::default()
+                }),
+            });
+        }
+
+        Self {
+            schedules,
+            orchestrator,
+            audit,
+        }
+    }
+
+    /// All schedules with their next and last run
+    pub fn info(&self) -> Vec<ScheduleInfo> {
+        self.schedules.iter().map(Schedule::info).collect()
+    }
+
+    /// Next run of each enabled schedule
+    pub fn next_runs(&self) -> Vec<ScheduleNextRun> {
+        self.schedules
+            .iter()
+            .filter(|s| s.config.enabled)
+            .map(|s| ScheduleNextRun {
+                id: s.config.id.clone(),
+                next_run: s.lock().next_run,
+            })
+            .collect()
+    }
+
+    /// Start a schedule immediately, independent of its timing
+    ///
+    /// # Errors
+    /// Returns an error if the schedule does not exist or is still running
+    pub fn run_now(self: &Arc<Self>, id: &str) -> Result<ScheduleInfo, ScheduleError> {
+        let index = self
+            .schedules
+            .iter()
+            .position(|s| s.config.id == id)
+            .ok_or_else(|| ScheduleError::NotFound(id.to_string()))?;
+        if !self.start(index, "manual") {
+            return Err(ScheduleError::AlreadyRunning(id.to_string()));
+        }
+        Ok(self.schedules[index].info())
+    }
+
+    /// Run schedules in the background until the task is aborted
+    pub fn spawn(self: &Arc<Self>) -> JoinHandle<()> {
+        let scheduler = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let wait = scheduler.tick(Utc::now());
+                tokio::time::sleep(wait).await;
+            }
+        })
+    }
+
+    /// Start every schedule due at `now`; returns how long to sleep
+    fn tick(self: &Arc<Self>, now: DateTime<Utc>) -> Duration {
+        let mut wake = None;
+
+        for (index, schedule) in self.schedules.iter().enumerate() {
+            let due = schedule.lock().next_run.is_some_and(|next| next <= now);
+            if due {
+                if !self.start(index, "schedule") {
+                    warn!(
+                        schedule = %schedule.config.id,
+                        "previous run still in progress, skipping"
+                    );
+                }
+                schedule.lock().next_run = schedule.time.next_after(now);
+            }
+
+            if let Some(next) = schedule.lock().next_run {
+                wake = Some(wake.map_or(next, |wake: DateTime<Utc>| wake.min(next)));
+            }
+        }
+
+        wake.and_then(|next| (next - now).to_std().ok())
+            .map_or(MAX_SLEEP, |wait| wait.min(MAX_SLEEP))
+    }
+
+    /// Start a fleet update for a schedule unless it is already running
+    fn start(self: &Arc<Self>, index: usize, trigger: &str) -> bool {
+        let schedule = &self.schedules[index];
+        let started_at = Utc::now();
+        {
+            let mut state = schedule.lock();
+            if state.running {
+                return false;
+            }
+            state.running = true;
+            state.last_run = Some(ScheduleRunInfo {
+                trigger: trigger.to_string(),
+                started_at,
+                finished_at: None,
+                total_hosts: 0,
+                completed: 0,
+                failed: 0,
+                error: None,
+            });
+        }
+
+        let scheduler = self.clone();
+        let trigger = trigger.to_string();
+        tokio::spawn(async move {
+            let schedule = &scheduler.schedules[index];
+            let id = &schedule.config.id;
+            info!(schedule = %id, trigger = %trigger, "starting scheduled fleet update");
+
+            if let Some(ref audit) = scheduler.audit {
+                let entry = AuditEntry {
+                    timestamp: started_at,
+                    operation: "fleet_update".to_string(),
+                    hosts: Vec::new(),
+                    params: serde_json::json!({
+                        "schedule": id,
+                        "trigger": trigger,
+                        "tags": schedule.config.tags,
+                        "dry_run": schedule.config.dry_run,
+                    }),
+                    source: "scheduler".to_string(),
+                    remote_addr: None,
+                    identity: None,
+                    status: None,
+                };
+                if let Err(e) = audit.record(&entry).await {
+                    warn!(schedule = %id, error = %e, "failed to write audit entry");
+                }
+            }
+
+            let result = scheduler
+                .orchestrator
+                .ask(TriggerFleetUpdate {
+                    config: schedule.fleet_config(),
+                    scheduled: true,
+                })
+                .await;
+
+            let mut state = schedule.lock();
+            state.running = false;
+            if let Some(run) = state.last_run.as_mut() {
+                run.finished_at = Some(Utc::now());
+                match result {
+                    Ok(progress) => {
+                        info!(
+                            schedule = %id,
+                            total = progress.total_hosts,
+                            completed = progress.completed,
+                            failed = progress.failed,
+                            "scheduled fleet update finished"
+                        );
+                        run.total_hosts = progress.total_hosts;
+                        run.completed = progress.completed;
+                        run.failed = progress.failed;
+                    }
+                    Err(e) => {
+                        warn!(schedule = %id, error = %e, "scheduled fleet update failed");
+                        run.error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use kameo::actor::Spawn;
+    use tendhost_core::OrchestratorActorArgs;
+
+    use super::*;
+    use crate::factory::DefaultHostFactory;
+
+    fn schedule(days: &[&str], at: &str, timezone: &str) -> ScheduleConfig {
+        ScheduleConfig {
+            id: "weekly".to_string(),
+            enabled: true,
+            days: days.iter().map(ToString::to_string).collect(),
+            at: at.to_string(),
+            timezone: timezone.to_string(),
+            tags: vec!["homelab".to_string()],
+            batch_size: 2,
+            delay_ms: 0,
+            dry_run: true,
+            scope: None,
+        }
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    fn orchestrator() -> ActorRef<OrchestratorActor> {
+        OrchestratorActor::spawn(OrchestratorActorArgs {
+            event_channel_capacity: 16,
+            host_factory: Arc::new(DefaultHostFactory::new()),
+            audit_log: None,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_next_run_on_weekday() {
+        let time = ScheduleTime::parse(&schedule(&["Sun"], "03:00", "UTC")).unwrap();
+
+        // 2026-10-14 is a Wednesday
+        let now = utc("2026-10-14T12:00:00Z");
+        assert_eq!(time.next_after(now), Some(utc("2026-10-18T03:00:00Z")));
+
+        // Exactly at the run time moves on to the next week
+        let now = utc("2026-10-18T03:00:00Z");
+        assert_eq!(time.next_after(now), Some(utc("2026-10-25T03:00:00Z")));
+    }
+
+    #[test]
+    fn test_next_run_every_day_with_offset() {
+        let time = ScheduleTime::parse(&schedule(&[], "03:00", "+02:00")).unwrap();
+
+        // 03:00 at +02:00 is 01:00 UTC
+        let now = utc("2026-10-14T00:30:00Z");
+        assert_eq!(time.next_after(now), Some(utc("2026-10-14T01:00:00Z")));
+        let now = utc("2026-10-14T01:30:00Z");
+        assert_eq!(time.next_after(now), Some(utc("2026-10-15T01:00:00Z")));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_fields() {
+        let errors =
+            ScheduleTime::parse(&schedule(&["Funday"], "25:00", "Mars/Olympus")).unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(fields, ["days[0]", "at", "timezone"]);
+
+        assert_eq!(
+            "local".parse::<ScheduleTimezone>(),
+            Ok(ScheduleTimezone::Local)
+        );
+        assert_eq!(
+            "-05:30".parse::<ScheduleTimezone>().unwrap().to_string(),
+            "-05:30"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_and_duplicate_schedules_are_skipped() {
+        let mut invalid = schedule(&[], "03:00", "UTC");
+        invalid.id = "bad id".to_string();
+        let mut zero_batch = schedule(&[], "03:00", "UTC");
+        zero_batch.id = "zero".to_string();
+        zero_batch.batch_size = 0;
+
+        let configs = [
+            schedule(&["Sun"], "03:00", "UTC"),
+            schedule(&["Sat"], "04:00", "UTC"),
+            invalid,
+            zero_batch,
+        ];
+        let scheduler = Scheduler::new(&configs, orchestrator(), None);
+
+        let info = scheduler.info();
+        assert_eq!(info.len(), 1);
+        assert_eq!(info[0].days, ["Sun"]);
+        assert!(info[0].next_run.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_due_schedule_runs_and_overlapping_run_is_skipped() {
+        let scheduler = Arc::new(Scheduler::new(
+            &[schedule(&[], "03:00", "UTC")],
+            orchestrator(),
+            None,
+        ));
+        let now = Utc::now();
+        scheduler.schedules[0].lock().next_run = Some(now);
+
+        scheduler.tick(now);
+        let info = &scheduler.info()[0];
+        assert!(info.running);
+        assert_eq!(info.last_run.as_ref().unwrap().trigger, "schedule");
+        assert!(info.next_run.unwrap() > now);
+
+        // The run has not had a chance to finish yet
+        assert_eq!(
+            scheduler.run_now("weekly").unwrap_err(),
+            ScheduleError::AlreadyRunning("weekly".to_string())
+        );
+        assert_eq!(
+            scheduler.run_now("missing").unwrap_err(),
+            ScheduleError::NotFound("missing".to_string())
+        );
+
+        // No hosts are registered, so the fleet update finishes right away
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while scheduler.info()[0].running {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let last_run = scheduler.info()[0].last_run.clone().unwrap();
+        assert!(last_run.finished_at.is_some());
+        assert_eq!(last_run.total_hosts, 0);
+        assert!(last_run.error.is_none());
+
+        assert!(scheduler.run_now("weekly").is_ok());
+        assert_eq!(
+            scheduler.info()[0].last_run.as_ref().unwrap().trigger,
+            "manual"
+        );
+    }
+}
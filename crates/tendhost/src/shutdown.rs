@@ -0,0 +1,112 @@
+//! Shutdown coordination
+//!
+//! Stopping host actors in the middle of a package upgrade can leave the
+//! package database half-configured, so shutdown first drains: mutating
+//! requests are rejected while the daemon waits for busy hosts to settle.
+
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderValue, Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use kameo::actor::ActorRef;
+use tendhost_core::{ListBusyHosts, OrchestratorActor};
+use tokio::time::{Instant, sleep};
+use tracing::{info, warn};
+
+use crate::api::error::AppError;
+use crate::state::AppState;
+
+/// Seconds clients are told to wait before retrying during a drain
+const RETRY_AFTER_SECS: &str = "30";
+
+/// How often busy hosts are polled while draining
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often drain progress is logged
+const LOG_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Whether a request with this method changes daemon or host state
+fn is_mutating(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    )
+}
+
+/// Reject mutating requests with 503 once the daemon is draining
+pub async fn reject_while_draining(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !state.draining.load(Ordering::Relaxed) || !is_mutating(request.method()) {
+        return next.run(request).await;
+    }
+
+    let mut response = AppError::shutting_down().into_response();
+    response.headers_mut().insert(
+        header::RETRY_AFTER,
+        HeaderValue::from_static(RETRY_AFTER_SECS),
+    );
+    response
+}
+
+/// Wait until no host is querying or updating, or `grace` elapses
+///
+/// Returns `true` if every host settled in time.
+pub async fn wait_for_idle_hosts(
+    orchestrator: &ActorRef<OrchestratorActor>,
+    grace: Duration,
+) -> bool {
+    let deadline = Instant::now() + grace;
+    let mut next_log = Instant::now();
+
+    loop {
+        let busy = match orchestrator.ask(ListBusyHosts).await {
+            Ok(busy) => busy,
+            Err(e) => {
+                warn!(error = %e, "failed to list busy hosts, not waiting");
+                return false;
+            }
+        };
+        if busy.is_empty() {
+            return true;
+        }
+
+        let now = Instant::now();
+        if now >= deadline {
+            warn!(hosts = ?busy, "shutdown grace period elapsed with hosts still busy");
+            return false;
+        }
+        if now >= next_log {
+            info!(
+                hosts = ?busy,
+                remaining_secs = (deadline - now).as_secs(),
+                "waiting for busy hosts before shutdown"
+            );
+            next_log = now + LOG_INTERVAL;
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_mutating() {
+        assert!(is_mutating(&Method::POST));
+        assert!(is_mutating(&Method::PATCH));
+        assert!(is_mutating(&Method::DELETE));
+        assert!(!is_mutating(&Method::GET));
+        assert!(!is_mutating(&Method::HEAD));
+    }
+}
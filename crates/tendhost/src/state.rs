@@ -2,28 +2,138 @@
 
 #![allow(dead_code)]
 
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, PoisonError, RwLock as StdRwLock};
 
-use kameo::actor::ActorRef;
-use tendhost_core::OrchestratorActor;
+use kameo::actor::{ActorRef, Spawn};
+use tendhost_core::{
+    AuditLog, EventHub, GetEventHub, HostActorFactory, HostName, OrchestratorActor,
+    OrchestratorActorArgs, RestartPolicy, SnapshotStore,
+};
+use tendhost_inventory::HostInventory;
+use tokio::sync::{Mutex, RwLock};
 
 use crate::config::Config;
+use crate::notify::Notifier;
+use crate::scheduler::Scheduler;
+
+/// Orchestrator events buffered for the event hub
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
 
 /// Application state shared across all handlers
 #[derive(Clone)]
 pub struct AppState {
     /// Reference to the orchestrator actor
     pub orchestrator: ActorRef<OrchestratorActor>,
-    /// Application configuration
-    pub config: Arc<Config>,
+    /// Application configuration; replaced on reload, read it with [`config`](Self::config)
+    config: Arc<StdRwLock<Arc<Config>>>,
+    /// File the configuration was read from; `None` when running on defaults
+    pub config_path: Option<PathBuf>,
+    /// Held while a reload runs so two never interleave
+    pub reload_lock: Arc<Mutex<()>>,
+    /// Audit log of mutating operations
+    pub audit: Arc<AuditLog>,
+    /// Last full inventory collected per host, used for reports
+    pub inventories: Arc<RwLock<HashMap<HostName, HostInventory>>>,
+    /// Event fan-out for WebSocket subscribers
+    pub events: EventHub,
+    /// Set once shutdown starts; mutating requests are rejected from then on
+    pub draining: Arc<AtomicBool>,
+    /// Recurring fleet updates
+    pub scheduler: Arc<Scheduler>,
+    /// Webhook notifications
+    pub notifier: Arc<Notifier>,
 }
 
 impl AppState {
     /// Create new application state
-    pub fn new(orchestrator: ActorRef<OrchestratorActor>, config: Config) -> Self {
+    pub fn new(
+        orchestrator: ActorRef<OrchestratorActor>,
+        config: Config,
+        config_path: Option<PathBuf>,
+        audit: Arc<AuditLog>,
+        events: EventHub,
+        scheduler: Arc<Scheduler>,
+        notifier: Arc<Notifier>,
+    ) -> Self {
         Self {
             orchestrator,
-            config: Arc::new(config),
+            config: Arc::new(StdRwLock::new(Arc::new(config))),
+            config_path,
+            reload_lock: Arc::default(),
+            audit,
+            inventories: Arc::new(RwLock::new(HashMap::new())),
+            events,
+            draining: Arc::new(AtomicBool::new(false)),
+            scheduler,
+            notifier,
         }
     }
+
+    /// Spawn the orchestrator for `config`, with hosts made by `host_factory`
+    ///
+    /// Opens the audit log at `daemon.audit.path` and sets up schedules and
+    /// notifications without starting them; the hosts in `config` are not
+    /// registered either. Nothing is read from disk, so tests can serve a
+    /// daemon from a [`Config`] built in code.
+    ///
+    /// # Errors
+    /// Returns an error if the orchestrator stops before handing out its
+    /// event hub.
+    pub async fn spawn(
+        config: Config,
+        config_path: Option<PathBuf>,
+        host_factory: Arc<dyn HostActorFactory>,
+    ) -> eyre::Result<Self> {
+        let daemon = &config.daemon;
+        let audit = Arc::new(
+            AuditLog::new(&daemon.audit.path)
+                .with_rotation(daemon.audit.max_size_bytes, daemon.audit.max_files),
+        );
+        let orchestrator = OrchestratorActor::spawn(OrchestratorActorArgs {
+            event_channel_capacity: EVENT_CHANNEL_CAPACITY,
+            host_factory,
+            audit_log: Some(audit.clone()),
+            snapshot_store: daemon
+                .snapshot
+                .enabled
+                .then(|| Arc::new(SnapshotStore::new(&daemon.snapshot.path))),
+            restart_policy: RestartPolicy::default(),
+            coalesce_window: daemon.events.coalesce_window(),
+            subscriber_queue_size: daemon.events.subscriber_queue_size,
+            registration_concurrency: daemon.registration_concurrency,
+        });
+        // Orchestrator events as fanned out to WebSocket subscribers
+        let events = orchestrator.ask(GetEventHub).await?;
+        let scheduler = Arc::new(Scheduler::new(
+            &config.schedule,
+            orchestrator.clone(),
+            Some(audit.clone()),
+        ));
+        let notifier = Arc::new(Notifier::new(&config.notify, orchestrator.clone()));
+        Ok(Self::new(
+            orchestrator,
+            config,
+            config_path,
+            audit,
+            events,
+            scheduler,
+            notifier,
+        ))
+    }
+
+    /// The configuration currently in effect
+    pub fn config(&self) -> Arc<Config> {
+        self.config
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Make `config` the configuration in effect
+    pub fn set_config(&self, config: Config) {
+        *self.config.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+    }
 }
@@ -0,0 +1,136 @@
+//! An in-process daemon for end-to-end tests
+//!
+//! [`TestDaemon`] serves the full router on an ephemeral port, with hosts
+//! from a mock [`HostActorFactory`], and drives it through the same
+//! [`HttpClient`] the CLI and TUI use.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tendhost::{AppState, Config, router};
+use tendhost_api::requests::RegisterHostRequest;
+use tendhost_api::responses::HostDetail;
+use tendhost_client::{ClientError, HttpClient, WsClient};
+use tendhost_core::HostActorFactory;
+use tendhost_core::testing::TestHostFactory;
+use tokio::task::JoinHandle;
+
+/// Longest wait for a host to reach an expected state
+const WAIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A daemon serving on 127.0.0.1, stopped when dropped
+pub struct TestDaemon {
+    pub state: Arc<AppState>,
+    pub client: HttpClient,
+    addr: SocketAddr,
+    audit_path: PathBuf,
+    server: JoinHandle<()>,
+}
+
+impl TestDaemon {
+    /// Serve a daemon whose hosts come from [`TestHostFactory`]
+    pub async fn start() -> Self {
+        Self::with_factory(Arc::new(TestHostFactory)).await
+    }
+
+    /// Serve a daemon whose hosts come from `factory`
+    pub async fn with_factory(factory: Arc<dyn HostActorFactory>) -> Self {
+        Self::with_config(Config::default(), factory).await
+    }
+
+    /// Serve a daemon with `config`, whose hosts come from `factory`
+    ///
+    /// The audit log always goes to a temporary file.
+    pub async fn with_config(mut config: Config, factory: Arc<dyn HostActorFactory>) -> Self {
+        let audit_path =
+            std::env::temp_dir().join(format!("tendhost-e2e-{}.jsonl", uuid::Uuid::new_v4()));
+        config.daemon.audit.path.clone_from(&audit_path);
+        let state = Arc::new(AppState::spawn(config, None, factory).await.unwrap());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router::create_router(state.clone())
+            .into_make_service_with_connect_info::<SocketAddr>();
+        let server = tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let client = HttpClient::new(format!("http://{addr}")).unwrap();
+
+        Self {
+            state,
+            client,
+            addr,
+            audit_path,
+            server,
+        }
+    }
+
+    /// Absolute URL of `path` on this daemon
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}{path}", self.addr)
+    }
+
+    /// Open the event stream
+    pub async fn events(&self) -> WsClient {
+        WsClient::try_connect(format!("ws://{}/ws/events", self.addr))
+            .await
+            .unwrap()
+    }
+
+    /// Register a host `name` tagged `test`
+    pub async fn register(&self, name: &str) {
+        self.client
+            .register_host(&host_request(name))
+            .await
+            .unwrap();
+    }
+
+    /// Poll `name` until `done` holds for its details
+    pub async fn wait_for(&self, name: &str, done: impl Fn(&HostDetail) -> bool) -> HostDetail {
+        tokio::time::timeout(WAIT_TIMEOUT, async {
+            loop {
+                let host = self.client.get_host(name).await.unwrap();
+                if done(&host) {
+                    return host;
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{name} did not reach the expected state"))
+    }
+
+    /// Poll `name` until it is in `state`, e.g. `PendingUpdates`
+    pub async fn wait_for_state(&self, name: &str, state: &str) -> HostDetail {
+        self.wait_for(name, |host| host.state == state).await
+    }
+}
+
+impl Drop for TestDaemon {
+    fn drop(&mut self) {
+        self.server.abort();
+        let _ = std::fs::remove_file(&self.audit_path);
+    }
+}
+
+/// Request registering a host `name` at 127.0.0.1, tagged `test`
+pub fn host_request(name: &str) -> RegisterHostRequest {
+    RegisterHostRequest {
+        name: name.to_string(),
+        addr: "127.0.0.1".to_string(),
+        port: None,
+        user: "root".to_string(),
+        ssh_key: None,
+        connect_timeout_secs: None,
+        jump_host: None,
+        tags: vec!["test".to_string()],
+    }
+}
+
+/// HTTP status of an API error, panicking on anything else
+pub fn status(result: Result<impl std::fmt::Debug, ClientError>) -> u16 {
+    match result {
+        Err(ClientError::Api { status, .. }) => status,
+        other => panic!("expected an API error, got {other:?}"),
+    }
+}
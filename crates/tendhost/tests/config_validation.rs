@@ -0,0 +1,97 @@
+//! Config file validation against the fixtures in `tests/fixtures/bad-config`
+//!
+//! Each fixture lists the issues it must produce in `# expect:` comment
+//! lines, written as the issues display; an issue matches when it starts
+//! with the expected text, so the list of valid keys after an unknown one
+//! can be left out. A new fixture needs no code here.
+
+use std::path::Path;
+
+use tendhost::Config;
+
+fn fixtures() -> Vec<(String, String)> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/bad-config");
+    let mut fixtures: Vec<_> = std::fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            (name, std::fs::read_to_string(&path).unwrap())
+        })
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+#[test]
+fn test_bad_configs_report_every_issue() {
+    let fixtures = fixtures();
+    assert!(fixtures.len() >= 5, "fixtures not found");
+
+    let mut mismatches = Vec::new();
+    for (name, content) in fixtures {
+        let expected: Vec<&str> = content
+            .lines()
+            .filter_map(|line| line.strip_prefix("# expect: "))
+            .collect();
+        assert!(!expected.is_empty(), "{name} expects nothing");
+
+        let issues: Vec<String> = Config::check(&content)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        let matches = issues.len() == expected.len()
+            && issues
+                .iter()
+                .zip(&expected)
+                .all(|(issue, expected)| issue.starts_with(expected));
+        if !matches {
+            mismatches.push(format!(
+                "{name}:\n  got      {issues:?}\n  expected {expected:?}"
+            ));
+        }
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn test_valid_config_has_no_issues() {
+    let content = r#"
+        [daemon]
+        bind = "127.0.0.1:8080"
+
+        [[host]]
+        name = "nfs"
+        addr = "10.0.0.2"
+
+        [[host]]
+        name = "web-1"
+        addr = "10.0.0.1"
+        tags = ["prod"]
+        depends_on = ["nfs"]
+
+        [host.policy]
+        maintenance_window = { start = "02:00", end = "06:00", days = ["Sat", "Sun"] }
+
+        [[schedule]]
+        id = "nightly"
+        at = "03:00"
+    "#;
+    assert_eq!(Config::check(content), []);
+}
+
+#[test]
+fn test_load_names_the_file_and_position() {
+    let dir = std::env::temp_dir().join(format!("tendhost-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tendhost.toml");
+    std::fs::write(&path, "[daemon]\nbnd = \"0.0.0.0:8080\"\n").unwrap();
+
+    let err = Config::load(&path).unwrap_err().to_string();
+    std::fs::remove_dir_all(&dir).unwrap();
+    let expected = format!(
+        "invalid config {}: line 2, column 1: daemon.bnd: unknown field `bnd`, expected one of `bind`",
+        path.display()
+    );
+    assert!(err.starts_with(&expected), "{err}");
+}
@@ -0,0 +1,934 @@
+//! End-to-end tests of the HTTP API against an in-process daemon
+
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use tendhost::Config;
+use tendhost_api::events::{EventEnvelope, WsEvent};
+use tendhost_api::requests::UpdateRequest;
+use tendhost_api::responses::{
+    CommandHistoryEntry, HostDetail, HostInventoryResponse, HostListResponse, StateTransitionInfo,
+    UpdatePreview,
+};
+use tendhost_api::version::{API_VERSION, API_VERSION_HEADER, DAEMON_VERSION_HEADER};
+use tendhost_core::testing::{MockExecutor, TestHostFactory};
+use tendhost_core::{AuditQuery, CoreError, HostActorFactory, HostConfig};
+use tendhost_exec::traits::RemoteExecutor;
+use tendhost_pkg::error::PackageError;
+use tendhost_pkg::traits::PackageManager;
+use tendhost_pkg::types::{PackageManagerType, UpdateResult, UpgradablePackage};
+
+use common::{TestDaemon, host_request, status};
+
+/// Package manager whose upgrades always fail
+struct BrokenPackageManager;
+
+#[async_trait]
+impl PackageManager for BrokenPackageManager {
+    async fn list_upgradable(&self) -> Result<Vec<UpgradablePackage>, PackageError> {
+        Ok(vec![UpgradablePackage::new("libc6", "2.36-8", "2.36-9")])
+    }
+
+    async fn upgrade_all(&self) -> Result<UpdateResult, PackageError> {
+        Err(PackageError::CommandFailed {
+            status: 100,
+            message: "dpkg was interrupted".to_string(),
+            output: String::new(),
+        })
+    }
+
+    async fn upgrade_dry_run(&self) -> Result<UpdateResult, PackageError> {
+        self.upgrade_all().await
+    }
+
+    async fn reboot_required(&self) -> Result<bool, PackageError> {
+        Ok(false)
+    }
+
+    fn manager_type(&self) -> PackageManagerType {
+        PackageManagerType::Apt
+    }
+
+    async fn is_available(&self) -> bool {
+        true
+    }
+}
+
+/// Hosts named `broken-*` fail their updates; the rest are mock hosts
+struct BrokenHostFactory;
+
+#[async_trait]
+impl HostActorFactory for BrokenHostFactory {
+    async fn create_executor(
+        &self,
+        _config: &HostConfig,
+    ) -> Result<Arc<dyn RemoteExecutor>, CoreError> {
+        Ok(Arc::new(MockExecutor::default()))
+    }
+
+    async fn create_package_manager(
+        &self,
+        config: &HostConfig,
+        executor: Arc<dyn RemoteExecutor>,
+    ) -> Arc<dyn PackageManager> {
+        if config.name.as_str().starts_with("broken-") {
+            Arc::new(BrokenPackageManager)
+        } else {
+            TestHostFactory
+                .create_package_manager(config, executor)
+                .await
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_register_list_and_get_hosts() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+    daemon.register("db-1").await;
+
+    let page = daemon.client.list_hosts().send().await.unwrap();
+    let mut names: Vec<_> = page.hosts.iter().map(|h| h.name.as_str()).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["db-1", "web-1"]);
+
+    let host = daemon.client.get_host("web-1").await.unwrap();
+    assert_eq!(host.name, "web-1");
+    assert_eq!(host.state, "Idle");
+    assert_eq!(host.tags, ["test"]);
+
+    // Names are case-insensitive, so this is the same host
+    let duplicate = daemon.client.register_host(&host_request("Web-1")).await;
+    assert_eq!(status(duplicate), 409);
+
+    daemon.client.delete_host("db-1").await.unwrap();
+    assert_eq!(
+        daemon.client.list_hosts().send().await.unwrap().hosts.len(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_missing_hosts_are_not_found() {
+    let daemon = TestDaemon::start().await;
+
+    assert_eq!(status(daemon.client.get_host("ghost").await), 404);
+    assert_eq!(status(daemon.client.delete_host("ghost").await), 404);
+    assert_eq!(
+        status(daemon.client.update_host_packages("ghost", true).await),
+        404
+    );
+    assert_eq!(status(daemon.client.retry_host("ghost").await), 404);
+    assert_eq!(status(daemon.client.acknowledge_host("ghost").await), 404);
+    assert_eq!(
+        status(daemon.client.get_host_inventory("ghost").send().await),
+        404
+    );
+}
+
+#[tokio::test]
+async fn test_dry_run_update_keeps_updates_pending() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+
+    // Nothing is pending before the first query
+    assert_eq!(
+        status(daemon.client.update_host_packages("web-1", true).await),
+        409
+    );
+
+    daemon
+        .client
+        .get_host_inventory("web-1")
+        .send()
+        .await
+        .unwrap();
+    let host = daemon.wait_for_state("web-1", "PendingUpdates").await;
+    assert_eq!(host.pending_updates, Some(2));
+
+    let accepted = daemon
+        .client
+        .update_host_packages("web-1", true)
+        .await
+        .unwrap();
+    assert!(accepted.dry_run);
+    let host = daemon
+        .wait_for("web-1", |host| {
+            host.state == "PendingUpdates" && host.last_updated.is_none()
+        })
+        .await;
+    assert_eq!(host.pending_updates, Some(2));
+
+    let history = daemon
+        .client
+        .get_update_history("web-1", None)
+        .await
+        .unwrap();
+    assert!(history.iter().any(|entry| entry.dry_run));
+}
+
+#[tokio::test]
+async fn test_update_preview_leaves_the_host_alone() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+    assert_eq!(
+        status(daemon.client.preview_update("ghost", None).await),
+        404
+    );
+
+    daemon
+        .client
+        .get_host_inventory("web-1")
+        .send()
+        .await
+        .unwrap();
+    daemon.wait_for_state("web-1", "PendingUpdates").await;
+    let transitions = daemon.client.get_transitions("web-1").await.unwrap();
+    let events = daemon.client.recent_events(100).await.unwrap();
+
+    let preview: UpdatePreview = round_trip(&daemon, "/hosts/web-1/update-preview").await;
+    assert_eq!(preview.upgraded_count, 2);
+    assert!(!preview.is_blocked(), "{:?}", preview.blockers);
+
+    let host = daemon.client.get_host("web-1").await.unwrap();
+    assert_eq!(host.state, "PendingUpdates");
+    assert_eq!(host.pending_updates, Some(2));
+    assert_eq!(
+        daemon.client.get_transitions("web-1").await.unwrap().len(),
+        transitions.len()
+    );
+    assert_eq!(
+        daemon.client.recent_events(100).await.unwrap().len(),
+        events.len()
+    );
+    assert!(
+        daemon
+            .client
+            .get_update_history("web-1", None)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_failed_update_is_acknowledged_and_retried() {
+    let daemon = TestDaemon::with_factory(Arc::new(BrokenHostFactory)).await;
+    daemon.register("broken-1").await;
+
+    // Only failed hosts can be retried or acknowledged
+    assert_eq!(status(daemon.client.retry_host("broken-1").await), 409);
+    assert_eq!(
+        status(daemon.client.acknowledge_host("broken-1").await),
+        409
+    );
+
+    daemon
+        .client
+        .get_host_inventory("broken-1")
+        .send()
+        .await
+        .unwrap();
+    daemon.wait_for_state("broken-1", "PendingUpdates").await;
+    daemon
+        .client
+        .update_host_packages("broken-1", false)
+        .await
+        .unwrap();
+    let host = daemon.wait_for_state("broken-1", "Failed").await;
+    assert!(
+        host.error
+            .as_deref()
+            .unwrap()
+            .contains("dpkg was interrupted"),
+        "{host:?}"
+    );
+    assert_eq!(host.acknowledged, Some(false));
+
+    daemon.client.acknowledge_host("broken-1").await.unwrap();
+    let host = daemon.client.get_host("broken-1").await.unwrap();
+    assert_eq!(host.acknowledged, Some(true));
+
+    // Retrying clears the failure so the host can be queried again
+    daemon.client.retry_host("broken-1").await.unwrap();
+    let host = daemon.wait_for_state("broken-1", "Idle").await;
+    assert_eq!(host.error, None);
+    assert_eq!(host.acknowledged, None);
+    assert_eq!(status(daemon.client.retry_host("broken-1").await), 409);
+
+    // Refused requests are audited with their status too, newest first
+    let entries = daemon
+        .state
+        .audit
+        .query(&AuditQuery {
+            host: Some("broken-1".to_string()),
+            ..AuditQuery::default()
+        })
+        .await
+        .unwrap();
+    let trail: Vec<_> = entries
+        .iter()
+        .map(|entry| (entry.operation.as_str(), entry.status))
+        .collect();
+    assert_eq!(
+        trail,
+        [
+            ("retry", Some(409)),
+            ("retry", Some(202)),
+            ("acknowledge", Some(202)),
+            ("update", Some(202)),
+            ("acknowledge", Some(409)),
+            ("retry", Some(409)),
+            ("register_host", Some(201)),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_host_list_filters_by_acknowledgement() {
+    let daemon = TestDaemon::with_factory(Arc::new(BrokenHostFactory)).await;
+    for name in ["broken-1", "broken-2", "broken-3"] {
+        daemon.register(name).await;
+    }
+    for name in ["broken-1", "broken-2"] {
+        daemon.client.get_host_inventory(name).send().await.unwrap();
+        daemon.wait_for_state(name, "PendingUpdates").await;
+        daemon
+            .client
+            .update_host_packages(name, false)
+            .await
+            .unwrap();
+        daemon.wait_for_state(name, "Failed").await;
+    }
+    daemon.client.acknowledge_host("broken-1").await.unwrap();
+
+    assert_eq!(
+        listed(&daemon, "state=failed&acknowledged=false").await,
+        ["broken-2"]
+    );
+    assert_eq!(
+        listed(&daemon, "state=failed&acknowledged=true").await,
+        ["broken-1"]
+    );
+    // Hosts that never failed have nothing to acknowledge
+    assert_eq!(listed(&daemon, "acknowledged=false").await, ["broken-2"]);
+    assert_eq!(
+        listed(&daemon, "state=failed").await,
+        ["broken-1", "broken-2"]
+    );
+}
+
+#[tokio::test]
+async fn test_audit_decodes_percent_encoded_host_names() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+
+    let response = reqwest::Client::new()
+        .post(daemon.url("/hosts/Web%2D1/pause"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+
+    let entries = daemon
+        .state
+        .audit
+        .query(&AuditQuery {
+            host: Some("web-1".to_string()),
+            ..AuditQuery::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(entries[0].operation, "pause");
+    assert_eq!(entries[0].hosts, ["web-1"]);
+}
+
+#[tokio::test]
+async fn test_paused_host_needs_forced_update() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+    assert_eq!(status(daemon.client.pause_host("ghost").await), 404);
+
+    daemon.client.pause_host("web-1").await.unwrap();
+    // Pausing twice is fine
+    daemon.client.pause_host("web-1").await.unwrap();
+    let page = daemon.client.list_hosts().send().await.unwrap();
+    assert!(page.hosts[0].paused);
+
+    daemon
+        .client
+        .get_host_inventory("web-1")
+        .send()
+        .await
+        .unwrap();
+    daemon.wait_for_state("web-1", "PendingUpdates").await;
+    let refused = daemon.client.update_host_packages("web-1", false).await;
+    let Err(tendhost_client::ClientError::Api { status, message }) = refused else {
+        panic!("expected a refusal, got {refused:?}");
+    };
+    assert_eq!(status, 409);
+    assert!(message.contains("paused"), "{message}");
+
+    let request = UpdateRequest {
+        dry_run: false,
+        scope: None,
+        stack: None,
+        packages: Vec::new(),
+        force: true,
+        skip_cleanup: false,
+    };
+    daemon.client.start_update("web-1", &request).await.unwrap();
+    let host = daemon
+        .wait_for("web-1", |host| host.last_updated.is_some())
+        .await;
+    assert!(host.paused);
+
+    daemon.client.resume_host("web-1").await.unwrap();
+    assert!(!daemon.client.get_host("web-1").await.unwrap().paused);
+}
+
+#[tokio::test]
+async fn test_osquery_install_is_opt_in() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+
+    let refused = daemon.client.install_osquery("web-1").await;
+    let Err(tendhost_client::ClientError::Api { status, message }) = refused else {
+        panic!("expected a refusal, got {refused:?}");
+    };
+    assert_eq!(status, 403);
+    assert!(message.contains("allow_remote_install"), "{message}");
+    assert_eq!(
+        daemon.client.list_hosts().send().await.unwrap().hosts[0].state,
+        "Idle"
+    );
+}
+
+#[tokio::test]
+async fn test_inventory_sections_and_compression() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+
+    let response = daemon
+        .client
+        .get_host_inventory("web-1")
+        .include("system")
+        .include("hardware")
+        .send()
+        .await
+        .unwrap();
+    let inventory = response.inventory.as_object().unwrap();
+    assert!(inventory.contains_key("system"));
+    assert!(inventory.contains_key("hardware"));
+    assert!(!inventory.contains_key("packages"));
+    assert_eq!(response.pending_updates, 2);
+
+    let unknown = daemon
+        .client
+        .get_host_inventory("web-1")
+        .include("kernel")
+        .send()
+        .await;
+    assert_eq!(status(unknown), 422);
+
+    // Responses are compressed when the client accepts it; reqwest would
+    // decompress them itself and hide the encoding
+    let raw = reqwest::Client::builder()
+        .no_gzip()
+        .no_brotli()
+        .build()
+        .unwrap();
+    for encoding in ["gzip", "br"] {
+        let response = raw
+            .get(daemon.url("/openapi.json"))
+            .header("accept-encoding", encoding)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-encoding"], encoding);
+    }
+    let plain = raw
+        .get(daemon.url("/openapi.json"))
+        .header("accept-encoding", "identity")
+        .send()
+        .await
+        .unwrap();
+    assert!(!plain.headers().contains_key("content-encoding"));
+}
+
+/// Fetch `path` bypassing the client, and check the client's type for it
+/// serializes back to exactly the JSON the daemon sent
+async fn round_trip<T: Serialize + DeserializeOwned>(daemon: &TestDaemon, path: &str) -> T {
+    let raw: serde_json::Value = reqwest::get(daemon.url(path))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let typed: T = serde_json::from_value(raw.clone()).unwrap();
+    assert_eq!(serde_json::to_value(&typed).unwrap(), raw, "{path}");
+    typed
+}
+
+#[tokio::test]
+async fn test_client_types_match_daemon_responses() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+
+    let inventory: HostInventoryResponse = round_trip(&daemon, "/hosts/web-1/inventory").await;
+    assert_eq!(inventory.name, "web-1");
+    assert_eq!(inventory.upgradable_packages.len(), 2);
+    daemon.wait_for_state("web-1", "PendingUpdates").await;
+
+    let request = UpdateRequest {
+        dry_run: false,
+        scope: None,
+        stack: None,
+        packages: Vec::new(),
+        force: false,
+        skip_cleanup: false,
+    };
+    let result = daemon.client.run_update("web-1", &request).await.unwrap();
+    assert!(result.success);
+    assert_eq!(result.upgraded_count, 2);
+    // The host is done with the update by the time the daemon answers
+    let host = daemon.client.get_host("web-1").await.unwrap();
+    assert!(host.last_updated.is_some());
+
+    let page: HostListResponse = round_trip(&daemon, "/hosts").await;
+    assert_eq!(page.hosts[0].name, "web-1");
+    let detail: HostDetail = round_trip(&daemon, "/hosts/web-1").await;
+    assert_eq!(detail.last_updated, host.last_updated);
+    let connection = detail.connection.unwrap();
+    assert!(connection.commands > 0);
+    assert_eq!(connection.failures, 0);
+    let transitions: Vec<StateTransitionInfo> =
+        round_trip(&daemon, "/hosts/web-1/transitions").await;
+    assert!(!transitions.is_empty());
+    round_trip::<Vec<CommandHistoryEntry>>(&daemon, "/hosts/web-1/commands").await;
+}
+
+#[tokio::test]
+async fn test_replies_carry_the_api_version() {
+    let daemon = TestDaemon::start().await;
+    let health = daemon.client.health().await.unwrap();
+    assert_eq!(health.api_version, API_VERSION);
+    assert_eq!(health.version, env!("CARGO_PKG_VERSION"));
+
+    // Errors and unknown routes say it too, so clients never misread them
+    for (path, code) in [("/health", 200), ("/hosts/nope", 404), ("/nope", 404)] {
+        let response = reqwest::get(daemon.url(path)).await.unwrap();
+        assert_eq!(response.status(), code, "{path}");
+        assert_eq!(
+            response.headers()[API_VERSION_HEADER],
+            API_VERSION.to_string(),
+            "{path}"
+        );
+        assert_eq!(
+            response.headers()[DAEMON_VERSION_HEADER],
+            env!("CARGO_PKG_VERSION"),
+            "{path}"
+        );
+    }
+
+    // The client's checked event stream accepts the upgrade reply
+    let mut events = daemon.events().await;
+    daemon.register("web-1").await;
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv()).await;
+    assert!(matches!(event, Ok(Some(_))), "{event:?}");
+}
+
+#[tokio::test]
+async fn test_batched_event_stream_sends_one_frame_per_interval() {
+    use futures::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+    // Let the new host's own events go by before subscribing
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    let url = daemon
+        .url("/ws/events?batch_ms=400")
+        .replacen("http", "ws", 1);
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+
+    // An interval without events sends nothing
+    let idle = tokio::time::timeout(Duration::from_millis(500), socket.next()).await;
+    assert!(idle.is_err(), "{idle:?}");
+
+    daemon.client.pause_host("web-1").await.unwrap();
+    daemon.client.resume_host("web-1").await.unwrap();
+    let frame = tokio::time::timeout(Duration::from_secs(2), socket.next())
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap();
+    let Message::Text(text) = frame else {
+        panic!("expected a text frame, got {frame:?}");
+    };
+    let envelopes: Vec<EventEnvelope> = serde_json::from_str(&text).unwrap();
+    let paused: Vec<_> = envelopes
+        .iter()
+        .filter_map(|envelope| match &envelope.event {
+            WsEvent::HostPauseChanged { paused, .. } => Some(*paused),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(paused, [true, false], "{text}");
+}
+
+#[tokio::test]
+async fn test_host_list_pagination() {
+    let daemon = TestDaemon::start().await;
+    for i in 1..=5 {
+        daemon.register(&format!("web-{i}")).await;
+    }
+
+    let page = daemon
+        .client
+        .list_hosts()
+        .per_page(2)
+        .page(2)
+        .sort("name")
+        .send()
+        .await
+        .unwrap();
+    let names: Vec<_> = page.hosts.iter().map(|h| h.name.as_str()).collect();
+    assert_eq!(names, ["web-3", "web-4"]);
+    assert_eq!(page.pagination.page, 2);
+    assert_eq!(page.pagination.total_items, 5);
+    assert_eq!(page.pagination.total_pages, 3);
+
+    let last = daemon
+        .client
+        .list_hosts()
+        .per_page(2)
+        .page(3)
+        .sort("name")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(last.hosts.len(), 1);
+
+    assert_eq!(
+        status(daemon.client.list_hosts().per_page(0).send().await),
+        422
+    );
+    assert_eq!(
+        status(daemon.client.list_hosts().per_page(201).send().await),
+        422
+    );
+    assert_eq!(
+        status(daemon.client.list_hosts().per_page(10_000_000).send().await),
+        422
+    );
+    assert!(
+        daemon
+            .client
+            .list_hosts()
+            .per_page(200)
+            .send()
+            .await
+            .is_ok()
+    );
+}
+
+/// A daemon with `web-1`, `web-2` and `db-1`; `web-1` has pending updates
+///
+/// `web-1` and `db-1` are tagged `prod`, and the `frontend` group holds
+/// the web hosts.
+async fn listed_fleet() -> TestDaemon {
+    let mut config = Config::default();
+    config.groups.insert(
+        "frontend".to_string(),
+        vec!["web-1".to_string(), "web-2".to_string()],
+    );
+    let daemon = TestDaemon::with_config(config, Arc::new(TestHostFactory)).await;
+    for (name, prod) in [("web-1", true), ("web-2", false), ("db-1", true)] {
+        let mut request = host_request(name);
+        if prod {
+            request.tags.push("prod".to_string());
+        }
+        daemon.client.register_host(&request).await.unwrap();
+    }
+    daemon
+        .client
+        .get_host_inventory("web-1")
+        .send()
+        .await
+        .unwrap();
+    daemon.wait_for_state("web-1", "PendingUpdates").await;
+    daemon
+}
+
+/// Names on the host list page for `query`
+async fn listed(daemon: &TestDaemon, query: &str) -> Vec<String> {
+    let page: HostListResponse = reqwest::get(daemon.url(&format!("/hosts?{query}")))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    page.hosts.into_iter().map(|host| host.name).collect()
+}
+
+#[tokio::test]
+async fn test_host_list_filters() {
+    let daemon = listed_fleet().await;
+
+    assert_eq!(listed(&daemon, "").await, ["db-1", "web-1", "web-2"]);
+    assert_eq!(listed(&daemon, "tags=prod").await, ["db-1", "web-1"]);
+    assert_eq!(listed(&daemon, "tags=prod,test").await, ["db-1", "web-1"]);
+    assert_eq!(listed(&daemon, "state=pending_updates").await, ["web-1"]);
+    assert_eq!(listed(&daemon, "state=idle").await, ["db-1", "web-2"]);
+    assert_eq!(listed(&daemon, "group=frontend").await, ["web-1", "web-2"]);
+    assert_eq!(listed(&daemon, "search=web").await, ["web-1", "web-2"]);
+    assert_eq!(listed(&daemon, "search=db").await, ["db-1"]);
+    // Filters combine
+    assert_eq!(listed(&daemon, "group=frontend&tags=prod").await, ["web-1"]);
+    assert!(
+        listed(&daemon, "search=web&state=idle&tags=prod")
+            .await
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_host_list_sorting() {
+    let daemon = listed_fleet().await;
+
+    assert_eq!(
+        listed(&daemon, "order=desc").await,
+        ["web-2", "web-1", "db-1"]
+    );
+    // Ties are broken by name, in the same direction
+    assert_eq!(
+        listed(&daemon, "sort=pending_updates&order=desc").await,
+        ["web-1", "web-2", "db-1"]
+    );
+    assert_eq!(
+        listed(&daemon, "sort=pending_updates").await,
+        ["db-1", "web-2", "web-1"]
+    );
+    assert_eq!(
+        listed(&daemon, "sort=state").await,
+        ["db-1", "web-2", "web-1"]
+    );
+    assert_eq!(
+        listed(&daemon, "sort=state&order=desc").await,
+        ["web-1", "web-2", "db-1"]
+    );
+}
+
+#[tokio::test]
+async fn test_host_list_rejects_invalid_pages() {
+    let daemon = listed_fleet().await;
+
+    for query in ["page=0", "per_page=0", "per_page=201", "group=backend"] {
+        let response = reqwest::get(daemon.url(&format!("/hosts?{query}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 422, "{query}");
+        let error: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(error["code"], "VALIDATION_FAILED", "{query}");
+    }
+    // Both page parameters are reported at once
+    let error: serde_json::Value = reqwest::get(daemon.url("/hosts?page=0&per_page=0"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(error["details"].as_array().unwrap().len(), 2, "{error}");
+}
+
+#[tokio::test]
+async fn test_host_list_page_past_the_end_is_empty() {
+    let daemon = listed_fleet().await;
+
+    assert_eq!(listed(&daemon, "per_page=2&page=2").await, ["web-2"]);
+    let page: HostListResponse = reqwest::get(daemon.url("/hosts?per_page=2&page=5"))
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(page.hosts.is_empty());
+    assert_eq!(page.pagination.page, 5);
+    assert_eq!(page.pagination.total_items, 3);
+    assert_eq!(page.pagination.total_pages, 2);
+}
+
+#[tokio::test]
+async fn test_host_list_etag_skips_unchanged_pages() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+
+    let raw = reqwest::Client::new();
+    let first = raw.get(daemon.url("/hosts")).send().await.unwrap();
+    assert_eq!(first.status(), 200);
+    let etag = first.headers()["etag"].to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/\""), "{etag}");
+
+    let unchanged = raw
+        .get(daemon.url("/hosts"))
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unchanged.status(), 304);
+    assert_eq!(unchanged.headers()["etag"], etag.as_str());
+    assert!(unchanged.bytes().await.unwrap().is_empty());
+    // Another page of the same hosts has its own ETag
+    let other = raw
+        .get(daemon.url("/hosts?per_page=1"))
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(other.status(), 200);
+
+    let mut poll = daemon.client.list_hosts();
+    assert_eq!(poll.poll().await.unwrap().unwrap().hosts.len(), 1);
+    assert!(poll.poll().await.unwrap().is_none());
+
+    // A state change shows on the next poll
+    daemon
+        .client
+        .get_host_inventory("web-1")
+        .send()
+        .await
+        .unwrap();
+    daemon.wait_for_state("web-1", "PendingUpdates").await;
+    let page = poll.poll().await.unwrap().expect("changed list");
+    assert_eq!(page.hosts[0].state, "PendingUpdates");
+    assert!(poll.poll().await.unwrap().is_none());
+
+    let changed = raw
+        .get(daemon.url("/hosts"))
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(changed.status(), 200);
+    assert_ne!(changed.headers()["etag"], etag.as_str());
+}
+
+#[tokio::test]
+async fn test_events_reach_websocket_subscribers() {
+    let daemon = TestDaemon::start().await;
+    let mut events = daemon.events().await;
+
+    daemon.register("web-1").await;
+    daemon
+        .client
+        .get_host_inventory("web-1")
+        .send()
+        .await
+        .unwrap();
+
+    let changed = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(event) = events.recv().await {
+            if let WsEvent::HostStateChanged { host, to, .. } = event
+                && host == "web-1"
+                && to == "pending_updates"
+            {
+                return true;
+            }
+        }
+        false
+    })
+    .await;
+    assert_eq!(changed, Ok(true));
+}
+
+#[tokio::test]
+async fn test_hosts_filter_by_fact() {
+    let daemon = TestDaemon::start().await;
+    daemon.register("web-1").await;
+
+    // The test hosts have no facts to match
+    let list = daemon
+        .client
+        .list_hosts()
+        .fact("os_id:debian")
+        .fact("arch:x86_64")
+        .send()
+        .await
+        .unwrap();
+    assert!(list.hosts.is_empty());
+    assert_eq!(list.filters.facts, ["os_id:debian", "arch:x86_64"]);
+    assert!(
+        daemon
+            .client
+            .get_host("web-1")
+            .await
+            .unwrap()
+            .facts
+            .is_empty()
+    );
+
+    for fact in ["distro:debian", "os_id"] {
+        assert_eq!(
+            status(daemon.client.list_hosts().fact(fact).send().await),
+            422
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_config_validation_lists_every_problem() {
+    let daemon = TestDaemon::start().await;
+
+    let report = daemon
+        .client
+        .validate_config(Some(
+            "[[host]]\nname = \"web-1\"\naddr = \"\"\n\n[[host]]\nname = \"web-1\"\naddr = \"10.0.0.2\"\n",
+        ))
+        .await
+        .unwrap();
+    assert!(!report.valid);
+    let issues: Vec<_> = report.issues.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        issues,
+        [
+            "line 3, column 1: host[0].addr: must not be empty",
+            "line 6, column 1: host[1].name: duplicate host name 'web-1', also used by host[0]",
+        ]
+    );
+
+    let report = daemon
+        .client
+        .validate_config(Some("[daemon]\nbind = \"127.0.0.1:8080\"\n"))
+        .await
+        .unwrap();
+    assert!(report.valid && report.issues.is_empty());
+
+    // Nothing is applied, and without a body there is no file to check
+    assert!(
+        daemon
+            .client
+            .list_hosts()
+            .send()
+            .await
+            .unwrap()
+            .hosts
+            .is_empty()
+    );
+    let err = daemon.client.validate_config(None).await.unwrap_err();
+    assert!(
+        matches!(err, tendhost_client::ClientError::Api { status: 400, .. }),
+        "{err:?}"
+    );
+}